@@ -15,16 +15,14 @@ use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
-use baker::{
-    EventWithTime, LiquidityBakingToggleVote, Protocol, ProtocolBlockHeaderI, ProtocolBlockHeaderJ,
-};
+use baker::{EventWithTime, LiquidityBakingToggleVote, Protocol};
 
 #[derive(StructOpt, Debug)]
 pub struct Arguments {
     #[structopt(long)]
     base_dir: PathBuf,
-    #[structopt(long)]
-    baker: String,
+    #[structopt(long, required = true, use_delimiter = true)]
+    baker: Vec<String>,
     #[structopt(long)]
     endpoint: Url,
     #[structopt(short, long)]
@@ -33,6 +31,8 @@ pub struct Arguments {
     protocol: Protocol,
     #[structopt(long, default_value = "off")]
     liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
+    #[structopt(long)]
+    password_filename: Option<PathBuf>,
     // #[structopt(long)]
     // node_dir: Option<PathBuf>,
 }
@@ -48,6 +48,7 @@ fn main() {
         archive,
         protocol,
         liquidity_baking_toggle_vote,
+        password_filename,
     } = Arguments::from_args();
 
     let env = env_logger::Env::default().default_filter_or("info");
@@ -60,7 +61,9 @@ fn main() {
     signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&terminating))
         .expect("cannot handle signals");
 
-    let (srv, events) = Services::new(endpoint, &base_dir, &baker);
+    let (mut srv, events) = Services::new(endpoint, &base_dir, &baker, password_filename);
+    // a single alias keeps the file names of the previous versions
+    let baker = baker.join("+");
     let chain_id = loop {
         match srv.client.get_chain_id() {
             Ok(v) => break v,
@@ -68,6 +71,12 @@ fn main() {
         }
     };
     slog::info!(srv.log, "chain_id: {chain_id}");
+    for (pkh, crypto) in &mut srv.crypto {
+        let last_signed = crypto
+            .last_signed(&chain_id)
+            .expect("cannot read the watermark");
+        slog::info!(srv.log, "delegate: {pkh}, watermark: {last_signed:?}");
+    }
     loop {
         match srv.client.wait_bootstrapped() {
             Ok(_) => break,
@@ -76,21 +85,12 @@ fn main() {
     }
     slog::info!(srv.log, "bootstrapped");
     let constants = loop {
-        match srv.client.get_constants() {
+        match srv.client.get_constants(protocol) {
             Ok(v) => break v,
             Err(_) => std::thread::sleep(std::time::Duration::from_millis(200)),
         }
     };
-    match protocol {
-        Protocol::Ithaca => srv
-            .client
-            .monitor_heads::<ProtocolBlockHeaderI>(&chain_id)
-            .unwrap(),
-        Protocol::Jakarta => srv
-            .client
-            .monitor_heads::<ProtocolBlockHeaderJ>(&chain_id)
-            .unwrap(),
-    }
+    srv.client.monitor_heads(&chain_id, protocol).unwrap();
     let log = srv.log.clone();
 
     // store the state here, and then atomically swap to avoid corruption
@@ -106,13 +106,15 @@ fn main() {
     let persistent_state = File::open(&file_path)
         .and_then(|rdr| serde_json::from_reader::<_, BakerState>(rdr).map_err(From::from));
 
-    let initial_state = if let Ok(persistent_state) = persistent_state {
+    let ours = srv.crypto.keys().cloned().collect();
+    let initial_state = if let Ok(mut persistent_state) = persistent_state {
+        persistent_state.as_mut().tb_config.map.ours = ours;
         persistent_state
     } else {
         BakerState::new(
             chain_id,
             constants,
-            srv.crypto.public_key_hash().clone(),
+            ours,
             protocol,
             liquidity_baking_toggle_vote,
         )
@@ -12,9 +12,8 @@ pub mod machine;
 
 mod services;
 pub use self::services::{
-    client::{
-        LiquidityBakingToggleVote, Protocol, ProtocolBlockHeaderI, ProtocolBlockHeaderJ, RpcClient,
-    },
+    client::{LiquidityBakingToggleVote, RpcClient},
+    protocol::{Protocol, ProtocolAdapter, ProtocolBlockHeaderI, ProtocolBlockHeaderJ},
     EventWithTime, Services,
 };
 
@@ -6,8 +6,9 @@ use std::collections::BTreeMap;
 use derive_more::From;
 use serde::{Deserialize, Serialize};
 
-use crypto::hash::{BlockHash, ContractTz1Hash, NonceHash};
+use crypto::hash::{BlockHash, NonceHash};
 use redux_rs::EnablingCondition;
+use tezos_messages::base::signature_public_key::SignaturePublicKeyHash;
 use tezos_messages::protocol::proto_012::operation::{InlinedEndorsement, InlinedPreendorsement};
 
 use crate::services::event::{Block, OperationSimple, Slots};
@@ -61,7 +62,7 @@ where
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SlotsEventAction {
     pub level: i32,
-    pub delegates: BTreeMap<ContractTz1Hash, Slots>,
+    pub delegates: BTreeMap<SignaturePublicKeyHash, Slots>,
 }
 
 impl<S> EnablingCondition<S> for SlotsEventAction
@@ -316,6 +317,7 @@ where
 #[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PreVoteAction {
+    pub delegate: SignaturePublicKeyHash,
     pub op: InlinedPreendorsement,
 }
 
@@ -331,6 +333,7 @@ where
 #[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct VoteAction {
+    pub delegate: SignaturePublicKeyHash,
     pub op: InlinedEndorsement,
 }
 
@@ -346,6 +349,7 @@ where
 // #[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ProposeAction {
+    pub delegate: SignaturePublicKeyHash,
     pub payload_round: i32,
     pub seed_nonce_hash: Option<NonceHash>,
     pub predecessor_hash: BlockHash,
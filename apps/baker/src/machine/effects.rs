@@ -1,7 +1,7 @@
 // Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use std::{convert::TryFrom, mem, time::Duration};
+use std::{convert::TryFrom, time::Duration};
 
 use redux_rs::{ActionWithMeta, Store, TimeService};
 
@@ -10,6 +10,7 @@ use serde::Deserialize;
 use tenderbake as tb;
 use tezos_encoding::{enc::BinWriter, types::SizedBytes};
 use tezos_messages::{
+    base::signature_public_key::SignaturePublicKeyHash,
     p2p::{
         binary_message::MessageHash,
         encoding::operation::{DecodedOperation, Operation},
@@ -26,10 +27,7 @@ use crate::{
     machine::state::Initialized,
     proof_of_work::guess_proof_of_work,
     services::{
-        client::{
-            LiquidityBakingToggleVote, Protocol, ProtocolBlockHeaderI, ProtocolBlockHeaderJ,
-            RpcErrorInner,
-        },
+        client::{LiquidityBakingToggleVote, RpcErrorInner},
         event::OperationSimple,
         BakerService,
     },
@@ -177,20 +175,25 @@ where
                 Err(err) => slog::error!(store.service.log(), " .  {err}"),
             }
         }
-        Some(BakerAction::PreVote(PreVoteAction { op })) => {
+        Some(BakerAction::PreVote(PreVoteAction { delegate, op })) => {
             let InlinedPreendorsementContents::Preendorsement(c) = &op.operations;
-            let (data, _) =
-                match store
-                    .service
-                    .crypto()
-                    .sign(0x12, &st.chain_id, op, c.level, c.round, false)
-                {
-                    Ok(v) => v,
-                    Err(err) => {
-                        slog::error!(store.service.log(), " .  {err}");
-                        return;
-                    }
-                };
+            let crypto = match store.service.crypto(delegate) {
+                Some(v) => v,
+                None => {
+                    slog::error!(
+                        store.service.log(),
+                        " .  no key for the delegate {delegate}"
+                    );
+                    return;
+                }
+            };
+            let (data, _) = match crypto.sign(0x12, &st.chain_id, op, c.level, c.round, false) {
+                Ok(v) => v,
+                Err(err) => {
+                    slog::error!(store.service.log(), " .  {err}");
+                    return;
+                }
+            };
             match store
                 .service
                 .client()
@@ -200,20 +203,25 @@ where
                 Err(err) => slog::error!(store.service.log(), " .  {err}"),
             }
         }
-        Some(BakerAction::Vote(VoteAction { op })) => {
+        Some(BakerAction::Vote(VoteAction { delegate, op })) => {
             let InlinedEndorsementMempoolContents::Endorsement(c) = &op.operations;
-            let (data, _) =
-                match store
-                    .service
-                    .crypto()
-                    .sign(0x13, &st.chain_id, op, c.level, c.round, false)
-                {
-                    Ok(v) => v,
-                    Err(err) => {
-                        slog::error!(store.service.log(), " .  {err}");
-                        return;
-                    }
-                };
+            let crypto = match store.service.crypto(delegate) {
+                Some(v) => v,
+                None => {
+                    slog::error!(
+                        store.service.log(),
+                        " .  no key for the delegate {delegate}"
+                    );
+                    return;
+                }
+            };
+            let (data, _) = match crypto.sign(0x13, &st.chain_id, op, c.level, c.round, false) {
+                Ok(v) => v,
+                Err(err) => {
+                    slog::error!(store.service.log(), " .  {err}");
+                    return;
+                }
+            };
             match store
                 .service
                 .client()
@@ -224,6 +232,7 @@ where
             }
         }
         Some(BakerAction::Propose(ProposeAction {
+            delegate,
             payload_round,
             seed_nonce_hash,
             predecessor_hash,
@@ -234,6 +243,7 @@ where
         })) => inject_block(
             &mut store.service,
             st,
+            delegate,
             *payload_round,
             seed_nonce_hash,
             predecessor_hash,
@@ -251,6 +261,7 @@ where
 fn inject_block<Srv>(
     srv: &mut Srv,
     st: &Initialized,
+    delegate: &SignaturePublicKeyHash,
     payload_round: i32,
     seed_nonce_hash: &Option<NonceHash>,
     predecessor_hash: &BlockHash,
@@ -281,26 +292,16 @@ fn inject_block<Srv>(
         .sum::<usize>();
 
     slog::info!(srv.log(), "begin preapply");
-    let r = match st.protocol {
-        Protocol::Ithaca => srv.client().preapply_block::<ProtocolBlockHeaderI>(
-            payload_hash,
-            payload_round,
-            seed_nonce_hash,
-            predecessor_hash.clone(),
-            *timestamp,
-            operations.clone(),
-            liquidity_baking_toggle_vote,
-        ),
-        Protocol::Jakarta => srv.client().preapply_block::<ProtocolBlockHeaderJ>(
-            payload_hash,
-            payload_round,
-            seed_nonce_hash,
-            predecessor_hash.clone(),
-            *timestamp,
-            operations.clone(),
-            liquidity_baking_toggle_vote,
-        ),
-    };
+    let r = srv.client().preapply_block(
+        st.protocol,
+        payload_hash,
+        payload_round,
+        seed_nonce_hash,
+        predecessor_hash.clone(),
+        *timestamp,
+        operations.clone(),
+        liquidity_baking_toggle_vote,
+    );
     slog::info!(srv.log(), "end preapply");
     let (mut header, ops) = match r {
         Ok(v) => v,
@@ -351,16 +352,18 @@ fn inject_block<Srv>(
         );
     }
 
-    // nonce_offset is the offset of `proof_of_work_nonce` in protocol header (Ithaca and Jakarta)
-    // 32 is the size of `BlockPayloadHash`
-    let nonce_offset = 32 + mem::size_of::<i32>();
+    let nonce_offset = st.protocol.pow_offset();
     let p = guess_proof_of_work(&header, nonce_offset, st.proof_of_work_threshold);
     header.set_proof_of_work_nonce(SizedBytes(p));
     slog::info!(srv.log(), "{:?}", header);
-    let (data, _) = match srv
-        .crypto()
-        .sign(0x11, &st.chain_id, &header, level, round, force)
-    {
+    let crypto = match srv.crypto(delegate) {
+        Some(v) => v,
+        None => {
+            slog::error!(srv.log(), " .  no key for the delegate {delegate}");
+            return;
+        }
+    };
+    let (data, _) = match crypto.sign(0x11, &st.chain_id, &header, level, round, force) {
         Ok(v) => v,
         Err(err) => {
             slog::error!(srv.log(), " .  {err}");
@@ -394,6 +397,7 @@ fn inject_block<Srv>(
                     inject_block(
                         srv,
                         st,
+                        delegate,
                         header.payload_round(),
                         seed_nonce_hash,
                         predecessor_hash,
@@ -472,7 +476,7 @@ fn extract_invalid_ops_test() {
 //     };
 
 //     let instant = std::time::Instant::now();
-//     let (header, ops) = client.preapply_block::<ProtocolBlockHeaderI>(payload_hash, payload_round, &seed_nonce_hash, predecessor_hash, timestamp, operations, LiquidityBakingToggleVote::Off).unwrap();
+//     let (header, ops) = client.preapply_block("ithaca".parse().unwrap(), payload_hash, payload_round, &seed_nonce_hash, predecessor_hash, timestamp, operations, LiquidityBakingToggleVote::Off).unwrap();
 //     println!("elapsed {:?}", instant.elapsed());
 //     println!("{header:?}");
 //     println!("{}", serde_json::to_string(&ops).unwrap());
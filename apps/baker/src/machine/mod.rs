@@ -243,7 +243,12 @@ mod tests {
     }
 
     mod prequorum {
+        use std::collections::BTreeMap;
+
+        use tezos_messages::protocol::proto_012::operation::InlinedEndorsementMempoolContents;
+
         use super::*;
+        use crate::machine::VoteAction;
 
         #[test]
         fn simplest() {
@@ -293,6 +298,58 @@ mod tests {
             }
         }
 
+        #[test]
+        fn several_delegates() {
+            test_initialized(|mut state, level, validators| {
+                // manage every delegate having rights at this level
+                let delegates = state.as_ref().tb_config.map.delegates[&level].clone();
+                state.as_mut().tb_config.map.ours = delegates.keys().cloned().collect();
+
+                let st = state.as_ref();
+                let predecessor_hash = st.tb_state.predecessor_hash().unwrap();
+                let payload_hash = st.tb_state.payload_hash().unwrap();
+                let round = st.tb_state.round().unwrap();
+                let preendorsements = BakerAction::OperationsEvent(OperationsEventAction {
+                    operations: validators
+                        .iter()
+                        .map(|&(slot, _)| {
+                            OperationSimple::preendorsement(
+                                &predecessor_hash,
+                                &payload_hash,
+                                level,
+                                round,
+                                slot,
+                            )
+                        })
+                        .collect(),
+                });
+                let now = st.tb_state.timestamp().unwrap();
+                let state = state.handle_event(EventWithTime {
+                    action: preendorsements,
+                    now,
+                });
+
+                // each delegate endorses with its own first slot
+                let votes = state
+                    .as_ref()
+                    .actions
+                    .iter()
+                    .filter_map(|a| match a {
+                        BakerAction::Vote(VoteAction { delegate, op }) => {
+                            let InlinedEndorsementMempoolContents::Endorsement(c) = &op.operations;
+                            Some((delegate.clone(), c.slot))
+                        }
+                        _ => None,
+                    })
+                    .collect::<BTreeMap<_, _>>();
+                let expected = delegates
+                    .into_iter()
+                    .map(|(delegate, slots)| (delegate, slots.0[0]))
+                    .collect::<BTreeMap<_, _>>();
+                assert_eq!(votes, expected);
+            })
+        }
+
         #[test]
         fn outdated() {
             test_initialized(outdated_inner)
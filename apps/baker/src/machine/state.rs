@@ -9,8 +9,9 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 
-use crypto::hash::{BlockHash, BlockPayloadHash, ChainId, ContractTz1Hash, Signature};
+use crypto::hash::{BlockHash, BlockPayloadHash, ChainId, Signature};
 use tenderbake as tb;
+use tezos_messages::base::signature_public_key::SignaturePublicKeyHash;
 use tezos_messages::protocol::proto_012::operation::{
     EndorsementOperation, InlinedEndorsement, InlinedEndorsementMempoolContents,
     InlinedEndorsementMempoolContentsEndorsementVariant, InlinedPreendorsement,
@@ -18,8 +19,9 @@ use tezos_messages::protocol::proto_012::operation::{
 };
 
 use crate::services::{
-    client::{Constants, LiquidityBakingToggleVote, Protocol},
+    client::{Constants, LiquidityBakingToggleVote},
     event::{Block, OperationKind, OperationSimple, Slots},
+    protocol::Protocol,
     EventWithTime,
 };
 
@@ -32,18 +34,18 @@ use super::{
 #[derive(Clone, Serialize, Deserialize)]
 pub struct SlotsInfo {
     pub committee_size: u32,
-    pub ours: Vec<ContractTz1Hash>,
+    pub ours: Vec<SignaturePublicKeyHash>,
     pub level: i32,
-    pub delegates: BTreeMap<i32, BTreeMap<ContractTz1Hash, Slots>>,
+    pub delegates: BTreeMap<i32, BTreeMap<SignaturePublicKeyHash, Slots>>,
 }
 
 #[derive(Serialize, Deserialize)]
 #[allow(clippy::enum_variant_names)]
 pub enum Gathering {
-    GetCornerSlots(Request<i32, BTreeMap<ContractTz1Hash, Slots>, String>),
+    GetCornerSlots(Request<i32, BTreeMap<SignaturePublicKeyHash, Slots>, String>),
     // for some `level: i32` we request a collection of public key hash
     // and corresponding slots
-    GetSlots(Request<i32, BTreeMap<ContractTz1Hash, Slots>, String>),
+    GetSlots(Request<i32, BTreeMap<SignaturePublicKeyHash, Slots>, String>),
     // for some `BlockHash` we request its operations
     GetOperations(Request<BlockHash, Vec<Vec<OperationSimple>>, String>),
     // for some `BlockHash` we request a list of live blocks
@@ -104,7 +106,6 @@ pub struct Initialized {
     pub liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
     pub chain_id: ChainId,
     pub proof_of_work_threshold: u64,
-    pub this: ContractTz1Hash,
     // cycle state
     pub nonces: CycleNonce,
     // live blocks
@@ -117,7 +118,7 @@ pub struct Initialized {
     pub new_operations: Vec<OperationSimple>,
     // tenderbake machine
     pub tb_config: tb::Config<tb::TimingLinearGrow, SlotsInfo>,
-    pub tb_state: tb::Machine<ContractTz1Hash, OperationSimple>,
+    pub tb_state: tb::Machine<SignaturePublicKeyHash, OperationSimple>,
 
     pub actions: Vec<BakerAction>,
 }
@@ -162,7 +163,7 @@ impl BakerState {
     pub fn new(
         chain_id: ChainId,
         constants: Constants,
-        this: ContractTz1Hash,
+        ours: Vec<SignaturePublicKeyHash>,
         protocol: Protocol,
         liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
     ) -> Self {
@@ -171,7 +172,6 @@ impl BakerState {
             delay_increment_per_round: constants.delay_increment_per_round,
         };
 
-        let ours = vec![this.clone()];
         let tb_config = tb::Config {
             timing,
             map: SlotsInfo {
@@ -188,7 +188,6 @@ impl BakerState {
             liquidity_baking_toggle_vote,
             chain_id,
             proof_of_work_threshold: constants.proof_of_work_threshold,
-            this,
             nonces: CycleNonce {
                 blocks_per_commitment: constants.blocks_per_commitment,
                 blocks_per_cycle: constants.blocks_per_cycle,
@@ -346,7 +345,7 @@ impl BakerState {
                     BakerState::HaveBlock { mut state, current_block } => {
                         state.actions.push(BakerAction::MonitorOperations(MonitorOperationsAction {}));
                         let operations = mem::take(&mut state.operations);
-                        let proposal = Box::new(proposal(&current_block, operations, &state.tb_config));
+                        let proposal = Box::new(proposal(&current_block, operations, state.protocol, &state.tb_config));
                         let (tb_actions, records) = state.tb_state.handle(&state.tb_config, tb::Event::Proposal(proposal, now));
                         state.actions.extend(records.into_iter().map(|record| {
                             BakerAction::LogTenderbake(LogTenderbakeAction { record })
@@ -467,7 +466,7 @@ impl BakerState {
             BakerAction::OperationsEvent(OperationsEventAction { operations }) => {
                 let state = self.as_mut();
                 for op in operations {
-                    match op.kind() {
+                    match state.protocol.operation_kind(&op) {
                         None => {
                             let description = format!("unclassified operation {op:?}");
                             state.actions.push(BakerAction::LogError(LogErrorAction { description }));
@@ -553,7 +552,10 @@ impl BakerState {
 }
 
 impl Initialized {
-    fn handle_tb_actions(&mut self, tb_actions: Vec<tb::Action<ContractTz1Hash, OperationSimple>>) {
+    fn handle_tb_actions(
+        &mut self,
+        tb_actions: Vec<tb::Action<SignaturePublicKeyHash, OperationSimple>>,
+    ) {
         for tb_action in tb_actions {
             match tb_action {
                 tb::Action::ScheduleTimeout(deadline) => {
@@ -562,8 +564,8 @@ impl Initialized {
                             deadline,
                         }));
                 }
-                tb::Action::Propose(block, _, _) => {
-                    self.propose(*block);
+                tb::Action::Propose(block, proposer, _) => {
+                    self.propose(*block, proposer);
                 }
                 tb::Action::Preendorse {
                     pred_hash,
@@ -582,62 +584,52 @@ impl Initialized {
     }
 
     fn pre_vote(&mut self, pred_hash: BlockHash, block_id: tb::BlockId) {
-        let slot = self
-            .tb_config
-            .map
-            .delegates
-            .get(&block_id.level)
-            .and_then(|v| v.get(&self.this))
-            .and_then(|v| v.0.first());
-        let slot = match slot {
-            Some(s) => *s,
-            None => return,
-        };
-        let preendorsement = InlinedPreendorsement {
-            branch: BlockHash(pred_hash.0.to_vec()),
-            operations: InlinedPreendorsementContents::Preendorsement(
-                InlinedPreendorsementVariant {
-                    slot,
-                    level: block_id.level,
-                    round: block_id.round,
-                    block_payload_hash: BlockPayloadHash(block_id.payload_hash.0.to_vec()),
-                },
-            ),
-            signature: Signature(vec![0x55; 64]),
-        };
-        self.actions
-            .push(BakerAction::PreVote(PreVoteAction { op: preendorsement }));
+        for (delegate, slot) in self.tb_config.map.our_slots(block_id.level) {
+            let preendorsement = InlinedPreendorsement {
+                branch: BlockHash(pred_hash.0.to_vec()),
+                operations: InlinedPreendorsementContents::Preendorsement(
+                    InlinedPreendorsementVariant {
+                        slot,
+                        level: block_id.level,
+                        round: block_id.round,
+                        block_payload_hash: BlockPayloadHash(block_id.payload_hash.0.to_vec()),
+                    },
+                ),
+                signature: Signature(vec![0x55; 64]),
+            };
+            self.actions.push(BakerAction::PreVote(PreVoteAction {
+                delegate,
+                op: preendorsement,
+            }));
+        }
     }
 
     fn vote(&mut self, pred_hash: BlockHash, block_id: tb::BlockId) {
-        let slot = self
-            .tb_config
-            .map
-            .delegates
-            .get(&block_id.level)
-            .and_then(|v| v.get(&self.this))
-            .and_then(|v| v.0.first());
-        let slot = match slot {
-            Some(s) => *s,
-            None => return,
-        };
-        let endorsement = InlinedEndorsement {
-            branch: BlockHash(pred_hash.0.to_vec()),
-            operations: InlinedEndorsementMempoolContents::Endorsement(
-                InlinedEndorsementMempoolContentsEndorsementVariant {
-                    slot,
-                    level: block_id.level,
-                    round: block_id.round,
-                    block_payload_hash: BlockPayloadHash(block_id.payload_hash.0.to_vec()),
-                },
-            ),
-            signature: Signature(vec![0x55; 64]),
-        };
-        self.actions
-            .push(BakerAction::Vote(VoteAction { op: endorsement }));
+        for (delegate, slot) in self.tb_config.map.our_slots(block_id.level) {
+            let endorsement = InlinedEndorsement {
+                branch: BlockHash(pred_hash.0.to_vec()),
+                operations: InlinedEndorsementMempoolContents::Endorsement(
+                    InlinedEndorsementMempoolContentsEndorsementVariant {
+                        slot,
+                        level: block_id.level,
+                        round: block_id.round,
+                        block_payload_hash: BlockPayloadHash(block_id.payload_hash.0.to_vec()),
+                    },
+                ),
+                signature: Signature(vec![0x55; 64]),
+            };
+            self.actions.push(BakerAction::Vote(VoteAction {
+                delegate,
+                op: endorsement,
+            }));
+        }
     }
 
-    fn propose(&mut self, block: tb::Block<ContractTz1Hash, OperationSimple>) {
+    fn propose(
+        &mut self,
+        block: tb::Block<SignaturePublicKeyHash, OperationSimple>,
+        delegate: SignaturePublicKeyHash,
+    ) {
         let payload = match block.payload {
             Some(v) => v,
             None => return,
@@ -668,7 +660,7 @@ impl Initialized {
                     continue;
                 }
             }
-            match op.kind() {
+            match self.protocol.operation_kind(&op) {
                 None => {
                     let description = format!("unclassified operation {op:?}");
                     self.actions
@@ -695,6 +687,7 @@ impl Initialized {
         let timestamp = block.time_header.timestamp.unix_epoch.as_secs() as i64;
 
         self.actions.push(BakerAction::Propose(ProposeAction {
+            delegate,
             payload_round,
             seed_nonce_hash,
             predecessor_hash,
@@ -707,7 +700,7 @@ impl Initialized {
 }
 
 impl tb::ProposerMap for SlotsInfo {
-    type Id = ContractTz1Hash;
+    type Id = SignaturePublicKeyHash;
 
     fn proposer(&self, level: i32, round: i32) -> Option<(i32, Self::Id)> {
         let c = self.committee_size as i32;
@@ -736,12 +729,25 @@ impl tb::ProposerMap for SlotsInfo {
 }
 
 impl SlotsInfo {
+    /// Our delegates having rights at the level, with the first slot of each,
+    /// a delegate (pre)endorses once with its first slot.
+    fn our_slots(&self, level: i32) -> Vec<(SignaturePublicKeyHash, u16)> {
+        let delegates = match self.delegates.get(&level) {
+            Some(v) => v,
+            None => return vec![],
+        };
+        self.ours
+            .iter()
+            .filter_map(|our| Some((our.clone(), *delegates.get(our)?.0.first()?)))
+            .collect()
+    }
+
     fn validator(
         &self,
         level: i32,
         slot: u16,
         operation: OperationSimple,
-    ) -> Option<tb::Validator<ContractTz1Hash, OperationSimple>> {
+    ) -> Option<tb::Validator<SignaturePublicKeyHash, OperationSimple>> {
         let i = self.delegates.get(&level)?;
         let (id, s) = i.iter().find(|&(_, v)| v.0.first() == Some(&slot))?;
         Some(tb::Validator {
@@ -763,8 +769,9 @@ fn block_id(content: &EndorsementOperation) -> tb::BlockId {
 fn proposal(
     block: &Block,
     operations: Vec<Vec<OperationSimple>>,
+    protocol: Protocol,
     tb_config: &tb::Config<tb::TimingLinearGrow, SlotsInfo>,
-) -> tb::Block<ContractTz1Hash, OperationSimple> {
+) -> tb::Block<SignaturePublicKeyHash, OperationSimple> {
     tb::Block {
         pred_hash: block.predecessor.clone(),
         level: block.level,
@@ -783,7 +790,7 @@ fn proposal(
                     pre_cer: operations.first().and_then(|ops| {
                         let v = ops
                             .iter()
-                            .filter_map(|op| match op.kind()? {
+                            .filter_map(|op| match protocol.operation_kind(op)? {
                                 OperationKind::Preendorsement(v) => Some((v, op.clone())),
                                 _ => None,
                             })
@@ -805,7 +812,7 @@ fn proposal(
                     cer: operations.first().map(|ops| tb::Certificate {
                         votes: {
                             ops.iter()
-                                .filter_map(|op| match op.kind()? {
+                                .filter_map(|op| match protocol.operation_kind(op)? {
                                     OperationKind::Endorsement(v) => {
                                         tb_config.map.validator(v.level, v.slot, op.clone())
                                     }
@@ -843,7 +850,7 @@ mod tests {
         let cer = operations.first().map(|ops| tb::Certificate {
             votes: {
                 ops.iter()
-                    .filter_map(|op| match op.kind()? {
+                    .filter_map(|op| match protocol.operation_kind(op)? {
                         OperationKind::Endorsement(v) => map.validator(v.level, v.slot, op.clone()),
                         _ => None,
                     })
@@ -4,7 +4,7 @@
 use std::{
     collections::BTreeMap,
     convert::TryInto,
-    io, mem,
+    io,
     num::ParseIntError,
     str::{self, FromStr},
     sync::mpsc,
@@ -22,23 +22,19 @@ use thiserror::Error;
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
 use crypto::hash::{
-    BlockHash, BlockPayloadHash, ChainId, ContextHash, ContractTz1Hash, NonceHash, OperationHash,
-    OperationListListHash, ProtocolHash, Signature,
+    BlockHash, BlockPayloadHash, ChainId, ContextHash, NonceHash, OperationHash,
+    OperationListListHash, ProtocolHash,
 };
-use tezos_encoding::{binary_reader::BinaryReaderError, types::SizedBytes};
+use tezos_encoding::binary_reader::BinaryReaderError;
 use tezos_encoding::{enc::BinWriter, encoding::HasEncoding, nom::NomReader};
 use tezos_messages::{
-    p2p::{
-        binary_message::BinaryRead,
-        encoding::{fitness::Fitness, operation::DecodedOperation},
-    },
-    Timestamp,
+    base::signature_public_key::SignaturePublicKeyHash, p2p::encoding::operation::DecodedOperation,
 };
 
-#[cfg(feature = "fuzzing")]
-use tezos_encoding::fuzzing::sizedbytes::SizedBytesMutator;
-
-use super::event::{Block, OperationSimple, Slots};
+use super::{
+    event::{Block, OperationSimple, Slots},
+    protocol::{Protocol, ProtocolHeaderFull, ShellBlockShortHeader},
+};
 use crate::machine::{BakerAction, OperationsEventAction, ProposalEventAction, RpcErrorAction};
 
 #[derive(Clone)]
@@ -97,223 +93,6 @@ pub enum RpcErrorInner {
     InvalidFitness,
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
-pub enum Protocol {
-    Ithaca,
-    Jakarta,
-}
-
-impl Default for Protocol {
-    fn default() -> Self {
-        Protocol::Ithaca
-    }
-}
-
-impl FromStr for Protocol {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "i" | "ithaca" | "ithacanet" => Ok(Protocol::Ithaca),
-            "j" | "jakarta" | "jakartanet" => Ok(Protocol::Jakarta),
-            _ => Err("unknown protocol".to_string()),
-        }
-    }
-}
-
-#[derive(Deserialize)]
-pub struct ShellBlockShortHeader {
-    level: i32,
-    proto: u8,
-    predecessor: BlockHash,
-    timestamp: String,
-    validation_pass: u8,
-    operations_hash: OperationListListHash,
-    fitness: Vec<String>,
-    context: ContextHash,
-}
-
-pub trait ProtocolHeaderFull
-where
-    Self: BinWriter + std::fmt::Debug,
-{
-    fn payload_round(&self) -> i32;
-
-    fn level(&self) -> i32;
-
-    fn set_payload_hash(&mut self, payload_hash: BlockPayloadHash);
-
-    fn set_proof_of_work_nonce(&mut self, pow: SizedBytes<8>);
-}
-
-pub trait ProtocolHeader {
-    type FullHeader: ProtocolHeaderFull + BinWriter;
-
-    const NAME: &'static str;
-
-    const POW_OFFSET: usize;
-
-    fn new(
-        payload_hash: BlockPayloadHash,
-        payload_round: i32,
-        seed_nonce_hash: Option<NonceHash>,
-        liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
-    ) -> Self;
-
-    fn payload_hash(&self) -> &BlockPayloadHash;
-
-    fn payload_round(&self) -> i32;
-
-    fn proof_of_work_nonce(&self) -> SizedBytes<8>;
-
-    fn full_header(
-        self,
-        shell_header: ShellBlockShortHeader,
-    ) -> Result<Self::FullHeader, RpcErrorInner>;
-}
-
-// signature watermark: 0x11 | chain_id
-#[derive(BinWriter, HasEncoding, NomReader, Clone, Debug, Serialize, Deserialize)]
-#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
-pub struct ProtocolBlockHeaderI {
-    pub payload_hash: BlockPayloadHash,
-    pub payload_round: i32,
-    #[cfg_attr(feature = "fuzzing", field_mutator(SizedBytesMutator<8>))]
-    pub proof_of_work_nonce: SizedBytes<8>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub seed_nonce_hash: Option<NonceHash>,
-    pub liquidity_baking_escape_vote: bool,
-    pub signature: Signature,
-}
-
-#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, HasEncoding, NomReader, BinWriter)]
-pub struct FullHeaderI {
-    #[encoding(builtin = "Int32")]
-    pub level: i32,
-    pub proto: u8,
-    pub predecessor: BlockHash,
-    pub timestamp: Timestamp,
-    pub validation_pass: u8,
-    pub operations_hash: OperationListListHash,
-    pub fitness: Fitness,
-    pub context: ContextHash,
-    pub payload_hash: BlockPayloadHash,
-    pub payload_round: i32,
-    #[cfg_attr(feature = "fuzzing", field_mutator(tezos_encoding::fuzzing::sizedbytes::SizedBytesMutator<8>))]
-    pub proof_of_work_nonce: SizedBytes<8>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub seed_nonce_hash: Option<NonceHash>,
-    pub liquidity_baking_escape_vote: bool,
-    pub signature: Signature,
-}
-
-impl ProtocolHeaderFull for FullHeaderI {
-    fn payload_round(&self) -> i32 {
-        self.payload_round
-    }
-
-    fn level(&self) -> i32 {
-        self.level
-    }
-
-    fn set_payload_hash(&mut self, payload_hash: BlockPayloadHash) {
-        self.payload_hash = payload_hash;
-    }
-
-    fn set_proof_of_work_nonce(&mut self, pow: SizedBytes<8>) {
-        self.proof_of_work_nonce = pow;
-    }
-}
-
-impl ProtocolHeader for ProtocolBlockHeaderI {
-    type FullHeader = FullHeaderI;
-
-    const NAME: &'static str = "Psithaca2MLRFYargivpo7YvUr7wUDqyxrdhC5CQq78mRvimz6A";
-
-    const POW_OFFSET: usize = mem::size_of::<BlockPayloadHash>() + mem::size_of::<i32>();
-
-    fn new(
-        payload_hash: BlockPayloadHash,
-        payload_round: i32,
-        seed_nonce_hash: Option<NonceHash>,
-        liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
-    ) -> Self {
-        let l = matches!(liquidity_baking_toggle_vote, LiquidityBakingToggleVote::On);
-        ProtocolBlockHeaderI {
-            payload_hash,
-            payload_round,
-            proof_of_work_nonce: SizedBytes(0x7985fafe1fb70300u64.to_be_bytes()),
-            seed_nonce_hash,
-            liquidity_baking_escape_vote: l,
-            // fake signature
-            signature: Signature(vec![0; 64]),
-        }
-    }
-
-    fn payload_hash(&self) -> &BlockPayloadHash {
-        &self.payload_hash
-    }
-
-    fn payload_round(&self) -> i32 {
-        self.payload_round
-    }
-
-    fn proof_of_work_nonce(&self) -> SizedBytes<8> {
-        self.proof_of_work_nonce.clone()
-    }
-
-    fn full_header(
-        self,
-        shell_header: ShellBlockShortHeader,
-    ) -> Result<Self::FullHeader, RpcErrorInner> {
-        let ShellBlockShortHeader {
-            level,
-            proto,
-            predecessor,
-            timestamp,
-            validation_pass,
-            operations_hash,
-            fitness,
-            context,
-        } = shell_header;
-        let ProtocolBlockHeaderI {
-            payload_hash,
-            payload_round,
-            proof_of_work_nonce,
-            seed_nonce_hash,
-            liquidity_baking_escape_vote,
-            ..
-        } = self;
-        let timestamp = OffsetDateTime::parse(&timestamp, &Rfc3339)?
-            .unix_timestamp()
-            .into();
-        Ok(FullHeaderI {
-            level,
-            proto,
-            predecessor,
-            timestamp,
-            validation_pass,
-            operations_hash,
-            fitness: {
-                let mut v = vec![];
-                for fitness_str in fitness {
-                    let item = hex::decode(fitness_str)?;
-                    v.push(item);
-                }
-                v.into()
-            },
-            context,
-            payload_hash,
-            payload_round,
-            proof_of_work_nonce,
-            seed_nonce_hash,
-            liquidity_baking_escape_vote,
-            signature: Signature(vec![0; 64]),
-        })
-    }
-}
-
 #[derive(BinWriter, HasEncoding, NomReader, Clone, Copy, Debug, Serialize, Deserialize)]
 #[encoding(tags = "u8")]
 #[serde(rename_all = "snake_case")]
@@ -342,146 +121,6 @@ impl FromStr for LiquidityBakingToggleVote {
     }
 }
 
-#[derive(BinWriter, HasEncoding, NomReader, Clone, Debug, Serialize, Deserialize)]
-#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
-pub struct ProtocolBlockHeaderJ {
-    pub payload_hash: BlockPayloadHash,
-    pub payload_round: i32,
-    #[cfg_attr(feature = "fuzzing", field_mutator(SizedBytesMutator<8>))]
-    pub proof_of_work_nonce: SizedBytes<8>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub seed_nonce_hash: Option<NonceHash>,
-    pub liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
-    pub signature: Signature,
-}
-
-#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, HasEncoding, NomReader, BinWriter)]
-pub struct FullHeaderJ {
-    #[encoding(builtin = "Int32")]
-    pub level: i32,
-    pub proto: u8,
-    pub predecessor: BlockHash,
-    pub timestamp: Timestamp,
-    pub validation_pass: u8,
-    pub operations_hash: OperationListListHash,
-    pub fitness: Fitness,
-    pub context: ContextHash,
-    pub payload_hash: BlockPayloadHash,
-    pub payload_round: i32,
-    #[cfg_attr(feature = "fuzzing", field_mutator(tezos_encoding::fuzzing::sizedbytes::SizedBytesMutator<8>))]
-    pub proof_of_work_nonce: SizedBytes<8>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub seed_nonce_hash: Option<NonceHash>,
-    pub liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
-    pub signature: Signature,
-}
-
-impl ProtocolHeaderFull for FullHeaderJ {
-    fn payload_round(&self) -> i32 {
-        self.payload_round
-    }
-
-    fn level(&self) -> i32 {
-        self.level
-    }
-
-    fn set_payload_hash(&mut self, payload_hash: BlockPayloadHash) {
-        self.payload_hash = payload_hash;
-    }
-
-    fn set_proof_of_work_nonce(&mut self, pow: SizedBytes<8>) {
-        self.proof_of_work_nonce = pow;
-    }
-}
-
-impl ProtocolHeader for ProtocolBlockHeaderJ {
-    type FullHeader = FullHeaderJ;
-
-    const NAME: &'static str = "PtJakart2xVj7pYXJBXrqHgd82rdkLey5ZeeGwDgPp9rhQUbSqY";
-
-    const POW_OFFSET: usize = mem::size_of::<BlockPayloadHash>() + mem::size_of::<i32>();
-
-    fn new(
-        payload_hash: BlockPayloadHash,
-        payload_round: i32,
-        seed_nonce_hash: Option<NonceHash>,
-        liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
-    ) -> Self {
-        ProtocolBlockHeaderJ {
-            payload_hash,
-            payload_round,
-            proof_of_work_nonce: SizedBytes(0x7985fafe1fb70300u64.to_be_bytes()),
-            seed_nonce_hash,
-            liquidity_baking_toggle_vote,
-            // fake signature
-            signature: Signature(vec![0; 64]),
-        }
-    }
-
-    fn payload_hash(&self) -> &BlockPayloadHash {
-        &self.payload_hash
-    }
-
-    fn payload_round(&self) -> i32 {
-        self.payload_round
-    }
-
-    fn proof_of_work_nonce(&self) -> SizedBytes<8> {
-        self.proof_of_work_nonce.clone()
-    }
-
-    fn full_header(
-        self,
-        shell_header: ShellBlockShortHeader,
-    ) -> Result<Self::FullHeader, RpcErrorInner> {
-        let ShellBlockShortHeader {
-            level,
-            proto,
-            predecessor,
-            timestamp,
-            validation_pass,
-            operations_hash,
-            fitness,
-            context,
-        } = shell_header;
-        let ProtocolBlockHeaderJ {
-            payload_hash,
-            payload_round,
-            proof_of_work_nonce,
-            seed_nonce_hash,
-            liquidity_baking_toggle_vote,
-            ..
-        } = self;
-        let timestamp = OffsetDateTime::parse(&timestamp, &Rfc3339)?
-            .unix_timestamp()
-            .into();
-        Ok(FullHeaderJ {
-            level,
-            proto,
-            predecessor,
-            timestamp,
-            validation_pass,
-            operations_hash,
-            fitness: {
-                let mut v = vec![];
-                for fitness_str in fitness {
-                    let item = hex::decode(fitness_str)?;
-                    v.push(item);
-                }
-                v.into()
-            },
-            context,
-            payload_hash,
-            payload_round,
-            proof_of_work_nonce,
-            seed_nonce_hash,
-            liquidity_baking_toggle_vote,
-            signature: Signature(vec![0; 64]),
-        })
-    }
-}
-
 #[derive(Clone)]
 pub struct Constants {
     pub nonce_length: usize,
@@ -532,69 +171,21 @@ impl RpcClient {
         Ok(block)
     }
 
-    pub fn get_constants(&self) -> Result<Constants, RpcError> {
-        #[derive(Deserialize, Debug)]
-        struct ConstantsInner {
-            nonce_length: usize,
-            blocks_per_cycle: u32,
-            blocks_per_commitment: u32,
-            consensus_committee_size: u32,
-            minimal_block_delay: String,
-            delay_increment_per_round: String,
-            proof_of_work_threshold: String,
-        }
-
+    pub fn get_constants(&self, protocol: Protocol) -> Result<Constants, RpcError> {
         let url = self
             .endpoint
             .join("chains/main/blocks/head/context/constants")
             .expect("valid constant url");
-        let ConstantsInner {
-            nonce_length,
-            blocks_per_cycle,
-            blocks_per_commitment,
-            consensus_committee_size,
-            minimal_block_delay,
-            delay_increment_per_round,
-            proof_of_work_threshold,
-        } = self.single_response_blocking::<ConstantsInner>(&url, None, None)?;
-
-        Ok(Constants {
-            nonce_length,
-            blocks_per_cycle,
-            blocks_per_commitment,
-            consensus_committee_size,
-            proof_of_work_threshold: u64::from_be_bytes(
-                proof_of_work_threshold
-                    .parse::<i64>()
-                    .map_err(|err| RpcErrorInner::IntParse(err, "pow threshold".to_string()))
-                    .map_err(|inner| RpcError::WithContext {
-                        url: url.clone(),
-                        inner,
-                    })?
-                    .to_be_bytes(),
-            ),
-            minimal_block_delay: Duration::from_secs(
-                minimal_block_delay
-                    .parse()
-                    .map_err(|err| RpcErrorInner::IntParse(err, "minimal block delay".to_string()))
-                    .map_err(|inner| RpcError::WithContext {
-                        url: url.clone(),
-                        inner,
-                    })?,
-            ),
-            delay_increment_per_round: Duration::from_secs(
-                delay_increment_per_round
-                    .parse()
-                    .map_err(|err| RpcErrorInner::IntParse(err, "delay increment".to_string()))
-                    .map_err(|inner| RpcError::WithContext {
-                        url: url.clone(),
-                        inner,
-                    })?,
-            ),
-        })
+        let constants = self.single_response_blocking::<serde_json::Value>(&url, None, None)?;
+        protocol
+            .parse_constants(constants)
+            .map_err(|inner| RpcError::WithContext { url, inner })
     }
 
-    pub fn validators(&self, level: i32) -> Result<BTreeMap<ContractTz1Hash, Slots>, RpcError> {
+    pub fn validators(
+        &self,
+        level: i32,
+    ) -> Result<BTreeMap<SignaturePublicKeyHash, Slots>, RpcError> {
         let mut url = self
             .endpoint
             .join("chains/main/blocks/head/helpers/validators")
@@ -604,7 +195,7 @@ impl RpcClient {
 
         #[derive(Deserialize)]
         struct Validator {
-            delegate: ContractTz1Hash,
+            delegate: SignaturePublicKeyHash,
             slots: Vec<u16>,
         }
 
@@ -616,13 +207,11 @@ impl RpcClient {
         Ok(validators)
     }
 
-    pub fn monitor_heads<H>(&self, chain_id: &ChainId) -> Result<(), RpcError>
-    where
-        H: ProtocolHeader + BinaryRead,
-    {
+    pub fn monitor_heads(&self, chain_id: &ChainId, protocol: Protocol) -> Result<(), RpcError> {
         let s = format!("monitor/heads/{chain_id}");
         let mut url = self.endpoint.join(&s).expect("valid constant url");
-        url.query_pairs_mut().append_pair("next_protocol", H::NAME);
+        url.query_pairs_mut()
+            .append_pair("next_protocol", protocol.hash());
 
         #[allow(dead_code)]
         #[derive(Deserialize)]
@@ -656,20 +245,24 @@ impl RpcClient {
 
             let s = format!("chains/main/blocks/{}/protocols", header.hash);
             let url = this.endpoint.join(&s).expect("valid url");
-            let Protocols { protocol } =
-                this.single_response_blocking(&url, None, Some(Duration::from_secs(30)))?;
+            let Protocols {
+                protocol: block_protocol,
+            } = this.single_response_blocking(&url, None, Some(Duration::from_secs(30)))?;
 
-            let transition = (protocol.to_base58_check() != ProtocolBlockHeaderI::NAME)
-                && (protocol.to_base58_check() != ProtocolBlockHeaderJ::NAME);
+            // a block of an unknown protocol is a transition block
+            let block_protocol = Protocol::from_hash(&block_protocol.to_base58_check());
+            let transition = block_protocol.is_none();
 
-            let (payload_hash, payload_round, round) = if !transition {
+            let (payload_hash, payload_round, round) = if let Some(block_protocol) = block_protocol
+            {
                 let protocol_data_bytes = hex::decode(header.protocol_data)
                     .map_err(RpcErrorInner::Hex)
                     .map_err(|inner| RpcError::WithContext {
                         url: url.clone(),
                         inner,
                     })?;
-                let protocol_header = H::from_bytes(&protocol_data_bytes)
+                let (payload_hash, payload_round) = block_protocol
+                    .decode_header(&protocol_data_bytes)
                     .map_err(RpcErrorInner::Nom)
                     .map_err(|inner| RpcError::WithContext {
                         url: url.clone(),
@@ -681,11 +274,7 @@ impl RpcClient {
                         inner,
                     })?;
 
-                (
-                    protocol_header.payload_hash().clone(),
-                    protocol_header.payload_round(),
-                    round,
-                )
+                (payload_hash, payload_round, round)
             } else {
                 (BlockPayloadHash(vec![0x55; 32]), 0, 0)
             };
@@ -764,8 +353,9 @@ impl RpcClient {
     }
 
     #[allow(clippy::too_many_arguments)]
-    pub fn preapply_block<H>(
+    pub fn preapply_block(
         &self,
+        protocol: Protocol,
         payload_hash: BlockPayloadHash,
         payload_round: i32,
         seed_nonce_hash: &Option<NonceHash>,
@@ -773,11 +363,7 @@ impl RpcClient {
         timestamp: i64,
         mut operations: [Vec<OperationSimple>; 4],
         liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
-    ) -> Result<(Box<dyn ProtocolHeaderFull>, Vec<serde_json::Value>), RpcError>
-    where
-        H: ProtocolHeader + Serialize,
-        H::FullHeader: 'static,
-    {
+    ) -> Result<(Box<dyn ProtocolHeaderFull>, Vec<serde_json::Value>), RpcError> {
         #[derive(Serialize)]
         struct BlockData {
             protocol_data: serde_json::Value,
@@ -790,28 +376,17 @@ impl RpcClient {
             operations: Vec<serde_json::Value>,
         }
 
-        let protocol_header = H::new(
+        let protocol_header = protocol.header_draft(
             payload_hash,
             payload_round,
             seed_nonce_hash.clone(),
             liquidity_baking_toggle_vote,
         );
 
-        let mut protocol_data = serde_json::to_value(&protocol_header)
+        let protocol_data = protocol_header
+            .protocol_data()
             .map_err(Into::into)
             .map_err(RpcError::Less)?;
-        let protocol_block_header_obj = protocol_data
-            .as_object_mut()
-            .expect("`ProtocolBlockHeader` is a structure");
-        let proof_of_work_str = hex::encode(&protocol_header.proof_of_work_nonce());
-        protocol_block_header_obj.insert(
-            "proof_of_work_nonce".to_string(),
-            serde_json::Value::String(proof_of_work_str),
-        );
-        protocol_block_header_obj.insert(
-            "protocol".to_string(),
-            serde_json::Value::String(H::NAME.to_string()),
-        );
 
         for ops_list in &mut operations {
             for op in ops_list {
@@ -853,7 +428,7 @@ impl RpcClient {
                     inner,
                 })?;
 
-        Ok((Box::new(full_block_header), operations))
+        Ok((full_block_header, operations))
     }
 
     pub fn inject_block(
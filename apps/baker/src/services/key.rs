@@ -1,22 +1,41 @@
 // Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use std::{convert::TryFrom, fs::File, io, path::Path, str::FromStr};
+use std::{
+    convert::TryFrom,
+    env,
+    fs::{self, File},
+    io::{self, Read, Write},
+    net::TcpStream,
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
 use derive_more::From;
 use reqwest::{blocking::Client, Url};
 use serde::Deserialize;
 use thiserror::Error;
+use zeroize::Zeroizing;
 
 use crypto::{
     base58::FromBase58CheckError,
     hash::{
-        ChainId, ContractTz1Hash, Ed25519Signature, SecretKeyEd25519, SeedEd25519, Signature,
-        TryFromPKError,
+        ChainId, ContractTz1Hash, ContractTz2Hash, ContractTz3Hash, Ed25519Signature,
+        EncryptedSecretKeyP256, EncryptedSecretKeySecp256k1, EncryptedSeedEd25519, P256Signature,
+        Secp256k1Signature, SecretKeyEd25519, SecretKeyP256, SecretKeySecp256k1, SeedEd25519,
+        Signature, TryFromPKError,
     },
     CryptoError,
 };
 use tezos_encoding::enc::{BinError, BinWriter};
+use tezos_messages::{
+    base::{signature_public_key::SignaturePublicKeyHash, ConversionError},
+    p2p::binary_message::BinaryRead,
+};
+
+use super::watermark::{LastSigned, Watermarks};
 
 #[derive(Debug, Error, From)]
 pub enum ReadKeyError {
@@ -28,6 +47,13 @@ pub enum ReadKeyError {
     KeyNotFound { baker: String },
     #[error("failed to parse key {_0}")]
     FailedToParseKey(SignerParseError),
+    #[error("remote signer: {_0}")]
+    RemoteSigner(SignError),
+    #[error("no key in secret_keys is authorized by the remote signer")]
+    NoAuthorizedKey,
+    #[error("wrong password for the encrypted key {name}")]
+    #[from(ignore)]
+    WrongPassword { name: String },
 }
 
 #[derive(Debug, Error, From)]
@@ -42,6 +68,11 @@ pub enum SignError {
     Reqwest(reqwest::Error),
     #[error("{_0}")]
     Base58(FromBase58CheckError),
+    #[error("{_0}")]
+    Io(io::Error),
+    #[error("remote signer error: {reason}")]
+    #[from(ignore)]
+    RemoteSigner { reason: String },
     #[error("already signed {} {level}:{round}")]
     AlreadySigned {
         kind: String,
@@ -50,20 +81,75 @@ pub enum SignError {
     },
 }
 
-#[derive(Default)]
-struct LastSigned {
-    preendorsement: (i32, i32),
-    endorsement: (i32, i32),
-    block: (i32, i32),
+pub struct CryptoService(Signer, Watermarks);
+
+/// Environment variable holding the password of the `encrypted:` keys in `secret_keys`.
+pub const PASSWORD_ENV_VAR: &str = "TEZEDGE_BAKER_PASSWORD";
+
+const PASSWORD_PROMPT_ATTEMPTS: usize = 3;
+
+/// Passwords for the `encrypted:` keys in `secret_keys`. The password is taken from the file,
+/// if any, then from the `TEZEDGE_BAKER_PASSWORD` environment variable, and asked on the
+/// terminal as a last resort. Passwords that worked are tried first for the next keys.
+pub struct Passwords {
+    filename: Option<PathBuf>,
+    unlocked: Vec<Zeroizing<String>>,
 }
 
-pub struct CryptoService(Signer, LastSigned);
+impl Passwords {
+    pub fn new(filename: Option<PathBuf>) -> Self {
+        Passwords {
+            filename,
+            unlocked: vec![],
+        }
+    }
+
+    fn decrypt<T, F>(&mut self, name: &str, decrypt: F) -> Result<T, ReadKeyError>
+    where
+        F: Fn(&[u8]) -> Result<T, CryptoError>,
+    {
+        for password in &self.unlocked {
+            if let Ok(value) = decrypt(password.as_bytes()) {
+                return Ok(value);
+            }
+        }
+
+        let unlocked = &mut self.unlocked;
+        let mut try_password = |password: Zeroizing<String>| {
+            let value = decrypt(password.as_bytes()).ok()?;
+            unlocked.push(password);
+            Some(value)
+        };
+        let wrong_password = || ReadKeyError::WrongPassword {
+            name: name.to_string(),
+        };
+
+        if let Some(filename) = &self.filename {
+            let mut password = Zeroizing::new(fs::read_to_string(filename)?);
+            let len = password.trim_end_matches(&['\r', '\n'][..]).len();
+            password.truncate(len);
+            return try_password(password).ok_or_else(wrong_password);
+        }
+        if let Ok(password) = env::var(PASSWORD_ENV_VAR) {
+            return try_password(Zeroizing::new(password)).ok_or_else(wrong_password);
+        }
+        for _ in 0..PASSWORD_PROMPT_ATTEMPTS {
+            let prompt = format!("Enter password for encrypted key \"{}\": ", name);
+            let password = Zeroizing::new(rpassword::read_password_from_tty(Some(&prompt))?);
+            if let Some(value) = try_password(password) {
+                return Ok(value);
+            }
+        }
+        Err(wrong_password())
+    }
+}
 
 impl CryptoService {
     pub fn read_key(
         log: &slog::Logger,
         base_dir: &Path,
         baker: &str,
+        passwords: &mut Passwords,
     ) -> Result<Self, ReadKeyError> {
         #[derive(Deserialize)]
         struct SecretKeyRecord {
@@ -71,30 +157,90 @@ impl CryptoService {
             value: String,
         }
 
+        #[derive(Deserialize)]
+        struct PublicKeyHashRecord {
+            name: String,
+            value: SignaturePublicKeyHash,
+        }
+
         let secret_keys = File::open(base_dir.join("secret_keys"))?;
         let secret_keys = serde_json::from_reader::<_, Vec<SecretKeyRecord>>(secret_keys)?;
-        let signer = secret_keys
-            .iter()
-            .find(|v| v.name == baker)
-            .ok_or(ReadKeyError::KeyNotFound {
-                baker: baker.to_string(),
-            })?
-            .value
-            .parse::<Signer>()?;
-
-        if let SignerBackend::RemoteHttps(_, url) = &signer.backend {
-            slog::info!(log, "using remote signer: {}", url);
-        } else {
-            slog::info!(log, "using local key: {}", signer.pkh);
+        let record =
+            secret_keys
+                .iter()
+                .find(|v| v.name == baker)
+                .ok_or(ReadKeyError::KeyNotFound {
+                    baker: baker.to_string(),
+                })?;
+        let mut signer = Signer::read(&record.name, &record.value, passwords)?;
+
+        match &signer.backend {
+            SignerBackend::RemoteHttps(_, url) => {
+                slog::info!(log, "using remote signer: {}", url);
+            }
+            SignerBackend::RemoteTcp(address, _) => {
+                slog::info!(log, "using remote signer: tcp://{}", address);
+            }
+            SignerBackend::UnixDomainSocket(path, _) => {
+                slog::info!(log, "using remote signer: unix:{}", path.display());
+            }
+            SignerBackend::LiteralSecretKey(_) => {
+                slog::info!(log, "using local key: {}", signer.pkh);
+            }
         }
 
-        Ok(CryptoService(signer, LastSigned::default()))
+        // the remote signer may require requests signed by one of its authorized keys,
+        // use the first local key it accepts, the same way `tezos-client` does
+        let authorized_keys = signer
+            .backend
+            .authorized_keys()
+            .map_err(ReadKeyError::RemoteSigner)?;
+        if let Some(authorized_keys) = authorized_keys {
+            // the hash of an encrypted key is known without decrypting it from `public_key_hashs`
+            let public_key_hashs = File::open(base_dir.join("public_key_hashs"))
+                .ok()
+                .and_then(|file| serde_json::from_reader::<_, Vec<PublicKeyHashRecord>>(file).ok())
+                .unwrap_or_default();
+            let record = secret_keys
+                .iter()
+                .find(|v| match v.value.parse::<Signer>() {
+                    Ok(Signer {
+                        backend: SignerBackend::LiteralSecretKey(_),
+                        pkh,
+                    }) => authorized_keys.contains(&pkh),
+                    _ => {
+                        v.value.starts_with("encrypted:")
+                            && public_key_hashs.iter().any(|pkh| {
+                                pkh.name == v.name && authorized_keys.contains(&pkh.value)
+                            })
+                    }
+                })
+                .ok_or(ReadKeyError::NoAuthorizedKey)?;
+            match Signer::read(&record.name, &record.value, passwords)? {
+                Signer {
+                    backend: SignerBackend::LiteralSecretKey(secret_key),
+                    pkh,
+                } if authorized_keys.contains(&pkh) => {
+                    slog::info!(log, "authenticating remote signer requests with: {}", pkh);
+                    signer.backend.authenticate(secret_key);
+                }
+                _ => return Err(ReadKeyError::NoAuthorizedKey),
+            }
+        }
+
+        let watermarks = Watermarks::new(base_dir, signer.pkh.clone());
+        Ok(CryptoService(signer, watermarks))
     }
 
-    pub fn public_key_hash(&self) -> &ContractTz1Hash {
+    pub fn public_key_hash(&self) -> &SignaturePublicKeyHash {
         &self.0.pkh
     }
 
+    /// The stored high watermark, nothing below it will be signed.
+    pub fn last_signed(&mut self, chain_id: &ChainId) -> Result<LastSigned, SignError> {
+        Ok(self.1.get(chain_id)?)
+    }
+
     pub fn sign<T>(
         &mut self,
         watermark_tag: u8,
@@ -107,10 +253,13 @@ impl CryptoService {
     where
         T: BinWriter,
     {
+        // a forced sign skips the check, but never lowers the watermark
+        let mut last_signed = self.1.get(chain_id)?;
         if watermark_tag == 0x12 {
             if !force
-                && (level < self.1.preendorsement.0
-                    || (level == self.1.preendorsement.0 && round <= self.1.preendorsement.1))
+                && (level < last_signed.preendorsement.0
+                    || (level == last_signed.preendorsement.0
+                        && round <= last_signed.preendorsement.1))
             {
                 return Err(SignError::AlreadySigned {
                     kind: "preendorsement".to_string(),
@@ -118,11 +267,11 @@ impl CryptoService {
                     round,
                 });
             }
-            self.1.preendorsement = (level, round);
+            last_signed.preendorsement = last_signed.preendorsement.max((level, round));
         } else if watermark_tag == 0x13 {
             if !force
-                && (level < self.1.endorsement.0
-                    || (level == self.1.endorsement.0 && round <= self.1.endorsement.1))
+                && (level < last_signed.endorsement.0
+                    || (level == last_signed.endorsement.0 && round <= last_signed.endorsement.1))
             {
                 return Err(SignError::AlreadySigned {
                     kind: "endorsement".to_string(),
@@ -130,10 +279,11 @@ impl CryptoService {
                     round,
                 });
             }
-            self.1.endorsement = (level, round);
+            last_signed.endorsement = last_signed.endorsement.max((level, round));
         } else if watermark_tag == 0x11 {
             if !force
-                && (level < self.1.block.0 || (level == self.1.block.0 && round <= self.1.block.1))
+                && (level < last_signed.block.0
+                    || (level == last_signed.block.0 && round <= last_signed.block.1))
             {
                 return Err(SignError::AlreadySigned {
                     kind: "block".to_string(),
@@ -141,7 +291,11 @@ impl CryptoService {
                     round,
                 });
             }
-            self.1.block = (level, round);
+            last_signed.block = last_signed.block.max((level, round));
+        }
+        // persist the watermark before the signature exists
+        if last_signed != self.1.get(chain_id)? {
+            self.1.set(chain_id, last_signed)?;
         }
 
         let mut v = Vec::new();
@@ -156,10 +310,10 @@ impl CryptoService {
             v
         };
         let cut = value_bytes.len() - 64;
-        let signature = self
-            .0
-            .backend
-            .sign(&[watermark_bytes.as_slice(), &value_bytes[..cut]])?;
+        let signature = self.0.backend.sign(
+            &self.0.pkh,
+            &[watermark_bytes.as_slice(), &value_bytes[..cut]],
+        )?;
         value_bytes.splice(cut.., signature.0.iter().cloned());
         Ok((value_bytes, signature))
     }
@@ -167,19 +321,116 @@ impl CryptoService {
 
 struct Signer {
     backend: SignerBackend,
-    pkh: ContractTz1Hash,
+    pkh: SignaturePublicKeyHash,
+}
+
+impl Signer {
+    /// Parses the `secret_keys` entry, decrypting it if needed.
+    fn read(name: &str, value: &str, passwords: &mut Passwords) -> Result<Self, ReadKeyError> {
+        match value.strip_prefix("encrypted:") {
+            Some(encrypted) => {
+                let (secret_key, pkh) = SecretKey::decrypt(encrypted, name, passwords)?;
+                Ok(Signer {
+                    backend: SignerBackend::LiteralSecretKey(secret_key),
+                    pkh,
+                })
+            }
+            None => Ok(value.parse()?),
+        }
+    }
+}
+
+/// Local secret key of any of the curves supported by the protocol.
+enum SecretKey {
+    Ed25519(SecretKeyEd25519),
+    Secp256k1(SecretKeySecp256k1),
+    P256(SecretKeyP256),
+}
+
+impl SecretKey {
+    /// Parses `edsk...`, `spsk...` or `p2sk...` and computes the hash of its public key.
+    fn parse(value: &str) -> Result<(Self, SignaturePublicKeyHash), SignerParseError> {
+        match value.get(..4) {
+            Some("edsk") => Self::ed25519(SeedEd25519::from_base58_check(value)?),
+            Some("spsk") => Self::secp256k1(SecretKeySecp256k1::from_base58_check(value)?),
+            Some("p2sk") => Self::p256(SecretKeyP256::from_base58_check(value)?),
+            _ => Err(SignerParseError::UnsupportedKey),
+        }
+    }
+
+    /// Decrypts `edesk...`, `spesk...` or `p2esk...` and computes the hash of its public key.
+    fn decrypt(
+        value: &str,
+        name: &str,
+        passwords: &mut Passwords,
+    ) -> Result<(Self, SignaturePublicKeyHash), ReadKeyError> {
+        let parsed = match value.get(..5) {
+            Some("edesk") => {
+                let encrypted = EncryptedSeedEd25519::from_base58_check(value)
+                    .map_err(SignerParseError::from)?;
+                Self::ed25519(passwords.decrypt(name, |p| encrypted.decrypt(p))?)
+            }
+            Some("spesk") => {
+                let encrypted = EncryptedSecretKeySecp256k1::from_base58_check(value)
+                    .map_err(SignerParseError::from)?;
+                Self::secp256k1(passwords.decrypt(name, |p| encrypted.decrypt(p))?)
+            }
+            Some("p2esk") => {
+                let encrypted = EncryptedSecretKeyP256::from_base58_check(value)
+                    .map_err(SignerParseError::from)?;
+                Self::p256(passwords.decrypt(name, |p| encrypted.decrypt(p))?)
+            }
+            _ => Err(SignerParseError::UnsupportedKey),
+        };
+        Ok(parsed?)
+    }
+
+    fn ed25519(seed: SeedEd25519) -> Result<(Self, SignaturePublicKeyHash), SignerParseError> {
+        let (public_key, secret_key) = seed.keypair()?;
+        let pkh = SignaturePublicKeyHash::Ed25519(ContractTz1Hash::try_from(public_key)?);
+        Ok((SecretKey::Ed25519(secret_key), pkh))
+    }
+
+    fn secp256k1(
+        secret_key: SecretKeySecp256k1,
+    ) -> Result<(Self, SignaturePublicKeyHash), SignerParseError> {
+        let public_key = secret_key.public_key()?;
+        let pkh = SignaturePublicKeyHash::Secp256k1(ContractTz2Hash::try_from(public_key)?);
+        Ok((SecretKey::Secp256k1(secret_key), pkh))
+    }
+
+    fn p256(secret_key: SecretKeyP256) -> Result<(Self, SignaturePublicKeyHash), SignerParseError> {
+        let public_key = secret_key.public_key()?;
+        let pkh = SignaturePublicKeyHash::P256(ContractTz3Hash::try_from(public_key)?);
+        Ok((SecretKey::P256(secret_key), pkh))
+    }
+
+    fn sign<T, I>(&self, data: T) -> Result<Signature, CryptoError>
+    where
+        T: IntoIterator<Item = I>,
+        I: AsRef<[u8]>,
+    {
+        match self {
+            SecretKey::Ed25519(sk) => sk.sign(data),
+            SecretKey::Secp256k1(sk) => sk.sign(data),
+            SecretKey::P256(sk) => sk.sign(data),
+        }
+    }
 }
 
 enum SignerBackend {
-    /// example: unencrypted:edsk4N...
-    LiteralSecretKey(SecretKeyEd25519),
+    /// example: unencrypted:edsk4N..., unencrypted:spsk..., unencrypted:p2sk...,
+    /// encrypted:edesk1..., encrypted:spesk1..., encrypted:p2esk...
+    LiteralSecretKey(SecretKey),
     // http also works here
     /// example: http://127.0.0.1:6732/keys/tz1TXkLKR4F4HUSCQKve7daPPLSxhZNx45px
     RemoteHttps(Client, Url),
-    // example: tcp://127.0.0.1:7732/tz1TXkLKR4F4HUSCQKve7daPPLSxhZNx45px
-    // RemoteTcp(SocketAddr),
-    // example: unix:/home/dev/.tezos-signer/socket?pkh=tz1TXkLKR4F4HUSCQKve7daPPLSxhZNx45px
-    // UnixDomainSocket(PathBuf),
+    /// example: tcp://127.0.0.1:7732/tz1TXkLKR4F4HUSCQKve7daPPLSxhZNx45px
+    /// the optional key authenticates the requests
+    RemoteTcp(String, Option<SecretKey>),
+    /// example: unix:/home/dev/.tezos-signer/socket?pkh=tz1TXkLKR4F4HUSCQKve7daPPLSxhZNx45px
+    /// the optional key authenticates the requests
+    UnixDomainSocket(PathBuf, Option<SecretKey>),
 }
 
 #[derive(Debug, Error, From)]
@@ -188,7 +439,7 @@ pub enum SignerParseError {
     NoSchema,
     #[error("unknown schema: \"{_0}\"")]
     UnknownSchema(String),
-    #[error("only ed25519 keys supported")]
+    #[error("only ed25519, secp256k1 and p256 keys supported")]
     UnsupportedKey,
     #[error("invalid key {_0}")]
     InvalidKey(FromBase58CheckError),
@@ -196,10 +447,16 @@ pub enum SignerParseError {
     Crypto(CryptoError),
     #[error("public key format error {_0}")]
     PkFormat(TryFromPKError),
+    #[error("invalid public key hash {_0}")]
+    InvalidPkh(ConversionError),
     #[error("{_0}")]
     InvalidUrl(url::ParseError),
     #[error("missing \"/tz1...\"")]
     MissingPkhPathSegment,
+    #[error("missing \"host:port\"")]
+    MissingAddress,
+    #[error("missing \"?pkh=tz1...\"")]
+    MissingPkhQuery,
 }
 
 impl FromStr for Signer {
@@ -211,14 +468,11 @@ impl FromStr for Signer {
         let value = it.next().ok_or(SignerParseError::NoSchema)?;
         match schema {
             "unencrypted" => {
-                if !value.starts_with("edsk") {
-                    return Err(SignerParseError::UnsupportedKey);
-                }
-                let (public_key, secret_key) = SeedEd25519::from_base58_check(value)?.keypair()?;
+                let (secret_key, pkh) = SecretKey::parse(value)?;
 
                 Ok(Signer {
                     backend: SignerBackend::LiteralSecretKey(secret_key),
-                    pkh: ContractTz1Hash::try_from(public_key)?,
+                    pkh,
                 })
             }
             "http" | "https" => {
@@ -228,26 +482,162 @@ impl FromStr for Signer {
                     .ok_or(SignerParseError::MissingPkhPathSegment)?
                     .last()
                     .ok_or(SignerParseError::MissingPkhPathSegment)?;
-                let pkh = ContractTz1Hash::from_base58_check(pkh_str)?;
+                let pkh = SignaturePublicKeyHash::from_b58_hash(pkh_str)?;
                 let client = Client::new();
                 Ok(Signer {
                     backend: SignerBackend::RemoteHttps(client, url),
                     pkh,
                 })
             }
+            "tcp" => {
+                let url = Url::parse(s)?;
+                let host = url.host_str().ok_or(SignerParseError::MissingAddress)?;
+                let port = url.port().ok_or(SignerParseError::MissingAddress)?;
+                let pkh_str = url
+                    .path_segments()
+                    .ok_or(SignerParseError::MissingPkhPathSegment)?
+                    .last()
+                    .ok_or(SignerParseError::MissingPkhPathSegment)?;
+                let pkh = SignaturePublicKeyHash::from_b58_hash(pkh_str)?;
+                Ok(Signer {
+                    backend: SignerBackend::RemoteTcp(format!("{}:{}", host, port), None),
+                    pkh,
+                })
+            }
+            "unix" => {
+                let url = Url::parse(s)?;
+                let pkh_str = url
+                    .query_pairs()
+                    .find(|(key, _)| key == "pkh")
+                    .ok_or(SignerParseError::MissingPkhQuery)?
+                    .1;
+                let pkh = SignaturePublicKeyHash::from_b58_hash(&pkh_str)?;
+                Ok(Signer {
+                    backend: SignerBackend::UnixDomainSocket(PathBuf::from(url.path()), None),
+                    pkh,
+                })
+            }
             s => Err(SignerParseError::UnknownSchema(s.to_string())),
         }
     }
 }
 
+/// Tezos signer binary protocol, see `signer_messages.ml`
+const SIGNER_SIGN_REQUEST: u8 = 0x00;
+const SIGNER_AUTHORIZED_KEYS_REQUEST: u8 = 0x02;
+/// Prepended to the pkh and data when signing a request with the authentication key
+const SIGNER_AUTHENTICATION_MAGIC_BYTE: u8 = 0x04;
+const SIGNER_TIMEOUT: Duration = Duration::from_secs(10);
+
 impl SignerBackend {
-    pub fn sign<T, I>(&self, data: T) -> Result<Signature, SignError>
+    /// Keys whose signature the remote signer requires on requests, `None` if it
+    /// does not require authentication.
+    fn authorized_keys(&self) -> Result<Option<Vec<SignaturePublicKeyHash>>, SignError> {
+        if !matches!(
+            self,
+            SignerBackend::RemoteTcp(..) | SignerBackend::UnixDomainSocket(..)
+        ) {
+            return Ok(None);
+        }
+
+        let response = self.request(&[SIGNER_AUTHORIZED_KEYS_REQUEST])?;
+        match response.split_first() {
+            Some((0x00, [])) => Ok(None),
+            Some((0x01, list)) => {
+                let list = list.get(4..).ok_or_else(malformed_response)?;
+                list.chunks(21)
+                    .map(|pkh| {
+                        SignaturePublicKeyHash::from_bytes(pkh).map_err(|_| malformed_response())
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(Some)
+            }
+            _ => Err(malformed_response()),
+        }
+    }
+
+    fn authenticate(&mut self, secret_key: SecretKey) {
+        match self {
+            SignerBackend::RemoteTcp(_, authentication)
+            | SignerBackend::UnixDomainSocket(_, authentication) => {
+                *authentication = Some(secret_key)
+            }
+            SignerBackend::LiteralSecretKey(_) | SignerBackend::RemoteHttps(..) => (),
+        }
+    }
+
+    /// Sends the request to the remote signer socket and returns the successful response.
+    fn request(&self, request: &[u8]) -> Result<Vec<u8>, SignError> {
+        let response = match self {
+            SignerBackend::RemoteTcp(address, _) => {
+                let stream = TcpStream::connect(address)?;
+                stream.set_read_timeout(Some(SIGNER_TIMEOUT))?;
+                stream.set_write_timeout(Some(SIGNER_TIMEOUT))?;
+                exchange(stream, request)?
+            }
+            SignerBackend::UnixDomainSocket(path, _) => {
+                let stream = UnixStream::connect(path)?;
+                stream.set_read_timeout(Some(SIGNER_TIMEOUT))?;
+                stream.set_write_timeout(Some(SIGNER_TIMEOUT))?;
+                exchange(stream, request)?
+            }
+            SignerBackend::LiteralSecretKey(_) | SignerBackend::RemoteHttps(..) => {
+                return Err(SignError::RemoteSigner {
+                    reason: "not a socket signer".to_string(),
+                })
+            }
+        };
+
+        // the response is wrapped in `result_encoding`
+        match response.split_first() {
+            Some((0x00, ok)) => Ok(ok.to_vec()),
+            Some((0x01, error)) => Err(SignError::RemoteSigner {
+                reason: String::from_utf8_lossy(error.get(4..).unwrap_or_default()).into_owned(),
+            }),
+            _ => Err(malformed_response()),
+        }
+    }
+
+    pub fn sign<T, I>(&self, pkh: &SignaturePublicKeyHash, data: T) -> Result<Signature, SignError>
     where
         T: IntoIterator<Item = I>,
         I: AsRef<[u8]>,
     {
         match self {
             SignerBackend::LiteralSecretKey(sk) => sk.sign(data).map_err(SignError::Crypto),
+            SignerBackend::RemoteTcp(_, authentication)
+            | SignerBackend::UnixDomainSocket(_, authentication) => {
+                let mut data_bytes = vec![];
+                for d in data {
+                    data_bytes.extend_from_slice(d.as_ref());
+                }
+                // tagged public key hash encoding, 0x00 ed25519, 0x01 secp256k1, 0x02 p256
+                let mut pkh_bytes = Vec::with_capacity(21);
+                pkh.bin_write(&mut pkh_bytes)?;
+
+                let mut request = vec![SIGNER_SIGN_REQUEST];
+                request.extend_from_slice(&pkh_bytes);
+                request.extend_from_slice(&(data_bytes.len() as u32).to_be_bytes());
+                request.extend_from_slice(&data_bytes);
+                match authentication {
+                    Some(secret_key) => {
+                        let signature = secret_key.sign([
+                            [SIGNER_AUTHENTICATION_MAGIC_BYTE].as_slice(),
+                            pkh_bytes.as_slice(),
+                            data_bytes.as_slice(),
+                        ])?;
+                        request.push(0xff);
+                        request.extend_from_slice(&signature.0);
+                    }
+                    None => request.push(0x00),
+                }
+
+                let signature = self.request(&request)?;
+                if signature.len() != 64 {
+                    return Err(malformed_response());
+                }
+                Ok(Signature(signature))
+            }
             SignerBackend::RemoteHttps(client, url) => {
                 let mut v = vec![];
                 for d in data {
@@ -262,12 +652,309 @@ impl SignerBackend {
 
                 #[derive(Deserialize)]
                 struct SignerResponse {
-                    signature: Ed25519Signature,
+                    signature: String,
                 }
                 let SignerResponse { signature } = serde_json::from_reader(response)?;
 
-                Ok(Signature(signature.0))
+                let signature = match signature.get(..5) {
+                    Some("edsig") => Ed25519Signature::from_base58_check(&signature)?.0,
+                    Some("spsig") => Secp256k1Signature::from_base58_check(&signature)?.0,
+                    Some("p2sig") => P256Signature::from_base58_check(&signature)?.0,
+                    _ => Signature::from_base58_check(&signature)?.0,
+                };
+                Ok(Signature(signature))
             }
         }
     }
 }
+
+fn malformed_response() -> SignError {
+    SignError::RemoteSigner {
+        reason: "malformed response".to_string(),
+    }
+}
+
+/// Messages on the signer socket are prefixed by their length as 2 bytes big endian.
+fn exchange<S>(mut stream: S, request: &[u8]) -> Result<Vec<u8>, SignError>
+where
+    S: Read + Write,
+{
+    let len = u16::try_from(request.len()).map_err(|_| SignError::RemoteSigner {
+        reason: format!("request of {} bytes is too long", request.len()),
+    })?;
+    stream.write_all(&len.to_be_bytes())?;
+    stream.write_all(request)?;
+    stream.flush()?;
+
+    let mut len = [0; 2];
+    stream.read_exact(&mut len)?;
+    let mut response = vec![0; usize::from(u16::from_be_bytes(len))];
+    stream.read_exact(&mut response)?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::{TryFrom, TryInto},
+        io::{Read, Write},
+        os::unix::net::{UnixListener, UnixStream},
+        thread,
+    };
+
+    use crypto::{
+        blake2b,
+        hash::{
+            ChainId, ContractTz1Hash, PublicKeyP256, PublicKeySecp256k1, SeedEd25519, Signature,
+        },
+        PublicKeySignatureVerifier,
+    };
+    use tezos_encoding::types::SizedBytes;
+    use tezos_messages::base::signature_public_key::SignaturePublicKeyHash;
+
+    use super::{CryptoService, Passwords, ReadKeyError, SecretKey, Signer, SignerBackend};
+
+    const BAKER_KEY: &str = "edsk31vznjHSSpGExDMHYASz45VZqXN4DPxvsa4hAyY8dHM28cZzp6";
+    const AUTHENTICATION_KEY: &str = "edsk39qAm1fiMjgmPkw1EgQYkMzkJezLNewd7PLNHTkr6w9XA2zdfo";
+
+    fn read_message(stream: &mut UnixStream) -> Vec<u8> {
+        let mut len = [0; 2];
+        stream.read_exact(&mut len).unwrap();
+        let mut message = vec![0; usize::from(u16::from_be_bytes(len))];
+        stream.read_exact(&mut message).unwrap();
+        message
+    }
+
+    fn write_message(stream: &mut UnixStream, message: &[u8]) {
+        stream
+            .write_all(&(message.len() as u16).to_be_bytes())
+            .unwrap();
+        stream.write_all(message).unwrap();
+    }
+
+    #[test]
+    fn parse_socket_signers() {
+        let pkh = "tz1TXkLKR4F4HUSCQKve7daPPLSxhZNx45px";
+
+        let signer = format!("tcp://127.0.0.1:7732/{}", pkh)
+            .parse::<Signer>()
+            .unwrap();
+        assert_eq!(signer.pkh.to_base58_check(), pkh);
+        assert!(
+            matches!(signer.backend, SignerBackend::RemoteTcp(address, None) if address == "127.0.0.1:7732")
+        );
+
+        let signer = format!("unix:/home/dev/.tezos-signer/socket?pkh={}", pkh)
+            .parse::<Signer>()
+            .unwrap();
+        assert_eq!(signer.pkh.to_base58_check(), pkh);
+        assert!(
+            matches!(signer.backend, SignerBackend::UnixDomainSocket(path, None) if path.to_str() == Some("/home/dev/.tezos-signer/socket"))
+        );
+    }
+
+    #[test]
+    fn unix_socket_signer_with_authentication() {
+        let (baker_pk, baker_sk) = SeedEd25519::from_base58_check(BAKER_KEY)
+            .unwrap()
+            .keypair()
+            .unwrap();
+        let (auth_pk, auth_sk) = SeedEd25519::from_base58_check(AUTHENTICATION_KEY)
+            .unwrap()
+            .keypair()
+            .unwrap();
+        let baker_pkh =
+            SignaturePublicKeyHash::Ed25519(ContractTz1Hash::try_from(baker_pk.clone()).unwrap());
+        let auth_pkh = ContractTz1Hash::try_from(auth_pk.clone()).unwrap();
+
+        let path =
+            std::env::temp_dir().join(format!("tezedge-baker-signer-{}.sock", std::process::id()));
+        std::fs::remove_file(&path).ok();
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let server_auth_pkh = auth_pkh.clone();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            assert_eq!(read_message(&mut stream), [0x02]);
+            let mut response = vec![0x00, 0x01];
+            response.extend_from_slice(&21u32.to_be_bytes());
+            response.push(0x00);
+            response.extend_from_slice(&server_auth_pkh.0);
+            write_message(&mut stream, &response);
+
+            let (mut stream, _) = listener.accept().unwrap();
+            let request = read_message(&mut stream);
+            assert_eq!(request[0], 0x00);
+            let pkh = &request[1..22];
+            let len = u32::from_be_bytes(request[22..26].try_into().unwrap()) as usize;
+            let data = &request[26..26 + len];
+            assert_eq!(request[26 + len], 0xff);
+
+            let authentication = Signature(request[27 + len..].to_vec());
+            let authenticated = [[0x04].as_slice(), pkh, data].concat();
+            let digest = blake2b::digest_256(&authenticated).unwrap();
+            assert!(auth_pk.verify_signature(&authentication, &digest).unwrap());
+
+            let mut response = vec![0x00];
+            response.extend_from_slice(&baker_sk.sign([data]).unwrap().0);
+            write_message(&mut stream, &response);
+        });
+
+        let mut signer = format!("unix:{}?pkh={}", path.display(), baker_pkh)
+            .parse::<Signer>()
+            .unwrap();
+        let authorized_keys = signer.backend.authorized_keys().unwrap();
+        assert_eq!(
+            authorized_keys,
+            Some(vec![SignaturePublicKeyHash::Ed25519(auth_pkh)])
+        );
+        signer.backend.authenticate(SecretKey::Ed25519(auth_sk));
+
+        let signature = signer
+            .backend
+            .sign(
+                &signer.pkh,
+                [b"watermark".as_slice(), b"payload".as_slice()],
+            )
+            .unwrap();
+        server.join().unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let digest = blake2b::digest_256(b"watermarkpayload").unwrap();
+        assert!(baker_pk.verify_signature(&signature, &digest).unwrap());
+    }
+
+    #[test]
+    fn secp256k1_and_p256_local_keys() {
+        let data = [[0x11].as_slice(), b"block header".as_slice()];
+        let digest = blake2b::digest_256(b"\x11block header").unwrap();
+
+        let signer = "unencrypted:spsk2m4hr5fFQ5yMBF1LNY3DArjXq1CpF9DdXfdQSxwxGbNFgYvBp5"
+            .parse::<Signer>()
+            .unwrap();
+        assert_eq!(
+            signer.pkh.to_base58_check(),
+            "tz29i8EQgNQiZbCNbyMDG3Cy5m6jJbDoPsUX"
+        );
+        let signature = signer.backend.sign(&signer.pkh, data).unwrap();
+        let pk = PublicKeySecp256k1::from_base58_check(
+            "sppk7bB6th7b26yLBqwkVGgptfFeWLGEKQKoTWZdB5WMi4aezXMaP3p",
+        )
+        .unwrap();
+        assert!(pk.verify_signature(&signature, &digest).unwrap());
+
+        let signer = "unencrypted:p2sk3mdKB2zYhubQh56EL1JWMkewAaBiLgwUp2VfZKHuNyhpbyJVZT"
+            .parse::<Signer>()
+            .unwrap();
+        assert_eq!(
+            signer.pkh.to_base58_check(),
+            "tz3Nu1paiRWyXPPs1gjWWLQUyH4DfoPTiHNF"
+        );
+        let signature = signer.backend.sign(&signer.pkh, data).unwrap();
+        let pk = PublicKeyP256::from_base58_check(
+            "p2pk6792eyMRyA6jjd8u6E84EgD8bj2ZKJ5mpvjWVxaVoxHnTqU3Qqr",
+        )
+        .unwrap();
+        assert!(pk.verify_signature(&signature, &digest).unwrap());
+
+        assert!(matches!(
+            "unencrypted:BLsk1hKAHyGqY9qRbgoSVnr7fHhbb4uA3Zk3M6gBFFYw6AM8aYSLbk".parse::<Signer>(),
+            Err(super::SignerParseError::UnsupportedKey)
+        ));
+    }
+
+    #[test]
+    fn encrypted_key_with_password_file() {
+        let dir = std::env::temp_dir().join(format!("tezedge-baker-keys-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("secret_keys"),
+            r#"[{ "name": "baker", "value": "encrypted:edesk1cSwttzXwr2SmVWMNqwoZH4rUP2aQGhNPWD1iFb3Lg8r6RtrMpeW9zK4MMJ7pK32kZQ8gBifJxBDDqpifVe" }]"#,
+        )
+        .unwrap();
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+
+        std::fs::write(dir.join("password"), "tezedge\n").unwrap();
+        let mut passwords = Passwords::new(Some(dir.join("password")));
+        let crypto = CryptoService::read_key(&log, &dir, "baker", &mut passwords).unwrap();
+        let (baker_pk, _) = SeedEd25519::from_base58_check(BAKER_KEY)
+            .unwrap()
+            .keypair()
+            .unwrap();
+        assert_eq!(
+            crypto.public_key_hash(),
+            &SignaturePublicKeyHash::Ed25519(ContractTz1Hash::try_from(baker_pk).unwrap())
+        );
+
+        std::fs::write(dir.join("password"), "wrong password").unwrap();
+        let mut passwords = Passwords::new(Some(dir.join("password")));
+        let result = CryptoService::read_key(&log, &dir, "baker", &mut passwords);
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(matches!(result, Err(ReadKeyError::WrongPassword { .. })));
+    }
+
+    #[test]
+    fn watermark_is_kept_across_restarts() {
+        let dir =
+            std::env::temp_dir().join(format!("tezedge-baker-restart-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("secret_keys"),
+            format!(
+                r#"[{{ "name": "baker", "value": "unencrypted:{}" }}]"#,
+                BAKER_KEY
+            ),
+        )
+        .unwrap();
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+        let chain_id = ChainId::try_from(vec![0x7a, 0x06, 0xa7, 0x70]).unwrap();
+        let block = SizedBytes::<100>([0; 100]);
+        let read_key =
+            || CryptoService::read_key(&log, &dir, "baker", &mut Passwords::new(None)).unwrap();
+
+        let mut crypto = read_key();
+        crypto.sign(0x11, &chain_id, &block, 5, 0, false).unwrap();
+        assert!(crypto.sign(0x11, &chain_id, &block, 5, 0, false).is_err());
+
+        // the baker restarted
+        let mut crypto = read_key();
+        assert_eq!(crypto.last_signed(&chain_id).unwrap().block, (5, 0));
+        assert!(crypto.sign(0x11, &chain_id, &block, 5, 0, false).is_err());
+        assert!(crypto.sign(0x11, &chain_id, &block, 4, 3, false).is_err());
+        crypto.sign(0x11, &chain_id, &block, 5, 1, false).unwrap();
+        crypto.sign(0x13, &chain_id, &block, 5, 0, false).unwrap();
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn forced_sign_keeps_watermark() {
+        let dir = std::env::temp_dir().join(format!("tezedge-baker-forced-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("secret_keys"),
+            format!(
+                r#"[{{ "name": "baker", "value": "unencrypted:{}" }}]"#,
+                BAKER_KEY
+            ),
+        )
+        .unwrap();
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+        let chain_id = ChainId::try_from(vec![0x7a, 0x06, 0xa7, 0x70]).unwrap();
+        let block = SizedBytes::<100>([0; 100]);
+        let mut crypto =
+            CryptoService::read_key(&log, &dir, "baker", &mut Passwords::new(None)).unwrap();
+
+        crypto.sign(0x11, &chain_id, &block, 5, 1, false).unwrap();
+        crypto.sign(0x11, &chain_id, &block, 3, 0, true).unwrap();
+        assert_eq!(crypto.last_signed(&chain_id).unwrap().block, (5, 1));
+        assert!(crypto.sign(0x11, &chain_id, &block, 4, 0, false).is_err());
+
+        // also after a restart
+        let mut crypto =
+            CryptoService::read_key(&log, &dir, "baker", &mut Passwords::new(None)).unwrap();
+        assert_eq!(crypto.last_signed(&chain_id).unwrap().block, (5, 1));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
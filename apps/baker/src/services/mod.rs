@@ -5,24 +5,33 @@ pub mod client;
 pub mod event;
 pub mod key;
 pub mod logger;
+pub mod protocol;
 pub mod timer;
+pub mod watermark;
 
 #[cfg(feature = "fuzzing")]
 mod operation_mutator;
 
-use std::{fs::File, path::Path, sync::mpsc, time::SystemTime};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::SystemTime,
+};
 
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 
 use redux_rs::TimeService;
 use tenderbake as tb;
+use tezos_messages::base::signature_public_key::SignaturePublicKeyHash;
 
 use crate::machine::BakerAction;
 
 pub struct Services {
     pub client: client::RpcClient,
-    pub crypto: key::CryptoService,
+    pub crypto: BTreeMap<SignaturePublicKeyHash, key::CryptoService>,
     pub log: slog::Logger,
     pub timer: timer::Timer,
 }
@@ -37,12 +46,13 @@ impl Services {
     pub fn new(
         endpoint: Url,
         base_dir: &Path,
-        baker: &str,
+        bakers: &[String],
+        password_filename: Option<PathBuf>,
     ) -> (Self, impl Iterator<Item = EventWithTime>) {
         let (tx, rx) = mpsc::channel();
 
         (
-            Self::new_with_id_and_log(endpoint, base_dir, baker, None, 0, tx),
+            Self::new_with_id_and_log(endpoint, base_dir, bakers, password_filename, None, 0, tx),
             rx.into_iter().map(|(_, event)| {
                 let unix_epoch = SystemTime::now()
                     .duration_since(SystemTime::UNIX_EPOCH)
@@ -56,7 +66,8 @@ impl Services {
     pub fn new_with_id_and_log(
         endpoint: Url,
         base_dir: &Path,
-        baker: &str,
+        bakers: &[String],
+        password_filename: Option<PathBuf>,
         log: Option<File>,
         id: u8,
         tx: mpsc::Sender<(u8, BakerAction)>,
@@ -65,9 +76,20 @@ impl Services {
             Some(f) => logger::file_logger(f),
             None => logger::main_logger(),
         };
+        // all delegates share the passwords, so the same password is asked only once
+        let mut passwords = key::Passwords::new(password_filename);
+        let mut crypto = BTreeMap::new();
+        for baker in bakers {
+            let service =
+                key::CryptoService::read_key(&log, base_dir, baker, &mut passwords).unwrap();
+            let pkh = service.public_key_hash().clone();
+            if crypto.insert(pkh.clone(), service).is_some() {
+                slog::warn!(log, "the delegate {pkh} is given more than once");
+            }
+        }
         Services {
             client: client::RpcClient::new(endpoint, id, tx.clone()),
-            crypto: key::CryptoService::read_key(&log, base_dir, baker).unwrap(),
+            crypto,
             log,
             timer: timer::Timer::spawn(id, tx),
         }
@@ -77,7 +99,7 @@ impl Services {
 pub trait BakerService {
     fn client(&self) -> &client::RpcClient;
 
-    fn crypto(&mut self) -> &mut key::CryptoService;
+    fn crypto(&mut self, delegate: &SignaturePublicKeyHash) -> Option<&mut key::CryptoService>;
 
     fn log(&self) -> &slog::Logger;
 
@@ -89,8 +111,8 @@ impl BakerService for Services {
         &self.client
     }
 
-    fn crypto(&mut self) -> &mut key::CryptoService {
-        &mut self.crypto
+    fn crypto(&mut self, delegate: &SignaturePublicKeyHash) -> Option<&mut key::CryptoService> {
+        self.crypto.get_mut(delegate)
     }
 
     fn log(&self) -> &slog::Logger {
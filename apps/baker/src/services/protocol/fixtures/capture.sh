@@ -0,0 +1,65 @@
+#!/usr/bin/env bash
+# Records the RPC fixtures of a protocol adapter from a Tezos node, see README.md
+#
+# usage: ./capture.sh <node url> <fixture name> <block level>
+#
+# The node must have the context of the block (an archive node for old blocks),
+# `curl` and `jq` are required.
+
+set -euo pipefail
+
+if [ $# -ne 3 ]; then
+    echo "usage: $0 <node url> <fixture name> <block level>" >&2
+    exit 1
+fi
+
+node=${1%/}
+dir=$(dirname "$0")/$2
+block=$3
+next=$((block + 1))
+
+rpc() {
+    curl -sSf "$node/$1"
+}
+
+mkdir -p "$dir"
+
+# an item of `monitor/heads/<chain_id>`, the shell header with the raw protocol data
+rpc "chains/main/blocks/$block/header" \
+    | jq --argjson protocol_data "$(rpc "chains/main/blocks/$block/header/protocol_data/raw")" \
+        '{hash, level, proto, predecessor, timestamp, validation_pass, operations_hash, fitness, context,
+          protocol_data: $protocol_data}' \
+    > "$dir/head.json"
+
+rpc "chains/main/blocks/$block/context/constants" > "$dir/constants.json"
+
+rpc "chains/main/blocks/$block/operations" > "$dir/operations.json"
+
+# the successor of the block preapplied again on top of it, the same request as the baker's
+timestamp=$(rpc "chains/main/blocks/$next/header" | jq -r .timestamp)
+rpc "chains/main/blocks/$next/operations" \
+    | jq --argjson protocol_data "$(rpc "chains/main/blocks/$next/header/protocol_data")" \
+        '{protocol_data: $protocol_data,
+          operations: [.[] | [.[] | {protocol, branch, contents: [.contents[] | del(.metadata)], signature}]]}' \
+    | curl -sSf -X POST -H 'Content-Type: application/json' --data @- \
+        "$node/chains/main/blocks/$block/helpers/preapply/block?sort=true&timestamp=$timestamp" \
+    | jq . \
+    > "$dir/preapply.json"
+
+# the values the adapter must read from the files above, taken from the decoded RPCs
+rpc "chains/main/blocks/$block/header/protocol_data" \
+    | jq --slurpfile constants "$dir/constants.json" --slurpfile operations "$dir/operations.json" \
+        '{payload_hash, payload_round,
+          consensus_committee_size: $constants[0].consensus_committee_size,
+          minimal_block_delay: ($constants[0].minimal_block_delay | tonumber),
+          operation_kinds: [$operations[0][][] | .contents[0].kind
+            | if . == "preendorsement" or . == "endorsement" then .
+              elif . == "proposals" or . == "ballot" then "votes"
+              elif . == "seed_nonce_revelation" or . == "double_preendorsement_evidence"
+                or . == "double_endorsement_evidence" or . == "double_baking_evidence"
+                or . == "activate_account" then "anonymous"
+              elif . == "failing_noop" then "unclassified"
+              else "managers" end]}' \
+    > "$dir/expected.json"
+
+echo "Recorded $dir from $node at level $block"
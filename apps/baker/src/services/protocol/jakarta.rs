@@ -0,0 +1,206 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crypto::hash::{
+    BlockHash, BlockPayloadHash, ContextHash, NonceHash, OperationListListHash, Signature,
+};
+use tezos_encoding::{
+    binary_reader::BinaryReaderError, enc::BinWriter, encoding::HasEncoding, nom::NomReader,
+    types::SizedBytes,
+};
+use tezos_messages::{p2p::encoding::fitness::Fitness, Timestamp};
+
+#[cfg(feature = "fuzzing")]
+use tezos_encoding::fuzzing::sizedbytes::SizedBytesMutator;
+
+use super::{
+    decode_header, HeaderDraft, ProtocolAdapter, ProtocolHeader, ProtocolHeaderFull,
+    ShellBlockShortHeader,
+};
+use crate::services::client::{LiquidityBakingToggleVote, RpcErrorInner};
+
+// signature watermark: 0x11 | chain_id
+#[derive(BinWriter, HasEncoding, NomReader, Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+pub struct ProtocolBlockHeaderJ {
+    pub payload_hash: BlockPayloadHash,
+    pub payload_round: i32,
+    #[cfg_attr(feature = "fuzzing", field_mutator(SizedBytesMutator<8>))]
+    pub proof_of_work_nonce: SizedBytes<8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed_nonce_hash: Option<NonceHash>,
+    pub liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
+    pub signature: Signature,
+}
+
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, HasEncoding, NomReader, BinWriter)]
+pub struct FullHeaderJ {
+    #[encoding(builtin = "Int32")]
+    pub level: i32,
+    pub proto: u8,
+    pub predecessor: BlockHash,
+    pub timestamp: Timestamp,
+    pub validation_pass: u8,
+    pub operations_hash: OperationListListHash,
+    pub fitness: Fitness,
+    pub context: ContextHash,
+    pub payload_hash: BlockPayloadHash,
+    pub payload_round: i32,
+    #[cfg_attr(feature = "fuzzing", field_mutator(tezos_encoding::fuzzing::sizedbytes::SizedBytesMutator<8>))]
+    pub proof_of_work_nonce: SizedBytes<8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed_nonce_hash: Option<NonceHash>,
+    pub liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
+    pub signature: Signature,
+}
+
+impl ProtocolHeaderFull for FullHeaderJ {
+    fn payload_round(&self) -> i32 {
+        self.payload_round
+    }
+
+    fn level(&self) -> i32 {
+        self.level
+    }
+
+    fn set_payload_hash(&mut self, payload_hash: BlockPayloadHash) {
+        self.payload_hash = payload_hash;
+    }
+
+    fn set_proof_of_work_nonce(&mut self, pow: SizedBytes<8>) {
+        self.proof_of_work_nonce = pow;
+    }
+}
+
+impl ProtocolHeader for ProtocolBlockHeaderJ {
+    type FullHeader = FullHeaderJ;
+
+    const NAME: &'static str = "PtJakart2xVj7pYXJBXrqHgd82rdkLey5ZeeGwDgPp9rhQUbSqY";
+
+    const POW_OFFSET: usize = mem::size_of::<BlockPayloadHash>() + mem::size_of::<i32>();
+
+    fn new(
+        payload_hash: BlockPayloadHash,
+        payload_round: i32,
+        seed_nonce_hash: Option<NonceHash>,
+        liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
+    ) -> Self {
+        ProtocolBlockHeaderJ {
+            payload_hash,
+            payload_round,
+            proof_of_work_nonce: SizedBytes(0x7985fafe1fb70300u64.to_be_bytes()),
+            seed_nonce_hash,
+            liquidity_baking_toggle_vote,
+            // fake signature
+            signature: Signature(vec![0; 64]),
+        }
+    }
+
+    fn payload_hash(&self) -> &BlockPayloadHash {
+        &self.payload_hash
+    }
+
+    fn payload_round(&self) -> i32 {
+        self.payload_round
+    }
+
+    fn proof_of_work_nonce(&self) -> SizedBytes<8> {
+        self.proof_of_work_nonce.clone()
+    }
+
+    fn full_header(
+        self,
+        shell_header: ShellBlockShortHeader,
+    ) -> Result<Self::FullHeader, RpcErrorInner> {
+        let ShellBlockShortHeader {
+            level,
+            proto,
+            predecessor,
+            timestamp,
+            validation_pass,
+            operations_hash,
+            fitness,
+            context,
+        } = shell_header;
+        let ProtocolBlockHeaderJ {
+            payload_hash,
+            payload_round,
+            proof_of_work_nonce,
+            seed_nonce_hash,
+            liquidity_baking_toggle_vote,
+            ..
+        } = self;
+        let timestamp = OffsetDateTime::parse(&timestamp, &Rfc3339)?
+            .unix_timestamp()
+            .into();
+        Ok(FullHeaderJ {
+            level,
+            proto,
+            predecessor,
+            timestamp,
+            validation_pass,
+            operations_hash,
+            fitness: {
+                let mut v = vec![];
+                for fitness_str in fitness {
+                    let item = hex::decode(fitness_str)?;
+                    v.push(item);
+                }
+                v.into()
+            },
+            context,
+            payload_hash,
+            payload_round,
+            proof_of_work_nonce,
+            seed_nonce_hash,
+            liquidity_baking_toggle_vote,
+            signature: Signature(vec![0; 64]),
+        })
+    }
+}
+
+/// Jakarta, `PtJakart2`, votes for the liquidity baking toggle.
+pub struct Jakarta;
+
+impl ProtocolAdapter for Jakarta {
+    fn name(&self) -> &'static str {
+        "Jakarta"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["j", "jakarta", "jakartanet"]
+    }
+
+    fn hash(&self) -> &'static str {
+        ProtocolBlockHeaderJ::NAME
+    }
+
+    fn pow_offset(&self) -> usize {
+        ProtocolBlockHeaderJ::POW_OFFSET
+    }
+
+    fn decode_header(&self, bytes: &[u8]) -> Result<(BlockPayloadHash, i32), BinaryReaderError> {
+        decode_header::<ProtocolBlockHeaderJ>(bytes)
+    }
+
+    fn header_draft(
+        &self,
+        payload_hash: BlockPayloadHash,
+        payload_round: i32,
+        seed_nonce_hash: Option<NonceHash>,
+        liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
+    ) -> Box<dyn HeaderDraft> {
+        Box::new(ProtocolBlockHeaderJ::new(
+            payload_hash,
+            payload_round,
+            seed_nonce_hash,
+            liquidity_baking_toggle_vote,
+        ))
+    }
+}
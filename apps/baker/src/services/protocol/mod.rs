@@ -0,0 +1,446 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Everything the baker knows about a particular economic protocol lives behind
+//! the `ProtocolAdapter` trait. To support a new protocol, add a module with its
+//! block header and an adapter, register the adapter in `REGISTRY` and record
+//! the RPC fixtures in `fixtures/<name>`.
+
+mod ithaca;
+mod jakarta;
+
+pub use self::{
+    ithaca::{Ithaca, ProtocolBlockHeaderI},
+    jakarta::{Jakarta, ProtocolBlockHeaderJ},
+};
+
+use std::{fmt, ops::Deref, str::FromStr, time::Duration};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crypto::hash::{BlockHash, BlockPayloadHash, ContextHash, NonceHash, OperationListListHash};
+use tezos_encoding::{binary_reader::BinaryReaderError, enc::BinWriter, types::SizedBytes};
+use tezos_messages::p2p::binary_message::BinaryRead;
+
+use super::{
+    client::{Constants, LiquidityBakingToggleVote, RpcErrorInner},
+    event::{OperationKind, OperationSimple},
+};
+
+static REGISTRY: &[&dyn ProtocolAdapter] = &[&Ithaca, &Jakarta];
+
+pub fn registry() -> &'static [&'static dyn ProtocolAdapter] {
+    REGISTRY
+}
+
+#[derive(Deserialize)]
+pub struct ShellBlockShortHeader {
+    pub level: i32,
+    pub proto: u8,
+    pub predecessor: BlockHash,
+    pub timestamp: String,
+    pub validation_pass: u8,
+    pub operations_hash: OperationListListHash,
+    pub fitness: Vec<String>,
+    pub context: ContextHash,
+}
+
+pub trait ProtocolHeaderFull
+where
+    Self: BinWriter + std::fmt::Debug,
+{
+    fn payload_round(&self) -> i32;
+
+    fn level(&self) -> i32;
+
+    fn set_payload_hash(&mut self, payload_hash: BlockPayloadHash);
+
+    fn set_proof_of_work_nonce(&mut self, pow: SizedBytes<8>);
+}
+
+pub trait ProtocolHeader {
+    type FullHeader: ProtocolHeaderFull + BinWriter;
+
+    const NAME: &'static str;
+
+    const POW_OFFSET: usize;
+
+    fn new(
+        payload_hash: BlockPayloadHash,
+        payload_round: i32,
+        seed_nonce_hash: Option<NonceHash>,
+        liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
+    ) -> Self;
+
+    fn payload_hash(&self) -> &BlockPayloadHash;
+
+    fn payload_round(&self) -> i32;
+
+    fn proof_of_work_nonce(&self) -> SizedBytes<8>;
+
+    fn full_header(
+        self,
+        shell_header: ShellBlockShortHeader,
+    ) -> Result<Self::FullHeader, RpcErrorInner>;
+}
+
+/// The protocol part of a block header which is not yet applied,
+/// it becomes a full header once the node returns the shell part.
+pub trait HeaderDraft {
+    /// The `protocol_data` object of the preapply request.
+    fn protocol_data(&self) -> Result<serde_json::Value, serde_json::Error>;
+
+    fn full_header(
+        self: Box<Self>,
+        shell_header: ShellBlockShortHeader,
+    ) -> Result<Box<dyn ProtocolHeaderFull>, RpcErrorInner>;
+}
+
+impl<H> HeaderDraft for H
+where
+    H: ProtocolHeader + Serialize,
+    H::FullHeader: 'static,
+{
+    fn protocol_data(&self) -> Result<serde_json::Value, serde_json::Error> {
+        let mut protocol_data = serde_json::to_value(self)?;
+        let protocol_block_header_obj = protocol_data
+            .as_object_mut()
+            .expect("`ProtocolBlockHeader` is a structure");
+        let proof_of_work_str = hex::encode(&self.proof_of_work_nonce());
+        protocol_block_header_obj.insert(
+            "proof_of_work_nonce".to_string(),
+            serde_json::Value::String(proof_of_work_str),
+        );
+        protocol_block_header_obj.insert(
+            "protocol".to_string(),
+            serde_json::Value::String(H::NAME.to_string()),
+        );
+        Ok(protocol_data)
+    }
+
+    fn full_header(
+        self: Box<Self>,
+        shell_header: ShellBlockShortHeader,
+    ) -> Result<Box<dyn ProtocolHeaderFull>, RpcErrorInner> {
+        Ok(Box::new(ProtocolHeader::full_header(*self, shell_header)?))
+    }
+}
+
+pub trait ProtocolAdapter: Sync {
+    /// The name stored in the baker state.
+    fn name(&self) -> &'static str;
+
+    /// The names accepted by the `--protocol` option.
+    fn aliases(&self) -> &'static [&'static str];
+
+    /// The base58 protocol hash.
+    fn hash(&self) -> &'static str;
+
+    /// The offset of `proof_of_work_nonce` in the protocol part of the header.
+    fn pow_offset(&self) -> usize;
+
+    /// Reads the payload hash and the payload round from the `protocol_data`
+    /// of a block header.
+    fn decode_header(&self, bytes: &[u8]) -> Result<(BlockPayloadHash, i32), BinaryReaderError>;
+
+    fn header_draft(
+        &self,
+        payload_hash: BlockPayloadHash,
+        payload_round: i32,
+        seed_nonce_hash: Option<NonceHash>,
+        liquidity_baking_toggle_vote: LiquidityBakingToggleVote,
+    ) -> Box<dyn HeaderDraft>;
+
+    /// Parses the response of `chains/main/blocks/head/context/constants`.
+    fn parse_constants(&self, constants: serde_json::Value) -> Result<Constants, RpcErrorInner> {
+        parse_tenderbake_constants(constants)
+    }
+
+    fn operation_kind(&self, op: &OperationSimple) -> Option<OperationKind> {
+        op.kind()
+    }
+}
+
+/// Helper for adapters whose header is a `ProtocolHeader`.
+pub fn decode_header<H>(bytes: &[u8]) -> Result<(BlockPayloadHash, i32), BinaryReaderError>
+where
+    H: ProtocolHeader + BinaryRead,
+{
+    let header = H::from_bytes(bytes)?;
+    Ok((header.payload_hash().clone(), header.payload_round()))
+}
+
+/// Constants as reported by the tenderbake protocols.
+pub fn parse_tenderbake_constants(
+    constants: serde_json::Value,
+) -> Result<Constants, RpcErrorInner> {
+    #[derive(Deserialize, Debug)]
+    struct ConstantsInner {
+        nonce_length: usize,
+        blocks_per_cycle: u32,
+        blocks_per_commitment: u32,
+        consensus_committee_size: u32,
+        minimal_block_delay: String,
+        delay_increment_per_round: String,
+        proof_of_work_threshold: String,
+    }
+
+    let ConstantsInner {
+        nonce_length,
+        blocks_per_cycle,
+        blocks_per_commitment,
+        consensus_committee_size,
+        minimal_block_delay,
+        delay_increment_per_round,
+        proof_of_work_threshold,
+    } = serde_json::from_value(constants)?;
+
+    Ok(Constants {
+        nonce_length,
+        blocks_per_cycle,
+        blocks_per_commitment,
+        consensus_committee_size,
+        proof_of_work_threshold: u64::from_be_bytes(
+            proof_of_work_threshold
+                .parse::<i64>()
+                .map_err(|err| RpcErrorInner::IntParse(err, "pow threshold".to_string()))?
+                .to_be_bytes(),
+        ),
+        minimal_block_delay: Duration::from_secs(
+            minimal_block_delay
+                .parse()
+                .map_err(|err| RpcErrorInner::IntParse(err, "minimal block delay".to_string()))?,
+        ),
+        delay_increment_per_round: Duration::from_secs(
+            delay_increment_per_round
+                .parse()
+                .map_err(|err| RpcErrorInner::IntParse(err, "delay increment".to_string()))?,
+        ),
+    })
+}
+
+/// The protocol the baker works with, one of the registered adapters.
+#[derive(Clone, Copy)]
+pub struct Protocol(&'static dyn ProtocolAdapter);
+
+impl Protocol {
+    pub fn from_hash(hash: &str) -> Option<Self> {
+        REGISTRY
+            .iter()
+            .find(|adapter| adapter.hash() == hash)
+            .map(|adapter| Protocol(*adapter))
+    }
+}
+
+impl Deref for Protocol {
+    type Target = dyn ProtocolAdapter;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl Default for Protocol {
+    fn default() -> Self {
+        Protocol(&Ithaca)
+    }
+}
+
+impl fmt::Debug for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        REGISTRY
+            .iter()
+            .find(|adapter| {
+                adapter.name().to_lowercase() == s
+                    || adapter.hash().to_lowercase() == s
+                    || adapter.aliases().contains(&s.as_str())
+            })
+            .map(|adapter| Protocol(*adapter))
+            .ok_or_else(|| "unknown protocol".to_string())
+    }
+}
+
+impl Serialize for Protocol {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Protocol {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use serde::Deserialize;
+
+    use crypto::hash::BlockPayloadHash;
+    use tezos_encoding::{enc::BinWriter, types::SizedBytes};
+
+    use super::{registry, Protocol, ShellBlockShortHeader};
+    use crate::services::{
+        client::LiquidityBakingToggleVote,
+        event::{OperationKind, OperationSimple},
+    };
+
+    fn fixture(name: &str, file: &str) -> serde_json::Value {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/services/protocol/fixtures")
+            .join(name)
+            .join(file);
+        let s = fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("cannot read {}: {err}", path.display()));
+        serde_json::from_str(&s).unwrap()
+    }
+
+    // an item of `monitor/heads/<chain_id>`
+    #[derive(Deserialize)]
+    struct Head {
+        protocol_data: String,
+    }
+
+    // the response of `helpers/preapply/block`
+    #[derive(Deserialize)]
+    struct Preapply {
+        shell_header: ShellBlockShortHeader,
+    }
+
+    #[derive(Deserialize)]
+    struct Expected {
+        payload_hash: BlockPayloadHash,
+        payload_round: i32,
+        consensus_committee_size: u32,
+        minimal_block_delay: u64,
+        // kinds of the operations in `operations.json`
+        operation_kinds: Vec<String>,
+    }
+
+    #[test]
+    fn protocol_names() {
+        for adapter in registry() {
+            let protocol = adapter.name().parse::<Protocol>().unwrap();
+            assert_eq!(protocol.hash(), adapter.hash());
+            let protocol = adapter.hash().parse::<Protocol>().unwrap();
+            assert_eq!(protocol.name(), adapter.name());
+            for alias in adapter.aliases() {
+                assert_eq!(alias.parse::<Protocol>().unwrap().name(), adapter.name());
+            }
+            let json = serde_json::to_string(&protocol).unwrap();
+            let protocol = serde_json::from_str::<Protocol>(&json).unwrap();
+            assert_eq!(protocol.name(), adapter.name());
+            assert_eq!(
+                Protocol::from_hash(adapter.hash()).unwrap().name(),
+                adapter.name()
+            );
+        }
+        assert!("unknown".parse::<Protocol>().is_err());
+    }
+
+    #[test]
+    fn adapters_against_fixtures() {
+        for adapter in registry() {
+            let name = adapter.name().to_lowercase();
+            let expected =
+                serde_json::from_value::<Expected>(fixture(&name, "expected.json")).unwrap();
+
+            // header encoding
+            let head = serde_json::from_value::<Head>(fixture(&name, "head.json")).unwrap();
+            let bytes = hex::decode(head.protocol_data).unwrap();
+            let (payload_hash, payload_round) = adapter.decode_header(&bytes).unwrap();
+            assert_eq!(payload_hash, expected.payload_hash, "{name}");
+            assert_eq!(payload_round, expected.payload_round, "{name}");
+
+            let draft = adapter.header_draft(
+                payload_hash.clone(),
+                payload_round,
+                None,
+                LiquidityBakingToggleVote::Off,
+            );
+            let protocol_data = draft.protocol_data().unwrap();
+            assert_eq!(protocol_data["protocol"], adapter.hash(), "{name}");
+            assert_eq!(
+                protocol_data["payload_hash"],
+                payload_hash.to_base58_check(),
+                "{name}"
+            );
+
+            let preapply =
+                serde_json::from_value::<Preapply>(fixture(&name, "preapply.json")).unwrap();
+            let level = preapply.shell_header.level;
+            let mut header = draft.full_header(preapply.shell_header).unwrap();
+            assert_eq!(header.level(), level, "{name}");
+            assert_eq!(header.payload_round(), payload_round, "{name}");
+            header.set_proof_of_work_nonce(SizedBytes([1, 2, 3, 4, 5, 6, 7, 8]));
+            let mut header_bytes = vec![];
+            header.bin_write(&mut header_bytes).unwrap();
+            // the protocol data follows the shell header, which ends with the context hash
+            let fitness_size = u32::from_be_bytes(header_bytes[78..82].try_into().unwrap());
+            let protocol_start = 82 + fitness_size as usize + 32;
+            let nonce_pos = protocol_start + adapter.pow_offset();
+            assert_eq!(
+                header_bytes[nonce_pos..(nonce_pos + 8)],
+                [1, 2, 3, 4, 5, 6, 7, 8],
+                "{name}"
+            );
+            let (decoded_hash, decoded_round) = adapter
+                .decode_header(&header_bytes[protocol_start..])
+                .unwrap();
+            assert_eq!(decoded_hash, payload_hash, "{name}");
+            assert_eq!(decoded_round, payload_round, "{name}");
+
+            // constants parsing
+            let constants = adapter
+                .parse_constants(fixture(&name, "constants.json"))
+                .unwrap();
+            assert_eq!(
+                constants.consensus_committee_size, expected.consensus_committee_size,
+                "{name}"
+            );
+            assert_eq!(
+                constants.minimal_block_delay.as_secs(),
+                expected.minimal_block_delay,
+                "{name}"
+            );
+
+            // operation kinds
+            let operations = serde_json::from_value::<Vec<Vec<OperationSimple>>>(fixture(
+                &name,
+                "operations.json",
+            ))
+            .unwrap();
+            let kinds = operations
+                .iter()
+                .flatten()
+                .map(|op| match adapter.operation_kind(op) {
+                    None => "unclassified",
+                    Some(OperationKind::Preendorsement(_)) => "preendorsement",
+                    Some(OperationKind::Endorsement(_)) => "endorsement",
+                    Some(OperationKind::Votes) => "votes",
+                    Some(OperationKind::Anonymous) => "anonymous",
+                    Some(OperationKind::Managers) => "managers",
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(kinds, expected.operation_kinds, "{name}");
+        }
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crypto::hash::ChainId;
+use tezos_messages::base::signature_public_key::SignaturePublicKeyHash;
+
+/// The highest `(level, round)` signed for each kind of consensus object.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastSigned {
+    pub preendorsement: (i32, i32),
+    pub endorsement: (i32, i32),
+    pub block: (i32, i32),
+}
+
+/// High watermarks of a key, stored in `base_dir` in a file per chain, so the baker
+/// never signs twice at the same level and round, even after a crash or a restart.
+pub struct Watermarks {
+    base_dir: PathBuf,
+    pkh: SignaturePublicKeyHash,
+    chains: BTreeMap<ChainId, LastSigned>,
+}
+
+impl Watermarks {
+    pub fn new(base_dir: &Path, pkh: SignaturePublicKeyHash) -> Self {
+        Watermarks {
+            base_dir: base_dir.to_path_buf(),
+            pkh,
+            chains: BTreeMap::new(),
+        }
+    }
+
+    fn path(&self, chain_id: &ChainId) -> PathBuf {
+        self.base_dir
+            .join(format!("watermark_{}_{}.json", self.pkh, chain_id))
+    }
+
+    /// Returns the watermark of the chain, reading it from the disk the first time.
+    /// A missing file means nothing was signed yet, a corrupted file is an error,
+    /// so the baker refuses to sign rather than risk a double signature.
+    pub fn get(&mut self, chain_id: &ChainId) -> Result<LastSigned, io::Error> {
+        if let Some(last_signed) = self.chains.get(chain_id) {
+            return Ok(*last_signed);
+        }
+        let last_signed = match File::open(self.path(chain_id)) {
+            Ok(file) => serde_json::from_reader(file)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => LastSigned::default(),
+            Err(err) => return Err(err),
+        };
+        self.chains.insert(chain_id.clone(), last_signed);
+        Ok(last_signed)
+    }
+
+    /// Atomically replaces the stored watermark of the chain, the new value is on the disk
+    /// when this function returns.
+    pub fn set(&mut self, chain_id: &ChainId, last_signed: LastSigned) -> Result<(), io::Error> {
+        let path = self.path(chain_id);
+        let path_swap = self
+            .base_dir
+            .join(format!(".watermark_{}_{}.json", self.pkh, chain_id));
+
+        let mut file = File::create(&path_swap)?;
+        serde_json::to_writer(&mut file, &last_signed)?;
+        file.flush()?;
+        file.sync_all()?;
+        fs::rename(&path_swap, &path)?;
+        // the rename itself is durable only once the directory is synced
+        File::open(&self.base_dir)?.sync_all()?;
+
+        self.chains.insert(chain_id.clone(), last_signed);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use crypto::hash::{ChainId, ContractTz1Hash};
+    use tezos_messages::base::signature_public_key::SignaturePublicKeyHash;
+
+    use super::{LastSigned, Watermarks};
+
+    #[test]
+    fn watermark_survives_restart() {
+        let dir =
+            std::env::temp_dir().join(format!("tezedge-baker-watermark-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pkh = SignaturePublicKeyHash::Ed25519(
+            ContractTz1Hash::from_base58_check("tz1TXkLKR4F4HUSCQKve7daPPLSxhZNx45px").unwrap(),
+        );
+        let chain_id = ChainId::try_from(vec![0x7a, 0x06, 0xa7, 0x70]).unwrap();
+        let other_chain_id = ChainId::try_from(vec![0x8e, 0xce, 0xda, 0x2f]).unwrap();
+
+        let last_signed = LastSigned {
+            preendorsement: (10, 1),
+            endorsement: (10, 0),
+            block: (9, 2),
+        };
+        let mut watermarks = Watermarks::new(&dir, pkh.clone());
+        assert_eq!(watermarks.get(&chain_id).unwrap(), LastSigned::default());
+        watermarks.set(&chain_id, last_signed).unwrap();
+
+        let mut watermarks = Watermarks::new(&dir, pkh.clone());
+        assert_eq!(watermarks.get(&chain_id).unwrap(), last_signed);
+        assert_eq!(
+            watermarks.get(&other_chain_id).unwrap(),
+            LastSigned::default()
+        );
+
+        // refuse to guess if the file is corrupted
+        std::fs::write(watermarks.path(&chain_id), "{").unwrap();
+        let mut watermarks = Watermarks::new(&dir, pkh);
+        assert!(watermarks.get(&chain_id).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
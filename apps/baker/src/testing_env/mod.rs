@@ -14,11 +14,12 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crypto::hash::{BlockHash, ContractTz1Hash};
+use crypto::hash::BlockHash;
 use reqwest::blocking::Client;
 use thiserror::Error;
 
 use redux_rs::Store;
+use tezos_messages::base::signature_public_key::SignaturePublicKeyHash;
 use tezos_messages::protocol::proto_012::operation::{
     InlinedEndorsementMempoolContents, InlinedPreendorsementContents,
 };
@@ -26,7 +27,7 @@ use tezos_messages::protocol::proto_012::operation::{
 use crate::{
     machine::{baker_effects, baker_reducer, Action, BakerAction, BakerState, BakerStateEjectable},
     services::event::Block,
-    LiquidityBakingToggleVote, Protocol, ProtocolBlockHeaderJ, RpcClient, Services,
+    LiquidityBakingToggleVote, Protocol, RpcClient, Services,
 };
 
 #[derive(Debug, Error)]
@@ -42,7 +43,7 @@ pub enum TestError {
 pub type Baker = Store<BakerStateEjectable, Services, Action>;
 
 pub mod accessor {
-    use crypto::hash::ContractTz1Hash;
+    use tezos_messages::base::signature_public_key::SignaturePublicKeyHash;
 
     use super::{Baker, BakerAction};
 
@@ -51,9 +52,9 @@ pub mod accessor {
         st.tb_state.level()
     }
 
-    pub fn key(baker: &Baker) -> ContractTz1Hash {
+    pub fn key(baker: &Baker) -> SignaturePublicKeyHash {
         let st = baker.state().as_ref().as_ref().unwrap().as_ref();
-        st.this.clone()
+        st.tb_config.map.ours[0].clone()
     }
 
     pub fn actions(baker: &Baker) -> &[BakerAction] {
@@ -68,7 +69,7 @@ pub mod accessor {
                 .map
                 .delegates
                 .get(&level)?
-                .get(&st.this)?
+                .get(&st.tb_config.map.ours[0])?
                 .0
                 .clone(),
         )
@@ -230,8 +231,8 @@ where
     let (tx, rx) = mpsc::channel();
     let client = RpcClient::new("http://localhost:18732".parse().unwrap(), 4, tx.clone());
     let chain_id = client.get_chain_id().unwrap();
-    let constants = client.get_constants().unwrap();
-    let protocol = Protocol::Jakarta;
+    let protocol = "jakarta".parse::<Protocol>().unwrap();
+    let constants = client.get_constants(protocol).unwrap();
     let liquidity_baking_toggle_vote = LiquidityBakingToggleVote::Off;
     let mut bakers = vec![];
     for id in 0..4 {
@@ -240,20 +241,18 @@ where
         let service = Services::new_with_id_and_log(
             "http://localhost:18732".parse().unwrap(),
             &dir.join("client"),
-            &format!("baker_{id}"),
+            &[format!("baker_{id}")],
+            None,
             Some(log),
             id,
             tx.clone(),
         );
-        service
-            .client
-            .monitor_heads::<ProtocolBlockHeaderJ>(&chain_id)
-            .unwrap();
-        let this = service.crypto.public_key_hash().clone();
+        service.client.monitor_heads(&chain_id, protocol).unwrap();
+        let ours = service.crypto.keys().cloned().collect();
         let state = BakerState::new(
             chain_id.clone(),
             constants.clone(),
-            this,
+            ours,
             protocol,
             liquidity_baking_toggle_vote,
         );
@@ -327,7 +326,7 @@ fn inspect_state(id: usize, baker: &Baker) {
 #[derive(Default)]
 pub struct BlockWatcher {
     blocks: BTreeMap<BlockHash, Block>,
-    observed: BTreeMap<ContractTz1Hash, BTreeSet<i32>>,
+    observed: BTreeMap<SignaturePublicKeyHash, BTreeSet<i32>>,
 }
 
 impl BlockWatcher {
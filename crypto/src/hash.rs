@@ -38,7 +38,14 @@ mod prefix_bytes {
     pub const PUBLIC_KEY_SECP256K1: [u8; 4] = [3, 254, 226, 86];
     pub const PUBLIC_KEY_P256: [u8; 4] = [3, 178, 139, 127];
     pub const SEED_ED25519: [u8; 4] = [43, 246, 78, 7];
+    pub const SECRET_KEY_SECP256K1: [u8; 4] = [17, 162, 224, 201];
+    pub const SECRET_KEY_P256: [u8; 4] = [16, 81, 238, 189];
+    pub const ENCRYPTED_SEED_ED25519: [u8; 5] = [7, 90, 60, 179, 41];
+    pub const ENCRYPTED_SECRET_KEY_SECP256K1: [u8; 5] = [9, 237, 241, 174, 150];
+    pub const ENCRYPTED_SECRET_KEY_P256: [u8; 5] = [9, 48, 57, 115, 171];
     pub const ED22519_SIGNATURE_HASH: [u8; 5] = [9, 245, 205, 134, 18];
+    pub const SECP256K1_SIGNATURE_HASH: [u8; 5] = [13, 115, 101, 19, 63];
+    pub const P256_SIGNATURE_HASH: [u8; 4] = [54, 240, 44, 52];
     pub const GENERIC_SIGNATURE_HASH: [u8; 3] = [4, 130, 43];
     pub const NONCE_HASH: [u8; 3] = [69, 220, 169];
     pub const OPERATION_LIST_HASH: [u8; 2] = [133, 233];
@@ -298,7 +305,14 @@ define_hash!(PublicKeyEd25519);
 define_hash!(PublicKeySecp256k1);
 define_hash!(PublicKeyP256);
 define_hash!(SeedEd25519);
+define_hash!(SecretKeySecp256k1);
+define_hash!(SecretKeyP256);
+define_hash!(EncryptedSeedEd25519);
+define_hash!(EncryptedSecretKeySecp256k1);
+define_hash!(EncryptedSecretKeyP256);
 define_hash!(Ed25519Signature);
+define_hash!(Secp256k1Signature);
+define_hash!(P256Signature);
 define_hash!(Signature);
 define_hash!(NonceHash);
 define_hash!(OperationListHash);
@@ -347,8 +361,22 @@ pub enum HashType {
     PublicKeyP256,
     // "\043\246\078\007" (* edsk(98) *)
     SeedEd25519,
+    // "\017\162\224\201" (* spsk(54) *)
+    SecretKeySecp256k1,
+    // "\016\081\238\189" (* p2sk(54) *)
+    SecretKeyP256,
+    // "\007\090\060\179\041" (* edesk(88) *)
+    EncryptedSeedEd25519,
+    // "\009\237\241\174\150" (* spesk(88) *)
+    EncryptedSecretKeySecp256k1,
+    // "\009\048\057\115\171" (* p2esk(88) *)
+    EncryptedSecretKeyP256,
     // "\009\245\205\134\018" (* edsig(99) *)
     Ed25519Signature,
+    // "\013\115\101\019\063" (* spsig1(99) *)
+    Secp256k1Signature,
+    // "\054\240\044\052" (* p2sig(98) *)
+    P256Signature,
     // "\004\130\043" (* sig(96) *)
     Signature,
     // "\069\220\169" (* nce(53) *)
@@ -384,7 +412,14 @@ impl HashType {
             HashType::PublicKeySecp256k1 => &PUBLIC_KEY_SECP256K1,
             HashType::PublicKeyP256 => &PUBLIC_KEY_P256,
             HashType::SeedEd25519 => &SEED_ED25519,
+            HashType::SecretKeySecp256k1 => &SECRET_KEY_SECP256K1,
+            HashType::SecretKeyP256 => &SECRET_KEY_P256,
+            HashType::EncryptedSeedEd25519 => &ENCRYPTED_SEED_ED25519,
+            HashType::EncryptedSecretKeySecp256k1 => &ENCRYPTED_SECRET_KEY_SECP256K1,
+            HashType::EncryptedSecretKeyP256 => &ENCRYPTED_SECRET_KEY_P256,
             HashType::Ed25519Signature => &ED22519_SIGNATURE_HASH,
+            HashType::Secp256k1Signature => &SECP256K1_SIGNATURE_HASH,
+            HashType::P256Signature => &P256_SIGNATURE_HASH,
             HashType::Signature => &GENERIC_SIGNATURE_HASH,
             HashType::NonceHash => &NONCE_HASH,
             HashType::OperationListHash => &OPERATION_LIST_HASH,
@@ -416,8 +451,15 @@ impl HashType {
             | HashType::Layer2Tz4Hash
             | HashType::SmartRollupHash => 20,
             HashType::PublicKeySecp256k1 | HashType::PublicKeyP256 => 33,
-            HashType::SeedEd25519 => 32,
-            HashType::Ed25519Signature | HashType::Signature => 64,
+            HashType::SeedEd25519 | HashType::SecretKeySecp256k1 | HashType::SecretKeyP256 => 32,
+            // 8 bytes of salt, 32 bytes of encrypted key and 16 bytes of MAC
+            HashType::EncryptedSeedEd25519
+            | HashType::EncryptedSecretKeySecp256k1
+            | HashType::EncryptedSecretKeyP256 => 56,
+            HashType::Ed25519Signature
+            | HashType::Secp256k1Signature
+            | HashType::P256Signature
+            | HashType::Signature => 64,
         }
     }
 
@@ -641,6 +683,149 @@ impl TryFrom<&Signature> for [u8; SIGNATURE_LENGTH] {
     }
 }
 
+/// Secret keys are encrypted by `tezos-client` with a key derived from the
+/// password and the salt, see `lib_signer_backends/encrypted.ml`.
+#[cfg(not(feature = "no_sodium"))]
+fn decrypt_secret_key(encrypted: &[u8], password: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    use sodiumoxide::crypto::secretbox;
+
+    const SALT_SIZE: usize = 8;
+    const PBKDF2_ROUNDS: u32 = 32768;
+
+    if encrypted.len() < SALT_SIZE {
+        return Err(CryptoError::InvalidKeySize {
+            expected: SALT_SIZE,
+            actual: encrypted.len(),
+        });
+    }
+    let (salt, encrypted) = encrypted.split_at(SALT_SIZE);
+    let mut key = secretbox::Key([0; secretbox::KEYBYTES]);
+    pbkdf2::pbkdf2::<hmac::Hmac<sha2::Sha512>>(password, salt, PBKDF2_ROUNDS, &mut key.0);
+    // the key is unique thanks to the salt, so the nonce is always zero
+    let nonce = secretbox::Nonce([0; secretbox::NONCEBYTES]);
+    secretbox::open(encrypted, &nonce, &key).map_err(|()| CryptoError::FailedToDecrypt)
+}
+
+#[cfg(not(feature = "no_sodium"))]
+impl EncryptedSeedEd25519 {
+    pub fn decrypt(&self, password: &[u8]) -> Result<SeedEd25519, CryptoError> {
+        let seed = decrypt_secret_key(&self.0, password)?;
+        SeedEd25519::from_vec(seed).map_err(|_| CryptoError::FailedToDecrypt)
+    }
+}
+
+#[cfg(not(feature = "no_sodium"))]
+impl EncryptedSecretKeySecp256k1 {
+    pub fn decrypt(&self, password: &[u8]) -> Result<SecretKeySecp256k1, CryptoError> {
+        let secret_key = decrypt_secret_key(&self.0, password)?;
+        SecretKeySecp256k1::from_vec(secret_key).map_err(|_| CryptoError::FailedToDecrypt)
+    }
+}
+
+#[cfg(not(feature = "no_sodium"))]
+impl EncryptedSecretKeyP256 {
+    pub fn decrypt(&self, password: &[u8]) -> Result<SecretKeyP256, CryptoError> {
+        let secret_key = decrypt_secret_key(&self.0, password)?;
+        SecretKeyP256::from_vec(secret_key).map_err(|_| CryptoError::FailedToDecrypt)
+    }
+}
+
+/// By default p256 crate uses sha256 to get a 32-bit hash from input message.
+/// Here though, the input data is already a Tezos hash of proper size.
+/// So we need to use identity digest.
+#[derive(Default, Clone)]
+struct NoHash([u8; CRYPTO_KEY_SIZE]);
+
+impl p256::ecdsa::signature::digest::Update for NoHash {
+    fn update(&mut self, data: impl AsRef<[u8]>) {
+        let data = data.as_ref();
+        let end = std::cmp::min(data.len(), self.0.len());
+        self.0[..end].copy_from_slice(&data[..end]);
+    }
+}
+
+impl p256::ecdsa::signature::digest::FixedOutput for NoHash {
+    type OutputSize = p256::elliptic_curve::consts::U32;
+
+    fn finalize_into(
+        self,
+        out: &mut p256::elliptic_curve::generic_array::GenericArray<u8, Self::OutputSize>,
+    ) {
+        out.copy_from_slice(&self.0[..]);
+    }
+
+    fn finalize_into_reset(
+        &mut self,
+        out: &mut p256::elliptic_curve::generic_array::GenericArray<u8, Self::OutputSize>,
+    ) {
+        out.copy_from_slice(&self.0[..]);
+    }
+}
+
+impl p256::ecdsa::signature::digest::Reset for NoHash {
+    fn reset(&mut self) {}
+}
+
+impl SecretKeySecp256k1 {
+    fn secret_key(&self) -> Result<libsecp256k1::SecretKey, CryptoError> {
+        libsecp256k1::SecretKey::parse_slice(&self.0).map_err(|e| CryptoError::InvalidKey {
+            reason: e.to_string(),
+        })
+    }
+
+    pub fn public_key(&self) -> Result<PublicKeySecp256k1, CryptoError> {
+        let pk = libsecp256k1::PublicKey::from_secret_key(&self.secret_key()?);
+        Ok(PublicKeySecp256k1(pk.serialize_compressed().to_vec()))
+    }
+
+    /// Signs the blake2b digest of `data`, the signature has low `s`.
+    pub fn sign<T, I>(&self, data: T) -> Result<Signature, CryptoError>
+    where
+        T: IntoIterator<Item = I>,
+        I: AsRef<[u8]>,
+    {
+        let digest = blake2b::digest_all(data, 32).map_err(|_| CryptoError::InvalidMessage)?;
+        let msg =
+            libsecp256k1::Message::parse_slice(&digest).map_err(|_| CryptoError::InvalidMessage)?;
+        let (signature, _) = libsecp256k1::sign(&msg, &self.secret_key()?);
+        Ok(Signature(signature.serialize().to_vec()))
+    }
+}
+
+impl SecretKeyP256 {
+    fn secret_key(&self) -> Result<p256::SecretKey, CryptoError> {
+        p256::SecretKey::from_bytes(&self.0).map_err(|e| CryptoError::InvalidKey {
+            reason: e.to_string(),
+        })
+    }
+
+    pub fn public_key(&self) -> Result<PublicKeyP256, CryptoError> {
+        let pk = p256::ecdsa::VerifyingKey::from(self.secret_key()?.public_key());
+        Ok(PublicKeyP256(pk.to_encoded_point(true).as_bytes().to_vec()))
+    }
+
+    /// Signs the blake2b digest of `data`.
+    pub fn sign<T, I>(&self, data: T) -> Result<Signature, CryptoError>
+    where
+        T: IntoIterator<Item = I>,
+        I: AsRef<[u8]>,
+    {
+        use ecdsa::hazmat::{FromDigest, SignPrimitive};
+        use sha2::{Digest, Sha256};
+
+        let digest = blake2b::digest_all(data, 32).map_err(|_| CryptoError::InvalidMessage)?;
+        let scalar = self.secret_key()?.to_secret_scalar();
+        // The message is signed as is (see `NoHash`), but the deterministic
+        // nonce (RFC 6979) must be derived with a real hash function.
+        let k = ecdsa::rfc6979::generate_k(&scalar, Sha256::new().chain(&digest), &[]);
+        let msg = p256::Scalar::from_digest(NoHash::default().chain(&digest));
+        let signature = scalar
+            .try_sign_prehashed(&**k, &msg)
+            .map_err(|e| CryptoError::AlgorithmError(e.to_string()))?;
+        Ok(Signature(signature.as_ref().to_vec()))
+    }
+}
+
 impl PublicKeySignatureVerifier for PublicKeyEd25519 {
     type Signature = Signature;
     type Error = CryptoError;
@@ -695,49 +880,7 @@ impl PublicKeySignatureVerifier for PublicKeyP256 {
 
     /// Verifies the correctness of `bytes` signed by P256 as the `signature`.
     fn verify_signature(&self, signature: &Signature, bytes: &[u8]) -> Result<bool, Self::Error> {
-        use p256::{
-            ecdsa::signature::{
-                digest::{FixedOutput, Reset, Update},
-                DigestVerifier,
-            },
-            elliptic_curve::consts::U32,
-        };
-
-        // By default p256 crate uses sha256 to get a 32-bit hash from input message.
-        // Here though, the input data is already a Tezos hash of proper size.
-        // So we need to use identity digest.
-        #[derive(Default, Clone)]
-        struct NoHash([u8; CRYPTO_KEY_SIZE]);
-
-        impl Update for NoHash {
-            fn update(&mut self, data: impl AsRef<[u8]>) {
-                let data = data.as_ref();
-                let end = std::cmp::min(data.len(), self.0.len());
-                self.0[..end].copy_from_slice(&data[..end]);
-            }
-        }
-
-        impl FixedOutput for NoHash {
-            type OutputSize = U32;
-
-            fn finalize_into(
-                self,
-                out: &mut p256::elliptic_curve::generic_array::GenericArray<u8, Self::OutputSize>,
-            ) {
-                out.copy_from_slice(&self.0[..]);
-            }
-
-            fn finalize_into_reset(
-                &mut self,
-                out: &mut p256::elliptic_curve::generic_array::GenericArray<u8, Self::OutputSize>,
-            ) {
-                out.copy_from_slice(&self.0[..]);
-            }
-        }
-
-        impl Reset for NoHash {
-            fn reset(&mut self) {}
-        }
+        use p256::ecdsa::signature::{digest::Update, DigestVerifier};
 
         let pk = p256::ecdsa::VerifyingKey::from_sec1_bytes(&self.0)
             .map_err(|_| CryptoError::InvalidPublicKey)?;
@@ -1254,6 +1397,10 @@ mod tests {
 
         test!(ed25519_sig, Ed25519Signature, ["edsigtXomBKi5CTRf5cjATJWSyaRvhfYNHqSUGrn4SdbYRcGwQrUGjzEfQDTuqHhuA8b2d8NarZjz8TRf65WkpQmo423BtomS8Q"]);
 
+        test!(secp256k1_sig, Secp256k1Signature, ["spsig1fJWmkWTSFeayPrYU3RomkHGpRNpgnfd11c8ezKJTi4ijUMNAys89hMV5s9QLWuBtEnAx5hJG5mP6jMbUQ1u3iR3Ho41ku"]);
+
+        test!(p256_sig, P256Signature, ["p2sigvo4Hmzm4NcV4A7YkcUBuM4gHzFUspTLjtFfGbcRx2bHqwyLJnqqRr43ggWqWZ2u894YEC5cASZyNqLQh7nbZmaGha6YRV"]);
+
         test!(generic_sig, Signature, ["sigNCaj9CnmD94eZH9C7aPPqBbVCJF72fYmCFAXqEbWfqE633WNFWYQJFnDUFgRUQXR8fQ5tKSfJeTe6UAi75eTzzQf7AEc1"]);
 
         test!(
@@ -1266,6 +1413,90 @@ mod tests {
         );
     }
 
+    #[test]
+    fn secp256k1_secret_key_sign() -> Result<(), anyhow::Error> {
+        let sk = SecretKeySecp256k1::from_base58_check(
+            "spsk2m4hr5fFQ5yMBF1LNY3DArjXq1CpF9DdXfdQSxwxGbNFgYvBp5",
+        )?;
+        let pk = sk.public_key()?;
+        assert_eq!(
+            pk.to_base58_check(),
+            "sppk7bB6th7b26yLBqwkVGgptfFeWLGEKQKoTWZdB5WMi4aezXMaP3p"
+        );
+        assert_eq!(
+            ContractTz2Hash::try_from(pk.clone())?.to_base58_check(),
+            "tz29i8EQgNQiZbCNbyMDG3Cy5m6jJbDoPsUX"
+        );
+
+        let data = [&[0x11][..], &b"block header"[..]];
+        let signature = sk.sign(data)?;
+        assert_eq!(signature, sk.sign(data)?);
+        let digest = blake2b::digest_all(data, 32)?;
+        assert!(pk.verify_signature(&signature, &digest)?);
+        assert!(!pk.verify_signature(&signature, &[0; 32])?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn p256_secret_key_sign() -> Result<(), anyhow::Error> {
+        let sk = SecretKeyP256::from_base58_check(
+            "p2sk3mdKB2zYhubQh56EL1JWMkewAaBiLgwUp2VfZKHuNyhpbyJVZT",
+        )?;
+        let pk = sk.public_key()?;
+        assert_eq!(
+            pk.to_base58_check(),
+            "p2pk6792eyMRyA6jjd8u6E84EgD8bj2ZKJ5mpvjWVxaVoxHnTqU3Qqr"
+        );
+        assert_eq!(
+            ContractTz3Hash::try_from(pk.clone())?.to_base58_check(),
+            "tz3Nu1paiRWyXPPs1gjWWLQUyH4DfoPTiHNF"
+        );
+
+        let data = [&[0x13][..], &b"endorsement"[..]];
+        let signature = sk.sign(data)?;
+        assert_eq!(signature, sk.sign(data)?);
+        let digest = blake2b::digest_all(data, 32)?;
+        assert!(pk.verify_signature(&signature, &digest)?);
+        assert!(!pk.verify_signature(&signature, &[0; 32])?);
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_sodium"))]
+    #[test]
+    fn decrypt_secret_keys() -> Result<(), anyhow::Error> {
+        let seed = EncryptedSeedEd25519::from_base58_check(
+            "edesk1cSwttzXwr2SmVWMNqwoZH4rUP2aQGhNPWD1iFb3Lg8r6RtrMpeW9zK4MMJ7pK32kZQ8gBifJxBDDqpifVe",
+        )?;
+        assert_eq!(
+            seed.decrypt(b"tezedge")?.to_base58_check(),
+            "edsk31vznjHSSpGExDMHYASz45VZqXN4DPxvsa4hAyY8dHM28cZzp6"
+        );
+        assert!(matches!(
+            seed.decrypt(b"wrong password"),
+            Err(CryptoError::FailedToDecrypt)
+        ));
+
+        let sk = EncryptedSecretKeySecp256k1::from_base58_check(
+            "spesk1mjokzuG8NLtmNGXrzTwSGU6cS4FGW4tPNyEpEFG8Ds5vcaf7XxJNaxsgdc6PrNe7FBKoBN3EmBVyjaNBVb",
+        )?;
+        assert_eq!(
+            sk.decrypt(b"tezedge")?.to_base58_check(),
+            "spsk2m4hr5fFQ5yMBF1LNY3DArjXq1CpF9DdXfdQSxwxGbNFgYvBp5"
+        );
+
+        let sk = EncryptedSecretKeyP256::from_base58_check(
+            "p2esk26ZSbHoXgJUEPLBQkAQwwoQdmbbbDEm5UJEHGBpgMSpBP6vdkS2kqsqM7JK1rRbTJLcM8v3ebn5riLWG6rv",
+        )?;
+        assert_eq!(
+            sk.decrypt(b"tezedge")?.to_base58_check(),
+            "p2sk3mdKB2zYhubQh56EL1JWMkewAaBiLgwUp2VfZKHuNyhpbyJVZT"
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn block_payload_hash() {
         let operation_0 = "oom9d3PpjjaMzgg9mZ1pDrF8kjdyzDb41Bd2XE6Y3kRtFHXLku3";
@@ -1,22 +1,47 @@
 // Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use edgekv::datastore::DataStore;
+use edgekv::repair::{repair, RepairOptions};
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
+fn database_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("DATABASE")
+        .short("p")
+        .long("path")
+        .help("Database directory")
+        .required(true)
+        .takes_value(true)
+}
+
 fn main() {
     let matches = App::new("Database CRC checker")
-        .arg(
-            Arg::with_name("DATABASE")
-                .short("p")
-                .long("path")
-                .help("Database directory")
-                .required(true)
-                .takes_value(true),
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .arg(database_arg())
+        .subcommand(
+            SubCommand::with_name("repair")
+                .about("Verifies the crc of every entry and repairs the damaged files")
+                .arg(database_arg())
+                .arg(
+                    Arg::with_name("dry-run")
+                        .long("dry-run")
+                        .help("Only report the damage, without changing any file"),
+                )
+                .arg(
+                    Arg::with_name("quarantine")
+                        .long("quarantine")
+                        .help("Directory the corrupt entries are copied to")
+                        .takes_value(true),
+                ),
         )
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("repair") {
+        return repair_database(matches);
+    }
+
     let database_url = matches
         .value_of("DATABASE")
         .expect("Provide database directory");
@@ -45,3 +70,49 @@ fn main() {
         total_duration.as_micros() / read_count as u128
     );
 }
+
+fn repair_database(matches: &ArgMatches) {
+    let database_url = matches
+        .value_of("DATABASE")
+        .expect("Provide database directory");
+    let options = RepairOptions {
+        dry_run: matches.is_present("dry-run"),
+        quarantine_dir: matches.value_of("quarantine").map(PathBuf::from),
+    };
+
+    let report = match repair(database_url, &options) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Repair of {} failed: {:?}", database_url, e);
+            std::process::exit(1);
+        }
+    };
+
+    for entry in report.damaged_entries.iter() {
+        println!(
+            "{:?} in {} at {} ({} bytes)",
+            entry.damage,
+            entry.file.display(),
+            entry.position,
+            entry.size
+        );
+    }
+    for file in report.rebuilt_hint_files.iter() {
+        println!("Rebuilt hint file {}", file.display());
+    }
+    for file in report.quarantined_files.iter() {
+        println!("Quarantined {}", file.display());
+    }
+    println!(
+        "Checked [{}] files, [{}] valid entries, [{}] damaged entries, truncated {} bytes",
+        report.checked_files,
+        report.valid_entries,
+        report.damaged_entries.len(),
+        report.truncated_bytes
+    );
+
+    if options.dry_run && !report.is_clean() {
+        println!("Dry run, nothing was changed");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::time::Duration;
+
+/// Decides when the background worker merges the data files, see
+/// [crate::datastore::DataStore::merge].
+///
+/// Only the files which are not written to anymore are considered, the
+/// active file is merged once it is split.
+#[derive(Copy, Clone, Debug)]
+pub struct CompactionPolicy {
+    /// Merge once at least this fraction of the data files is dead,
+    /// i.e. overwritten or deleted entries...
+    pub dead_bytes_ratio: f64,
+    /// ...and there are at least this many dead bytes, so that small
+    /// stores are not rewritten over and over.
+    pub min_dead_bytes: u64,
+    /// Merge once there are more data files than this, regardless of
+    /// the dead bytes.
+    pub max_data_files: usize,
+    /// How often the policy is evaluated.
+    pub check_interval: Duration,
+}
+
+const DEFAULT_DEAD_BYTES_RATIO: f64 = 0.5;
+const DEFAULT_MIN_DEAD_BYTES: u64 = 32 * 1024 * 1024;
+const DEFAULT_MAX_DATA_FILES: usize = 1024;
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        Self {
+            dead_bytes_ratio: DEFAULT_DEAD_BYTES_RATIO,
+            min_dead_bytes: DEFAULT_MIN_DEAD_BYTES,
+            max_data_files: DEFAULT_MAX_DATA_FILES,
+            check_interval: DEFAULT_CHECK_INTERVAL,
+        }
+    }
+}
+
+impl CompactionPolicy {
+    pub fn should_merge(&self, stats: &FileStats) -> bool {
+        if stats.data_files == 0 {
+            return false;
+        }
+        stats.data_files > self.max_data_files
+            || (stats.dead_bytes() >= self.min_dead_bytes
+                && stats.dead_bytes_ratio() >= self.dead_bytes_ratio)
+    }
+}
+
+/// Sizes of the data files which are not written to anymore.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileStats {
+    pub data_files: usize,
+    pub total_bytes: u64,
+    /// Bytes of the entries which are still reachable through the keys.
+    pub live_bytes: u64,
+}
+
+impl FileStats {
+    pub fn dead_bytes(&self) -> u64 {
+        self.total_bytes.saturating_sub(self.live_bytes)
+    }
+
+    pub fn dead_bytes_ratio(&self) -> f64 {
+        if self.total_bytes == 0 {
+            0.0
+        } else {
+            self.dead_bytes() as f64 / self.total_bytes as f64
+        }
+    }
+}
+
+/// Totals of the merges done since the store was opened.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MergeStats {
+    pub merges: u64,
+    pub merged_files: u64,
+    /// Size of the merged files minus the size of the files they were
+    /// merged into.
+    pub reclaimed_bytes: u64,
+    pub duration: Duration,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_merge() {
+        let policy = CompactionPolicy {
+            dead_bytes_ratio: 0.5,
+            min_dead_bytes: 100,
+            max_data_files: 10,
+            check_interval: Duration::from_secs(1),
+        };
+        let stats = |data_files, total_bytes, live_bytes| FileStats {
+            data_files,
+            total_bytes,
+            live_bytes,
+        };
+
+        assert!(!policy.should_merge(&FileStats::default()));
+        assert!(!policy.should_merge(&stats(2, 1000, 600)));
+        assert!(policy.should_merge(&stats(2, 1000, 500)));
+        // ratio is reached, but there is too little to reclaim
+        assert!(!policy.should_merge(&stats(2, 150, 60)));
+        assert!(policy.should_merge(&stats(11, 1000, 1000)));
+    }
+}
@@ -3,19 +3,22 @@
 
 #![allow(clippy::ptr_arg)]
 
+use crate::compaction::{FileStats, MergeStats};
 use crate::datastore::DataIndex::Persisted;
 use crate::errors::EdgeKVError;
 use crate::file_ops::{
-    create_new_file_pair, fetch_file_pairs, get_lock_file, ActiveFilePair, FilePair, Index,
+    create_file_pair, create_new_file_pair, fetch_file_pairs, get_lock_file, merged_file_id,
+    ActiveFilePair, FilePair, Index,
 };
-use crate::schema::{DataEntry, Decoder, Encoder};
+use crate::schema::{DataEntry, Decoder, Encoder, DATA_ENTRY_HEADER_SIZE};
 use fs2::FileExt;
 
 use std::collections::{BTreeMap, HashMap};
 use std::fs::{File, OpenOptions};
 use std::ops::{Add, RangeBounds};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::Instant;
 
 use crate::Result;
 use std::io::{BufReader, Write};
@@ -58,24 +61,53 @@ impl KeyDirEntry {
             + std::mem::size_of_val(&self.data_entry_position)
             + self.file_id.len()
     }
+
+    /// Size of the encoded data entry in the data file.
+    pub fn data_entry_size(&self) -> u64 {
+        DATA_ENTRY_HEADER_SIZE + self.key_size + self.value_size
+    }
 }
 
 pub type IVec = Arc<Vec<u8>>;
 
 pub struct KeysDir {
     keys: RwLock<BTreeMap<IVec, DataIndex>>,
+    /// Bytes of the entries each data file holds for the `keys`, the rest
+    /// of the file is dead. Only changed with the `keys` write lock held.
+    live_bytes: Mutex<HashMap<String, u64>>,
 }
 
 impl KeysDir {
+    /// Accounts for the `removed` index being replaced with the `added` entry.
+    fn account(&self, removed: Option<&DataIndex>, added: Option<&KeyDirEntry>) -> Result<()> {
+        let mut live_bytes = self
+            .live_bytes
+            .lock()
+            .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?;
+        if let Some(Persisted(entry)) = removed {
+            if let Some(bytes) = live_bytes.get_mut(&entry.file_id) {
+                *bytes = bytes.saturating_sub(entry.data_entry_size());
+                if *bytes == 0 {
+                    live_bytes.remove(&entry.file_id);
+                }
+            }
+        }
+        if let Some(entry) = added {
+            *live_bytes.entry(entry.file_id.clone()).or_default() += entry.data_entry_size();
+        }
+        Ok(())
+    }
+
     pub fn insert(&self, key: IVec, value: KeyDirEntry) -> Result<()> {
         let mut keys_dir_writer = self
             .keys
             .write()
             .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?;
 
+        self.account(None, Some(&value))?;
         let index = DataIndex::Persisted(value);
-        keys_dir_writer.insert(key, index);
-        Ok(())
+        let removed = keys_dir_writer.insert(key, index);
+        self.account(removed.as_ref(), None)
     }
 
     pub fn insert_bulk(&self, bulk: BTreeMap<Vec<u8>, KeyDirEntry>) -> Result<()> {
@@ -83,10 +115,11 @@ impl KeysDir {
             .keys
             .write()
             .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?;
-        keys_dir_writer.extend(
-            bulk.iter()
-                .map(|(k, v)| (Arc::new(k.clone()), DataIndex::Persisted(v.clone()))),
-        );
+        for (k, v) in bulk {
+            self.account(None, Some(&v))?;
+            let removed = keys_dir_writer.insert(Arc::new(k), DataIndex::Persisted(v));
+            self.account(removed.as_ref(), None)?;
+        }
         Ok(())
     }
 
@@ -96,8 +129,31 @@ impl KeysDir {
             .write()
             .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?;
         let index = DataIndex::InBuffer;
-        keys_dir_writer.insert(key, index);
-        Ok(())
+        let removed = keys_dir_writer.insert(key, index);
+        self.account(removed.as_ref(), None)
+    }
+
+    /// Points the `key` to the `to` entry, only if it still points to
+    /// the `from` entry. Returns whether the key was relocated.
+    pub fn relocate(&self, key: &Vec<u8>, from: &KeyDirEntry, to: KeyDirEntry) -> Result<bool> {
+        let mut keys_dir_writer = self
+            .keys
+            .write()
+            .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?;
+        let index = match keys_dir_writer.get_mut(key) {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        let unchanged = matches!(index, Persisted(current)
+            if current.file_id == from.file_id
+                && current.data_entry_position == from.data_entry_position);
+        if !unchanged {
+            return Ok(false);
+        }
+        self.account(None, Some(&to))?;
+        let removed = std::mem::replace(index, Persisted(to));
+        self.account(Some(&removed), None)?;
+        Ok(true)
     }
 
     pub fn remove(&self, key: &Vec<u8>) -> Result<()> {
@@ -105,8 +161,8 @@ impl KeysDir {
             .keys
             .write()
             .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?;
-        keys_dir_writer.remove(key);
-        Ok(())
+        let removed = keys_dir_writer.remove(key);
+        self.account(removed.as_ref(), None)
     }
 
     pub fn clear(&self) -> Result<()> {
@@ -115,9 +171,20 @@ impl KeysDir {
             .write()
             .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?;
         keys_dir_writer.clear();
+        self.live_bytes
+            .lock()
+            .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?
+            .clear();
         Ok(())
     }
 
+    pub fn live_bytes(&self, file_id: &str) -> u64 {
+        match self.live_bytes.lock() {
+            Ok(live_bytes) => live_bytes.get(file_id).copied().unwrap_or(0),
+            Err(_) => 0,
+        }
+    }
+
     pub fn keys(&self) -> Vec<Arc<Vec<u8>>> {
         let keys_dir_reader = match self.keys.read() {
             Ok(rdr) => rdr,
@@ -201,6 +268,7 @@ impl KeysDir {
     pub fn new(file_pairs: &BTreeMap<String, FilePair>) -> Result<Self> {
         let keys_dir = Self {
             keys: Default::default(),
+            live_bytes: Default::default(),
         };
         for fp in file_pairs.values() {
             fp.fetch_hint_entries(&keys_dir)?;
@@ -244,6 +312,17 @@ impl IndexDir {
         indexes.insert(file_pair.file_id(), file_pair.to_index()?);
         Ok(())
     }
+
+    pub fn remove(&self, file_ids: &[String]) -> Result<()> {
+        let mut indexes = self
+            .indexes
+            .write()
+            .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?;
+        for file_id in file_ids {
+            indexes.remove(file_id);
+        }
+        Ok(())
+    }
 }
 
 pub struct DataStore {
@@ -256,6 +335,8 @@ pub struct DataStore {
     double_buffer: HashMap<Vec<u8>, DataEntry>,
     buffer_size: RwLock<usize>,
     cache: RwLock<LruCache<Arc<Vec<u8>>, Arc<Vec<u8>>>>,
+    merge_lock: Mutex<()>,
+    merge_stats: Mutex<MergeStats>,
 }
 
 pub fn fetch_double_buffer_file(
@@ -293,6 +374,8 @@ impl DataStore {
             double_buffer,
             buffer_size: RwLock::new(0),
             cache: RwLock::new(LruCache::new(24_000)),
+            merge_lock: Mutex::new(()),
+            merge_stats: Default::default(),
         };
         instance.lock()?;
         Ok(instance)
@@ -363,6 +446,9 @@ impl DataStore {
             return Ok(Some(value.to_vec()));
         }
 
+        // locked before the keys are looked up, so that the merge can't
+        // remove the file the key points to in between
+        let indexes_read_lock = self.index_dir.indexes()?;
         let key_dir_entry = if let Some(entry) = self.keys_dir.get(key) {
             entry
         } else {
@@ -372,7 +458,6 @@ impl DataStore {
             return Ok(None);
         };
 
-        let indexes_read_lock = self.index_dir.indexes()?;
         let index = indexes_read_lock
             .get(&key_dir_entry.file_id)
             .ok_or(EdgeKVError::CorruptData)?;
@@ -485,34 +570,113 @@ impl DataStore {
         self.keys_dir.prefix(prefix)
     }
 
-    pub fn merge(&self) -> Result<()> {
-        let active_file = self
+    /// Sizes of the data files which are not written to anymore.
+    pub fn file_stats(&self) -> Result<FileStats> {
+        let active_file_id = self
             .active_file
             .read()
-            .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?;
-        let merged_file_pair = ActiveFilePair::from(create_new_file_pair(self.dir.as_path())?)?;
-        let mut mark_for_removal = Vec::new();
+            .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?
+            .file_id();
 
-        for (_, index) in self.index_dir.indexes()?.iter() {
-            if index.file_id() == active_file.file_id() {
+        let mut stats = FileStats::default();
+        for (file_id, index) in self.index_dir.indexes()?.iter() {
+            if *file_id == active_file_id {
                 continue;
             }
-            let hints = index.get_hints()?;
-            for hint in hints {
-                if let Some(keys_dir_entry) = self.keys_dir.get(&hint.key()) {
-                    if keys_dir_entry.file_id == index.file_id() {
-                        let data_entry = index.read(hint.data_entry_position(), hint.size())?;
-                        let key_entry = merged_file_pair.write(&data_entry, &self.keys_dir)?;
-                        self.keys_dir.insert(Arc::new(hint.key()), key_entry)?;
-                    }
+            stats.data_files += 1;
+            stats.total_bytes += std::fs::metadata(index.data_file_path())
+                .map(|metadata| metadata.len())
+                .unwrap_or(0);
+            stats.live_bytes += self.keys_dir.live_bytes(file_id);
+        }
+        Ok(stats)
+    }
+
+    pub fn merge_stats(&self) -> MergeStats {
+        match self.merge_stats.lock() {
+            Ok(stats) => *stats,
+            Err(_) => MergeStats::default(),
+        }
+    }
+
+    /// Rewrites the live entries of all the files which are not written to
+    /// anymore into a single file pair and removes the old files.
+    ///
+    /// Writes and reads are not blocked, keys which change in the meantime
+    /// stay where they were written to.
+    pub fn merge(&self) -> Result<()> {
+        let _merge_guard = self
+            .merge_lock
+            .lock()
+            .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?;
+        let timer = Instant::now();
+
+        let active_file_id = self
+            .active_file
+            .read()
+            .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?
+            .file_id();
+        let merged_indexes: Vec<Index> = self
+            .index_dir
+            .indexes()?
+            .values()
+            .filter(|index| index.file_id() != active_file_id)
+            .cloned()
+            .collect();
+        let max_file_id = match merged_indexes.iter().map(Index::file_id).max() {
+            Some(file_id) => file_id,
+            None => return Ok(()),
+        };
+
+        let merged_file_pair = create_file_pair(self.dir.as_path(), &merged_file_id(&max_file_id))?;
+        self.index_dir.insert(merged_file_pair.clone())?;
+        let merged_file = ActiveFilePair::from(merged_file_pair)?;
+
+        let mut merged_bytes = 0;
+        let mut mark_for_removal = Vec::new();
+        for index in merged_indexes.iter() {
+            merged_bytes += std::fs::metadata(index.data_file_path())
+                .map(|metadata| metadata.len())
+                .unwrap_or(0);
+            for hint in index.get_hints()? {
+                if hint.is_deleted() {
+                    continue;
+                }
+                let key = hint.key();
+                let keys_dir_entry = match self.keys_dir.get(&key) {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+                if keys_dir_entry.file_id != index.file_id()
+                    || keys_dir_entry.data_entry_position != hint.data_entry_position()
+                {
+                    continue;
                 }
+                let data_entry = index.read(hint.data_entry_position(), hint.size())?;
+                let key_entry = merged_file.write(&data_entry, &self.keys_dir)?;
+                self.keys_dir.relocate(&key, &keys_dir_entry, key_entry)?;
             }
             mark_for_removal.push(index.data_file_path());
             mark_for_removal.push(index.hint_file_path());
         }
-
+        merged_file.sync()?;
+
+        self.index_dir.remove(
+            &merged_indexes
+                .iter()
+                .map(Index::file_id)
+                .collect::<Vec<_>>(),
+        )?;
         fs_extra::remove_items(&mark_for_removal)?;
 
+        let mut stats = self
+            .merge_stats
+            .lock()
+            .map_err(|e| EdgeKVError::RWLockPoisonError(format!("{}", e)))?;
+        stats.merges += 1;
+        stats.merged_files += merged_indexes.len() as u64;
+        stats.reclaimed_bytes += merged_bytes.saturating_sub(merged_file.data_file_size()?);
+        stats.duration += timer.elapsed();
         Ok(())
     }
 
@@ -580,6 +744,7 @@ impl Drop for DataStore {
 mod tests {
     use crate::datastore::DataStore;
     use crate::edgekv::DBIterator;
+    use crate::schema::DATA_ENTRY_HEADER_SIZE;
     use serial_test::serial;
     use std::sync::Arc;
 
@@ -779,6 +944,53 @@ mod tests {
         clean_up();
     }
 
+    #[test]
+    #[serial]
+    fn test_merge_reclaims_dead_bytes() {
+        let dir = "./testdir/_test_merge_reclaims_dead_bytes";
+        fs_extra::dir::remove(dir).ok();
+        {
+            let ds = DataStore::open(dir).unwrap();
+            for i in 0..10u8 {
+                ds.put(vec![i], vec![i; 100]).unwrap();
+            }
+            ds.sync_all(true).unwrap();
+            for i in 0..5u8 {
+                ds.put(vec![i], vec![i + 1; 100]).unwrap();
+            }
+            ds.delete(&vec![9]).unwrap();
+            ds.sync_all(true).unwrap();
+
+            let entry_size = DATA_ENTRY_HEADER_SIZE + 1 + 100;
+            let stats = ds.file_stats().unwrap();
+            assert_eq!(stats.data_files, 2);
+            assert_eq!(stats.dead_bytes(), 6 * entry_size);
+
+            ds.merge().unwrap();
+            let stats = ds.file_stats().unwrap();
+            assert_eq!(stats.data_files, 1);
+            assert_eq!(stats.dead_bytes(), 0);
+            assert_eq!(ds.merge_stats().merged_files, 2);
+            assert_eq!(ds.merge_stats().reclaimed_bytes, 6 * entry_size);
+            assert_eq!(ds.get(&vec![0]).unwrap(), Some(vec![1; 100]));
+            assert_eq!(ds.get(&vec![7]).unwrap(), Some(vec![7; 100]));
+            assert_eq!(ds.get(&vec![9]).unwrap(), None);
+
+            // written after the merge, so they must win over it on reopen
+            ds.put(vec![1], vec![7]).unwrap();
+            ds.delete(&vec![2]).unwrap();
+        }
+        {
+            let ds = DataStore::open(dir).unwrap();
+            assert_eq!(ds.get(&vec![0]).unwrap(), Some(vec![1; 100]));
+            assert_eq!(ds.get(&vec![1]).unwrap(), Some(vec![7]));
+            assert_eq!(ds.get(&vec![2]).unwrap(), None);
+            assert_eq!(ds.get(&vec![9]).unwrap(), None);
+            assert_eq!(ds.keys().len(), 8);
+        }
+        fs_extra::dir::remove(dir).ok();
+    }
+
     fn clean_up() {
         fs_extra::dir::remove("./testdir/_test_data_store").ok();
     }
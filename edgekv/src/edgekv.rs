@@ -3,6 +3,7 @@
 
 #![allow(clippy::ptr_arg)]
 
+use crate::compaction::{CompactionPolicy, FileStats, MergeStats};
 use crate::datastore::{DataStore, MergeOperator};
 
 use crate::Result;
@@ -13,7 +14,7 @@ use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 // TODO - TE-721: dir and config are not used
 pub struct EdgeKV {
@@ -34,14 +35,19 @@ impl Display for EdgeKV {
 #[derive(Copy, Clone)]
 pub struct EdgeKVConfiguration {
     pub write_threshold: usize,
+    /// Background merging of the data files, disabled if `None`.
+    pub compaction: Option<CompactionPolicy>,
 }
 
 const DEFAULT_WRITE_THRESHOLD: usize = 1000;
+/// How often the compaction worker checks whether the store was dropped.
+const COMPACTION_WORKER_TICK: Duration = Duration::from_millis(10);
 
 impl Default for EdgeKVConfiguration {
     fn default() -> Self {
         Self {
             write_threshold: DEFAULT_WRITE_THRESHOLD,
+            compaction: Some(CompactionPolicy::default()),
         }
     }
 }
@@ -109,6 +115,38 @@ impl EdgeKV {
                 }
                 drop(store)
             })?;
+
+        if let Some(policy) = config.compaction {
+            let is_dropped = self.dropped.clone();
+            let store = self.store.clone();
+            thread::Builder::new()
+                .name(format!("edgekv-merge-{}", worker_name))
+                .spawn(move || {
+                    let mut last_check = Instant::now();
+                    loop {
+                        thread::sleep(COMPACTION_WORKER_TICK);
+                        if is_dropped.load(Ordering::Acquire) {
+                            break;
+                        }
+                        if last_check.elapsed() < policy.check_interval {
+                            continue;
+                        }
+                        last_check = Instant::now();
+                        let result = store.file_stats().and_then(|stats| {
+                            if policy.should_merge(&stats) {
+                                store.merge()
+                            } else {
+                                Ok(())
+                            }
+                        });
+                        if let Err(e) = result {
+                            //Todo log Error
+                            println!("Merge Error {:?}", e)
+                        }
+                    }
+                    drop(store)
+                })?;
+        }
         Ok(())
     }
     pub fn put(&self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
@@ -140,6 +178,14 @@ impl EdgeKV {
         self.store.merge()
     }
 
+    pub fn file_stats(&self) -> Result<FileStats> {
+        self.store.file_stats()
+    }
+
+    pub fn merge_stats(&self) -> MergeStats {
+        self.store.merge_stats()
+    }
+
     pub fn clear(&self) -> Result<()> {
         self.store.clear()
     }
@@ -282,3 +328,45 @@ impl DoubleEndedIterator for DBIterator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn test_background_compaction() {
+        let dir = "./testdir/_test_background_compaction";
+        fs_extra::dir::remove(dir).ok();
+        {
+            let db = EdgeKV::open_with_configuration(
+                dir,
+                EdgeKVConfiguration {
+                    write_threshold: 1,
+                    compaction: Some(CompactionPolicy {
+                        dead_bytes_ratio: 0.5,
+                        min_dead_bytes: 0,
+                        max_data_files: 1000,
+                        check_interval: Duration::from_millis(10),
+                    }),
+                },
+            )
+            .unwrap();
+            // every sync leaves the previous value dead in its own file
+            for i in 0..10u8 {
+                db.put(vec![1], vec![i; 1000]).unwrap();
+                db.sync_all().unwrap();
+            }
+
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while db.merge_stats().merges == 0 && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(10));
+            }
+            assert!(db.merge_stats().merges > 0);
+            assert!(db.merge_stats().reclaimed_bytes > 0);
+            assert_eq!(db.get(&vec![1]).unwrap(), Some(vec![9; 1000]));
+        }
+        fs_extra::dir::remove(dir).ok();
+    }
+}
@@ -27,6 +27,8 @@ pub enum EdgeKVError {
     Unknown,
     #[error("unknown error with message : {0}")]
     UnknownMsg(String),
+    #[error("repair refused: {0}")]
+    RepairRefused(String),
     #[error("error converting string to integer")]
     StringToIntegerParseError,
 }
@@ -17,6 +17,7 @@ use time::OffsetDateTime;
 const DATA_FILE_EXTENSION: &str = "data";
 const HINT_FILE_EXTENSION: &str = "hint";
 const BUFFER_FILE_EXTENSION: &str = "buff";
+const MERGED_FILE_SEPARATOR: char = '_';
 
 #[derive(Debug, Clone)]
 pub struct FilePair {
@@ -42,7 +43,7 @@ impl FilePair {
         String::from(self.hint_file_path.to_string_lossy())
     }
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Index {
     file_id: String,
     data_file_path: PathBuf,
@@ -253,9 +254,26 @@ impl ActiveFilePair {
 }
 
 pub fn create_new_file_pair<P: AsRef<Path>>(dir: P) -> Result<FilePair> {
+    let file_name = OffsetDateTime::now_utc().unix_timestamp_nanos().to_string();
+    create_file_pair(dir, &file_name)
+}
+
+/// Id of the file pair the files up to `max_file_id` are merged into.
+///
+/// File pairs are loaded in the order of their ids, so the merged pair has
+/// to come after the merged ones, but before any newer pair, e.g. files
+/// `100` and `200` are merged into `200_00000001`.
+pub fn merged_file_id(max_file_id: &str) -> String {
+    let (base, generation) = match max_file_id.split_once(MERGED_FILE_SEPARATOR) {
+        Some((base, generation)) => (base, generation.parse::<u32>().unwrap_or(0)),
+        None => (max_file_id, 0),
+    };
+    format!("{}{}{:08}", base, MERGED_FILE_SEPARATOR, generation + 1)
+}
+
+pub fn create_file_pair<P: AsRef<Path>>(dir: P, file_name: &str) -> Result<FilePair> {
     fs_extra::dir::create_all(dir.as_ref(), false)?;
 
-    let file_name = OffsetDateTime::now_utc().unix_timestamp_nanos().to_string();
     let mut data_file_path = PathBuf::new();
     data_file_path.push(dir.as_ref());
     data_file_path.push(format!("{}.{}", file_name, DATA_FILE_EXTENSION));
@@ -278,7 +296,7 @@ pub fn create_new_file_pair<P: AsRef<Path>>(dir: P) -> Result<FilePair> {
     Ok(FilePair {
         data_file_path,
         hint_file_path,
-        file_id: file_name,
+        file_id: file_name.to_string(),
     })
 }
 
@@ -346,3 +364,21 @@ pub fn fetch_file_pairs<P: AsRef<Path>>(
     }
     Ok((file_pairs, buffer_files))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merged_file_id_order() {
+        let older = "1636457612345678900";
+        let newer = "1636457612345679000";
+        let merged = merged_file_id(older);
+        assert_eq!(merged, "1636457612345678900_00000001");
+        assert_eq!(merged_file_id(&merged), "1636457612345678900_00000002");
+
+        let mut ids = vec![newer.to_string(), merged.clone(), older.to_string()];
+        ids.sort();
+        assert_eq!(ids, vec![older.to_string(), merged, newer.to_string()]);
+    }
+}
@@ -3,10 +3,12 @@
 
 use crate::errors::EdgeKVError;
 
+pub mod compaction;
 pub mod datastore;
 pub mod edgekv;
 pub mod errors;
 pub mod file_ops;
+pub mod repair;
 pub mod schema;
 pub type Result<T> = std::result::Result<T, EdgeKVError>;
 
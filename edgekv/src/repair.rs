@@ -0,0 +1,797 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Offline recovery of an EdgeKV directory after a crash.
+//!
+//! Every entry of the data, hint and buffer files is verified against its
+//! crc. After a damaged entry the reading continues at the next valid one,
+//! found through the positions in the hint files. Incomplete entries at the
+//! end of a file are truncated, damaged entries are reported (and optionally
+//! copied to a quarantine directory) and the hint files are rebuilt from the
+//! valid data entries, keeping the tombstones which only live in the hint
+//! files.
+
+use crate::errors::EdgeKVError;
+use crate::file_ops::{fetch_file_pairs, get_lock_file, FilePair};
+use crate::schema::{DataEntry, Decoder, Encoder, HintEntry, DATA_ENTRY_HEADER_SIZE};
+use crate::Result;
+use fs2::FileExt;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const HINT_ENTRY_HEADER_SIZE: u64 = 4 + 8 + 8 + 8 + 8;
+const HINT_FILE_EXTENSION: &str = "hint";
+const DATA_FILE_EXTENSION: &str = "data";
+
+#[derive(Clone, Debug, Default)]
+pub struct RepairOptions {
+    /// Only report the damage, without changing any file.
+    pub dry_run: bool,
+    /// Directory the bytes of the corrupt and torn entries are copied to,
+    /// named after the file and the position they were found at.
+    pub quarantine_dir: Option<PathBuf>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Damage {
+    /// Bytes which are not a valid entry, e.g. the crc doesn't match, but
+    /// a valid entry follows. They are left in place, but nothing points to
+    /// them anymore.
+    CorruptEntry,
+    /// Incomplete entry at the end of the file, it is truncated.
+    TornTail,
+    /// Hint pointing to a data entry which is missing or corrupt, it is
+    /// dropped from the rebuilt hint file.
+    DanglingHint,
+}
+
+#[derive(Clone, Debug)]
+pub struct DamagedEntry {
+    pub file: PathBuf,
+    pub position: u64,
+    pub size: u64,
+    pub damage: Damage,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RepairReport {
+    pub checked_files: usize,
+    pub valid_entries: usize,
+    pub damaged_entries: Vec<DamagedEntry>,
+    /// Bytes cut from the end of the data and buffer files.
+    pub truncated_bytes: u64,
+    /// Hint files which are (or would be in a dry run) rewritten.
+    pub rebuilt_hint_files: Vec<PathBuf>,
+    pub quarantined_files: Vec<PathBuf>,
+}
+
+impl RepairReport {
+    pub fn is_clean(&self) -> bool {
+        self.damaged_entries.is_empty() && self.rebuilt_hint_files.is_empty()
+    }
+
+    fn damaged(&mut self, file: &Path, position: u64, size: u64, damage: Damage) {
+        self.damaged_entries.push(DamagedEntry {
+            file: file.to_path_buf(),
+            position,
+            size,
+            damage,
+        })
+    }
+}
+
+/// Verifies and repairs the EdgeKV directory `dir`, which must not be
+/// opened by anyone else.
+///
+/// Torn tails longer than a single entry are only truncated with a
+/// quarantine directory to copy them to.
+pub fn repair<P: AsRef<Path>>(dir: P, options: &RepairOptions) -> Result<RepairReport> {
+    std::fs::metadata(dir.as_ref())?;
+    let lock_file = get_lock_file(dir.as_ref())?;
+    lock_file
+        .try_lock_exclusive()
+        .map_err(|_| EdgeKVError::LockFailed(String::from(dir.as_ref().to_string_lossy())))?;
+
+    let mut report = RepairReport::default();
+    let (file_pairs, buffer_files) = fetch_file_pairs(dir.as_ref())?;
+    for file_pair in file_pairs.values() {
+        repair_file_pair(file_pair, options, &mut report)?;
+    }
+    for buffer_file in buffer_files.values() {
+        repair_buffer_file(buffer_file, options, &mut report)?;
+    }
+
+    lock_file.unlock()?;
+    Ok(report)
+}
+
+/// Encoding of the entries of a file, see [crate::schema].
+struct Format {
+    header_size: u64,
+    body_size: fn(&[u8]) -> u64,
+    is_valid: fn(&[u8]) -> bool,
+}
+
+const DATA_FORMAT: Format = Format {
+    header_size: DATA_ENTRY_HEADER_SIZE,
+    body_size: data_entry_body_size,
+    is_valid: is_valid_data_entry,
+};
+
+const HINT_FORMAT: Format = Format {
+    header_size: HINT_ENTRY_HEADER_SIZE,
+    body_size: hint_entry_body_size,
+    is_valid: is_valid_hint_entry,
+};
+
+/// How much of a file is searched for the next valid entry at once.
+const RESYNC_WINDOW_SIZE: u64 = 1024 * 1024;
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(bytes[offset..offset + 8].try_into().unwrap_or_default())
+}
+
+fn data_entry_body_size(header: &[u8]) -> u64 {
+    read_u64(header, 12).saturating_add(read_u64(header, 20))
+}
+
+fn hint_entry_body_size(header: &[u8]) -> u64 {
+    read_u64(header, 12)
+}
+
+fn is_valid_data_entry(bytes: &[u8]) -> bool {
+    matches!(DataEntry::decode(&mut Cursor::new(bytes)), Ok(entry) if entry.check_crc())
+}
+
+fn is_valid_hint_entry(bytes: &[u8]) -> bool {
+    matches!(HintEntry::decode(&mut Cursor::new(bytes)), Ok(hint) if hint.check_crc())
+}
+
+struct Record {
+    position: u64,
+    bytes: Vec<u8>,
+}
+
+struct TornTail {
+    position: u64,
+    size: u64,
+    /// The tail is the beginning of the single entry its header describes,
+    /// as left by a crash while appending it.
+    single_entry: bool,
+}
+
+#[derive(Default)]
+struct Scan {
+    records: Vec<Record>,
+    /// Damaged bytes between the valid records.
+    corrupt: Vec<Record>,
+    torn_tail: Option<TornTail>,
+}
+
+struct RecordReader<'a> {
+    file: File,
+    len: u64,
+    format: &'a Format,
+}
+
+impl<'a> RecordReader<'a> {
+    fn open(path: &Path, format: &'a Format) -> Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self { file, len, format })
+    }
+
+    fn read_at(&mut self, position: u64, size: u64) -> Result<Vec<u8>> {
+        let mut bytes = vec![0_u8; size as usize];
+        self.file.seek(SeekFrom::Start(position))?;
+        self.file.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Size of the record described by the header at `position`, if it fits
+    /// in the file.
+    fn record_size(&mut self, position: u64) -> Result<Option<u64>> {
+        let remaining = self.len.saturating_sub(position);
+        if remaining < self.format.header_size {
+            return Ok(None);
+        }
+        let header = self.read_at(position, self.format.header_size)?;
+        let size = self
+            .format
+            .header_size
+            .checked_add((self.format.body_size)(&header));
+        Ok(size.filter(|size| *size <= remaining))
+    }
+
+    fn record_at(&mut self, position: u64) -> Result<Option<Record>> {
+        let size = match self.record_size(position)? {
+            Some(size) => size,
+            None => return Ok(None),
+        };
+        let bytes = self.read_at(position, size)?;
+        if (self.format.is_valid)(&bytes) {
+            Ok(Some(Record { position, bytes }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Position of the first valid record after the damaged one at
+    /// `position`. The positions known from the hints are tried first, then
+    /// the end of the damaged record, if its size is plausible, and at last
+    /// every single position up to the end of the file.
+    fn resync(&mut self, position: u64, hinted: &BTreeSet<u64>) -> Result<Option<u64>> {
+        for candidate in hinted.range(position + 1..) {
+            if self.record_at(*candidate)?.is_some() {
+                return Ok(Some(*candidate));
+            }
+        }
+        if let Some(size) = self.record_size(position)? {
+            if self.record_at(position + size)?.is_some() {
+                return Ok(Some(position + size));
+            }
+        }
+
+        let header_size = self.format.header_size;
+        let mut start = position + 1;
+        while start + header_size <= self.len {
+            let window_len = (RESYNC_WINDOW_SIZE + header_size).min(self.len - start);
+            let window = self.read_at(start, window_len)?;
+            for offset in 0..=(window_len - header_size) {
+                let candidate = start + offset;
+                let header = &window[offset as usize..(offset + header_size) as usize];
+                let size = match header_size.checked_add((self.format.body_size)(header)) {
+                    Some(size) if size <= self.len - candidate => size,
+                    _ => continue,
+                };
+                let valid = if offset + size <= window_len {
+                    (self.format.is_valid)(&window[offset as usize..(offset + size) as usize])
+                } else {
+                    self.record_at(candidate)?.is_some()
+                };
+                if valid {
+                    return Ok(Some(candidate));
+                }
+            }
+            start += RESYNC_WINDOW_SIZE;
+        }
+        Ok(None)
+    }
+}
+
+/// Splits the file into the valid records and the damaged bytes between
+/// them. After a damaged record the reading continues at the next valid
+/// one, see [RecordReader::resync], only if there is none the rest of the
+/// file is a torn tail.
+fn scan_records(path: &Path, format: &Format, hinted: &BTreeSet<u64>) -> Result<Scan> {
+    let mut reader = RecordReader::open(path, format)?;
+    let mut scan = Scan::default();
+    let mut position = 0;
+
+    while position < reader.len {
+        if let Some(record) = reader.record_at(position)? {
+            position += record.bytes.len() as u64;
+            scan.records.push(record);
+            continue;
+        }
+        if let Some(next) = reader.resync(position, hinted)? {
+            // the hints still tell where each of the skipped entries starts
+            let mut starts: Vec<u64> = hinted.range(position + 1..next).copied().collect();
+            starts.push(next);
+            for end in starts {
+                let bytes = reader.read_at(position, end - position)?;
+                scan.corrupt.push(Record { position, bytes });
+                position = end;
+            }
+            continue;
+        }
+
+        let size = reader.len - position;
+        match reader.record_size(position)? {
+            // a complete record, only its crc doesn't match
+            Some(record_size) if record_size == size => {
+                let bytes = reader.read_at(position, size)?;
+                scan.corrupt.push(Record { position, bytes });
+            }
+            record_size => {
+                scan.torn_tail = Some(TornTail {
+                    position,
+                    size,
+                    single_entry: record_size.is_none(),
+                })
+            }
+        }
+        break;
+    }
+    Ok(scan)
+}
+
+/// The bytes of a torn tail are only lost without a copy if they are the
+/// single entry a crash interrupted, anything longer may hold entries which
+/// only lost their hints and their headers.
+fn check_truncation(path: &Path, tail: &TornTail, options: &RepairOptions) -> Result<()> {
+    if tail.single_entry || options.quarantine_dir.is_some() {
+        return Ok(());
+    }
+    Err(EdgeKVError::RepairRefused(format!(
+        "truncating {} bytes of {} at {} needs a quarantine directory",
+        tail.size,
+        path.display(),
+        tail.position
+    )))
+}
+
+fn read_tail(path: &Path, tail: &TornTail) -> Result<Vec<u8>> {
+    let mut bytes = vec![0_u8; tail.size as usize];
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(tail.position))?;
+    file.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Returns the valid data entries of the file by their position, truncates
+/// the torn tail and reports the rest.
+fn verify_data_file(
+    path: &Path,
+    hinted: &BTreeSet<u64>,
+    options: &RepairOptions,
+    report: &mut RepairReport,
+) -> Result<BTreeMap<u64, DataEntry>> {
+    let scan = scan_records(path, &DATA_FORMAT, hinted)?;
+    if let Some(tail) = &scan.torn_tail {
+        check_truncation(path, tail, options)?;
+    }
+
+    let mut entries = BTreeMap::new();
+    for record in scan.records {
+        let entry = DataEntry::decode(&mut Cursor::new(&record.bytes))?;
+        entries.insert(record.position, entry);
+    }
+    report.valid_entries += entries.len();
+
+    for record in scan.corrupt {
+        let size = record.bytes.len() as u64;
+        report.damaged(path, record.position, size, Damage::CorruptEntry);
+        quarantine(path, record.position, &record.bytes, options, report)?;
+    }
+
+    if let Some(tail) = scan.torn_tail {
+        report.damaged(path, tail.position, tail.size, Damage::TornTail);
+        report.truncated_bytes += tail.size;
+        if !options.dry_run {
+            quarantine(
+                path,
+                tail.position,
+                &read_tail(path, &tail)?,
+                options,
+                report,
+            )?;
+            let file = OpenOptions::new().write(true).open(path)?;
+            file.set_len(tail.position)?;
+            file.sync_all()?;
+        }
+    }
+    Ok(entries)
+}
+
+fn repair_file_pair(
+    file_pair: &FilePair,
+    options: &RepairOptions,
+    report: &mut RepairReport,
+) -> Result<()> {
+    // a file pair is found by either of its files, the other one may be missing
+    let (data_file_path, hint_file_path) =
+        match (file_pair.data_file_path(), file_pair.hint_file_path()) {
+            (data, hint) if hint.is_empty() => {
+                let data = PathBuf::from(data);
+                let hint = data.with_extension(HINT_FILE_EXTENSION);
+                (data, hint)
+            }
+            (data, hint) if data.is_empty() => {
+                let hint = PathBuf::from(hint);
+                let data = hint.with_extension(DATA_FILE_EXTENSION);
+                (data, hint)
+            }
+            (data, hint) => (PathBuf::from(data), PathBuf::from(hint)),
+        };
+
+    let hint_scan = if hint_file_path.exists() {
+        report.checked_files += 1;
+        scan_records(&hint_file_path, &HINT_FORMAT, &BTreeSet::new())?
+    } else {
+        Scan::default()
+    };
+    let mut hints = Vec::new();
+    for record in hint_scan.records.iter() {
+        hints.push((
+            record.position,
+            HintEntry::decode(&mut Cursor::new(&record.bytes))?,
+        ));
+    }
+    // where the data entries start, to find them again after a damaged one
+    let hinted: BTreeSet<u64> = hints
+        .iter()
+        .filter(|(_, hint)| !hint.is_deleted())
+        .map(|(_, hint)| hint.data_entry_position())
+        .collect();
+
+    let data_entries = if data_file_path.exists() {
+        report.checked_files += 1;
+        verify_data_file(&data_file_path, &hinted, options, report)?
+    } else {
+        if !options.dry_run {
+            File::create(&data_file_path)?.sync_all()?;
+        }
+        BTreeMap::new()
+    };
+
+    for record in hint_scan.corrupt {
+        let size = record.bytes.len() as u64;
+        report.damaged(&hint_file_path, record.position, size, Damage::CorruptEntry);
+    }
+    if let Some(tail) = hint_scan.torn_tail {
+        report.damaged(&hint_file_path, tail.position, tail.size, Damage::TornTail);
+    }
+
+    // the hints which still point to a valid data entry, and the tombstones
+    let hints: Vec<HintEntry> = hints
+        .into_iter()
+        .filter_map(|(position, hint)| {
+            let valid = hint.is_deleted()
+                || matches!(data_entries.get(&hint.data_entry_position()), Some(entry)
+                    if entry.key_size() == hint.key_size()
+                        && entry.value_size() == hint.value_size()
+                        && entry.key() == hint.key());
+            if !valid {
+                let size = hint.size() as u64;
+                report.damaged(&hint_file_path, position, size, Damage::DanglingHint);
+                return None;
+            }
+            Some(hint)
+        })
+        .collect();
+
+    // data entries without a hint are put in front of the first hint which
+    // comes after them, so the tombstones keep their order relative to the
+    // entries they delete
+    let mut unreferenced = data_entries;
+    for hint in hints.iter().filter(|hint| !hint.is_deleted()) {
+        unreferenced.remove(&hint.data_entry_position());
+    }
+    let mut rebuilt = Vec::new();
+    for hint in hints {
+        if !hint.is_deleted() {
+            let position = hint.data_entry_position();
+            let preceding: Vec<u64> = unreferenced.range(..position).map(|(p, _)| *p).collect();
+            for p in preceding {
+                if let Some(entry) = unreferenced.remove(&p) {
+                    rebuilt.extend_from_slice(&HintEntry::from(&entry, p).encode());
+                }
+            }
+        }
+        rebuilt.extend_from_slice(&hint.encode());
+    }
+    for (position, entry) in unreferenced {
+        rebuilt.extend_from_slice(&HintEntry::from(&entry, position).encode());
+    }
+
+    let current = if hint_file_path.exists() {
+        std::fs::read(&hint_file_path)?
+    } else {
+        Vec::new()
+    };
+    if current != rebuilt || !hint_file_path.exists() {
+        if !options.dry_run {
+            replace_file(&hint_file_path, &rebuilt)?;
+        }
+        report.rebuilt_hint_files.push(hint_file_path);
+    }
+    Ok(())
+}
+
+/// Buffer files are only read back into memory, so the damaged entries are
+/// simply dropped from them.
+fn repair_buffer_file(
+    path: &Path,
+    options: &RepairOptions,
+    report: &mut RepairReport,
+) -> Result<()> {
+    report.checked_files += 1;
+    let scan = scan_records(path, &DATA_FORMAT, &BTreeSet::new())?;
+    if let Some(tail) = &scan.torn_tail {
+        check_truncation(path, tail, options)?;
+    }
+    if scan.corrupt.is_empty() && scan.torn_tail.is_none() {
+        report.valid_entries += scan.records.len();
+        return Ok(());
+    }
+
+    let mut valid = Vec::new();
+    for record in scan.records {
+        report.valid_entries += 1;
+        valid.extend_from_slice(&record.bytes);
+    }
+    for record in scan.corrupt {
+        let size = record.bytes.len() as u64;
+        report.damaged(path, record.position, size, Damage::CorruptEntry);
+        quarantine(path, record.position, &record.bytes, options, report)?;
+    }
+    if let Some(tail) = scan.torn_tail {
+        report.damaged(path, tail.position, tail.size, Damage::TornTail);
+        report.truncated_bytes += tail.size;
+        if !options.dry_run {
+            quarantine(
+                path,
+                tail.position,
+                &read_tail(path, &tail)?,
+                options,
+                report,
+            )?;
+        }
+    }
+
+    if !options.dry_run {
+        replace_file(path, &valid)?;
+    }
+    Ok(())
+}
+
+fn quarantine(
+    path: &Path,
+    position: u64,
+    bytes: &[u8],
+    options: &RepairOptions,
+    report: &mut RepairReport,
+) -> Result<()> {
+    let quarantine_dir = match (&options.quarantine_dir, options.dry_run) {
+        (Some(quarantine_dir), false) => quarantine_dir,
+        _ => return Ok(()),
+    };
+    fs_extra::dir::create_all(quarantine_dir, false)?;
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let quarantine_path = quarantine_dir.join(format!("{}.{}", file_name, position));
+    let mut file = File::create(&quarantine_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    report.quarantined_files.push(quarantine_path);
+    Ok(())
+}
+
+/// Writes the file next to the original first, so that a crash during the
+/// repair leaves either the old or the new content.
+fn replace_file(path: &Path, content: &[u8]) -> Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datastore::DataStore;
+    use serial_test::serial;
+
+    fn data_files(dir: &str) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().unwrap_or_default() == DATA_FILE_EXTENSION)
+            .filter(|path| std::fs::metadata(path).unwrap().len() > 0)
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn append(path: &Path, bytes: &[u8]) {
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(bytes).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_repair_torn_tail_and_missing_hints() {
+        let dir = "./testdir/_test_repair_torn_tail";
+        fs_extra::dir::remove(dir).ok();
+        {
+            let ds = DataStore::open(dir).unwrap();
+            ds.put(vec![1], vec![1]).unwrap();
+            ds.put(vec![2], vec![2]).unwrap();
+            ds.put(vec![3], vec![3]).unwrap();
+            ds.sync_all(true).unwrap();
+            ds.delete(&vec![2]).unwrap();
+            ds.put(vec![4], vec![4]).unwrap();
+        }
+        let files = data_files(dir);
+        assert_eq!(files.len(), 2);
+        // crashed while appending to the second file, the first lost its hints
+        append(&files[1], &[7; 10]);
+        append(&files[1].with_extension(HINT_FILE_EXTENSION), &[7; 5]);
+        std::fs::remove_file(files[0].with_extension(HINT_FILE_EXTENSION)).unwrap();
+        assert!(DataStore::open(dir).is_err());
+
+        let dry_run = RepairOptions {
+            dry_run: true,
+            quarantine_dir: None,
+        };
+        let report = repair(dir, &dry_run).unwrap();
+        assert_eq!(report.truncated_bytes, 10);
+        assert_eq!(report.rebuilt_hint_files.len(), 2);
+        assert!(!files[0].with_extension(HINT_FILE_EXTENSION).exists());
+
+        let report = repair(dir, &RepairOptions::default()).unwrap();
+        assert_eq!(report.valid_entries, 4);
+        assert_eq!(report.truncated_bytes, 10);
+        assert_eq!(report.rebuilt_hint_files.len(), 2);
+        assert!(report
+            .damaged_entries
+            .iter()
+            .all(|entry| entry.damage == Damage::TornTail));
+        assert_eq!(report.damaged_entries.len(), 2);
+        assert!(repair(dir, &RepairOptions::default()).unwrap().is_clean());
+
+        {
+            let ds = DataStore::open(dir).unwrap();
+            assert_eq!(ds.get(&vec![1]).unwrap(), Some(vec![1]));
+            assert_eq!(ds.get(&vec![2]).unwrap(), None);
+            assert_eq!(ds.get(&vec![3]).unwrap(), Some(vec![3]));
+            assert_eq!(ds.get(&vec![4]).unwrap(), Some(vec![4]));
+        }
+        fs_extra::dir::remove(dir).ok();
+    }
+
+    #[test]
+    #[serial]
+    fn test_repair_quarantines_corrupt_entries() {
+        let dir = "./testdir/_test_repair_corrupt_entry";
+        let quarantine_dir = "./testdir/_test_repair_corrupt_entry_quarantine";
+        fs_extra::dir::remove(dir).ok();
+        fs_extra::dir::remove(quarantine_dir).ok();
+        {
+            let ds = DataStore::open(dir).unwrap();
+            for i in 0..3u8 {
+                ds.put(vec![i], vec![i; 10]).unwrap();
+            }
+        }
+        // flip the last byte of the value of the last entry
+        let data_file = data_files(dir).pop().unwrap();
+        let mut bytes = std::fs::read(&data_file).unwrap();
+        *bytes.last_mut().unwrap() ^= 0xff;
+        std::fs::write(&data_file, &bytes).unwrap();
+
+        let options = RepairOptions {
+            dry_run: false,
+            quarantine_dir: Some(PathBuf::from(quarantine_dir)),
+        };
+        let report = repair(dir, &options).unwrap();
+        let damage: Vec<Damage> = report
+            .damaged_entries
+            .iter()
+            .map(|entry| entry.damage)
+            .collect();
+        assert_eq!(damage, vec![Damage::CorruptEntry, Damage::DanglingHint]);
+        assert_eq!(report.valid_entries, 2);
+        assert_eq!(report.truncated_bytes, 0);
+        assert_eq!(report.quarantined_files.len(), 1);
+        assert_eq!(
+            std::fs::read(&report.quarantined_files[0]).unwrap().len() as u64,
+            DATA_ENTRY_HEADER_SIZE + 1 + 10
+        );
+
+        {
+            let ds = DataStore::open(dir).unwrap();
+            assert_eq!(ds.keys().len(), 2);
+            for key in ds.keys() {
+                assert_eq!(ds.get(&key).unwrap(), Some(vec![key[0]; 10]));
+            }
+        }
+        fs_extra::dir::remove(dir).ok();
+        fs_extra::dir::remove(quarantine_dir).ok();
+    }
+
+    #[test]
+    #[serial]
+    fn test_repair_resyncs_after_corrupt_size() {
+        let dir = "./testdir/_test_repair_corrupt_size";
+        let entry_size = DATA_ENTRY_HEADER_SIZE + 1 + 10;
+
+        for with_hints in [true, false] {
+            fs_extra::dir::remove(dir).ok();
+            {
+                let ds = DataStore::open(dir).unwrap();
+                for i in 0..4u8 {
+                    ds.put(vec![i], vec![i; 10]).unwrap();
+                }
+            }
+            let data_file = data_files(dir).pop().unwrap();
+            let mut bytes = std::fs::read(&data_file).unwrap();
+            // the value size of the first entry gets smaller, of the second one huge
+            bytes[27] ^= 0x02;
+            bytes[entry_size as usize + 20] ^= 0x80;
+            std::fs::write(&data_file, &bytes).unwrap();
+            if !with_hints {
+                std::fs::remove_file(data_file.with_extension(HINT_FILE_EXTENSION)).unwrap();
+            }
+
+            let report = repair(dir, &RepairOptions::default()).unwrap();
+            assert_eq!(report.valid_entries, 2);
+            assert_eq!(report.truncated_bytes, 0);
+            let corrupt: Vec<(u64, u64)> = report
+                .damaged_entries
+                .iter()
+                .filter(|entry| entry.damage == Damage::CorruptEntry)
+                .map(|entry| (entry.position, entry.size))
+                .collect();
+            if with_hints {
+                assert_eq!(corrupt, vec![(0, entry_size), (entry_size, entry_size)]);
+            } else {
+                // without the hints the two entries can't be told apart
+                assert_eq!(corrupt, vec![(0, 2 * entry_size)]);
+            }
+            assert_eq!(std::fs::metadata(&data_file).unwrap().len(), 4 * entry_size);
+
+            {
+                let ds = DataStore::open(dir).unwrap();
+                assert_eq!(ds.keys().len(), 2);
+                for key in ds.keys() {
+                    assert_eq!(ds.get(&key).unwrap(), Some(vec![key[0]; 10]));
+                }
+            }
+        }
+        fs_extra::dir::remove(dir).ok();
+    }
+
+    #[test]
+    #[serial]
+    fn test_repair_refuses_long_truncation_without_quarantine() {
+        let dir = "./testdir/_test_repair_long_truncation";
+        let quarantine_dir = "./testdir/_test_repair_long_truncation_quarantine";
+        let entry_size = DATA_ENTRY_HEADER_SIZE + 1 + 10;
+        fs_extra::dir::remove(dir).ok();
+        fs_extra::dir::remove(quarantine_dir).ok();
+        {
+            let ds = DataStore::open(dir).unwrap();
+            for i in 0..3u8 {
+                ds.put(vec![i], vec![i; 10]).unwrap();
+            }
+        }
+        // the crc of the last two entries is damaged and nothing valid follows
+        let data_file = data_files(dir).pop().unwrap();
+        let mut bytes = std::fs::read(&data_file).unwrap();
+        bytes[entry_size as usize] ^= 0xff;
+        bytes[2 * entry_size as usize] ^= 0xff;
+        std::fs::write(&data_file, &bytes).unwrap();
+        std::fs::remove_file(data_file.with_extension(HINT_FILE_EXTENSION)).unwrap();
+
+        assert!(matches!(
+            repair(dir, &RepairOptions::default()),
+            Err(EdgeKVError::RepairRefused(_))
+        ));
+        assert_eq!(std::fs::read(&data_file).unwrap(), bytes);
+
+        let options = RepairOptions {
+            dry_run: false,
+            quarantine_dir: Some(PathBuf::from(quarantine_dir)),
+        };
+        let report = repair(dir, &options).unwrap();
+        assert_eq!(report.valid_entries, 1);
+        assert_eq!(report.truncated_bytes, 2 * entry_size);
+        assert_eq!(
+            std::fs::read(&report.quarantined_files[0]).unwrap(),
+            bytes[entry_size as usize..]
+        );
+        assert_eq!(std::fs::metadata(&data_file).unwrap().len(), entry_size);
+
+        fs_extra::dir::remove(dir).ok();
+        fs_extra::dir::remove(quarantine_dir).ok();
+    }
+}
@@ -5,6 +5,8 @@ use crc::{Crc, CRC_32_CKSUM};
 use std::io::Read;
 use time::OffsetDateTime;
 pub const CRC_CKSUM: Crc<u32> = Crc::<u32>::new(&CRC_32_CKSUM);
+/// Size of the crc, timestamp, key size and value size of a [DataEntry].
+pub const DATA_ENTRY_HEADER_SIZE: u64 = 4 + 8 + 8 + 8;
 use crate::Result;
 
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
@@ -110,6 +112,12 @@ impl DataEntry {
         buf
     }
 
+    pub fn key_size(&self) -> u64 {
+        self.key_size
+    }
+    pub fn value_size(&self) -> u64 {
+        self.value_size
+    }
     pub fn key(&self) -> Vec<u8> {
         self.key.to_owned()
     }
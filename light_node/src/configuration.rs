@@ -5,7 +5,7 @@ use std::env;
 use std::ffi::OsString;
 use std::fs;
 use std::io::{self, BufRead};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
@@ -17,11 +17,15 @@ use slog::Logger;
 
 use crypto::hash::BlockHash;
 use logging::config::{FileLoggerConfig, LogFormat, LoggerType, NoDrainError, SlogConfig};
+use rpc::server::acl::{ListenAddress, RpcAcl};
+use rpc::server::limits::{RpcConcurrencyLimit, RpcLimitsConfiguration, RpcRateLimit};
+use rpc::server::listener::{RpcListenerConfiguration, RpcListenerSpec, RpcTlsConfiguration};
 use shell::shell_automaton_manager::P2p;
 use shell::PeerConnectionThreshold;
+use storage::database::schema_migration::SchemaMigrationOptions;
 use storage::database::tezedge_database::TezedgeDatabaseBackendConfiguration;
 use storage::initializer::{DbsRocksDbTableInitializer, RocksDbConfig};
-use storage::{BlockReference, Replay, StorageSnapshot};
+use storage::{BlockReference, HistoryMode, Replay, SnapshotFormat, StorageSnapshot};
 use tezos_api::environment::{self, TezosEnvironmentConfiguration};
 use tezos_api::environment::{TezosEnvironment, ZcashParams};
 use tezos_context_api::{
@@ -32,12 +36,14 @@ use tezos_context_api::{
 
 #[derive(Debug, Clone)]
 pub struct Rpc {
-    pub listener_port: u16,
+    pub listeners: Vec<RpcListenerConfiguration>,
     /// Tuple of :
     ///     SocketAddr
     ///     u16 - max_number_of_websocket_connections
     pub websocket_cfg: Option<(SocketAddr, u16)>,
     pub allow_unsafe_rpc: bool,
+    pub acl: RpcAcl,
+    pub limits: RpcLimitsConfiguration,
 }
 
 impl Rpc {
@@ -90,14 +96,19 @@ pub struct Storage {
     pub compute_context_action_tree_hashes: bool,
     pub patch_context: Option<PatchContext>,
     pub main_db: TezedgeDatabaseBackendConfiguration,
+    pub schema_migration: SchemaMigrationOptions,
     pub initialize_context_timeout: Duration,
+    pub history_mode: HistoryMode,
+    /// Number of cycles preserved by the garbage collector of the on-disk context,
+    /// `None` when it is disabled. Derived from `history_mode` unless given explicitly.
+    pub context_gc_preserved_cycles: Option<usize>,
 }
 
 impl Storage {
     const STORAGES_COUNT: usize = 3;
     const MINIMAL_THREAD_COUNT: usize = 1;
 
-    const DB_STORAGE_VERSION: i64 = 21;
+    const DB_STORAGE_VERSION: i64 = storage::system_storage::MAIN_DB_VERSION;
 
     const LRU_CACHE_SIZE_96MB: usize = 96 * 1024 * 1024;
 
@@ -105,6 +116,8 @@ impl Storage {
 
     const DEFAULT_MAINDB: &'static str = "rocksdb";
 
+    const DEFAULT_HISTORY_MODE: &'static str = "archive";
+
     const DEFAULT_INITIALIZE_CONTEXT_TIMEOUT_IN_SECONDS: u64 = 15;
 }
 
@@ -127,6 +140,21 @@ impl Ffi {
         "tezos/sys/lib_tezos/artifacts/sapling-output.params";
 }
 
+/// Offline replay of the recorded shell automaton actions.
+#[derive(Debug, Clone)]
+pub struct ReplayActions {
+    pub from_action_id: Option<u64>,
+    pub to_action_id: Option<u64>,
+}
+
+/// Offline copy of the main database to another backend.
+#[derive(Debug, Clone)]
+pub struct MigrateMainDb {
+    pub target_backend: TezedgeDatabaseBackendConfiguration,
+    pub target_db_path: PathBuf,
+    pub batch_size: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct Environment {
     pub p2p: P2p,
@@ -137,6 +165,9 @@ pub struct Environment {
     pub ffi: Ffi,
     pub replay: Option<Replay>,
     pub snapshot: Option<StorageSnapshot>,
+    pub import_octez_snapshot: Option<PathBuf>,
+    pub replay_actions: Option<ReplayActions>,
+    pub migrate_maindb: Option<MigrateMainDb>,
 
     pub tezos_network: TezosEnvironment,
     pub tezos_network_config: TezosEnvironmentConfiguration,
@@ -462,6 +493,54 @@ pub fn tezos_app() -> App<'static, 'static> {
             .value_name("PORT")
             .help("Rust server RPC port for communication with rust node")
             .validator(parse_validator_fn!(u16, "Value must be a valid port number")))
+        .arg(Arg::with_name("rpc-listener")
+            .long("rpc-listener")
+            .global(true)
+            .takes_value(true)
+            .multiple(true)
+            .value_name("IP:PORT[,read-only][,tls]")
+            .help("RPC listen address, may be repeated, replaces the listener on --rpc-port.
+                       read-only - serves only GET routes, e.g. for a public endpoint
+                       tls - serves HTTPS with --rpc-tls-cert and --rpc-tls-key")
+            .validator(parse_validator_fn!(RpcListenerSpec, "Value must be IP:PORT optionally followed by ,read-only and/or ,tls")))
+        .arg(Arg::with_name("rpc-tls-cert")
+            .long("rpc-tls-cert")
+            .global(true)
+            .takes_value(true)
+            .value_name("PATH")
+            .requires("rpc-tls-key")
+            .help("PEM encoded certificate chain for the RPC server, when set the listener on --rpc-port serves HTTPS. Reloaded on SIGHUP."))
+        .arg(Arg::with_name("rpc-tls-key")
+            .long("rpc-tls-key")
+            .global(true)
+            .takes_value(true)
+            .value_name("PATH")
+            .requires("rpc-tls-cert")
+            .help("PEM encoded PKCS #8 private key of --rpc-tls-cert. Reloaded on SIGHUP."))
+        .arg(Arg::with_name("rpc-rate-limit")
+            .long("rpc-rate-limit")
+            .global(true)
+            .takes_value(true)
+            .value_name("REQUESTS_PER_SECOND[/BURST]")
+            .help("Per client IP token bucket for RPC requests, e.g. 10/50 is 10 requests per second with bursts of up to 50 requests. Rejected requests get 429.")
+            .validator(parse_validator_fn!(RpcRateLimit, "Value must be <REQUESTS_PER_SECOND>[/<BURST>]")))
+        .arg(Arg::with_name("rpc-rate-limit-exempt")
+            .long("rpc-rate-limit-exempt")
+            .global(true)
+            .takes_value(true)
+            .multiple(true)
+            .value_name("IP")
+            .help("Client IP address which is not rate limited, e.g. the one of a baker, may be repeated. An IPv6 address exempts its /64 network, like clients are limited by it.")
+            .validator(parse_validator_fn!(IpAddr, "Value must be a valid IP address")))
+        .arg(Arg::with_name("rpc-concurrency-limit")
+            .long("rpc-concurrency-limit")
+            .global(true)
+            .takes_value(true)
+            .multiple(true)
+            .value_name("ROUTE_PATTERN=MAX")
+            .help("Maximal number of concurrent requests on routes matching the pattern, e.g. \"/dev/rewards/**=2\", may be repeated, the first matching limit applies.
+                       The pattern is an optional HTTP method and a path, `*` matches one path segment and a trailing `**` any number of segments. Rejected requests get 429.")
+            .validator(parse_validator_fn!(RpcConcurrencyLimit, "Value must be <ROUTE_PATTERN>=<MAX>")))
         .arg(Arg::with_name("enable-testchain")
             .long("enable-testchain")
             .global(true)
@@ -565,6 +644,25 @@ pub fn tezos_app() -> App<'static, 'static> {
             .possible_values(&TezedgeDatabaseBackendConfiguration::possible_values())
             .default_value(Storage::DEFAULT_MAINDB)
             .help("Options fo main database backend"))
+        .arg(Arg::with_name("maindb-migration-dry-run")
+            .long("maindb-migration-dry-run")
+            .takes_value(false)
+            .help("Logs the schema migrations needed to upgrade the main database and stops the node, without migrating"))
+        .arg(Arg::with_name("maindb-backup-dir")
+            .long("maindb-backup-dir")
+            .takes_value(true)
+            .value_name("PATH")
+            .help("Directory for the backups of the main database, taken before destructive schema migrations. Default is 'db_backup' in the --bootstrap-db-path.
+                       In case it starts with ./ or ../, it is relative path to the current dir, otherwise to the --tezos-data-dir"))
+        .arg(Arg::with_name("history-mode")
+            .long("history-mode")
+            .takes_value(true)
+            .value_name("MODE")
+            .default_value(Storage::DEFAULT_HISTORY_MODE)
+            .help("History mode of the node: 'archive', 'full[:<cycle offset>]' or 'rolling[:<cycle offset>]'.
+                       In full mode block and operations metadata older than <cycle offset> cycles is pruned, in rolling mode whole blocks are pruned.
+                       Context history is pruned in both modes. Default <cycle offset> is 6.")
+            .validator(parse_validator_fn!(HistoryMode, "Value must be 'archive', 'full[:<cycle offset>]' or 'rolling[:<cycle offset>]'")))
         .arg(Arg::with_name("context-kv-store")
             .long("context-kv-store")
             .global(true)
@@ -572,6 +670,14 @@ pub fn tezos_app() -> App<'static, 'static> {
             .value_name("STRING")
             .possible_values(&SupportedContextKeyValueStore::possible_values())
             .help("Choose the TezEdge context storage backend - supported backends: 'inmem', 'ondisk'"))
+        .arg(Arg::with_name("context-gc-preserved-cycles")
+            .long("context-gc-preserved-cycles")
+            .global(true)
+            .takes_value(true)
+            .value_name("NUM")
+            .help("Enables the garbage collection of the 'ondisk' context, the commits of the last <NUM> cycles are preserved.
+                       Default is the <cycle offset> of the --history-mode plus the current cycle, disabled in 'archive' mode")
+            .validator(parse_validator_fn!(usize, "Value must be a valid number")))
         // TODO - TE-261: right now this is obsolete, either reintegrate with the timings database or remove
         .arg(Arg::with_name("compute-context-action-tree-hashes")
             .long("compute-context-action-tree-hashes")
@@ -626,6 +732,24 @@ pub fn tezos_app() -> App<'static, 'static> {
             .global(true)
             .takes_value(false)
             .help("Allows unsafe RPC functions, like adding/removing baker in runtime."))
+        .arg(Arg::with_name("rpc-acl-file")
+            .long("rpc-acl-file")
+            .global(true)
+            .takes_value(true)
+            .value_name("PATH")
+            .help("Path to a JSON file with the RPC access control lists per listen address, e.g.
+                       [{ \"address\": \"localhost\", \"whitelist\": [\"**\"] },
+                        { \"address\": \"0.0.0.0\", \"blacklist\": [\"POST /injection/**\", \"/dev/**\"] }]
+                       Rules are an optional HTTP method and a path pattern, `*` matches one path segment and a trailing `**` any number of segments.
+                       Requests on listen addresses without an entry are allowed, denied requests get 403."))
+        .arg(Arg::with_name("allow-all-rpc")
+            .long("allow-all-rpc")
+            .global(true)
+            .takes_value(true)
+            .multiple(true)
+            .value_name("ADDRESS")
+            .help("Allows all RPCs on the listen address (e.g. localhost, 127.0.0.1:18732), takes precedence over --rpc-acl-file.")
+            .validator(parse_validator_fn!(ListenAddress, "Value must be an IP address or localhost, with an optional port")))
         .subcommand(
             clap::SubCommand::with_name("replay")
                 .arg(Arg::with_name("from-block")
@@ -723,6 +847,76 @@ pub fn tezos_app() -> App<'static, 'static> {
                              }
                          }
                      }))
+                .arg(Arg::with_name("format")
+                     .long("format")
+                     .takes_value(true)
+                     .value_name("FORMAT")
+                     .display_order(2)
+                     .required(false)
+                     .possible_values(&SnapshotFormat::possible_values())
+                     .default_value("tezedge")
+                     .help("Format of the snapshot, 'tezedge' copies the storage directories, \
+                            'full' and 'rolling' produce an Octez compatible snapshot file"))
+        ).subcommand(
+            clap::SubCommand::with_name("import-octez-snapshot")
+                .arg(Arg::with_name("from")
+                     .long("from")
+                     .takes_value(true)
+                     .value_name("PATH")
+                     .display_order(0)
+                     .required(true)
+                     .help("Path to the Octez '.full' or '.rolling' snapshot file")
+                     .validator(|v| {
+                         if Path::new(&v).is_file() {
+                             Ok(())
+                         } else {
+                             Err(format!("Snapshot file '{}' does not exist", v))
+                         }
+                     }))
+        ).subcommand(
+            clap::SubCommand::with_name("replay-actions")
+                .about("Replays the shell automaton actions recorded with --record-shell-automaton-actions \
+                        and reports the first action after which the state differs from the recorded snapshot")
+                .arg(Arg::with_name("from-action")
+                     .long("from-action")
+                     .takes_value(true)
+                     .value_name("ACTION_ID")
+                     .display_order(0)
+                     .help("Start from the closest state snapshot recorded before this action, the first snapshot by default")
+                     .validator(parse_validator_fn!(u64, "Value must be a valid number")))
+                .arg(Arg::with_name("to-action")
+                     .long("to-action")
+                     .takes_value(true)
+                     .value_name("ACTION_ID")
+                     .display_order(1)
+                     .help("Replay until this action, the last recorded action by default")
+                     .validator(parse_validator_fn!(u64, "Value must be a valid number")))
+        ).subcommand(
+            clap::SubCommand::with_name("migrate-maindb")
+                .about("Copies the main database (--maindb-backend) to an empty database with another backend \
+                        and verifies row counts and checksums of every column")
+                .arg(Arg::with_name("target-backend")
+                     .long("target-backend")
+                     .takes_value(true)
+                     .required(true)
+                     .value_name("STRING")
+                     .display_order(0)
+                     .possible_values(&TezedgeDatabaseBackendConfiguration::possible_values())
+                     .help("Backend of the new main database"))
+                .arg(Arg::with_name("target-db-path")
+                     .long("target-db-path")
+                     .takes_value(true)
+                     .required(true)
+                     .value_name("PATH")
+                     .display_order(1)
+                     .help("Directory of the new main database, it must not contain any data"))
+                .arg(Arg::with_name("batch-size")
+                     .long("batch-size")
+                     .takes_value(true)
+                     .value_name("NUM")
+                     .display_order(2)
+                     .help("Number of rows written to the new main database at once")
+                     .validator(parse_validator_fn!(usize, "Value must be a valid number")))
         );
     app
 }
@@ -902,33 +1096,60 @@ fn import_snapshot_app() -> App<'static, 'static> {
                 }),
         )
         .subcommand(
-            clap::SubCommand::with_name("import-snapshot").arg(
-                Arg::with_name("from")
-                    .long("from")
-                    .takes_value(true)
-                    .value_name("PATH")
-                    .required(true)
-                    .help("Url to the snapshot"), // .validator(|v| {
-                                                  //     let dir = Path::new(&v);
-                                                  //     if dir.exists() {
-                                                  //         if dir.is_file() {
-                                                  //             Ok(())
-                                                  //         } else {
-                                                  //             Err(format!("Required '{}' exists, but is not a file!", v))
-                                                  //         }
-                                                  //     } else {
-                                                  //         Err(format!("Required '{}' file does not exist", v))
-                                                  //     }
-                                                  // })
-            ),
+            clap::SubCommand::with_name("import-snapshot")
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .takes_value(true)
+                        .value_name("URL|PATH")
+                        .required(true)
+                        .help("Url or local path to the snapshot tarball"),
+                )
+                .arg(
+                    Arg::with_name("maindb-backend")
+                        .long("maindb-backend")
+                        .takes_value(true)
+                        .value_name("STRING")
+                        .possible_values(&TezedgeDatabaseBackendConfiguration::possible_values())
+                        .default_value(Storage::DEFAULT_MAINDB)
+                        .help("Main database backend of the snapshot, used to verify the imported block headers"),
+                ),
         );
 
     app
 }
 
+/// Where the snapshot tarball is imported from
+pub enum SnapshotSource {
+    /// Snapshot is downloaded first, an interrupted download is resumed
+    Url(Url),
+    /// Snapshot is read directly from the local file
+    File(PathBuf),
+}
+
+impl FromStr for SnapshotSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<Url>() {
+            Ok(url) if url.scheme() == "file" => url
+                .to_file_path()
+                .map(SnapshotSource::File)
+                .map_err(|_| format!("Invalid file url: {}", s)),
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {
+                Ok(SnapshotSource::Url(url))
+            }
+            Ok(url) => Err(format!("Unsupported url scheme: {}", url.scheme())),
+            // not an absolute url, so it is a local path
+            Err(_) => Ok(SnapshotSource::File(PathBuf::from(s))),
+        }
+    }
+}
+
 pub struct ImportSnapshot {
-    pub from: Url,
+    pub from: SnapshotSource,
     pub to: PathBuf,
+    pub main_db: TezedgeDatabaseBackendConfiguration,
 }
 
 impl ImportSnapshot {
@@ -940,15 +1161,27 @@ impl ImportSnapshot {
             let from = import
                 .value_of("from")
                 .unwrap()
-                .parse::<Url>()
-                .expect("Provided value cannot be converted to URL");
+                .parse::<SnapshotSource>()
+                .unwrap_or_else(|e| panic!("Invalid snapshot source, reason: {}", e));
+
+            let main_db = import
+                .value_of("maindb-backend")
+                .unwrap_or(Storage::DEFAULT_MAINDB)
+                .parse::<TezedgeDatabaseBackendConfiguration>()
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "Expecting one value from {:?}, error: {:?}",
+                        TezedgeDatabaseBackendConfiguration::possible_values(),
+                        e
+                    )
+                });
 
             let to = args
                 .value_of("tezos-data-dir")
                 .unwrap_or("/tmp/tezedge")
                 .parse::<PathBuf>()
                 .expect("Provided value cannot be converted to path");
-            ImportSnapshot { from, to }
+            ImportSnapshot { from, to, main_db }
         })
     }
 }
@@ -1080,9 +1313,69 @@ impl Environment {
                 );
             });
 
-            StorageSnapshot { block, target_path }
+            let format = args
+                .value_of("format")
+                .unwrap_or("tezedge")
+                .parse::<SnapshotFormat>()
+                .unwrap_or_else(|e| panic!("{}", e));
+
+            StorageSnapshot {
+                block,
+                target_path,
+                format,
+            }
         });
 
+        let import_octez_snapshot = args
+            .subcommand_matches("import-octez-snapshot")
+            .map(|args| {
+                args.value_of("from")
+                    .unwrap()
+                    .parse::<PathBuf>()
+                    .expect("Provided value cannot be converted to path")
+            });
+
+        let replay_actions = args
+            .subcommand_matches("replay-actions")
+            .map(|args| ReplayActions {
+                from_action_id: args.value_of("from-action").map(|v| {
+                    v.parse::<u64>()
+                        .expect("Provided value cannot be converted to number")
+                }),
+                to_action_id: args.value_of("to-action").map(|v| {
+                    v.parse::<u64>()
+                        .expect("Provided value cannot be converted to number")
+                }),
+            });
+
+        let migrate_maindb = args
+            .subcommand_matches("migrate-maindb")
+            .map(|args| MigrateMainDb {
+                target_backend: args
+                    .value_of("target-backend")
+                    .unwrap()
+                    .parse::<TezedgeDatabaseBackendConfiguration>()
+                    .unwrap_or_else(|e| {
+                        panic!(
+                            "Expecting one value from {:?}, error: {:?}",
+                            TezedgeDatabaseBackendConfiguration::possible_values(),
+                            e
+                        )
+                    }),
+                target_db_path: args
+                    .value_of("target-db-path")
+                    .unwrap()
+                    .parse::<PathBuf>()
+                    .expect("Provided value cannot be converted to path"),
+                batch_size: args.value_of("batch-size").map_or(
+                    storage::database::backend_migration::DEFAULT_MIGRATION_BATCH_SIZE,
+                    |v| {
+                        v.parse::<usize>()
+                            .expect("Provided value cannot be converted to number")
+                    },
+                ),
+            });
+
         let log_targets: HashSet<String> = match args.values_of("log") {
             Some(v) => v.map(String::from).collect(),
             None => std::iter::once("terminal".to_string()).collect(),
@@ -1261,11 +1554,42 @@ impl Environment {
                     .expect("incorrect value"),
             },
             rpc: crate::configuration::Rpc {
-                listener_port: args
-                    .value_of("rpc-port")
-                    .unwrap_or("")
-                    .parse::<u16>()
-                    .expect("Was expecting value of rpc-port"),
+                listeners: {
+                    let tls = match (args.value_of("rpc-tls-cert"), args.value_of("rpc-tls-key")) {
+                        (Some(cert_path), Some(key_path)) => Some(RpcTlsConfiguration {
+                            cert_path: cert_path.into(),
+                            key_path: key_path.into(),
+                        }),
+                        _ => None,
+                    };
+                    match args.values_of("rpc-listener") {
+                        Some(listeners) => listeners
+                            .map(|listener| {
+                                let spec = listener
+                                    .parse::<RpcListenerSpec>()
+                                    .expect("Provided value cannot be converted to RPC listener");
+                                if spec.tls && tls.is_none() {
+                                    panic!(
+                                        "RPC listener `{}` requires --rpc-tls-cert and --rpc-tls-key",
+                                        listener
+                                    );
+                                }
+                                spec.into_configuration(tls.as_ref())
+                            })
+                            .collect(),
+                        None => {
+                            let port = args
+                                .value_of("rpc-port")
+                                .unwrap_or("")
+                                .parse::<u16>()
+                                .expect("Was expecting value of rpc-port");
+                            let mut listener =
+                                RpcListenerConfiguration::new(([0, 0, 0, 0], port).into());
+                            listener.tls = tls;
+                            vec![listener]
+                        }
+                    }
+                },
                 websocket_cfg: args.value_of("websocket-address").and_then(|address| {
                     address.parse::<SocketAddr>().map_or(None, |socket_addrs| {
                         let max_connections = args
@@ -1277,6 +1601,54 @@ impl Environment {
                     })
                 }),
                 allow_unsafe_rpc: args.is_present("allow-unsafe-rpc"),
+                acl: {
+                    let mut acl = match args.value_of("rpc-acl-file") {
+                        Some(path) => RpcAcl::from_file(path)
+                            .unwrap_or_else(|e| panic!("Invalid --rpc-acl-file: {}", e)),
+                        None => RpcAcl::default(),
+                    };
+                    if let Some(addresses) = args.values_of("allow-all-rpc") {
+                        for address in addresses {
+                            acl.allow_all(
+                                address
+                                    .parse()
+                                    .expect("Provided value cannot be converted to listen address"),
+                            );
+                        }
+                    }
+                    acl
+                },
+                limits: RpcLimitsConfiguration {
+                    rate_limit: args.value_of("rpc-rate-limit").map(|limit| {
+                        limit
+                            .parse()
+                            .expect("Provided value cannot be converted to RPC rate limit")
+                    }),
+                    rate_limit_exempt: args
+                        .values_of("rpc-rate-limit-exempt")
+                        .map(|addresses| {
+                            addresses
+                                .map(|address| {
+                                    address
+                                        .parse()
+                                        .expect("Provided value cannot be converted to IP address")
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    concurrency_limits: args
+                        .values_of("rpc-concurrency-limit")
+                        .map(|limits| {
+                            limits
+                                .map(|limit| {
+                                    limit.parse().expect(
+                                        "Provided value cannot be converted to RPC concurrency limit",
+                                    )
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                },
             },
             logging: crate::configuration::Logging {
                 slog: SlogConfig {
@@ -1428,10 +1800,44 @@ impl Environment {
                     ),
                 };
 
+                let schema_migration = SchemaMigrationOptions {
+                    dry_run: args.is_present("maindb-migration-dry-run"),
+                    backup_dir: Some(match args.value_of("maindb-backup-dir") {
+                        Some(path) => get_final_path(
+                            &tezos_data_dir,
+                            path.parse::<PathBuf>()
+                                .expect("Provided value cannot be converted to path"),
+                        ),
+                        None => db_path.join("db_backup"),
+                    }),
+                };
+
+                let history_mode = args
+                    .value_of("history-mode")
+                    .unwrap_or(Storage::DEFAULT_HISTORY_MODE)
+                    .parse::<HistoryMode>()
+                    .unwrap_or_else(|e| panic!("{}", e));
+
+                // Explicit configuration wins, otherwise the history mode decides,
+                // the current cycle and `offset` cycles behind it are preserved
+                let context_gc_preserved_cycles = args
+                    .value_of("context-gc-preserved-cycles")
+                    .map(|value| {
+                        value
+                            .parse::<usize>()
+                            .expect("Provided value cannot be converted to number")
+                    })
+                    .or_else(|| {
+                        history_mode
+                            .cycle_offset()
+                            .map(|offset| offset as usize + 1)
+                    });
+
                 crate::configuration::Storage {
                     db,
                     context_storage_configuration,
                     main_db: maindb_backend,
+                    schema_migration,
                     db_path,
                     context_stats_db_path,
                     compute_context_action_tree_hashes,
@@ -1481,6 +1887,8 @@ impl Environment {
                             .parse::<u64>()
                             .expect("Provided value cannot be converted to number"),
                     ),
+                    history_mode,
+                    context_gc_preserved_cycles,
                 }
             },
             identity: crate::configuration::Identity {
@@ -1515,6 +1923,9 @@ impl Environment {
             },
             replay,
             snapshot,
+            import_octez_snapshot,
+            replay_actions,
+            migrate_maindb,
             tokio_threads: args
                 .value_of("tokio-threads")
                 .unwrap_or("0")
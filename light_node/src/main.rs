@@ -8,7 +8,7 @@ use std::path::Path;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use configuration::TezedgeEnv;
+use configuration::{SnapshotSource, TezedgeEnv};
 use reqwest::Client;
 use slog::{error, info, warn, Logger};
 use tezedge_actor_system::actors::*;
@@ -21,15 +21,22 @@ use shell::shell_automaton_manager::{
     ApplyBlockCallback, ApplyBlockResult, ShellAutomatonManager, ShellAutomatonMsg,
 };
 use shell::ShellCompatibilityVersion;
+use storage::database::backend_migration::migrate_backend;
+use storage::database::error::Error as DatabaseError;
+use storage::database::schema_migration::SchemaMigrations;
+use storage::database::tezedge_database::TezedgeDatabase;
 use storage::persistent::sequence::Sequences;
-use storage::persistent::{open_cl, CommitLogSchema};
+use storage::persistent::{maindb_columns, open_cl, open_main_db, CommitLogSchema};
 use storage::{
     hydrate_current_head, resolve_storage_init_chain_data, BlockHeaderWithHash, BlockStorage,
     PersistentStorage, StorageInitInfo,
 };
 use storage::{
-    initializer::{initialize_rocksdb, GlobalRocksDbCacheHolder, MainChain, RocksDbCache},
-    BlockMetaStorage, Replay,
+    initializer::{
+        initialize_rocksdb, DbsRocksDbTableInitializer, GlobalRocksDbCacheHolder, MainChain,
+        RocksDbCache, RocksDbConfig,
+    },
+    BlockMetaStorage, Replay, SnapshotFormat,
 };
 use tezos_api::environment;
 use tezos_api::ffi::TezosRuntimeConfiguration;
@@ -38,7 +45,7 @@ use tezos_identity::Identity;
 use tezos_messages::Head;
 use tezos_protocol_ipc_client::{ProtocolRunnerApi, ProtocolRunnerConfiguration};
 
-use crate::configuration::Environment;
+use crate::configuration::{Environment, MigrateMainDb, ReplayActions};
 use crate::notification_integration::RpcNotificationCallbackActor;
 use crate::snapshot_command::snapshot_storage;
 use storage::database::tezedge_database::TezedgeDatabaseBackendConfiguration;
@@ -47,6 +54,7 @@ use storage::initializer::initialize_maindb;
 mod configuration;
 mod identity;
 mod notification_integration;
+mod octez_snapshot;
 mod snapshot_command;
 mod system;
 
@@ -92,9 +100,20 @@ fn create_protocol_runner_configuration(
         env.storage.context_storage_configuration.clone(),
         env.ffi.protocol_runner.clone(),
         env.logging.slog.level,
+        env.storage.context_gc_preserved_cycles,
     )
 }
 
+/// Context history is pruned by the persistent context garbage collector running inside
+/// the protocol runner, see `Storage::context_gc_preserved_cycles`.
+fn log_context_history_mode(env: &crate::configuration::Environment, log: &Logger) {
+    if let Some(preserved_cycles) = env.storage.context_gc_preserved_cycles {
+        info!(log, "Context history pruning enabled";
+            "history_mode" => env.storage.history_mode.to_string(),
+            "preserved_cycles" => preserved_cycles);
+    }
+}
+
 fn block_on_actors(
     env: crate::configuration::Environment,
     init_storage_data: StorageInitInfo,
@@ -103,6 +122,8 @@ fn block_on_actors(
     mut blocks_replay: Option<Vec<Arc<BlockHash>>>,
     log: Logger,
 ) {
+    log_context_history_mode(&env, &log);
+
     // if feeding is started, than run chain manager
     let is_sandbox = env.tezos_network == environment::TezosEnvironment::Sandbox;
     // version
@@ -165,6 +186,7 @@ fn block_on_actors(
         shell_compatibility_version.clone(),
         env.p2p.clone(),
         env.identity.expected_pow,
+        env.storage.history_mode,
         init_storage_data.clone(),
         protocol_runner_configuration,
         context_init_status_sender,
@@ -199,7 +221,7 @@ fn block_on_actors(
     let mut rpc_server = RpcServer::new(
         log.clone(),
         rpc_shell_automaton_channel,
-        ([0, 0, 0, 0], env.rpc.listener_port).into(),
+        env.rpc.listeners.clone(),
         tokio_runtime.handle().clone(),
         &persistent_storage,
         Arc::clone(&tezos_protocol_api),
@@ -211,6 +233,8 @@ fn block_on_actors(
             .context_storage_configuration
             .tezedge_is_enabled(),
         env.rpc.allow_unsafe_rpc,
+        env.rpc.acl.clone(),
+        env.rpc.limits.clone(),
     );
     let _ = RpcNotificationCallbackActor::actor(
         actor_system.as_ref(),
@@ -471,6 +495,146 @@ fn collect_replayed_blocks(
     blocks
 }
 
+/// Replays the recorded shell automaton actions, exits with an error
+/// code if the replayed state diverges from the recorded one.
+fn replay_shell_automaton_actions(
+    persistent_storage: &PersistentStorage,
+    replay: &ReplayActions,
+    log: &Logger,
+) {
+    let report = shell_automaton::replay::replay_from_storage(
+        persistent_storage,
+        replay.from_action_id,
+        replay.to_action_id,
+    )
+    .unwrap_or_else(|e| panic!("Shell automaton actions replay failed, reason: {}", e));
+
+    info!(log, "Shell automaton actions replayed";
+        "from_action_id" => u64::from(report.initial_action_id),
+        "to_action_id" => u64::from(report.last_action_id),
+        "actions" => report.replayed_actions,
+        "compared_snapshots" => report.compared_snapshots);
+
+    if let Some(divergence) = report.divergence {
+        error!(log, "Replayed state diverged from the recorded one";
+            "action_id" => u64::from(divergence.action.id),
+            "action" => format!("{:?}", divergence.action.action),
+            "last_matched_action_id" => u64::from(divergence.last_matched_action_id),
+            "path" => divergence.path.as_str(),
+            "recorded" => divergence.recorded.to_string(),
+            "replayed" => divergence.replayed.to_string());
+        std::process::exit(1);
+    }
+}
+
+/// Copies the main database to an empty database with another backend,
+/// exits with an error code if the copy doesn't match the original.
+fn migrate_maindb(
+    env: &Environment,
+    persistent_storage: &PersistentStorage,
+    migrate: &MigrateMainDb,
+    log: &Logger,
+) {
+    if migrate.target_db_path == env.storage.db.db_path {
+        panic!("Main database migration failed, reason: target is the current main database");
+    }
+    let target_config = RocksDbConfig {
+        db_path: migrate.target_db_path.clone(),
+        ..env.storage.db.clone()
+    };
+
+    // IMPORTANT: Cache object must live at least as long as DB (returned by open_kv)
+    let kv_cache = RocksDbCache::new_lru_cache(env.storage.db.cache_size)
+        .expect("Failed to initialize RocksDB cache (target db)");
+    let target = open_maindb_unchecked(env, &target_config, migrate.target_backend, &kv_cache, log)
+        .expect("Failed to create MainDB database (target db)");
+
+    info!(log, "Migrating main database";
+        "from" => format!("{:?}", env.storage.main_db),
+        "to" => format!("{:?}", migrate.target_backend),
+        "target_db_path" => migrate.target_db_path.to_string_lossy().to_string());
+    let report = migrate_backend(
+        &persistent_storage.main_db(),
+        &target,
+        &maindb_columns(),
+        migrate.batch_size,
+        log,
+    )
+    .unwrap_or_else(|e| panic!("Main database migration failed, reason: {}", e));
+
+    for column in report
+        .columns
+        .iter()
+        .filter(|column| !column.is_consistent())
+    {
+        error!(log, "Migrated column differs from the original";
+            "column" => column.column,
+            "rows" => column.source.rows,
+            "migrated_rows" => column.target.rows,
+            "checksum" => column.source.checksum,
+            "migrated_checksum" => column.target.checksum);
+    }
+    if !report.is_consistent() {
+        std::process::exit(1);
+    }
+    info!(log, "Main database migrated and verified";
+        "rows" => report.rows(),
+        "columns" => report.columns.len(),
+        "duration" => format!("{:?}", report.duration));
+}
+
+/// Opens the main database, without upgrading or checking its schema.
+fn open_maindb_unchecked(
+    env: &Environment,
+    config: &RocksDbConfig<DbsRocksDbTableInitializer>,
+    backend: TezedgeDatabaseBackendConfiguration,
+    kv_cache: &RocksDbCache,
+    log: &Logger,
+) -> Result<TezedgeDatabase, DatabaseError> {
+    let kv = match backend {
+        TezedgeDatabaseBackendConfiguration::RocksDB => {
+            let main_chain = MainChain::new(
+                env.tezos_network_config
+                    .main_chain_id()
+                    .expect("Failed to decode chainId"),
+                env.tezos_network_config.version.clone(),
+            );
+            Some(
+                initialize_rocksdb(log, kv_cache, config, &main_chain).map_err(|e| {
+                    DatabaseError::DatabaseIncompatibility {
+                        name: format!("Failed to open RocksDB database, reason: {}", e),
+                    }
+                })?,
+            )
+        }
+        _ => None,
+    };
+    open_main_db(kv, config, backend, log.clone())
+}
+
+/// Logs the schema migrations needed to upgrade the main database.
+fn plan_maindb_schema_migration(env: &Environment, log: &Logger) {
+    // IMPORTANT: Cache object must live at least as long as DB (returned by open_kv)
+    let kv_cache = RocksDbCache::new_lru_cache(env.storage.db.cache_size)
+        .expect("Failed to initialize RocksDB cache (db)");
+    let db = open_maindb_unchecked(env, &env.storage.db, env.storage.main_db, &kv_cache, log)
+        .map(Arc::new)
+        .expect("Failed to open MainDB database (db)");
+
+    let steps = SchemaMigrations::main_db()
+        .run(
+            &db,
+            env.storage.db.expected_db_version,
+            &env.storage.schema_migration,
+            log,
+        )
+        .unwrap_or_else(|e| panic!("Main database schema migration failed, reason: {}", e));
+    info!(log, "Main database schema migration dry run finished";
+        "expected_db_version" => env.storage.db.expected_db_version,
+        "migrations" => steps.len(),
+        "destructive" => steps.iter().filter(|step| step.destructive).count());
+}
+
 #[cfg(dyncov)]
 fn set_gcov_handler() {
     use signal_hook::{consts::SIGUSR2, iterator::Signals};
@@ -504,31 +668,39 @@ fn main() {
             let tokio_runtime =
                 create_tokio_runtime_default().expect("Failed to create tokio runtime");
 
-            let url = env.from.clone();
-            if let Some(file_name) = url.path_segments().map(|segments| segments.last().unwrap()) {
-                let file_path_string = format!("/tmp/{}", file_name);
-                let file_path = Path::new(&file_path_string);
-
-                // Check whether the file that we want to download already exists, if not download it
-                // TODO: This does not include partially downloaded file. Solution should come later with the included manifest
-                if file_path.exists() {
-                    println!("Snapshot file already exists. Continuing");
-                } else {
-                    tokio_runtime.block_on(async {
-                        snapshot_command::download_file(
-                            &Client::new(),
-                            env.from.as_str(),
-                            file_path,
-                        )
-                        .await
-                        .unwrap_or_else(|_| panic!("Download failed"));
-                    });
+            let file_path = match &env.from {
+                SnapshotSource::File(path) => path.clone(),
+                SnapshotSource::Url(url) => {
+                    let file_name = url
+                        .path_segments()
+                        .and_then(|segments| segments.last())
+                        .filter(|file_name| !file_name.is_empty())
+                        .unwrap_or_else(|| panic!("Snapshot url '{}' has no file name", url));
+                    let file_path = Path::new("/tmp").join(file_name);
+
+                    // Check whether the file that we want to download already exists, if not download it,
+                    // a partially downloaded file is resumed
+                    if file_path.exists() {
+                        println!("Snapshot file already exists. Continuing");
+                    } else {
+                        tokio_runtime.block_on(async {
+                            snapshot_command::download_file(
+                                &Client::new(),
+                                url.as_str(),
+                                &file_path,
+                            )
+                            .await
+                            .unwrap_or_else(|e| panic!("Download failed, reason: {}", e));
+                        });
+                    }
+                    file_path
                 }
+            };
 
-                println!("Importing snapshot...");
-                snapshot_command::import_snapshot(file_path, &env.to);
-                println!("Snapshot imported successfully!");
-            }
+            println!("Importing snapshot...");
+            snapshot_command::import_snapshot(&file_path, &env.to, env.main_db)
+                .unwrap_or_else(|e| panic!("Snapshot import failed, reason: {}", e));
+            println!("Snapshot imported successfully!");
         }
         TezedgeEnv::Normal(env) => {
             // Creates loggers
@@ -565,6 +737,11 @@ fn main() {
             info!(log, "Loading databases...");
             let instant = Instant::now();
 
+            if env.storage.schema_migration.dry_run {
+                plan_maindb_schema_migration(&env, &log);
+                return;
+            }
+
             {
                 let persistent_storage = initialize_persistent_storage(&env, &log);
 
@@ -591,14 +768,48 @@ fn main() {
                         if let Some(snapshot) = &env.snapshot {
                             let target_block = snapshot.block.clone();
                             let target_path = snapshot.target_path.clone();
-                            snapshot_storage(
-                                env,
-                                persistent_storage,
-                                init_storage_data,
-                                target_block,
-                                target_path,
-                                log,
+                            let format = snapshot.format;
+                            match format {
+                                SnapshotFormat::Tezedge => snapshot_storage(
+                                    env,
+                                    persistent_storage,
+                                    init_storage_data,
+                                    target_block,
+                                    target_path,
+                                    log,
+                                ),
+                                _ => {
+                                    octez_snapshot::export_snapshot(
+                                        &env,
+                                        &persistent_storage,
+                                        &init_storage_data,
+                                        target_block,
+                                        &target_path,
+                                        format,
+                                        &log,
+                                    )
+                                    .unwrap_or_else(|e| {
+                                        panic!("Snapshot export failed, reason: {}", e)
+                                    });
+                                }
+                            }
+                        } else if let Some(from) = &env.import_octez_snapshot {
+                            octez_snapshot::import_snapshot(
+                                &env,
+                                &persistent_storage,
+                                &init_storage_data,
+                                from,
+                                &log,
                             )
+                            .unwrap_or_else(|e| panic!("Snapshot import failed, reason: {}", e));
+                        } else if let Some(replay_actions) = &env.replay_actions {
+                            replay_shell_automaton_actions(
+                                &persistent_storage,
+                                replay_actions,
+                                &log,
+                            );
+                        } else if let Some(migrate) = &env.migrate_maindb {
+                            migrate_maindb(&env, &persistent_storage, migrate, &log);
                         } else {
                             // Validate zcash-params
                             info!(log, "Checking zcash-params for sapling...");
@@ -687,6 +898,7 @@ fn initialize_persistent_storage(env: &Environment, log: &Logger) -> PersistentS
             env.storage.db.expected_db_version,
             &main_chain,
             env.storage.main_db,
+            &env.storage.schema_migration,
         )
         .expect("Failed to create/initialize MainDB database (db)"),
         TezedgeDatabaseBackendConfiguration::RocksDB => {
@@ -700,6 +912,7 @@ fn initialize_persistent_storage(env: &Environment, log: &Logger) -> PersistentS
                 env.storage.db.expected_db_version,
                 &main_chain,
                 env.storage.main_db,
+                &env.storage.schema_migration,
             )
             .expect("Failed to create/initialize MainDB database (db)")
         }
@@ -710,6 +923,7 @@ fn initialize_persistent_storage(env: &Environment, log: &Logger) -> PersistentS
             env.storage.db.expected_db_version,
             &main_chain,
             env.storage.main_db,
+            &env.storage.schema_migration,
         )
         .expect("Failed to create/initialize MainDB database (db)"),
     };
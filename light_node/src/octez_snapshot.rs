@@ -0,0 +1,2000 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Octez `.full` and `.rolling` snapshots.
+//!
+//! Export writes a single tar file `<chain_name>-<block_hash>-<level>.<full|rolling>`
+//! into the target path, import restores such a file into the configured storage.
+//!
+//! ```
+//! ./target/release/light-node \
+//!     --config-file ./light_node/etc/tezedge/tezedge.config \
+//!     --tezos-data-dir /path/to/source \
+//!     snapshot \
+//!     --target-path /path/to/target \
+//!     --format rolling
+//!
+//! ./target/release/light-node \
+//!     --config-file ./light_node/etc/tezedge/tezedge.config \
+//!     --tezos-data-dir /path/to/empty/target \
+//!     import-octez-snapshot \
+//!     --from /path/to/target/TEZOS_MAINNET-BL...-1234.rolling
+//! ```
+//!
+//! The layout follows the Octez tar snapshot format (`snapshots.ml`), entries are:
+//!
+//! - `VERSION` - JSON, `{"version": <OCTEZ_SNAPSHOT_VERSION>}`
+//! - `METADATA` - JSON, chain name, history mode, target block, level, timestamp and
+//!   number of context elements
+//! - `BLOCK_DATA` - target block header, its operations, predecessor header,
+//!   resulting context hash and the block/operations metadata hashes
+//! - `PROTOCOLS` - protocol activations (proto level, activation block, protocol hash)
+//! - `CONTEXT` - context dump of the target block, produced by the protocol runner
+//!   (same code path as `tezos-node snapshot export`)
+//! - `cemented/<start_level>_<end_level>` - blocks of the cycles below the last allowed
+//!   fork level of the target block, one file per cycle. The file starts with the
+//!   offsets of its blocks (8 bytes each), followed by the blocks, each one as a
+//!   length prefixed `Block_repr`.
+//! - `floating/floating_blocks` - blocks above the cemented cycles in ascending order,
+//!   each one as a length prefixed `Block_repr`. The target block is the last one and
+//!   the only one that is required to carry metadata.
+//!
+//! Binary entries use the `data_encoding` conventions: big endian integers,
+//! `0x00`/`0xff` tagged options and 4 byte length prefixed dynamic fields and lists.
+//!
+//! A full snapshot contains all blocks down to level 1, a rolling snapshot only
+//! `max_operations_ttl` blocks below the target block, its first cemented cycle may
+//! be partial. Export fails if the history mode of the node already pruned some of them.
+//!
+//! Import reads the archive as a stream, blocks are checked to form a chain and stored
+//! one by one, only `CONTEXT` is written to disk for the protocol runner to restore it.
+//! Header protocol data of the target block in JSON is parsed by its protocol
+//! (`helpers/parse/block`), the other blocks carry no metadata.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use slog::{info, Logger};
+use tar::{Archive, Builder, Header};
+use tempfile::tempdir_in;
+use thiserror::Error;
+
+use crypto::hash::{
+    BlockHash, BlockMetadataHash, ChainId, ContextHash, HashTrait, OperationMetadataHash,
+    OperationMetadataListListHash, ProtocolHash,
+};
+use rpc::helpers::BlockHeaderShellInfo;
+use storage::cycle_eras_storage::CycleEra;
+use storage::{
+    initialize_storage_with_genesis_block, store_commit_genesis_result, BlockAdditionalData,
+    BlockHeaderWithHash, BlockJsonData, BlockMetaStorage, BlockMetaStorageReader, BlockReference,
+    BlockStorage, BlockStorageReader, ChainMetaStorage, ChainMetaStorageReader, CycleErasStorage,
+    OperationsMetaStorage, OperationsStorage, OperationsStorageReader, PersistentStorage,
+    SnapshotFormat, StorageError, StorageInitInfo,
+};
+use tezos_api::ffi::{
+    ComputePathRequest, ProtocolRpcRequest, ProtocolRpcResponse, RpcMethod, RpcRequest,
+};
+use tezos_context_api::{ContextKvStoreConfiguration, TezosContextStorageConfiguration};
+use tezos_messages::p2p::binary_message::{BinaryRead, BinaryWrite};
+use tezos_messages::p2p::encoding::block_header::BlockHeader;
+use tezos_messages::p2p::encoding::operation::Operation;
+use tezos_messages::p2p::encoding::operations_for_blocks::{
+    OperationsForBlock, OperationsForBlocksMessage, Path as OperationsPath,
+};
+use tezos_messages::Head;
+use tezos_protocol_ipc_client::{ProtocolRunnerApi, ProtocolRunnerConnection};
+use tokio::process::Child;
+use tokio::runtime::Runtime;
+
+use crate::configuration::Environment;
+use crate::snapshot_command::{resolve_block_reference, terminate_or_kill};
+use crate::{create_protocol_runner_configuration, create_tokio_runtime};
+
+/// Version of the Octez snapshot format this module reads and writes
+pub const OCTEZ_SNAPSHOT_VERSION: u32 = 4;
+
+const VERSION_FILE: &str = "VERSION";
+const METADATA_FILE: &str = "METADATA";
+const BLOCK_DATA_FILE: &str = "BLOCK_DATA";
+const PROTOCOLS_FILE: &str = "PROTOCOLS";
+const CONTEXT_FILE: &str = "CONTEXT";
+const CEMENTED_DIR: &str = "cemented";
+const FLOATING_BLOCKS_FILE: &str = "floating/floating_blocks";
+
+#[derive(Error, Debug)]
+pub enum OctezSnapshotError {
+    #[error("I/O error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("Invalid snapshot json: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("Storage error: {0}")]
+    StorageError(#[from] StorageError),
+    #[error("Protocol runner error: {reason}")]
+    ProtocolRunnerError { reason: String },
+    #[error("Block {block_hash} is not available in the storage")]
+    MissingBlock { block_hash: String },
+    #[error("Block {block_hash} has no metadata in the storage")]
+    MissingMetadata { block_hash: String },
+    #[error("Context {context_hash} is not complete, reason: {reason}")]
+    IncompleteContext {
+        context_hash: String,
+        reason: String,
+    },
+    #[error("Unsupported snapshot version {found}, expected {expected}")]
+    UnsupportedVersion { found: u32, expected: u32 },
+    #[error("Snapshot is for chain {found}, but the node is configured for {expected}")]
+    ChainMismatch { found: String, expected: String },
+    #[error("Storage already contains blocks, current head is at level {level}")]
+    StorageNotEmpty { level: i32 },
+    #[error("Missing {file} in the snapshot")]
+    MissingFile { file: &'static str },
+    #[error("Invalid {file} in the snapshot, reason: {reason}")]
+    InvalidData { file: &'static str, reason: String },
+    #[error("Failed to encode snapshot data, reason: {reason}")]
+    EncodingError { reason: String },
+}
+
+impl OctezSnapshotError {
+    fn protocol_runner(error: impl std::fmt::Display) -> Self {
+        Self::ProtocolRunnerError {
+            reason: error.to_string(),
+        }
+    }
+
+    fn encoding(error: impl std::fmt::Display) -> Self {
+        Self::EncodingError {
+            reason: error.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SnapshotVersion {
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SnapshotMetadata {
+    chain_name: String,
+    history_mode: String,
+    block_hash: String,
+    level: i32,
+    timestamp: String,
+    context_elements: i64,
+}
+
+/// Target block of the snapshot, stored in `BLOCK_DATA`
+struct SnapshotBlockData {
+    block_header: BlockHeader,
+    operations: Vec<Vec<Operation>>,
+    predecessor_header: BlockHeader,
+    resulting_context_hash: ContextHash,
+    block_metadata_hash: Option<BlockMetadataHash>,
+    operations_metadata_hash: Option<OperationMetadataListListHash>,
+}
+
+/// Protocol activation, stored in `PROTOCOLS`
+struct ProtocolLevel {
+    proto_level: u8,
+    block_hash: BlockHash,
+    level: i32,
+    protocol_hash: ProtocolHash,
+}
+
+/// Block stored in `floating/floating_blocks`
+struct BlockRepr {
+    hash: BlockHash,
+    header: BlockHeader,
+    operations: Vec<Vec<Operation>>,
+    block_metadata_hash: Option<BlockMetadataHash>,
+    operations_metadata_hashes: Option<Vec<Vec<OperationMetadataHash>>>,
+    metadata: Option<BlockReprMetadata>,
+}
+
+struct BlockReprMetadata {
+    max_operations_ttl: u16,
+    last_allowed_fork_level: i32,
+    block_metadata: Vec<u8>,
+    operations_metadata: Vec<Vec<Vec<u8>>>,
+}
+
+/// Exports the storage at `target_block` (defaults to the current head) as an
+/// Octez snapshot file in `target_path`. Returns path of the created file.
+pub fn export_snapshot(
+    env: &Environment,
+    persistent_storage: &PersistentStorage,
+    init_storage_data: &StorageInitInfo,
+    target_block: Option<BlockReference>,
+    target_path: &Path,
+    format: SnapshotFormat,
+    log: &Logger,
+) -> Result<PathBuf, OctezSnapshotError> {
+    let history_mode = match format {
+        SnapshotFormat::OctezFull => "full",
+        SnapshotFormat::OctezRolling => "rolling",
+        SnapshotFormat::Tezedge => {
+            return Err(OctezSnapshotError::EncodingError {
+                reason: "tezedge format is not an Octez snapshot".to_owned(),
+            })
+        }
+    };
+
+    let block_storage = BlockStorage::new(persistent_storage);
+    let block_meta_storage = BlockMetaStorage::new(persistent_storage);
+    let operations_storage = OperationsStorage::new(persistent_storage);
+    let chain_meta_storage = ChainMetaStorage::new(persistent_storage);
+
+    let head = chain_meta_storage
+        .get_current_head(&init_storage_data.chain_id)?
+        .ok_or_else(|| OctezSnapshotError::MissingBlock {
+            block_hash: "current head".to_owned(),
+        })?;
+    let target_block_hash = resolve_block_reference(
+        target_block.unwrap_or(BlockReference::BlockHash(head.block_hash().clone())),
+        &block_storage,
+        &block_meta_storage,
+        &head,
+    );
+
+    let target = read_block_repr(
+        &target_block_hash,
+        true,
+        &block_storage,
+        &block_meta_storage,
+        &operations_storage,
+    )?;
+    let target_additional_data = block_meta_storage
+        .get_additional_data(&target_block_hash)?
+        .ok_or_else(|| OctezSnapshotError::MissingMetadata {
+            block_hash: target_block_hash.to_base58_check(),
+        })?;
+    if target.metadata.is_none() {
+        return Err(OctezSnapshotError::MissingMetadata {
+            block_hash: target_block_hash.to_base58_check(),
+        });
+    }
+    let predecessor_header = block_storage
+        .get(target.header.predecessor())?
+        .ok_or_else(|| OctezSnapshotError::MissingBlock {
+            block_hash: target.header.predecessor().to_base58_check(),
+        })?;
+    let context_hash = target.header.context().clone();
+    let block_data = SnapshotBlockData {
+        block_header: target.header.clone(),
+        operations: target.operations.clone(),
+        predecessor_header: predecessor_header.header.as_ref().clone(),
+        resulting_context_hash: context_hash.clone(),
+        block_metadata_hash: target_additional_data.block_metadata_hash().clone(),
+        operations_metadata_hash: target_additional_data.ops_metadata_hash().clone(),
+    };
+
+    info!(log, "Checking the context of the target block..."; "block_hash" => target_block_hash.to_base58_check(), "context_hash" => context_hash.to_base58_check());
+    check_context_complete(&env.storage.context_storage_configuration, &context_hash)?;
+
+    let mut protocols = BTreeMap::new();
+    protocols.insert(
+        predecessor_header.header.proto(),
+        ProtocolLevel {
+            proto_level: predecessor_header.header.proto(),
+            block_hash: predecessor_header.hash.clone(),
+            level: predecessor_header.header.level(),
+            protocol_hash: target_additional_data.protocol_hash().clone(),
+        },
+    );
+    protocols.insert(
+        target.header.proto(),
+        ProtocolLevel {
+            proto_level: target.header.proto(),
+            block_hash: target.hash.clone(),
+            level: target.header.level(),
+            protocol_hash: target_additional_data.next_protocol_hash().clone(),
+        },
+    );
+
+    let lowest_level = match format {
+        SnapshotFormat::OctezRolling => {
+            1.max(target.header.level() - i32::from(target_additional_data.max_operations_ttl()))
+        }
+        _ => 1,
+    };
+
+    let tmpdir = tempdir_in(target_path)?;
+
+    // Blocks are collected from the target down and written back in ascending order
+    let mut block_hashes = vec![target.hash.clone()];
+    let mut current = predecessor_header;
+    while current.header.level() >= lowest_level {
+        if let Some(additional_data) = block_meta_storage.get_additional_data(&current.hash)? {
+            protocols.insert(
+                current.header.proto(),
+                ProtocolLevel {
+                    proto_level: current.header.proto(),
+                    block_hash: current.hash.clone(),
+                    level: current.header.level(),
+                    protocol_hash: additional_data.next_protocol_hash().clone(),
+                },
+            );
+        }
+        block_hashes.push(current.hash.clone());
+        if current.header.level() == lowest_level {
+            break;
+        }
+        current = block_storage
+            .get(current.header.predecessor())?
+            .ok_or_else(|| OctezSnapshotError::MissingBlock {
+                block_hash: current.header.predecessor().to_base58_check(),
+            })?;
+    }
+
+    let cemented_until = target_additional_data.last_allowed_fork_level();
+    let cycle_eras = CycleErasStorage::new(persistent_storage)
+        .iterator()?
+        .into_iter()
+        .flat_map(|(_, eras)| eras)
+        .collect::<Vec<_>>();
+
+    info!(log, "Writing blocks..."; "from_level" => lowest_level, "to_level" => target.header.level(), "cemented_until" => cemented_until);
+
+    let block_files = write_blocks(
+        tmpdir.path(),
+        block_hashes.iter().rev().map(|block_hash| {
+            read_block_repr(
+                block_hash,
+                block_hash == &target.hash,
+                &block_storage,
+                &block_meta_storage,
+                &operations_storage,
+            )
+        }),
+        cemented_until,
+        &cycle_eras,
+    )?;
+
+    info!(log, "Dumping the context of the target block...");
+    let context_dump_path = tmpdir.path().join("context-dump");
+    let context_elements = dump_context(env, &context_hash, &context_dump_path, log)?;
+
+    let metadata = SnapshotMetadata {
+        chain_name: env.tezos_network_config.version.clone(),
+        history_mode: history_mode.to_owned(),
+        block_hash: target.hash.to_base58_check(),
+        level: target.header.level(),
+        timestamp: target
+            .header
+            .timestamp()
+            .to_rfc3339()
+            .map_err(OctezSnapshotError::encoding)?,
+        context_elements,
+    };
+
+    let file_name = format!(
+        "{}-{}-{}.{}",
+        metadata.chain_name, metadata.block_hash, metadata.level, history_mode
+    );
+    let snapshot_path = target_path.join(&file_name);
+    let partial_snapshot_path = tmpdir.path().join(&file_name);
+
+    info!(log, "Writing snapshot file..."; "path" => snapshot_path.display().to_string());
+
+    let mut builder = Builder::new(BufWriter::new(File::create(&partial_snapshot_path)?));
+    append_bytes(
+        &mut builder,
+        VERSION_FILE,
+        &serde_json::to_vec(&SnapshotVersion {
+            version: OCTEZ_SNAPSHOT_VERSION,
+        })?,
+    )?;
+    append_bytes(&mut builder, METADATA_FILE, &serde_json::to_vec(&metadata)?)?;
+
+    let mut writer = Writer::default();
+    encode_block_data(&mut writer, &block_data)?;
+    append_bytes(&mut builder, BLOCK_DATA_FILE, &writer.0)?;
+
+    let mut writer = Writer::default();
+    writer.list(protocols.values(), |w, protocol| {
+        w.u8(protocol.proto_level);
+        w.hash(&protocol.block_hash);
+        w.i32(protocol.level);
+        w.hash(&protocol.protocol_hash);
+        Ok(())
+    })?;
+    append_bytes(&mut builder, PROTOCOLS_FILE, &writer.0)?;
+
+    builder.append_path_with_name(&context_dump_path, CONTEXT_FILE)?;
+    for (name, path) in &block_files {
+        builder.append_path_with_name(path, name)?;
+    }
+    builder.into_inner()?.flush()?;
+
+    std::fs::rename(&partial_snapshot_path, &snapshot_path)?;
+
+    info!(log, "Snapshot exported"; "path" => snapshot_path.display().to_string(), "blocks" => block_hashes.len(), "cemented_cycles" => block_files.len() - 1, "context_elements" => context_elements);
+
+    Ok(snapshot_path)
+}
+
+/// Imports the Octez snapshot file `from` into the storage and context configured in `env`.
+///
+/// The storage must not contain any block besides genesis.
+pub fn import_snapshot(
+    env: &Environment,
+    persistent_storage: &PersistentStorage,
+    init_storage_data: &StorageInitInfo,
+    from: &Path,
+    log: &Logger,
+) -> Result<(), OctezSnapshotError> {
+    let chain_meta_storage = ChainMetaStorage::new(persistent_storage);
+
+    if let Some(head) = chain_meta_storage.get_current_head(&init_storage_data.chain_id)? {
+        if *head.level() > 0 {
+            return Err(OctezSnapshotError::StorageNotEmpty {
+                level: *head.level(),
+            });
+        }
+    }
+
+    let data_dir = env.storage.db_path.parent().unwrap_or(&env.storage.db_path);
+    let tmpdir = tempdir_in(data_dir)?;
+
+    let mut runner = ProtocolRunner::start(env, log)?;
+    let result = import_archive(
+        env,
+        &mut runner,
+        persistent_storage,
+        init_storage_data,
+        from,
+        tmpdir.path(),
+        log,
+    );
+    runner.stop()?;
+    result
+}
+
+fn import_archive(
+    env: &Environment,
+    runner: &mut ProtocolRunner,
+    persistent_storage: &PersistentStorage,
+    init_storage_data: &StorageInitInfo,
+    from: &Path,
+    tmp_path: &Path,
+    log: &Logger,
+) -> Result<(), OctezSnapshotError> {
+    let block_storage = BlockStorage::new(persistent_storage);
+    let block_meta_storage = BlockMetaStorage::new(persistent_storage);
+    let operations_meta_storage = OperationsMetaStorage::new(persistent_storage);
+    let chain_meta_storage = ChainMetaStorage::new(persistent_storage);
+    let chain_id = &init_storage_data.chain_id;
+    let ProtocolRunner {
+        tokio_runtime,
+        conn,
+        ..
+    } = runner;
+
+    let genesis_commit_hash = tokio_runtime
+        .block_on(conn.init_protocol_for_write(true, &env.storage.patch_context, None))
+        .map_err(OctezSnapshotError::protocol_runner)?
+        .genesis_commit_hash
+        .ok_or_else(|| OctezSnapshotError::ProtocolRunnerError {
+            reason: "Expected genesis commit hash not found".to_owned(),
+        })?;
+    let genesis_result = tokio_runtime
+        .block_on(conn.genesis_result_data(&genesis_commit_hash))
+        .map_err(OctezSnapshotError::protocol_runner)?;
+
+    info!(log, "Initializing genesis block data...");
+    initialize_storage_with_genesis_block(
+        &block_storage,
+        &block_meta_storage,
+        init_storage_data,
+        &env.tezos_network_config,
+        &genesis_commit_hash,
+        log,
+    )?;
+    store_commit_genesis_result(
+        &block_storage,
+        &block_meta_storage,
+        &chain_meta_storage,
+        &operations_meta_storage,
+        init_storage_data,
+        genesis_result,
+    )?;
+
+    let mut version_checked = false;
+    let mut metadata = None;
+    let mut block_data = None;
+    let mut protocols = None;
+    let mut context_restored = false;
+    let mut importer = BlockImporter::new(
+        persistent_storage,
+        chain_id,
+        &init_storage_data.genesis_block_header_hash,
+        log,
+    );
+
+    info!(log, "Importing snapshot..."; "from" => from.display().to_string());
+
+    let mut archive = Archive::new(BufReader::new(File::open(from)?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+
+        match path.as_str() {
+            VERSION_FILE => {
+                let version: SnapshotVersion = serde_json::from_reader(&mut entry)?;
+                if version.version != OCTEZ_SNAPSHOT_VERSION {
+                    return Err(OctezSnapshotError::UnsupportedVersion {
+                        found: version.version,
+                        expected: OCTEZ_SNAPSHOT_VERSION,
+                    });
+                }
+                version_checked = true;
+            }
+            METADATA_FILE => {
+                let snapshot_metadata: SnapshotMetadata = serde_json::from_reader(&mut entry)?;
+                if snapshot_metadata.chain_name != env.tezos_network_config.version {
+                    return Err(OctezSnapshotError::ChainMismatch {
+                        found: snapshot_metadata.chain_name,
+                        expected: env.tezos_network_config.version.clone(),
+                    });
+                }
+                metadata = Some(snapshot_metadata);
+            }
+            BLOCK_DATA_FILE => {
+                let data = read_entry(&mut entry)?;
+                let mut reader = Reader::new(BLOCK_DATA_FILE, &data);
+                block_data = Some(decode_block_data(&mut reader)?);
+                reader.finish()?;
+            }
+            PROTOCOLS_FILE => {
+                let data = read_entry(&mut entry)?;
+                let mut reader = Reader::new(PROTOCOLS_FILE, &data);
+                let protocol_levels = reader.list(|r| {
+                    Ok(ProtocolLevel {
+                        proto_level: r.u8()?,
+                        block_hash: r.hash()?,
+                        level: r.i32()?,
+                        protocol_hash: r.hash()?,
+                    })
+                })?;
+                reader.finish()?;
+                protocols = Some(
+                    protocol_levels
+                        .into_iter()
+                        .map(|protocol| (protocol.proto_level, protocol.protocol_hash))
+                        .collect::<BTreeMap<_, _>>(),
+                );
+            }
+            CONTEXT_FILE => {
+                let snapshot_metadata =
+                    metadata.as_ref().ok_or(OctezSnapshotError::MissingFile {
+                        file: METADATA_FILE,
+                    })?;
+                let block_data = block_data.as_ref().ok_or(OctezSnapshotError::MissingFile {
+                    file: BLOCK_DATA_FILE,
+                })?;
+
+                let context_path = tmp_path.join(CONTEXT_FILE);
+                let mut context_file = BufWriter::new(File::create(&context_path)?);
+                std::io::copy(&mut entry, &mut context_file)?;
+                context_file.flush()?;
+                drop(context_file);
+
+                info!(log, "Restoring context..."; "context_hash" => block_data.resulting_context_hash.to_base58_check(), "context_elements" => snapshot_metadata.context_elements);
+                tokio_runtime
+                    .block_on(conn.restore_context(
+                        block_data.resulting_context_hash.clone(),
+                        context_path.to_string_lossy().to_string(),
+                        snapshot_metadata.context_elements,
+                    ))
+                    .map_err(OctezSnapshotError::protocol_runner)?;
+                std::fs::remove_file(&context_path)?;
+                context_restored = true;
+            }
+            path => {
+                if is_block_file(path) && metadata.is_none() {
+                    return Err(OctezSnapshotError::MissingFile {
+                        file: METADATA_FILE,
+                    });
+                }
+                importer.import_entry(path, &mut entry, &mut |operations| {
+                    compute_operation_paths(tokio_runtime, conn, operations)
+                })?;
+            }
+        }
+    }
+
+    if !version_checked {
+        return Err(OctezSnapshotError::MissingFile { file: VERSION_FILE });
+    }
+    let metadata = metadata.ok_or(OctezSnapshotError::MissingFile {
+        file: METADATA_FILE,
+    })?;
+    let block_data = block_data.ok_or(OctezSnapshotError::MissingFile {
+        file: BLOCK_DATA_FILE,
+    })?;
+    let protocols = protocols.ok_or(OctezSnapshotError::MissingFile {
+        file: PROTOCOLS_FILE,
+    })?;
+    if !context_restored {
+        return Err(OctezSnapshotError::MissingFile { file: CONTEXT_FILE });
+    }
+
+    let invalid_block_data = |reason: String| OctezSnapshotError::InvalidData {
+        file: BLOCK_DATA_FILE,
+        reason,
+    };
+    let target = BlockHeaderWithHash::new(block_data.block_header.clone())
+        .map_err(|e| invalid_block_data(e.to_string()))?;
+    if target.hash.to_base58_check() != metadata.block_hash
+        || target.header.context() != &block_data.resulting_context_hash
+    {
+        return Err(invalid_block_data(format!(
+            "block {} does not match METADATA",
+            metadata.block_hash
+        )));
+    }
+    let predecessor = BlockHeaderWithHash::new(block_data.predecessor_header.clone())
+        .map_err(|e| invalid_block_data(e.to_string()))?;
+    if &predecessor.hash != target.header.predecessor() {
+        return Err(invalid_block_data(format!(
+            "predecessor {} is not the predecessor of block {}",
+            predecessor.hash.to_base58_check(),
+            metadata.block_hash
+        )));
+    }
+
+    let (lowest, target_block) = match (importer.lowest.take(), importer.last.take()) {
+        (Some(lowest), Some(last)) => (lowest, last),
+        _ => {
+            return Err(OctezSnapshotError::MissingFile {
+                file: FLOATING_BLOCKS_FILE,
+            })
+        }
+    };
+    let (target_metadata, operations_metadata_hashes) = match target_block {
+        BlockRepr {
+            hash,
+            metadata: Some(metadata),
+            operations_metadata_hashes,
+            ..
+        } if hash == target.hash => (metadata, operations_metadata_hashes),
+        _ => {
+            return Err(OctezSnapshotError::InvalidData {
+                file: FLOATING_BLOCKS_FILE,
+                reason: "target block with metadata is not the last block".to_owned(),
+            })
+        }
+    };
+
+    info!(log, "Parsing protocol data of the target block...");
+    let protocol_data_json = parse_protocol_data(tokio_runtime, conn, chain_id, &target)?;
+
+    // Protocol of the block is the one activated by its predecessor
+    block_meta_storage.put_block_additional_data(
+        &target.hash,
+        &BlockAdditionalData::new(
+            target_metadata.max_operations_ttl,
+            target_metadata.last_allowed_fork_level,
+            protocol_for_level(&protocols, block_data.predecessor_header.proto())?,
+            protocol_for_level(&protocols, target.header.proto())?,
+            block_data.block_metadata_hash.clone(),
+            block_data.operations_metadata_hash.clone(),
+            operations_metadata_hashes,
+        ),
+    )?;
+    block_storage.put_block_json_data(
+        &target.hash,
+        BlockJsonData::new(
+            protocol_data_json,
+            target_metadata.block_metadata,
+            target_metadata.operations_metadata,
+        ),
+    )?;
+
+    chain_meta_storage.set_caboose(chain_id, lowest)?;
+    chain_meta_storage.set_current_head(
+        chain_id,
+        Head::new(
+            target.hash.clone(),
+            target.header.level(),
+            target.header.fitness().clone(),
+        ),
+    )?;
+    block_storage.flush()?;
+
+    info!(log, "Snapshot imported"; "block_hash" => target.hash.to_base58_check(), "level" => target.header.level(), "blocks" => importer.count);
+
+    Ok(())
+}
+
+fn protocol_for_level(
+    protocols: &BTreeMap<u8, ProtocolHash>,
+    proto_level: u8,
+) -> Result<ProtocolHash, OctezSnapshotError> {
+    protocols
+        .get(&proto_level)
+        .cloned()
+        .ok_or_else(|| OctezSnapshotError::InvalidData {
+            file: PROTOCOLS_FILE,
+            reason: format!("no protocol for proto level {}", proto_level),
+        })
+}
+
+fn read_entry(entry: &mut impl Read) -> Result<Vec<u8>, OctezSnapshotError> {
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn append_bytes<W: Write>(
+    builder: &mut Builder<W>,
+    name: &str,
+    data: &[u8],
+) -> Result<(), OctezSnapshotError> {
+    let mut header = Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, name, data)?;
+    Ok(())
+}
+
+/// Pruned context history would silently produce an incomplete dump,
+/// so the whole tree of the commit is read first (TezEdge on-disk context only).
+fn check_context_complete(
+    context_storage_configuration: &TezosContextStorageConfiguration,
+    context_hash: &ContextHash,
+) -> Result<(), OctezSnapshotError> {
+    let tezedge = match context_storage_configuration {
+        TezosContextStorageConfiguration::TezEdgeOnly(tezedge)
+        | TezosContextStorageConfiguration::Both(_, tezedge) => tezedge,
+        TezosContextStorageConfiguration::IrminOnly(_) => return Ok(()),
+    };
+    let base_path = match &tezedge.backend {
+        ContextKvStoreConfiguration::OnDisk(options) => options.base_path.clone(),
+        _ => return Ok(()),
+    };
+
+    tezos_context::snapshot::reload_context_readonly(base_path)
+        .and_then(|context| tezos_context::snapshot::read_commit_tree(context, context_hash))
+        .map(|_| ())
+        .map_err(|e| OctezSnapshotError::IncompleteContext {
+            context_hash: context_hash.to_base58_check(),
+            reason: e.to_string(),
+        })
+}
+
+/// Protocol runner started for the duration of an export or import
+struct ProtocolRunner {
+    tokio_runtime: Runtime,
+    child: Child,
+    conn: ProtocolRunnerConnection,
+}
+
+impl ProtocolRunner {
+    fn start(env: &Environment, log: &Logger) -> Result<Self, OctezSnapshotError> {
+        let tokio_runtime = create_tokio_runtime(env)?;
+
+        let (_context_init_status_sender, context_init_status_receiver) =
+            tokio::sync::watch::channel(false);
+        let mut tezos_protocol_api = ProtocolRunnerApi::new(
+            create_protocol_runner_configuration(env),
+            context_init_status_receiver,
+            tokio_runtime.handle(),
+            log.clone(),
+        );
+
+        info!(log, "Initializing protocol runner...");
+        let (child, conn) = tokio_runtime.block_on(async {
+            let child = tezos_protocol_api
+                .start(None)
+                .await
+                .map_err(OctezSnapshotError::protocol_runner)?;
+            let conn = tezos_protocol_api
+                .connect()
+                .await
+                .map_err(OctezSnapshotError::protocol_runner)?;
+            Ok::<_, OctezSnapshotError>((child, conn))
+        })?;
+
+        Ok(Self {
+            tokio_runtime,
+            child,
+            conn,
+        })
+    }
+
+    fn stop(self) -> Result<(), OctezSnapshotError> {
+        let Self {
+            tokio_runtime,
+            mut child,
+            ..
+        } = self;
+        tokio_runtime
+            .block_on(terminate_or_kill(&mut child, "Done".into()))
+            .map_err(OctezSnapshotError::protocol_runner)
+    }
+}
+
+fn dump_context(
+    env: &Environment,
+    context_hash: &ContextHash,
+    path: &Path,
+    log: &Logger,
+) -> Result<i64, OctezSnapshotError> {
+    let mut runner = ProtocolRunner::start(env, log)?;
+    let ProtocolRunner {
+        tokio_runtime,
+        conn,
+        ..
+    } = &mut runner;
+
+    let context_elements = tokio_runtime.block_on(async {
+        conn.init_protocol_for_write(false, &env.storage.patch_context, None)
+            .await
+            .map_err(OctezSnapshotError::protocol_runner)?;
+        conn.dump_context(context_hash.clone(), path.to_string_lossy().to_string())
+            .await
+            .map_err(OctezSnapshotError::protocol_runner)
+    });
+
+    runner.stop()?;
+    context_elements
+}
+
+// `ComputePathRequest` is built from a `Vec`
+#[allow(clippy::ptr_arg)]
+fn compute_operation_paths(
+    tokio_runtime: &Runtime,
+    conn: &mut ProtocolRunnerConnection,
+    operations: &Vec<Vec<Operation>>,
+) -> Result<Vec<OperationsPath>, OctezSnapshotError> {
+    // Operation paths are not part of the snapshot
+    let request = ComputePathRequest::try_from(operations).map_err(OctezSnapshotError::encoding)?;
+    tokio_runtime
+        .block_on(conn.compute_path(request))
+        .map(|response| response.operations_hashes_path)
+        .map_err(OctezSnapshotError::protocol_runner)
+}
+
+/// Header protocol data in JSON is not part of the snapshot, it is parsed by the
+/// protocol of the block, from the restored context of the block.
+fn parse_protocol_data(
+    tokio_runtime: &Runtime,
+    conn: &mut ProtocolRunnerConnection,
+    chain_id: &ChainId,
+    block: &BlockHeaderWithHash,
+) -> Result<String, OctezSnapshotError> {
+    let mut body = serde_json::to_value(
+        BlockHeaderShellInfo::try_new(block).map_err(OctezSnapshotError::encoding)?,
+    )?;
+    body["protocol_data"] = serde_json::Value::String(hex::encode(block.header.protocol_data()));
+
+    let request = ProtocolRpcRequest {
+        block_header: block.header.as_ref().clone(),
+        chain_arg: "main".to_owned(),
+        chain_id: chain_id.clone(),
+        request: RpcRequest {
+            body: body.to_string(),
+            context_path: format!(
+                "/chains/main/blocks/{}/helpers/parse/block",
+                block.hash.to_base58_check()
+            ),
+            meth: RpcMethod::POST,
+            content_type: Some("application/json".to_owned()),
+            accept: None,
+        },
+    };
+
+    match tokio_runtime
+        .block_on(conn.call_protocol_rpc(request))
+        .map_err(OctezSnapshotError::protocol_runner)?
+    {
+        ProtocolRpcResponse::RPCOk(protocol_data_json) => Ok(protocol_data_json),
+        response => Err(OctezSnapshotError::ProtocolRunnerError {
+            reason: format!(
+                "Failed to parse protocol data of block {}: {:?}",
+                block.hash.to_base58_check(),
+                response
+            ),
+        }),
+    }
+}
+
+fn is_block_file(path: &str) -> bool {
+    path == FLOATING_BLOCKS_FILE || path.starts_with(CEMENTED_DIR)
+}
+
+/// First and last level of the cycle of `level`, `None` if no cycle era covers the level
+fn cycle_bounds(cycle_eras: &[CycleEra], level: i32) -> Option<(i32, i32)> {
+    let era = cycle_eras
+        .iter()
+        .filter(|era| *era.first_level() <= level)
+        .max_by_key(|era| *era.first_level())?;
+    let blocks_per_cycle = *era.blocks_per_cycle();
+    if blocks_per_cycle <= 0 {
+        return None;
+    }
+
+    let first_level =
+        era.first_level() + (level - era.first_level()) / blocks_per_cycle * blocks_per_cycle;
+    Some((first_level, first_level + blocks_per_cycle - 1))
+}
+
+/// Writes the blocks, in ascending order, into `dir`. Blocks of the cycles that end at
+/// `cemented_until` or below are written to the cemented cycle files, the blocks above
+/// them to the floating blocks file.
+///
+/// Returns the written files with their name in the snapshot.
+fn write_blocks(
+    dir: &Path,
+    blocks: impl IntoIterator<Item = Result<BlockRepr, OctezSnapshotError>>,
+    cemented_until: i32,
+    cycle_eras: &[CycleEra],
+) -> Result<Vec<(String, PathBuf)>, OctezSnapshotError> {
+    let cemented_dir = dir.join(CEMENTED_DIR);
+    std::fs::create_dir_all(&cemented_dir)?;
+    let floating_blocks_path = dir.join("floating_blocks");
+    let mut floating_blocks = BufWriter::new(File::create(&floating_blocks_path)?);
+
+    let mut files = Vec::new();
+    let mut cycle: Option<CementedCycleWriter> = None;
+    let mut floating = false;
+
+    for block in blocks {
+        let block = block?;
+        let level = block.header.level();
+        let mut writer = Writer::default();
+        writer.dynamic(|w| encode_block_repr(w, &block))?;
+
+        let cemented_cycle = cycle_bounds(cycle_eras, level)
+            .filter(|(_, last_level)| !floating && *last_level <= cemented_until);
+        match cemented_cycle {
+            Some((first_level, last_level)) => {
+                let mut current = match cycle.take() {
+                    Some(current) if current.last_level == last_level => current,
+                    previous => {
+                        if let Some(previous) = previous {
+                            files.push(previous.finish()?);
+                        }
+                        // First cycle of a rolling snapshot is partial
+                        CementedCycleWriter::create(
+                            &cemented_dir,
+                            first_level.max(level),
+                            last_level,
+                        )?
+                    }
+                };
+                current.write_block(level, &writer.0)?;
+                cycle = Some(current);
+            }
+            None => {
+                floating = true;
+                if let Some(previous) = cycle.take() {
+                    files.push(previous.finish()?);
+                }
+                floating_blocks.write_all(&writer.0)?;
+            }
+        }
+    }
+    if let Some(previous) = cycle.take() {
+        files.push(previous.finish()?);
+    }
+
+    floating_blocks.flush()?;
+    files.push((FLOATING_BLOCKS_FILE.to_owned(), floating_blocks_path));
+
+    Ok(files)
+}
+
+/// Cemented cycle file, see module documentation
+struct CementedCycleWriter {
+    path: PathBuf,
+    first_level: i32,
+    last_level: i32,
+    file: BufWriter<File>,
+    offsets: Vec<u64>,
+    position: u64,
+}
+
+impl CementedCycleWriter {
+    fn create(dir: &Path, first_level: i32, last_level: i32) -> Result<Self, OctezSnapshotError> {
+        let path = dir.join(format!("{}_{}", first_level, last_level));
+        let mut file = BufWriter::new(File::create(&path)?);
+
+        // Offsets are written once all blocks are
+        let header_size = (last_level - first_level + 1) as u64 * 8;
+        file.write_all(&vec![0; header_size as usize])?;
+
+        Ok(Self {
+            path,
+            first_level,
+            last_level,
+            file,
+            offsets: Vec::new(),
+            position: header_size,
+        })
+    }
+
+    fn write_block(&mut self, level: i32, block: &[u8]) -> Result<(), OctezSnapshotError> {
+        let expected_level = self.first_level + self.offsets.len() as i32;
+        if level != expected_level {
+            return Err(OctezSnapshotError::EncodingError {
+                reason: format!(
+                    "block at level {} in the cemented cycle {}_{}, expected level {}",
+                    level, self.first_level, self.last_level, expected_level
+                ),
+            });
+        }
+
+        self.offsets.push(self.position);
+        self.file.write_all(block)?;
+        self.position += block.len() as u64;
+        Ok(())
+    }
+
+    /// Returns the file with its name in the snapshot
+    fn finish(self) -> Result<(String, PathBuf), OctezSnapshotError> {
+        let name = format!("{}/{}_{}", CEMENTED_DIR, self.first_level, self.last_level);
+        if self.offsets.len() as i32 != self.last_level - self.first_level + 1 {
+            return Err(OctezSnapshotError::EncodingError {
+                reason: format!("cemented cycle {} is not complete", name),
+            });
+        }
+
+        let mut file = self.file.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+        for offset in &self.offsets {
+            file.write_all(&offset.to_be_bytes())?;
+        }
+        file.sync_all()?;
+
+        Ok((name, self.path))
+    }
+}
+
+/// Reads the blocks of the cemented cycle file `name` (`<first_level>_<last_level>`)
+fn read_cemented_blocks(
+    reader: impl Read,
+    name: &str,
+    mut f: impl FnMut(BlockRepr) -> Result<(), OctezSnapshotError>,
+) -> Result<(), OctezSnapshotError> {
+    let invalid = |reason: String| OctezSnapshotError::InvalidData {
+        file: CEMENTED_DIR,
+        reason,
+    };
+    let (first_level, last_level) = name
+        .split_once('_')
+        .and_then(|(first, last)| Some((first.parse::<i32>().ok()?, last.parse::<i32>().ok()?)))
+        .filter(|(first, last)| first <= last)
+        .ok_or_else(|| invalid(format!("invalid cemented cycle file name {}", name)))?;
+    let mut reader = BufReader::new(reader);
+
+    // Offsets are read one by one, the size of the header is only trusted once
+    // the data is there
+    let mut offsets = Vec::new();
+    for _ in first_level..=last_level {
+        let mut offset = [0; 8];
+        reader.read_exact(&mut offset)?;
+        offsets.push(u64::from_be_bytes(offset));
+    }
+
+    let mut position = offsets.len() as u64 * 8;
+    for (level, offset) in (first_level..=last_level).zip(offsets) {
+        if offset != position {
+            return Err(invalid(format!(
+                "block at level {} of cycle {} is at offset {}, expected {}",
+                level, name, offset, position
+            )));
+        }
+        let data = read_length_prefixed(&mut reader, CEMENTED_DIR)?
+            .ok_or_else(|| invalid(format!("cycle {} is not complete", name)))?;
+        position += 4 + data.len() as u64;
+
+        let block = decode_block(CEMENTED_DIR, &data)?;
+        if block.header.level() != level {
+            return Err(invalid(format!(
+                "block {} at level {} in cycle {}, expected level {}",
+                block.hash.to_base58_check(),
+                block.header.level(),
+                name,
+                level
+            )));
+        }
+        f(block)?;
+    }
+
+    if reader.read(&mut [0])? != 0 {
+        return Err(invalid(format!("trailing data in cycle {}", name)));
+    }
+    Ok(())
+}
+
+/// Reads the blocks of the floating blocks file
+fn read_floating_blocks(
+    reader: impl Read,
+    mut f: impl FnMut(BlockRepr) -> Result<(), OctezSnapshotError>,
+) -> Result<(), OctezSnapshotError> {
+    let mut reader = BufReader::new(reader);
+    while let Some(data) = read_length_prefixed(&mut reader, FLOATING_BLOCKS_FILE)? {
+        f(decode_block(FLOATING_BLOCKS_FILE, &data)?)?;
+    }
+    Ok(())
+}
+
+/// Reads a 4 bytes length prefixed field, `None` at the end of data
+fn read_length_prefixed(
+    reader: &mut impl Read,
+    file: &'static str,
+) -> Result<Option<Vec<u8>>, OctezSnapshotError> {
+    let mut len = [0; 4];
+    let mut read = 0;
+    while read < len.len() {
+        match reader.read(&mut len[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => {
+                return Err(OctezSnapshotError::InvalidData {
+                    file,
+                    reason: "unexpected end of data".to_owned(),
+                })
+            }
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => (),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let mut data = vec![0; u32::from_be_bytes(len) as usize];
+    reader.read_exact(&mut data)?;
+    Ok(Some(data))
+}
+
+fn decode_block(file: &'static str, data: &[u8]) -> Result<BlockRepr, OctezSnapshotError> {
+    let mut reader = Reader::new(file, data);
+    let block = decode_block_repr(&mut reader)?;
+    reader.finish()?;
+    Ok(block)
+}
+
+/// Stores the blocks of the snapshot as they are read, checking that each block
+/// is the successor of the previous one.
+struct BlockImporter<'a> {
+    block_storage: BlockStorage,
+    block_meta_storage: BlockMetaStorage,
+    operations_storage: OperationsStorage,
+    chain_id: &'a ChainId,
+    genesis_hash: &'a BlockHash,
+    log: &'a Logger,
+    /// Lowest imported block, the caboose
+    lowest: Option<Head>,
+    /// Last imported block, the target block once all blocks are read
+    last: Option<BlockRepr>,
+    count: usize,
+}
+
+impl<'a> BlockImporter<'a> {
+    fn new(
+        persistent_storage: &PersistentStorage,
+        chain_id: &'a ChainId,
+        genesis_hash: &'a BlockHash,
+        log: &'a Logger,
+    ) -> Self {
+        Self {
+            block_storage: BlockStorage::new(persistent_storage),
+            block_meta_storage: BlockMetaStorage::new(persistent_storage),
+            operations_storage: OperationsStorage::new(persistent_storage),
+            chain_id,
+            genesis_hash,
+            log,
+            lowest: None,
+            last: None,
+            count: 0,
+        }
+    }
+
+    /// Imports the blocks of the archive entry `path`, other entries are skipped
+    fn import_entry(
+        &mut self,
+        path: &str,
+        reader: impl Read,
+        compute_paths: &mut dyn FnMut(
+            &Vec<Vec<Operation>>,
+        ) -> Result<Vec<OperationsPath>, OctezSnapshotError>,
+    ) -> Result<(), OctezSnapshotError> {
+        if path == FLOATING_BLOCKS_FILE {
+            return read_floating_blocks(reader, |block| {
+                self.import(FLOATING_BLOCKS_FILE, block, compute_paths)
+            });
+        }
+
+        match path
+            .strip_prefix(CEMENTED_DIR)
+            .and_then(|name| name.strip_prefix('/'))
+        {
+            // Cemented block metadata is not part of TezEdge snapshots, it is skipped
+            Some(name) if !name.is_empty() && !name.contains('/') => {
+                read_cemented_blocks(reader, name, |block| {
+                    self.import(CEMENTED_DIR, block, compute_paths)
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn import(
+        &mut self,
+        file: &'static str,
+        block: BlockRepr,
+        compute_paths: &mut dyn FnMut(
+            &Vec<Vec<Operation>>,
+        ) -> Result<Vec<OperationsPath>, OctezSnapshotError>,
+    ) -> Result<(), OctezSnapshotError> {
+        let invalid = |reason: String| OctezSnapshotError::InvalidData { file, reason };
+
+        let header =
+            BlockHeaderWithHash::new(block.header.clone()).map_err(|e| invalid(e.to_string()))?;
+        if header.hash != block.hash {
+            return Err(invalid(format!(
+                "header of block {} hashes to {}",
+                block.hash.to_base58_check(),
+                header.hash.to_base58_check()
+            )));
+        }
+
+        let (predecessor, predecessor_level) = match &self.last {
+            Some(last) => (&last.hash, last.header.level()),
+            None => (self.genesis_hash, 0),
+        };
+        // Lowest block of a rolling snapshot has no predecessor in the snapshot
+        let is_successor = block.header.level() == predecessor_level + 1
+            && block.header.predecessor() == predecessor;
+        if !is_successor && (self.last.is_some() || block.header.level() == 1) {
+            return Err(invalid(format!(
+                "block {} at level {} is not a successor of block {} at level {}",
+                block.hash.to_base58_check(),
+                block.header.level(),
+                predecessor.to_base58_check(),
+                predecessor_level
+            )));
+        }
+
+        let paths = compute_paths(&block.operations)?;
+
+        self.block_storage.put_block_header(&header)?;
+        let meta = self.block_meta_storage.put_block_header_with_applied(
+            &header,
+            self.chain_id,
+            self.log,
+        )?;
+        self.block_meta_storage
+            .store_predecessors(&header.hash, &meta)?;
+
+        for (validation_pass, (operations, path)) in block.operations.iter().zip(paths).enumerate()
+        {
+            self.operations_storage
+                .put_operations(&OperationsForBlocksMessage::new(
+                    OperationsForBlock::new(header.hash.clone(), validation_pass as i8),
+                    path,
+                    operations.clone(),
+                ))?;
+        }
+
+        if self.lowest.is_none() {
+            self.lowest = Some(Head::new(
+                header.hash.clone(),
+                header.header.level(),
+                header.header.fitness().clone(),
+            ));
+        }
+        self.last = Some(block);
+        self.count += 1;
+
+        Ok(())
+    }
+}
+
+/// Octez snapshots carry metadata of the target block only, `with_metadata` is set for it
+fn read_block_repr(
+    block_hash: &BlockHash,
+    with_metadata: bool,
+    block_storage: &BlockStorage,
+    block_meta_storage: &BlockMetaStorage,
+    operations_storage: &OperationsStorage,
+) -> Result<BlockRepr, OctezSnapshotError> {
+    let missing_block = || OctezSnapshotError::MissingBlock {
+        block_hash: block_hash.to_base58_check(),
+    };
+    let header = block_storage.get(block_hash)?.ok_or_else(missing_block)?;
+
+    let mut operations = operations_storage.get_operations(block_hash)?;
+    operations.sort_by_key(|message| message.operations_for_block().validation_pass());
+    if operations.len() != usize::from(header.header.validation_pass()) {
+        return Err(missing_block());
+    }
+    let operations = operations
+        .into_iter()
+        .map(|message| message.operations().clone())
+        .collect();
+
+    let additional_data = block_meta_storage.get_additional_data(block_hash)?;
+    let json_data = if with_metadata {
+        block_storage.get_with_json_data(block_hash)?
+    } else {
+        None
+    };
+    let metadata = match (&additional_data, json_data) {
+        (Some(additional_data), Some((_, json_data))) => Some(BlockReprMetadata {
+            max_operations_ttl: additional_data.max_operations_ttl(),
+            last_allowed_fork_level: additional_data.last_allowed_fork_level(),
+            block_metadata: json_data.block_header_proto_metadata_bytes,
+            operations_metadata: json_data.operations_proto_metadata_bytes,
+        }),
+        _ => None,
+    };
+
+    Ok(BlockRepr {
+        hash: header.hash,
+        header: header.header.as_ref().clone(),
+        operations,
+        block_metadata_hash: additional_data
+            .as_ref()
+            .and_then(|data| data.block_metadata_hash().clone()),
+        operations_metadata_hashes: additional_data
+            .as_ref()
+            .and_then(|data| data.ops_metadata_hashes().clone()),
+        metadata,
+    })
+}
+
+fn encode_block_data(
+    w: &mut Writer,
+    block_data: &SnapshotBlockData,
+) -> Result<(), OctezSnapshotError> {
+    w.block_header(&block_data.block_header)?;
+    w.operations(&block_data.operations)?;
+    w.block_header(&block_data.predecessor_header)?;
+    w.hash(&block_data.resulting_context_hash);
+    w.option(block_data.block_metadata_hash.as_ref(), |w, hash| {
+        w.hash(hash);
+        Ok(())
+    })?;
+    w.option(block_data.operations_metadata_hash.as_ref(), |w, hash| {
+        w.hash(hash);
+        Ok(())
+    })
+}
+
+fn decode_block_data(r: &mut Reader) -> Result<SnapshotBlockData, OctezSnapshotError> {
+    Ok(SnapshotBlockData {
+        block_header: r.block_header()?,
+        operations: r.operations()?,
+        predecessor_header: r.block_header()?,
+        resulting_context_hash: r.hash()?,
+        block_metadata_hash: r.option(Reader::hash)?,
+        operations_metadata_hash: r.option(Reader::hash)?,
+    })
+}
+
+fn encode_block_repr(w: &mut Writer, block: &BlockRepr) -> Result<(), OctezSnapshotError> {
+    w.hash(&block.hash);
+    w.dynamic(|w| {
+        w.block_header(&block.header)?;
+        w.operations(&block.operations)?;
+        w.option(block.block_metadata_hash.as_ref(), |w, hash| {
+            w.hash(hash);
+            Ok(())
+        })?;
+        w.option(block.operations_metadata_hashes.as_ref(), |w, hashes| {
+            w.list(hashes, |w, hashes| {
+                w.list(hashes, |w, hash| {
+                    w.hash(hash);
+                    Ok(())
+                })
+            })
+        })
+    })?;
+    w.option(block.metadata.as_ref(), |w, metadata| {
+        // Octez keeps the optional commit message here, it is never set by TezEdge
+        w.u8(0x00);
+        w.i32(i32::from(metadata.max_operations_ttl));
+        w.i32(metadata.last_allowed_fork_level);
+        w.bytes(&metadata.block_metadata);
+        w.list(&metadata.operations_metadata, |w, operations| {
+            w.list(operations, |w, operation| {
+                w.bytes(operation);
+                Ok(())
+            })
+        })
+    })
+}
+
+fn decode_block_repr(r: &mut Reader) -> Result<BlockRepr, OctezSnapshotError> {
+    let hash = r.hash()?;
+    let (header, operations, block_metadata_hash, operations_metadata_hashes) = r.dynamic(|r| {
+        Ok((
+            r.block_header()?,
+            r.operations()?,
+            r.option(Reader::hash)?,
+            r.option(|r| r.list(|r| r.list(Reader::hash)))?,
+        ))
+    })?;
+    let metadata = r.option(|r| {
+        r.option(|r| r.bytes())?;
+        let max_operations_ttl = r.i32()?;
+        Ok(BlockReprMetadata {
+            max_operations_ttl: u16::try_from(max_operations_ttl).map_err(|e| r.invalid(e))?,
+            last_allowed_fork_level: r.i32()?,
+            block_metadata: r.bytes()?,
+            operations_metadata: r.list(|r| r.list(|r| r.bytes()))?,
+        })
+    })?;
+
+    Ok(BlockRepr {
+        hash,
+        header,
+        operations,
+        block_metadata_hash,
+        operations_metadata_hashes,
+        metadata,
+    })
+}
+
+/// Encoder of the binary snapshot entries, see module documentation
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn hash<H: HashTrait>(&mut self, hash: &H) {
+        let hash: &Vec<u8> = hash.as_ref();
+        self.0.extend_from_slice(hash);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.0
+            .extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn dynamic(
+        &mut self,
+        f: impl FnOnce(&mut Writer) -> Result<(), OctezSnapshotError>,
+    ) -> Result<(), OctezSnapshotError> {
+        let mut inner = Writer::default();
+        f(&mut inner)?;
+        self.bytes(&inner.0);
+        Ok(())
+    }
+
+    fn list<'a, T: 'a>(
+        &mut self,
+        items: impl IntoIterator<Item = &'a T>,
+        mut f: impl FnMut(&mut Writer, &T) -> Result<(), OctezSnapshotError>,
+    ) -> Result<(), OctezSnapshotError> {
+        self.dynamic(|w| items.into_iter().try_for_each(|item| f(w, item)))
+    }
+
+    fn option<T>(
+        &mut self,
+        value: Option<&T>,
+        f: impl FnOnce(&mut Writer, &T) -> Result<(), OctezSnapshotError>,
+    ) -> Result<(), OctezSnapshotError> {
+        match value {
+            Some(value) => {
+                self.u8(0xff);
+                f(self, value)
+            }
+            None => {
+                self.u8(0x00);
+                Ok(())
+            }
+        }
+    }
+
+    fn block_header(&mut self, header: &BlockHeader) -> Result<(), OctezSnapshotError> {
+        self.bytes(&header.as_bytes().map_err(OctezSnapshotError::encoding)?);
+        Ok(())
+    }
+
+    fn operations(&mut self, operations: &[Vec<Operation>]) -> Result<(), OctezSnapshotError> {
+        self.list(operations, |w, operations| {
+            w.list(operations, |w, operation| {
+                w.bytes(&operation.as_bytes().map_err(OctezSnapshotError::encoding)?);
+                Ok(())
+            })
+        })
+    }
+}
+
+/// Decoder of the binary snapshot entries, see module documentation
+struct Reader<'a> {
+    file: &'static str,
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(file: &'static str, data: &'a [u8]) -> Self {
+        Self { file, data }
+    }
+
+    fn invalid(&self, reason: impl std::fmt::Display) -> OctezSnapshotError {
+        OctezSnapshotError::InvalidData {
+            file: self.file,
+            reason: reason.to_string(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn finish(&self) -> Result<(), OctezSnapshotError> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self.invalid(format!("{} trailing bytes", self.data.len())))
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], OctezSnapshotError> {
+        if self.data.len() < len {
+            return Err(self.invalid("unexpected end of data"));
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, OctezSnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i32(&mut self) -> Result<i32, OctezSnapshotError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(i32::from_be_bytes(bytes))
+    }
+
+    fn hash<H: HashTrait + for<'b> TryFrom<&'b [u8]>>(&mut self) -> Result<H, OctezSnapshotError> {
+        let bytes = self.take(H::hash_type().size())?;
+        H::try_from(bytes).map_err(|_| self.invalid("invalid hash"))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, OctezSnapshotError> {
+        self.dynamic(|r| Ok(r.take(r.data.len())?.to_vec()))
+    }
+
+    fn dynamic<T>(
+        &mut self,
+        f: impl FnOnce(&mut Reader<'a>) -> Result<T, OctezSnapshotError>,
+    ) -> Result<T, OctezSnapshotError> {
+        let mut len = [0; 4];
+        len.copy_from_slice(self.take(4)?);
+        let mut inner = Reader::new(self.file, self.take(u32::from_be_bytes(len) as usize)?);
+        let value = f(&mut inner)?;
+        inner.finish()?;
+        Ok(value)
+    }
+
+    fn list<T>(
+        &mut self,
+        mut f: impl FnMut(&mut Reader<'a>) -> Result<T, OctezSnapshotError>,
+    ) -> Result<Vec<T>, OctezSnapshotError> {
+        self.dynamic(|r| {
+            let mut items = Vec::new();
+            while !r.is_empty() {
+                items.push(f(r)?);
+            }
+            Ok(items)
+        })
+    }
+
+    fn option<T>(
+        &mut self,
+        f: impl FnOnce(&mut Reader<'a>) -> Result<T, OctezSnapshotError>,
+    ) -> Result<Option<T>, OctezSnapshotError> {
+        match self.u8()? {
+            0x00 => Ok(None),
+            0xff => f(self).map(Some),
+            tag => Err(self.invalid(format!("invalid option tag {}", tag))),
+        }
+    }
+
+    fn block_header(&mut self) -> Result<BlockHeader, OctezSnapshotError> {
+        let bytes = self.bytes()?;
+        BlockHeader::from_bytes(bytes).map_err(|e| self.invalid(e))
+    }
+
+    fn operations(&mut self) -> Result<Vec<Vec<Operation>>, OctezSnapshotError> {
+        self.list(|r| {
+            r.list(|r| {
+                let bytes = r.bytes()?;
+                Operation::from_bytes(bytes).map_err(|e| r.invalid(e))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use storage::tests_common::TmpStorage;
+    use tempfile::tempdir;
+    use tezos_messages::p2p::encoding::fitness::Fitness;
+    use tezos_messages::p2p::encoding::prelude::BlockHeaderBuilder;
+
+    use super::*;
+
+    const BLOCKS_COUNT: i32 = 30;
+    const MAINNET_GENESIS: &str = "BLockGenesisGenesisGenesisGenesisGenesisf79b5d1CoW2";
+
+    /// Blocks from genesis, each one with a single operation, 8 blocks per cycle
+    fn make_chain() -> Vec<BlockHeaderWithHash> {
+        let mut blocks: Vec<BlockHeaderWithHash> = Vec::new();
+        for level in 0..BLOCKS_COUNT {
+            let predecessor = match blocks.last() {
+                Some(block) => block.hash.clone(),
+                None => MAINNET_GENESIS.try_into().unwrap(),
+            };
+            let header = BlockHeaderBuilder::default()
+                .level(level)
+                .proto(1)
+                .predecessor(predecessor)
+                .timestamp((1_600_000_000 + level as i64 * 30).into())
+                .validation_pass(1)
+                .operations_hash(
+                    "LLoaGLRPRx3Zf8kB4ACtgku8F4feeBiskeb41J1ciwfcXB3KzHKXc"
+                        .try_into()
+                        .unwrap(),
+                )
+                .fitness(Fitness::default())
+                .context(
+                    "CoVmAcMV64uAQo8XvfLr9VDuz7HVZLT4cgK1w1qYmTjQNbGwQwDd"
+                        .try_into()
+                        .unwrap(),
+                )
+                .protocol_data(vec![level as u8; 8].into())
+                .build()
+                .unwrap();
+            blocks.push(BlockHeaderWithHash::new(header).unwrap());
+        }
+        blocks
+    }
+
+    fn make_operation(block: &BlockHeaderWithHash) -> Operation {
+        let mut bytes: Vec<u8> = block.header.predecessor().as_ref().clone();
+        bytes.extend_from_slice(&block.header.level().to_be_bytes());
+        Operation::from_bytes(bytes).unwrap()
+    }
+
+    fn make_cycle_eras() -> Vec<CycleEra> {
+        serde_json::from_str(
+            r#"[{"first_level":1,"first_cycle":0,"blocks_per_cycle":8,"blocks_per_commitment":4}]"#,
+        )
+        .unwrap()
+    }
+
+    /// Stores the chain with the metadata of the last block only
+    fn store_chain(persistent_storage: &PersistentStorage, blocks: &[BlockHeaderWithHash]) {
+        let block_storage = BlockStorage::new(persistent_storage);
+        let block_meta_storage = BlockMetaStorage::new(persistent_storage);
+        let operations_storage = OperationsStorage::new(persistent_storage);
+
+        for block in blocks {
+            block_storage.put_block_header(block).unwrap();
+            operations_storage
+                .put_operations(&OperationsForBlocksMessage::new(
+                    OperationsForBlock::new(block.hash.clone(), 0),
+                    OperationsPath(vec![]),
+                    vec![make_operation(block)],
+                ))
+                .unwrap();
+        }
+
+        let target = blocks.last().unwrap();
+        block_meta_storage
+            .put_block_additional_data(
+                &target.hash,
+                &BlockAdditionalData::new(
+                    60,
+                    20,
+                    "PsBabyM1eUXZseaJdmXFApDSBqj8YBfwELoxZHHW77EMcAbbwAS"
+                        .try_into()
+                        .unwrap(),
+                    "PsBabyM1eUXZseaJdmXFApDSBqj8YBfwELoxZHHW77EMcAbbwAS"
+                        .try_into()
+                        .unwrap(),
+                    None,
+                    None,
+                    None,
+                ),
+            )
+            .unwrap();
+        block_storage
+            .put_block_json_data(
+                &target.hash,
+                BlockJsonData::new(
+                    String::new(),
+                    b"block metadata".to_vec(),
+                    vec![vec![b"operation metadata".to_vec()]],
+                ),
+            )
+            .unwrap();
+    }
+
+    /// Writes the blocks from `from_level` up to the last one as `write_blocks` does
+    /// on export, into a tar archive
+    fn export_blocks(
+        persistent_storage: &PersistentStorage,
+        blocks: &[BlockHeaderWithHash],
+        dir: &Path,
+    ) -> Result<(PathBuf, Vec<String>), OctezSnapshotError> {
+        let block_storage = BlockStorage::new(persistent_storage);
+        let block_meta_storage = BlockMetaStorage::new(persistent_storage);
+        let operations_storage = OperationsStorage::new(persistent_storage);
+        let target_hash = &blocks.last().unwrap().hash;
+
+        let files = write_blocks(
+            dir,
+            blocks.iter().map(|block| {
+                read_block_repr(
+                    &block.hash,
+                    &block.hash == target_hash,
+                    &block_storage,
+                    &block_meta_storage,
+                    &operations_storage,
+                )
+            }),
+            20,
+            &make_cycle_eras(),
+        )?;
+
+        let archive_path = dir.join("snapshot.tar");
+        let mut builder = Builder::new(File::create(&archive_path)?);
+        for (name, path) in &files {
+            builder.append_path_with_name(path, name)?;
+        }
+        builder.into_inner()?.flush()?;
+
+        Ok((
+            archive_path,
+            files.into_iter().map(|(name, _)| name).collect(),
+        ))
+    }
+
+    fn import_blocks(
+        importer: &mut BlockImporter,
+        archive_path: &Path,
+    ) -> Result<(), OctezSnapshotError> {
+        let mut archive = Archive::new(File::open(archive_path)?);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_string_lossy().into_owned();
+            importer.import_entry(&path, &mut entry, &mut |operations| {
+                Ok(vec![OperationsPath(vec![]); operations.len()])
+            })?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cycle_bounds() {
+        let cycle_eras = make_cycle_eras();
+
+        assert_eq!(cycle_bounds(&cycle_eras, 0), None);
+        assert_eq!(cycle_bounds(&cycle_eras, 1), Some((1, 8)));
+        assert_eq!(cycle_bounds(&cycle_eras, 8), Some((1, 8)));
+        assert_eq!(cycle_bounds(&cycle_eras, 9), Some((9, 16)));
+        assert_eq!(cycle_bounds(&[], 9), None);
+    }
+
+    #[test]
+    fn test_export_import_blocks() {
+        let dir = tempdir().unwrap();
+        let source = TmpStorage::create(dir.path().join("source")).unwrap();
+        let target = TmpStorage::create(dir.path().join("target")).unwrap();
+        let chain_id = ChainId::from_base58_check("NetXdQprcVkpaWU").unwrap();
+        let log = Logger::root(slog::Discard, slog::o!());
+        let blocks = make_chain();
+        store_chain(source.storage(), &blocks);
+
+        // Cycles ending at the last allowed fork level (20) or below are cemented
+        let (archive_path, files) =
+            export_blocks(source.storage(), &blocks[1..], dir.path()).unwrap();
+        assert_eq!(
+            files,
+            vec!["cemented/1_8", "cemented/9_16", "floating/floating_blocks"]
+        );
+
+        let mut importer = BlockImporter::new(target.storage(), &chain_id, &blocks[0].hash, &log);
+        import_blocks(&mut importer, &archive_path).unwrap();
+
+        assert_eq!(importer.count, blocks.len() - 1);
+        assert_eq!(
+            importer.lowest.as_ref().unwrap().block_hash(),
+            &blocks[1].hash
+        );
+        let last = importer.last.as_ref().unwrap();
+        assert_eq!(last.hash, blocks[BLOCKS_COUNT as usize - 1].hash);
+        let metadata = last.metadata.as_ref().unwrap();
+        assert_eq!(metadata.max_operations_ttl, 60);
+        assert_eq!(metadata.last_allowed_fork_level, 20);
+        assert_eq!(metadata.block_metadata, b"block metadata");
+        assert_eq!(
+            metadata.operations_metadata,
+            vec![vec![b"operation metadata".to_vec()]]
+        );
+
+        let block_storage = BlockStorage::new(target.storage());
+        let operations_storage = OperationsStorage::new(target.storage());
+        for block in &blocks[1..] {
+            assert_eq!(
+                block_storage.get(&block.hash).unwrap().as_ref(),
+                Some(block)
+            );
+            let operations = operations_storage.get_operations(&block.hash).unwrap();
+            assert_eq!(operations.len(), 1);
+            assert_eq!(operations[0].operations(), &vec![make_operation(block)]);
+        }
+    }
+
+    #[test]
+    fn test_export_import_blocks_rolling() {
+        let dir = tempdir().unwrap();
+        let source = TmpStorage::create(dir.path().join("source")).unwrap();
+        let target = TmpStorage::create(dir.path().join("target")).unwrap();
+        let chain_id = ChainId::from_base58_check("NetXdQprcVkpaWU").unwrap();
+        let log = Logger::root(slog::Discard, slog::o!());
+        let blocks = make_chain();
+        store_chain(source.storage(), &blocks);
+
+        // First cycle is partial
+        let (archive_path, files) =
+            export_blocks(source.storage(), &blocks[5..], dir.path()).unwrap();
+        assert_eq!(
+            files,
+            vec!["cemented/5_8", "cemented/9_16", "floating/floating_blocks"]
+        );
+
+        let mut importer = BlockImporter::new(target.storage(), &chain_id, &blocks[0].hash, &log);
+        import_blocks(&mut importer, &archive_path).unwrap();
+
+        assert_eq!(importer.count, blocks.len() - 5);
+        assert_eq!(
+            importer.lowest.as_ref().unwrap().block_hash(),
+            &blocks[5].hash
+        );
+    }
+
+    #[test]
+    fn test_import_blocks_broken_chain() {
+        let dir = tempdir().unwrap();
+        let source = TmpStorage::create(dir.path().join("source")).unwrap();
+        let target = TmpStorage::create(dir.path().join("target")).unwrap();
+        let chain_id = ChainId::from_base58_check("NetXdQprcVkpaWU").unwrap();
+        let log = Logger::root(slog::Discard, slog::o!());
+        let blocks = make_chain();
+        store_chain(source.storage(), &blocks);
+
+        // A cemented cycle can't be written with a block missing
+        let missing_cemented = blocks[1..5].iter().chain(&blocks[6..]).cloned();
+        assert!(matches!(
+            export_blocks(
+                source.storage(),
+                &missing_cemented.collect::<Vec<_>>(),
+                dir.path()
+            ),
+            Err(OctezSnapshotError::EncodingError { .. })
+        ));
+
+        // A floating block is missing
+        let missing_floating = blocks[1..22].iter().chain(&blocks[23..]).cloned();
+        let (archive_path, _) = export_blocks(
+            source.storage(),
+            &missing_floating.collect::<Vec<_>>(),
+            dir.path(),
+        )
+        .unwrap();
+
+        let mut importer = BlockImporter::new(target.storage(), &chain_id, &blocks[0].hash, &log);
+        assert!(matches!(
+            import_blocks(&mut importer, &archive_path),
+            Err(OctezSnapshotError::InvalidData {
+                file: FLOATING_BLOCKS_FILE,
+                ..
+            })
+        ));
+        assert_eq!(importer.last.as_ref().unwrap().hash, blocks[21].hash);
+
+        // Lowest block of a full snapshot must follow genesis
+        let target = TmpStorage::create(dir.path().join("other_target")).unwrap();
+        let (archive_path, _) = export_blocks(source.storage(), &blocks[1..], dir.path()).unwrap();
+        let mut importer = BlockImporter::new(target.storage(), &chain_id, &blocks[1].hash, &log);
+        assert!(matches!(
+            import_blocks(&mut importer, &archive_path),
+            Err(OctezSnapshotError::InvalidData {
+                file: CEMENTED_DIR,
+                ..
+            })
+        ));
+    }
+
+    /// `octez_snapshot_blocks.tar` holds blocks 1 to 6 of a chain on top of the mainnet
+    /// genesis, encoded independently of this module following the Octez store layout:
+    /// `cemented/1_4` and `floating/floating_blocks` with the metadata of block 6.
+    #[test]
+    fn test_import_blocks_fixture() {
+        let dir = tempdir().unwrap();
+        let target = TmpStorage::create(dir.path().join("target")).unwrap();
+        let chain_id = ChainId::from_base58_check("NetXdQprcVkpaWU").unwrap();
+        let genesis_hash: BlockHash = MAINNET_GENESIS.try_into().unwrap();
+        let log = Logger::root(slog::Discard, slog::o!());
+        let archive_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("resources")
+            .join("octez_snapshot_blocks.tar");
+
+        let mut importer = BlockImporter::new(target.storage(), &chain_id, &genesis_hash, &log);
+        import_blocks(&mut importer, &archive_path).unwrap();
+
+        assert_eq!(importer.count, 6);
+        assert_eq!(
+            importer
+                .lowest
+                .as_ref()
+                .unwrap()
+                .block_hash()
+                .to_base58_check(),
+            "BMFcgHvmxC4MCMK7W7Btb2RZwEHnG3AJahQ3iXuNRm4vH9Y8zTj"
+        );
+        let last = importer.last.as_ref().unwrap();
+        assert_eq!(
+            last.hash.to_base58_check(),
+            "BLzVZQHsm1ieGppc9rxZFN8UnpMuP2q3ZyYw7c1ExCvN9vbimvL"
+        );
+        assert_eq!(last.header.level(), 6);
+        assert!(last.block_metadata_hash.is_some());
+        assert_eq!(
+            last.operations_metadata_hashes
+                .as_ref()
+                .map(|hashes| hashes[0].len()),
+            Some(1)
+        );
+        let metadata = last.metadata.as_ref().unwrap();
+        assert_eq!(metadata.max_operations_ttl, 60);
+        assert_eq!(metadata.last_allowed_fork_level, 2);
+        assert_eq!(metadata.block_metadata, b"block metadata");
+
+        let block_storage = BlockStorage::new(target.storage());
+        let operations_storage = OperationsStorage::new(target.storage());
+        let block_hash: BlockHash = "BLNo4biWsZZMMfyevKDv5CDCXMLVqKdbqir3zaD1AYtupUUECjJ"
+            .try_into()
+            .unwrap();
+        let block = block_storage.get(&block_hash).unwrap().unwrap();
+        assert_eq!(block.header.level(), 3);
+        let operations = operations_storage.get_operations(&block_hash).unwrap();
+        assert_eq!(operations.len(), 1);
+        assert_eq!(
+            operations[0].operations()[0].branch(),
+            block.header.predecessor()
+        );
+
+        // Blocks are checked against the genesis of the configured chain
+        let target = TmpStorage::create(dir.path().join("other_target")).unwrap();
+        let mut importer = BlockImporter::new(target.storage(), &chain_id, &block_hash, &log);
+        assert!(import_blocks(&mut importer, &archive_path).is_err());
+    }
+}
@@ -20,25 +20,37 @@
 use flate2::bufread::GzDecoder;
 use futures::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::Client;
+use reqwest::{header::RANGE, Client, StatusCode};
+use serde::{Deserialize, Serialize};
 use std::{
-    fs::File,
-    io::{BufReader, Write},
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
-    time::Instant,
+    sync::Arc,
+    time::{Instant, SystemTime},
 };
 use tar::Archive;
 use tempfile::tempdir_in;
+use thiserror::Error;
 
 use slog::{info, Logger};
 
-use crypto::hash::{BlockHash, ContextHash};
+use crypto::blake2b;
+use crypto::hash::{BlockHash, ChainId, ContextHash};
+use storage::database::tezedge_database::TezedgeDatabaseBackendConfiguration;
+use storage::initializer::{
+    DbsRocksDbTableInitializer, RocksDbCache, RocksDbColumnFactory, RocksDbConfig,
+};
+use storage::persistent::database::open_kv;
+use storage::persistent::sequence::Sequences;
+use storage::persistent::{open_cl, open_main_db, CommitLogSchema, DbConfiguration};
 use storage::{
-    initialize_storage_with_genesis_block, store_commit_genesis_result, BlockMetaStorage,
-    BlockMetaStorageReader, BlockReference, BlockStorage, BlockStorageReader, ChainMetaStorage,
-    ChainMetaStorageReader, ConstantsStorage, CycleErasStorage, CycleMetaStorage,
+    initialize_storage_with_genesis_block, store_commit_genesis_result, BlockHeaderWithHash,
+    BlockMetaStorage, BlockMetaStorageReader, BlockReference, BlockStorage, BlockStorageReader,
+    ChainMetaStorage, ChainMetaStorageReader, ConstantsStorage, CycleErasStorage, CycleMetaStorage,
     OperationsMetaStorage, OperationsStorage, OperationsStorageReader, PersistentStorage,
-    StorageInitInfo, SystemStorage,
+    StorageError, StorageInitInfo, SystemStorage,
 };
 
 use nix::{
@@ -77,7 +89,6 @@ pub fn snapshot_storage(
     let system_storage = SystemStorage::new(persistent_storage.main_db());
     let block_storage = BlockStorage::new(&persistent_storage);
     let block_meta_storage = BlockMetaStorage::new(&persistent_storage);
-    let operations_storage = OperationsStorage::new(&persistent_storage);
     let chain_meta_storage = ChainMetaStorage::new(&persistent_storage);
     let constants_storage = ConstantsStorage::new(&persistent_storage);
     let cycles_storage = CycleMetaStorage::new(&persistent_storage);
@@ -150,7 +161,6 @@ pub fn snapshot_storage(
     let new_chain_meta_storage = ChainMetaStorage::new(&new_persistent_storage);
     let new_block_storage = BlockStorage::new(&new_persistent_storage);
     let new_block_meta_storage = BlockMetaStorage::new(&new_persistent_storage);
-    let new_operations_storage = OperationsStorage::new(&new_persistent_storage);
     let new_operations_meta_storage = OperationsMetaStorage::new(&new_persistent_storage);
     let new_constants_storage = ConstantsStorage::new(&new_persistent_storage);
     let new_cycles_storage = CycleMetaStorage::new(&new_persistent_storage);
@@ -186,53 +196,14 @@ pub fn snapshot_storage(
     // Store all block headers up until target_block (included)
     info!(log, "Copying block headers up until the target block...");
 
-    let mut current_block_hash = new_genesis_block.hash.clone();
-    let mut block_count = 0;
-    let mut operations_count = 0;
-
-    'outer: loop {
-        let blocks = block_storage
-            .get_multiple_without_json(&current_block_hash, 100)
-            .expect("Failed when obtaining block headers data from source sorage");
-
-        for block_header_with_hash in &blocks {
-            if block_header_with_hash.hash != new_genesis_block.hash {
-                new_block_storage
-                    .put_block_header(block_header_with_hash)
-                    .expect("Failed to store block header to new main storage");
-                block_count += 1;
-
-                let operations_data = operations_storage
-                    .get_operations(&block_header_with_hash.hash)
-                    .expect("Failed to obtain operations for block");
-
-                for message in operations_data {
-                    new_operations_storage
-                        .put_operations(&message)
-                        .expect("Failed to store operations data into new main storage");
-                    operations_count += 1;
-                }
-
-                let block_meta = new_block_meta_storage
-                    .put_block_header_with_applied(block_header_with_hash, &chain_id, &log)
-                    .expect("Failed to store block header meta to new main storage");
-                new_block_meta_storage
-                    .store_predecessors(&block_header_with_hash.hash, &block_meta)
-                    .expect("Failed to store predecessors metadata to new main storage");
-            }
-
-            // Last block was the target block, skip the rest
-            if block_header_with_hash.hash == target_block {
-                break 'outer;
-            }
-        }
-
-        current_block_hash = blocks
-            .last()
-            .expect("Reached end of chain of blocks without finding the target block hash")
-            .hash
-            .clone();
-    }
+    let (block_count, operations_count) = copy_blocks(
+        &persistent_storage,
+        &new_persistent_storage,
+        &chain_id,
+        &new_genesis_block.hash,
+        &target_block,
+        &log,
+    );
 
     info!(log, "Done copying block headers and operations"; "block_count" => block_count, "operations_count" => operations_count);
 
@@ -312,7 +283,82 @@ pub fn snapshot_storage(
     std::fs::remove_file(tezedge_lock_file).ok();
 }
 
-async fn terminate_or_kill(process: &mut Child, reason: String) -> Result<(), ProtocolRunnerError> {
+/// Copies the block headers and operations of the source storage, from genesis up until
+/// `target_block` (included), to the new storage.
+///
+/// Blocks pruned from the source storage (rolling history mode) are skipped, the copy
+/// continues from the lowest block stored after genesis.
+///
+/// Returns the number of copied blocks and operations messages.
+fn copy_blocks(
+    persistent_storage: &PersistentStorage,
+    new_persistent_storage: &PersistentStorage,
+    chain_id: &ChainId,
+    genesis_hash: &BlockHash,
+    target_block: &BlockHash,
+    log: &Logger,
+) -> (usize, usize) {
+    let block_storage = BlockStorage::new(persistent_storage);
+    let operations_storage = OperationsStorage::new(persistent_storage);
+    let new_block_storage = BlockStorage::new(new_persistent_storage);
+    let new_block_meta_storage = BlockMetaStorage::new(new_persistent_storage);
+    let new_operations_storage = OperationsStorage::new(new_persistent_storage);
+
+    let mut current_block_hash = genesis_hash.clone();
+    let mut block_count = 0;
+    let mut operations_count = 0;
+
+    'outer: loop {
+        let blocks = block_storage
+            .get_multiple_without_json(&current_block_hash, 100)
+            .expect("Failed when obtaining block headers data from source sorage");
+
+        for block_header_with_hash in &blocks {
+            if &block_header_with_hash.hash != genesis_hash {
+                new_block_storage
+                    .put_block_header(block_header_with_hash)
+                    .expect("Failed to store block header to new main storage");
+                block_count += 1;
+
+                let operations_data = operations_storage
+                    .get_operations(&block_header_with_hash.hash)
+                    .expect("Failed to obtain operations for block");
+
+                for message in operations_data {
+                    new_operations_storage
+                        .put_operations(&message)
+                        .expect("Failed to store operations data into new main storage");
+                    operations_count += 1;
+                }
+
+                let block_meta = new_block_meta_storage
+                    .put_block_header_with_applied(block_header_with_hash, chain_id, log)
+                    .expect("Failed to store block header meta to new main storage");
+                new_block_meta_storage
+                    .store_predecessors(&block_header_with_hash.hash, &block_meta)
+                    .expect("Failed to store predecessors metadata to new main storage");
+            }
+
+            // Last block was the target block, skip the rest
+            if &block_header_with_hash.hash == target_block {
+                break 'outer;
+            }
+        }
+
+        current_block_hash = blocks
+            .last()
+            .expect("Reached end of chain of blocks without finding the target block hash")
+            .hash
+            .clone();
+    }
+
+    (block_count, operations_count)
+}
+
+pub async fn terminate_or_kill(
+    process: &mut Child,
+    reason: String,
+) -> Result<(), ProtocolRunnerError> {
     // try to send SIGINT (ctrl-c)
     if let Some(pid) = process.id() {
         let pid = Pid::from_raw(pid as i32);
@@ -420,7 +466,7 @@ fn initialize_protocol_runner_and_snapshot_context(
     })
 }
 
-fn resolve_block_reference(
+pub fn resolve_block_reference(
     block_reference: BlockReference,
     block_storage: &BlockStorage,
     block_meta_storage: &BlockMetaStorage,
@@ -442,32 +488,442 @@ fn resolve_block_reference(
     }
 }
 
-/// Import a snapshot
-pub fn import_snapshot(from: &Path, tezos_data_dir: &Path) {
-    let tar_gz = File::open(from)
-        .unwrap_or_else(|_| panic!("Failed to open snapshot file {}", from.display()));
+/// Name of the file in the target directory holding the progress of an import
+const IMPORT_CHECKPOINT_FILE_NAME: &str = ".snapshot-import.json";
+
+const IMPORT_ROCKSDB_CACHE_SIZE: usize = 32 * 1024 * 1024;
+
+const ARCHIVE_HASH_CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum SnapshotImportError {
+    #[error("I/O error: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("Invalid import checkpoint file: {0}")]
+    InvalidCheckpoint(#[from] serde_json::Error),
+    #[error("Import checkpoint {path} was created for a different snapshot, remove the directory contents to start over")]
+    CheckpointMismatch { path: PathBuf },
+    #[error("Failed to hash the snapshot archive: {reason}")]
+    ArchiveHash { reason: String },
+    #[error("Failed to open imported storage: {reason}")]
+    OpenStorage { reason: String },
+    #[error("Storage error: {0}")]
+    StorageError(#[from] StorageError),
+    #[error("Imported storage has no current head")]
+    MissingHead,
+    #[error("Broken block header chain at {block_hash}: {reason}")]
+    InvalidBlockHeader { block_hash: String, reason: String },
+    #[error("Context verification failed: {reason}")]
+    InvalidContext { reason: String },
+}
+
+/// Identifies the archive of an import cheaply, to detect that the import is
+/// resumed with another snapshot without reading the whole archive again.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct ArchiveFingerprint {
+    size: u64,
+    modified: Option<SystemTime>,
+    /// Blake2b hash of the first and the last MiB of the archive
+    ends_hash: String,
+}
+
+impl ArchiveFingerprint {
+    fn new(from: &Path) -> Result<Self, SnapshotImportError> {
+        let metadata = std::fs::metadata(from)?;
+        let size = metadata.len();
+        let mut file = File::open(from)?;
+
+        let head_len = size.min(ARCHIVE_HASH_CHUNK_SIZE as u64);
+        let mut head = vec![0; head_len as usize];
+        file.read_exact(&mut head)?;
+
+        let tail_len = (size - head_len).min(ARCHIVE_HASH_CHUNK_SIZE as u64);
+        let mut tail = vec![0; tail_len as usize];
+        file.seek(SeekFrom::End(-(tail_len as i64)))?;
+        file.read_exact(&mut tail)?;
+
+        let ends_hash = blake2b::digest_all([head, tail], 32).map_err(|e| {
+            SnapshotImportError::ArchiveHash {
+                reason: e.to_string(),
+            }
+        })?;
+
+        Ok(Self {
+            size,
+            modified: metadata.modified().ok(),
+            ends_hash: hex::encode(ends_hash),
+        })
+    }
+}
+
+/// Progress of the snapshot import, saved after each unpacked entry of the archive.
+///
+/// The archive is compressed so it cannot be seeked, on resume it is read from
+/// the beginning again but entries already unpacked are not written.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ImportCheckpoint {
+    /// The archive being imported, compared on resume
+    #[serde(default)]
+    archive: ArchiveFingerprint,
+    /// Blake2b hash of the whole archive content, computed once when the import starts
+    #[serde(default)]
+    archive_hash: String,
+    /// Unpacked file entries and their size
+    entries: BTreeMap<PathBuf, u64>,
+    /// All entries were unpacked, only the verification is left
+    unpacked: bool,
+}
+
+impl ImportCheckpoint {
+    fn path(tezos_data_dir: &Path) -> PathBuf {
+        tezos_data_dir.join(IMPORT_CHECKPOINT_FILE_NAME)
+    }
+
+    fn load(tezos_data_dir: &Path) -> Result<Option<Self>, SnapshotImportError> {
+        match std::fs::read(Self::path(tezos_data_dir)) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the checkpoint to a temporary file first, so an interruption never
+    /// leaves a truncated checkpoint behind
+    fn save(&self, tezos_data_dir: &Path) -> Result<(), SnapshotImportError> {
+        let path = Self::path(tezos_data_dir);
+        let tmp_path = path.with_extension("tmp");
+
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&serde_json::to_vec(self)?)?;
+        file.sync_all()?;
+        std::fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+
+    fn is_unpacked(&self, entry_path: &Path, size: u64, tezos_data_dir: &Path) -> bool {
+        self.entries.get(entry_path) == Some(&size)
+            && std::fs::metadata(tezos_data_dir.join(entry_path))
+                .map(|metadata| metadata.len() == size)
+                .unwrap_or(false)
+    }
+}
+
+/// Hashes the content of the archive, reading it in chunks.
+fn archive_hash(from: &Path, archive_size: u64) -> Result<String, SnapshotImportError> {
+    let pb = ProgressBar::new(archive_size);
+    pb.set_style(ProgressStyle::default_bar()
+        .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+        .progress_chars("#>-"));
+    pb.set_message(&format!("Hashing {}", from.display()));
+
+    let mut reader =
+        BufReader::with_capacity(ARCHIVE_HASH_CHUNK_SIZE, pb.wrap_read(File::open(from)?));
+    let mut read_error = None;
+    let chunks = std::iter::from_fn(|| match reader.fill_buf() {
+        Ok([]) => None,
+        Ok(buf) => {
+            let chunk = buf.to_vec();
+            reader.consume(chunk.len());
+            Some(chunk)
+        }
+        Err(e) => {
+            read_error = Some(e);
+            None
+        }
+    });
+    let hash = blake2b::digest_all(chunks, 32).map_err(|e| SnapshotImportError::ArchiveHash {
+        reason: e.to_string(),
+    })?;
+    if let Some(e) = read_error {
+        return Err(e.into());
+    }
+
+    pb.finish_and_clear();
+    Ok(hex::encode(hash))
+}
+
+/// Returns true if an interrupted import can be resumed in this directory
+pub fn import_checkpoint_exists(tezos_data_dir: &Path) -> bool {
+    ImportCheckpoint::path(tezos_data_dir).exists()
+}
+
+/// Import a snapshot from the local tarball.
+///
+/// The tarball is unpacked entry by entry and the progress is checkpointed, an interrupted
+/// import continues where it stopped, if the archive has the same size, modification
+/// time and first and last MiB. Once unpacked,
+/// the block header chain is verified from the current head down to the lowest stored
+/// block, and the tree of the context of the current head is hashed again.
+pub fn import_snapshot(
+    from: &Path,
+    tezos_data_dir: &Path,
+    main_db: TezedgeDatabaseBackendConfiguration,
+) -> Result<(), SnapshotImportError> {
+    let archive = ArchiveFingerprint::new(from)?;
+
+    let mut checkpoint = match ImportCheckpoint::load(tezos_data_dir)? {
+        Some(checkpoint) if checkpoint.archive != archive => {
+            return Err(SnapshotImportError::CheckpointMismatch {
+                path: ImportCheckpoint::path(tezos_data_dir),
+            })
+        }
+        Some(checkpoint) => {
+            println!(
+                "Resuming snapshot import, {} entries already unpacked",
+                checkpoint.entries.len()
+            );
+            checkpoint
+        }
+        None => {
+            let checkpoint = ImportCheckpoint {
+                archive_hash: archive_hash(from, archive.size)?,
+                archive,
+                ..Default::default()
+            };
+            println!(
+                "Importing snapshot with blake2b hash {}",
+                checkpoint.archive_hash
+            );
+            checkpoint.save(tezos_data_dir)?;
+            checkpoint
+        }
+    };
+
+    if !checkpoint.unpacked {
+        unpack_snapshot(from, tezos_data_dir, &mut checkpoint)?;
+        checkpoint.unpacked = true;
+        checkpoint.save(tezos_data_dir)?;
+    }
+
+    println!("Verifying block headers...");
+    // IMPORTANT: cache must live at least as long as the database
+    let cache = RocksDbCache::new_lru_cache(IMPORT_ROCKSDB_CACHE_SIZE).map_err(|e| {
+        SnapshotImportError::OpenStorage {
+            reason: e.to_string(),
+        }
+    })?;
+    let head = verify_block_headers(&open_imported_storage(tezos_data_dir, main_db, &cache)?)?;
+
+    println!("Verifying context...");
+    let context_path = tezos_data_dir.join("context");
+    let context_hash = head.header.context();
+    tezos_context::snapshot::verify_context(
+        context_path.to_string_lossy().into_owned(),
+        context_hash,
+    )
+    .map_err(|e| SnapshotImportError::InvalidContext {
+        reason: format!("{:?}", e),
+    })?;
+    println!(
+        "Verified context {} of block {} at level {}",
+        context_hash.to_base58_check(),
+        head.hash.to_base58_check(),
+        head.header.level()
+    );
+
+    std::fs::remove_file(ImportCheckpoint::path(tezos_data_dir))?;
+
+    Ok(())
+}
+
+fn unpack_snapshot(
+    from: &Path,
+    tezos_data_dir: &Path,
+    checkpoint: &mut ImportCheckpoint,
+) -> Result<(), SnapshotImportError> {
+    let pb = ProgressBar::new(checkpoint.archive.size);
+    pb.set_style(ProgressStyle::default_bar()
+        .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+        .progress_chars("#>-"));
+    pb.set_message(&format!("Unpacking {}", from.display()));
+
+    let tar_gz = pb.wrap_read(File::open(from)?);
+    let mut archive = Archive::new(GzDecoder::new(BufReader::new(tar_gz)));
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+
+        if !entry.header().entry_type().is_file() {
+            entry.unpack_in(tezos_data_dir)?;
+            continue;
+        }
+
+        let size = entry.header().size()?;
+        if checkpoint.is_unpacked(&entry_path, size, tezos_data_dir) {
+            continue;
+        }
+
+        entry.unpack_in(tezos_data_dir)?;
+        checkpoint.entries.insert(entry_path, size);
+        checkpoint.save(tezos_data_dir)?;
+    }
+
+    pb.finish_with_message(&format!(
+        "Unpacked {} to {}",
+        from.display(),
+        tezos_data_dir.display()
+    ));
+    Ok(())
+}
+
+fn open_imported_storage(
+    tezos_data_dir: &Path,
+    main_db: TezedgeDatabaseBackendConfiguration,
+    cache: &RocksDbCache,
+) -> Result<PersistentStorage, SnapshotImportError> {
+    let log = Logger::root(slog::Discard, slog::o!());
+    let db_path = tezos_data_dir.join("bootstrap_db");
+    let open_storage_error = |reason: String| SnapshotImportError::OpenStorage { reason };
+
+    let config = RocksDbConfig {
+        cache_size: IMPORT_ROCKSDB_CACHE_SIZE,
+        expected_db_version: 0,
+        db_path: db_path.join("db"),
+        columns: DbsRocksDbTableInitializer,
+        threads: None,
+    };
+    let kv = match main_db {
+        TezedgeDatabaseBackendConfiguration::RocksDB => Some(Arc::new(
+            open_kv(
+                &config.db_path,
+                config.columns.create(cache),
+                &DbConfiguration::default(),
+            )
+            .map_err(|e| open_storage_error(e.to_string()))?,
+        )),
+        TezedgeDatabaseBackendConfiguration::Sled | TezedgeDatabaseBackendConfiguration::EdgeKV => {
+            None
+        }
+    };
+    let maindb = Arc::new(
+        open_main_db(kv, &config, main_db, log.clone())
+            .map_err(|e| open_storage_error(e.to_string()))?,
+    );
+    let commit_logs = Arc::new(
+        open_cl(&db_path, vec![BlockStorage::descriptor()], log)
+            .map_err(|e| open_storage_error(e.to_string()))?,
+    );
+    let sequences = Arc::new(Sequences::new(maindb.clone(), 1000));
+
+    Ok(PersistentStorage::new(maindb, commit_logs, sequences))
+}
+
+/// Walks the block headers from the current head down to the lowest stored block, verifying
+/// that each header hashes to the hash it is stored under and links to its predecessor.
+///
+/// Snapshots exported from a storage in rolling history mode don't have the blocks
+/// below its caboose, only genesis is kept below the lowest stored block. When the chain
+/// is complete, the walk ends at genesis.
+///
+/// Returns the current head.
+fn verify_block_headers(
+    persistent_storage: &PersistentStorage,
+) -> Result<BlockHeaderWithHash, SnapshotImportError> {
+    let system_storage = SystemStorage::new(persistent_storage.main_db());
+    let chain_meta_storage = ChainMetaStorage::new(persistent_storage);
+    let block_storage = BlockStorage::new(persistent_storage);
+
+    let chain_id = system_storage
+        .get_chain_id()?
+        .ok_or(SnapshotImportError::MissingHead)?;
+    let head = chain_meta_storage
+        .get_current_head(&chain_id)?
+        .ok_or(SnapshotImportError::MissingHead)?;
+
+    let invalid =
+        |block_hash: &BlockHash, reason: String| SnapshotImportError::InvalidBlockHeader {
+            block_hash: block_hash.to_base58_check(),
+            reason,
+        };
+
+    let lowest_level = block_storage
+        .get_blocks_from_level(1, 1)?
+        .first()
+        .map(|block| block.header.level())
+        .unwrap_or(0);
+    let stop_level = if lowest_level > 1 { lowest_level } else { 0 };
+
+    let pb = ProgressBar::new((*head.level() - stop_level) as u64 + 1);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} block headers ({eta})")
+            .progress_chars("#>-"),
+    );
+
+    let mut block_hash = head.block_hash().clone();
+    let mut expected_level = *head.level();
+    let mut head_block = None;
+    loop {
+        let block = block_storage
+            .get(&block_hash)?
+            .ok_or_else(|| invalid(&block_hash, "block header not found".to_string()))?;
+
+        let computed = BlockHeaderWithHash::new(block.header.as_ref().clone())
+            .map_err(|e| invalid(&block_hash, format!("failed to hash header: {}", e)))?;
+        if computed.hash != block.hash {
+            return Err(invalid(
+                &block_hash,
+                format!("header hashes to {}", computed.hash.to_base58_check()),
+            ));
+        }
+        if block.header.level() != expected_level {
+            return Err(invalid(
+                &block_hash,
+                format!(
+                    "expected level {}, found {}",
+                    expected_level,
+                    block.header.level()
+                ),
+            ));
+        }
+        pb.inc(1);
 
-    let buffered = BufReader::new(tar_gz);
+        let level = block.header.level();
+        block_hash = block.header.predecessor().clone();
+        head_block.get_or_insert(block);
 
-    let tar = GzDecoder::new(buffered);
-    let mut archive = Archive::new(tar);
+        if level <= stop_level {
+            break;
+        }
+        expected_level -= 1;
+    }
 
-    archive
-        .unpack(tezos_data_dir)
-        .expect("Failed to unpack snapshot tarball");
+    pb.finish_with_message("Verified block headers");
+    head_block.ok_or(SnapshotImportError::MissingHead)
 }
 
-/// Download a file from the url to the supplied path
+/// Download a file from the url to the supplied path.
+///
+/// Data is downloaded into `<path>.part` first, an interrupted download is resumed
+/// from its size and the file is moved to `path` once complete.
 pub async fn download_file(client: &Client, url: &str, path: &Path) -> Result<(), String> {
+    let part_path = path.with_extension(
+        path.extension()
+            .map(|ext| format!("{}.part", ext.to_string_lossy()))
+            .unwrap_or_else(|| "part".to_string()),
+    );
+    let downloaded = std::fs::metadata(&part_path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+
     // Reqwest setup
-    let res = client
-        .get(url)
+    let mut request = client.get(url);
+    if downloaded > 0 {
+        request = request.header(RANGE, format!("bytes={}-", downloaded));
+    }
+    let res = request
         .send()
         .await
         .map_err(|_| format!("Failed to GET from '{}'", &url))?;
+    // the server may ignore the range and send the whole file
+    let resumed = res.status() == StatusCode::PARTIAL_CONTENT;
+    let mut downloaded = if resumed { downloaded } else { 0 };
     let total_size = res
         .content_length()
-        .ok_or(format!("Failed to get content length from '{}'", &url))?;
+        .ok_or(format!("Failed to get content length from '{}'", &url))?
+        + downloaded;
 
     // Indicatif setup
     let pb = ProgressBar::new(total_size);
@@ -475,11 +931,16 @@ pub async fn download_file(client: &Client, url: &str, path: &Path) -> Result<()
         .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
         .progress_chars("#>-"));
     pb.set_message(&format!("Downloading {}", url));
+    pb.set_position(downloaded);
 
     // download chunks
-    let mut file =
-        File::create(path).map_err(|_| format!("Failed to create file '{}'", path.display()))?;
-    let mut downloaded: u64 = 0;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&part_path)
+        .map_err(|_| format!("Failed to create file '{}'", part_path.display()))?;
     let mut stream = res.bytes_stream();
 
     while let Some(item) = stream.next().await {
@@ -491,6 +952,9 @@ pub async fn download_file(client: &Client, url: &str, path: &Path) -> Result<()
         pb.set_position(new);
     }
 
+    std::fs::rename(&part_path, path)
+        .map_err(|_| format!("Failed to move downloaded file to '{}'", path.display()))?;
+
     pb.finish_with_message(&format!("Downloaded {} to {}", url, path.display()));
     Ok(())
 }
@@ -501,7 +965,8 @@ pub fn verify_snapshot_target_directory(tezos_data_dir: &Path) {
     if tezos_data_dir.exists() {
         match tezos_data_dir.read_dir() {
             Ok(mut entries) => {
-                if entries.next().is_some() {
+                // an interrupted import is resumed
+                if entries.next().is_some() && !import_checkpoint_exists(tezos_data_dir) {
                     panic!("It seems a tezedge database already exists at {}. If you wish to continue please move/remove the directory contenst and restart the command.", tezos_data_dir.display())
                 }
             }
@@ -516,3 +981,261 @@ pub fn verify_snapshot_target_directory(tezos_data_dir: &Path) {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use flate2::{write::GzEncoder, Compression};
+    use storage::tests_common::TmpStorage;
+    use storage::{prune_history, HistoryMode};
+    use tempfile::tempdir;
+    use tezos_messages::p2p::encoding::fitness::Fitness;
+    use tezos_messages::p2p::encoding::prelude::BlockHeaderBuilder;
+
+    use super::*;
+
+    fn make_chain(count: i32) -> Vec<BlockHeaderWithHash> {
+        let mut blocks: Vec<BlockHeaderWithHash> = Vec::new();
+        for level in 0..count {
+            let predecessor = match blocks.last() {
+                Some(block) => block.hash.clone(),
+                None => "BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe"
+                    .try_into()
+                    .unwrap(),
+            };
+            let header = BlockHeaderBuilder::default()
+                .level(level)
+                .proto(1)
+                .predecessor(predecessor)
+                .timestamp((1_600_000_000 + level as i64 * 30).into())
+                .validation_pass(4)
+                .operations_hash(
+                    "LLoaGLRPRx3Zf8kB4ACtgku8F4feeBiskeb41J1ciwfcXB3KzHKXc"
+                        .try_into()
+                        .unwrap(),
+                )
+                .fitness(Fitness::default())
+                .context(
+                    "CoVmAcMV64uAQo8XvfLr9VDuz7HVZLT4cgK1w1qYmTjQNbGwQwDd"
+                        .try_into()
+                        .unwrap(),
+                )
+                .protocol_data(vec![0, 1, 2, 3, 4, 5, 6, 7, 8].into())
+                .build()
+                .unwrap();
+            blocks.push(BlockHeaderWithHash::new(header).unwrap());
+        }
+        blocks
+    }
+
+    fn make_chain_id() -> ChainId {
+        ChainId::from_base58_check("NetXdQprcVkpaWU").unwrap()
+    }
+
+    fn store_blocks(
+        persistent_storage: &PersistentStorage,
+        chain_id: &ChainId,
+        blocks: &[BlockHeaderWithHash],
+    ) {
+        let log = Logger::root(slog::Discard, slog::o!());
+        let block_storage = BlockStorage::new(persistent_storage);
+        let block_meta_storage = BlockMetaStorage::new(persistent_storage);
+
+        for block in blocks {
+            block_storage.put_block_header(block).unwrap();
+            block_meta_storage
+                .put_block_header(block, chain_id, &log)
+                .unwrap();
+        }
+    }
+
+    fn set_head(
+        persistent_storage: &PersistentStorage,
+        chain_id: &ChainId,
+        block: &BlockHeaderWithHash,
+    ) {
+        SystemStorage::new(persistent_storage.main_db())
+            .set_chain_id(chain_id)
+            .unwrap();
+        ChainMetaStorage::new(persistent_storage)
+            .set_current_head(
+                chain_id,
+                Head::new(
+                    block.hash.clone(),
+                    block.header.level(),
+                    block.header.fitness().clone(),
+                ),
+            )
+            .unwrap();
+    }
+
+    fn make_archive(path: &Path, files: &[(&str, &[u8])]) {
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(path).unwrap(),
+            Compression::default(),
+        ));
+        for (file_path, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, file_path, *data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn test_verify_block_headers() {
+        let dir = tempdir().unwrap();
+        let tmp_storage = TmpStorage::create(dir.path().join("storage")).unwrap();
+        let chain_id = make_chain_id();
+        let blocks = make_chain(30);
+
+        store_blocks(tmp_storage.storage(), &chain_id, &blocks);
+        set_head(tmp_storage.storage(), &chain_id, &blocks[29]);
+
+        let head = verify_block_headers(tmp_storage.storage()).unwrap();
+        assert_eq!(head, blocks[29]);
+    }
+
+    #[test]
+    fn test_verify_block_headers_broken_chain() {
+        let dir = tempdir().unwrap();
+        let tmp_storage = TmpStorage::create(dir.path().join("storage")).unwrap();
+        let chain_id = make_chain_id();
+        let blocks = make_chain(30);
+
+        store_blocks(tmp_storage.storage(), &chain_id, &blocks[..15]);
+        store_blocks(tmp_storage.storage(), &chain_id, &blocks[16..]);
+        set_head(tmp_storage.storage(), &chain_id, &blocks[29]);
+
+        match verify_block_headers(tmp_storage.storage()) {
+            Err(SnapshotImportError::InvalidBlockHeader { block_hash, .. }) => {
+                assert_eq!(block_hash, blocks[15].hash.to_base58_check())
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_copy_blocks_from_pruned_storage() {
+        let dir = tempdir().unwrap();
+        let source = TmpStorage::create(dir.path().join("source")).unwrap();
+        let target = TmpStorage::create(dir.path().join("target")).unwrap();
+        let chain_id = make_chain_id();
+        let blocks = make_chain(30);
+        let log = Logger::root(slog::Discard, slog::o!());
+
+        // Rolling history mode source, only genesis is kept below level 10
+        store_blocks(source.storage(), &chain_id, &blocks);
+        prune_history(
+            source.storage(),
+            &chain_id,
+            &HistoryMode::Rolling { offset: 1 },
+            10,
+            100,
+        )
+        .unwrap();
+
+        store_blocks(target.storage(), &chain_id, &blocks[..1]);
+        let (block_count, _) = copy_blocks(
+            source.storage(),
+            target.storage(),
+            &chain_id,
+            &blocks[0].hash,
+            &blocks[25].hash,
+            &log,
+        );
+        assert_eq!(block_count, 16);
+        set_head(target.storage(), &chain_id, &blocks[25]);
+
+        let block_storage = BlockStorage::new(target.storage());
+        for block in blocks[1..10].iter().chain(&blocks[26..]) {
+            assert!(block_storage.get(&block.hash).unwrap().is_none());
+        }
+
+        // Verification stops at the lowest copied block
+        let head = verify_block_headers(target.storage()).unwrap();
+        assert_eq!(head, blocks[25]);
+    }
+
+    #[test]
+    fn test_unpack_snapshot_resume() {
+        let dir = tempdir().unwrap();
+        let archive_path = dir.path().join("snapshot.tar.gz");
+        let tezos_data_dir = dir.path().join("data");
+        std::fs::create_dir(&tezos_data_dir).unwrap();
+        make_archive(&archive_path, &[("a", b"aaaa"), ("dir/b", b"bbbbbb")]);
+
+        let mut checkpoint = ImportCheckpoint {
+            archive: ArchiveFingerprint::new(&archive_path).unwrap(),
+            ..Default::default()
+        };
+        unpack_snapshot(&archive_path, &tezos_data_dir, &mut checkpoint).unwrap();
+        assert_eq!(checkpoint.entries.len(), 2);
+        assert_eq!(
+            std::fs::read(tezos_data_dir.join("dir/b")).unwrap(),
+            b"bbbbbb"
+        );
+
+        // Interrupted while unpacking `dir/b`, `a` is not unpacked again
+        checkpoint.entries.remove(Path::new("dir/b"));
+        checkpoint.save(&tezos_data_dir).unwrap();
+        std::fs::write(tezos_data_dir.join("dir/b"), b"bb").unwrap();
+        std::fs::write(tezos_data_dir.join("a"), b"xxxx").unwrap();
+
+        let mut checkpoint = ImportCheckpoint::load(&tezos_data_dir).unwrap().unwrap();
+        unpack_snapshot(&archive_path, &tezos_data_dir, &mut checkpoint).unwrap();
+        assert_eq!(checkpoint.entries.len(), 2);
+        assert_eq!(
+            std::fs::read(tezos_data_dir.join("dir/b")).unwrap(),
+            b"bbbbbb"
+        );
+        assert_eq!(std::fs::read(tezos_data_dir.join("a")).unwrap(), b"xxxx");
+    }
+
+    #[test]
+    fn test_import_checkpoint_mismatch() {
+        let dir = tempdir().unwrap();
+        let archive_path = dir.path().join("snapshot.tar.gz");
+        let other_archive_path = dir.path().join("other.tar.gz");
+        let tezos_data_dir = dir.path().join("data");
+        std::fs::create_dir(&tezos_data_dir).unwrap();
+        make_archive(&archive_path, &[("a", b"aaaa")]);
+        make_archive(&other_archive_path, &[("a", b"aaab")]);
+
+        let archive = ArchiveFingerprint::new(&archive_path).unwrap();
+        let other_archive = ArchiveFingerprint::new(&other_archive_path).unwrap();
+        assert_eq!(archive, ArchiveFingerprint::new(&archive_path).unwrap());
+        assert_eq!(archive.size, other_archive.size);
+        assert_ne!(archive.ends_hash, other_archive.ends_hash);
+
+        let hash = archive_hash(&archive_path, archive.size).unwrap();
+        let other_hash = archive_hash(&other_archive_path, other_archive.size).unwrap();
+        assert_eq!(hash, archive_hash(&archive_path, archive.size).unwrap());
+        assert_ne!(hash, other_hash);
+
+        // Same size and modification time, different content
+        ImportCheckpoint {
+            archive: ArchiveFingerprint {
+                modified: archive.modified,
+                ..other_archive
+            },
+            archive_hash: other_hash,
+            ..Default::default()
+        }
+        .save(&tezos_data_dir)
+        .unwrap();
+
+        assert!(matches!(
+            import_snapshot(
+                &archive_path,
+                &tezos_data_dir,
+                TezedgeDatabaseBackendConfiguration::RocksDB
+            ),
+            Err(SnapshotImportError::CheckpointMismatch { .. })
+        ));
+        assert!(!tezos_data_dir.join("a").exists());
+    }
+}
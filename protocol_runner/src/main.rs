@@ -64,6 +64,13 @@ fn main() {
                 .possible_values(&["critical", "error", "warn", "info", "debug", "trace"])
                 .help("Set log level"),
         )
+        .arg(
+            Arg::with_name("context-gc-preserved-cycles")
+                .long("context-gc-preserved-cycles")
+                .takes_value(true)
+                .value_name("NUM")
+                .help("Enables the garbage collection of the on-disk context, the commits of the last NUM cycles are preserved"),
+        )
         .get_matches();
 
     let cmd_socket_path = matches
@@ -78,9 +85,15 @@ fn main() {
         .unwrap_or("info")
         .parse::<slog::Level>()
         .expect("Was expecting one value from slog::Level");
+    let context_gc_preserved_cycles = matches.value_of("context-gc-preserved-cycles").map(|v| {
+        v.parse::<usize>()
+            .expect("Was expecting a number of cycles for context-gc-preserved-cycles")
+    });
 
     let log = create_logger(log_level, endpoint_name);
 
+    tezos_context::initializer::set_persistent_gc_preserved_cycles(context_gc_preserved_cycles);
+
     let shutdown_callback = |log: &Logger| {
         debug!(log, "Shutting down OCaml runtime");
         match std::panic::catch_unwind(|| {
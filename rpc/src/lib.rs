@@ -6,6 +6,7 @@ use hyper::{Body, Response, StatusCode};
 use slog::{error, Logger};
 
 use crate::helpers::RpcServiceError;
+use crate::server::limits::RpcLimitRejection;
 
 pub mod encoding;
 pub mod helpers;
@@ -192,6 +193,48 @@ pub(crate) fn not_found() -> ServiceResult {
         .body(Body::empty())?)
 }
 
+/// Generate 403 response for a request denied by the RPC access control lists
+pub(crate) fn forbidden(method: &hyper::Method, path: &str) -> ServiceResult {
+    let body = serde_json::json!([{
+        "kind": "permanent",
+        "id": "tezedge.rpc.forbidden",
+        "method": method.as_str(),
+        "path": path,
+        "msg": "Access to this RPC is not allowed on this listen address",
+    }]);
+    make_response_with_status_and_json_string(403, &body.to_string())
+}
+
+/// Generate 429 response for a request rejected by the RPC rate or concurrency limits
+pub(crate) fn too_many_requests(rejection: &RpcLimitRejection) -> ServiceResult {
+    let (body, retry_after) = match rejection {
+        RpcLimitRejection::RateLimited { retry_after } => (
+            serde_json::json!([{
+                "kind": "temporary",
+                "id": "tezedge.rpc.rate_limited",
+                "msg": "Too many requests from this client",
+            }]),
+            Some(retry_after.as_secs_f64().ceil() as u64),
+        ),
+        RpcLimitRejection::ConcurrencyLimited { route } => (
+            serde_json::json!([{
+                "kind": "temporary",
+                "id": "tezedge.rpc.concurrency_limited",
+                "route": route,
+                "msg": "Too many concurrent requests for this RPC",
+            }]),
+            None,
+        ),
+    };
+    let mut response = make_response_with_status_and_json_string(429, &body.to_string())?;
+    if let Some(retry_after) = retry_after {
+        response
+            .headers_mut()
+            .insert(hyper::header::RETRY_AFTER, retry_after.max(1).into());
+    }
+    Ok(response)
+}
+
 /// Generate 500 error
 pub(crate) fn error(error: anyhow::Error) -> ServiceResult {
     error_with_message(format!("{:?}", error))
@@ -0,0 +1,375 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Access control lists for the RPC server, similar to the `acl` section of the Octez
+//! node configuration.
+//!
+//! Each entry applies to the connections accepted on a listen address and is either
+//! a whitelist or a blacklist of rules. A rule is an optional HTTP method followed by
+//! a path pattern, where `*` matches exactly one path segment and a trailing `**`
+//! matches any number of segments:
+//!
+//! ```json
+//! [
+//!   { "address": "localhost", "whitelist": ["**"] },
+//!   { "address": "0.0.0.0:18732", "blacklist": ["POST /injection/**", "/dev/**"] }
+//! ]
+//! ```
+//!
+//! The first entry matching the listen address decides, requests on addresses without
+//! any entry are allowed.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::str::FromStr;
+
+use hyper::Method;
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RpcAclError {
+    #[error("Failed to read RPC ACL file: {reason}")]
+    IoError {
+        #[from]
+        reason: std::io::Error,
+    },
+    #[error("Failed to parse RPC ACL file: {reason}")]
+    ParseError {
+        #[from]
+        reason: serde_json::Error,
+    },
+    #[error("Invalid listen address `{address}`")]
+    InvalidAddress { address: String },
+    #[error("Invalid RPC route pattern `{rule}`: {reason}")]
+    InvalidRule { rule: String, reason: String },
+    #[error("RPC ACL entry for `{address}` must have exactly one of `whitelist` or `blacklist`")]
+    InvalidEntry { address: String },
+}
+
+/// Access control lists for all listen addresses.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct RpcAcl {
+    entries: Vec<RpcAclEntry>,
+}
+
+impl RpcAcl {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, RpcAclError> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Allows all RPCs on `address`, takes precedence over the entries already present.
+    pub fn allow_all(&mut self, address: ListenAddress) {
+        self.entries.insert(
+            0,
+            RpcAclEntry {
+                address,
+                policy: RpcAclPolicy::Whitelist(vec![RoutePattern::any()]),
+            },
+        );
+    }
+
+    /// Checks whether `method` on `path` is allowed for a request accepted on `listen_address`.
+    pub fn is_allowed(&self, listen_address: &SocketAddr, method: &Method, path: &str) -> bool {
+        match self
+            .entries
+            .iter()
+            .find(|entry| entry.address.matches(listen_address))
+        {
+            Some(entry) => entry.policy.is_allowed(method, path),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawRpcAclEntry")]
+struct RpcAclEntry {
+    address: ListenAddress,
+    policy: RpcAclPolicy,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRpcAclEntry {
+    address: String,
+    whitelist: Option<Vec<String>>,
+    blacklist: Option<Vec<String>>,
+}
+
+impl TryFrom<RawRpcAclEntry> for RpcAclEntry {
+    type Error = RpcAclError;
+
+    fn try_from(raw: RawRpcAclEntry) -> Result<Self, Self::Error> {
+        let parse_rules = |rules: Vec<String>| -> Result<Vec<RoutePattern>, RpcAclError> {
+            rules.iter().map(|rule| rule.parse()).collect()
+        };
+        let policy = match (raw.whitelist, raw.blacklist) {
+            (Some(rules), None) => RpcAclPolicy::Whitelist(parse_rules(rules)?),
+            (None, Some(rules)) => RpcAclPolicy::Blacklist(parse_rules(rules)?),
+            _ => {
+                return Err(RpcAclError::InvalidEntry {
+                    address: raw.address,
+                })
+            }
+        };
+        Ok(Self {
+            address: raw.address.parse()?,
+            policy,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum RpcAclPolicy {
+    /// Only requests matching one of the rules are allowed.
+    Whitelist(Vec<RoutePattern>),
+    /// Requests matching one of the rules are denied.
+    Blacklist(Vec<RoutePattern>),
+}
+
+impl RpcAclPolicy {
+    fn is_allowed(&self, method: &Method, path: &str) -> bool {
+        match self {
+            Self::Whitelist(rules) => rules.iter().any(|rule| rule.matches(method, path)),
+            Self::Blacklist(rules) => !rules.iter().any(|rule| rule.matches(method, path)),
+        }
+    }
+}
+
+/// Listen address an ACL entry applies to, `0.0.0.0` and `::` match any address
+/// and `localhost` matches the loopback addresses, the port is optional.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenAddress {
+    host: ListenHost,
+    port: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ListenHost {
+    Localhost,
+    Ip(IpAddr),
+}
+
+impl ListenAddress {
+    fn matches(&self, address: &SocketAddr) -> bool {
+        let host_matches = match &self.host {
+            ListenHost::Localhost => address.ip().is_loopback(),
+            ListenHost::Ip(ip) => ip.is_unspecified() || *ip == address.ip(),
+        };
+        host_matches && self.port.map_or(true, |port| port == address.port())
+    }
+}
+
+impl FromStr for ListenAddress {
+    type Err = RpcAclError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RpcAclError::InvalidAddress {
+            address: s.to_string(),
+        };
+
+        if let Ok(address) = s.parse::<SocketAddr>() {
+            return Ok(Self {
+                host: ListenHost::Ip(address.ip()),
+                port: Some(address.port()),
+            });
+        }
+        if let Ok(ip) = s.trim_start_matches('[').trim_end_matches(']').parse() {
+            return Ok(Self {
+                host: ListenHost::Ip(ip),
+                port: None,
+            });
+        }
+        let (host, port) = match s.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port.parse().map_err(|_| invalid())?)),
+            None => (s, None),
+        };
+        if host.eq_ignore_ascii_case("localhost") {
+            Ok(Self {
+                host: ListenHost::Localhost,
+                port,
+            })
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+/// Optional HTTP method and path pattern, e.g. `POST /injection/**`.
+#[derive(Debug, Clone)]
+pub(crate) struct RoutePattern {
+    method: Option<Method>,
+    segments: Vec<String>,
+}
+
+impl RoutePattern {
+    fn any() -> Self {
+        Self {
+            method: None,
+            segments: vec!["**".to_string()],
+        }
+    }
+
+    pub(crate) fn matches(&self, method: &Method, path: &str) -> bool {
+        if self.method.as_ref().map_or(false, |m| m != method) {
+            return false;
+        }
+
+        let mut path = path.split('/').filter(|s| !s.is_empty());
+        for segment in &self.segments {
+            match (segment.as_str(), path.next()) {
+                ("**", _) => return true,
+                (_, None) => return false,
+                ("*", Some(_)) => (),
+                (pattern, Some(s)) if pattern == s => (),
+                _ => return false,
+            }
+        }
+        path.next().is_none()
+    }
+}
+
+impl FromStr for RoutePattern {
+    type Err = RpcAclError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| RpcAclError::InvalidRule {
+            rule: s.to_string(),
+            reason: reason.to_string(),
+        };
+
+        let (method, path) = match s.trim().split_once(' ') {
+            Some((method, path)) => (
+                Some(
+                    Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                        .map_err(|_| invalid("invalid HTTP method"))?,
+                ),
+                path.trim(),
+            ),
+            None => (None, s.trim()),
+        };
+        let segments: Vec<String> = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .collect();
+        if let Some(position) = segments.iter().position(|s| s == "**") {
+            if position != segments.len() - 1 {
+                return Err(invalid("`**` is only allowed as the last segment"));
+            }
+        }
+
+        Ok(Self { method, segments })
+    }
+}
+
+impl fmt::Display for RoutePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(method) = &self.method {
+            write!(f, "{} ", method)?;
+        }
+        write!(f, "/{}", self.segments.join("/"))
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.host {
+            ListenHost::Localhost => write!(f, "localhost")?,
+            ListenHost::Ip(IpAddr::V6(ip)) if self.port.is_some() => write!(f, "[{}]", ip)?,
+            ListenHost::Ip(ip) => write!(f, "{}", ip)?,
+        }
+        match self.port {
+            Some(port) => write!(f, ":{}", port),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_acl(json: &str) -> RpcAcl {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let rule: RoutePattern = "POST /injection/*".parse().unwrap();
+        assert!(rule.matches(&Method::POST, "/injection/operation"));
+        assert!(!rule.matches(&Method::GET, "/injection/operation"));
+        assert!(!rule.matches(&Method::POST, "/injection"));
+        assert!(!rule.matches(&Method::POST, "/injection/operation/other"));
+
+        let rule: RoutePattern = "/chains/*/blocks/**".parse().unwrap();
+        assert!(rule.matches(&Method::GET, "/chains/main/blocks"));
+        assert!(rule.matches(&Method::GET, "/chains/main/blocks/head/header"));
+        assert!(!rule.matches(&Method::GET, "/chains/main/mempool"));
+
+        assert!(RoutePattern::any().matches(&Method::PATCH, "/"));
+        assert!("/a/**/b".parse::<RoutePattern>().is_err());
+        assert!("G(ET /a".parse::<RoutePattern>().is_err());
+    }
+
+    #[test]
+    fn test_listen_address() {
+        let local: SocketAddr = "127.0.0.1:18732".parse().unwrap();
+        let public: SocketAddr = "10.0.0.1:18732".parse().unwrap();
+
+        let address: ListenAddress = "localhost".parse().unwrap();
+        assert!(address.matches(&local));
+        assert!(address.matches(&"[::1]:1".parse().unwrap()));
+        assert!(!address.matches(&public));
+
+        let address: ListenAddress = "0.0.0.0:18732".parse().unwrap();
+        assert!(address.matches(&local));
+        assert!(address.matches(&public));
+        assert!(!address.matches(&"10.0.0.1:8732".parse().unwrap()));
+
+        let address: ListenAddress = "10.0.0.1".parse().unwrap();
+        assert!(address.matches(&public));
+        assert!(!address.matches(&local));
+        assert_eq!(address.to_string(), "10.0.0.1");
+
+        assert!("localhost:port".parse::<ListenAddress>().is_err());
+        assert!("example.com".parse::<ListenAddress>().is_err());
+    }
+
+    #[test]
+    fn test_acl() {
+        let local: SocketAddr = "127.0.0.1:18732".parse().unwrap();
+        let public: SocketAddr = "10.0.0.1:18732".parse().unwrap();
+        let acl = parse_acl(
+            r#"[
+                { "address": "localhost", "whitelist": ["**"] },
+                { "address": "0.0.0.0", "whitelist": ["GET /dev/**", "/chains/*/blocks"] }
+            ]"#,
+        );
+
+        assert!(acl.is_allowed(&local, &Method::POST, "/injection/operation"));
+        assert!(!acl.is_allowed(&public, &Method::POST, "/injection/operation"));
+        assert!(acl.is_allowed(&public, &Method::GET, "/dev/version"));
+        assert!(!acl.is_allowed(&public, &Method::PATCH, "/dev/shell/automaton/bakers"));
+        assert!(acl.is_allowed(&public, &Method::GET, "/chains/main/blocks"));
+        assert!(!acl.is_allowed(&public, &Method::GET, "/chains/main/blocks/head"));
+
+        let mut acl = parse_acl(r#"[{ "address": "0.0.0.0", "blacklist": ["/injection/**"] }]"#);
+        assert!(!acl.is_allowed(&public, &Method::POST, "/injection/operation"));
+        assert!(acl.is_allowed(&public, &Method::GET, "/chains/main/blocks"));
+        acl.allow_all("10.0.0.1".parse().unwrap());
+        assert!(acl.is_allowed(&public, &Method::POST, "/injection/operation"));
+
+        assert!(RpcAcl::default().is_allowed(&public, &Method::POST, "/injection/operation"));
+        assert!(serde_json::from_str::<RpcAcl>(r#"[{ "address": "localhost" }]"#).is_err());
+        assert!(serde_json::from_str::<RpcAcl>(
+            r#"[{ "address": "localhost", "whitelist": [], "blacklist": [] }]"#
+        )
+        .is_err());
+    }
+}
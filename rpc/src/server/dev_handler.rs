@@ -4,7 +4,7 @@
 use crate::helpers::{parse_block_hash, parse_chain_id, RpcServiceError, MAIN_CHAIN_ID};
 use crate::result_option_to_json_response;
 use crate::server::{HasSingleValue, Params, Query, RpcServiceEnvironment};
-use crate::services::{context, dev_services, rewards_services};
+use crate::services::{base_services, context, dev_services, metrics_services, rewards_services};
 use crate::{empty, make_json_response, required_param, result_to_json_response, ServiceResult};
 use anyhow::format_err;
 use crypto::hash::{BlockHash, CryptoboxPublicKeyHash, OperationHash};
@@ -106,6 +106,15 @@ pub async fn dev_db_stats(
     result_to_json_response(Ok(env.persistent_storage.main_db().db_stats()), env.log())
 }
 
+pub async fn rpc_limits_stats(
+    _: Request<Body>,
+    _params: Params,
+    _query: Query,
+    env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    result_to_json_response(Ok(env.rpc_limits.stats()), env.log())
+}
+
 pub async fn dev_action_cursor(
     _: Request<Body>,
     params: Params,
@@ -219,6 +228,29 @@ pub async fn dev_stats_memory_protocol_runners(
     }
 }
 
+pub async fn metrics(
+    _: Request<Body>,
+    _: Params,
+    _: Query,
+    env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    let contents = metrics_services::get_metrics(&env).await?;
+
+    Ok(Response::builder()
+        .header(
+            hyper::header::CONTENT_TYPE,
+            metrics_services::METRICS_CONTENT_TYPE,
+        )
+        .header(hyper::header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(hyper::header::ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type")
+        .header(hyper::header::ACCESS_CONTROL_ALLOW_HEADERS, "content-type")
+        .header(
+            hyper::header::ACCESS_CONTROL_ALLOW_METHODS,
+            "GET, POST, OPTIONS, PUT",
+        )
+        .body(Body::from(contents))?)
+}
+
 pub async fn context_stats(
     _: Request<Body>,
     _: Params,
@@ -265,6 +297,24 @@ pub async fn cycle_eras(
     )
 }
 
+/// Merkle proof of `data/<path>` in the TezEdge context of the block
+pub async fn context_merkle_proof(
+    _: Request<Body>,
+    params: Params,
+    _: Query,
+    env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    let chain_id = parse_chain_id(required_param!(params, "chain_id")?, &env)?;
+    let block_hash = parse_block_hash(&chain_id, required_param!(params, "block_id")?, &env)
+        .map_err(|e| format_err!("Failed to parse_block_hash, reason: {}", e))?;
+    let path = params.get_str("any").map(|s| s.to_owned());
+
+    result_to_json_response(
+        base_services::get_context_merkle_proof(&chain_id, &block_hash, path, &env).await,
+        env.log(),
+    )
+}
+
 /// Get the version string
 pub async fn dev_version(
     _: Request<Body>,
@@ -0,0 +1,521 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Per-client rate limits and per-route concurrency limits of the RPC server.
+//!
+//! Every client IP address (IPv6 /64 network) has a token bucket refilled at
+//! `requests_per_second` and holding at most `burst` tokens, each request takes one token. Routes matching a concurrency limit
+//! pattern (see [`crate::server::acl`] for the syntax) share a number of permits, a request
+//! holds its permit until its response body is sent, so streams count until they end. Rejected requests get 429.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use hyper::Method;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::server::acl::{RoutePattern, RpcAclError};
+
+/// At most this many clients have a bucket, the least recently seen one is forgotten
+/// to make room for a new one.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+#[derive(Debug, Error)]
+pub enum RpcLimitParseError {
+    #[error("Invalid RPC rate limit `{value}`, expected <REQUESTS_PER_SECOND>[/<BURST>]")]
+    InvalidRateLimit { value: String },
+    #[error("Invalid RPC concurrency limit `{value}`, expected <ROUTE_PATTERN>=<MAX>")]
+    InvalidConcurrencyLimit { value: String },
+    #[error("Invalid RPC concurrency limit route: {reason}")]
+    InvalidRoutePattern {
+        #[from]
+        reason: RpcAclError,
+    },
+}
+
+/// Token bucket parameters, `10/50` is 10 requests per second with bursts of up to 50 requests.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RpcRateLimit {
+    pub requests_per_second: f64,
+    pub burst: u32,
+}
+
+impl FromStr for RpcRateLimit {
+    type Err = RpcLimitParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RpcLimitParseError::InvalidRateLimit {
+            value: s.to_string(),
+        };
+        let (rate, burst) = match s.split_once('/') {
+            Some((rate, burst)) => (rate, Some(burst)),
+            None => (s, None),
+        };
+        let requests_per_second: f64 = rate.trim().parse().map_err(|_| invalid())?;
+        if !requests_per_second.is_finite() || requests_per_second <= 0.0 {
+            return Err(invalid());
+        }
+        let burst = match burst {
+            Some(burst) => burst.trim().parse().map_err(|_| invalid())?,
+            None => requests_per_second.ceil() as u32,
+        };
+        if burst == 0 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            requests_per_second,
+            burst,
+        })
+    }
+}
+
+/// Maximal number of requests matching the route pattern handled at once, e.g. `/dev/rewards/**=2`.
+#[derive(Debug, Clone)]
+pub struct RpcConcurrencyLimit {
+    route: RoutePattern,
+    pub max: usize,
+}
+
+impl FromStr for RpcConcurrencyLimit {
+    type Err = RpcLimitParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RpcLimitParseError::InvalidConcurrencyLimit {
+            value: s.to_string(),
+        };
+        let (route, max) = s.rsplit_once('=').ok_or_else(invalid)?;
+        let max = max.trim().parse().map_err(|_| invalid())?;
+        if max == 0 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            route: route.parse()?,
+            max,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RpcLimitsConfiguration {
+    pub rate_limit: Option<RpcRateLimit>,
+    /// Clients which are not rate limited, e.g. a local baker.
+    pub rate_limit_exempt: Vec<IpAddr>,
+    /// The first limit matching the request applies.
+    pub concurrency_limits: Vec<RpcConcurrencyLimit>,
+}
+
+/// Reason of a 429 response.
+#[derive(Debug)]
+pub(crate) enum RpcLimitRejection {
+    RateLimited { retry_after: Duration },
+    ConcurrencyLimited { route: String },
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn full(limit: &RpcRateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, limit: &RpcRateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.requests_per_second).min(limit.burst as f64);
+        self.updated = now;
+    }
+
+    /// Takes a token, or returns how long to wait for the next one.
+    fn take(&mut self, limit: &RpcRateLimit, now: Instant) -> Result<(), Duration> {
+        self.refill(limit, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / limit.requests_per_second,
+            ))
+        }
+    }
+}
+
+/// Clients are limited by their address, IPv6 clients by their /64 network, which is
+/// usually assigned to a single host, so that rotating the addresses doesn't help.
+fn client_key(client: IpAddr) -> IpAddr {
+    match client {
+        IpAddr::V4(_) => client,
+        IpAddr::V6(ip) => match ip.segments() {
+            [0, 0, 0, 0, 0, 0xffff, high, low] => {
+                IpAddr::V4(Ipv4Addr::from((u32::from(high) << 16) | u32::from(low)))
+            }
+            [a, b, c, d, ..] => IpAddr::V6(Ipv6Addr::new(a, b, c, d, 0, 0, 0, 0)),
+        },
+    }
+}
+
+#[derive(Debug, Default)]
+struct ClientBuckets {
+    /// Bucket of the client and the sequence number of its last request.
+    buckets: HashMap<IpAddr, (TokenBucket, u64)>,
+    /// Clients by the sequence number of their last request, the least recent first.
+    recent: BTreeMap<u64, IpAddr>,
+    sequence: u64,
+}
+
+#[derive(Debug)]
+struct RateLimiter {
+    limit: RpcRateLimit,
+    /// Keys of the exempt clients, see [`client_key`].
+    exempt: Vec<IpAddr>,
+    clients: Mutex<ClientBuckets>,
+}
+
+impl RateLimiter {
+    fn take(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let key = client_key(client);
+        if self.exempt.contains(&key) {
+            return Ok(());
+        }
+
+        let mut clients = match self.clients.lock() {
+            Ok(clients) => clients,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let ClientBuckets {
+            buckets,
+            recent,
+            sequence,
+        } = &mut *clients;
+        *sequence += 1;
+
+        if let Some((_, last)) = buckets.get(&key) {
+            recent.remove(last);
+        } else if buckets.len() >= MAX_TRACKED_CLIENTS {
+            if let Some(least_recent) = recent.keys().next().copied() {
+                if let Some(forgotten) = recent.remove(&least_recent) {
+                    buckets.remove(&forgotten);
+                }
+            }
+        }
+        recent.insert(*sequence, key);
+
+        let (bucket, last) = buckets
+            .entry(key)
+            .or_insert_with(|| (TokenBucket::full(&self.limit, now), 0));
+        *last = *sequence;
+        bucket.take(&self.limit, now)
+    }
+
+    fn tracked_clients(&self) -> usize {
+        match self.clients.lock() {
+            Ok(clients) => clients.buckets.len(),
+            Err(poisoned) => poisoned.into_inner().buckets.len(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ConcurrencyLimiter {
+    limit: RpcConcurrencyLimit,
+    permits: Arc<Semaphore>,
+    rejected: AtomicU64,
+}
+
+/// Limits of the RPC server with their counters.
+#[derive(Debug, Default)]
+pub struct RpcLimits {
+    rate_limiter: Option<RateLimiter>,
+    concurrency_limiters: Vec<ConcurrencyLimiter>,
+    rate_limited: AtomicU64,
+}
+
+impl RpcLimits {
+    pub fn new(configuration: RpcLimitsConfiguration) -> Self {
+        Self {
+            rate_limiter: configuration.rate_limit.map(|limit| RateLimiter {
+                limit,
+                exempt: configuration
+                    .rate_limit_exempt
+                    .into_iter()
+                    .map(client_key)
+                    .collect(),
+                clients: Mutex::new(ClientBuckets::default()),
+            }),
+            concurrency_limiters: configuration
+                .concurrency_limits
+                .into_iter()
+                .map(|limit| ConcurrencyLimiter {
+                    permits: Arc::new(Semaphore::new(limit.max)),
+                    limit,
+                    rejected: AtomicU64::new(0),
+                })
+                .collect(),
+            rate_limited: AtomicU64::new(0),
+        }
+    }
+
+    /// Checks the limits for the request, the returned permit (if any) must be held
+    /// while the request is handled.
+    pub(crate) fn check(
+        &self,
+        client: IpAddr,
+        method: &Method,
+        path: &str,
+    ) -> Result<Option<OwnedSemaphorePermit>, RpcLimitRejection> {
+        self.check_at(client, method, path, Instant::now())
+    }
+
+    fn check_at(
+        &self,
+        client: IpAddr,
+        method: &Method,
+        path: &str,
+        now: Instant,
+    ) -> Result<Option<OwnedSemaphorePermit>, RpcLimitRejection> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            if let Err(retry_after) = rate_limiter.take(client, now) {
+                self.rate_limited.fetch_add(1, Ordering::Relaxed);
+                return Err(RpcLimitRejection::RateLimited { retry_after });
+            }
+        }
+
+        match self
+            .concurrency_limiters
+            .iter()
+            .find(|limiter| limiter.limit.route.matches(method, path))
+        {
+            Some(limiter) => match limiter.permits.clone().try_acquire_owned() {
+                Ok(permit) => Ok(Some(permit)),
+                Err(_) => {
+                    limiter.rejected.fetch_add(1, Ordering::Relaxed);
+                    Err(RpcLimitRejection::ConcurrencyLimited {
+                        route: limiter.limit.route.to_string(),
+                    })
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn stats(&self) -> RpcLimitsStats {
+        RpcLimitsStats {
+            rate_limited_requests: self.rate_limited.load(Ordering::Relaxed),
+            rate_limited_clients: self
+                .rate_limiter
+                .as_ref()
+                .map_or(0, RateLimiter::tracked_clients),
+            routes: self
+                .concurrency_limiters
+                .iter()
+                .map(|limiter| RouteConcurrencyStats {
+                    route: limiter.limit.route.to_string(),
+                    max: limiter.limit.max,
+                    in_flight: limiter.limit.max - limiter.permits.available_permits(),
+                    rejected_requests: limiter.rejected.load(Ordering::Relaxed),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RpcLimitsStats {
+    /// Requests rejected by the per-client rate limit.
+    pub rate_limited_requests: u64,
+    /// Clients with a token bucket.
+    pub rate_limited_clients: usize,
+    pub routes: Vec<RouteConcurrencyStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RouteConcurrencyStats {
+    pub route: String,
+    pub max: usize,
+    pub in_flight: usize,
+    /// Requests rejected by the concurrency limit.
+    pub rejected_requests: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(
+            "10/50".parse::<RpcRateLimit>().unwrap(),
+            RpcRateLimit {
+                requests_per_second: 10.0,
+                burst: 50
+            }
+        );
+        assert_eq!("0.5".parse::<RpcRateLimit>().unwrap().burst, 1);
+        assert!("0".parse::<RpcRateLimit>().is_err());
+        assert!("10/0".parse::<RpcRateLimit>().is_err());
+
+        let limit: RpcConcurrencyLimit = "GET /dev/rewards/** = 2".parse().unwrap();
+        assert_eq!(limit.max, 2);
+        assert_eq!(limit.route.to_string(), "GET /dev/rewards/**");
+        assert!("/chains/*/blocks".parse::<RpcConcurrencyLimit>().is_err());
+        assert!("/chains/*/blocks=0".parse::<RpcConcurrencyLimit>().is_err());
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let limits = RpcLimits::new(RpcLimitsConfiguration {
+            rate_limit: Some("2/3".parse().unwrap()),
+            rate_limit_exempt: vec!["127.0.0.1".parse().unwrap()],
+            concurrency_limits: vec![],
+        });
+        let client: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limits.check_at(client, &Method::GET, "/", now).is_ok());
+        }
+        match limits.check_at(client, &Method::GET, "/", now) {
+            Err(RpcLimitRejection::RateLimited { retry_after }) => {
+                assert_eq!(retry_after, Duration::from_millis(500))
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+        // other clients have their own bucket
+        assert!(limits.check_at(other, &Method::GET, "/", now).is_ok());
+
+        let later = now + Duration::from_millis(500);
+        assert!(limits.check_at(client, &Method::GET, "/", later).is_ok());
+        assert!(limits.check_at(client, &Method::GET, "/", later).is_err());
+
+        for _ in 0..10 {
+            assert!(limits
+                .check_at("127.0.0.1".parse().unwrap(), &Method::GET, "/", now)
+                .is_ok());
+        }
+
+        let stats = limits.stats();
+        assert_eq!(stats.rate_limited_requests, 2);
+        assert_eq!(stats.rate_limited_clients, 2);
+    }
+
+    #[test]
+    fn test_rate_limit_exempt_ipv4_mapped() {
+        let limits = RpcLimits::new(RpcLimitsConfiguration {
+            rate_limit: Some("1/1".parse().unwrap()),
+            rate_limit_exempt: vec![
+                "127.0.0.1".parse().unwrap(),
+                "::ffff:10.0.0.1".parse().unwrap(),
+            ],
+            concurrency_limits: vec![],
+        });
+        let now = Instant::now();
+
+        // loopback as accepted by a dual-stack `[::]` listener
+        let mapped_loopback: IpAddr = "::ffff:127.0.0.1".parse().unwrap();
+        let exempt: IpAddr = "10.0.0.1".parse().unwrap();
+        for _ in 0..10 {
+            assert!(limits
+                .check_at(mapped_loopback, &Method::GET, "/", now)
+                .is_ok());
+            assert!(limits.check_at(exempt, &Method::GET, "/", now).is_ok());
+        }
+        assert_eq!(limits.stats().rate_limited_clients, 0);
+
+        // a mapped client shares the bucket of its IPv4 address
+        let client: IpAddr = "10.0.0.2".parse().unwrap();
+        let mapped_client: IpAddr = "::ffff:10.0.0.2".parse().unwrap();
+        assert!(limits.check_at(client, &Method::GET, "/", now).is_ok());
+        assert!(limits
+            .check_at(mapped_client, &Method::GET, "/", now)
+            .is_err());
+        assert_eq!(limits.stats().rate_limited_clients, 1);
+    }
+
+    #[test]
+    fn test_tracked_clients_are_bounded() {
+        let limits = RpcLimits::new(RpcLimitsConfiguration {
+            rate_limit: Some("1/1".parse().unwrap()),
+            rate_limit_exempt: vec![],
+            concurrency_limits: vec![],
+        });
+        let now = Instant::now();
+        let active: IpAddr = "10.0.0.1".parse().unwrap();
+        assert!(limits.check_at(active, &Method::GET, "/", now).is_ok());
+
+        // addresses of one /64 network share a bucket
+        assert!(limits
+            .check_at("2001:db8::1".parse().unwrap(), &Method::GET, "/", now)
+            .is_ok());
+        assert!(limits
+            .check_at("2001:db8::2".parse().unwrap(), &Method::GET, "/", now)
+            .is_err());
+        assert_eq!(limits.stats().rate_limited_clients, 2);
+
+        // a client rotating its networks
+        for i in 0..2 * MAX_TRACKED_CLIENTS as u32 {
+            let client = IpAddr::V6(Ipv6Addr::new(
+                0x2001,
+                0xdb9,
+                (i >> 16) as u16,
+                i as u16,
+                0,
+                0,
+                0,
+                1,
+            ));
+            assert!(limits.check_at(client, &Method::GET, "/", now).is_ok());
+            if i % 1000 == 0 {
+                // still seen, so never forgotten
+                assert!(limits.check_at(active, &Method::GET, "/", now).is_err());
+            }
+            assert!(limits.stats().rate_limited_clients <= MAX_TRACKED_CLIENTS);
+        }
+        assert_eq!(limits.stats().rate_limited_clients, MAX_TRACKED_CLIENTS);
+        assert!(limits.check_at(active, &Method::GET, "/", now).is_err());
+    }
+
+    #[test]
+    fn test_concurrency_limit() {
+        let limits = RpcLimits::new(RpcLimitsConfiguration {
+            rate_limit: None,
+            rate_limit_exempt: vec![],
+            concurrency_limits: vec!["/chains/*/blocks=1".parse().unwrap()],
+        });
+        let client: IpAddr = "10.0.0.1".parse().unwrap();
+
+        let permit = limits
+            .check(client, &Method::GET, "/chains/main/blocks")
+            .unwrap();
+        assert!(permit.is_some());
+        assert!(matches!(
+            limits.check(client, &Method::GET, "/chains/main/blocks"),
+            Err(RpcLimitRejection::ConcurrencyLimited { .. })
+        ));
+        assert!(matches!(
+            limits.check(client, &Method::GET, "/chains/main/blocks/head"),
+            Ok(None)
+        ));
+
+        let stats = limits.stats();
+        assert_eq!(stats.routes[0].in_flight, 1);
+        assert_eq!(stats.routes[0].rejected_requests, 1);
+
+        drop(permit);
+        assert!(limits
+            .check(client, &Method::GET, "/chains/main/blocks")
+            .is_ok());
+        assert_eq!(limits.stats().routes[0].in_flight, 0);
+    }
+}
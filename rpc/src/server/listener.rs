@@ -0,0 +1,328 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Listen addresses of the RPC server, each one with its own route set and optional TLS.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_native_tls::native_tls;
+use tokio_native_tls::{TlsAcceptor, TlsStream};
+
+/// Connections which don't finish the TLS handshake in time are closed.
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Routes served on a listen address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcRouteSet {
+    /// All routes, unsafe routes only with `--allow-unsafe-rpc`.
+    All,
+    /// Only `GET` routes, e.g. for a public endpoint.
+    ReadOnly,
+}
+
+/// Certificate chain and private key (PKCS #8), both PEM encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcTlsConfiguration {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcListenerConfiguration {
+    pub address: SocketAddr,
+    pub routes: RpcRouteSet,
+    pub tls: Option<RpcTlsConfiguration>,
+}
+
+impl RpcListenerConfiguration {
+    pub fn new(address: SocketAddr) -> Self {
+        Self {
+            address,
+            routes: RpcRouteSet::All,
+            tls: None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RpcListenerParseError {
+    #[error("Invalid listen address `{address}`")]
+    InvalidAddress { address: String },
+    #[error("Unknown listener option `{option}`, expected `read-only` or `tls`")]
+    UnknownOption { option: String },
+}
+
+/// Parsed listener specification `<IP:PORT>[,read-only][,tls]`, TLS certificate and key
+/// are shared by all listeners and so they are not part of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcListenerSpec {
+    pub address: SocketAddr,
+    pub routes: RpcRouteSet,
+    pub tls: bool,
+}
+
+impl RpcListenerSpec {
+    pub fn into_configuration(self, tls: Option<&RpcTlsConfiguration>) -> RpcListenerConfiguration {
+        RpcListenerConfiguration {
+            address: self.address,
+            routes: self.routes,
+            tls: if self.tls { tls.cloned() } else { None },
+        }
+    }
+}
+
+impl FromStr for RpcListenerSpec {
+    type Err = RpcListenerParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',').map(str::trim);
+        let address = parts.next().unwrap_or_default();
+        let mut spec = Self {
+            address: address
+                .parse()
+                .map_err(|_| RpcListenerParseError::InvalidAddress {
+                    address: address.to_string(),
+                })?,
+            routes: RpcRouteSet::All,
+            tls: false,
+        };
+        for option in parts {
+            match option {
+                "read-only" => spec.routes = RpcRouteSet::ReadOnly,
+                "tls" => spec.tls = true,
+                _ => {
+                    return Err(RpcListenerParseError::UnknownOption {
+                        option: option.to_string(),
+                    })
+                }
+            }
+        }
+        Ok(spec)
+    }
+}
+
+impl fmt::Display for RpcListenerConfiguration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}://{}",
+            if self.tls.is_some() { "https" } else { "http" },
+            self.address
+        )?;
+        if self.routes == RpcRouteSet::ReadOnly {
+            write!(f, " (read-only)")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RpcTlsError {
+    #[error("Failed to read TLS certificate or key: {reason}")]
+    IoError {
+        #[from]
+        reason: std::io::Error,
+    },
+    #[error("Invalid TLS certificate or key: {reason}")]
+    TlsError {
+        #[from]
+        reason: native_tls::Error,
+    },
+    #[error("TLS handshake failed: {reason}")]
+    HandshakeError { reason: native_tls::Error },
+    #[error("TLS handshake timed out")]
+    HandshakeTimeout,
+}
+
+/// TLS acceptor which can be reloaded from the certificate and key files,
+/// connections being established keep the acceptor they started with.
+#[derive(Clone)]
+pub(crate) struct ReloadableTlsAcceptor {
+    configuration: RpcTlsConfiguration,
+    acceptor: Arc<RwLock<TlsAcceptor>>,
+    handshake_timeout: Duration,
+}
+
+impl ReloadableTlsAcceptor {
+    pub(crate) fn load(configuration: RpcTlsConfiguration) -> Result<Self, RpcTlsError> {
+        let acceptor = Self::load_acceptor(&configuration)?;
+        Ok(Self {
+            configuration,
+            acceptor: Arc::new(RwLock::new(acceptor)),
+            handshake_timeout: TLS_HANDSHAKE_TIMEOUT,
+        })
+    }
+
+    /// Reloads the certificate and key, the current ones are kept on failure.
+    pub(crate) fn reload(&self) -> Result<(), RpcTlsError> {
+        let acceptor = Self::load_acceptor(&self.configuration)?;
+        match self.acceptor.write() {
+            Ok(mut current) => *current = acceptor,
+            Err(poisoned) => *poisoned.into_inner() = acceptor,
+        }
+        Ok(())
+    }
+
+    /// Accepts the connection with the current certificate and key.
+    pub(crate) async fn accept<S>(&self, stream: S) -> Result<TlsStream<S>, RpcTlsError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let acceptor = self.current();
+        match tokio::time::timeout(self.handshake_timeout, acceptor.accept(stream)).await {
+            Ok(Ok(stream)) => Ok(stream),
+            Ok(Err(reason)) => Err(RpcTlsError::HandshakeError { reason }),
+            Err(_) => Err(RpcTlsError::HandshakeTimeout),
+        }
+    }
+
+    fn current(&self) -> TlsAcceptor {
+        match self.acceptor.read() {
+            Ok(acceptor) => acceptor.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    fn load_acceptor(configuration: &RpcTlsConfiguration) -> Result<TlsAcceptor, RpcTlsError> {
+        let cert = std::fs::read(&configuration.cert_path)?;
+        let key = std::fs::read(&configuration.key_path)?;
+        let identity = native_tls::Identity::from_pkcs8(&cert, &key)?;
+        Ok(TlsAcceptor::from(native_tls::TlsAcceptor::new(identity)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_native_tls::TlsConnector;
+
+    use super::*;
+
+    fn tls_resource(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/resources/tls")
+            .join(name)
+    }
+
+    fn certificate_der(name: &str) -> Vec<u8> {
+        let pem = std::fs::read(tls_resource(name)).unwrap();
+        native_tls::Certificate::from_pem(&pem)
+            .unwrap()
+            .to_der()
+            .unwrap()
+    }
+
+    /// Serves TLS handshakes on a local port until the test ends.
+    async fn serve_handshakes(tls: ReloadableTlsAcceptor) -> SocketAddr {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let tls = tls.clone();
+                tokio::spawn(async move { tls.accept(stream).await });
+            }
+        });
+        address
+    }
+
+    /// Returns the certificate presented by the server.
+    async fn handshake(address: SocketAddr) -> Vec<u8> {
+        let connector = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+        let stream = TcpStream::connect(address).await.unwrap();
+        let stream = TlsConnector::from(connector)
+            .connect("localhost", stream)
+            .await
+            .unwrap();
+        stream
+            .get_ref()
+            .peer_certificate()
+            .unwrap()
+            .unwrap()
+            .to_der()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_listener_spec() {
+        let spec: RpcListenerSpec = "127.0.0.1:18732".parse().unwrap();
+        assert_eq!(spec.routes, RpcRouteSet::All);
+        assert!(!spec.tls);
+
+        let tls = RpcTlsConfiguration {
+            cert_path: "cert.pem".into(),
+            key_path: "key.pem".into(),
+        };
+        let spec: RpcListenerSpec = "0.0.0.0:8443, read-only, tls".parse().unwrap();
+        let listener = spec.into_configuration(Some(&tls));
+        assert_eq!(listener.address, "0.0.0.0:8443".parse().unwrap());
+        assert_eq!(listener.routes, RpcRouteSet::ReadOnly);
+        assert_eq!(listener.tls, Some(tls));
+        assert_eq!(listener.to_string(), "https://0.0.0.0:8443 (read-only)");
+
+        assert!("localhost:18732".parse::<RpcListenerSpec>().is_err());
+        assert!("127.0.0.1:18732,public".parse::<RpcListenerSpec>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tls_handshake_and_reload() {
+        let dir = std::env::temp_dir().join(format!("tezedge-rpc-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let configuration = RpcTlsConfiguration {
+            cert_path: dir.join("cert.pem"),
+            key_path: dir.join("key.pem"),
+        };
+        std::fs::copy(tls_resource("cert_a.pem"), &configuration.cert_path).unwrap();
+        std::fs::copy(tls_resource("key_a.pem"), &configuration.key_path).unwrap();
+
+        let tls = ReloadableTlsAcceptor::load(configuration.clone()).unwrap();
+        let address = serve_handshakes(tls.clone()).await;
+        assert_eq!(handshake(address).await, certificate_der("cert_a.pem"));
+
+        // renewed certificate
+        std::fs::copy(tls_resource("cert_b.pem"), &configuration.cert_path).unwrap();
+        std::fs::copy(tls_resource("key_b.pem"), &configuration.key_path).unwrap();
+        tls.reload().unwrap();
+        assert_eq!(handshake(address).await, certificate_der("cert_b.pem"));
+
+        // the current certificate is kept when the new one is broken
+        std::fs::write(&configuration.key_path, b"not a key").unwrap();
+        assert!(tls.reload().is_err());
+        assert_eq!(handshake(address).await, certificate_der("cert_b.pem"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tls_handshake_timeout() {
+        let mut tls = ReloadableTlsAcceptor::load(RpcTlsConfiguration {
+            cert_path: tls_resource("cert_a.pem"),
+            key_path: tls_resource("key_a.pem"),
+        })
+        .unwrap();
+        tls.handshake_timeout = Duration::from_millis(100);
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        // connected, but never starts the handshake
+        let _client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+
+        assert!(matches!(
+            tls.accept(stream).await,
+            Err(RpcTlsError::HandshakeTimeout)
+        ));
+    }
+}
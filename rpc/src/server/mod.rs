@@ -7,6 +7,7 @@ use std::net::SocketAddr;
 use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use std::{
     collections::{HashMap, HashSet},
     path::PathBuf,
@@ -18,8 +19,10 @@ use hyper::body::HttpBody;
 use hyper::server::conn::AddrStream;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Method, Request, Response};
-use slog::{error, Logger};
+use path_tree::PathTree;
+use slog::{error, warn, Logger};
 use tezos_protocol_ipc_client::ProtocolRunnerApi;
+use thiserror::Error;
 use tokio::runtime::Handle;
 
 use crypto::hash::{ChainId, HashTrait};
@@ -31,9 +34,15 @@ use tezos_context_ipc_client::TezedgeContextClient;
 use tezos_messages::p2p::encoding::version::NetworkVersion;
 use url::Url;
 
-use crate::{error_with_message, not_found, options};
+use self::acl::RpcAcl;
+use self::limits::{RpcLimits, RpcLimitsConfiguration};
+use self::listener::{ReloadableTlsAcceptor, RpcListenerConfiguration};
+use crate::{error_with_message, forbidden, not_found, options, too_many_requests};
 
+pub mod acl;
 mod dev_handler;
+pub mod limits;
+pub mod listener;
 mod openapi_handler;
 mod protocol_handler;
 pub mod router;
@@ -98,6 +107,8 @@ pub struct RpcServiceEnvironment {
     context_stats_db_path: Option<PathBuf>,
     pub tezedge_is_enabled: bool,
     pub allow_unsafe_rpc: bool,
+    pub rpc_acl: RpcAcl,
+    pub rpc_limits: RpcLimits,
 }
 
 impl RpcServiceEnvironment {
@@ -113,6 +124,8 @@ impl RpcServiceEnvironment {
         context_stats_db_path: Option<PathBuf>,
         tezedge_is_enabled: bool,
         allow_unsafe_rpc: bool,
+        rpc_acl: RpcAcl,
+        rpc_limits: RpcLimitsConfiguration,
         log: Logger,
     ) -> Self {
         let tezedge_context = TezedgeContextClient::new(Arc::clone(&tezos_protocol_api));
@@ -130,6 +143,8 @@ impl RpcServiceEnvironment {
             context_stats_db_path,
             tezedge_is_enabled,
             allow_unsafe_rpc,
+            rpc_acl,
+            rpc_limits: RpcLimits::new(rpc_limits),
         }
     }
 }
@@ -165,128 +180,247 @@ impl MethodHandler {
     }
 }
 
-/// Spawn new HTTP server on given address interacting with specific actor system
-pub fn spawn_server(
-    bind_address: &SocketAddr,
+#[derive(Debug, Error)]
+pub enum RpcServerError {
+    #[error("HTTP server error: {reason}")]
+    HyperError {
+        #[from]
+        reason: hyper::Error,
+    },
+    #[error("I/O error: {reason}")]
+    IoError {
+        #[from]
+        reason: std::io::Error,
+    },
+}
+
+/// Serves the RPCs on the listen address, over TLS when `tls` is set.
+pub(crate) async fn spawn_server(
+    listener: &RpcListenerConfiguration,
+    tls: Option<ReloadableTlsAcceptor>,
     env: Arc<RpcServiceEnvironment>,
-) -> impl Future<Output = Result<(), hyper::Error>> {
-    let routes = Arc::new(router::create_routes(
+) -> Result<(), RpcServerError> {
+    let routes = Arc::new(router::create_route_set(
+        listener.routes,
         env.tezedge_is_enabled,
         env.allow_unsafe_rpc,
     ));
 
-    hyper::Server::bind(bind_address)
-        .serve(make_service_fn(move |socket: &AddrStream| {
-            let remote_addr = socket.remote_addr();
-            let env = env.clone();
-            let routes = routes.clone();
-
-            async move {
-                Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+    match tls {
+        None => {
+            hyper::Server::try_bind(&listener.address)?
+                .serve(make_service_fn(move |socket: &AddrStream| {
+                    let remote_addr = socket.remote_addr();
+                    let local_addr = socket.local_addr();
                     let env = env.clone();
                     let routes = routes.clone();
-                    async move {
-                        let log = env.log().clone();
-                        let req_method = req.method().clone();
-                        let original_path = req.uri().path();
-                        let normalized_path = normalize_path(req.uri().path()).unwrap_or_else(|| original_path.to_owned());
 
-                        slog::debug!(&log, "Rpc request";
+                    async move {
+                        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                            handle_request(
+                                req,
+                                remote_addr,
+                                local_addr,
+                                routes.clone(),
+                                env.clone(),
+                            )
+                        }))
+                    }
+                }))
+                .await?;
+            Ok(())
+        }
+        Some(tls) => {
+            let tcp_listener = tokio::net::TcpListener::bind(&listener.address).await?;
+            loop {
+                let (stream, remote_addr) = match tcp_listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        // e.g. too many open files, the next accept may succeed
+                        warn!(env.log(), "Failed to accept RPC connection"; "reason" => format!("{}", e));
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let local_addr = stream.local_addr()?;
+                let tls = tls.clone();
+                let routes = routes.clone();
+                let env = env.clone();
+
+                tokio::spawn(async move {
+                    let stream = match tls.accept(stream).await {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            slog::debug!(env.log(), "RPC TLS handshake failed";
+                                "remote_addr" => remote_addr,
+                                "reason" => format!("{}", e));
+                            return;
+                        }
+                    };
+                    let log = env.log().clone();
+                    let service = service_fn(move |req: Request<Body>| {
+                        handle_request(req, remote_addr, local_addr, routes.clone(), env.clone())
+                    });
+                    if let Err(e) = hyper::server::conn::Http::new()
+                        .serve_connection(stream, service)
+                        .await
+                    {
+                        slog::debug!(&log, "RPC connection error";
                             "remote_addr" => remote_addr,
-                            "method" => req_method.to_string(),
-                            "original_path" => &original_path,
-                            "normalized_path" => &normalized_path,
-                            "body" => slog::FnValue(|_| {
-                                format!("{:?}", req.body())
-                            }));
-
-                        let result = if let Some((method_and_handler, params)) = routes.find(normalized_path.trim_end_matches('/')) {
-                            let MethodHandler {
-                                allowed_methods,
-                                handler,
-                            } = method_and_handler;
-
-                            let request_method = req.method();
-
-                            match *request_method {
-                                Method::OPTIONS => {
-                                    // lets globaly handle options
-                                    options()
-                                }
-                                _ => {
-                                    if allowed_methods.contains(request_method) {
-                                        let params: Params = params.into_iter().map(|(param, value)| (param.to_string(), value.to_string())).collect();
-                                        let query: Query = req.uri().query().map(parse_query_string).unwrap_or_else(HashMap::new);
-
-                                        let handler = handler.clone();
-                                        let fut = handler(req, params, query, env);
-                                        match Pin::from(fut).await {
-                                            Ok(response) => Ok(response),
-                                            Err(e) => {
-                                                error!(log, "Failed to execute RPC function - unhandled error"; "reason" => format!("{:?}", &e));
-                                                error_with_message(format!("{:?}", e))
-                                            }
-                                        }
-                                    } else {
-                                        let error_message = format!("Failed to execute RPC function - Method {} not registered for this RPC function", request_method);
-                                        error!(log, "{}", error_message);
-                                        error_with_message(format!("{:?}", error_message))
-                                    }
-                                }
-                            }
-                        } else {
-                            not_found()
-                        };
-
-                        let result = match result {
-                            Ok(v) => {
-                                let remote_addr = remote_addr;
-                                let req_method = req_method.clone();
-                                let normalized_path = normalized_path.clone();
-                                let status = v.status();
-
-                                let (mut parts, data) = v.into_parts();
-
-                                // If the size is known, set Content-Length now, because
-                                // the `map_data` call bellow prevents hyper from being able to
-                                // infer it automatically
-                                if !data.is_end_stream() {
-                                    if let Some(exact) = data.size_hint().exact() {
-                                        if !parts.headers.contains_key(hyper::header::CONTENT_LENGTH) {
-                                            parts.headers.append(hyper::header::CONTENT_LENGTH, exact.into());
-                                        }
+                            "reason" => format!("{}", e));
+                    }
+                });
+            }
+        }
+    }
+}
+
+async fn handle_request(
+    req: Request<Body>,
+    remote_addr: SocketAddr,
+    local_addr: SocketAddr,
+    routes: Arc<PathTree<MethodHandler>>,
+    env: Arc<RpcServiceEnvironment>,
+) -> HResult {
+    let log = env.log().clone();
+    let req_method = req.method().clone();
+    let original_path = req.uri().path();
+    let normalized_path =
+        normalize_path(req.uri().path()).unwrap_or_else(|| original_path.to_owned());
+
+    slog::debug!(&log, "Rpc request";
+    "remote_addr" => remote_addr,
+    "method" => req_method.to_string(),
+    "original_path" => &original_path,
+    "normalized_path" => &normalized_path,
+    "body" => slog::FnValue(|_| {
+        format!("{:?}", req.body())
+    }));
+
+    // the concurrency permit of the request, held by the response body until it is
+    // sent or dropped, so that streamed responses keep their permit
+    let mut permit = None;
+    let result = if !env
+        .rpc_acl
+        .is_allowed(&local_addr, &req_method, &normalized_path)
+    {
+        slog::debug!(&log, "Rpc request denied by ACL";
+            "remote_addr" => remote_addr,
+            "local_addr" => local_addr,
+            "method" => req_method.to_string(),
+            "normalized_path" => &normalized_path);
+        forbidden(&req_method, &normalized_path)
+    } else {
+        match env
+            .rpc_limits
+            .check(remote_addr.ip(), &req_method, &normalized_path)
+        {
+            Err(rejection) => {
+                slog::debug!(&log, "Rpc request rejected by limits";
+                    "remote_addr" => remote_addr,
+                    "method" => req_method.to_string(),
+                    "normalized_path" => &normalized_path,
+                    "reason" => format!("{:?}", rejection));
+                too_many_requests(&rejection)
+            }
+            Ok(request_permit) => {
+                permit = request_permit;
+                if let Some((method_and_handler, params)) =
+                    routes.find(normalized_path.trim_end_matches('/'))
+                {
+                    let MethodHandler {
+                        allowed_methods,
+                        handler,
+                    } = method_and_handler;
+
+                    let request_method = req.method();
+
+                    match *request_method {
+                        Method::OPTIONS => {
+                            // lets globaly handle options
+                            options()
+                        }
+                        _ => {
+                            if allowed_methods.contains(request_method) {
+                                let params: Params = params
+                                    .into_iter()
+                                    .map(|(param, value)| (param.to_string(), value.to_string()))
+                                    .collect();
+                                let query: Query = req
+                                    .uri()
+                                    .query()
+                                    .map(parse_query_string)
+                                    .unwrap_or_else(HashMap::new);
+
+                                let handler = handler.clone();
+                                let fut = handler(req, params, query, env);
+                                match Pin::from(fut).await {
+                                    Ok(response) => Ok(response),
+                                    Err(e) => {
+                                        error!(log, "Failed to execute RPC function - unhandled error"; "reason" => format!("{:?}", &e));
+                                        error_with_message(format!("{:?}", e))
                                     }
                                 }
-
-                                let data = data.map_data(move |data| {
-                                    slog::trace!(&log, "Rpc response";
-                                        "remote_addr" => remote_addr,
-                                        "method" => req_method.to_string(),
-                                        "normalized_path" => &normalized_path,
-                                        "status" => status.to_string(),
-                                        "body" => slog::FnValue(|_| {
-                                            format!("{:?}", data)
-                                        }));
-                                    data
-                                });
-                                Ok(Response::from_parts(parts, data))
+                            } else {
+                                let error_message = format!("Failed to execute RPC function - Method {} not registered for this RPC function", request_method);
+                                error!(log, "{}", error_message);
+                                error_with_message(format!("{:?}", error_message))
                             }
-                            Err(err) => {
-                                slog::trace!(&log, "Rpc response error";
-                                    "remote_addr" => remote_addr,
-                                    "method" => req_method.to_string(),
-                                    "normalized_path" => &normalized_path,
-                                    "status" => "500".to_owned(),
-                                    "body" => slog::FnValue(|_| format!("Err: {}", err)));
-                                Err(err)
-                            }
-                        };
-
-                        result
+                        }
                     }
-                }))
+                } else {
+                    not_found()
+                }
+            }
+        }
+    };
+
+    match result {
+        Ok(v) => {
+            let remote_addr = remote_addr;
+            let req_method = req_method.clone();
+            let normalized_path = normalized_path.clone();
+            let status = v.status();
+
+            let (mut parts, data) = v.into_parts();
+
+            // If the size is known, set Content-Length now, because
+            // the `map_data` call bellow prevents hyper from being able to
+            // infer it automatically
+            if !data.is_end_stream() {
+                if let Some(exact) = data.size_hint().exact() {
+                    if !parts.headers.contains_key(hyper::header::CONTENT_LENGTH) {
+                        parts
+                            .headers
+                            .append(hyper::header::CONTENT_LENGTH, exact.into());
+                    }
+                }
             }
-        }))
+
+            let data = data.map_data(move |data| {
+                let _permit = &permit;
+                slog::trace!(&log, "Rpc response";
+                "remote_addr" => remote_addr,
+                "method" => req_method.to_string(),
+                "normalized_path" => &normalized_path,
+                "status" => status.to_string(),
+                "body" => slog::FnValue(|_| {
+                    format!("{:?}", data)
+                }));
+                data
+            });
+            Ok(Response::from_parts(parts, data))
+        }
+        Err(err) => {
+            slog::trace!(&log, "Rpc response error";
+                "remote_addr" => remote_addr,
+                "method" => req_method.to_string(),
+                "normalized_path" => &normalized_path,
+                "status" => "500".to_owned(),
+                "body" => slog::FnValue(|_| format!("Err: {}", err)));
+            Err(err)
+        }
+    }
 }
 
 /// Normalizes the request path
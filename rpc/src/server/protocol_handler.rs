@@ -4,10 +4,11 @@
 use crate::{make_response_with_status_and_json_string, not_found};
 use hyper::{Body, Request};
 use slog::warn;
+use tezos_api::ffi::RpcRequest;
 
 use crate::helpers::{create_rpc_request, parse_block_hash, parse_chain_id, RpcServiceError};
 use crate::server::{HasSingleValue, Params, Query, RpcServiceEnvironment};
-use crate::services::protocol::{ContextParamsError, RightsError, VotesError};
+use crate::services::protocol::{RightsError, VotesError};
 use crate::{
     handle_rpc_service_error, parse_block_hash_or_fail, required_param, result_to_json_response,
     services, ServiceResult,
@@ -26,24 +27,21 @@ pub async fn context_constants(
         parse_block_hash_or_fail!(&chain_id, required_param!(params, "block_id")?, &env);
 
     // try to call our implementation
-    let result =
-        services::protocol::get_context_constants_just_for_rpc(&chain_id, &block_hash, &env);
-
-    // fallback, if protocol is not supported, we trigger rpc protocol router
-    if let Err(ContextParamsError::UnsupportedProtocolError { .. }) = result {
-        result_to_json_response(
-            services::protocol::call_protocol_rpc(
+    match services::protocol::get_context_constants_just_for_rpc(&chain_id, &block_hash, &env) {
+        Ok(Some(constants)) => result_to_json_response(Ok(constants), env.log()),
+        Ok(None) => {
+            // fallback, if the constants were not stored, we trigger rpc protocol router
+            let result = services::protocol::call_protocol_rpc(
                 chain_id_param,
                 chain_id,
                 block_hash,
                 create_rpc_request(req).await?,
                 &env,
             )
-            .await,
-            env.log(),
-        )
-    } else {
-        result_to_json_response(result.map_err(|e| e.into()), env.log())
+            .await?;
+            make_response_with_status_and_json_string(result.0, &result.1)
+        }
+        Err(e) => result_to_json_response::<()>(Err(e.into()), env.log()),
     }
 }
 
@@ -181,17 +179,15 @@ pub async fn votes_listings(
         }
         Err(VotesError::UnsupportedProtocolError { .. }) => {
             // fallback, if protocol is not supported in Tezedge impl, we trigger rpc protocol router
-            result_to_json_response(
-                services::protocol::call_protocol_rpc(
-                    chain_id_param,
-                    chain_id,
-                    block_hash,
-                    create_rpc_request(req).await?,
-                    &env,
-                )
-                .await,
-                env.log(),
+            let result = services::protocol::call_protocol_rpc(
+                chain_id_param,
+                chain_id,
+                block_hash,
+                create_rpc_request(req).await?,
+                &env,
             )
+            .await?;
+            make_response_with_status_and_json_string(result.0, &result.1)
         }
         Err(VotesError::ServiceError { reason }) => {
             slog::warn!(env.log(), "Failed to execute RPC function  for votings"; "reason" => format!("{:?}", &reason));
@@ -210,13 +206,37 @@ pub async fn call_protocol_rpc(
     params: Params,
     _: Query,
     env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    let json_request = create_rpc_request(req).await?;
+    protocol_rpc_response(json_request, params, env).await
+}
+
+/// Routes the rpc through the protocol runner, even if it is served from the TezEdge context,
+/// e.g. `/dev/ocaml/chains/main/blocks/head/context/constants` is answered by OCaml
+/// as `/chains/main/blocks/head/context/constants`, so both responses can be compared.
+pub async fn call_ocaml_rpc(
+    req: Request<Body>,
+    params: Params,
+    _: Query,
+    env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    let mut json_request = create_rpc_request(req).await?;
+    if let Some(context_path) = json_request.context_path.strip_prefix("/dev/ocaml") {
+        json_request.context_path = context_path.to_string();
+    }
+    protocol_rpc_response(json_request, params, env).await
+}
+
+async fn protocol_rpc_response(
+    json_request: RpcRequest,
+    params: Params,
+    env: Arc<RpcServiceEnvironment>,
 ) -> ServiceResult {
     let chain_id_param = required_param!(params, "chain_id")?;
     let chain_id = parse_chain_id(chain_id_param, &env)?;
     let block_hash =
         parse_block_hash_or_fail!(&chain_id, required_param!(params, "block_id")?, &env);
 
-    let json_request = create_rpc_request(req).await?;
     let context_path = json_request.context_path.clone();
     let result = services::protocol::call_protocol_rpc(
         chain_id_param,
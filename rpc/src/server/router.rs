@@ -8,6 +8,7 @@ use std::sync::Arc;
 use hyper::{Body, Method, Request};
 use path_tree::PathTree;
 
+use crate::server::listener::RpcRouteSet;
 use crate::server::{dev_handler, openapi_handler, protocol_handler, shell_handler};
 use crate::server::{HResult, MethodHandler, Params, Query, RpcServiceEnvironment};
 
@@ -18,7 +19,19 @@ macro_rules! hash_set {
 }
 
 pub fn create_routes(tezedge_is_enabled: bool, allow_unsafe: bool) -> PathTree<MethodHandler> {
-    let mut routes = PathTree::<MethodHandler>::new();
+    create_route_set(RpcRouteSet::All, tezedge_is_enabled, allow_unsafe)
+}
+
+/// Creates the routes of `route_set`, for [`RpcRouteSet::ReadOnly`] only the `GET` methods are kept.
+pub fn create_route_set(
+    route_set: RpcRouteSet,
+    tezedge_is_enabled: bool,
+    allow_unsafe: bool,
+) -> PathTree<MethodHandler> {
+    let mut routes = RouteSet {
+        tree: PathTree::<MethodHandler>::new(),
+        read_only: route_set == RpcRouteSet::ReadOnly,
+    };
 
     // Shell rpc - implemented
     routes.handle(
@@ -162,14 +175,9 @@ pub fn create_routes(tezedge_is_enabled: bool, allow_unsafe: bool) -> PathTree<M
         shell_handler::get_block_operation,
     );
 
-    // TODO - TE-261: we are routing these to OCaml for now, even when the TezEdge
-    // context is available. Once these handlers have been tested better and revised, enable again.
-    // TODO - TE-261 - remove this function, it is just to make clippy happy
-    fn enable_tezedge_rpcs_with_context(tezedge_is_enabled: bool, really_allow: bool) -> bool {
-        tezedge_is_enabled && really_allow
-    }
-
-    if enable_tezedge_rpcs_with_context(tezedge_is_enabled, false) {
+    // Served from the TezEdge context, without it they go through the protocol runner,
+    // `/dev/ocaml/...` below always does, so both can be compared.
+    if tezedge_is_enabled {
         routes.handle(
             hash_set![Method::GET],
             "/chains/:chain_id/blocks/:block_id/context/raw/bytes",
@@ -236,9 +244,7 @@ pub fn create_routes(tezedge_is_enabled: bool, allow_unsafe: bool) -> PathTree<M
         protocol_handler::endorsing_rights,
     );
 
-    // TODO - TE-261: we are routing these to OCaml for now, even when the TezEdge
-    // context is available. Once these handlers have been tested better and revised, enable again.
-    if enable_tezedge_rpcs_with_context(tezedge_is_enabled, false) {
+    if tezedge_is_enabled {
         // These only work if the TezEdge context is available
         routes.handle(
             hash_set![Method::GET],
@@ -258,6 +264,11 @@ pub fn create_routes(tezedge_is_enabled: bool, allow_unsafe: bool) -> PathTree<M
         "/chains/:chain_id/blocks/:block_id/*any",
         protocol_handler::call_protocol_rpc,
     );
+    routes.handle(
+        hash_set![Method::GET],
+        "/dev/ocaml/chains/:chain_id/blocks/:block_id/*any",
+        protocol_handler::call_ocaml_rpc,
+    );
 
     // Tezedge dev and support rpcs
     routes.handle(
@@ -290,6 +301,18 @@ pub fn create_routes(tezedge_is_enabled: bool, allow_unsafe: bool) -> PathTree<M
         "/dev/chains/:chain_id/blocks/:block_id/cycle_eras",
         dev_handler::cycle_eras,
     );
+    if tezedge_is_enabled {
+        routes.handle(
+            hash_set![Method::GET],
+            "/dev/chains/:chain_id/blocks/:block_id/context/merkle_proof",
+            dev_handler::context_merkle_proof,
+        );
+        routes.handle(
+            hash_set![Method::GET],
+            "/dev/chains/:chain_id/blocks/:block_id/context/merkle_proof/*any",
+            dev_handler::context_merkle_proof,
+        );
+    }
     routes.handle(
         hash_set![Method::GET],
         "/dev/shell/automaton/state",
@@ -430,11 +453,17 @@ pub fn create_routes(tezedge_is_enabled: bool, allow_unsafe: bool) -> PathTree<M
         "/stats/dbrw",
         dev_handler::dev_db_stats,
     );
+    routes.handle(
+        hash_set![Method::GET],
+        "/stats/rpc",
+        dev_handler::rpc_limits_stats,
+    );
     routes.handle(
         hash_set![Method::GET],
         "/stats/:chain_id/blocks/:block_id",
         dev_handler::block_actions,
     );
+    routes.handle(hash_set![Method::GET], "/metrics", dev_handler::metrics);
 
     routes.handle(
         hash_set![Method::GET],
@@ -457,6 +486,31 @@ pub fn create_routes(tezedge_is_enabled: bool, allow_unsafe: bool) -> PathTree<M
         shell_handler::network_connections,
     );
 
+    // Octez peer and point management, mutating `GET` routes are not
+    // served on read-only listeners.
+    if allow_unsafe && route_set == RpcRouteSet::All {
+        routes.handle(
+            hash_set![Method::GET],
+            "/network/peers/:peer_id/:command",
+            shell_handler::network_peer_acl,
+        );
+        routes.handle(
+            hash_set![Method::PATCH],
+            "/network/peers/:peer_id",
+            shell_handler::network_peer_acl,
+        );
+        routes.handle(
+            hash_set![Method::GET],
+            "/network/points/:point/:command",
+            shell_handler::network_point_acl,
+        );
+        routes.handle(
+            hash_set![Method::PATCH],
+            "/network/points/:point",
+            shell_handler::network_point_acl,
+        );
+    }
+
     // DEPRECATED in ocaml but still used by python tests
     routes.handle(
         hash_set![Method::GET],
@@ -470,28 +524,39 @@ pub fn create_routes(tezedge_is_enabled: bool, allow_unsafe: bool) -> PathTree<M
         openapi_handler::get_spec_file,
     );
 
-    routes
+    routes.tree
+}
+
+struct RouteSet {
+    tree: PathTree<MethodHandler>,
+    read_only: bool,
 }
 
 trait Routes<Fut> {
     fn handle(&mut self, method: HashSet<Method>, path: &str, f: Fut);
 }
 
-impl<T, F> Routes<T> for PathTree<MethodHandler>
+impl<T, F> Routes<T> for RouteSet
 where
     T: Fn(Request<Body>, Params, Query, Arc<RpcServiceEnvironment>) -> F + Send + Sync + 'static,
     F: Future<Output = HResult> + Send + 'static,
 {
-    fn handle(&mut self, allowed_methods: HashSet<Method>, path: &str, f: T) {
+    fn handle(&mut self, mut allowed_methods: HashSet<Method>, path: &str, f: T) {
+        if self.read_only {
+            allowed_methods.retain(|method| *method == Method::GET);
+            if allowed_methods.is_empty() {
+                return;
+            }
+        }
         let allowed_methods = Arc::new(allowed_methods);
-        self.insert(
+        self.tree.insert(
             path,
             MethodHandler::new(
                 allowed_methods.clone(),
                 Arc::new(move |req, params, query, env| Box::new(f(req, params, query, env))),
             ),
         );
-        self.insert(
+        self.tree.insert(
             &format!("/describe{}", path),
             MethodHandler::new(
                 Arc::new(hash_set![Method::GET]),
@@ -508,3 +573,268 @@ where
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::path::PathBuf;
+    use std::sync::{Arc, RwLock};
+    use std::time::Duration;
+
+    use crypto::hash::CryptoboxPublicKeyHash;
+    use hyper::{Body, Method, Request, StatusCode};
+    use shell_automaton::service::rpc_service::{
+        AclCommand, PeerAclInfo, PeerConnectionState, PointAclInfo, PointConnectionState,
+        RpcRequest, RpcServiceDefault,
+    };
+    use shell_automaton::service::{MioServiceDefault, RpcService};
+    use slog::Level;
+    use storage::tests_common::{create_logger, TmpStorage};
+    use storage::BlockHeaderWithHash;
+    use tezos_api::environment::{default_networks, TezosEnvironment};
+    use tezos_api::ffi::TezosRuntimeConfiguration;
+    use tezos_context_api::{
+        ContextKvStoreConfiguration, TezosContextStorageConfiguration,
+        TezosContextTezEdgeStorageConfiguration,
+    };
+    use tezos_messages::p2p::encoding::version::NetworkVersion;
+    use tezos_protocol_ipc_client::{ProtocolRunnerApi, ProtocolRunnerConfiguration};
+    use tokio::runtime::Handle;
+
+    use crate::server::acl::RpcAcl;
+    use crate::server::limits::RpcLimitsConfiguration;
+    use crate::server::{handle_request, RpcCollectedState, RpcServiceEnvironment};
+
+    use super::create_routes;
+
+    fn environment(
+        storage: &TmpStorage,
+        shell_automaton_sender: shell_automaton::service::rpc_service::RpcShellAutomatonSender,
+    ) -> Arc<RpcServiceEnvironment> {
+        let log = create_logger(Level::Info);
+        let tezos_env = default_networks()
+            .remove(&TezosEnvironment::Sandbox)
+            .unwrap();
+        let genesis = tezos_env
+            .genesis_header(
+                "CoVmAcMV64uAQo8XvfLr9VDuz7HVZLT4cgK1w1qYmTjQNbGwQwDd"
+                    .try_into()
+                    .unwrap(),
+                "LLoaGLRPRx3Zf8kB4ACtgku8F4feeBiskeb41J1ciwfcXB3KzHKXc"
+                    .try_into()
+                    .unwrap(),
+            )
+            .unwrap();
+        // never started, the tested routes don't use the protocol runner
+        let tezos_protocol_api = ProtocolRunnerApi::new(
+            ProtocolRunnerConfiguration::new(
+                TezosRuntimeConfiguration {
+                    log_enabled: false,
+                    log_level: None,
+                },
+                tezos_env.clone(),
+                false,
+                TezosContextStorageConfiguration::TezEdgeOnly(
+                    TezosContextTezEdgeStorageConfiguration {
+                        backend: ContextKvStoreConfiguration::ReadOnlyIpc,
+                        ipc_socket_path: None,
+                    },
+                ),
+                PathBuf::from("protocol-runner"),
+                Level::Info,
+                None,
+            ),
+            tokio::sync::watch::channel(false).1,
+            &Handle::current(),
+            log.clone(),
+        );
+        let state = Arc::new(RwLock::new(RpcCollectedState {
+            current_head: Arc::new(BlockHeaderWithHash::new(genesis).unwrap()),
+            best_remote_level: None,
+            streams: HashMap::new(),
+        }));
+
+        Arc::new(RpcServiceEnvironment::new(
+            Arc::new(Handle::current()),
+            shell_automaton_sender,
+            tezos_env.clone(),
+            Arc::new(NetworkVersion::new(tezos_env.version.clone(), 0, 1)),
+            storage.storage(),
+            Arc::new(tezos_protocol_api),
+            tezos_env.main_chain_id().unwrap(),
+            state,
+            None,
+            false,
+            true,
+            RpcAcl::default(),
+            RpcLimitsConfiguration::default(),
+            log,
+        ))
+    }
+
+    /// Routes the request like the server does, the shell automaton side is
+    /// played by answering the received [`RpcRequest`] with `info`.
+    async fn request<T>(
+        env: &Arc<RpcServiceEnvironment>,
+        rpc_service: &mut RpcServiceDefault,
+        method: Method,
+        path: &str,
+        body: &'static str,
+        info: T,
+    ) -> (RpcRequest, StatusCode, serde_json::Value)
+    where
+        T: serde::Serialize + Send + 'static,
+    {
+        let address: SocketAddr = "127.0.0.1:8732".parse().unwrap();
+        let req = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::from(body))
+            .unwrap();
+        let response = tokio::spawn(handle_request(
+            req,
+            address,
+            address,
+            Arc::new(create_routes(false, true)),
+            env.clone(),
+        ));
+
+        let rpc_request = loop {
+            match rpc_service.try_recv() {
+                Ok((rpc_request, rpc_id)) => {
+                    rpc_service.respond(rpc_id, info);
+                    break rpc_request;
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(1)).await,
+            }
+        };
+
+        let response = response.await.unwrap().unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (rpc_request, status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_network_acl_routes() {
+        let storage = TmpStorage::create_to_out_dir("__rpc_network_acl_routes").unwrap();
+        let mio_service = MioServiceDefault::new("127.0.0.1:0".parse().unwrap(), 1024);
+        let (mut rpc_service, sender) = RpcServiceDefault::new(mio_service.waker(), 16);
+        let env = environment(&storage, sender);
+
+        let peer_id = CryptoboxPublicKeyHash::try_from(vec![1; 16]).unwrap();
+        let peer_info = PeerAclInfo {
+            trusted: false,
+            banned: false,
+            state: PeerConnectionState::Disconnected,
+        };
+        let point_info = PointAclInfo {
+            trusted: true,
+            banned: false,
+            state: PointConnectionState {
+                event_kind: PeerConnectionState::Running,
+                p2p_peer_id: Some(peer_id.to_base58_check()),
+            },
+        };
+
+        // the `GET` commands respond with an empty object, even when they
+        // don't change anything
+        for (name, command) in [
+            ("ban", AclCommand::Ban),
+            ("unban", AclCommand::Unban),
+            ("trust", AclCommand::Trust),
+            ("untrust", AclCommand::Untrust),
+        ] {
+            let (rpc_request, status, body) = request(
+                &env,
+                &mut rpc_service,
+                Method::GET,
+                &format!("/network/peers/{}/{}", peer_id.to_base58_check(), name),
+                "",
+                peer_info.clone(),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK, "{}", name);
+            assert_eq!(body, serde_json::json!({}), "{}", name);
+            match rpc_request {
+                RpcRequest::PatchPeerAcl {
+                    peer_id: requested,
+                    command: requested_command,
+                } => {
+                    assert_eq!(requested, peer_id);
+                    assert_eq!(requested_command, command);
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+
+            let (rpc_request, status, body) = request(
+                &env,
+                &mut rpc_service,
+                Method::GET,
+                &format!("/network/points/10.0.0.1:9732/{}", name),
+                "",
+                point_info.clone(),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK, "{}", name);
+            assert_eq!(body, serde_json::json!({}), "{}", name);
+            match rpc_request {
+                RpcRequest::PatchPointAcl {
+                    address,
+                    command: requested_command,
+                } => {
+                    assert_eq!(address, "10.0.0.1:9732".parse().unwrap());
+                    assert_eq!(requested_command, command);
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+
+        // `PATCH` responds with the peer or point info
+        let (rpc_request, status, body) = request(
+            &env,
+            &mut rpc_service,
+            Method::PATCH,
+            &format!("/network/peers/{}", peer_id.to_base58_check()),
+            r#"{"acl": "open"}"#,
+            peer_info.clone(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_value::<PeerAclInfo>(body).unwrap(),
+            peer_info
+        );
+        assert!(matches!(
+            rpc_request,
+            RpcRequest::PatchPeerAcl {
+                command: AclCommand::Open,
+                ..
+            }
+        ));
+
+        let (rpc_request, status, body) = request(
+            &env,
+            &mut rpc_service,
+            Method::PATCH,
+            "/network/points/10.0.0.1:9732",
+            r#"{"acl": "trust"}"#,
+            point_info.clone(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["state"]["event_kind"], "running");
+        assert_eq!(
+            serde_json::from_value::<PointAclInfo>(body).unwrap(),
+            point_info
+        );
+        assert!(matches!(
+            rpc_request,
+            RpcRequest::PatchPointAcl {
+                command: AclCommand::Trust,
+                ..
+            }
+        ));
+    }
+}
@@ -6,7 +6,6 @@ use shell_automaton::service::rpc_service::RpcShellAutomatonSender;
 use shell_integration::*;
 use slog::{error, info, warn, Logger};
 use std::collections::HashMap;
-use std::net::SocketAddr;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use storage::PersistentStorage;
@@ -16,24 +15,28 @@ use tezos_messages::p2p::encoding::version::NetworkVersion;
 use tezos_protocol_ipc_client::ProtocolRunnerApi;
 use tokio::runtime::Handle;
 
+use crate::server::acl::RpcAcl;
+use crate::server::limits::RpcLimitsConfiguration;
+use crate::server::listener::{ReloadableTlsAcceptor, RpcListenerConfiguration, RpcTlsError};
 use crate::server::{spawn_server, RpcCollectedState, RpcServiceEnvironment};
 use crate::RpcServiceEnvironmentRef;
 
 enum RpcServerThreadHandle {
-    Running(tokio::task::JoinHandle<()>),
+    Running(Vec<tokio::task::JoinHandle<()>>),
     NotRunning,
 }
 
 #[derive(Debug)]
 pub enum RpcServerSpawnError {
     IoError(std::io::Error),
+    TlsError(RpcTlsError),
 }
 
 /// RpcServer is responsible for managing HTTP REST API and server, and to share parts of inner actor
 /// system with the server.
 pub struct RpcServer {
     env: Arc<RpcServiceEnvironment>,
-    rpc_listen_address: SocketAddr,
+    listeners: Vec<RpcListenerConfiguration>,
     log: Logger,
     rpc_server_thread_handle: Option<RpcServerThreadHandle>,
 }
@@ -42,7 +45,7 @@ impl RpcServer {
     pub fn new(
         log: Logger,
         shell_automaton_channel: RpcShellAutomatonSender,
-        rpc_listen_address: SocketAddr,
+        listeners: Vec<RpcListenerConfiguration>,
         tokio_executor: Handle,
         persistent_storage: &PersistentStorage,
         tezos_protocol_api: Arc<ProtocolRunnerApi>,
@@ -52,6 +55,8 @@ impl RpcServer {
         hydrated_current_head_block: Arc<BlockHeaderWithHash>,
         tezedge_is_enabled: bool,
         allow_unsafe_rpc: bool,
+        rpc_acl: RpcAcl,
+        rpc_limits: RpcLimitsConfiguration,
     ) -> Self {
         let shared_state = Arc::new(RwLock::new(RpcCollectedState {
             current_head: hydrated_current_head_block,
@@ -71,12 +76,14 @@ impl RpcServer {
             init_storage_data.context_stats_db_path.clone(),
             tezedge_is_enabled,
             allow_unsafe_rpc,
+            rpc_acl,
+            rpc_limits,
             log.clone(),
         ));
 
         Self {
             env,
-            rpc_listen_address,
+            listeners,
             log,
             rpc_server_thread_handle: Some(RpcServerThreadHandle::NotRunning),
         }
@@ -84,27 +91,77 @@ impl RpcServer {
 
     pub fn start(&mut self) -> Result<(), RpcServerSpawnError> {
         if let Some(RpcServerThreadHandle::NotRunning) = self.rpc_server_thread_handle.take() {
-            // spawn RPC JSON server
-            let rpc_server_thread_handle = {
-                let rpc_listen_address = self.rpc_listen_address;
-                let log = self.log.clone();
-                let env_for_server = self.env.clone();
-
-                self.env.tokio_executor().spawn(async move {
-                    info!(log, "Starting RPC server"; "address" => format!("{}", &rpc_listen_address));
-                    if let Err(e) = spawn_server(&rpc_listen_address, env_for_server).await {
-                        error!(log, "HTTP Server encountered failure"; "error" => format!("{}", e));
-                    }
+            let listeners = self
+                .listeners
+                .iter()
+                .map(|listener| {
+                    let tls = listener
+                        .tls
+                        .clone()
+                        .map(ReloadableTlsAcceptor::load)
+                        .transpose()
+                        .map_err(RpcServerSpawnError::TlsError)?;
+                    Ok((listener.clone(), tls))
                 })
-            };
+                .collect::<Result<Vec<_>, RpcServerSpawnError>>()?;
+
+            let tls_acceptors: Vec<ReloadableTlsAcceptor> = listeners
+                .iter()
+                .filter_map(|(_, tls)| tls.clone())
+                .collect();
+            if !tls_acceptors.is_empty() {
+                self.spawn_tls_reload_on_sighup(tls_acceptors);
+            }
+
+            // spawn RPC JSON server for every listen address
+            let rpc_server_thread_handles = listeners
+                .into_iter()
+                .map(|(listener, tls)| {
+                    let log = self.log.clone();
+                    let env_for_server = self.env.clone();
+
+                    self.env.tokio_executor().spawn(async move {
+                        info!(log, "Starting RPC server"; "address" => format!("{}", &listener));
+                        if let Err(e) = spawn_server(&listener, tls, env_for_server).await {
+                            error!(log, "HTTP Server encountered failure"; "address" => format!("{}", &listener), "error" => format!("{}", e));
+                        }
+                    })
+                })
+                .collect();
 
             self.rpc_server_thread_handle =
-                Some(RpcServerThreadHandle::Running(rpc_server_thread_handle));
+                Some(RpcServerThreadHandle::Running(rpc_server_thread_handles));
         }
 
         Ok(())
     }
 
+    /// Reloads TLS certificates and keys on SIGHUP, so they can be renewed without restarting the node.
+    fn spawn_tls_reload_on_sighup(&self, tls_acceptors: Vec<ReloadableTlsAcceptor>) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let log = self.log.clone();
+        self.env.tokio_executor().spawn(async move {
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(hangup) => hangup,
+                Err(e) => {
+                    warn!(log, "Failed to set SIGHUP handler, RPC TLS certificates will not be reloaded"; "reason" => format!("{}", e));
+                    return;
+                }
+            };
+            while hangup.recv().await.is_some() {
+                for tls in &tls_acceptors {
+                    match tls.reload() {
+                        Ok(()) => info!(log, "RPC TLS certificate reloaded"),
+                        Err(e) => {
+                            error!(log, "Failed to reload RPC TLS certificate, keeping the current one"; "reason" => format!("{}", e))
+                        }
+                    }
+                }
+            }
+        });
+    }
+
     pub fn rpc_env(&self) -> RpcServiceEnvironmentRef {
         self.env.clone()
     }
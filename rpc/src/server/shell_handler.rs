@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: MIT
 
 use std::collections::HashSet;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use hyper::body::Buf;
@@ -9,19 +10,19 @@ use hyper::{Body, Method, Request};
 
 use tokio_stream::{wrappers::UnboundedReceiverStream, StreamExt};
 
-use crypto::hash::{BlockHash, ChainId, ProtocolHash};
-use shell_automaton::service::rpc_service::RpcRequestStream;
+use crypto::hash::{BlockHash, ChainId, CryptoboxPublicKeyHash, ProtocolHash};
+use shell_automaton::service::rpc_service::{AclCommand, RpcRequestStream};
 
 use crate::helpers::{
     create_rpc_request, parse_async, parse_block_hash, parse_chain_id, RpcServiceError,
     MAIN_CHAIN_ID,
 };
 use crate::server::{HResult, HasSingleValue, Params, Query, RpcServiceEnvironment};
-use crate::services::{base_services, stream_services};
+use crate::services::{base_services, dev_services, stream_services};
 use crate::{
-    empty, encoding::base_types::*, error, helpers, make_json_response, make_json_stream_response,
-    not_found, parse_block_hash_or_fail, required_param, result_to_empty_json_response,
-    result_to_json_response, services, ServiceResult,
+    empty, encoding::base_types::*, error, handle_rpc_service_error, helpers, make_json_response,
+    make_json_stream_response, not_found, parse_block_hash_or_fail, required_param,
+    result_to_empty_json_response, result_to_json_response, services, ServiceResult,
 };
 
 pub async fn bootstrapped(
@@ -740,6 +741,98 @@ pub async fn network_connections(
     result_to_json_response(Ok(connections), env.log())
 }
 
+#[derive(serde::Deserialize)]
+struct AclPatch {
+    acl: AclCommand,
+}
+
+/// Command from the `:command` path segment, or from the `PATCH` body.
+async fn acl_command(req: Request<Body>, params: &Params) -> Result<AclCommand, RpcServiceError> {
+    let res = match params.get_str("command") {
+        Some(command) => serde_json::from_value(serde_json::Value::String(command.to_string())),
+        None => {
+            let body = hyper::body::to_bytes(req.into_body())
+                .await
+                .map_err(|err| RpcServiceError::InvalidParameters {
+                    reason: err.to_string(),
+                })?;
+            serde_json::from_slice::<AclPatch>(&body).map(|patch| patch.acl)
+        }
+    };
+    res.map_err(|err| RpcServiceError::InvalidParameters {
+        reason: format!("Invalid acl command: {}", err),
+    })
+}
+
+/// Octez responds to the `GET` commands with an empty object, and to `PATCH`
+/// with the info of the peer or point.
+fn acl_response<T: serde::Serialize>(method: &Method, info: &T) -> ServiceResult {
+    match *method {
+        Method::PATCH => make_json_response(info),
+        _ => make_json_response(&serde_json::json!({})),
+    }
+}
+
+/// Ban, unban, trust or untrust the peer identified by `:peer_id`.
+pub async fn network_peer_acl(
+    req: Request<Body>,
+    params: Params,
+    _: Query,
+    env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    let peer_id = required_param!(params, "peer_id")?;
+    let peer_id = match CryptoboxPublicKeyHash::from_base58_check(peer_id) {
+        Ok(peer_id) => peer_id,
+        Err(err) => {
+            return handle_rpc_service_error(RpcServiceError::InvalidParameters {
+                reason: format!("Invalid peer id: {}", err),
+            })
+        }
+    };
+    let method = req.method().clone();
+    let command = match acl_command(req, &params).await {
+        Ok(AclCommand::Forget) => {
+            return handle_rpc_service_error(RpcServiceError::InvalidParameters {
+                reason: "Only points can be forgotten".to_string(),
+            })
+        }
+        Ok(command) => command,
+        Err(err) => return handle_rpc_service_error(err),
+    };
+
+    acl_response(
+        &method,
+        &dev_services::patch_peer_acl(peer_id, command, &env).await?,
+    )
+}
+
+/// Ban, unban, trust, untrust or forget the point `:point` (`<ip>:<port>`).
+pub async fn network_point_acl(
+    req: Request<Body>,
+    params: Params,
+    _: Query,
+    env: Arc<RpcServiceEnvironment>,
+) -> ServiceResult {
+    let address = match required_param!(params, "point")?.parse::<SocketAddr>() {
+        Ok(address) => address,
+        Err(err) => {
+            return handle_rpc_service_error(RpcServiceError::InvalidParameters {
+                reason: format!("Invalid point: {}", err),
+            })
+        }
+    };
+    let method = req.method().clone();
+    let command = match acl_command(req, &params).await {
+        Ok(command) => command,
+        Err(err) => return handle_rpc_service_error(err),
+    };
+
+    acl_response(
+        &method,
+        &dev_services::patch_point_acl(address, command, &env).await?,
+    )
+}
+
 pub async fn node_version(
     _: Request<Body>,
     _: Params,
@@ -10,6 +10,7 @@ use storage::{
     BlockJsonData, BlockMetaStorage, BlockMetaStorageReader, BlockStorage, BlockStorageReader,
     OperationsStorage, OperationsStorageReader,
 };
+use tezos_context::proof::ContextProof;
 use tezos_context_api::{context_key_owned, StringTreeObject};
 use tezos_messages::p2p::encoding::version::NetworkVersion;
 
@@ -294,14 +295,19 @@ pub(crate) async fn get_context_raw_bytes(
     };
 
     let ctx_hash = get_context_hash(chain_id, block_hash, env)?;
-    Ok(Arc::new(
-        env.tezedge_context()
-            .get_context_tree_by_prefix(&ctx_hash, key_prefix, depth)
-            .await
-            .map_err(|e| RpcServiceError::UnexpectedError {
-                reason: format!("{}", e),
-            })?,
-    ))
+    match env
+        .tezedge_context()
+        .get_context_tree_by_prefix(&ctx_hash, key_prefix.clone(), depth)
+        .await
+        .map_err(|e| RpcServiceError::UnexpectedError {
+            reason: format!("{}", e),
+        })? {
+        // the same as OCaml, which answers 404 for a missing key
+        StringTreeObject::Null => Err(RpcServiceError::NoDataFoundError {
+            reason: format!("No data found in context for key: {}", key_prefix.join("/")),
+        }),
+        tree => Ok(Arc::new(tree)),
+    }
 }
 
 /// Extract the current_protocol and the next_protocol from the block metadata
@@ -653,6 +659,38 @@ pub(crate) fn get_context_hash(
         .map(|block_header| block_header.header.context().clone())
 }
 
+/// Builds the Merkle proof of `data/<path>` in the context of the block.
+pub(crate) async fn get_context_merkle_proof(
+    chain_id: &ChainId,
+    block_hash: &BlockHash,
+    path: Option<String>,
+    env: &RpcServiceEnvironment,
+) -> Result<ContextProof, RpcServiceError> {
+    // the same root as `context/raw/bytes`
+    let mut key = context_key_owned!("data");
+    if let Some(path) = path {
+        key.extend(
+            path.split('/')
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string()),
+        );
+    };
+
+    let ctx_hash = get_context_hash(chain_id, block_hash, env)?;
+    env.tezedge_context()
+        .get_merkle_proof(&ctx_hash, key)
+        .await
+        .map_err(|e| RpcServiceError::UnexpectedError {
+            reason: format!("{}", e),
+        })?
+        .ok_or_else(|| RpcServiceError::NoDataFoundError {
+            reason: format!(
+                "No context found for context_hash: {}",
+                ctx_hash.to_base58_check()
+            ),
+        })
+}
+
 /// Cached database call for additional block data
 #[cached(
     name = "BLOCK_ADDITIONAL_DATA_CACHE",
@@ -1173,3 +1173,29 @@ pub(crate) async fn patch_bakers(
     let response = rx.await?;
     Ok(response)
 }
+
+pub(crate) async fn patch_peer_acl(
+    peer_id: crypto::hash::CryptoboxPublicKeyHash,
+    command: shell_automaton::service::rpc_service::AclCommand,
+    env: &RpcServiceEnvironment,
+) -> anyhow::Result<shell_automaton::service::rpc_service::PeerAclInfo> {
+    let rx = env
+        .shell_automaton_sender()
+        .send(RpcShellAutomatonMsg::PatchPeerAcl { peer_id, command })
+        .await?;
+
+    Ok(serde_json::from_value(rx.await?)?)
+}
+
+pub(crate) async fn patch_point_acl(
+    address: std::net::SocketAddr,
+    command: shell_automaton::service::rpc_service::AclCommand,
+    env: &RpcServiceEnvironment,
+) -> anyhow::Result<shell_automaton::service::rpc_service::PointAclInfo> {
+    let rx = env
+        .shell_automaton_sender()
+        .send(RpcShellAutomatonMsg::PatchPointAcl { address, command })
+        .await?;
+
+    Ok(serde_json::from_value(rx.await?)?)
+}
@@ -0,0 +1,401 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Prometheus text exposition format (version 0.0.4) of the node statistics.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use shell_automaton::service::rpc_service::{
+    RpcRequest as RpcShellAutomatonMsg, ShellAutomatonMetrics,
+};
+use shell_automaton::service::statistics_service::DurationHistogram;
+
+use crate::server::RpcServiceEnvironment;
+use crate::services::dev_services;
+
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+#[derive(Debug, Clone, Copy)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl MetricKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+            MetricKind::Histogram => "histogram",
+        }
+    }
+}
+
+/// Writes metric families, each family is a `# HELP` and `# TYPE` header followed by its samples.
+#[derive(Debug, Default)]
+pub struct MetricsEncoder {
+    out: String,
+}
+
+impl MetricsEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn family(&mut self, name: &str, kind: MetricKind, help: &str) {
+        let help = help.replace('\\', "\\\\").replace('\n', "\\n");
+        let _ = writeln!(self.out, "# HELP {} {}", name, help);
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind.as_str());
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            self.out.push('{');
+            for (i, (label, label_value)) in labels.iter().enumerate() {
+                if i > 0 {
+                    self.out.push(',');
+                }
+                let label_value = label_value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n");
+                let _ = write!(self.out, "{}=\"{}\"", label, label_value);
+            }
+            self.out.push('}');
+        }
+        let _ = writeln!(self.out, " {}", value);
+    }
+
+    pub fn counter(&mut self, name: &str, help: &str, value: f64) {
+        self.family(name, MetricKind::Counter, help);
+        self.sample(name, &[], value);
+    }
+
+    pub fn gauge(&mut self, name: &str, help: &str, value: f64) {
+        self.family(name, MetricKind::Gauge, help);
+        self.sample(name, &[], value);
+    }
+
+    /// Writes a histogram of durations, converted from nanoseconds to seconds.
+    pub fn duration_histogram(&mut self, name: &str, help: &str, histogram: &DurationHistogram) {
+        self.family(name, MetricKind::Histogram, help);
+        self.duration_histogram_samples(name, &[], histogram);
+    }
+
+    /// Writes the `_bucket`, `_sum` and `_count` samples of one histogram of a
+    /// histogram family, durations are converted from nanoseconds to seconds.
+    pub fn duration_histogram_samples(
+        &mut self,
+        name: &str,
+        labels: &[(&str, &str)],
+        histogram: &DurationHistogram,
+    ) {
+        const NANOS: f64 = 1_000_000_000.0;
+
+        let bucket = format!("{}_bucket", name);
+        for (bound, count) in &histogram.buckets {
+            let le = (*bound as f64 / NANOS).to_string();
+            let mut bucket_labels = labels.to_vec();
+            bucket_labels.push(("le", &le));
+            self.sample(&bucket, &bucket_labels, *count as f64);
+        }
+        let mut bucket_labels = labels.to_vec();
+        bucket_labels.push(("le", "+Inf"));
+        self.sample(&bucket, &bucket_labels, histogram.count as f64);
+        self.sample(
+            &format!("{}_sum", name),
+            labels,
+            histogram.sum as f64 / NANOS,
+        );
+        self.sample(&format!("{}_count", name), labels, histogram.count as f64);
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+async fn get_shell_automaton_metrics(
+    env: &RpcServiceEnvironment,
+) -> Result<ShellAutomatonMetrics, tokio::sync::oneshot::error::RecvError> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    let _ = env
+        .shell_automaton_sender()
+        .send(RpcShellAutomatonMsg::GetMetrics { channel: tx })
+        .await;
+    rx.await
+}
+
+fn encode_shell_automaton_metrics(enc: &mut MetricsEncoder, metrics: &ShellAutomatonMetrics) {
+    enc.counter(
+        "tezedge_shell_automaton_actions_total",
+        "Number of actions applied by the shell automaton.",
+        metrics.applied_actions_count as f64,
+    );
+
+    enc.family(
+        "tezedge_peers",
+        MetricKind::Gauge,
+        "Number of peers by connection state.",
+    );
+    for (state, len) in [
+        ("potential", metrics.peers_potential),
+        ("connected", metrics.peers_connected),
+        ("handshaked", metrics.peers_handshaked),
+    ] {
+        enc.sample("tezedge_peers", &[("state", state)], len as f64);
+    }
+    enc.gauge(
+        "tezedge_peers_blacklisted_ips",
+        "Number of blacklisted peer IP addresses.",
+        metrics.peers_blacklisted as f64,
+    );
+
+    if let Some(level) = metrics.current_head_level {
+        enc.gauge(
+            "tezedge_current_head_level",
+            "Level of the current head.",
+            level as f64,
+        );
+    }
+    if let Some(level) = metrics.best_remote_level {
+        enc.gauge(
+            "tezedge_best_remote_level",
+            "Highest level of the current heads of handshaked peers.",
+            level as f64,
+        );
+    }
+    enc.gauge(
+        "tezedge_bootstrapped",
+        "1 if the node is bootstrapped, 0 otherwise.",
+        if metrics.bootstrapped { 1.0 } else { 0.0 },
+    );
+
+    enc.family(
+        "tezedge_mempool_operations",
+        MetricKind::Gauge,
+        "Number of mempool operations by status.",
+    );
+    for (status, len) in [
+        ("pending", metrics.mempool_pending),
+        ("applied", metrics.mempool_applied),
+        ("branch_delayed", metrics.mempool_branch_delayed),
+        ("branch_refused", metrics.mempool_branch_refused),
+        ("refused", metrics.mempool_refused),
+        ("outdated", metrics.mempool_outdated),
+    ] {
+        enc.sample(
+            "tezedge_mempool_operations",
+            &[("status", status)],
+            len as f64,
+        );
+    }
+
+    if let Some(histogram) = &metrics.block_apply_durations {
+        enc.duration_histogram(
+            "tezedge_block_application_duration_seconds",
+            "Time spent in the protocol runner applying a block.",
+            histogram,
+        );
+    }
+}
+
+fn encode_db_metrics(enc: &mut MetricsEncoder, env: &RpcServiceEnvironment) {
+    let stats = env
+        .persistent_storage()
+        .main_db()
+        .db_stats()
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+
+    enc.family(
+        "tezedge_db_operations_total",
+        MetricKind::Counter,
+        "Number of main database operations by column.",
+    );
+    for (column, s) in &stats {
+        for (op, count) in [
+            ("read", s.total_reads),
+            ("write", s.total_writes),
+            ("update", s.total_updates),
+        ] {
+            enc.sample(
+                "tezedge_db_operations_total",
+                &[("column", *column), ("op", op)],
+                count as f64,
+            );
+        }
+    }
+
+    enc.family(
+        "tezedge_db_operation_duration_seconds",
+        MetricKind::Histogram,
+        "Duration of main database operations by column.",
+    );
+    for (column, s) in &stats {
+        for (op, histogram) in [
+            ("read", &s.read_durations),
+            ("write", &s.write_durations),
+            ("update", &s.update_durations),
+        ] {
+            enc.duration_histogram_samples(
+                "tezedge_db_operation_duration_seconds",
+                &[("column", *column), ("op", op)],
+                histogram,
+            );
+        }
+    }
+}
+
+fn encode_memory_metrics(enc: &mut MetricsEncoder) {
+    if let Ok(memory) = dev_services::get_stats_memory() {
+        if let Some(bytes) = memory.resident_bytes() {
+            enc.gauge(
+                "tezedge_node_resident_memory_bytes",
+                "Resident memory size of the node process.",
+                bytes as f64,
+            );
+        }
+        if let Some(bytes) = memory.virtual_bytes() {
+            enc.gauge(
+                "tezedge_node_virtual_memory_bytes",
+                "Virtual memory size of the node process.",
+                bytes as f64,
+            );
+        }
+    }
+
+    // not supported on macOS
+    if let Ok(runners) = dev_services::get_stats_memory_protocol_runners() {
+        enc.gauge(
+            "tezedge_protocol_runners",
+            "Number of running protocol runner processes.",
+            runners.len() as f64,
+        );
+        enc.family(
+            "tezedge_protocol_runner_resident_memory_bytes",
+            MetricKind::Gauge,
+            "Resident memory size of each protocol runner process.",
+        );
+        for (i, memory) in runners.iter().enumerate() {
+            if let Some(bytes) = memory.resident_bytes() {
+                let runner = i.to_string();
+                enc.sample(
+                    "tezedge_protocol_runner_resident_memory_bytes",
+                    &[("runner", runner.as_str())],
+                    bytes as f64,
+                );
+            }
+        }
+    }
+}
+
+fn encode_rpc_limits_metrics(enc: &mut MetricsEncoder, env: &RpcServiceEnvironment) {
+    let stats = env.rpc_limits.stats();
+
+    enc.counter(
+        "tezedge_rpc_rate_limited_requests_total",
+        "Number of RPC requests rejected by the per-client rate limit.",
+        stats.rate_limited_requests as f64,
+    );
+    enc.gauge(
+        "tezedge_rpc_rate_limited_clients",
+        "Number of clients tracked by the RPC rate limit.",
+        stats.rate_limited_clients as f64,
+    );
+
+    enc.family(
+        "tezedge_rpc_concurrency_limited_requests_total",
+        MetricKind::Counter,
+        "Number of RPC requests rejected by the concurrency limit of the route.",
+    );
+    for route in &stats.routes {
+        enc.sample(
+            "tezedge_rpc_concurrency_limited_requests_total",
+            &[("route", route.route.as_str())],
+            route.rejected_requests as f64,
+        );
+    }
+    enc.family(
+        "tezedge_rpc_requests_in_flight",
+        MetricKind::Gauge,
+        "Number of RPC requests being handled by a concurrency limited route.",
+    );
+    for route in &stats.routes {
+        enc.sample(
+            "tezedge_rpc_requests_in_flight",
+            &[("route", route.route.as_str())],
+            route.in_flight as f64,
+        );
+    }
+}
+
+pub(crate) async fn get_metrics(
+    env: &RpcServiceEnvironment,
+) -> Result<String, tokio::sync::oneshot::error::RecvError> {
+    let shell_automaton_metrics = get_shell_automaton_metrics(env).await?;
+
+    let mut enc = MetricsEncoder::new();
+    encode_shell_automaton_metrics(&mut enc, &shell_automaton_metrics);
+    encode_db_metrics(&mut enc, env);
+    encode_memory_metrics(&mut enc);
+    encode_rpc_limits_metrics(&mut enc, env);
+    Ok(enc.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use shell_automaton::service::statistics_service::DurationHistogram;
+
+    use super::{MetricKind, MetricsEncoder};
+
+    #[test]
+    fn encode_text_format() {
+        let mut histogram = DurationHistogram::new(&[100_000_000, 1_000_000_000]);
+        histogram.observe(50_000_000);
+        histogram.observe(500_000_000);
+        histogram.observe(2_000_000_000);
+
+        let mut enc = MetricsEncoder::new();
+        enc.counter("actions_total", "Applied actions.", 42.0);
+        enc.family("peers", MetricKind::Gauge, "Peers by state.");
+        enc.sample("peers", &[("state", "connected")], 3.0);
+        enc.sample("peers", &[("state", "a\"b\\c")], 0.0);
+        enc.duration_histogram("apply_seconds", "Block application.", &histogram);
+        enc.family("db_seconds", MetricKind::Histogram, "Database operations.");
+        enc.duration_histogram_samples(
+            "db_seconds",
+            &[("column", "blocks"), ("op", "read")],
+            &histogram,
+        );
+
+        assert_eq!(
+            enc.finish(),
+            "# HELP actions_total Applied actions.\n\
+             # TYPE actions_total counter\n\
+             actions_total 42\n\
+             # HELP peers Peers by state.\n\
+             # TYPE peers gauge\n\
+             peers{state=\"connected\"} 3\n\
+             peers{state=\"a\\\"b\\\\c\"} 0\n\
+             # HELP apply_seconds Block application.\n\
+             # TYPE apply_seconds histogram\n\
+             apply_seconds_bucket{le=\"0.1\"} 1\n\
+             apply_seconds_bucket{le=\"1\"} 2\n\
+             apply_seconds_bucket{le=\"+Inf\"} 3\n\
+             apply_seconds_sum 2.55\n\
+             apply_seconds_count 3\n\
+             # HELP db_seconds Database operations.\n\
+             # TYPE db_seconds histogram\n\
+             db_seconds_bucket{column=\"blocks\",op=\"read\",le=\"0.1\"} 1\n\
+             db_seconds_bucket{column=\"blocks\",op=\"read\",le=\"1\"} 2\n\
+             db_seconds_bucket{column=\"blocks\",op=\"read\",le=\"+Inf\"} 3\n\
+             db_seconds_sum{column=\"blocks\",op=\"read\"} 2.55\n\
+             db_seconds_count{column=\"blocks\",op=\"read\"} 3\n"
+        );
+    }
+}
@@ -14,6 +14,7 @@ use std::convert::{TryFrom, TryInto};
 use std::sync::Arc;
 
 use anyhow::{bail, format_err, Error};
+use serde_json::value::RawValue;
 use thiserror::Error;
 
 use crypto::hash::{BlockHash, ChainId, FromBytesError, ProtocolHash};
@@ -584,25 +585,35 @@ pub(crate) async fn get_votes_listings(
     }
 }
 
-/// Get protocol context constants from context list
+/// Get protocol context constants of the block as they were stored when its protocol was activated
 /// (just for RPC render use-case, do not use in processing or algorithms)
 ///
+/// Returns `None` if there are no constants stored for the protocol.
+///
 /// # Arguments
 ///
-/// * `block_id` - Url path parameter 'block_id', it contains string "head", block level or block hash.
-/// * `opt_level` - Optionaly input block level from block_id if is already known to prevent double code execution.
-/// * `list` - Context list handler.
-/// * `persistent_storage` - Persistent storage handler.
-/// * `state` - Current RPC collected state (head).
+/// * `chain_id` - [ChainId]
+/// * `block_hash` - [BlockHash]
+/// * `env` - RPC service environment.
 pub(crate) fn get_context_constants_just_for_rpc(
     chain_id: &ChainId,
     block_hash: &BlockHash,
     env: &RpcServiceEnvironment,
-) -> Result<Option<String>, ContextParamsError> {
-    // TODO: just get constants from the constants storage
-    let context_proto_params = get_context_protocol_params(chain_id, block_hash, env)?;
-    // TODO: TEST THIS
-    Ok(Some(context_proto_params.constants_data))
+) -> Result<Option<Box<RawValue>>, ContextParamsError> {
+    let protocol_hash =
+        get_additional_data_or_fail(chain_id, block_hash, env.persistent_storage())?
+            .next_protocol_hash;
+
+    match ConstantsStorage::new(env.persistent_storage()).get(&protocol_hash)? {
+        Some(constants) => RawValue::from_string(constants).map(Some).map_err(|e| {
+            ContextParamsError::ServiceError {
+                reason: RpcServiceError::UnexpectedError {
+                    reason: format!("Stored constants are not valid JSON: {}", e),
+                },
+            }
+        }),
+        None => Ok(None),
+    }
 }
 
 // We want error responses to be errors in `call_protocol_rpc_with_cache`
@@ -8,7 +8,7 @@ use itertools::Itertools;
 use storage::num_from_slice;
 use tezos_context_api::context_key_owned;
 use tezos_messages::base::signature_public_key::SignaturePublicKeyHash;
-use tezos_messages::protocol::proto_012::votes::VoteListings;
+use tezos_messages::protocol::proto_013::votes::VoteListings;
 
 use crate::server::RpcServiceEnvironment;
 use crate::services::protocol::VotesError;
@@ -46,7 +46,7 @@ pub async fn get_votes_listings(
             .to_string_representation();
         listings.push(VoteListings::new(
             address_decoded,
-            num_from_slice!(value, 0, i32),
+            num_from_slice!(value, 0, i64),
         ));
     }
 
@@ -0,0 +1,150 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Differential test of the rpcs served from the TezEdge context, every response is compared
+//! with the same rpc routed through the protocol runner (`/dev/ocaml/...`) of the same node,
+//! for each block of a recorded set of blocks
+//!
+//! usage:
+//!
+//! ```
+//!     NODE_RPC_CONTEXT_ROOT=http://127.0.0.1:18732 BLOCKS_FILE=rpc/tests/resources/context_rpc_blocks.txt cargo test -p rpc --test context_rpc_diff_tests -- --ignored --nocapture
+//! ```
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use anyhow::format_err;
+use hyper::body::Buf;
+use hyper::{Client, StatusCode};
+use serde_json::Value;
+
+/// Rpcs served from the TezEdge context, relative to `chains/main/blocks/<block_id>`
+const CONTEXT_RPCS: &[&str] = &[
+    "context/constants",
+    "votes/listings",
+    "context/raw/bytes?depth=1",
+    "context/raw/bytes/cycle?depth=1",
+    "context/raw/bytes/delegates?depth=0",
+    "context/raw/bytes/delegates?depth=1",
+    "context/raw/bytes/delegates?depth=2",
+    "context/raw/bytes/rolls/owner/current?depth=1",
+    "context/raw/bytes/votes",
+    "context/raw/bytes/votes/listings",
+    "context/raw/bytes/v1/constants",
+    "context/raw/bytes/no_such_key",
+];
+
+#[ignore]
+#[tokio::test]
+async fn test_context_rpc_diff() {
+    let node_url = env::var("NODE_RPC_CONTEXT_ROOT")
+        .expect("env variable 'NODE_RPC_CONTEXT_ROOT' should be set");
+    let blocks_file = blocks_file();
+    let blocks = read_blocks(&blocks_file);
+
+    println!("========================================");
+    println!("Running context rpc diff with settings:");
+    println!("========================================");
+    println!("Node url: {}", node_url);
+    println!("Blocks: {} from {}", blocks.len(), blocks_file.display());
+
+    let mut failures = Vec::new();
+    for block_id in &blocks {
+        for rpc in CONTEXT_RPCS {
+            let rpc_path = format!("chains/main/blocks/{}/{}", block_id, rpc);
+            match compare(&node_url, &rpc_path).await {
+                Ok(()) => println!("Checked OK: {}", rpc_path),
+                Err(e) => {
+                    println!("\nMismatch: {}\n{}\n", rpc_path, e);
+                    failures.push(rpc_path);
+                }
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} responses differ from OCaml:\n{}",
+        failures.len(),
+        blocks.len() * CONTEXT_RPCS.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_recorded_blocks() {
+    let blocks = read_blocks(&blocks_file());
+    assert!(!blocks.is_empty());
+    assert!(blocks.iter().all(|block_id| !block_id.contains(' ')));
+}
+
+async fn compare(node_url: &str, rpc_path: &str) -> Result<(), anyhow::Error> {
+    let tezedge_url = format!("{}/{}", node_url, rpc_path);
+    let ocaml_url = format!("{}/dev/ocaml/{}", node_url, rpc_path);
+    let ((tezedge_status, tezedge_json), (ocaml_status, ocaml_json)) =
+        futures::try_join!(get_rpc_as_json(&tezedge_url), get_rpc_as_json(&ocaml_url))?;
+
+    if tezedge_status != ocaml_status {
+        return Err(format_err!(
+            "StatusCodes mismatch: tezedge: {}, ocaml: {}",
+            tezedge_status,
+            ocaml_status
+        ));
+    }
+
+    assert_json_diff::assert_json_matches_no_panic(
+        &tezedge_json,
+        &ocaml_json,
+        assert_json_diff::Config::new(assert_json_diff::CompareMode::Strict),
+    )
+    .map_err(|diff| {
+        format_err!(
+            "{}\n\ntezedge: {}\n\nocaml: {}",
+            diff,
+            tezedge_json,
+            ocaml_json
+        )
+    })
+}
+
+async fn get_rpc_as_json(url: &str) -> Result<(StatusCode, Value), anyhow::Error> {
+    let uri: hyper::Uri = url
+        .parse()
+        .map_err(|e| format_err!("Invalid URL: {}, reason: {}", url, e))?;
+
+    // a new client for every call, see `integration_tests::get_rpc_as_json`
+    let res = Client::new()
+        .get(uri)
+        .await
+        .map_err(|e| format_err!("Request url: {} failed: {}", url, e))?;
+    let status = res.status();
+    let body = hyper::body::aggregate(res.into_body()).await?;
+
+    // error responses are not always JSON, those are compared only by the status code
+    let json = serde_json::from_reader(body.reader()).unwrap_or(Value::Null);
+    Ok((status, json))
+}
+
+fn blocks_file() -> PathBuf {
+    env::var("BLOCKS_FILE").map_or_else(
+        |_| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("tests")
+                .join("resources")
+                .join("context_rpc_blocks.txt")
+        },
+        PathBuf::from,
+    )
+}
+
+/// One block id per line, empty lines and lines starting with `#` are skipped
+fn read_blocks(path: &Path) -> Vec<String> {
+    std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Failed to read blocks file {}: {}", path.display(), e))
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
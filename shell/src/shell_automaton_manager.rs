@@ -18,7 +18,7 @@ use serde::Deserialize;
 use slog::{info, warn, Logger};
 
 use networking::network_channel::NetworkChannelRef;
-use storage::{PersistentStorage, StorageInitInfo};
+use storage::{HistoryMode, PersistentStorage, StorageInitInfo};
 use tezos_identity::Identity;
 use tezos_messages::base::signature_public_key::SignaturePublicKeyHash;
 use tezos_messages::p2p::encoding::block_header::Level;
@@ -104,6 +104,7 @@ impl ShellAutomatonManager {
         shell_compatibility_version: Arc<ShellCompatibilityVersion>,
         p2p_config: P2p,
         pow_target: f64,
+        history_mode: HistoryMode,
         init_storage_data: StorageInitInfo,
         protocol_runner_config: ProtocolRunnerConfiguration,
         context_init_status_sender: tokio::sync::watch::Sender<bool>,
@@ -224,6 +225,9 @@ impl ShellAutomatonManager {
             peers_graylist_disable: p2p_config.disable_peer_graylist,
             peers_graylist_timeout: Duration::from_secs(15 * 60),
 
+            peers_address_book_max: 1000,
+            peers_address_book_save_interval: Duration::from_secs(60),
+
             bootstrap_block_header_get_timeout: Duration::from_millis(500),
             bootstrap_block_operations_get_timeout: Duration::from_millis(1000),
 
@@ -237,6 +241,8 @@ impl ShellAutomatonManager {
             },
             record_actions: p2p_config.record_shell_automaton_actions,
 
+            history_mode,
+
             disable_block_precheck: p2p_config.disable_block_precheck,
             disable_endorsements_precheck: p2p_config.disable_endorsements_precheck,
             mempool_get_operation_timeout: Duration::from_millis(
@@ -41,6 +41,32 @@ pub enum MemoryData {
     DarwinOs(DarwinOsData),
 }
 
+impl MemoryData {
+    /// Resident set size in bytes.
+    pub fn resident_bytes(&self) -> Option<u64> {
+        match self {
+            MemoryData::Linux(data) => pages_to_bytes(&data.resident, data.page_size),
+            // `ps` reports the resident set size in kilobytes
+            MemoryData::DarwinOs(data) => data.resident.parse::<u64>().ok().map(|kb| kb * 1024),
+        }
+    }
+
+    /// Total program size in bytes, only known on Linux.
+    pub fn virtual_bytes(&self) -> Option<u64> {
+        match self {
+            MemoryData::Linux(data) => pages_to_bytes(&data.size, data.page_size),
+            MemoryData::DarwinOs(_) => None,
+        }
+    }
+}
+
+fn pages_to_bytes(pages: &str, page_size: usize) -> Option<u64> {
+    pages
+        .parse::<u64>()
+        .ok()
+        .map(|pages| pages * page_size as u64)
+}
+
 impl From<LinuxData> for MemoryData {
     fn from(data: LinuxData) -> Self {
         MemoryData::Linux(data)
@@ -252,6 +278,26 @@ pub mod tests {
         assert_eq!(Ok(parse_result), memory.parse_linux_statm(statm_to_parse))
     }
 
+    #[test]
+    fn memory_data_bytes() {
+        let linux = MemoryData::Linux(LinuxData {
+            page_size: 4096,
+            size: "218428".to_string(),
+            resident: "10272".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(linux.resident_bytes(), Some(10272 * 4096));
+        assert_eq!(linux.virtual_bytes(), Some(218428 * 4096));
+
+        let mac = MemoryData::DarwinOs(DarwinOsData {
+            page_size: 4096,
+            mem: 0.3,
+            resident: "5336".to_string(),
+        });
+        assert_eq!(mac.resident_bytes(), Some(5336 * 1024));
+        assert_eq!(mac.virtual_bytes(), None);
+    }
+
     #[test]
     fn correct_parsing_mac() {
         let to_parse = "PID %MEM   RSS\n0.3  5336\n".to_string();
@@ -56,8 +56,15 @@ use crate::peer::disconnection::{PeerDisconnectAction, PeerDisconnectedAction};
 use crate::peer::handshaking::*;
 
 use crate::mempool::validator::*;
+use crate::peers::acl::{PeersAclPeerSetAction, PeersAclPointForgetAction, PeersAclPointSetAction};
 use crate::peers::add::multi::PeersAddMultiAction;
 use crate::peers::add::PeersAddIncomingPeerAction;
+use crate::peers::address_book::{
+    PeersAddressBookLoadErrorAction, PeersAddressBookLoadInitAction,
+    PeersAddressBookLoadPendingAction, PeersAddressBookLoadSuccessAction,
+    PeersAddressBookSaveErrorAction, PeersAddressBookSaveInitAction,
+    PeersAddressBookSavePendingAction, PeersAddressBookSaveSuccessAction,
+};
 use crate::peers::check::timeouts::{
     PeersCheckTimeoutsCleanupAction, PeersCheckTimeoutsInitAction, PeersCheckTimeoutsSuccessAction,
 };
@@ -144,6 +151,11 @@ use crate::storage::blocks::genesis::init::header_put::{
 use crate::storage::blocks::genesis::init::{
     StorageBlocksGenesisInitAction, StorageBlocksGenesisInitSuccessAction,
 };
+use crate::storage::history_prune::{
+    StorageHistoryPruneErrorAction, StorageHistoryPruneInitAction,
+    StorageHistoryPrunePendingAction, StorageHistoryPruneProgressAction,
+    StorageHistoryPruneSuccessAction,
+};
 use crate::storage::request::{
     StorageRequestCreateAction, StorageRequestErrorAction, StorageRequestFinishAction,
     StorageRequestInitAction, StorageRequestPendingAction, StorageRequestSuccessAction,
@@ -318,6 +330,19 @@ pub enum Action {
     PeersGraylistIpRemove(PeersGraylistIpRemoveAction),
     PeersGraylistIpRemoved(PeersGraylistIpRemovedAction),
 
+    PeersAddressBookLoadInit(PeersAddressBookLoadInitAction),
+    PeersAddressBookLoadPending(PeersAddressBookLoadPendingAction),
+    PeersAddressBookLoadError(PeersAddressBookLoadErrorAction),
+    PeersAddressBookLoadSuccess(PeersAddressBookLoadSuccessAction),
+    PeersAddressBookSaveInit(PeersAddressBookSaveInitAction),
+    PeersAddressBookSavePending(PeersAddressBookSavePendingAction),
+    PeersAddressBookSaveError(PeersAddressBookSaveErrorAction),
+    PeersAddressBookSaveSuccess(PeersAddressBookSaveSuccessAction),
+
+    PeersAclPeerSet(PeersAclPeerSetAction),
+    PeersAclPointSet(PeersAclPointSetAction),
+    PeersAclPointForget(PeersAclPointForgetAction),
+
     PeersAddIncomingPeer(PeersAddIncomingPeerAction),
     PeersAddMulti(PeersAddMultiAction),
     PeersRemove(PeersRemoveAction),
@@ -665,6 +690,12 @@ pub enum Action {
     StorageStateSnapshotCreateError(StorageStateSnapshotCreateErrorAction),
     StorageStateSnapshotCreateSuccess(StorageStateSnapshotCreateSuccessAction),
 
+    StorageHistoryPruneInit(StorageHistoryPruneInitAction),
+    StorageHistoryPrunePending(StorageHistoryPrunePendingAction),
+    StorageHistoryPruneProgress(StorageHistoryPruneProgressAction),
+    StorageHistoryPruneError(StorageHistoryPruneErrorAction),
+    StorageHistoryPruneSuccess(StorageHistoryPruneSuccessAction),
+
     StorageBlocksGenesisCheckAppliedInit(StorageBlocksGenesisCheckAppliedInitAction),
     StorageBlocksGenesisCheckAppliedGetMetaPending(
         StorageBlocksGenesisCheckAppliedGetMetaPendingAction,
@@ -6,7 +6,7 @@ use std::time::{Duration, SystemTime};
 
 use hex::FromHex;
 use serde::{Deserialize, Serialize};
-use storage::StorageInitInfo;
+use storage::{HistoryMode, StorageInitInfo};
 use tezos_api::{environment::TezosEnvironmentConfiguration, ffi::TezosRuntimeConfiguration};
 use tezos_context_api::{
     ContextKvStoreConfiguration, GenesisChain, ProtocolOverrides, TezosContextStorageConfiguration,
@@ -102,6 +102,12 @@ pub struct Config {
     /// Duration after which graylisted peer will timeout and be whitelisted.
     pub peers_graylist_timeout: Duration,
 
+    /// Maximum number of peer addresses remembered in the address book.
+    pub peers_address_book_max: usize,
+
+    /// How often to persist the peer address book, if it changed.
+    pub peers_address_book_save_interval: Duration,
+
     pub bootstrap_block_header_get_timeout: Duration,
     pub bootstrap_block_operations_get_timeout: Duration,
 
@@ -118,6 +124,10 @@ pub struct Config {
     /// Record/Persist actions.
     pub record_actions: bool,
 
+    /// How much of the chain history is kept in storage. Anything older
+    /// is pruned on cycle boundaries.
+    pub history_mode: HistoryMode,
+
     pub disable_block_precheck: bool,
     pub disable_endorsements_precheck: bool,
 
@@ -177,6 +187,7 @@ pub fn default_test_config() -> Config {
             ),
             executable_path: Default::default(),
             log_level: slog::Level::Error,
+            context_gc_preserved_cycles: None,
         },
         init_storage_data: StorageInitInfo {
             chain_id: ChainId::try_from_bytes(&[122, 6, 167, 112]).unwrap(),
@@ -218,6 +229,9 @@ pub fn default_test_config() -> Config {
         peers_graylist_disable: false,
         peers_graylist_timeout: Duration::from_secs(15 * 60),
 
+        peers_address_book_max: 1000,
+        peers_address_book_save_interval: Duration::from_secs(60),
+
         bootstrap_block_header_get_timeout: Duration::from_millis(500),
         bootstrap_block_operations_get_timeout: Duration::from_millis(1000),
 
@@ -226,6 +240,8 @@ pub fn default_test_config() -> Config {
         record_state_snapshots_with_interval: None,
         record_actions: false,
 
+        history_mode: HistoryMode::Archive,
+
         disable_endorsements_precheck: true,
         disable_block_precheck: true,
 
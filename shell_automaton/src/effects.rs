@@ -48,7 +48,9 @@ use crate::peer::remote_requests::block_operations_get::peer_remote_requests_blo
 use crate::peer::remote_requests::current_branch_get::peer_remote_requests_current_branch_get_effects;
 use crate::peer::requests::potential_peers_get::peer_requests_potential_peers_get_effects;
 
+use crate::peers::acl::peers_acl_effects;
 use crate::peers::add::multi::peers_add_multi_effects;
+use crate::peers::address_book::{peers_address_book_effects, PeersAddressBookSaveInitAction};
 use crate::peers::check::timeouts::{peers_check_timeouts_effects, PeersCheckTimeoutsInitAction};
 use crate::peers::dns_lookup::peers_dns_lookup_effects;
 use crate::peers::graylist::peers_graylist_effects;
@@ -61,6 +63,7 @@ use crate::storage::blocks::genesis::check_applied::storage_blocks_genesis_check
 use crate::storage::blocks::genesis::init::additional_data_put::storage_blocks_genesis_init_additional_data_put_effects;
 use crate::storage::blocks::genesis::init::header_put::storage_blocks_genesis_init_header_put_effects;
 use crate::storage::blocks::genesis::init::storage_blocks_genesis_init_effects;
+use crate::storage::history_prune::storage_history_prune_effects;
 use crate::storage::request::storage_request_effects;
 use crate::storage::state_snapshot::create::{
     storage_state_snapshot_create_effects, StorageStateSnapshotCreateInitAction,
@@ -113,6 +116,7 @@ pub fn check_timeouts<S: Service>(store: &mut Store<S>) {
     store.dispatch(PeersCheckTimeoutsInitAction {});
     store.dispatch(BootstrapCheckTimeoutsInitAction {});
     store.dispatch(MempoolTimeoutsInitAction {});
+    store.dispatch(PeersAddressBookSaveInitAction {});
 
     let bakers = store.state().baker_keys_iter().cloned().collect::<Vec<_>>();
     for baker in bakers {
@@ -185,6 +189,8 @@ pub fn effects<S: Service>(store: &mut Store<S>, action: &ActionWithMeta) {
     peers_add_multi_effects(store, action);
     peers_check_timeouts_effects(store, action);
     peers_graylist_effects(store, action);
+    peers_address_book_effects(store, action);
+    peers_acl_effects(store, action);
 
     bootstrap_effects(store, action);
     mempool_validator_effects(store, action);
@@ -201,6 +207,7 @@ pub fn effects<S: Service>(store: &mut Store<S>, action: &ActionWithMeta) {
     storage_blocks_genesis_init_commit_result_put_effects(store, action);
 
     storage_state_snapshot_create_effects(store, action);
+    storage_history_prune_effects(store, action);
 
     actors_effects(store, action);
     rpc_effects(store, action);
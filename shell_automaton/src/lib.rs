@@ -71,6 +71,8 @@ pub mod baker;
 
 pub mod stats;
 
+pub mod replay;
+
 pub mod service;
 use service::MioService;
 pub use service::{Service, ServiceDefault};
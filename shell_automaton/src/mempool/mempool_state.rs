@@ -73,6 +73,11 @@ pub struct MempoolState {
 }
 
 impl MempoolState {
+    /// Number of operations waiting for prevalidation.
+    pub fn pending_operations_len(&self) -> usize {
+        self.pending_operations.len()
+    }
+
     pub fn has_peer_seen_op(&self, peer: SocketAddr, op_hash: &OperationHash) -> bool {
         self.peer_state
             .get(&peer)
@@ -137,6 +142,10 @@ impl MempoolPendingOperations {
         self.ops.is_empty()
     }
 
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
     pub fn contains_key(&self, key: &OperationHash) -> bool {
         self.ops.contains_key(key)
     }
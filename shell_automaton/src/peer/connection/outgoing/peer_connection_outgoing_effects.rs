@@ -18,7 +18,11 @@ where
     match &action.action {
         Action::PeerConnectionOutgoingRandomInit(_) => {
             let state = store.state.get();
-            let potential_peers = state.peers.potential_iter().collect::<Vec<_>>();
+            // Dial randomly among the potential peers with the best score.
+            let potential_peers = state
+                .peers
+                .address_book
+                .best_of(state.peers.potential_iter(), state.time_as_nanos());
 
             if state.peers.connected_len() >= state.config.peers_connected_max {
                 return;
@@ -0,0 +1,14 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Peers and points banned or trusted by the node operator. Decisions
+//! are persisted in the address book.
+
+mod peers_acl_actions;
+pub use peers_acl_actions::*;
+
+mod peers_acl_reducer;
+pub use peers_acl_reducer::*;
+
+mod peers_acl_effects;
+pub use peers_acl_effects::*;
@@ -0,0 +1,57 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::net::SocketAddr;
+
+use serde::{Deserialize, Serialize};
+
+use crypto::hash::CryptoboxPublicKeyHash;
+
+use crate::peers::address_book::PeerAcl;
+use crate::{EnablingCondition, State};
+
+#[cfg(feature = "fuzzing")]
+use crate::fuzzing::net::SocketAddrMutator;
+
+/// Ban, trust or reset the peer identified by its public key hash.
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeersAclPeerSetAction {
+    pub peer_id: CryptoboxPublicKeyHash,
+    pub acl: PeerAcl,
+}
+
+impl EnablingCondition<State> for PeersAclPeerSetAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        state.peers.address_book.peer_acl(&self.peer_id) != self.acl
+    }
+}
+
+/// Ban, trust or reset the point (listening address).
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeersAclPointSetAction {
+    #[cfg_attr(feature = "fuzzing", field_mutator(SocketAddrMutator))]
+    pub address: SocketAddr,
+    pub acl: PeerAcl,
+}
+
+impl EnablingCondition<State> for PeersAclPointSetAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        state.peers.address_book.point_acl(&self.address) != self.acl
+    }
+}
+
+/// Remove the point from the address book, lifting its ban if any.
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeersAclPointForgetAction {
+    #[cfg_attr(feature = "fuzzing", field_mutator(SocketAddrMutator))]
+    pub address: SocketAddr,
+}
+
+impl EnablingCondition<State> for PeersAclPointForgetAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        state.peers.address_book.get(&self.address).is_some()
+    }
+}
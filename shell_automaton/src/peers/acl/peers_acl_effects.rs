@@ -0,0 +1,54 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use crate::peer::disconnection::PeerDisconnectAction;
+use crate::peers::add::multi::PeersAddMultiAction;
+use crate::peers::address_book::PeerAcl;
+use crate::peers::graylist::PeersGraylistIpAddedAction;
+use crate::{Action, ActionWithMeta, Service, Store};
+
+pub fn peers_acl_effects<S: Service>(store: &mut Store<S>, action: &ActionWithMeta) {
+    match &action.action {
+        Action::PeersAclPeerSet(content) if content.acl == PeerAcl::Banned => {
+            let addresses = store
+                .state()
+                .peers
+                .handshaked_iter()
+                .filter(|(_, peer)| peer.public_key_hash == content.peer_id)
+                .map(|(address, _)| address)
+                .collect::<Vec<_>>();
+            for address in addresses {
+                store.dispatch(PeerDisconnectAction { address });
+            }
+        }
+        Action::PeersAclPointSet(content) => match content.acl {
+            // Disconnects and removes all peers with the banned ip.
+            PeerAcl::Banned => {
+                store.dispatch(PeersGraylistIpAddedAction {
+                    ip: content.address.ip(),
+                });
+            }
+            PeerAcl::Trusted => {
+                store.dispatch(PeersAddMultiAction {
+                    addresses: vec![content.address],
+                });
+            }
+            PeerAcl::Open => {}
+        },
+        Action::PeerHandshakingFinish(content) => {
+            let state = store.state();
+            let banned = state
+                .peers
+                .get_handshaked(&content.address)
+                .map_or(false, |peer| {
+                    state.peers.address_book.peer_acl(&peer.public_key_hash) == PeerAcl::Banned
+                });
+            if banned {
+                store.dispatch(PeerDisconnectAction {
+                    address: content.address,
+                });
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,91 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::net::IpAddr;
+
+use crate::peers::address_book::PeerAcl;
+use crate::peers::{PeerBlacklistState, PeersState};
+use crate::{Action, ActionWithMeta, State};
+
+/// Blacklist the ip if any point with it is banned, otherwise lift the ban.
+fn update_ip_ban(peers: &mut PeersState, ip: IpAddr) {
+    if peers.address_book.is_ip_banned(&ip) {
+        *peers
+            .ip_blacklist_entry(ip)
+            .or_insert(PeerBlacklistState::Banned) = PeerBlacklistState::Banned;
+    } else if matches!(
+        peers.get_blacklisted_ip(&ip),
+        Some(PeerBlacklistState::Banned)
+    ) {
+        peers.remove_blacklisted_ip(&ip);
+    }
+}
+
+pub fn peers_acl_reducer(state: &mut State, action: &ActionWithMeta) {
+    let time = action.time_as_nanos();
+    let max = state.config.peers_address_book_max;
+
+    match &action.action {
+        Action::PeersAclPeerSet(content) => {
+            state
+                .peers
+                .address_book
+                .set_peer_acl(content.peer_id.clone(), content.acl);
+        }
+        Action::PeersAclPointSet(content) => {
+            state
+                .peers
+                .address_book
+                .set_point_acl(content.address, content.acl, time, max);
+            update_ip_ban(&mut state.peers, content.address.ip());
+        }
+        Action::PeersAclPointForget(content) => {
+            state.peers.address_book.forget_point(&content.address);
+            update_ip_ban(&mut state.peers, content.address.ip());
+        }
+        Action::PeersAddressBookLoadSuccess(_) => {
+            let banned = state
+                .peers
+                .address_book
+                .banned_points()
+                .map(|address| address.ip())
+                .collect::<Vec<_>>();
+            for ip in banned {
+                update_ip_ban(&mut state.peers, ip);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+
+    #[test]
+    fn test_ip_ban_lifted_with_last_banned_point() {
+        let mut peers = PeersState::new();
+        let first: SocketAddr = "1.2.3.4:9732".parse().unwrap();
+        let second: SocketAddr = "1.2.3.4:9733".parse().unwrap();
+
+        for address in [first, second] {
+            peers
+                .address_book
+                .set_point_acl(address, PeerAcl::Banned, 0, 10);
+            update_ip_ban(&mut peers, address.ip());
+        }
+        assert!(peers.is_blacklisted(&first.ip()));
+
+        peers
+            .address_book
+            .set_point_acl(first, PeerAcl::Open, 0, 10);
+        update_ip_ban(&mut peers, first.ip());
+        assert!(peers.is_blacklisted(&first.ip()));
+
+        peers.address_book.forget_point(&second);
+        update_ip_ban(&mut peers, second.ip());
+        assert!(!peers.is_blacklisted(&first.ip()));
+    }
+}
@@ -13,6 +13,17 @@ pub fn peers_add_multi_reducer(state: &mut State, action: &ActionWithMeta) {
             .peers_potential_max
             .saturating_sub(state.peers.potential_len());
 
+        // Prefer addresses with the best score in the address book
+        // and skip the ones which misbehaved.
+        let time = action.time_as_nanos();
+        let address_book = &state.peers.address_book;
+        let mut addresses = addresses
+            .iter()
+            .filter(|address| !address_book.is_bad(address, time))
+            .cloned()
+            .collect::<Vec<_>>();
+        address_book.sort_by_score(&mut addresses, time);
+
         for address in addresses.iter().take(max_len) {
             if let Ok(entry) = state.peers.entry(*address) {
                 entry.or_insert_with(|| Peer {
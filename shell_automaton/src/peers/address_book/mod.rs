@@ -0,0 +1,17 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Persisted address book with scored peer points. Peers with the
+//! best score are dialed first.
+
+mod peers_address_book_state;
+pub use peers_address_book_state::*;
+
+mod peers_address_book_actions;
+pub use peers_address_book_actions::*;
+
+mod peers_address_book_reducer;
+pub use peers_address_book_reducer::*;
+
+mod peers_address_book_effects;
+pub use peers_address_book_effects::*;
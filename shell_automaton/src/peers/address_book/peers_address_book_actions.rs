@@ -0,0 +1,121 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+
+use crate::service::storage_service::StorageError;
+use crate::{EnablingCondition, State};
+
+use super::{PeerAddressBookSnapshot, PeersAddressBookLoadState};
+
+/// Load address book from the storage.
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeersAddressBookLoadInitAction {}
+
+impl EnablingCondition<State> for PeersAddressBookLoadInitAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        matches!(
+            state.peers.address_book.load,
+            PeersAddressBookLoadState::Idle
+        )
+    }
+}
+
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeersAddressBookLoadPendingAction {}
+
+impl EnablingCondition<State> for PeersAddressBookLoadPendingAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        matches!(
+            state.peers.address_book.load,
+            PeersAddressBookLoadState::Idle
+        )
+    }
+}
+
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeersAddressBookLoadErrorAction {
+    pub error: StorageError,
+}
+
+impl EnablingCondition<State> for PeersAddressBookLoadErrorAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        matches!(
+            state.peers.address_book.load,
+            PeersAddressBookLoadState::Pending { .. }
+        )
+    }
+}
+
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeersAddressBookLoadSuccessAction {
+    pub snapshot: PeerAddressBookSnapshot,
+}
+
+impl EnablingCondition<State> for PeersAddressBookLoadSuccessAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        matches!(
+            state.peers.address_book.load,
+            PeersAddressBookLoadState::Pending { .. }
+        )
+    }
+}
+
+/// Persist address book to the storage, if it changed and
+/// `peers_address_book_save_interval` passed since the last save.
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeersAddressBookSaveInitAction {}
+
+impl EnablingCondition<State> for PeersAddressBookSaveInitAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        let address_book = &state.peers.address_book;
+        // Don't overwrite the persisted address book before we load it.
+        if !address_book.load.is_finished()
+            || address_book.save.is_pending()
+            || !address_book.changed
+        {
+            return false;
+        }
+        address_book.save.last_time().map_or(true, |last_time| {
+            state.time_as_nanos().saturating_sub(last_time)
+                >= state.config.peers_address_book_save_interval.as_nanos() as u64
+        })
+    }
+}
+
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeersAddressBookSavePendingAction {}
+
+impl EnablingCondition<State> for PeersAddressBookSavePendingAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        !state.peers.address_book.save.is_pending()
+    }
+}
+
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeersAddressBookSaveErrorAction {
+    pub error: StorageError,
+}
+
+impl EnablingCondition<State> for PeersAddressBookSaveErrorAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        state.peers.address_book.save.is_pending()
+    }
+}
+
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeersAddressBookSaveSuccessAction {}
+
+impl EnablingCondition<State> for PeersAddressBookSaveSuccessAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        state.peers.address_book.save.is_pending()
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use crate::service::storage_service::{
+    StorageRequestPayload, StorageResponseError, StorageResponseSuccess,
+};
+use crate::storage::request::{
+    StorageRequestCreateAction, StorageRequestErrorAction, StorageRequestSuccessAction,
+    StorageRequestor,
+};
+use crate::{Action, ActionWithMeta, Service, Store};
+
+use super::{
+    PeersAddressBookLoadErrorAction, PeersAddressBookLoadPendingAction,
+    PeersAddressBookLoadSuccessAction, PeersAddressBookSaveErrorAction,
+    PeersAddressBookSavePendingAction, PeersAddressBookSaveSuccessAction,
+};
+
+pub fn peers_address_book_effects<S>(store: &mut Store<S>, action: &ActionWithMeta)
+where
+    S: Service,
+{
+    match &action.action {
+        Action::PeersAddressBookLoadInit(_) => {
+            let chain_id = store.state().config.chain_id.clone();
+            store.dispatch(StorageRequestCreateAction {
+                payload: StorageRequestPayload::PeerAddressBookGet(chain_id),
+                requestor: StorageRequestor::None,
+            });
+            store.dispatch(PeersAddressBookLoadPendingAction {});
+        }
+        Action::PeersAddressBookSaveInit(_) => {
+            let chain_id = store.state().config.chain_id.clone();
+            let snapshot = store.state().peers.address_book.snapshot();
+            store.dispatch(StorageRequestCreateAction {
+                payload: StorageRequestPayload::PeerAddressBookPut(chain_id, snapshot),
+                requestor: StorageRequestor::None,
+            });
+            store.dispatch(PeersAddressBookSavePendingAction {});
+        }
+        Action::StorageRequestSuccess(StorageRequestSuccessAction { result, .. }) => match result {
+            StorageResponseSuccess::PeerAddressBookGetSuccess(snapshot) => {
+                store.dispatch(PeersAddressBookLoadSuccessAction {
+                    snapshot: snapshot.clone().unwrap_or_default(),
+                });
+            }
+            StorageResponseSuccess::PeerAddressBookPutSuccess(_) => {
+                store.dispatch(PeersAddressBookSaveSuccessAction {});
+            }
+            _ => {}
+        },
+        Action::StorageRequestError(StorageRequestErrorAction { error, .. }) => match error {
+            StorageResponseError::PeerAddressBookGetError(error) => {
+                slog::warn!(store.state().log, "Failed to load peer address book";
+                    "error" => error.to_string());
+                store.dispatch(PeersAddressBookLoadErrorAction {
+                    error: error.clone(),
+                });
+            }
+            StorageResponseError::PeerAddressBookPutError(error) => {
+                slog::warn!(store.state().log, "Failed to save peer address book";
+                    "error" => error.to_string());
+                store.dispatch(PeersAddressBookSaveErrorAction {
+                    error: error.clone(),
+                });
+            }
+            _ => {}
+        },
+        _ => {}
+    }
+}
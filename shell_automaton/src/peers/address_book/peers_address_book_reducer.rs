@@ -0,0 +1,110 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::net::SocketAddr;
+
+use tezos_messages::p2p::encoding::peer::PeerMessage;
+
+use crate::{Action, ActionWithMeta, State};
+
+use super::{PeersAddressBookLoadState, PeersAddressBookSaveState};
+
+pub fn peers_address_book_reducer(state: &mut State, action: &ActionWithMeta) {
+    let time = action.time_as_nanos();
+    let max = state.config.peers_address_book_max;
+
+    match &action.action {
+        Action::PeersAddressBookLoadPending(_) => {
+            state.peers.address_book.load = PeersAddressBookLoadState::Pending { time };
+        }
+        Action::PeersAddressBookLoadError(content) => {
+            state.peers.address_book.load = PeersAddressBookLoadState::Error {
+                time,
+                error: content.error.clone(),
+            };
+        }
+        Action::PeersAddressBookLoadSuccess(content) => {
+            let address_book = &mut state.peers.address_book;
+            address_book.extend(&content.snapshot, time, max);
+            address_book.load = PeersAddressBookLoadState::Success { time };
+        }
+        Action::PeersAddressBookSavePending(_) => {
+            let address_book = &mut state.peers.address_book;
+            address_book.save = PeersAddressBookSaveState::Pending { time };
+            address_book.changed = false;
+        }
+        Action::PeersAddressBookSaveError(content) => {
+            state.peers.address_book.save = PeersAddressBookSaveState::Error {
+                time,
+                error: content.error.clone(),
+            };
+        }
+        Action::PeersAddressBookSaveSuccess(_) => {
+            state.peers.address_book.save = PeersAddressBookSaveState::Success { time };
+        }
+
+        Action::PeerConnectionOutgoingPending(content) => {
+            state
+                .peers
+                .address_book
+                .dialing
+                .insert(content.address, time);
+        }
+        Action::PeerConnectionOutgoingSuccess(content) => {
+            let address_book = &mut state.peers.address_book;
+            if let Some(since) = address_book.dialing.remove(&content.address) {
+                let latency_ms = time.saturating_sub(since) / 1_000_000;
+                let entry = address_book.entry_or_insert(content.address, time, max);
+                entry.add_latency(latency_ms.min(u32::MAX as u64) as u32);
+                entry.last_seen = time;
+            }
+        }
+        Action::PeerConnectionOutgoingError(content) => {
+            state.peers.address_book.dialing.remove(&content.address);
+        }
+        Action::PeerDisconnected(content) => {
+            state.peers.address_book.dialing.remove(&content.address);
+        }
+        Action::PeerHandshakingFinish(content) => {
+            if let Some(point) = state
+                .peers
+                .get_handshaked(&content.address)
+                .map(|peer| SocketAddr::new(content.address.ip(), peer.port))
+            {
+                let entry = state.peers.address_book.entry_or_insert(point, time, max);
+                entry.connections = entry.connections.saturating_add(1);
+                entry.last_seen = time;
+            }
+        }
+        Action::PeerMessageReadSuccess(content) => {
+            if !matches!(
+                content.message.message(),
+                PeerMessage::BlockHeader(_)
+                    | PeerMessage::Operation(_)
+                    | PeerMessage::OperationsForBlocks(_)
+            ) {
+                return;
+            }
+            if let Some(point) = state
+                .peers
+                .get_handshaked(&content.address)
+                .map(|peer| SocketAddr::new(content.address.ip(), peer.port))
+            {
+                if let Some(entry) = state.peers.address_book.get_mut(&point) {
+                    entry.useful_messages = entry.useful_messages.saturating_add(1);
+                    entry.last_seen = time;
+                }
+            }
+        }
+        Action::PeersGraylistAddress(content) => {
+            if let Some(point) = state.peers.peer_point(&content.address) {
+                state
+                    .peers
+                    .address_book
+                    .entry_or_insert(point, time, max)
+                    .add_penalty(content.reason.penalty(), time);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,558 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::{IpAddr, SocketAddr};
+
+use serde::{Deserialize, Serialize};
+
+use crypto::hash::CryptoboxPublicKeyHash;
+use storage::persistent::SchemaError;
+
+use crate::service::storage_service::StorageError;
+
+#[cfg(feature = "fuzzing")]
+use crate::fuzzing::net::SocketAddrMutator;
+
+/// Graylist penalties are halved after this much time (6 hours).
+pub const PEER_ADDRESS_BOOK_PENALTY_HALF_LIFE: u64 = 6 * 60 * 60 * 1_000_000_000;
+
+/// Addresses with the score below this won't be added as potential peers.
+pub const PEER_ADDRESS_BOOK_BAD_SCORE: i64 = -50;
+
+const SUCCESSFUL_CONNECTIONS_MAX: u32 = 20;
+const SUCCESSFUL_CONNECTION_SCORE: i64 = 10;
+const USEFUL_MESSAGES_PER_SCORE: u64 = 100;
+const USEFUL_MESSAGES_SCORE_MAX: i64 = 100;
+const LATENCY_MS_PER_SCORE: u32 = 50;
+const LATENCY_SCORE_MAX: i64 = 40;
+
+/// Access control of a peer or a point, set by the node operator.
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerAcl {
+    Open,
+    /// Never graylisted and dialed before other peers.
+    Trusted,
+    /// Never connected to, banned points have their ip blacklisted
+    /// without a timeout.
+    Banned,
+}
+
+impl Default for PeerAcl {
+    fn default() -> Self {
+        Self::Open
+    }
+}
+
+/// What we know about the point (listening address) of a peer.
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PeerAddressBookEntry {
+    #[cfg_attr(feature = "fuzzing", field_mutator(SocketAddrMutator))]
+    pub address: SocketAddr,
+
+    /// Last time we were connected to the peer or it misbehaved.
+    pub last_seen: u64,
+
+    /// Number of successful handshakes with the peer.
+    pub connections: u32,
+
+    /// Sum of the penalties for the graylist reasons, decayed until
+    /// `penalty_time` with [PEER_ADDRESS_BOOK_PENALTY_HALF_LIFE].
+    pub penalty: u32,
+    pub penalty_time: u64,
+
+    /// Moving average of the tcp connection latency in milliseconds.
+    pub latency_ms: Option<u32>,
+
+    /// Number of block headers and operations received from the peer.
+    pub useful_messages: u64,
+
+    #[serde(default)]
+    pub acl: PeerAcl,
+}
+
+impl PeerAddressBookEntry {
+    pub fn new(address: SocketAddr, time: u64) -> Self {
+        Self {
+            address,
+            last_seen: time,
+            connections: 0,
+            penalty: 0,
+            penalty_time: time,
+            latency_ms: None,
+            useful_messages: 0,
+            acl: PeerAcl::Open,
+        }
+    }
+
+    /// Penalty decayed until `time`.
+    pub fn penalty_at(&self, time: u64) -> u32 {
+        let half_lives =
+            time.saturating_sub(self.penalty_time) / PEER_ADDRESS_BOOK_PENALTY_HALF_LIFE;
+        u32::try_from(half_lives)
+            .ok()
+            .and_then(|half_lives| self.penalty.checked_shr(half_lives))
+            .unwrap_or(0)
+    }
+
+    pub fn score(&self, time: u64) -> i64 {
+        let connections = self.connections.min(SUCCESSFUL_CONNECTIONS_MAX) as i64;
+        let useful = ((self.useful_messages / USEFUL_MESSAGES_PER_SCORE) as i64)
+            .min(USEFUL_MESSAGES_SCORE_MAX);
+        let latency = self.latency_ms.map_or(0, |ms| {
+            ((ms / LATENCY_MS_PER_SCORE) as i64).min(LATENCY_SCORE_MAX)
+        });
+
+        connections * SUCCESSFUL_CONNECTION_SCORE + useful - latency - self.penalty_at(time) as i64
+    }
+
+    /// Whether we should try to connect to the peer after restart.
+    pub fn is_good(&self, time: u64) -> bool {
+        self.connections > 0 && self.score(time) > 0
+    }
+
+    pub fn add_penalty(&mut self, penalty: u32, time: u64) {
+        self.penalty = self.penalty_at(time).saturating_add(penalty);
+        self.penalty_time = time;
+        self.last_seen = time;
+    }
+
+    pub fn add_latency(&mut self, latency_ms: u32) {
+        self.latency_ms = Some(match self.latency_ms {
+            Some(avg) => ((avg as u64 * 3 + latency_ms as u64) / 4) as u32,
+            None => latency_ms,
+        });
+    }
+}
+
+/// Persisted form of the [PeersAddressBookState].
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PeerAddressBookSnapshot {
+    pub entries: Vec<PeerAddressBookEntry>,
+    #[serde(default)]
+    pub trusted_peers: Vec<CryptoboxPublicKeyHash>,
+    #[serde(default)]
+    pub banned_peers: Vec<CryptoboxPublicKeyHash>,
+}
+
+impl storage::persistent::Encoder for PeerAddressBookSnapshot {
+    fn encode(&self) -> Result<Vec<u8>, SchemaError> {
+        rmp_serde::to_vec(self).map_err(|_| SchemaError::EncodeError)
+    }
+}
+
+impl storage::persistent::Decoder for PeerAddressBookSnapshot {
+    fn decode(bytes: &[u8]) -> Result<Self, SchemaError> {
+        rmp_serde::from_slice(bytes).map_err(|_| SchemaError::DecodeError)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum PeersAddressBookLoadState {
+    Idle,
+    Pending { time: u64 },
+    Error { time: u64, error: StorageError },
+    Success { time: u64 },
+}
+
+impl PeersAddressBookLoadState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Error { .. } | Self::Success { .. })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum PeersAddressBookSaveState {
+    Idle,
+    Pending { time: u64 },
+    Error { time: u64, error: StorageError },
+    Success { time: u64 },
+}
+
+impl PeersAddressBookSaveState {
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Self::Pending { .. })
+    }
+
+    /// Time of the last finished save attempt.
+    pub fn last_time(&self) -> Option<u64> {
+        match self {
+            Self::Idle | Self::Pending { .. } => None,
+            Self::Error { time, .. } | Self::Success { time } => Some(*time),
+        }
+    }
+}
+
+/// Scored points of the peers, persisted to storage, so that we
+/// remember good and bad peers across restarts.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeersAddressBookState {
+    entries: BTreeMap<SocketAddr, PeerAddressBookEntry>,
+
+    trusted_peers: BTreeSet<CryptoboxPublicKeyHash>,
+    banned_peers: BTreeSet<CryptoboxPublicKeyHash>,
+
+    /// Time when outgoing connection to the address was initiated,
+    /// used to measure the latency.
+    pub(super) dialing: BTreeMap<SocketAddr, u64>,
+
+    pub load: PeersAddressBookLoadState,
+    pub save: PeersAddressBookSaveState,
+
+    /// Whether address book changed since the last save.
+    pub changed: bool,
+}
+
+impl PeersAddressBookState {
+    pub fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            trusted_peers: BTreeSet::new(),
+            banned_peers: BTreeSet::new(),
+            dialing: BTreeMap::new(),
+            load: PeersAddressBookLoadState::Idle,
+            save: PeersAddressBookSaveState::Idle,
+            changed: false,
+        }
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline(always)]
+    pub fn get(&self, address: &SocketAddr) -> Option<&PeerAddressBookEntry> {
+        self.entries.get(address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PeerAddressBookEntry> {
+        self.entries.values()
+    }
+
+    /// Score of the address, `0` if we know nothing about it. Trusted
+    /// points have the highest score and banned ones the lowest.
+    pub fn score(&self, address: &SocketAddr, time: u64) -> i64 {
+        self.get(address).map_or(0, |entry| match entry.acl {
+            PeerAcl::Open => entry.score(time),
+            PeerAcl::Trusted => i64::MAX,
+            PeerAcl::Banned => i64::MIN,
+        })
+    }
+
+    pub fn point_acl(&self, address: &SocketAddr) -> PeerAcl {
+        self.get(address).map_or(PeerAcl::Open, |entry| entry.acl)
+    }
+
+    pub fn peer_acl(&self, peer_id: &CryptoboxPublicKeyHash) -> PeerAcl {
+        if self.trusted_peers.contains(peer_id) {
+            PeerAcl::Trusted
+        } else if self.banned_peers.contains(peer_id) {
+            PeerAcl::Banned
+        } else {
+            PeerAcl::Open
+        }
+    }
+
+    /// Whether any point with the `ip` is banned.
+    pub fn is_ip_banned(&self, ip: &IpAddr) -> bool {
+        self.banned_points().any(|address| address.ip() == *ip)
+    }
+
+    pub fn banned_points(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.iter()
+            .filter(|entry| entry.acl == PeerAcl::Banned)
+            .map(|entry| entry.address)
+    }
+
+    pub fn is_bad(&self, address: &SocketAddr, time: u64) -> bool {
+        self.score(address, time) < PEER_ADDRESS_BOOK_BAD_SCORE
+    }
+
+    /// Sort addresses by the score, best first.
+    pub fn sort_by_score(&self, addresses: &mut [SocketAddr], time: u64) {
+        addresses.sort_by_cached_key(|address| std::cmp::Reverse(self.score(address, time)));
+    }
+
+    /// Addresses with the highest score among `addresses`.
+    pub fn best_of<I>(&self, addresses: I, time: u64) -> Vec<SocketAddr>
+    where
+        I: IntoIterator<Item = SocketAddr>,
+    {
+        let mut best_score = i64::MIN;
+        let mut best = vec![];
+        for address in addresses {
+            let score = self.score(&address, time);
+            if score > best_score {
+                best_score = score;
+                best.clear();
+            }
+            if score == best_score {
+                best.push(address);
+            }
+        }
+        best
+    }
+
+    /// Trusted and good addresses that we should connect to after
+    /// restart, best first.
+    pub fn good_addresses(&self, time: u64) -> Vec<SocketAddr> {
+        let mut addresses = self
+            .iter()
+            .filter(|entry| match entry.acl {
+                PeerAcl::Open => entry.is_good(time),
+                PeerAcl::Trusted => true,
+                PeerAcl::Banned => false,
+            })
+            .map(|entry| entry.address)
+            .collect::<Vec<_>>();
+        self.sort_by_score(&mut addresses, time);
+        addresses
+    }
+
+    /// Get existing entry or add a new one. If there are already `max`
+    /// entries, the one with the lowest score is replaced, entries with
+    /// the acl set by the operator are never replaced.
+    pub(in crate::peers) fn entry_or_insert(
+        &mut self,
+        address: SocketAddr,
+        time: u64,
+        max: usize,
+    ) -> &mut PeerAddressBookEntry {
+        if !self.entries.contains_key(&address) && self.entries.len() >= max.max(1) {
+            let worst = self
+                .entries
+                .values()
+                .filter(|entry| entry.acl == PeerAcl::Open)
+                .min_by_key(|entry| (entry.score(time), entry.last_seen))
+                .map(|entry| entry.address);
+            if let Some(worst) = worst {
+                self.entries.remove(&worst);
+            }
+        }
+        self.changed = true;
+        self.entries
+            .entry(address)
+            .or_insert_with(|| PeerAddressBookEntry::new(address, time))
+    }
+
+    pub(super) fn get_mut(&mut self, address: &SocketAddr) -> Option<&mut PeerAddressBookEntry> {
+        let entry = self.entries.get_mut(address);
+        if entry.is_some() {
+            self.changed = true;
+        }
+        entry
+    }
+
+    pub(in crate::peers) fn set_point_acl(
+        &mut self,
+        address: SocketAddr,
+        acl: PeerAcl,
+        time: u64,
+        max: usize,
+    ) {
+        self.entry_or_insert(address, time, max).acl = acl;
+    }
+
+    pub(in crate::peers) fn set_peer_acl(&mut self, peer_id: CryptoboxPublicKeyHash, acl: PeerAcl) {
+        self.trusted_peers.remove(&peer_id);
+        self.banned_peers.remove(&peer_id);
+        match acl {
+            PeerAcl::Open => {}
+            PeerAcl::Trusted => {
+                self.trusted_peers.insert(peer_id);
+            }
+            PeerAcl::Banned => {
+                self.banned_peers.insert(peer_id);
+            }
+        }
+        self.changed = true;
+    }
+
+    /// Remove everything we know about the point.
+    pub(in crate::peers) fn forget_point(&mut self, address: &SocketAddr) {
+        if self.entries.remove(address).is_some() {
+            self.changed = true;
+        }
+    }
+
+    /// Add entries loaded from storage, entries which we already
+    /// have since the start are kept. Entries with the acl set are
+    /// always kept.
+    pub(super) fn extend(&mut self, snapshot: &PeerAddressBookSnapshot, time: u64, max: usize) {
+        let mut entries = snapshot.entries.clone();
+        entries.sort_by_cached_key(|entry| {
+            (
+                entry.acl == PeerAcl::Open,
+                std::cmp::Reverse(entry.score(time)),
+            )
+        });
+        for entry in entries {
+            if self.entries.len() >= max && entry.acl == PeerAcl::Open {
+                break;
+            }
+            self.entries.entry(entry.address).or_insert(entry);
+        }
+        for peer_id in &snapshot.trusted_peers {
+            if self.peer_acl(peer_id) == PeerAcl::Open {
+                self.trusted_peers.insert(peer_id.clone());
+            }
+        }
+        for peer_id in &snapshot.banned_peers {
+            if self.peer_acl(peer_id) == PeerAcl::Open {
+                self.banned_peers.insert(peer_id.clone());
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> PeerAddressBookSnapshot {
+        PeerAddressBookSnapshot {
+            entries: self.entries.values().cloned().collect(),
+            trusted_peers: self.trusted_peers.iter().cloned().collect(),
+            banned_peers: self.banned_peers.iter().cloned().collect(),
+        }
+    }
+}
+
+impl Default for PeersAddressBookState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto::hash::HashTrait;
+
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn address(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn test_entry_score() {
+        let mut entry = PeerAddressBookEntry::new(address(9732), 0);
+        assert_eq!(entry.score(0), 0);
+        assert!(!entry.is_good(0));
+
+        entry.connections = 2;
+        entry.useful_messages = 550;
+        entry.add_latency(200);
+        assert_eq!(entry.latency_ms, Some(200));
+        assert_eq!(entry.score(0), 20 + 5 - 4);
+        assert!(entry.is_good(0));
+
+        entry.add_penalty(40, 0);
+        assert_eq!(entry.score(0), 21 - 40);
+        assert!(!entry.is_good(0));
+
+        // Penalty is halved every half life.
+        let later = PEER_ADDRESS_BOOK_PENALTY_HALF_LIFE * 2 + SECOND;
+        assert_eq!(entry.penalty_at(later), 10);
+        assert_eq!(entry.score(later), 21 - 10);
+        assert_eq!(
+            entry.penalty_at(PEER_ADDRESS_BOOK_PENALTY_HALF_LIFE * 64),
+            0
+        );
+
+        entry.add_penalty(5, later);
+        assert_eq!(entry.penalty, 15);
+        assert_eq!(entry.penalty_time, later);
+    }
+
+    #[test]
+    fn test_best_addresses() {
+        let mut book = PeersAddressBookState::new();
+        book.entry_or_insert(address(1), 0, 10).connections = 1;
+        book.entry_or_insert(address(2), 0, 10).connections = 3;
+        book.entry_or_insert(address(3), 0, 10).add_penalty(100, 0);
+
+        assert!(book.is_bad(&address(3), 0));
+        assert!(!book.is_bad(&address(4), 0));
+        assert_eq!(book.good_addresses(0), vec![address(2), address(1)]);
+
+        let mut addresses = vec![address(4), address(3), address(1), address(2)];
+        book.sort_by_score(&mut addresses, 0);
+        assert_eq!(
+            addresses,
+            vec![address(2), address(1), address(4), address(3)]
+        );
+
+        assert_eq!(
+            book.best_of(vec![address(1), address(4), address(3)], 0),
+            vec![address(1)]
+        );
+        assert_eq!(
+            book.best_of(vec![address(4), address(3), address(5)], 0),
+            vec![address(4), address(5)]
+        );
+    }
+
+    #[test]
+    fn test_worst_entry_replaced_when_full() {
+        let mut book = PeersAddressBookState::new();
+        book.entry_or_insert(address(1), 0, 2).connections = 1;
+        book.entry_or_insert(address(2), 0, 2).add_penalty(10, 0);
+        book.entry_or_insert(address(3), 0, 2);
+
+        assert_eq!(book.len(), 2);
+        assert!(book.get(&address(1)).is_some());
+        assert!(book.get(&address(2)).is_none());
+        assert!(book.get(&address(3)).is_some());
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        use storage::persistent::{Decoder, Encoder};
+
+        let mut book = PeersAddressBookState::new();
+        book.entry_or_insert(address(1), SECOND, 10).add_latency(30);
+        book.entry_or_insert(address(2), SECOND, 10).connections = 4;
+
+        let encoded = book.snapshot().encode().unwrap();
+        let decoded = PeerAddressBookSnapshot::decode(&encoded).unwrap();
+
+        let mut loaded = PeersAddressBookState::new();
+        loaded.extend(&decoded, SECOND, 10);
+        assert_eq!(loaded.snapshot().entries, book.snapshot().entries);
+    }
+
+    #[test]
+    fn test_acl() {
+        let peer_id = CryptoboxPublicKeyHash::try_from_bytes(&[1; 16]).unwrap();
+        let mut book = PeersAddressBookState::new();
+        book.entry_or_insert(address(1), 0, 3).connections = 5;
+        book.set_point_acl(address(2), PeerAcl::Trusted, 0, 3);
+        book.set_point_acl(address(3), PeerAcl::Banned, 0, 3);
+        book.set_peer_acl(peer_id.clone(), PeerAcl::Banned);
+
+        assert_eq!(book.good_addresses(0), vec![address(2), address(1)]);
+        assert!(book.is_ip_banned(&address(3).ip()));
+        assert_eq!(book.peer_acl(&peer_id), PeerAcl::Banned);
+
+        // Only the open entry is replaced when full.
+        book.entry_or_insert(address(4), 0, 3);
+        assert!(book.get(&address(1)).is_none());
+        assert_eq!(book.point_acl(&address(2)), PeerAcl::Trusted);
+        assert_eq!(book.point_acl(&address(3)), PeerAcl::Banned);
+
+        let mut loaded = PeersAddressBookState::new();
+        loaded.extend(&book.snapshot(), 0, 1);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.point_acl(&address(3)), PeerAcl::Banned);
+        assert_eq!(loaded.peer_acl(&peer_id), PeerAcl::Banned);
+
+        loaded.forget_point(&address(3));
+        assert!(!loaded.is_ip_banned(&address(3).ip()));
+    }
+}
@@ -42,6 +42,32 @@ pub enum PeerGraylistReason {
     Unknown,
 }
 
+impl PeerGraylistReason {
+    /// Penalty subtracted from the peer's score in the address book.
+    pub fn penalty(&self) -> u32 {
+        match self {
+            Self::ConnectionClosed => 2,
+            Self::NackReceived(NackMotive::TooManyConnections | NackMotive::AlreadyConnected) => 2,
+
+            Self::ConnectionIncomingError
+            | Self::ConnectionOutgoingError
+            | Self::BinaryMessageReadError
+            | Self::BinaryMessageWriteError
+            | Self::ChunkReadError
+            | Self::ChunkWriteError
+            | Self::Unknown => 5,
+
+            Self::NackReceived(_) | Self::MessageReadError(_) | Self::MessageWriteError(_) => 10,
+
+            Self::NackSent(_) | Self::HandshakeError => 20,
+
+            Self::RequestedBlockHeaderLevelMismatch
+            | Self::BootstrapBlockHeaderInconsistentChain
+            | Self::BootstrapCementedBlockReorg => 50,
+        }
+    }
+}
+
 #[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PeersGraylistAddressAction {
@@ -11,7 +11,9 @@ use super::{PeersGraylistIpAddAction, PeersGraylistIpAddedAction, PeersGraylistI
 pub fn peers_graylist_effects<S: Service>(store: &mut Store<S>, action: &ActionWithMeta) {
     match &action.action {
         Action::PeersGraylistAddress(action) => {
-            if store.state.get().config.peers_graylist_disable {
+            let state = store.state.get();
+            // Trusted peers are only disconnected, never graylisted.
+            if state.config.peers_graylist_disable || state.peers.is_trusted(&action.address) {
                 store.dispatch(PeerDisconnectAction {
                     address: action.address,
                 });
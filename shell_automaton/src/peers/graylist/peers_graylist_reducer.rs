@@ -14,7 +14,13 @@ pub fn peers_graylist_reducer(state: &mut State, action: &ActionWithMeta) {
             );
         }
         Action::PeersGraylistIpRemove(action_content) => {
-            state.peers.remove_blacklisted_ip(&action_content.ip);
+            // Bans are only lifted by the node operator.
+            if !matches!(
+                state.peers.get_blacklisted_ip(&action_content.ip),
+                Some(PeerBlacklistState::Banned)
+            ) {
+                state.peers.remove_blacklisted_ip(&action_content.ip);
+            }
         }
         _ => {}
     }
@@ -2,20 +2,39 @@
 // SPDX-License-Identifier: MIT
 
 use crate::peer::connection::outgoing::PeerConnectionOutgoingRandomInitAction;
+use crate::peers::add::multi::PeersAddMultiAction;
+use crate::peers::address_book::PeersAddressBookLoadInitAction;
 use crate::peers::dns_lookup::PeersDnsLookupInitAction;
 use crate::service::Service;
 use crate::{Action, ActionWithMeta, Store};
 
 pub fn peers_init_effects<S: Service>(store: &mut Store<S>, action: &ActionWithMeta) {
-    if let Action::PeersInit(_) = &action.action {
-        let list = store.state().config.peers_dns_lookup_addresses.clone();
-
-        // Do dns lookups to gather some potential peers.
-        for (address, port) in list.into_iter() {
-            store.dispatch(PeersDnsLookupInitAction { address, port });
+    match &action.action {
+        Action::PeersInit(_) => {
+            // Good peers from the previous runs should be dialed first,
+            // so dns lookups are done once address book is loaded.
+            store.dispatch(PeersAddressBookLoadInitAction {});
         }
+        Action::PeersAddressBookLoadError(_) | Action::PeersAddressBookLoadSuccess(_) => {
+            let addresses = store
+                .state()
+                .peers
+                .address_book
+                .good_addresses(action.time_as_nanos());
+            if !addresses.is_empty() {
+                store.dispatch(PeersAddMultiAction { addresses });
+            }
+
+            let list = store.state().config.peers_dns_lookup_addresses.clone();
 
-        // Try connecting to potential peers if we need peers.
-        store.dispatch(PeerConnectionOutgoingRandomInitAction {});
+            // Do dns lookups to gather some potential peers.
+            for (address, port) in list.into_iter() {
+                store.dispatch(PeersDnsLookupInitAction { address, port });
+            }
+
+            // Try connecting to potential peers if we need peers.
+            store.dispatch(PeerConnectionOutgoingRandomInitAction {});
+        }
+        _ => {}
     }
 }
@@ -1,6 +1,8 @@
 // Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+pub mod acl;
+pub mod address_book;
 pub mod dns_lookup;
 pub mod graylist;
 pub mod init;
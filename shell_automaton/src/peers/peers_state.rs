@@ -9,8 +9,10 @@ use serde::{Deserialize, Serialize};
 
 use crypto::hash::BlockHash;
 
+use crate::peer::connection::PeerConnectionState;
 use crate::peer::{Peer, PeerHandshaked, PeerStatus};
 
+use super::address_book::{PeerAcl, PeersAddressBookState};
 use super::check::timeouts::PeersCheckTimeoutsState;
 use super::dns_lookup::PeersDnsLookupState;
 
@@ -19,12 +21,15 @@ use super::dns_lookup::PeersDnsLookupState;
 pub enum PeerBlacklistState {
     /// Peer is temporarily graylisted.
     Graylisted { since: u64 },
+    /// Point with the ip is banned by the node operator.
+    Banned,
 }
 
 impl PeerBlacklistState {
     pub fn timeout(&self, graylist_duration: Duration) -> Option<u64> {
         match self {
             Self::Graylisted { since } => Some(*since + graylist_duration.as_nanos() as u64),
+            Self::Banned => None,
         }
     }
 }
@@ -36,6 +41,8 @@ pub struct PeersState {
 
     pub dns_lookup: Option<PeersDnsLookupState>,
 
+    pub address_book: PeersAddressBookState,
+
     pub check_timeouts: PeersCheckTimeoutsState,
 
     // TODO(zura): implement p2p peer requests to better track each request.
@@ -51,6 +58,8 @@ impl PeersState {
 
             dns_lookup: None,
 
+            address_book: PeersAddressBookState::new(),
+
             check_timeouts: PeersCheckTimeoutsState::new(),
 
             pending_block_header_requests: BTreeMap::new(),
@@ -120,6 +129,29 @@ impl PeersState {
         self.ip_blacklist.remove(ip)
     }
 
+    /// Point (listening address) of the peer, if we know it.
+    ///
+    /// For incoming connections we only learn it during the handshake.
+    pub fn peer_point(&self, address: &SocketAddr) -> Option<SocketAddr> {
+        match &self.get(address)?.status {
+            PeerStatus::Handshaked(peer) => Some(SocketAddr::new(address.ip(), peer.port)),
+            PeerStatus::Connecting(PeerConnectionState::Outgoing(_)) => Some(*address),
+            PeerStatus::Handshaking(handshaking) if !handshaking.incoming => Some(*address),
+            _ => None,
+        }
+    }
+
+    /// Whether the peer or its point is trusted by the node operator.
+    pub fn is_trusted(&self, address: &SocketAddr) -> bool {
+        let point_trusted = self.peer_point(address).map_or(false, |point| {
+            self.address_book.point_acl(&point) == PeerAcl::Trusted
+        });
+        let peer_trusted = self.get_handshaked(address).map_or(false, |peer| {
+            self.address_book.peer_acl(&peer.public_key_hash) == PeerAcl::Trusted
+        });
+        point_trusted || peer_trusted
+    }
+
     #[inline(always)]
     pub fn iter(&self) -> impl Iterator<Item = (&SocketAddr, &Peer)> {
         self.list.iter()
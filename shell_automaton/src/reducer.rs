@@ -29,8 +29,10 @@ use crate::peer::remote_requests::block_operations_get::peer_remote_requests_blo
 use crate::peer::remote_requests::current_branch_get::peer_remote_requests_current_branch_get_reducer;
 use crate::peer::requests::potential_peers_get::peer_requests_potential_peers_get_reducer;
 
+use crate::peers::acl::peers_acl_reducer;
 use crate::peers::add::multi::peers_add_multi_reducer;
 use crate::peers::add::peers_add_reducer;
+use crate::peers::address_book::peers_address_book_reducer;
 use crate::peers::check::timeouts::peers_check_timeouts_reducer;
 use crate::peers::dns_lookup::peers_dns_lookup_reducer;
 use crate::peers::graylist::peers_graylist_reducer;
@@ -62,6 +64,7 @@ use crate::storage::blocks::genesis::init::commit_result_get::storage_blocks_gen
 use crate::storage::blocks::genesis::init::commit_result_put::storage_blocks_genesis_init_commit_result_put_reducer;
 use crate::storage::blocks::genesis::init::header_put::storage_blocks_genesis_init_header_put_reducer;
 use crate::storage::blocks::genesis::init::storage_blocks_genesis_init_reducer;
+use crate::storage::history_prune::storage_history_prune_reducer;
 use crate::storage::request::storage_request_reducer;
 use crate::storage::state_snapshot::create::storage_state_snapshot_create_reducer;
 use crate::storage::{
@@ -131,6 +134,8 @@ pub fn reducer(state: &mut State, action: &ActionWithMeta) {
         peers_remove_reducer,
         peers_check_timeouts_reducer,
         peers_graylist_reducer,
+        peers_address_book_reducer,
+        peers_acl_reducer,
         bootstrap_reducer,
         mempool_validator_reducer,
         mempool_reducer,
@@ -149,6 +154,7 @@ pub fn reducer(state: &mut State, action: &ActionWithMeta) {
         storage_blocks_genesis_init_commit_result_get_reducer,
         storage_blocks_genesis_init_commit_result_put_reducer,
         storage_state_snapshot_create_reducer,
+        storage_history_prune_reducer,
         kv_block_meta_reducer,
         kv_block_header_reducer,
         kv_block_additional_data_reducer,
@@ -0,0 +1,283 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Deterministic offline replay of the actions recorded with
+//! `--record-shell-automaton-actions`.
+//!
+//! Starting from a recorded state snapshot, the recorded actions are
+//! applied with the [`reducer`] and the resulting state is compared with
+//! every recorded snapshot on the way, so that a production incident can
+//! be reproduced locally.
+
+use std::borrow::Cow;
+
+use serde_json::Value;
+use thiserror::Error;
+
+use storage::persistent::Decoder;
+use storage::{
+    Direction, IteratorMode, PersistentStorage, ShellAutomatonActionStorage,
+    ShellAutomatonStateStorage, StorageError,
+};
+
+use crate::{reducer, Action, ActionId, ActionWithMeta, State};
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("Storage error: {0}")]
+    Storage(#[from] StorageError),
+    #[error("No state snapshot recorded for action {action_id}")]
+    SnapshotNotFound { action_id: u64 },
+    #[error("Failed to serialize state: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+/// First difference between the replayed and the recorded state.
+#[derive(Debug, Clone)]
+pub struct ReplayDivergence {
+    /// Action after which the states differ.
+    pub action: ActionWithMeta,
+    /// Last action after which the states were equal, the divergence was
+    /// introduced by one of the actions after it.
+    pub last_matched_action_id: ActionId,
+    /// JSON pointer of the first differing value in the state.
+    pub path: String,
+    pub recorded: Value,
+    pub replayed: Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayReport {
+    /// Action of the snapshot the replay started from.
+    pub initial_action_id: ActionId,
+    /// Last replayed action.
+    pub last_action_id: ActionId,
+    pub replayed_actions: u64,
+    pub compared_snapshots: u64,
+    pub divergence: Option<ReplayDivergence>,
+}
+
+/// Applies `actions` on top of the `initial_state`, comparing the state
+/// with the one returned by `recorded_state` after each action. Stops at
+/// the first divergence.
+///
+/// Actions that precede the `initial_state` are skipped.
+pub fn replay<I, F>(
+    initial_state: State,
+    actions: I,
+    mut recorded_state: F,
+) -> Result<ReplayReport, ReplayError>
+where
+    I: IntoIterator<Item = Result<ActionWithMeta, ReplayError>>,
+    F: FnMut(ActionId) -> Result<Option<State>, ReplayError>,
+{
+    let mut state = initial_state;
+    let initial_action_id = state.last_action.id();
+    let mut report = ReplayReport {
+        initial_action_id,
+        last_action_id: initial_action_id,
+        replayed_actions: 0,
+        compared_snapshots: 0,
+        divergence: None,
+    };
+    let mut last_matched_action_id = initial_action_id;
+
+    for action in actions {
+        let action = action?;
+        if action.id <= initial_action_id {
+            continue;
+        }
+        reducer(&mut state, &action);
+        report.replayed_actions += 1;
+        report.last_action_id = action.id;
+
+        let recorded = match recorded_state(action.id)? {
+            Some(recorded) => recorded,
+            None => continue,
+        };
+        let recorded = serde_json::to_value(&recorded)?;
+        let replayed = serde_json::to_value(&state)?;
+        report.compared_snapshots += 1;
+
+        match first_difference(&recorded, &replayed) {
+            Some((path, recorded, replayed)) => {
+                report.divergence = Some(ReplayDivergence {
+                    action,
+                    last_matched_action_id,
+                    path,
+                    recorded,
+                    replayed,
+                });
+                return Ok(report);
+            }
+            None => last_matched_action_id = report.last_action_id,
+        }
+    }
+
+    Ok(report)
+}
+
+/// Replays the actions recorded in the storage, starting from the
+/// closest snapshot before `from_action_id` (the first snapshot if
+/// `None`) until `to_action_id` (the last recorded action if `None`).
+pub fn replay_from_storage(
+    storage: &PersistentStorage,
+    from_action_id: Option<u64>,
+    to_action_id: Option<u64>,
+) -> Result<ReplayReport, ReplayError> {
+    let snapshot_storage = ShellAutomatonStateStorage::new(storage);
+    let action_storage = ShellAutomatonActionStorage::new(storage);
+
+    let initial_state: State = match from_action_id {
+        Some(action_id) => snapshot_storage
+            .get_closest_before(&action_id)?
+            .ok_or(ReplayError::SnapshotNotFound { action_id })?,
+        None => snapshot_storage
+            .get_closest_after(&0)?
+            .ok_or(ReplayError::SnapshotNotFound { action_id: 0 })?,
+    };
+    let start = u64::from(initial_state.last_action.id()).saturating_add(1);
+    let to_action_id = to_action_id.unwrap_or(u64::MAX);
+
+    let actions = action_storage
+        .find(IteratorMode::From(Cow::Owned(start), Direction::Forward))?
+        .map(|result| -> Result<ActionWithMeta, ReplayError> {
+            let (key, value) = result.map_err(StorageError::from)?;
+            Ok(ActionWithMeta {
+                id: ActionId::new_unchecked(u64::decode(&key).map_err(StorageError::from)?),
+                // Not recorded, reducers don't depend on it.
+                depth: 0,
+                action: Action::decode(&value).map_err(StorageError::from)?,
+            })
+        })
+        .take_while(|result| {
+            result
+                .as_ref()
+                .map_or(true, |action| u64::from(action.id) <= to_action_id)
+        });
+
+    replay(initial_state, actions, |action_id| {
+        Ok(snapshot_storage.get(&action_id.into())?)
+    })
+}
+
+/// Returns the JSON pointer and the values of the first difference
+/// between `recorded` and `replayed`.
+pub fn first_difference(recorded: &Value, replayed: &Value) -> Option<(String, Value, Value)> {
+    fn escape(key: &str) -> String {
+        key.replace('~', "~0").replace('/', "~1")
+    }
+
+    fn find(path: String, a: &Value, b: &Value) -> Option<(String, Value, Value)> {
+        match (a, b) {
+            (Value::Object(a_map), Value::Object(b_map)) => {
+                let mut keys = a_map.keys().chain(b_map.keys()).collect::<Vec<_>>();
+                keys.sort();
+                keys.dedup();
+                keys.into_iter().find_map(|key| {
+                    let path = format!("{}/{}", path, escape(key));
+                    match (a_map.get(key), b_map.get(key)) {
+                        (Some(a), Some(b)) => find(path, a, b),
+                        (a, b) => Some((
+                            path,
+                            a.cloned().unwrap_or(Value::Null),
+                            b.cloned().unwrap_or(Value::Null),
+                        )),
+                    }
+                })
+            }
+            (Value::Array(a_list), Value::Array(b_list)) => (0..a_list.len().max(b_list.len()))
+                .find_map(|i| {
+                    let path = format!("{}/{}", path, i);
+                    match (a_list.get(i), b_list.get(i)) {
+                        (Some(a), Some(b)) => find(path, a, b),
+                        (a, b) => Some((
+                            path,
+                            a.cloned().unwrap_or(Value::Null),
+                            b.cloned().unwrap_or(Value::Null),
+                        )),
+                    }
+                }),
+            (a, b) if a == b => None,
+            (a, b) => Some((path, a.clone(), b.clone())),
+        }
+    }
+
+    find(String::new(), recorded, replayed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::net::SocketAddr;
+
+    use serde_json::json;
+
+    use crate::config::default_test_config;
+    use crate::peers::add::multi::PeersAddMultiAction;
+
+    use super::*;
+
+    fn action(id: u64, port: u16) -> ActionWithMeta {
+        ActionWithMeta {
+            id: ActionId::new_unchecked(id),
+            depth: 0,
+            action: PeersAddMultiAction {
+                addresses: vec![SocketAddr::from(([127, 0, 0, 1], port))],
+            }
+            .into(),
+        }
+    }
+
+    #[test]
+    fn test_first_difference() {
+        let a = json!({"a": {"b": [1, 2], "c/d": 1}, "e": 1});
+        assert_eq!(first_difference(&a, &a), None);
+
+        let b = json!({"a": {"b": [1, 3], "c/d": 2}, "e": 1});
+        assert_eq!(
+            first_difference(&a, &b),
+            Some(("/a/b/1".to_string(), json!(2), json!(3)))
+        );
+
+        let b = json!({"a": {"b": [1, 2], "c/d": 2}});
+        assert_eq!(
+            first_difference(&a, &b),
+            Some(("/a/c~1d".to_string(), json!(1), json!(2)))
+        );
+    }
+
+    #[test]
+    fn test_replay_reports_first_divergence() {
+        let initial = State::new(default_test_config());
+        let actions = vec![action(10, 1), action(20, 2), action(30, 3)];
+
+        // Recorded states, the one after the last action is tampered with.
+        let mut recorded = BTreeMap::new();
+        let mut state = initial.clone();
+        for action in &actions {
+            reducer(&mut state, action);
+            recorded.insert(action.id, state.clone());
+        }
+        recorded.get_mut(&actions[2].id).unwrap().peers.list.clear();
+
+        let report = replay(
+            initial.clone(),
+            actions.iter().cloned().map(Ok).take(2),
+            |id| Ok(recorded.get(&id).cloned()),
+        )
+        .unwrap();
+        assert_eq!(report.replayed_actions, 2);
+        assert_eq!(report.compared_snapshots, 2);
+        assert!(report.divergence.is_none());
+
+        let report = replay(initial, actions.iter().cloned().map(Ok), |id| {
+            Ok(recorded.get(&id).cloned())
+        })
+        .unwrap();
+        let divergence = report.divergence.unwrap();
+        assert_eq!(divergence.action.id, actions[2].id);
+        assert_eq!(divergence.last_matched_action_id, actions[1].id);
+        assert!(divergence.path.starts_with("/peers/list"));
+    }
+}
@@ -16,6 +16,7 @@ pub use rights_effects::rights_effects;
 use tezos_messages::p2p::encoding::block_header::Level;
 
 mod utils;
+pub(crate) use utils::{get_cycle_first_level, get_level_cycle};
 
 pub mod cycle_delegates;
 pub mod cycle_eras;
@@ -42,6 +42,26 @@ fn cycle_from_level(level: Level, era: &CycleEra) -> Result<(Cycle, Position), C
     }
 }
 
+/// Returns cycle of the `level` and position of the level within that cycle.
+pub(crate) fn get_level_cycle(
+    level: Level,
+    cycle_eras: &CycleEras,
+) -> Result<(Cycle, Position), CycleError> {
+    cycle_from_level(level, get_cycle_era(level, cycle_eras)?)
+}
+
+/// Returns level of the first block in the `cycle`.
+pub(crate) fn get_cycle_first_level(
+    cycle: Cycle,
+    cycle_eras: &CycleEras,
+) -> Result<Level, CycleError> {
+    let era = cycle_eras
+        .iter()
+        .find(|era| era.first_cycle <= cycle)
+        .ok_or(CycleError::EraNotFound(cycle))?;
+    Ok(era.first_level + (cycle - era.first_cycle) * era.blocks_per_cycle)
+}
+
 fn assert_cycle(cycle: Cycle, block_cycle: Cycle, preserved_cycles: u8) -> Result<(), CycleError> {
     if (block_cycle - cycle).abs() <= preserved_cycles.into() {
         Ok(())
@@ -1,10 +1,11 @@
 // Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use crypto::hash::{BlockHash, ChainId, SeedEd25519};
+use crypto::hash::{BlockHash, ChainId, CryptoboxPublicKeyHash, SeedEd25519};
 use crypto::PublicKeyWithHash;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::time::Instant;
 use tezos_messages::base::signature_public_key::SignaturePublicKey;
 use tezos_messages::p2p::encoding::block_header::BlockHeader;
@@ -18,11 +19,16 @@ use crate::mempool::mempool_actions::{
     MempoolRpcEndorsementsStatusGetAction,
 };
 use crate::mempool::OperationKind;
+use crate::peers::acl::{PeersAclPeerSetAction, PeersAclPointForgetAction, PeersAclPointSetAction};
+use crate::peers::address_book::PeerAcl;
 use crate::rights::{rights_actions::RightsRpcGetAction, RightsKey};
-use crate::service::rpc_service::{BakerPatch, BakingState, RpcRequest, RpcRequestStream};
+use crate::service::rpc_service::{
+    AclCommand, BakerPatch, BakingState, PeerAclInfo, PeerConnectionState, PointAclInfo,
+    PointConnectionState, RpcRequest, RpcRequestStream, ShellAutomatonMetrics,
+};
 use crate::service::{BakerService, RpcService, Service};
 use crate::storage::request::StorageRequestStatus;
-use crate::{Action, ActionWithMeta, Store};
+use crate::{Action, ActionWithMeta, State, Store};
 
 use super::rpc_actions::RpcInjectBlockAction;
 use super::rpc_actions::RpcRejectOutdatedInjectedBlockAction;
@@ -192,6 +198,36 @@ pub fn rpc_effects<S: Service>(store: &mut Store<S>, action: &ActionWithMeta) {
                         let stats = store.service().statistics();
                         let _ = channel.send(stats.map(|s| s.block_stats_get_all().clone()));
                     }
+                    RpcRequest::GetMetrics { channel } => {
+                        let block_apply_durations = store
+                            .service()
+                            .statistics()
+                            .map(|s| s.block_apply_durations().clone());
+                        let state = store.state();
+                        let validated = &state.mempool.validated_operations;
+                        let metrics = ShellAutomatonMetrics {
+                            applied_actions_count: state.applied_actions_count,
+                            peers_potential: state.peers.potential_len(),
+                            peers_connected: state.peers.connected_len(),
+                            peers_handshaked: state.peers.handshaked_len(),
+                            peers_blacklisted: state.peers.blacklist_ip_iter().count(),
+                            current_head_level: state.current_head_level(),
+                            best_remote_level: state.best_remote_level(),
+                            bootstrapped: state.is_bootstrapped(),
+                            mempool_pending: state.mempool.pending_operations_len(),
+                            mempool_applied: validated.applied.len(),
+                            mempool_branch_delayed: validated.branch_delayed.len(),
+                            mempool_branch_refused: validated.branch_refused.len(),
+                            mempool_refused: validated.refused.len(),
+                            mempool_outdated: validated.outdated.len(),
+                            block_apply_durations,
+                        };
+                        let _ = channel.send(metrics);
+                        store
+                            .service()
+                            .rpc()
+                            .respond(rpc_id, serde_json::Value::Null);
+                    }
                     RpcRequest::GetBakingState { channel } => {
                         let data = Some(())
                             .and_then(|_| store.state().current_head.get().cloned())
@@ -378,6 +414,27 @@ pub fn rpc_effects<S: Service>(store: &mut Store<S>, action: &ActionWithMeta) {
                             .rpc()
                             .respond(rpc_id, serde_json::Value::Bool(res));
                     }
+                    RpcRequest::PatchPeerAcl { peer_id, command } => {
+                        let current = store.state().peers.address_book.peer_acl(&peer_id);
+                        if let Some(acl) = acl_after_command(current, command) {
+                            store.dispatch(PeersAclPeerSetAction {
+                                peer_id: peer_id.clone(),
+                                acl,
+                            });
+                        }
+                        let info = peer_acl_info(store.state(), &peer_id);
+                        store.service().rpc().respond(rpc_id, info);
+                    }
+                    RpcRequest::PatchPointAcl { address, command } => {
+                        let current = store.state().peers.address_book.point_acl(&address);
+                        if command == AclCommand::Forget {
+                            store.dispatch(PeersAclPointForgetAction { address });
+                        } else if let Some(acl) = acl_after_command(current, command) {
+                            store.dispatch(PeersAclPointSetAction { address, acl });
+                        }
+                        let info = point_acl_info(store.state(), &address);
+                        store.service().rpc().respond(rpc_id, info);
+                    }
                 }
             }
         }
@@ -555,3 +612,58 @@ fn enqueue_injected_block<S: Service>(store: &mut Store<S>) {
         }
     }
 }
+
+/// Acl and connection state of the peer after an operator's command.
+fn peer_acl_info(state: &State, peer_id: &CryptoboxPublicKeyHash) -> PeerAclInfo {
+    let acl = state.peers.address_book.peer_acl(peer_id);
+    let connected = state
+        .peers
+        .handshaked_iter()
+        .any(|(_, peer)| peer.public_key_hash == *peer_id);
+    PeerAclInfo {
+        trusted: acl == PeerAcl::Trusted,
+        banned: acl == PeerAcl::Banned,
+        state: if connected {
+            PeerConnectionState::Running
+        } else {
+            PeerConnectionState::Disconnected
+        },
+    }
+}
+
+/// Acl and connection state of the point after an operator's command, the
+/// point is the listening address of a peer.
+fn point_acl_info(state: &State, address: &SocketAddr) -> PointAclInfo {
+    let acl = state.peers.address_book.point_acl(address);
+    let peer = state
+        .peers
+        .handshaked_iter()
+        .find(|(peer_address, peer)| {
+            peer_address.ip() == address.ip() && peer.port == address.port()
+        })
+        .map(|(_, peer)| peer);
+    PointAclInfo {
+        trusted: acl == PeerAcl::Trusted,
+        banned: acl == PeerAcl::Banned,
+        state: PointConnectionState {
+            event_kind: match peer {
+                Some(_) => PeerConnectionState::Running,
+                None => PeerConnectionState::Disconnected,
+            },
+            p2p_peer_id: peer.map(|peer| peer.public_key_hash.to_base58_check()),
+        },
+    }
+}
+
+/// Acl of a peer or a point after the operator's `command`, `None` if
+/// the command doesn't change it.
+fn acl_after_command(current: PeerAcl, command: AclCommand) -> Option<PeerAcl> {
+    match command {
+        AclCommand::Ban => Some(PeerAcl::Banned),
+        AclCommand::Trust => Some(PeerAcl::Trusted),
+        AclCommand::Open | AclCommand::Forget => Some(PeerAcl::Open),
+        AclCommand::Unban if current == PeerAcl::Banned => Some(PeerAcl::Open),
+        AclCommand::Untrust if current == PeerAcl::Trusted => Some(PeerAcl::Open),
+        AclCommand::Unban | AclCommand::Untrust => None,
+    }
+}
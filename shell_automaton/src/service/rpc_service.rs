@@ -3,6 +3,7 @@
 
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap},
+    net::SocketAddr,
     sync::Arc,
     thread,
     time::Instant,
@@ -11,7 +12,7 @@ use std::{
 use serde::{Deserialize, Serialize};
 use tokio::sync::{mpsc, oneshot};
 
-use crypto::hash::{BlockHash, ChainId, OperationHash};
+use crypto::hash::{BlockHash, ChainId, CryptoboxPublicKeyHash, OperationHash};
 use storage::persistent::SchemaError;
 use storage::{
     shell_automaton_action_meta_storage::ShellAutomatonActionsStats, BlockHeaderWithHash,
@@ -34,7 +35,7 @@ use crate::{
 use crate::{Action, State};
 
 use super::{
-    statistics_service::{ActionGraph, ActionKindStatsForBlock},
+    statistics_service::{ActionGraph, ActionKindStatsForBlock, DurationHistogram},
     storage_service::StorageRequestPayloadKind,
     BlockApplyStats,
 };
@@ -85,6 +86,31 @@ pub struct BakingState {
     pub bakers: BTreeMap<SignaturePublicKeyHash, BakerState>,
 }
 
+/// Snapshot of the automaton state exported by the `/metrics` endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ShellAutomatonMetrics {
+    pub applied_actions_count: u64,
+
+    pub peers_potential: usize,
+    pub peers_connected: usize,
+    pub peers_handshaked: usize,
+    pub peers_blacklisted: usize,
+
+    pub current_head_level: Option<Level>,
+    pub best_remote_level: Option<Level>,
+    pub bootstrapped: bool,
+
+    pub mempool_pending: usize,
+    pub mempool_applied: usize,
+    pub mempool_branch_delayed: usize,
+    pub mempool_branch_refused: usize,
+    pub mempool_refused: usize,
+    pub mempool_outdated: usize,
+
+    /// `None` if the statistics service is disabled.
+    pub block_apply_durations: Option<DurationHistogram>,
+}
+
 #[derive(Debug)]
 pub enum RpcRequest {
     GetCurrentGlobalState {
@@ -114,6 +140,9 @@ pub enum RpcRequest {
     GetBlockStats {
         channel: oneshot::Sender<Option<crate::service::statistics_service::BlocksApplyStats>>,
     },
+    GetMetrics {
+        channel: oneshot::Sender<ShellAutomatonMetrics>,
+    },
 
     GetBakingState {
         channel: oneshot::Sender<Option<BakingState>>,
@@ -155,6 +184,14 @@ pub enum RpcRequest {
     PatchBakers {
         patch: BakerPatch,
     },
+    PatchPeerAcl {
+        peer_id: CryptoboxPublicKeyHash,
+        command: AclCommand,
+    },
+    PatchPointAcl {
+        address: SocketAddr,
+        command: AclCommand,
+    },
 }
 
 #[derive(Debug, Deserialize)]
@@ -164,6 +201,55 @@ pub enum BakerPatch {
     Remove { baker: String },
 }
 
+/// Operator's decision about a peer or a point, as in Octez
+/// `/network/{peers,points}/<id>/<command>` RPCs.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AclCommand {
+    Ban,
+    Unban,
+    Trust,
+    Untrust,
+    /// Neither banned nor trusted.
+    Open,
+    /// Remove everything we know about the point.
+    Forget,
+}
+
+/// Connection state of a peer, as in Octez `peer_info`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerConnectionState {
+    Running,
+    Disconnected,
+}
+
+/// The part of Octez `peer_info` the node keeps, returned by
+/// `PATCH /network/peers/<peer_id>`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PeerAclInfo {
+    pub trusted: bool,
+    pub banned: bool,
+    pub state: PeerConnectionState,
+}
+
+/// Connection state of a point, as in Octez `point_info`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PointConnectionState {
+    pub event_kind: PeerConnectionState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p2p_peer_id: Option<String>,
+}
+
+/// The part of Octez `point_info` the node keeps, returned by
+/// `PATCH /network/points/<point>`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PointAclInfo {
+    pub trusted: bool,
+    pub banned: bool,
+    pub state: PointConnectionState,
+}
+
 #[derive(Debug)]
 pub enum MempoolOperationStatsFilter {
     None,
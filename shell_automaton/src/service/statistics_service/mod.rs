@@ -9,6 +9,7 @@ use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 
 use crypto::hash::{BlockHash, BlockPayloadHash, CryptoboxPublicKeyHash};
+pub use storage::database::backend::DurationHistogram;
 use storage::shell_automaton_action_meta_storage::ShellAutomatonActionStatsForRanges;
 use storage::BlockHeaderWithHash;
 use tezos_api::ffi::{ApplyBlockExecutionTimestamps, ApplyBlockResponse};
@@ -20,6 +21,20 @@ use crate::{ActionId, ActionKind, ActionWithMeta};
 
 const STORAGE_REQUESTS_FINISHED_LEN: usize = 1024;
 
+/// Upper bounds (in nanoseconds) of the block application histogram buckets.
+const BLOCK_APPLY_BUCKETS: [u64; 10] = [
+    10_000_000,
+    25_000_000,
+    50_000_000,
+    100_000_000,
+    250_000_000,
+    500_000_000,
+    1_000_000_000,
+    2_500_000_000,
+    5_000_000_000,
+    10_000_000_000,
+];
+
 fn ocaml_time_normalize(ocaml_time: f64) -> u64 {
     (ocaml_time * 1_000_000_000.0) as u64
 }
@@ -254,6 +269,7 @@ pub struct StatisticsService {
 
     blocks_apply: BlocksApplyStats,
     levels: VecDeque<(Level, Vec<BlockHash>)>,
+    block_apply_durations: DurationHistogram,
 
     storage_requests_finished: VecDeque<StorageRequestFinished>,
 }
@@ -268,6 +284,7 @@ impl Default for StatisticsService {
             action_graph: Default::default(),
             blocks_apply: Default::default(),
             levels: Default::default(),
+            block_apply_durations: DurationHistogram::new(&BLOCK_APPLY_BUCKETS),
             storage_requests_finished: VecDeque::with_capacity(STORAGE_REQUESTS_FINISHED_LEN),
         }
     }
@@ -313,6 +330,11 @@ impl StatisticsService {
         &self.blocks_apply
     }
 
+    /// Durations of all block applications since the node started.
+    pub fn block_apply_durations(&self) -> &DurationHistogram {
+        &self.block_apply_durations
+    }
+
     pub fn block_stats_get_by_level(
         &self,
         level: Level,
@@ -520,6 +542,10 @@ impl StatisticsService {
         if let Some(v) = self.blocks_apply.get_mut(block_hash) {
             v.apply_block_stats = Some((&result.execution_timestamps).into());
             v.apply_block_end = Some(time);
+            if let Some(start) = v.apply_block_start {
+                self.block_apply_durations
+                    .observe(time.saturating_sub(start));
+            }
         }
     }
 
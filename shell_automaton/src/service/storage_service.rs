@@ -15,9 +15,9 @@ use storage::cycle_storage::CycleData;
 use storage::{
     BlockAdditionalData, BlockHeaderWithHash, BlockMetaStorage, BlockMetaStorageReader,
     BlockStorage, BlockStorageReader, ChainMetaStorage, ConstantsStorage, CycleErasStorage,
-    CycleMetaStorage, OperationKey, OperationsMetaStorage, OperationsStorage,
-    OperationsStorageReader, PersistentStorage, ShellAutomatonActionStorage,
-    ShellAutomatonStateStorage, StorageInitInfo,
+    CycleMetaStorage, HistoryMode, OperationKey, OperationsMetaStorage, OperationsStorage,
+    OperationsStorageReader, PeerAddressBookStorage, PersistentStorage,
+    ShellAutomatonActionStorage, ShellAutomatonStateStorage, StorageInitInfo,
 };
 use tezos_api::ffi::{ApplyBlockRequest, ApplyBlockResponse, CommitGenesisResult};
 use tezos_messages::p2p::encoding::block_header::{BlockHeader, Level};
@@ -26,6 +26,7 @@ use tezos_messages::p2p::encoding::operation::Operation;
 use tezos_messages::p2p::encoding::operations_for_blocks::OperationsForBlocksMessage;
 
 use crate::current_head::ProtocolConstants;
+use crate::peers::address_book::PeerAddressBookSnapshot;
 use crate::request::RequestId;
 use crate::storage::kv_cycle_meta::CycleKey;
 use crate::{Action, ActionId, ActionWithMeta, State};
@@ -117,6 +118,16 @@ pub enum StorageRequestPayload {
         block_result: Arc<ApplyBlockResponse>,
         block_metadata: Arc<Meta>,
     },
+
+    /// Prune history below the level according to the history mode.
+    HistoryPrune {
+        chain_id: Arc<ChainId>,
+        history_mode: HistoryMode,
+        below_level: Level,
+    },
+
+    PeerAddressBookGet(ChainId),
+    PeerAddressBookPut(ChainId, PeerAddressBookSnapshot),
 }
 
 impl StorageRequestPayload {
@@ -183,6 +194,14 @@ pub enum StorageResponseSuccess {
         apply_block_req: Arc<ApplyBlockRequest>,
     },
     StoreApplyBlockResultSuccess(Arc<BlockAdditionalData>),
+
+    HistoryPruneSuccess {
+        below_level: Level,
+        caboose_level: Level,
+    },
+
+    PeerAddressBookGetSuccess(Option<PeerAddressBookSnapshot>),
+    PeerAddressBookPutSuccess(()),
 }
 
 #[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
@@ -209,6 +228,11 @@ pub enum StorageResponseError {
 
     PrepareApplyBlockDataError(StorageError),
     StoreApplyBlockResultError(StorageError),
+
+    HistoryPruneError(Level, StorageError),
+
+    PeerAddressBookGetError(StorageError),
+    PeerAddressBookPutError(StorageError),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -340,6 +364,7 @@ impl StorageServiceDefault {
         let constants_storage = ConstantsStorage::new(&storage);
         let cycle_meta_storage = CycleMetaStorage::new(&storage);
         let cycle_eras_storage = CycleErasStorage::new(&storage);
+        let peer_address_book_storage = PeerAddressBookStorage::new(&storage);
 
         // let mut last_time_meta_saved = Instant::now();
 
@@ -552,6 +577,45 @@ impl StorageServiceDefault {
                         Err(err) => Err(StoreApplyBlockResultError(err.into())),
                     }
                 }
+                HistoryPrune {
+                    chain_id,
+                    history_mode,
+                    below_level,
+                } => {
+                    match storage::prune_history(
+                        &storage,
+                        &chain_id,
+                        &history_mode,
+                        below_level,
+                        storage::history_mode::PRUNE_BATCH_SIZE,
+                    ) {
+                        Ok(progress) => {
+                            slog::debug!(&log, "Pruned chain history";
+                                "history_mode" => history_mode.to_string(),
+                                "below_level" => below_level,
+                                "caboose_level" => progress.caboose_level,
+                                "pruned_blocks" => progress.pruned);
+                            if progress.is_done(below_level) {
+                                slog::info!(&log, "Chain history pruned";
+                                    "history_mode" => history_mode.to_string(),
+                                    "below_level" => below_level);
+                            }
+                            Ok(HistoryPruneSuccess {
+                                below_level,
+                                caboose_level: progress.caboose_level,
+                            })
+                        }
+                        Err(err) => Err(HistoryPruneError(below_level, err.into())),
+                    }
+                }
+                PeerAddressBookGet(chain_id) => peer_address_book_storage
+                    .get(&chain_id)
+                    .map(PeerAddressBookGetSuccess)
+                    .map_err(|err| PeerAddressBookGetError(err.into())),
+                PeerAddressBookPut(chain_id, snapshot) => peer_address_book_storage
+                    .put(&chain_id, &snapshot)
+                    .map(PeerAddressBookPutSuccess)
+                    .map_err(|err| PeerAddressBookPutError(err.into())),
             };
 
             if req.subscribe {
@@ -0,0 +1,17 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Pruning of the chain history on cycle boundaries, according to the
+//! configured [storage::HistoryMode].
+
+mod storage_history_prune_state;
+pub use storage_history_prune_state::*;
+
+mod storage_history_prune_actions;
+pub use storage_history_prune_actions::*;
+
+mod storage_history_prune_reducer;
+pub use storage_history_prune_reducer::*;
+
+mod storage_history_prune_effects;
+pub use storage_history_prune_effects::*;
@@ -0,0 +1,99 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+
+use tezos_messages::p2p::encoding::block_header::Level;
+
+use crate::rights::Cycle;
+use crate::service::storage_service::StorageError;
+use crate::{EnablingCondition, State};
+
+use super::StorageHistoryPruneState;
+
+/// Prune history below `below_level`, triggered by the start of `cycle`.
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StorageHistoryPruneInitAction {
+    pub cycle: Cycle,
+    pub below_level: Level,
+}
+
+impl EnablingCondition<State> for StorageHistoryPruneInitAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        if state.config.history_mode.cycle_offset().is_none() {
+            return false;
+        }
+        match &state.storage.history_prune {
+            StorageHistoryPruneState::Idle => true,
+            // Only one pending pruning at a time.
+            StorageHistoryPruneState::Pending { .. } => false,
+            // Retry on the next cycle.
+            StorageHistoryPruneState::Error { cycle, .. } => self.cycle > *cycle,
+            StorageHistoryPruneState::Success {
+                cycle, below_level, ..
+            } => self.cycle > *cycle && self.below_level > *below_level,
+        }
+    }
+}
+
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StorageHistoryPrunePendingAction {
+    pub cycle: Cycle,
+    pub below_level: Level,
+}
+
+impl EnablingCondition<State> for StorageHistoryPrunePendingAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        !state.storage.history_prune.is_pending()
+    }
+}
+
+/// A batch of blocks was pruned, but some blocks below `below_level` are left.
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StorageHistoryPruneProgressAction {
+    pub below_level: Level,
+    pub caboose_level: Level,
+}
+
+impl EnablingCondition<State> for StorageHistoryPruneProgressAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        matches!(
+            state.storage.history_prune,
+            StorageHistoryPruneState::Pending { below_level, .. } if below_level == self.below_level
+        ) && self.caboose_level < self.below_level
+    }
+}
+
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StorageHistoryPruneErrorAction {
+    pub below_level: Level,
+    pub error: StorageError,
+}
+
+impl EnablingCondition<State> for StorageHistoryPruneErrorAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        matches!(
+            state.storage.history_prune,
+            StorageHistoryPruneState::Pending { below_level, .. } if below_level == self.below_level
+        )
+    }
+}
+
+#[cfg_attr(feature = "fuzzing", derive(fuzzcheck::DefaultMutator))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StorageHistoryPruneSuccessAction {
+    pub below_level: Level,
+}
+
+impl EnablingCondition<State> for StorageHistoryPruneSuccessAction {
+    fn is_enabled(&self, state: &State) -> bool {
+        matches!(
+            state.storage.history_prune,
+            StorageHistoryPruneState::Pending { below_level, .. } if below_level == self.below_level
+        )
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use tezos_messages::p2p::encoding::block_header::Level;
+
+use crate::rights::cycle_eras::rights_cycle_eras_actions::RightsCycleErasGetAction;
+use crate::rights::{get_cycle_first_level, get_level_cycle, Cycle};
+use crate::service::storage_service::{
+    StorageRequestPayload, StorageResponseError, StorageResponseSuccess,
+};
+use crate::storage::request::{
+    StorageRequestCreateAction, StorageRequestErrorAction, StorageRequestSuccessAction,
+    StorageRequestor,
+};
+use crate::{Action, ActionWithMeta, Service, Store};
+
+use super::{
+    StorageHistoryPruneErrorAction, StorageHistoryPruneInitAction,
+    StorageHistoryPrunePendingAction, StorageHistoryPruneProgressAction,
+    StorageHistoryPruneSuccessAction,
+};
+
+pub fn storage_history_prune_effects<S>(store: &mut Store<S>, action: &ActionWithMeta)
+where
+    S: Service,
+{
+    match &action.action {
+        Action::CurrentHeadUpdate(content) => {
+            let offset = match store.state().config.history_mode.cycle_offset() {
+                Some(v) => v,
+                None => return,
+            };
+            let cycle_eras = match store
+                .state()
+                .rights
+                .cycle_eras
+                .get_result(&content.next_protocol)
+            {
+                Some(v) => v,
+                None => {
+                    // Cycle eras are only known for protocols we already needed
+                    // rights for, fetch them so they are ready for next head.
+                    store.dispatch(RightsCycleErasGetAction {
+                        protocol_hash: content.next_protocol.clone(),
+                        block_hash: content.new_head.hash.clone(),
+                        block_header: (*content.new_head.header).clone(),
+                    });
+                    return;
+                }
+            };
+
+            let level = content.new_head.header.level();
+            let (cycle, below_level) =
+                match get_level_cycle(level, cycle_eras).and_then(|(cycle, _)| {
+                    get_cycle_first_level(cycle - offset as Cycle, cycle_eras)
+                        .map(|below_level| (cycle, below_level))
+                }) {
+                    Ok(v) => v,
+                    // Not enough cycles yet.
+                    Err(_) => return,
+                };
+            if below_level <= 0 {
+                return;
+            }
+
+            store.dispatch(StorageHistoryPruneInitAction { cycle, below_level });
+        }
+        Action::StorageHistoryPruneInit(content) => {
+            request_history_prune(store, content.below_level);
+            store.dispatch(StorageHistoryPrunePendingAction {
+                cycle: content.cycle,
+                below_level: content.below_level,
+            });
+        }
+        Action::StorageRequestSuccess(StorageRequestSuccessAction {
+            result:
+                StorageResponseSuccess::HistoryPruneSuccess {
+                    below_level,
+                    caboose_level,
+                },
+            ..
+        }) => {
+            if caboose_level < below_level {
+                store.dispatch(StorageHistoryPruneProgressAction {
+                    below_level: *below_level,
+                    caboose_level: *caboose_level,
+                });
+            } else {
+                store.dispatch(StorageHistoryPruneSuccessAction {
+                    below_level: *below_level,
+                });
+            }
+        }
+        Action::StorageHistoryPruneProgress(content) => {
+            // Continue with the next batch, the storage can serve other
+            // requests in between
+            request_history_prune(store, content.below_level);
+        }
+        Action::StorageRequestError(StorageRequestErrorAction {
+            error: StorageResponseError::HistoryPruneError(below_level, error),
+            ..
+        }) => {
+            slog::warn!(store.state().log, "Failed to prune chain history";
+                "below_level" => below_level,
+                "error" => error.to_string());
+            store.dispatch(StorageHistoryPruneErrorAction {
+                below_level: *below_level,
+                error: error.clone(),
+            });
+        }
+        _ => {}
+    }
+}
+
+fn request_history_prune<S>(store: &mut Store<S>, below_level: Level)
+where
+    S: Service,
+{
+    let chain_id = store.state().config.chain_id.clone();
+    let history_mode = store.state().config.history_mode;
+    store.dispatch(StorageRequestCreateAction {
+        payload: StorageRequestPayload::HistoryPrune {
+            chain_id: chain_id.into(),
+            history_mode,
+            below_level,
+        },
+        requestor: StorageRequestor::None,
+    });
+}
@@ -0,0 +1,48 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use crate::{Action, ActionWithMeta, State};
+
+use super::StorageHistoryPruneState;
+
+pub fn storage_history_prune_reducer(state: &mut State, action: &ActionWithMeta) {
+    let prune_state = &mut state.storage.history_prune;
+    match &action.action {
+        Action::StorageHistoryPrunePending(content) => {
+            *prune_state = StorageHistoryPruneState::Pending {
+                cycle: content.cycle,
+                below_level: content.below_level,
+                caboose_level: None,
+            };
+        }
+        Action::StorageHistoryPruneProgress(content) => {
+            if let StorageHistoryPruneState::Pending { caboose_level, .. } = prune_state {
+                *caboose_level = Some(content.caboose_level);
+            }
+        }
+        Action::StorageHistoryPruneError(content) => {
+            if let StorageHistoryPruneState::Pending {
+                cycle, below_level, ..
+            } = prune_state
+            {
+                *prune_state = StorageHistoryPruneState::Error {
+                    cycle: *cycle,
+                    below_level: *below_level,
+                    error: content.error.clone(),
+                };
+            }
+        }
+        Action::StorageHistoryPruneSuccess(_) => {
+            if let StorageHistoryPruneState::Pending {
+                cycle, below_level, ..
+            } = prune_state
+            {
+                *prune_state = StorageHistoryPruneState::Success {
+                    cycle: *cycle,
+                    below_level: *below_level,
+                };
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,54 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use serde::{Deserialize, Serialize};
+
+use tezos_messages::p2p::encoding::block_header::Level;
+
+use crate::rights::Cycle;
+use crate::service::storage_service::StorageError;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum StorageHistoryPruneState {
+    Idle,
+    Pending {
+        /// Cycle which triggered the pruning.
+        cycle: Cycle,
+        below_level: Level,
+        /// Level of the caboose after the last pruned batch.
+        caboose_level: Option<Level>,
+    },
+    Error {
+        cycle: Cycle,
+        below_level: Level,
+        error: StorageError,
+    },
+    Success {
+        cycle: Cycle,
+        below_level: Level,
+    },
+}
+
+impl StorageHistoryPruneState {
+    pub fn new() -> Self {
+        Self::Idle
+    }
+
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Self::Pending { .. })
+    }
+
+    /// Last cycle for which the pruning was successfully done.
+    pub fn last_pruned_cycle(&self) -> Option<Cycle> {
+        match self {
+            Self::Success { cycle, .. } => Some(*cycle),
+            _ => None,
+        }
+    }
+}
+
+impl Default for StorageHistoryPruneState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
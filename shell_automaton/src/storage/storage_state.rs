@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use crate::request::PendingRequests;
 
 use super::blocks::StorageBlocksState;
+use super::history_prune::StorageHistoryPruneState;
 use super::request::StorageRequestState;
 use super::state_snapshot::StorageStateSnapshotState;
 
@@ -17,6 +18,8 @@ pub struct StorageState {
 
     pub blocks: StorageBlocksState,
 
+    pub history_prune: StorageHistoryPruneState,
+
     pub block_meta: super::kv_block_meta::State,
     pub block_additional_data: super::kv_block_additional_data::State,
     pub block_header: super::kv_block_header::State,
@@ -35,6 +38,8 @@ impl StorageState {
 
             blocks: StorageBlocksState::new(),
 
+            history_prune: StorageHistoryPruneState::new(),
+
             block_meta: Default::default(),
             block_additional_data: Default::default(),
             block_header: Default::default(),
@@ -12,6 +12,7 @@ use tezos_messages::p2p::encoding::block_header::{BlockHeaderBuilder, Level};
 
 pub mod one_real_node_cluster;
 pub mod service;
+pub mod simulator;
 
 pub fn generate_chain(
     genesis_block: BlockHeaderWithHash,
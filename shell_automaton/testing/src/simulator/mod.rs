@@ -0,0 +1,248 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Deterministic simulation of many shell automaton nodes connected by
+//! a virtual network.
+//!
+//! All nodes share a virtual clock which only moves when the simulator
+//! is stepped, and every random choice, of the nodes and of the network,
+//! comes from the simulator seed, so the same seed and node configs
+//! produce the same run.
+//!
+//! Only the p2p layer is simulated for now. The protocol runner is
+//! [crate::service::ProtocolRunnerServiceDummy], which never responds, and
+//! [SimulatedStorage] only answers the requests of the p2p layer, the rest
+//! are left in [SimulatedStorage::requests]. So the nodes connect and
+//! handshake, but they don't apply blocks nor validate operations, so
+//! neither chain bootstrap, mempool gossip nor consensus convergence can be
+//! tested yet. Those need scripted protocol runner and storage responses,
+//! which are left for a separate change.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant, SystemTime};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use shell_automaton::peers::add::multi::PeersAddMultiAction;
+use shell_automaton::peers::init::PeersInitAction;
+use shell_automaton::service::MioService;
+use shell_automaton::{Config, State};
+
+mod network;
+pub use network::*;
+
+mod service;
+pub use service::*;
+
+mod node;
+pub use node::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub usize);
+
+impl NodeId {
+    /// Each node gets its own ip address, derived from its id.
+    pub fn ip(&self) -> IpAddr {
+        let [_, a, b, c] = (self.0 as u32).to_be_bytes();
+        Ipv4Addr::new(10, a, b, c).into()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulatorConfig {
+    pub seed: u64,
+    /// Wall clock time at the start of the simulation.
+    pub initial_time: SystemTime,
+    /// How much the virtual clock advances with each step.
+    pub step: Duration,
+    /// Link used between nodes, unless overridden with
+    /// [Simulator::set_link].
+    pub link: LinkConfig,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            initial_time: SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000),
+            step: Duration::from_millis(10),
+            link: LinkConfig::default(),
+        }
+    }
+}
+
+pub struct Simulator {
+    config: SimulatorConfig,
+    rng: StdRng,
+    start: Instant,
+    now: Instant,
+    network: Network,
+    nodes: Vec<SimulatedNode>,
+}
+
+impl Simulator {
+    pub fn new(config: SimulatorConfig) -> Self {
+        let now = Instant::now();
+        Self {
+            rng: StdRng::seed_from_u64(config.seed),
+            start: now,
+            now,
+            network: Network::new(config.link),
+            nodes: Vec::new(),
+            config,
+        }
+    }
+
+    /// Adds a node listening on [NodeId::ip] with the port from the
+    /// `config`, which starts by connecting to the `bootstrap` nodes.
+    ///
+    /// Identity from the `config` is used as is, as it can't be derived
+    /// from the seed. Reuse the same configs to reproduce a run.
+    pub fn add_node(&mut self, mut config: Config, bootstrap: &[NodeId]) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        let initial_time = self.config.initial_time + (self.now - self.start);
+        config.initial_time = initial_time;
+
+        let mut mio =
+            SimulatedMio::new(id, SocketAddr::new(id.ip(), config.port), u16::MAX as usize);
+        let _ = mio.peer_connection_incoming_listen_start();
+        let randomness = StdRng::seed_from_u64(self.rng.gen());
+        let service = SimulatorService::new(self.now, randomness, mio);
+
+        let mut node = SimulatedNode::new(id, State::new(config), initial_time, service);
+        node.dispatch(PeersInitAction {});
+        node.dispatch(PeersAddMultiAction {
+            addresses: bootstrap
+                .iter()
+                .map(|id| self.node(*id).address())
+                .collect(),
+        });
+        self.nodes.push(node);
+        id
+    }
+
+    /// Panics if node with such id is not found.
+    pub fn node(&self, id: NodeId) -> &SimulatedNode {
+        &self.nodes[id.0]
+    }
+
+    /// Panics if node with such id is not found.
+    pub fn node_mut(&mut self, id: NodeId) -> &mut SimulatedNode {
+        &mut self.nodes[id.0]
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &SimulatedNode> {
+        self.nodes.iter()
+    }
+
+    /// Node listening on the `ip`.
+    pub fn node_by_ip(&self, ip: IpAddr) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .find(|node| node.address().ip() == ip)
+            .map(SimulatedNode::id)
+    }
+
+    /// Time elapsed since the start of the simulation.
+    pub fn elapsed(&self) -> Duration {
+        self.now - self.start
+    }
+
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+
+    pub fn set_link(&mut self, a: NodeId, b: NodeId, link: LinkConfig) {
+        self.network.set_link(a, b, link);
+    }
+
+    /// See [Network::partition].
+    pub fn partition(&mut self, groups: &[Vec<NodeId>]) {
+        self.network.partition(groups, self.now, &mut self.rng);
+    }
+
+    pub fn heal(&mut self) {
+        self.network.heal();
+    }
+
+    /// Advances the clock by [SimulatorConfig::step], delivers packets
+    /// that are due and lets every node make progress.
+    pub fn step(&mut self) {
+        self.now += self.config.step;
+
+        while let Some(packet) = self.network.pop_due(self.now) {
+            self.deliver(packet);
+        }
+
+        for i in 0..self.nodes.len() {
+            let node = &mut self.nodes[i];
+            node.step(self.now);
+            let commands = node.service().mio.take_commands();
+            for command in commands {
+                self.execute(NodeId(i), command);
+            }
+        }
+    }
+
+    pub fn run_for(&mut self, duration: Duration) {
+        let until = self.now + duration;
+        while self.now < until {
+            self.step();
+        }
+    }
+
+    /// Steps until `condition` holds or `timeout` elapses. Returns
+    /// whether the `condition` holds.
+    pub fn run_until<F>(&mut self, timeout: Duration, mut condition: F) -> bool
+    where
+        F: FnMut(&Self) -> bool,
+    {
+        let until = self.now + timeout;
+        while !condition(self) {
+            if self.now >= until {
+                return false;
+            }
+            self.step();
+        }
+        true
+    }
+
+    fn deliver(&mut self, packet: Packet) {
+        let now = self.now;
+        let mio = &mut self.nodes[packet.to.0].service().mio;
+        match packet.kind {
+            PacketKind::Syn { from } => {
+                if mio.deliver_syn(packet.connection, from) {
+                    self.network.accept(packet.connection, now, &mut self.rng);
+                } else {
+                    self.network.reset(packet.connection, now, &mut self.rng);
+                }
+            }
+            PacketKind::SynAck => mio.deliver_syn_ack(packet.connection),
+            PacketKind::Data(bytes) => mio.deliver_data(packet.connection, bytes),
+            PacketKind::Reset => mio.deliver_reset(packet.connection),
+        }
+    }
+
+    fn execute(&mut self, node: NodeId, command: MioCommand) {
+        let now = self.now;
+        let rng = &mut self.rng;
+        match command {
+            MioCommand::Connect {
+                connection,
+                from,
+                to,
+            } => {
+                // If nobody listens on the address, the connection times out.
+                if let Some(to) = self.nodes.iter().position(|n| n.address() == to) {
+                    self.network.connect(connection, from, NodeId(to), now, rng);
+                }
+            }
+            MioCommand::Send { connection, bytes } => {
+                self.network.send(connection, node, bytes, now, rng)
+            }
+            MioCommand::Close { connection } => self.network.close(connection, node, now, rng),
+        }
+    }
+}
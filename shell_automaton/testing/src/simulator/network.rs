@@ -0,0 +1,420 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use super::NodeId;
+
+/// Properties of the link between two nodes.
+#[derive(Debug, Clone, Copy)]
+pub struct LinkConfig {
+    /// Minimum time it takes for a packet to reach the other node.
+    pub latency: Duration,
+    /// Random delay, up to this value, added to the `latency`.
+    pub jitter: Duration,
+    /// Probability, between `0.0` and `1.0`, that a packet is lost.
+    pub drop_rate: f64,
+}
+
+impl Default for LinkConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::from_millis(10),
+            jitter: Duration::ZERO,
+            drop_rate: 0.0,
+        }
+    }
+}
+
+/// Identifier of the simulated tcp connection, unique across the network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConnectionId {
+    /// Node which initiated the connection.
+    pub initiator: NodeId,
+    pub seq: u64,
+}
+
+#[derive(Debug, Clone)]
+pub enum PacketKind {
+    /// Connection request, `from` is the address of the initiator as
+    /// seen by the acceptor.
+    Syn {
+        from: SocketAddr,
+    },
+    /// Connection was accepted.
+    SynAck,
+    Data(Vec<u8>),
+    /// Connection was closed by the other side or was lost.
+    Reset,
+}
+
+#[derive(Debug, Clone)]
+pub struct Packet {
+    pub to: NodeId,
+    pub connection: ConnectionId,
+    pub kind: PacketKind,
+}
+
+#[derive(Debug, Clone)]
+struct Connection {
+    /// Initiator and acceptor.
+    nodes: [NodeId; 2],
+    /// Time of the last scheduled delivery to each of the `nodes`, so
+    /// that the jitter doesn't reorder packets within the connection.
+    last_delivery: [Option<Instant>; 2],
+}
+
+impl Connection {
+    fn side(&self, node: NodeId) -> usize {
+        if self.nodes[0] == node {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+/// Counters of the packets that went through the network.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NetworkStats {
+    pub sent: u64,
+    pub delivered: u64,
+    pub dropped: u64,
+}
+
+/// Virtual network connecting the simulated nodes.
+///
+/// Packets that are lost, or that would cross a partition, reset the
+/// connection they belong to, as a lost tcp segment eventually surfaces
+/// to the application as a broken connection. Connection requests are
+/// lost silently instead, so the initiator has to time out.
+#[derive(Debug, Clone)]
+pub struct Network {
+    default_link: LinkConfig,
+    links: BTreeMap<(NodeId, NodeId), LinkConfig>,
+    /// Group of each partitioned node. Nodes can only reach the nodes
+    /// from the same group. `None` when there is no partition.
+    partition: Option<BTreeMap<NodeId, usize>>,
+    connections: BTreeMap<ConnectionId, Connection>,
+    in_flight: BTreeMap<(Instant, u64), Packet>,
+    next_seq: u64,
+    stats: NetworkStats,
+}
+
+fn link_key(a: NodeId, b: NodeId) -> (NodeId, NodeId) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+impl Network {
+    pub fn new(default_link: LinkConfig) -> Self {
+        Self {
+            default_link,
+            links: BTreeMap::new(),
+            partition: None,
+            connections: BTreeMap::new(),
+            in_flight: BTreeMap::new(),
+            next_seq: 0,
+            stats: NetworkStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> NetworkStats {
+        self.stats
+    }
+
+    pub fn link(&self, a: NodeId, b: NodeId) -> LinkConfig {
+        self.links
+            .get(&link_key(a, b))
+            .copied()
+            .unwrap_or(self.default_link)
+    }
+
+    pub fn set_link(&mut self, a: NodeId, b: NodeId, link: LinkConfig) {
+        self.links.insert(link_key(a, b), link);
+    }
+
+    pub fn is_reachable(&self, a: NodeId, b: NodeId) -> bool {
+        match &self.partition {
+            Some(groups) => groups.get(&a) == groups.get(&b),
+            None => true,
+        }
+    }
+
+    /// Splits the network into `groups`. Nodes missing from `groups`
+    /// end up together in an implicit group of their own.
+    ///
+    /// Connections crossing the partition are reset right away.
+    pub fn partition(&mut self, groups: &[Vec<NodeId>], now: Instant, rng: &mut StdRng) {
+        self.partition = Some(
+            groups
+                .iter()
+                .enumerate()
+                .flat_map(|(group, nodes)| nodes.iter().map(move |node| (*node, group)))
+                .collect(),
+        );
+
+        let crossing = self
+            .connections
+            .iter()
+            .filter(|(_, conn)| !self.is_reachable(conn.nodes[0], conn.nodes[1]))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for connection in crossing {
+            self.reset(connection, now, rng);
+        }
+    }
+
+    /// Removes the partition.
+    pub fn heal(&mut self) {
+        self.partition = None;
+    }
+
+    pub fn connect(
+        &mut self,
+        connection: ConnectionId,
+        from: SocketAddr,
+        to: NodeId,
+        now: Instant,
+        rng: &mut StdRng,
+    ) {
+        let initiator = connection.initiator;
+        if !self.is_reachable(initiator, to) || self.is_lost(initiator, to, rng) {
+            self.stats.dropped += 1;
+            return;
+        }
+        self.connections.insert(
+            connection,
+            Connection {
+                nodes: [initiator, to],
+                last_delivery: [None, None],
+            },
+        );
+        self.schedule(connection, initiator, PacketKind::Syn { from }, now, rng);
+    }
+
+    /// Sent by the acceptor once the connection request is delivered.
+    pub fn accept(&mut self, connection: ConnectionId, now: Instant, rng: &mut StdRng) {
+        if let Some(conn) = self.connections.get(&connection) {
+            let acceptor = conn.nodes[1];
+            self.schedule(connection, acceptor, PacketKind::SynAck, now, rng);
+        }
+    }
+
+    pub fn send(
+        &mut self,
+        connection: ConnectionId,
+        from: NodeId,
+        bytes: Vec<u8>,
+        now: Instant,
+        rng: &mut StdRng,
+    ) {
+        let to = match self.connections.get(&connection) {
+            Some(conn) => conn.nodes[1 - conn.side(from)],
+            None => return,
+        };
+        if !self.is_reachable(from, to) || self.is_lost(from, to, rng) {
+            self.stats.dropped += 1;
+            return self.reset(connection, now, rng);
+        }
+        self.schedule(connection, from, PacketKind::Data(bytes), now, rng);
+    }
+
+    /// Connection was closed by the node `from`.
+    pub fn close(
+        &mut self,
+        connection: ConnectionId,
+        from: NodeId,
+        now: Instant,
+        rng: &mut StdRng,
+    ) {
+        if self.connections.contains_key(&connection) {
+            self.schedule(connection, from, PacketKind::Reset, now, rng);
+            self.connections.remove(&connection);
+        }
+    }
+
+    /// Connection is lost, both sides are notified.
+    pub fn reset(&mut self, connection: ConnectionId, now: Instant, rng: &mut StdRng) {
+        if let Some(conn) = self.connections.get(&connection) {
+            let nodes = conn.nodes;
+            for node in nodes {
+                // Schedules the reset to the other side of the `node`.
+                self.schedule(connection, node, PacketKind::Reset, now, rng);
+            }
+            self.connections.remove(&connection);
+        }
+    }
+
+    /// Pops the next packet due at `now`.
+    pub fn pop_due(&mut self, now: Instant) -> Option<Packet> {
+        let key = *self.in_flight.keys().next()?;
+        if key.0 > now {
+            return None;
+        }
+        self.stats.delivered += 1;
+        self.in_flight.remove(&key)
+    }
+
+    pub fn in_flight_len(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    fn is_lost(&self, a: NodeId, b: NodeId, rng: &mut StdRng) -> bool {
+        let drop_rate = self.link(a, b).drop_rate;
+        drop_rate > 0.0 && rng.gen_bool(drop_rate.min(1.0))
+    }
+
+    fn schedule(
+        &mut self,
+        connection: ConnectionId,
+        from: NodeId,
+        kind: PacketKind,
+        now: Instant,
+        rng: &mut StdRng,
+    ) {
+        let conn = match self.connections.get_mut(&connection) {
+            Some(v) => v,
+            None => return,
+        };
+        let to_side = 1 - conn.side(from);
+        let to = conn.nodes[to_side];
+
+        let link = self
+            .links
+            .get(&link_key(from, to))
+            .copied()
+            .unwrap_or(self.default_link);
+        let jitter = link.jitter.as_nanos() as u64;
+        let jitter = if jitter > 0 {
+            Duration::from_nanos(rng.gen_range(0, jitter + 1))
+        } else {
+            Duration::ZERO
+        };
+        let mut deliver_at = now + link.latency + jitter;
+        if let Some(last) = conn.last_delivery[to_side] {
+            deliver_at = deliver_at.max(last);
+        }
+        conn.last_delivery[to_side] = Some(deliver_at);
+
+        self.stats.sent += 1;
+        self.in_flight.insert(
+            (deliver_at, self.next_seq),
+            Packet {
+                to,
+                connection,
+                kind,
+            },
+        );
+        self.next_seq += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn drain(network: &mut Network, now: Instant) -> Vec<Packet> {
+        std::iter::from_fn(|| network.pop_due(now)).collect()
+    }
+
+    #[test]
+    fn test_jitter_keeps_connection_order() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = Network::new(LinkConfig {
+            latency: Duration::from_millis(5),
+            jitter: Duration::from_millis(50),
+            drop_rate: 0.0,
+        });
+        let now = Instant::now();
+        let connection = ConnectionId {
+            initiator: NodeId(0),
+            seq: 0,
+        };
+
+        network.connect(
+            connection,
+            ([10, 0, 0, 0], 1).into(),
+            NodeId(1),
+            now,
+            &mut rng,
+        );
+        for i in 0..100u8 {
+            network.send(connection, NodeId(0), vec![i], now, &mut rng);
+        }
+        assert!(drain(&mut network, now + Duration::from_millis(4)).is_empty());
+
+        let packets = drain(&mut network, now + Duration::from_secs(1));
+        assert!(matches!(packets[0].kind, PacketKind::Syn { .. }));
+        let bytes = packets[1..]
+            .iter()
+            .flat_map(|packet| match &packet.kind {
+                PacketKind::Data(bytes) => bytes.clone(),
+                kind => panic!("unexpected packet: {:?}", kind),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(bytes, (0..100u8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_partition() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = Network::new(LinkConfig::default());
+        let now = Instant::now();
+        let connection = |initiator, seq| ConnectionId {
+            initiator: NodeId(initiator),
+            seq,
+        };
+
+        network.connect(
+            connection(0, 0),
+            ([10, 0, 0, 0], 1).into(),
+            NodeId(2),
+            now,
+            &mut rng,
+        );
+        network.partition(&[vec![NodeId(0), NodeId(1)]], now, &mut rng);
+        assert!(network.is_reachable(NodeId(0), NodeId(1)));
+        assert!(network.is_reachable(NodeId(2), NodeId(3)));
+        assert!(!network.is_reachable(NodeId(1), NodeId(2)));
+
+        // Connection crossing the partition is reset on both sides.
+        let packets = drain(&mut network, now + Duration::from_secs(1));
+        let resets = packets
+            .iter()
+            .filter(|packet| matches!(packet.kind, PacketKind::Reset))
+            .map(|packet| packet.to)
+            .collect::<Vec<_>>();
+        assert_eq!(resets, vec![NodeId(2), NodeId(0)]);
+
+        network.connect(
+            connection(1, 0),
+            ([10, 0, 0, 1], 1).into(),
+            NodeId(3),
+            now,
+            &mut rng,
+        );
+        assert_eq!(network.in_flight_len(), 0);
+        assert_eq!(network.stats().dropped, 1);
+
+        network.heal();
+        network.connect(
+            connection(1, 1),
+            ([10, 0, 0, 1], 1).into(),
+            NodeId(3),
+            now,
+            &mut rng,
+        );
+        assert_eq!(network.in_flight_len(), 1);
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::net::SocketAddr;
+use std::time::{Instant, SystemTime};
+
+use shell_automaton::event::Event;
+use shell_automaton::paused_loops::PausedLoopsResumeAllAction;
+use shell_automaton::service::MioService;
+use shell_automaton::{
+    check_timeouts, effects, reducer, Action, EnablingCondition, MioTimeoutEvent,
+    MioWaitForEventsAction, State, Store,
+};
+
+use super::service::SimulatorService;
+use super::NodeId;
+
+/// Shell automaton running on top of the [SimulatorService].
+#[derive(Clone)]
+pub struct SimulatedNode {
+    id: NodeId,
+    events: Vec<Event>,
+    store: Store<SimulatorService>,
+}
+
+impl SimulatedNode {
+    pub(super) fn new(
+        id: NodeId,
+        initial_state: State,
+        initial_time: SystemTime,
+        service: SimulatorService,
+    ) -> Self {
+        Self {
+            id,
+            events: Vec::new(),
+            store: Store::new(reducer, effects, service, initial_time, initial_state),
+        }
+    }
+
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.store.service.mio.listen_addr()
+    }
+
+    pub fn state(&self) -> &State {
+        self.store.state()
+    }
+
+    pub fn service(&mut self) -> &mut SimulatorService {
+        self.store.service()
+    }
+
+    pub fn dispatch<T>(&mut self, action: T) -> bool
+    where
+        T: Into<Action> + EnablingCondition<State>,
+    {
+        self.store.dispatch(action)
+    }
+
+    /// Single iteration of the event loop, same as
+    /// `ShellAutomaton::make_progress`, except that it never blocks.
+    pub(super) fn step(&mut self, now: Instant) {
+        self.store.service.time = now;
+        if self.store.service.storage.has_responses() {
+            self.store.service.mio.wakeup();
+        }
+
+        check_timeouts(&mut self.store);
+        self.store.dispatch(MioWaitForEventsAction {});
+        self.store
+            .service
+            .mio
+            .wait_for_events(&mut self.events, None);
+
+        if self.events.is_empty() {
+            self.store.dispatch(MioTimeoutEvent {});
+        }
+        for event in std::mem::take(&mut self.events) {
+            match event {
+                Event::P2pServer(event) => self.store.dispatch(event),
+                Event::P2pPeer(event) => self.store.dispatch(event),
+                Event::Wakeup(event) => self.store.dispatch(event),
+                _ => false,
+            };
+        }
+
+        if !self.store.state().paused_loops.is_empty() {
+            self.store.dispatch(PausedLoopsResumeAllAction {});
+        }
+    }
+}
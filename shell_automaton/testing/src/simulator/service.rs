@@ -0,0 +1,494 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use slab::Slab;
+
+use shell_automaton::event::{Event, P2pPeerEvent, P2pServerEvent, WakeupEvent};
+use shell_automaton::peer::PeerToken;
+use shell_automaton::service::mio_service::{
+    MioPeer, MioPeerRefMut, PeerConnectionIncomingAcceptError,
+};
+use shell_automaton::service::service_channel::{RequestSendError, ResponseTryRecvError};
+use shell_automaton::service::storage_service::{
+    StorageError, StorageRequestPayload, StorageResponseSuccess,
+};
+use shell_automaton::service::MioService;
+use storage::StorageInitInfo;
+use tezos_api::ffi::CommitGenesisResult;
+
+use crate::service::{
+    ActorsServiceDummy, BakerServiceDummy, DnsServiceMocked, ProtocolRunnerServiceDummy,
+    RpcServiceDummy, Service, StorageRequest, StorageResponse, StorageService, TimeService,
+};
+
+use super::network::ConnectionId;
+use super::NodeId;
+
+/// Ports assigned to the outgoing connections start from here.
+const EPHEMERAL_PORT_START: u16 = 49152;
+
+/// Request of the node to the virtual network.
+#[derive(Debug, Clone)]
+pub enum MioCommand {
+    Connect {
+        connection: ConnectionId,
+        from: SocketAddr,
+        to: SocketAddr,
+    },
+    Send {
+        connection: ConnectionId,
+        bytes: Vec<u8>,
+    },
+    Close {
+        connection: ConnectionId,
+    },
+}
+
+/// Simulated peer tcp stream.
+///
+/// Reading with nothing received and writing before the connection is
+/// established both fail with [io::ErrorKind::WouldBlock].
+#[derive(Debug, Clone)]
+pub struct SimulatedStream {
+    connection: ConnectionId,
+    /// Whether mio events are reported for this stream. Incoming
+    /// connections are registered once accepted.
+    registered: bool,
+    connected: bool,
+    closed: bool,
+    received: VecDeque<u8>,
+    written: Vec<u8>,
+}
+
+impl SimulatedStream {
+    fn new(connection: ConnectionId, registered: bool, connected: bool) -> Self {
+        Self {
+            connection,
+            registered,
+            connected,
+            closed: false,
+            received: VecDeque::new(),
+            written: Vec::new(),
+        }
+    }
+
+    pub fn connection(&self) -> ConnectionId {
+        self.connection
+    }
+}
+
+impl Read for SimulatedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.received.is_empty() {
+            return Err(match self.closed {
+                true => io::ErrorKind::ConnectionReset.into(),
+                false => io::ErrorKind::WouldBlock.into(),
+            });
+        }
+        let len = buf.len().min(self.received.len());
+        for (dst, src) in buf.iter_mut().zip(self.received.drain(..len)) {
+            *dst = src;
+        }
+        Ok(len)
+    }
+}
+
+impl Write for SimulatedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        if !self.connected {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// [MioService] backed by the virtual network.
+///
+/// Network operations are queued as [MioCommand]s for the simulator and
+/// packets delivered by the simulator are turned into mio events.
+#[derive(Clone)]
+pub struct SimulatedMio {
+    node: NodeId,
+    listen_addr: SocketAddr,
+    listening: bool,
+    buffer: Vec<u8>,
+    next_port: u16,
+    next_connection: u64,
+    peers: Slab<MioPeer<SimulatedStream>>,
+    tokens: BTreeMap<ConnectionId, usize>,
+    /// Incoming connections waiting to be accepted.
+    backlog: VecDeque<usize>,
+    events: Vec<Event>,
+    commands: Vec<MioCommand>,
+}
+
+impl SimulatedMio {
+    pub fn new(node: NodeId, listen_addr: SocketAddr, buffer_size: usize) -> Self {
+        Self {
+            node,
+            listen_addr,
+            listening: false,
+            buffer: vec![0; buffer_size],
+            next_port: EPHEMERAL_PORT_START,
+            next_connection: 0,
+            peers: Slab::new(),
+            tokens: BTreeMap::new(),
+            backlog: VecDeque::new(),
+            events: Vec::new(),
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn listen_addr(&self) -> SocketAddr {
+        self.listen_addr
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.listening
+    }
+
+    pub fn wakeup(&mut self) {
+        self.events.push(WakeupEvent {}.into());
+    }
+
+    pub fn has_events(&self) -> bool {
+        !self.events.is_empty()
+    }
+
+    /// Takes commands issued since the last call, including the data
+    /// written to the streams.
+    pub fn take_commands(&mut self) -> Vec<MioCommand> {
+        let mut commands = std::mem::take(&mut self.commands);
+        for (_, peer) in self.peers.iter_mut() {
+            if !peer.stream.written.is_empty() {
+                commands.push(MioCommand::Send {
+                    connection: peer.stream.connection,
+                    bytes: std::mem::take(&mut peer.stream.written),
+                });
+            }
+        }
+        commands
+    }
+
+    /// Incoming connection request. Returns `false` if it was refused.
+    pub fn deliver_syn(&mut self, connection: ConnectionId, from: SocketAddr) -> bool {
+        if !self.listening {
+            return false;
+        }
+        let token = self.peers.insert(MioPeer::new(
+            from,
+            SimulatedStream::new(connection, false, true),
+        ));
+        self.tokens.insert(connection, token);
+        self.backlog.push_back(token);
+        self.events.push(P2pServerEvent {}.into());
+        true
+    }
+
+    pub fn deliver_syn_ack(&mut self, connection: ConnectionId) {
+        if let Some(token) = self.tokens.get(&connection).copied() {
+            self.peers[token].stream.connected = true;
+            self.push_peer_event(token, false, true, false);
+        }
+    }
+
+    pub fn deliver_data(&mut self, connection: ConnectionId, bytes: Vec<u8>) {
+        if let Some(token) = self.tokens.get(&connection).copied() {
+            self.peers[token].stream.received.extend(bytes);
+            self.push_peer_event(token, true, false, false);
+        }
+    }
+
+    pub fn deliver_reset(&mut self, connection: ConnectionId) {
+        if let Some(token) = self.tokens.get(&connection).copied() {
+            self.peers[token].stream.closed = true;
+            self.push_peer_event(token, false, false, true);
+        }
+    }
+
+    fn push_peer_event(&mut self, token: usize, readable: bool, writable: bool, closed: bool) {
+        let peer = &self.peers[token];
+        if !peer.stream.registered {
+            return;
+        }
+        self.events.push(
+            P2pPeerEvent {
+                token: PeerToken::new_unchecked(token),
+                address: peer.address,
+                is_readable: readable,
+                is_writable: writable,
+                is_closed: closed,
+            }
+            .into(),
+        );
+    }
+}
+
+impl MioService for SimulatedMio {
+    type PeerStream = SimulatedStream;
+    type Events = Vec<Event>;
+    type InternalEvent = Event;
+
+    fn wait_for_events(&mut self, events: &mut Self::Events, _: Option<Duration>) {
+        events.clear();
+        events.append(&mut self.events);
+    }
+
+    fn transform_event(&mut self, event: &Self::InternalEvent) -> Event {
+        event.clone()
+    }
+
+    fn peer_connection_incoming_listen_start(&mut self) -> io::Result<()> {
+        self.listening = true;
+        Ok(())
+    }
+
+    fn peer_connection_incoming_listen_stop(&mut self) {
+        self.listening = false;
+    }
+
+    fn peer_connection_incoming_accept(
+        &mut self,
+    ) -> Result<(PeerToken, MioPeerRefMut<Self::PeerStream>), PeerConnectionIncomingAcceptError>
+    {
+        if !self.listening {
+            return Err(PeerConnectionIncomingAcceptError::ServerNotListening);
+        }
+        let token = self
+            .backlog
+            .pop_front()
+            .ok_or(PeerConnectionIncomingAcceptError::WouldBlock)?;
+
+        // Like mio, report the current readiness once registered.
+        self.peers[token].stream.registered = true;
+        let stream = &self.peers[token].stream;
+        let (readable, closed) = (!stream.received.is_empty(), stream.closed);
+        self.push_peer_event(token, readable, true, closed);
+
+        let peer = &mut self.peers[token];
+        Ok((
+            PeerToken::new_unchecked(token),
+            MioPeerRefMut::new(&mut self.buffer, peer.address, &mut peer.stream),
+        ))
+    }
+
+    fn peer_connection_init(&mut self, address: SocketAddr) -> io::Result<PeerToken> {
+        let connection = ConnectionId {
+            initiator: self.node,
+            seq: self.next_connection,
+        };
+        self.next_connection += 1;
+        let from = SocketAddr::new(self.listen_addr.ip(), self.next_port);
+        self.next_port = self
+            .next_port
+            .checked_add(1)
+            .unwrap_or(EPHEMERAL_PORT_START);
+
+        let token = self.peers.insert(MioPeer::new(
+            address,
+            SimulatedStream::new(connection, true, false),
+        ));
+        self.tokens.insert(connection, token);
+        self.commands.push(MioCommand::Connect {
+            connection,
+            from,
+            to: address,
+        });
+        Ok(PeerToken::new_unchecked(token))
+    }
+
+    fn peer_disconnect(&mut self, token: PeerToken) {
+        let index = token.index();
+        if !self.peers.contains(index) {
+            return;
+        }
+        let mut stream = self.peers.remove(index).stream;
+        self.tokens.remove(&stream.connection);
+        self.backlog.retain(|token| *token != index);
+
+        if stream.closed {
+            return;
+        }
+        if !stream.written.is_empty() {
+            self.commands.push(MioCommand::Send {
+                connection: stream.connection,
+                bytes: std::mem::take(&mut stream.written),
+            });
+        }
+        self.commands.push(MioCommand::Close {
+            connection: stream.connection,
+        });
+    }
+
+    fn peer_get(&mut self, token: PeerToken) -> Option<MioPeerRefMut<Self::PeerStream>> {
+        let buffer = &mut self.buffer;
+        self.peers
+            .get_mut(token.index())
+            .map(|peer| MioPeerRefMut::new(buffer, peer.address, &mut peer.stream))
+    }
+}
+
+/// Storage which answers the requests needed by the p2p layer right
+/// away. Other requests are kept in `requests` for the test to answer
+/// with [SimulatedStorage::respond].
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedStorage {
+    pub requests: VecDeque<StorageRequest>,
+    responses: VecDeque<StorageResponse>,
+}
+
+impl SimulatedStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn respond(&mut self, response: StorageResponse) {
+        self.responses.push_back(response);
+    }
+
+    pub fn has_responses(&self) -> bool {
+        !self.responses.is_empty()
+    }
+}
+
+impl StorageService for SimulatedStorage {
+    fn request_send(
+        &mut self,
+        req: StorageRequest,
+    ) -> Result<(), RequestSendError<StorageRequest>> {
+        let result = match &req.payload {
+            StorageRequestPayload::StateSnapshotPut(state) => {
+                StorageResponseSuccess::StateSnapshotPutSuccess(state.last_action.id())
+            }
+            StorageRequestPayload::ActionPut(action) => {
+                StorageResponseSuccess::ActionPutSuccess(action.id)
+            }
+            StorageRequestPayload::PeerAddressBookGet(_) => {
+                StorageResponseSuccess::PeerAddressBookGetSuccess(None)
+            }
+            StorageRequestPayload::PeerAddressBookPut(..) => {
+                StorageResponseSuccess::PeerAddressBookPutSuccess(())
+            }
+            _ => {
+                self.requests.push_back(req);
+                return Ok(());
+            }
+        };
+        if req.subscribe {
+            self.respond(StorageResponse::new(req.id, Ok(result)));
+        }
+        Ok(())
+    }
+
+    fn response_try_recv(&mut self) -> Result<StorageResponse, ResponseTryRecvError> {
+        self.responses
+            .pop_front()
+            .ok_or(ResponseTryRecvError::Empty)
+    }
+
+    fn blocks_genesis_commit_result_put(
+        &mut self,
+        _: &StorageInitInfo,
+        _: CommitGenesisResult,
+    ) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+/// Service of a simulated node. Time is driven by the simulator and
+/// randomness is seeded from the simulator seed.
+#[derive(Clone)]
+pub struct SimulatorService {
+    pub(super) time: Instant,
+    pub randomness: StdRng,
+    pub dns: DnsServiceMocked,
+    pub mio: SimulatedMio,
+    pub protocol_runner: ProtocolRunnerServiceDummy,
+    pub storage: SimulatedStorage,
+    pub rpc: RpcServiceDummy,
+    pub actors: ActorsServiceDummy,
+    pub baker: BakerServiceDummy,
+}
+
+impl SimulatorService {
+    pub fn new(time: Instant, randomness: StdRng, mio: SimulatedMio) -> Self {
+        Self {
+            time,
+            randomness,
+            dns: DnsServiceMocked::Constant(Ok(vec![])),
+            mio,
+            protocol_runner: ProtocolRunnerServiceDummy::new(),
+            storage: SimulatedStorage::new(),
+            rpc: RpcServiceDummy::new(),
+            actors: ActorsServiceDummy::new(),
+            baker: BakerServiceDummy::default(),
+        }
+    }
+}
+
+impl TimeService for SimulatorService {
+    fn monotonic_time(&mut self) -> Instant {
+        self.time
+    }
+}
+
+impl Service for SimulatorService {
+    type Randomness = StdRng;
+    type Dns = DnsServiceMocked;
+    type Mio = SimulatedMio;
+    type ProtocolRunner = ProtocolRunnerServiceDummy;
+    type Storage = SimulatedStorage;
+    type Rpc = RpcServiceDummy;
+    type Actors = ActorsServiceDummy;
+    type Baker = BakerServiceDummy;
+
+    fn randomness(&mut self) -> &mut Self::Randomness {
+        &mut self.randomness
+    }
+
+    fn dns(&mut self) -> &mut Self::Dns {
+        &mut self.dns
+    }
+
+    fn mio(&mut self) -> &mut Self::Mio {
+        &mut self.mio
+    }
+
+    fn protocol_runner(&mut self) -> &mut Self::ProtocolRunner {
+        &mut self.protocol_runner
+    }
+
+    fn storage(&mut self) -> &mut Self::Storage {
+        &mut self.storage
+    }
+
+    fn rpc(&mut self) -> &mut Self::Rpc {
+        &mut self.rpc
+    }
+
+    fn actors(&mut self) -> &mut Self::Actors {
+        &mut self.actors
+    }
+
+    fn prevalidator(&mut self) -> &mut Self::ProtocolRunner {
+        self.protocol_runner()
+    }
+
+    fn baker(&mut self) -> &mut Self::Baker {
+        &mut self.baker
+    }
+}
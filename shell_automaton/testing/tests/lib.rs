@@ -2,3 +2,4 @@ pub mod bootstrap;
 pub mod p2p_requests;
 
 pub mod test_handshaking_basic;
+pub mod test_simulator;
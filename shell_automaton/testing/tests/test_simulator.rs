@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use shell_automaton::config::default_test_config;
+use shell_automaton::Config;
+use shell_automaton_testing::simulator::{LinkConfig, NodeId, Simulator, SimulatorConfig};
+use tezos_identity::Identity;
+
+fn configs(count: usize) -> Vec<Config> {
+    (0..count)
+        .map(|_| Config {
+            identity: Identity::generate(0.0).unwrap(),
+            ..default_test_config()
+        })
+        .collect()
+}
+
+/// Every node but the first one bootstraps from the first one.
+fn bootstrap_from_first(config: SimulatorConfig, node_configs: &[Config]) -> Simulator {
+    let mut simulator = Simulator::new(config);
+    for (i, node_config) in node_configs.iter().enumerate() {
+        let bootstrap = if i == 0 { vec![] } else { vec![NodeId(0)] };
+        simulator.add_node(node_config.clone(), &bootstrap);
+    }
+    simulator
+}
+
+fn all_handshaked(simulator: &Simulator) -> bool {
+    simulator
+        .nodes()
+        .all(|node| node.state().peers.handshaked_len() > 0)
+}
+
+#[test]
+fn test_simulator_handshake() {
+    let mut simulator = bootstrap_from_first(
+        SimulatorConfig {
+            link: LinkConfig {
+                latency: Duration::from_millis(20),
+                jitter: Duration::from_millis(30),
+                drop_rate: 0.0,
+            },
+            ..Default::default()
+        },
+        &configs(24),
+    );
+
+    assert!(simulator.run_until(Duration::from_secs(60), all_handshaked));
+}
+
+#[test]
+fn test_simulator_partition() {
+    // Even nodes in one group, odd ones in the other.
+    let groups = vec![
+        (0..8).step_by(2).map(NodeId).collect::<Vec<_>>(),
+        (1..8).step_by(2).map(NodeId).collect::<Vec<_>>(),
+    ];
+    let mut simulator = Simulator::new(SimulatorConfig::default());
+    simulator.partition(&groups);
+    for (i, node_config) in configs(8).into_iter().enumerate() {
+        // Each group has its own bootstrap node, but everyone knows both.
+        let bootstrap = if i < 2 {
+            vec![]
+        } else {
+            vec![NodeId(0), NodeId(1)]
+        };
+        simulator.add_node(node_config, &bootstrap);
+    }
+
+    assert!(simulator.run_until(Duration::from_secs(60), |simulator| {
+        simulator
+            .nodes()
+            .skip(2)
+            .all(|node| node.state().peers.handshaked_len() > 0)
+    }));
+    simulator.run_for(Duration::from_secs(10));
+
+    let group_of = |node: NodeId| groups.iter().position(|group| group.contains(&node));
+    for node in simulator.nodes() {
+        for (address, _) in node.state().peers.handshaked_iter() {
+            let peer = simulator.node_by_ip(address.ip()).unwrap();
+            assert_eq!(group_of(node.id()), group_of(peer));
+        }
+    }
+}
+
+#[test]
+fn test_simulator_is_deterministic() {
+    let config = SimulatorConfig {
+        seed: 42,
+        link: LinkConfig {
+            latency: Duration::from_millis(5),
+            jitter: Duration::from_millis(20),
+            drop_rate: 0.01,
+        },
+        ..Default::default()
+    };
+    let node_configs = configs(12);
+
+    let run = || {
+        let mut simulator = bootstrap_from_first(config.clone(), &node_configs);
+        simulator.run_for(Duration::from_secs(20));
+        simulator
+            .nodes()
+            .map(|node| serde_json::to_value(node.state()).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(run(), run());
+}
@@ -11,7 +11,9 @@ use crypto::hash::{BlockHash, ContextHash};
 use tezos_messages::p2p::encoding::block_header::Level;
 
 use crate::commit_log::{CommitLogWithSchema, Location};
-use crate::database::tezedge_database::{KVStoreKeyValueSchema, TezedgeDatabaseWithIterator};
+use crate::database::tezedge_database::{
+    KVStoreBatch, KVStoreKeyValueSchema, TezedgeDatabaseWithIterator, WriteBatch,
+};
 use crate::persistent::database::RocksDbKeyValueSchema;
 use crate::persistent::{BincodeEncoded, CommitLogSchema, KeyValueSchema};
 use crate::{BlockHeaderWithHash, Direction, IteratorMode, PersistentStorage, StorageError};
@@ -164,6 +166,22 @@ impl BlockStorage {
         &self,
         block_hash: &BlockHash,
         json_data: BlockJsonData,
+    ) -> Result<(), StorageError> {
+        let mut batch = WriteBatch::new();
+        self.put_block_json_data_batched(&mut batch, block_hash, json_data)?;
+        self.primary_index
+            .kv
+            .commit_batch(batch)
+            .map_err(StorageError::from)
+    }
+
+    /// Same as [BlockStorage::put_block_json_data], but the indexes are only
+    /// updated once the `batch` is committed.
+    pub fn put_block_json_data_batched(
+        &self,
+        batch: &mut WriteBatch,
+        block_hash: &BlockHash,
+        json_data: BlockJsonData,
     ) -> Result<(), StorageError> {
         let updated_column_location = {
             let block_json_data_location = self
@@ -180,12 +198,65 @@ impl BlockStorage {
         };
         let block_header = self.get_block_header_by_location(&updated_column_location)?;
         // update indexes
+        batch.put::<BlockPrimaryIndex>(&block_header.hash, &updated_column_location)?;
+        batch.put::<BlockByLevelIndex>(&block_header.header.level(), &updated_column_location)?;
+        Ok(())
+    }
+
+    /// Removes the block from the hash and level indexes, and releases the disk
+    /// space of its header and json data in the commit log.
+    pub fn delete_block(
+        &self,
+        block_hash: &BlockHash,
+        level: BlockLevel,
+    ) -> Result<(), StorageError> {
+        let location = match self.primary_index.get(block_hash)? {
+            Some(location) => location,
+            None => return Ok(()),
+        };
+
         self.primary_index
-            .put(&block_header.hash, &updated_column_location)
-            .and(
-                self.by_level_index
-                    .put(block_header.header.level(), &updated_column_location),
-            )
+            .delete(block_hash)
+            .and(self.by_level_index.delete(level))?;
+
+        self.clog.discard(&location.block_header)?;
+        if let Some(block_json_data) = location.block_json_data.as_ref() {
+            self.clog.discard(block_json_data)?;
+        }
+        Ok(())
+    }
+
+    /// Drops the block json data (block and operations metadata), keeping the
+    /// block header available. Its disk space in the commit log is released.
+    pub fn delete_block_json_data(&self, block_hash: &BlockHash) -> Result<(), StorageError> {
+        let mut location = match self.primary_index.get(block_hash)? {
+            Some(location) if location.block_json_data.is_some() => location,
+            _ => return Ok(()),
+        };
+        let block_json_data = location.block_json_data.take();
+        let block_header = self.get_block_header_by_location(&location)?;
+        self.primary_index.put(block_hash, &location).and(
+            self.by_level_index
+                .put(block_header.header.level(), &location),
+        )?;
+
+        if let Some(block_json_data) = block_json_data.as_ref() {
+            self.clog.discard(block_json_data)?;
+        }
+        Ok(())
+    }
+
+    /// Blocks stored from `from_level` (included) upwards, at most `limit` of them.
+    pub fn get_blocks_from_level(
+        &self,
+        from_level: BlockLevel,
+        limit: usize,
+    ) -> Result<Vec<BlockHeaderWithHash>, StorageError> {
+        self.by_level_index
+            .get_blocks_directed(from_level, limit, Direction::Forward)?
+            .into_iter()
+            .map(|location| self.get_block_header_by_location(&location))
+            .collect()
     }
 
     #[inline]
@@ -453,6 +524,11 @@ impl BlockPrimaryIndex {
         self.kv.contains(block_hash).map_err(StorageError::from)
     }
 
+    #[inline]
+    fn delete(&self, block_hash: &BlockHash) -> Result<(), StorageError> {
+        self.kv.delete(block_hash).map_err(StorageError::from)
+    }
+
     #[inline]
     fn iterator(&self) -> Result<Vec<BlockHash>, StorageError> {
         use crate::persistent::codec::Decoder;
@@ -507,6 +583,10 @@ impl BlockByLevelIndex {
         self.kv.put(&level, location).map_err(StorageError::from)
     }
 
+    fn delete(&self, level: BlockLevel) -> Result<(), StorageError> {
+        self.kv.delete(&level).map_err(StorageError::from)
+    }
+
     fn get_blocks(
         &self,
         from_level: BlockLevel,
@@ -93,6 +93,42 @@ impl CommitLog {
         };
     }
 
+    /// Releases the disk space used by `buf_size` bytes at `offset`, the file keeps
+    /// its size so that other locations stay valid, reading them back returns zeros.
+    ///
+    /// Only supported on Linux, elsewhere the data stays on disk.
+    #[cfg(target_os = "linux")]
+    pub fn discard(&mut self, offset: u64, buf_size: usize) -> Result<(), CommitLogError> {
+        use std::os::unix::io::AsRawFd;
+
+        if buf_size == 0 {
+            return Ok(());
+        }
+
+        let result = unsafe {
+            libc::fallocate(
+                self.data_file.as_raw_fd(),
+                libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+                offset as libc::off_t,
+                buf_size as libc::off_t,
+            )
+        };
+
+        match result {
+            0 => Ok(()),
+            _ => match io::Error::last_os_error() {
+                // The filesystem can't punch holes
+                e if e.raw_os_error() == Some(libc::EOPNOTSUPP) => Ok(()),
+                e => Err(e.into()),
+            },
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn discard(&mut self, _offset: u64, _buf_size: usize) -> Result<(), CommitLogError> {
+        Ok(())
+    }
+
     /// Flushes data to disc
     pub fn sync(&mut self) -> Result<(), CommitLogError> {
         self.data_file.sync_data()?;
@@ -174,6 +210,9 @@ pub trait CommitLogWithSchema<S: CommitLogSchema> {
     /// Retrieve a stored record.
     fn get(&self, location: &Location) -> Result<S::Value, CommitLogError>;
 
+    /// Release the disk space of a record which is not referenced anymore.
+    fn discard(&self, location: &Location) -> Result<(), CommitLogError>;
+
     /// Flush to disk.
     fn sync(&self) -> Result<(), CommitLogError>;
 }
@@ -204,6 +243,17 @@ impl<S: CommitLogSchema> CommitLogWithSchema<S> for CommitLogs {
         Ok(value)
     }
 
+    fn discard(&self, location: &Location) -> Result<(), CommitLogError> {
+        let cl = self
+            .cl_handle(S::name())?
+            .ok_or(CommitLogError::MissingCommitLog { name: S::name() })?;
+        let mut cl = cl.write().map_err(|e| CommitLogError::RwLockPoisonError {
+            error: e.to_string(),
+        })?;
+
+        cl.discard(location.0, location.1)
+    }
+
     fn sync(&self) -> Result<(), CommitLogError> {
         let cl = self
             .cl_handle(S::name())?
@@ -9,7 +9,9 @@ use serde::{Deserialize, Serialize};
 
 use tezos_api::ffi::CycleRollsOwnerSnapshot;
 
-use crate::database::tezedge_database::{KVStoreKeyValueSchema, TezedgeDatabaseWithIterator};
+use crate::database::tezedge_database::{
+    KVStoreKeyValueSchema, TezedgeDatabaseWithIterator, WriteBatch,
+};
 use crate::persistent::database::{default_table_options, RocksDbKeyValueSchema};
 use crate::persistent::{BincodeEncoded, KeyValueSchema};
 use crate::{IteratorMode, PersistentStorage, StorageError};
@@ -73,6 +75,17 @@ impl CycleMetaStorage {
         Ok(())
     }
 
+    pub fn store_cycle_data_batched(
+        &self,
+        batch: &mut WriteBatch,
+        ffi_cycle_data: CycleRollsOwnerSnapshot,
+    ) -> Result<(), StorageError> {
+        let cycle = ffi_cycle_data.cycle;
+        batch
+            .put::<Self>(&cycle, &ffi_cycle_data.into())
+            .map_err(StorageError::from)
+    }
+
     #[inline]
     pub fn put(&self, key: &CycleKey, data: &CycleData) -> Result<(), StorageError> {
         self.kv.put(key, data).map_err(StorageError::from)
@@ -5,6 +5,7 @@ use crate::database::error::Error;
 use crate::Direction;
 use serde::{Deserialize, Serialize, Serializer};
 use std::collections::HashMap;
+use std::path::Path;
 use std::time::Duration;
 
 pub type BoxedSliceKV = (Box<[u8]>, Box<[u8]>);
@@ -17,7 +18,86 @@ pub enum BackendIteratorMode {
     From(Vec<u8>, Direction),
 }
 
-#[derive(Default, Clone, Serialize, Deserialize)]
+/// Single write of a batch committed with
+/// [TezedgeDatabaseBackendStore::commit_batch].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BatchOperation {
+    Put {
+        column: &'static str,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Merge {
+        column: &'static str,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Delete {
+        column: &'static str,
+        key: Vec<u8>,
+    },
+}
+
+impl BatchOperation {
+    pub fn column(&self) -> &'static str {
+        match self {
+            Self::Put { column, .. } | Self::Merge { column, .. } | Self::Delete { column, .. } => {
+                column
+            }
+        }
+    }
+}
+
+/// Cumulative histogram of durations, it is never truncated, unlike
+/// the per-block statistics, so it can be exported as monotonic counters.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DurationHistogram {
+    /// Pairs of the bucket upper bound and the number of observations
+    /// less or equal to it, both in nanoseconds.
+    pub buckets: Vec<(u64, u64)>,
+    /// Sum of all observed durations in nanoseconds.
+    pub sum: u64,
+    /// Number of all observations.
+    pub count: u64,
+}
+
+impl DurationHistogram {
+    pub fn new(bounds: &[u64]) -> Self {
+        Self {
+            buckets: bounds.iter().map(|bound| (*bound, 0)).collect(),
+            sum: 0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, duration: u64) {
+        self.buckets
+            .iter_mut()
+            .filter(|(bound, _)| duration <= *bound)
+            .for_each(|(_, count)| *count += 1);
+        self.sum = self.sum.saturating_add(duration);
+        self.count += 1;
+    }
+}
+
+/// Upper bounds of the buckets of the main database operation durations,
+/// in nanoseconds.
+const DB_OPERATION_DURATION_BUCKETS: [u64; 12] = [
+    10_000,
+    25_000,
+    50_000,
+    100_000,
+    250_000,
+    500_000,
+    1_000_000,
+    2_500_000,
+    5_000_000,
+    10_000_000,
+    50_000_000,
+    250_000_000,
+];
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DBStats {
     pub total_reads: u64,
     #[serde(serialize_with = "to_u128")]
@@ -28,6 +108,63 @@ pub struct DBStats {
     pub total_updates: u64,
     #[serde(serialize_with = "to_u128")]
     pub total_update_duration: Duration,
+    /// Merges of the data files, for the backends which do them.
+    pub total_merges: u64,
+    #[serde(serialize_with = "to_u128")]
+    pub total_merge_duration: Duration,
+    pub merged_files: u64,
+    pub reclaimed_bytes: u64,
+    /// Data files not written to anymore, and bytes of overwritten or
+    /// deleted entries in them.
+    pub data_files: u64,
+    pub dead_bytes: u64,
+    /// Durations of the operations counted in `total_reads`, `total_writes`
+    /// and `total_updates`.
+    pub read_durations: DurationHistogram,
+    pub write_durations: DurationHistogram,
+    pub update_durations: DurationHistogram,
+}
+
+impl Default for DBStats {
+    fn default() -> Self {
+        Self {
+            total_reads: 0,
+            total_read_duration: Duration::ZERO,
+            total_writes: 0,
+            total_write_duration: Duration::ZERO,
+            total_updates: 0,
+            total_update_duration: Duration::ZERO,
+            total_merges: 0,
+            total_merge_duration: Duration::ZERO,
+            merged_files: 0,
+            reclaimed_bytes: 0,
+            data_files: 0,
+            dead_bytes: 0,
+            read_durations: DurationHistogram::new(&DB_OPERATION_DURATION_BUCKETS),
+            write_durations: DurationHistogram::new(&DB_OPERATION_DURATION_BUCKETS),
+            update_durations: DurationHistogram::new(&DB_OPERATION_DURATION_BUCKETS),
+        }
+    }
+}
+
+impl DBStats {
+    pub fn record_read(&mut self, duration: Duration) {
+        self.total_reads += 1;
+        self.total_read_duration += duration;
+        self.read_durations.observe(duration.as_nanos() as u64);
+    }
+
+    pub fn record_write(&mut self, duration: Duration) {
+        self.total_writes += 1;
+        self.total_write_duration += duration;
+        self.write_durations.observe(duration.as_nanos() as u64);
+    }
+
+    pub fn record_update(&mut self, duration: Duration) {
+        self.total_updates += 1;
+        self.total_update_duration += duration;
+        self.update_durations.observe(duration.as_nanos() as u64);
+    }
 }
 
 fn to_u128<S>(x: &Duration, s: S) -> Result<S::Ok, S::Error>
@@ -49,7 +186,13 @@ pub trait TezedgeDatabaseBackendStore {
         column: &'static str,
         batch: Vec<(Vec<u8>, Vec<u8>)>,
     ) -> Result<(), Error>;
+    /// Applies writes to any number of columns atomically, either all of
+    /// them are persisted or none.
+    fn commit_batch(&self, batch: Vec<BatchOperation>) -> Result<(), Error>;
     fn flush(&self) -> Result<usize, Error>;
+    /// Creates a consistent copy of the whole database in the `path`
+    /// directory, which must not exist yet.
+    fn checkpoint(&self, path: &Path) -> Result<(), Error>;
     fn size(&self) -> HashMap<&'static str, usize>;
     fn sync(&self) -> Result<(), Error>;
     fn find<'a>(
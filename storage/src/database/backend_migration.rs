@@ -0,0 +1,292 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Offline copy of the main database from one backend to another, e.g.
+//! from RocksDB to EdgeKV, without resyncing the node.
+//!
+//! Every column is streamed from the source with [BackendIteratorMode::Start]
+//! and written to the target in batches. Afterwards both databases are
+//! read again and the row count and checksum of every column are compared.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use slog::{info, Logger};
+
+use crate::database::backend::{BackendIteratorMode, TezedgeDatabaseBackendStore};
+use crate::database::error::Error;
+use crate::database::tezedge_database::TezedgeDatabase;
+
+/// Number of rows written to the target database at once.
+pub const DEFAULT_MIGRATION_BATCH_SIZE: usize = 10_000;
+
+/// Number of rows and checksum of a column.
+///
+/// The checksum doesn't depend on the order in which the backend iterates
+/// the column, so it can be compared between different backends.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnSummary {
+    pub rows: u64,
+    pub checksum: u64,
+}
+
+impl ColumnSummary {
+    fn add(&mut self, key: &[u8], value: &[u8]) {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        value.hash(&mut hasher);
+        self.rows += 1;
+        self.checksum = self.checksum.wrapping_add(hasher.finish());
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ColumnMigration {
+    pub column: &'static str,
+    pub source: ColumnSummary,
+    pub target: ColumnSummary,
+}
+
+impl ColumnMigration {
+    pub fn is_consistent(&self) -> bool {
+        self.source == self.target
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MigrationReport {
+    pub columns: Vec<ColumnMigration>,
+    pub duration: Duration,
+}
+
+impl MigrationReport {
+    /// Returns true if every column of the target matches the source.
+    pub fn is_consistent(&self) -> bool {
+        self.columns.iter().all(ColumnMigration::is_consistent)
+    }
+
+    pub fn rows(&self) -> u64 {
+        self.columns.iter().map(|column| column.target.rows).sum()
+    }
+}
+
+/// Computes the [ColumnSummary] of the `column` of the `db`.
+pub fn summarize_column(
+    db: &TezedgeDatabase,
+    column: &'static str,
+) -> Result<ColumnSummary, Error> {
+    let mut summary = ColumnSummary::default();
+    for entry in db.backend().find(column, BackendIteratorMode::Start)? {
+        let (key, value) = entry?;
+        summary.add(&key, &value);
+    }
+    Ok(summary)
+}
+
+/// Copies the `columns` of the `source` database to the `target` database
+/// and verifies that the target ends up with the same data.
+///
+/// The `target` columns must be empty, so that the verification compares
+/// only the migrated data.
+pub fn migrate_backend(
+    source: &TezedgeDatabase,
+    target: &TezedgeDatabase,
+    columns: &[&'static str],
+    batch_size: usize,
+    log: &Logger,
+) -> Result<MigrationReport, Error> {
+    let timer = Instant::now();
+    let batch_size = batch_size.max(1);
+
+    for &column in columns {
+        if target
+            .backend()
+            .find(column, BackendIteratorMode::Start)?
+            .next()
+            .is_some()
+        {
+            return Err(Error::MigrationError {
+                reason: format!("target column {} is not empty", column),
+            });
+        }
+    }
+
+    for &column in columns {
+        let column_timer = Instant::now();
+        let mut rows = 0;
+        let mut batch = Vec::with_capacity(batch_size);
+        for entry in source.backend().find(column, BackendIteratorMode::Start)? {
+            let (key, value) = entry?;
+            batch.push((key.into_vec(), value.into_vec()));
+            if batch.len() >= batch_size {
+                rows += batch.len();
+                target
+                    .backend()
+                    .write_batch(column, std::mem::take(&mut batch))?;
+            }
+        }
+        if !batch.is_empty() {
+            rows += batch.len();
+            target.backend().write_batch(column, batch)?;
+        }
+        info!(log, "Column migrated";
+            "column" => column,
+            "rows" => rows,
+            "duration" => format!("{:?}", column_timer.elapsed()));
+    }
+    target.backend().flush()?;
+
+    let columns = columns
+        .iter()
+        .map(|&column| {
+            Ok(ColumnMigration {
+                column,
+                source: summarize_column(source, column)?,
+                target: summarize_column(target, column)?,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(MigrationReport {
+        columns,
+        duration: timer.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::database::edgekv_backend::EdgeKVBackend;
+    use crate::database::rockdb_backend::RocksDBBackend;
+    use crate::database::sled_backend::SledDBBackend;
+    use crate::database::tezedge_database::TezedgeDatabaseBackendOptions;
+    use crate::persistent::database::{open_kv, RocksDbKeyValueSchema};
+    use crate::persistent::DbConfiguration;
+    use crate::{tests_common, ConstantsStorage, CycleMetaStorage};
+
+    fn columns() -> Vec<&'static str> {
+        vec![CycleMetaStorage::name(), ConstantsStorage::name()]
+    }
+
+    fn clean(path: &str) {
+        if Path::new(path).exists() {
+            std::fs::remove_dir_all(path).unwrap();
+        }
+    }
+
+    fn open(backend: TezedgeDatabaseBackendOptions) -> TezedgeDatabase {
+        let log = tests_common::create_logger(tests_common::log_level());
+        TezedgeDatabase::new(backend, log)
+    }
+
+    fn fill(db: &TezedgeDatabase, rows: u8) {
+        for column in columns() {
+            for i in 0..rows {
+                db.backend().put(column, &[i], &[i, i]).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_migrate_sled_to_edgekv_to_rocksdb() {
+        let (sled_path, edgekv_path, rocksdb_path) = (
+            "__backend_migration_sled",
+            "__backend_migration_edgekv",
+            "__backend_migration_rocksdb",
+        );
+        for path in [sled_path, edgekv_path, rocksdb_path] {
+            clean(path);
+        }
+        let log = tests_common::create_logger(tests_common::log_level());
+
+        {
+            let sled = open(TezedgeDatabaseBackendOptions::SledDB(
+                SledDBBackend::new(sled_path).unwrap(),
+            ));
+            let edgekv = open(TezedgeDatabaseBackendOptions::EdgeKV(
+                EdgeKVBackend::new(edgekv_path, columns()).unwrap(),
+            ));
+            let cache = rocksdb::Cache::new_lru_cache(32 * 1024 * 1024).unwrap();
+            let rocksdb = open(TezedgeDatabaseBackendOptions::RocksDB(
+                RocksDBBackend::from_db(Arc::new(
+                    open_kv(
+                        rocksdb_path,
+                        vec![
+                            CycleMetaStorage::descriptor(&cache),
+                            ConstantsStorage::descriptor(&cache),
+                        ],
+                        &DbConfiguration::default(),
+                    )
+                    .unwrap(),
+                ))
+                .unwrap(),
+            ));
+            fill(&sled, 25);
+
+            // batch size not dividing the number of rows
+            let report = migrate_backend(&sled, &edgekv, &columns(), 10, &log).unwrap();
+            assert!(report.is_consistent(), "{:?}", report);
+            assert_eq!(report.rows(), 50);
+
+            let report = migrate_backend(&edgekv, &rocksdb, &columns(), 1000, &log).unwrap();
+            assert!(report.is_consistent(), "{:?}", report);
+            assert_eq!(report.rows(), 50);
+            assert_eq!(
+                rocksdb
+                    .backend()
+                    .get(CycleMetaStorage::name(), &[7])
+                    .unwrap(),
+                Some(vec![7, 7])
+            );
+
+            // migrating again would mix the data with the existing one
+            assert!(matches!(
+                migrate_backend(&sled, &rocksdb, &columns(), 10, &log),
+                Err(Error::MigrationError { .. })
+            ));
+        }
+
+        for path in [sled_path, edgekv_path, rocksdb_path] {
+            clean(path);
+        }
+    }
+
+    #[test]
+    fn test_summary_detects_differences() {
+        let (first_path, second_path) = ("__backend_migration_first", "__backend_migration_second");
+        clean(first_path);
+        clean(second_path);
+
+        {
+            let first = open(TezedgeDatabaseBackendOptions::SledDB(
+                SledDBBackend::new(first_path).unwrap(),
+            ));
+            let second = open(TezedgeDatabaseBackendOptions::SledDB(
+                SledDBBackend::new(second_path).unwrap(),
+            ));
+            fill(&first, 5);
+            fill(&second, 5);
+            let column = CycleMetaStorage::name();
+            assert_eq!(
+                summarize_column(&first, column).unwrap(),
+                summarize_column(&second, column).unwrap()
+            );
+
+            second.backend().put(column, &[3], &[0, 0]).unwrap();
+            let (first, second) = (
+                summarize_column(&first, column).unwrap(),
+                summarize_column(&second, column).unwrap(),
+            );
+            assert_eq!(first.rows, second.rows);
+            assert_ne!(first.checksum, second.checksum);
+        }
+
+        clean(first_path);
+        clean(second_path);
+    }
+}
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: MIT
 
 use crate::database::backend::{
-    BackendIterator, BackendIteratorMode, DBStats, TezedgeDatabaseBackendStore,
+    BackendIterator, BackendIteratorMode, BatchOperation, DBStats, TezedgeDatabaseBackendStore,
 };
 use crate::database::error::Error;
 use crate::database::tezedge_database::{KVStoreKeyValueSchema, TezdegeDatabaseBackendKV};
@@ -10,14 +10,42 @@ use crate::{
     block_meta_storage, operations_meta_storage, BlockMetaStorage, Direction, OperationsMetaStorage,
 };
 use edgekv::edgekv::EdgeKV;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Instant;
 
+/// Batch being committed, kept in the database directory until all of
+/// its writes are synced to the columns.
+const BATCH_JOURNAL_FILE: &str = "batch.journal";
+
+type EdgeKVMergeOperator = fn(&[u8], Option<Vec<u8>>, &[u8]) -> Option<Vec<u8>>;
+
+/// Write of a journaled batch. Merges are resolved before the batch is
+/// journaled, so replaying the journal more than once is harmless.
+#[derive(Serialize, Deserialize, Debug)]
+enum JournalEntry {
+    Put {
+        column: String,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Delete {
+        column: String,
+        key: Vec<u8>,
+    },
+}
+
 pub struct EdgeKVBackend {
     column_stats: Arc<RwLock<HashMap<&'static str, DBStats>>>,
     db: HashMap<&'static str, EdgeKV>,
+    journal_path: PathBuf,
+    /// Serializes the read-modify-write of `merge` and of the merges in
+    /// `commit_batch`, so that neither overwrites the other.
+    merge_lock: Mutex<()>,
 }
 
 impl EdgeKVBackend {
@@ -34,11 +62,86 @@ impl EdgeKVBackend {
             db.insert(col, col_db);
         }
 
-        Ok(Self {
+        let backend = Self {
             column_stats: Arc::new(Default::default()),
             db,
+            journal_path: p.join(BATCH_JOURNAL_FILE),
+            merge_lock: Mutex::new(()),
+        };
+        backend.replay_journal()?;
+        Ok(backend)
+    }
+
+    fn column(&self, column: &str) -> Result<&EdgeKV, Error> {
+        self.db.get(column).ok_or(Error::EdgeKVError {
+            error: format!("Column Missing: {}", column),
         })
     }
+
+    fn merge_operator(column: &'static str) -> Option<EdgeKVMergeOperator> {
+        if column == OperationsMetaStorage::column_name() {
+            Some(operations_meta_storage::merge_meta_value_edgekv)
+        } else if column == BlockMetaStorage::column_name() {
+            Some(block_meta_storage::merge_meta_value_edgekv)
+        } else {
+            None
+        }
+    }
+
+    /// Finishes a batch interrupted by a crash, if there is one.
+    fn replay_journal(&self) -> Result<(), Error> {
+        let bytes = match fs::read(&self.journal_path) {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(Error::IOError { error }),
+        };
+        let entries: Vec<JournalEntry> =
+            bincode::deserialize(&bytes).map_err(|error| Error::EdgeKVError {
+                error: format!("Corrupted batch journal: {:?}", error),
+            })?;
+        self.apply_journal(&entries)
+    }
+
+    fn write_journal(&self, entries: &[JournalEntry]) -> Result<(), Error> {
+        let bytes = bincode::serialize(entries).map_err(|error| Error::EdgeKVError {
+            error: format!("{:?}", error),
+        })?;
+        // the journal only appears once it is complete
+        let tmp_path = self.journal_path.with_extension("tmp");
+        let mut file = File::create(&tmp_path).map_err(|error| Error::IOError { error })?;
+        file.write_all(&bytes)
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::rename(&tmp_path, &self.journal_path))
+            .map_err(|error| Error::IOError { error })
+    }
+
+    fn apply_journal(&self, entries: &[JournalEntry]) -> Result<(), Error> {
+        let mut columns = BTreeMap::new();
+        for entry in entries {
+            let result = match entry {
+                JournalEntry::Put { column, key, value } => {
+                    let db = self.column(column)?;
+                    columns.insert(column.as_str(), db);
+                    db.put(key.clone(), value.clone())
+                }
+                JournalEntry::Delete { column, key } => {
+                    let db = self.column(column)?;
+                    columns.insert(column.as_str(), db);
+                    db.delete(key)
+                }
+            };
+            result.map_err(|error| Error::EdgeKVError {
+                error: format!("{:?}", error),
+            })?;
+        }
+        // the journal can go away only once every write is on disk
+        for db in columns.values() {
+            db.sync_all().map_err(|e| Error::EdgeKVError {
+                error: format!("EdgeKV Error: {:?}", e),
+            })?;
+        }
+        fs::remove_file(&self.journal_path).map_err(|error| Error::IOError { error })
+    }
 }
 
 #[derive(Clone)]
@@ -125,9 +228,10 @@ impl TezedgeDatabaseBackendStore for EdgeKVBackend {
             })?;
 
         let total_write_duration = timer.elapsed();
-        let mut stat = stats.entry(column).or_insert_with(Default::default);
-        stat.total_write_duration += total_write_duration;
-        stat.total_writes += 1;
+        stats
+            .entry(column)
+            .or_insert_with(Default::default)
+            .record_write(total_write_duration);
 
         Ok(())
     }
@@ -145,6 +249,9 @@ impl TezedgeDatabaseBackendStore for EdgeKVBackend {
     }
 
     fn merge(&self, column: &'static str, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        let _merge_guard = self.merge_lock.lock().map_err(|e| Error::GuardPoison {
+            error: format!("{}", e),
+        })?;
         let mut stats = self.column_stats.write().map_err(|e| Error::GuardPoison {
             error: format!("{}", e),
         })?;
@@ -186,9 +293,10 @@ impl TezedgeDatabaseBackendStore for EdgeKVBackend {
         }
 
         let total_update_duration = timer.elapsed();
-        let mut stat = stats.entry(column).or_insert_with(Default::default);
-        stat.total_update_duration += total_update_duration;
-        stat.total_updates += 1;
+        stats
+            .entry(column)
+            .or_insert_with(Default::default)
+            .record_update(total_update_duration);
         Ok(())
     }
 
@@ -206,9 +314,10 @@ impl TezedgeDatabaseBackendStore for EdgeKVBackend {
             error: format!("{:?}", error),
         })?;
         let total_read_duration = timer.elapsed();
-        let mut stat = stats.entry(column).or_insert_with(Default::default);
-        stat.total_read_duration += total_read_duration;
-        stat.total_reads += 1;
+        stats
+            .entry(column)
+            .or_insert_with(Default::default)
+            .record_read(total_read_duration);
 
         Ok(value)
     }
@@ -239,6 +348,65 @@ impl TezedgeDatabaseBackendStore for EdgeKVBackend {
         Ok(())
     }
 
+    fn commit_batch(&self, batch: Vec<BatchOperation>) -> Result<(), Error> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        // held until the batch is applied, a merge in between would be lost
+        let _merge_guard = self.merge_lock.lock().map_err(|e| Error::GuardPoison {
+            error: format!("{}", e),
+        })?;
+
+        // final value of every key written by the batch, `None` for deletes
+        let mut writes: BTreeMap<(&'static str, Vec<u8>), Option<Vec<u8>>> = BTreeMap::new();
+        for operation in batch {
+            match operation {
+                BatchOperation::Put { column, key, value } => {
+                    writes.insert((column, key), Some(value));
+                }
+                BatchOperation::Merge { column, key, value } => {
+                    let merged = match Self::merge_operator(column) {
+                        Some(merge_operator) => {
+                            let existing = match writes.get(&(column, key.clone())) {
+                                Some(pending) => pending.clone(),
+                                None => self.column(column)?.get(&key).map_err(|error| {
+                                    Error::EdgeKVError {
+                                        error: format!("{:?}", error),
+                                    }
+                                })?,
+                            };
+                            merge_operator(&key, existing, &value)
+                        }
+                        None => Some(value),
+                    };
+                    writes.insert((column, key), merged);
+                }
+                BatchOperation::Delete { column, key } => {
+                    writes.insert((column, key), None);
+                }
+            }
+        }
+
+        let entries = writes
+            .into_iter()
+            .map(|((column, key), value)| match value {
+                Some(value) => JournalEntry::Put {
+                    column: column.to_string(),
+                    key,
+                    value,
+                },
+                None => JournalEntry::Delete {
+                    column: column.to_string(),
+                    key,
+                },
+            })
+            .collect::<Vec<_>>();
+
+        // once journaled, the batch survives a crash and is finished on the next open
+        self.write_journal(&entries)?;
+        self.apply_journal(&entries)
+    }
+
     fn flush(&self) -> Result<usize, Error> {
         for (_, db) in self.db.iter() {
             db.sync_all().map_err(|e| Error::EdgeKVError {
@@ -248,6 +416,25 @@ impl TezedgeDatabaseBackendStore for EdgeKVBackend {
         Ok(0)
     }
 
+    fn checkpoint(&self, path: &Path) -> Result<(), Error> {
+        if path.exists() {
+            return Err(Error::IOError {
+                error: std::io::Error::from(std::io::ErrorKind::AlreadyExists),
+            });
+        }
+        // Copies the live entries instead of the files, which the background
+        // sync of the columns may split or remove while being copied.
+        let checkpoint = Self::new(path, self.db.keys().copied().collect())?;
+        for &column in self.db.keys() {
+            for entry in self.find(column, BackendIteratorMode::Start)? {
+                let (key, value) = entry?;
+                checkpoint.put(column, &key, &value)?;
+            }
+        }
+        checkpoint.flush()?;
+        Ok(())
+    }
+
     fn size(&self) -> HashMap<&'static str, usize> {
         self.db
             .iter()
@@ -305,12 +492,119 @@ impl TezedgeDatabaseBackendStore for EdgeKVBackend {
     }
 
     fn column_stats(&self) -> HashMap<&'static str, DBStats> {
-        let stats = match self.column_stats.read().map_err(|e| Error::GuardPoison {
+        let mut stats = match self.column_stats.read().map_err(|e| Error::GuardPoison {
             error: format!("{}", e),
         }) {
-            Ok(stats) => stats,
+            Ok(stats) => stats.clone(),
             Err(_) => return Default::default(),
         };
-        stats.clone()
+        for (column, db) in self.db.iter() {
+            let column_stats = stats.entry(*column).or_default();
+            let merge_stats = db.merge_stats();
+            column_stats.total_merges = merge_stats.merges;
+            column_stats.total_merge_duration = merge_stats.duration;
+            column_stats.merged_files = merge_stats.merged_files;
+            column_stats.reclaimed_bytes = merge_stats.reclaimed_bytes;
+            if let Ok(file_stats) = db.file_stats() {
+                column_stats.data_files = file_stats.data_files as u64;
+                column_stats.dead_bytes = file_stats.dead_bytes();
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interrupted_batch_is_replayed_on_open() {
+        let path = Path::new("__edgekv_backend_batch_journal");
+        if path.exists() {
+            fs::remove_dir_all(path).unwrap();
+        }
+        let columns = vec!["first", "second"];
+
+        {
+            let backend = EdgeKVBackend::new(path, columns.clone()).unwrap();
+            backend.put("first", &[1], &[1]).unwrap();
+            backend.put("second", &[2], &[2]).unwrap();
+
+            // crash right after the batch was journaled
+            backend
+                .write_journal(&[
+                    JournalEntry::Put {
+                        column: "first".to_string(),
+                        key: vec![1],
+                        value: vec![10],
+                    },
+                    JournalEntry::Delete {
+                        column: "second".to_string(),
+                        key: vec![2],
+                    },
+                ])
+                .unwrap();
+        }
+
+        {
+            let backend = EdgeKVBackend::new(path, columns).unwrap();
+            assert!(!backend.journal_path.exists());
+            assert_eq!(backend.get("first", &[1]).unwrap(), Some(vec![10]));
+            assert_eq!(backend.get("second", &[2]).unwrap(), None);
+        }
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_merge_and_commit_batch() {
+        let path = Path::new("__edgekv_backend_concurrent_merge");
+        if path.exists() {
+            fs::remove_dir_all(path).unwrap();
+        }
+        let column = OperationsMetaStorage::column_name();
+        let backend = Arc::new(EdgeKVBackend::new(path, vec![column]).unwrap());
+        let keys = 0..200u8;
+        // 8 validation passes, each of the writers sets the flags of 4 of them
+        let flag = |pass: usize| {
+            let mut value = vec![0; 10];
+            value[0] = 8;
+            value[pass] = 1;
+            value
+        };
+
+        let merging = {
+            let backend = backend.clone();
+            let keys = keys.clone();
+            std::thread::spawn(move || {
+                for key in keys {
+                    for pass in 1..=4 {
+                        backend.merge(column, &[key], &flag(pass)).unwrap();
+                    }
+                }
+            })
+        };
+        for key in keys.clone() {
+            for pass in 5..=8 {
+                backend
+                    .commit_batch(vec![BatchOperation::Merge {
+                        column,
+                        key: vec![key],
+                        value: flag(pass),
+                    }])
+                    .unwrap();
+            }
+        }
+        merging.join().unwrap();
+
+        for key in keys {
+            assert_eq!(
+                backend.get(column, &[key]).unwrap(),
+                Some(vec![8, 1, 1, 1, 1, 1, 1, 1, 1, 0])
+            );
+        }
+        drop(backend);
+        fs::remove_dir_all(path).unwrap();
     }
 }
@@ -33,6 +33,10 @@ pub enum Error {
     RocksDBError { error: rocksdb::Error },
     #[error("Column family {name} is missing")]
     MissingColumnFamily { name: &'static str },
+    #[error("Backend migration failed: {reason}")]
+    MigrationError { reason: String },
+    #[error("Schema migration from version {from} to {to} failed: {reason}")]
+    SchemaMigrationError { from: i64, to: i64, reason: String },
 }
 
 impl From<SchemaError> for Error {
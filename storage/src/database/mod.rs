@@ -2,8 +2,10 @@
 // SPDX-License-Identifier: MIT
 
 pub mod backend;
+pub mod backend_migration;
 pub mod edgekv_backend;
 pub mod error;
 pub mod rockdb_backend;
+pub mod schema_migration;
 pub mod sled_backend;
 pub mod tezedge_database;
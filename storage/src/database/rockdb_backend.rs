@@ -1,12 +1,15 @@
 // Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use crate::database::backend::{BackendIteratorMode, DBStats, TezedgeDatabaseBackendStore};
+use crate::database::backend::{
+    BackendIteratorMode, BatchOperation, DBStats, TezedgeDatabaseBackendStore,
+};
 use crate::database::error::Error;
 use crate::database::tezedge_database::TezdegeDatabaseBackendKV;
 use crate::initializer::{RocksDbColumnFactory, RocksDbConfig};
 use crate::persistent::database::default_kv_options;
 use crate::persistent::DbConfiguration;
+use rocksdb::checkpoint::Checkpoint;
 use rocksdb::{Cache, ColumnFamilyDescriptor, WriteBatch, WriteOptions, DB};
 use std::collections::HashMap;
 use std::path::Path;
@@ -74,9 +77,10 @@ impl TezedgeDatabaseBackendStore for RocksDBBackend {
             .map_err(Error::from)?;
 
         let total_write_duration = timer.elapsed();
-        let mut stat = stats.entry(column).or_insert_with(Default::default);
-        stat.total_write_duration += total_write_duration;
-        stat.total_writes += 1;
+        stats
+            .entry(column)
+            .or_insert_with(Default::default)
+            .record_write(total_write_duration);
         Ok(())
     }
 
@@ -108,9 +112,10 @@ impl TezedgeDatabaseBackendStore for RocksDBBackend {
             .map_err(Error::from);
 
         let total_update_duration = timer.elapsed();
-        let mut stat = stats.entry(column).or_insert_with(Default::default);
-        stat.total_update_duration += total_update_duration;
-        stat.total_updates += 1;
+        stats
+            .entry(column)
+            .or_insert_with(Default::default)
+            .record_update(total_update_duration);
 
         res
     }
@@ -129,9 +134,10 @@ impl TezedgeDatabaseBackendStore for RocksDBBackend {
         let value = self.db.get_cf(cf, key).map_err(Error::from)?;
 
         let total_read_duration = timer.elapsed();
-        let mut stat = stats.entry(column).or_insert_with(Default::default);
-        stat.total_read_duration += total_read_duration;
-        stat.total_reads += 1;
+        stats
+            .entry(column)
+            .or_insert_with(Default::default)
+            .record_read(total_read_duration);
 
         Ok(value)
     }
@@ -162,12 +168,36 @@ impl TezedgeDatabaseBackendStore for RocksDBBackend {
         Ok(())
     }
 
+    fn commit_batch(&self, batch: Vec<BatchOperation>) -> Result<(), Error> {
+        let mut rocksdb_batch = WriteBatch::default();
+        for operation in batch.iter() {
+            let column = operation.column();
+            let cf = self
+                .db
+                .cf_handle(column)
+                .ok_or(Error::MissingColumnFamily { name: column })?;
+            match operation {
+                BatchOperation::Put { key, value, .. } => rocksdb_batch.put_cf(cf, key, value),
+                BatchOperation::Merge { key, value, .. } => rocksdb_batch.merge_cf(cf, key, value),
+                BatchOperation::Delete { key, .. } => rocksdb_batch.delete_cf(cf, key),
+            }
+        }
+        self.db.write_opt(rocksdb_batch, &default_write_options())?;
+        Ok(())
+    }
+
     fn flush(&self) -> Result<usize, Error> {
         self.db.flush()?;
         self.db.flush_wal(true)?;
         Ok(0)
     }
 
+    fn checkpoint(&self, path: &Path) -> Result<(), Error> {
+        Checkpoint::new(&self.db)?
+            .create_checkpoint(path)
+            .map_err(Error::from)
+    }
+
     fn size(&self) -> HashMap<&'static str, usize> {
         // TODO - TE-721: this doesn't compute anyhting
         HashMap::new()
@@ -0,0 +1,555 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Upgrades of the main database created by an older version of the node,
+//! so that it can be used without resyncing.
+//!
+//! Every [SchemaMigration] upgrades the database by one [DbVersion], the
+//! [SchemaMigrations] registry chains them from the version stored in
+//! [SystemStorage] up to the expected one. The version is bumped after
+//! each finished step, so an interrupted upgrade resumes with the step
+//! which didn't finish.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use slog::{info, warn, Logger};
+
+use crate::database::backend::TezedgeDatabaseBackendStore;
+use crate::database::error::Error;
+use crate::database::tezedge_database::TezedgeDatabase;
+use crate::system_storage::{DbVersion, MAIN_DB_VERSION};
+use crate::{StorageError, SystemStorage};
+
+/// Single upgrade of the main database schema, from [from_version]
+/// to the next version.
+///
+/// [from_version]: SchemaMigration::from_version
+pub trait SchemaMigration: Send + Sync {
+    /// Version of the database which is upgraded by this migration.
+    fn from_version(&self) -> DbVersion;
+
+    fn description(&self) -> &'static str;
+
+    /// Destructive migrations delete or rewrite existing data, so the
+    /// database is backed up before they run.
+    fn is_destructive(&self) -> bool {
+        false
+    }
+
+    /// Upgrades the `db` to the version following [SchemaMigration::from_version].
+    ///
+    /// If the node is stopped in the middle, the migration runs again from
+    /// the start on the next startup, so it has to be idempotent.
+    fn migrate(&self, db: &Arc<TezedgeDatabase>, log: &Logger) -> Result<(), StorageError>;
+}
+
+/// Migration between versions which share the same schema, only the
+/// version stored in the database changes.
+pub struct VersionBump {
+    from: DbVersion,
+}
+
+impl VersionBump {
+    pub fn new(from: DbVersion) -> Self {
+        Self { from }
+    }
+}
+
+impl SchemaMigration for VersionBump {
+    fn from_version(&self) -> DbVersion {
+        self.from
+    }
+
+    fn description(&self) -> &'static str {
+        "schema is unchanged"
+    }
+
+    fn migrate(&self, _db: &Arc<TezedgeDatabase>, _log: &Logger) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SchemaMigrationOptions {
+    /// Only plan the migrations, without touching the database.
+    pub dry_run: bool,
+    /// Directory for the backups taken before destructive migrations,
+    /// which can't run without it.
+    pub backup_dir: Option<PathBuf>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SchemaMigrationStep {
+    pub from: DbVersion,
+    pub to: DbVersion,
+    pub description: &'static str,
+    pub destructive: bool,
+    /// Checkpoint of the database taken before the migration.
+    pub backup: Option<PathBuf>,
+    /// `None` if the migration didn't run, e.g. in the dry run.
+    pub duration: Option<Duration>,
+}
+
+/// Ordered registry of the schema migrations, keyed by the version they
+/// upgrade from.
+#[derive(Default)]
+pub struct SchemaMigrations {
+    migrations: BTreeMap<DbVersion, Box<dyn SchemaMigration>>,
+}
+
+impl SchemaMigrations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Migrations of the main database, known to this version of the node.
+    ///
+    /// None yet, databases older than [MAIN_DB_VERSION] still have to be
+    /// resynced. A step from [MAIN_DB_VERSION] is registered here together
+    /// with the bump of the version.
+    pub fn main_db() -> Self {
+        Self::new()
+    }
+
+    /// Panics if a migration from the same version is registered already.
+    pub fn register(&mut self, migration: Box<dyn SchemaMigration>) {
+        let from = migration.from_version();
+        if self.migrations.insert(from, migration).is_some() {
+            panic!("Schema migration from version {} registered twice", from);
+        }
+    }
+
+    /// Migrations needed to upgrade the database from the version `from`
+    /// to the version `to`, in the order they have to run.
+    pub fn plan(
+        &self,
+        from: DbVersion,
+        to: DbVersion,
+    ) -> Result<Vec<&dyn SchemaMigration>, StorageError> {
+        (from..to)
+            .map(|version| {
+                self.migrations
+                    .get(&version)
+                    .map(|migration| migration.as_ref())
+                    .ok_or_else(|| {
+                        StorageError::from(Error::SchemaMigrationError {
+                            from,
+                            to,
+                            reason: format!("no migration from version {}", version),
+                        })
+                    })
+            })
+            .collect()
+    }
+
+    /// Upgrades the `db` to the version `to`.
+    ///
+    /// Nothing is done for a new database, or for a database which is not
+    /// older than `to`, the compatibility of those is checked elsewhere.
+    pub fn run(
+        &self,
+        db: &Arc<TezedgeDatabase>,
+        to: DbVersion,
+        options: &SchemaMigrationOptions,
+        log: &Logger,
+    ) -> Result<Vec<SchemaMigrationStep>, StorageError> {
+        let mut system_storage = SystemStorage::new(db.clone());
+        let from = match system_storage.get_db_version()? {
+            Some(version) if version < to => version,
+            _ => return Ok(Vec::new()),
+        };
+        let plan = self.plan(from, to)?;
+
+        if let Some(interrupted) = system_storage.get_db_migration()? {
+            warn!(log, "Resuming interrupted schema migration"; "from" => interrupted);
+        }
+
+        let mut steps = Vec::with_capacity(plan.len());
+        for migration in plan {
+            let from = migration.from_version();
+            let mut step = SchemaMigrationStep {
+                from,
+                to: from + 1,
+                description: migration.description(),
+                destructive: migration.is_destructive(),
+                backup: None,
+                duration: None,
+            };
+
+            if options.dry_run {
+                info!(log, "Schema migration planned";
+                    "from" => step.from,
+                    "to" => step.to,
+                    "description" => step.description,
+                    "destructive" => step.destructive);
+                steps.push(step);
+                continue;
+            }
+
+            if step.destructive {
+                let backup_dir = match &options.backup_dir {
+                    Some(backup_dir) => backup_dir,
+                    None => {
+                        return Err(Error::SchemaMigrationError {
+                            from: step.from,
+                            to: step.to,
+                            reason: "backup directory is required".to_string(),
+                        }
+                        .into())
+                    }
+                };
+                step.backup = Some(backup(db, backup_dir, from, log)?);
+            }
+
+            info!(log, "Migrating database schema";
+                "from" => step.from,
+                "to" => step.to,
+                "description" => step.description);
+            let timer = Instant::now();
+            system_storage.set_db_migration(from)?;
+            migration
+                .migrate(db, log)
+                .and_then(|_| db.backend().flush().map_err(StorageError::from))
+                .map_err(|e| Error::SchemaMigrationError {
+                    from: step.from,
+                    to: step.to,
+                    reason: e.to_string(),
+                })?;
+            system_storage.finish_db_migration(step.to)?;
+            step.duration = Some(timer.elapsed());
+
+            info!(log, "Database schema migrated";
+                "version" => step.to,
+                "duration" => format!("{:?}", timer.elapsed()));
+            steps.push(step);
+        }
+
+        Ok(steps)
+    }
+}
+
+/// Checkpoints the `db` before the migration from the version `from`.
+///
+/// A backup left by an interrupted run is kept, as it was taken before
+/// the migration started to modify the database.
+fn backup(
+    db: &TezedgeDatabase,
+    backup_dir: &Path,
+    from: DbVersion,
+    log: &Logger,
+) -> Result<PathBuf, StorageError> {
+    let path = backup_dir.join(format!("db_version_{}", from));
+    if path.exists() {
+        info!(log, "Using existing database backup"; "path" => path.to_string_lossy().to_string());
+        return Ok(path);
+    }
+
+    // checkpoint is renamed only once complete
+    let tmp_path = backup_dir.join(format!("db_version_{}.tmp", from));
+    let io_error = |error| StorageError::from(Error::IOError { error });
+    if tmp_path.exists() {
+        std::fs::remove_dir_all(&tmp_path).map_err(io_error)?;
+    }
+    std::fs::create_dir_all(backup_dir).map_err(io_error)?;
+
+    let timer = Instant::now();
+    db.backend().checkpoint(&tmp_path)?;
+    std::fs::rename(&tmp_path, &path).map_err(io_error)?;
+    info!(log, "Database backed up";
+        "path" => path.to_string_lossy().to_string(),
+        "duration" => format!("{:?}", timer.elapsed()));
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::database::sled_backend::SledDBBackend;
+    use crate::database::tezedge_database::TezedgeDatabaseBackendOptions;
+    use crate::persistent::database::RocksDbKeyValueSchema;
+    use crate::{tests_common, CycleMetaStorage};
+
+    /// Stores the version it migrated to under its own key.
+    #[derive(Default)]
+    struct TestMigration {
+        from: DbVersion,
+        destructive: bool,
+        fail: AtomicBool,
+        runs: Arc<AtomicUsize>,
+    }
+
+    impl SchemaMigration for TestMigration {
+        fn from_version(&self) -> DbVersion {
+            self.from
+        }
+
+        fn description(&self) -> &'static str {
+            "test"
+        }
+
+        fn is_destructive(&self) -> bool {
+            self.destructive
+        }
+
+        fn migrate(&self, db: &Arc<TezedgeDatabase>, _log: &Logger) -> Result<(), StorageError> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(Error::FailedToOpenDatabase.into());
+            }
+            db.backend()
+                .put(
+                    CycleMetaStorage::name(),
+                    &[self.from as u8],
+                    &[self.from as u8 + 1],
+                )
+                .map_err(StorageError::from)
+        }
+    }
+
+    fn migration(from: DbVersion, runs: &Arc<AtomicUsize>) -> Box<TestMigration> {
+        Box::new(TestMigration {
+            from,
+            runs: runs.clone(),
+            ..Default::default()
+        })
+    }
+
+    fn clean(path: &str) {
+        if Path::new(path).exists() {
+            std::fs::remove_dir_all(path).unwrap();
+        }
+    }
+
+    fn open(path: &str, version: Option<DbVersion>) -> Arc<TezedgeDatabase> {
+        let log = tests_common::create_logger(tests_common::log_level());
+        let db = Arc::new(TezedgeDatabase::new(
+            TezedgeDatabaseBackendOptions::SledDB(SledDBBackend::new(path).unwrap()),
+            log,
+        ));
+        if let Some(version) = version {
+            SystemStorage::new(db.clone())
+                .set_db_version(version)
+                .unwrap();
+        }
+        db
+    }
+
+    fn db_version(db: &Arc<TezedgeDatabase>) -> Option<DbVersion> {
+        SystemStorage::new(db.clone()).get_db_version().unwrap()
+    }
+
+    fn migrated(db: &TezedgeDatabase, from: DbVersion) -> bool {
+        db.backend()
+            .get(CycleMetaStorage::name(), &[from as u8])
+            .unwrap()
+            .is_some()
+    }
+
+    #[test]
+    fn test_plan() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut migrations = SchemaMigrations::new();
+        // registered out of order
+        migrations.register(migration(3, &runs));
+        migrations.register(migration(1, &runs));
+        migrations.register(migration(2, &runs));
+
+        let plan = migrations.plan(1, 4).unwrap();
+        assert_eq!(
+            plan.iter().map(|m| m.from_version()).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert!(migrations.plan(2, 2).unwrap().is_empty());
+        assert!(matches!(
+            migrations.plan(0, 3),
+            Err(StorageError::MainDBError {
+                error: Error::SchemaMigrationError { .. }
+            })
+        ));
+    }
+
+    #[test]
+    fn test_main_db_reaches_current_version() {
+        let migrations = SchemaMigrations::main_db();
+        for from in migrations.migrations.keys() {
+            assert!(*from < MAIN_DB_VERSION);
+            let plan = migrations.plan(*from, MAIN_DB_VERSION).unwrap();
+            assert_eq!(
+                plan.last().map(|m| m.from_version() + 1),
+                Some(MAIN_DB_VERSION)
+            );
+        }
+
+        let path = "__schema_migration_main_db";
+        clean(path);
+        {
+            let log = tests_common::create_logger(tests_common::log_level());
+            let db = open(path, Some(MAIN_DB_VERSION));
+            assert!(migrations
+                .run(
+                    &db,
+                    MAIN_DB_VERSION,
+                    &SchemaMigrationOptions::default(),
+                    &log
+                )
+                .unwrap()
+                .is_empty());
+            assert_eq!(db_version(&db), Some(MAIN_DB_VERSION));
+        }
+        clean(path);
+    }
+
+    #[test]
+    fn test_version_bump() {
+        let path = "__schema_migration_version_bump";
+        clean(path);
+        {
+            let log = tests_common::create_logger(tests_common::log_level());
+            let mut migrations = SchemaMigrations::new();
+            migrations.register(Box::new(VersionBump::new(MAIN_DB_VERSION)));
+            let db = open(path, Some(MAIN_DB_VERSION));
+            let steps = migrations
+                .run(
+                    &db,
+                    MAIN_DB_VERSION + 1,
+                    &SchemaMigrationOptions::default(),
+                    &log,
+                )
+                .unwrap();
+            assert_eq!(steps.len(), 1);
+            assert_eq!(db_version(&db), Some(MAIN_DB_VERSION + 1));
+        }
+        clean(path);
+    }
+
+    #[test]
+    fn test_run_dry_run_and_new_database() {
+        let path = "__schema_migration_dry_run";
+        clean(path);
+        {
+            let log = tests_common::create_logger(tests_common::log_level());
+            let runs = Arc::new(AtomicUsize::new(0));
+            let mut migrations = SchemaMigrations::new();
+            migrations.register(migration(1, &runs));
+            migrations.register(migration(2, &runs));
+
+            // new database has nothing to migrate
+            let db = open(path, None);
+            assert!(migrations
+                .run(&db, 3, &SchemaMigrationOptions::default(), &log)
+                .unwrap()
+                .is_empty());
+            assert_eq!(db_version(&db), None);
+
+            SystemStorage::new(db.clone()).set_db_version(1).unwrap();
+            let dry_run = SchemaMigrationOptions {
+                dry_run: true,
+                ..Default::default()
+            };
+            let steps = migrations.run(&db, 3, &dry_run, &log).unwrap();
+            assert_eq!(steps.len(), 2);
+            assert!(steps.iter().all(|step| step.duration.is_none()));
+            assert_eq!(runs.load(Ordering::SeqCst), 0);
+            assert_eq!(db_version(&db), Some(1));
+
+            let steps = migrations
+                .run(&db, 3, &SchemaMigrationOptions::default(), &log)
+                .unwrap();
+            assert_eq!(steps.len(), 2);
+            assert_eq!(runs.load(Ordering::SeqCst), 2);
+            assert_eq!(db_version(&db), Some(3));
+            assert!(migrated(&db, 1) && migrated(&db, 2));
+        }
+        clean(path);
+    }
+
+    #[test]
+    fn test_interrupted_migration_is_resumed() {
+        let path = "__schema_migration_resume";
+        clean(path);
+        {
+            let log = tests_common::create_logger(tests_common::log_level());
+            let runs = Arc::new(AtomicUsize::new(0));
+            let mut migrations = SchemaMigrations::new();
+            migrations.register(migration(1, &runs));
+            migrations.register(Box::new(TestMigration {
+                from: 2,
+                fail: AtomicBool::new(true),
+                runs: runs.clone(),
+                ..Default::default()
+            }));
+            let db = open(path, Some(1));
+
+            assert!(migrations
+                .run(&db, 3, &SchemaMigrationOptions::default(), &log)
+                .is_err());
+            // first step is kept, the failed one is recorded
+            assert_eq!(db_version(&db), Some(2));
+            assert_eq!(
+                SystemStorage::new(db.clone()).get_db_migration().unwrap(),
+                Some(2)
+            );
+
+            let mut migrations = SchemaMigrations::new();
+            migrations.register(migration(1, &runs));
+            migrations.register(migration(2, &runs));
+            let steps = migrations
+                .run(&db, 3, &SchemaMigrationOptions::default(), &log)
+                .unwrap();
+            assert_eq!(steps.iter().map(|s| s.from).collect::<Vec<_>>(), vec![2]);
+            assert_eq!(runs.load(Ordering::SeqCst), 3);
+            assert_eq!(db_version(&db), Some(3));
+            assert_eq!(
+                SystemStorage::new(db.clone()).get_db_migration().unwrap(),
+                None
+            );
+        }
+        clean(path);
+    }
+
+    #[test]
+    fn test_destructive_migration_is_backed_up() {
+        let (path, backup_dir) = ("__schema_migration_backup", "__schema_migration_backups");
+        clean(path);
+        clean(backup_dir);
+        {
+            let log = tests_common::create_logger(tests_common::log_level());
+            let runs = Arc::new(AtomicUsize::new(0));
+            let mut migrations = SchemaMigrations::new();
+            migrations.register(Box::new(TestMigration {
+                from: 1,
+                destructive: true,
+                runs: runs.clone(),
+                ..Default::default()
+            }));
+            let db = open(path, Some(1));
+
+            assert!(migrations
+                .run(&db, 2, &SchemaMigrationOptions::default(), &log)
+                .is_err());
+            assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+            let options = SchemaMigrationOptions {
+                dry_run: false,
+                backup_dir: Some(PathBuf::from(backup_dir)),
+            };
+            let steps = migrations.run(&db, 2, &options, &log).unwrap();
+            let backup_path = steps[0].backup.clone().unwrap();
+            assert_eq!(backup_path, Path::new(backup_dir).join("db_version_1"));
+            assert!(migrated(&db, 1));
+
+            // backup holds the database from before the migration
+            let backup = open(backup_path.to_str().unwrap(), None);
+            assert_eq!(db_version(&backup), Some(1));
+            assert!(!migrated(&backup, 1));
+        }
+        clean(path);
+        clean(backup_dir);
+    }
+}
@@ -2,12 +2,15 @@
 // SPDX-License-Identifier: MIT
 
 use crate::block_meta_storage;
-use crate::database::backend::{BackendIteratorMode, DBStats, TezedgeDatabaseBackendStore};
+use crate::database::backend::{
+    BackendIteratorMode, BatchOperation, DBStats, TezedgeDatabaseBackendStore,
+};
 use crate::database::error::Error;
 use crate::database::tezedge_database::{KVStoreKeyValueSchema, TezdegeDatabaseBackendKV};
 use crate::operations_meta_storage;
 use crate::{BlockMetaStorage, Direction, OperationsMetaStorage};
-use sled::{Config, IVec, Tree};
+use sled::transaction::{ConflictableTransactionError, TransactionError};
+use sled::{Config, IVec, Transactional, Tree};
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::{Arc, RwLock};
@@ -15,6 +18,8 @@ use std::time::Instant;
 
 use super::backend::BackendIterator;
 
+type SledMergeOperator = fn(&[u8], Option<&[u8]>, &[u8]) -> Option<Vec<u8>>;
+
 pub struct SledDBBackend {
     column_stats: Arc<RwLock<HashMap<&'static str, DBStats>>>,
     db: sled::Db,
@@ -36,17 +41,21 @@ impl SledDBBackend {
     }
     pub fn get_tree(&self, name: &'static str) -> Result<Tree, Error> {
         let tree = self.db.open_tree(name).map_err(Error::from)?;
+        if let Some(merge_operator) = Self::merge_operator(name) {
+            tree.set_merge_operator(merge_operator)
+        }
+        Ok(tree)
+    }
 
+    fn merge_operator(name: &'static str) -> Option<SledMergeOperator> {
         // TODO - TE-498: refactor - SledBackend should be universal, this should be pass here by "some cfg"
         if name == OperationsMetaStorage::column_name() {
-            tree.set_merge_operator(operations_meta_storage::merge_meta_value_sled)
+            Some(operations_meta_storage::merge_meta_value_sled)
+        } else if name == BlockMetaStorage::column_name() {
+            Some(block_meta_storage::merge_meta_value_sled)
+        } else {
+            None
         }
-        // TODO - TE-498: refactor - SledBackend should be universal, this should be pass here by "some cfg"
-        if name == BlockMetaStorage::column_name() {
-            tree.set_merge_operator(block_meta_storage::merge_meta_value_sled)
-        }
-
-        Ok(tree)
     }
 }
 
@@ -62,9 +71,10 @@ impl TezedgeDatabaseBackendStore for SledDBBackend {
         let _ = tree.insert(key, value).map_err(Error::from)?;
 
         let total_write_duration = timer.elapsed();
-        let mut stat = stats.entry(column).or_insert_with(Default::default);
-        stat.total_write_duration += total_write_duration;
-        stat.total_writes += 1;
+        stats
+            .entry(column)
+            .or_insert_with(Default::default)
+            .record_write(total_write_duration);
 
         Ok(())
     }
@@ -86,9 +96,10 @@ impl TezedgeDatabaseBackendStore for SledDBBackend {
         let _ = tree.merge(key, value).map_err(Error::from)?;
 
         let total_update_duration = timer.elapsed();
-        let mut stat = stats.entry(column).or_insert_with(Default::default);
-        stat.total_update_duration += total_update_duration;
-        stat.total_updates += 1;
+        stats
+            .entry(column)
+            .or_insert_with(Default::default)
+            .record_update(total_update_duration);
         Ok(())
     }
 
@@ -106,9 +117,10 @@ impl TezedgeDatabaseBackendStore for SledDBBackend {
             .map_err(Error::from)?;
 
         let total_read_duration = timer.elapsed();
-        let mut stat = stats.entry(column).or_insert_with(Default::default);
-        stat.total_read_duration += total_read_duration;
-        stat.total_reads += 1;
+        stats
+            .entry(column)
+            .or_insert_with(Default::default)
+            .record_read(total_read_duration);
 
         Ok(value)
     }
@@ -131,10 +143,82 @@ impl TezedgeDatabaseBackendStore for SledDBBackend {
         tree.apply_batch(sled_batch).map_err(Error::from)
     }
 
+    fn commit_batch(&self, batch: Vec<BatchOperation>) -> Result<(), Error> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        // every tree touched by the batch takes part in the same transaction
+        let mut columns = batch.iter().map(BatchOperation::column).collect::<Vec<_>>();
+        columns.sort_unstable();
+        columns.dedup();
+        let trees = columns
+            .iter()
+            .map(|column| self.get_tree(column))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        trees
+            .as_slice()
+            .transaction(|trees| -> Result<(), ConflictableTransactionError<Error>> {
+                for operation in batch.iter() {
+                    let column = operation.column();
+                    let tree = match columns.binary_search(&column) {
+                        Ok(index) => &trees[index],
+                        Err(_) => {
+                            return Err(ConflictableTransactionError::Abort(
+                                Error::MissingSubTree {
+                                    error: column.to_string(),
+                                },
+                            ))
+                        }
+                    };
+                    match operation {
+                        BatchOperation::Put { key, value, .. } => {
+                            tree.insert(key.as_slice(), value.as_slice())?;
+                        }
+                        BatchOperation::Merge { key, value, .. } => {
+                            // transactional trees don't support merge operators
+                            let merged = match Self::merge_operator(column) {
+                                Some(merge_operator) => {
+                                    let existing = tree.get(key.as_slice())?;
+                                    merge_operator(key, existing.as_deref(), value)
+                                }
+                                None => Some(value.clone()),
+                            };
+                            match merged {
+                                Some(merged) => tree.insert(key.as_slice(), merged)?,
+                                None => tree.remove(key.as_slice())?,
+                            };
+                        }
+                        BatchOperation::Delete { key, .. } => {
+                            tree.remove(key.as_slice())?;
+                        }
+                    }
+                }
+                Ok(())
+            })
+            .map_err(|error| match error {
+                TransactionError::Abort(error) => error,
+                TransactionError::Storage(error) => Error::from(error),
+            })
+    }
+
     fn flush(&self) -> Result<usize, Error> {
         self.db.flush().map_err(Error::from)
     }
 
+    fn checkpoint(&self, path: &Path) -> Result<(), Error> {
+        if path.exists() {
+            return Err(Error::IOError {
+                error: std::io::Error::from(std::io::ErrorKind::AlreadyExists),
+            });
+        }
+        let checkpoint = Config::default().path(path).open()?;
+        checkpoint.import(self.db.export());
+        checkpoint.flush()?;
+        Ok(())
+    }
+
     fn size(&self) -> HashMap<&'static str, usize> {
         // TODO - TE-721: this doesn't compute anyhting
         HashMap::new()
@@ -1,7 +1,9 @@
 // Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use crate::database::backend::{BackendIteratorMode, DBStats, TezedgeDatabaseBackendStore};
+use crate::database::backend::{
+    BackendIteratorMode, BatchOperation, DBStats, TezedgeDatabaseBackendStore,
+};
 use crate::database::edgekv_backend::EdgeKVBackend;
 use crate::database::error::Error;
 use crate::database::rockdb_backend::RocksDBBackend;
@@ -76,6 +78,70 @@ pub trait KVStoreWithSchemaIterator<S: KeyValueSchema> {
     ) -> Result<BackendIterator<'a>, Error>;
 }
 
+/// Writes to any number of columns, committed atomically with
+/// [KVStoreBatch::commit_batch].
+#[derive(Default, Debug)]
+pub struct WriteBatch {
+    operations: Vec<BatchOperation>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put<S: KVStoreKeyValueSchema>(
+        &mut self,
+        key: &S::Key,
+        value: &S::Value,
+    ) -> Result<(), Error> {
+        self.operations.push(BatchOperation::Put {
+            column: S::column_name(),
+            key: key.encode()?,
+            value: value.encode()?,
+        });
+        Ok(())
+    }
+
+    pub fn merge<S: KVStoreKeyValueSchema>(
+        &mut self,
+        key: &S::Key,
+        value: &S::Value,
+    ) -> Result<(), Error> {
+        self.operations.push(BatchOperation::Merge {
+            column: S::column_name(),
+            key: key.encode()?,
+            value: value.encode()?,
+        });
+        Ok(())
+    }
+
+    pub fn delete<S: KVStoreKeyValueSchema>(&mut self, key: &S::Key) -> Result<(), Error> {
+        self.operations.push(BatchOperation::Delete {
+            column: S::column_name(),
+            key: key.encode()?,
+        });
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+pub trait KVStoreBatch {
+    /// Commit all writes of the batch, possibly to different columns,
+    /// atomically. Either all of them are persisted or none.
+    ///
+    /// # Arguments
+    /// * `batch` - Writes to be committed, in order
+    fn commit_batch(&self, batch: WriteBatch) -> Result<(), Error>;
+}
+
 // TODO - TE-498: Todo Change name
 pub type List<S> = Vec<(
     Result<<S as KeyValueSchema>::Key, SchemaError>,
@@ -139,7 +205,7 @@ pub trait TezdegeDatabaseBackendKV: TezedgeDatabaseBackendStore {}
 pub type TezedgeDatabaseBackend = dyn TezdegeDatabaseBackendKV + Send + Sync;
 
 pub trait TezedgeDatabaseWithIterator<S: KVStoreKeyValueSchema>:
-    KVStore<S> + KVStoreWithSchemaIterator<S>
+    KVStore<S> + KVStoreWithSchemaIterator<S> + KVStoreBatch
 {
 }
 
@@ -172,6 +238,10 @@ impl TezedgeDatabase {
         self.backend.flush()
     }
 
+    pub(crate) fn backend(&self) -> &TezedgeDatabaseBackend {
+        self.backend.as_ref()
+    }
+
     pub fn db_stats(&self) -> HashMap<&'static str, DBStats> {
         self.backend.column_stats()
     }
@@ -246,6 +316,12 @@ impl<S: KVStoreKeyValueSchema> KVStore<S> for TezedgeDatabase {
     }
 }
 
+impl KVStoreBatch for TezedgeDatabase {
+    fn commit_batch(&self, batch: WriteBatch) -> Result<(), Error> {
+        self.backend.commit_batch(batch.operations)
+    }
+}
+
 impl TezedgeDatabase {
     pub fn size(&self) -> HashMap<&'static str, usize> {
         self.backend.size()
@@ -281,4 +357,138 @@ impl<S: KVStoreKeyValueSchema> KVStoreWithSchemaIterator<S> for TezedgeDatabase
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use std::convert::TryInto;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use crypto::hash::{BlockHash, ProtocolHash};
+
+    use super::*;
+    use crate::block_meta_storage::Meta;
+    use crate::cycle_storage::CycleData;
+    use crate::persistent::database::{open_kv, RocksDbKeyValueSchema};
+    use crate::persistent::DbConfiguration;
+    use crate::{tests_common, BlockMetaStorage, ConstantsStorage, CycleMetaStorage};
+
+    fn columns() -> Vec<&'static str> {
+        vec![
+            BlockMetaStorage::column_name(),
+            CycleMetaStorage::column_name(),
+            ConstantsStorage::column_name(),
+        ]
+    }
+
+    fn open_backend(path: &Path, backend: TezedgeDatabaseBackendConfiguration) -> TezedgeDatabase {
+        let log = tests_common::create_logger(tests_common::log_level());
+        let backend = match backend {
+            TezedgeDatabaseBackendConfiguration::RocksDB => {
+                let cache = rocksdb::Cache::new_lru_cache(32 * 1024 * 1024).unwrap();
+                let db = open_kv(
+                    path,
+                    vec![
+                        BlockMetaStorage::descriptor(&cache),
+                        CycleMetaStorage::descriptor(&cache),
+                        ConstantsStorage::descriptor(&cache),
+                    ],
+                    &DbConfiguration::default(),
+                )
+                .unwrap();
+                TezedgeDatabaseBackendOptions::RocksDB(
+                    RocksDBBackend::from_db(Arc::new(db)).unwrap(),
+                )
+            }
+            TezedgeDatabaseBackendConfiguration::Sled => {
+                TezedgeDatabaseBackendOptions::SledDB(SledDBBackend::new(path).unwrap())
+            }
+            TezedgeDatabaseBackendConfiguration::EdgeKV => {
+                TezedgeDatabaseBackendOptions::EdgeKV(EdgeKVBackend::new(path, columns()).unwrap())
+            }
+        };
+        TezedgeDatabase::new(backend, log)
+    }
+
+    fn meta(is_applied: bool, successors: Vec<BlockHash>) -> Meta {
+        Meta {
+            is_applied,
+            predecessor: Some(vec![98; 32].try_into().unwrap()),
+            successors,
+            level: 2,
+            chain_id: vec![44; 4].try_into().unwrap(),
+        }
+    }
+
+    fn check_commit_batch(backend: TezedgeDatabaseBackendConfiguration, path: &str) {
+        let path = Path::new(path);
+        if path.exists() {
+            std::fs::remove_dir_all(path).unwrap();
+        }
+
+        {
+            let db = open_backend(path, backend);
+            let block_hash: BlockHash = vec![44; 32].try_into().unwrap();
+            let successor: BlockHash = vec![21; 32].try_into().unwrap();
+            let protocol_hash: ProtocolHash = vec![7; 32].try_into().unwrap();
+            let cycle_data = CycleData::new(vec![1, 2, 3], vec![], 10);
+            KVStore::<CycleMetaStorage>::put(&db, &5, &cycle_data).unwrap();
+
+            let mut batch = WriteBatch::new();
+            batch
+                .merge::<BlockMetaStorage>(&block_hash, &meta(false, vec![]))
+                .unwrap();
+            batch
+                .merge::<BlockMetaStorage>(&block_hash, &meta(true, vec![successor.clone()]))
+                .unwrap();
+            batch.put::<CycleMetaStorage>(&6, &cycle_data).unwrap();
+            batch.delete::<CycleMetaStorage>(&5).unwrap();
+            batch
+                .put::<ConstantsStorage>(&protocol_hash, &"{}".to_string())
+                .unwrap();
+            assert_eq!(batch.len(), 5);
+            db.commit_batch(batch).unwrap();
+
+            assert_eq!(
+                KVStore::<BlockMetaStorage>::get(&db, &block_hash).unwrap(),
+                Some(meta(true, vec![successor]))
+            );
+            assert_eq!(
+                KVStore::<CycleMetaStorage>::get(&db, &6).unwrap(),
+                Some(cycle_data)
+            );
+            assert!(!KVStore::<CycleMetaStorage>::contains(&db, &5).unwrap());
+            assert_eq!(
+                KVStore::<ConstantsStorage>::get(&db, &protocol_hash).unwrap(),
+                Some("{}".to_string())
+            );
+
+            // empty batch is a no-op
+            db.commit_batch(WriteBatch::new()).unwrap();
+        }
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_commit_batch_rocksdb() {
+        check_commit_batch(
+            TezedgeDatabaseBackendConfiguration::RocksDB,
+            "__tezedge_database_batch_rocksdb",
+        )
+    }
+
+    #[test]
+    fn test_commit_batch_sled() {
+        check_commit_batch(
+            TezedgeDatabaseBackendConfiguration::Sled,
+            "__tezedge_database_batch_sled",
+        )
+    }
+
+    #[test]
+    fn test_commit_batch_edgekv() {
+        check_commit_batch(
+            TezedgeDatabaseBackendConfiguration::EdgeKV,
+            "__tezedge_database_batch_edgekv",
+        )
+    }
+}
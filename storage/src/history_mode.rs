@@ -0,0 +1,259 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! History modes of the node and pruning of the main storage they imply.
+//!
+//! - `archive` - nothing is ever removed.
+//! - `full` - block headers and operations are kept for the whole chain, but block
+//!   and operations metadata (receipts, block additional data) older than the cycle
+//!   offset are removed.
+//! - `rolling` - everything older than the cycle offset is removed: block headers,
+//!   operations, block metadata and additional data.
+//!
+//! The lowest block whose metadata is still available is recorded as the chain
+//! `caboose`, pruning always continues from there, by batches of at most
+//! [PRUNE_BATCH_SIZE] blocks so that the storage is never blocked for long.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crypto::hash::ChainId;
+use tezos_messages::p2p::encoding::block_header::Level;
+use tezos_messages::Head;
+
+use crate::{
+    BlockMetaStorage, BlockStorage, ChainMetaStorage, ChainMetaStorageReader,
+    OperationsMetaStorage, OperationsStorage, PersistentStorage, StorageError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HistoryMode {
+    Archive,
+    /// Keeps metadata for `offset` cycles behind the current one.
+    Full {
+        offset: u32,
+    },
+    /// Keeps blocks for `offset` cycles behind the current one.
+    Rolling {
+        offset: u32,
+    },
+}
+
+impl HistoryMode {
+    /// Default number of cycles kept behind the current one, matches
+    /// `preserved_cycles` on mainnet plus one additional cycle.
+    pub const DEFAULT_CYCLE_OFFSET: u32 = 6;
+
+    pub fn possible_values() -> Vec<&'static str> {
+        vec![
+            "archive",
+            "full",
+            "full:<offset>",
+            "rolling",
+            "rolling:<offset>",
+        ]
+    }
+
+    /// Number of cycles to keep behind the current cycle, `None` if nothing is pruned.
+    pub fn cycle_offset(&self) -> Option<u32> {
+        match self {
+            Self::Archive => None,
+            Self::Full { offset } | Self::Rolling { offset } => Some(*offset),
+        }
+    }
+}
+
+impl Default for HistoryMode {
+    fn default() -> Self {
+        Self::Archive
+    }
+}
+
+#[derive(Debug, Error)]
+#[error(
+    "Invalid history mode: {0}, expected one of {:?}",
+    HistoryMode::possible_values()
+)]
+pub struct ParseHistoryModeError(String);
+
+impl FromStr for HistoryMode {
+    type Err = ParseHistoryModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_ascii_lowercase();
+        let (mode, offset) = match lowercase.split_once(':') {
+            Some((mode, offset)) => {
+                let offset = offset
+                    .parse::<u32>()
+                    .map_err(|_| ParseHistoryModeError(s.to_owned()))?;
+                (mode, Some(offset))
+            }
+            None => (lowercase.as_str(), None),
+        };
+        let offset = offset.unwrap_or(Self::DEFAULT_CYCLE_OFFSET);
+
+        match mode {
+            "archive" if !s.contains(':') => Ok(Self::Archive),
+            "full" => Ok(Self::Full { offset }),
+            "rolling" => Ok(Self::Rolling { offset }),
+            _ => Err(ParseHistoryModeError(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for HistoryMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Archive => write!(f, "archive"),
+            Self::Full { offset } => write!(f, "full:{}", offset),
+            Self::Rolling { offset } => write!(f, "rolling:{}", offset),
+        }
+    }
+}
+
+/// Maximum number of blocks pruned by one call to [prune_history].
+pub const PRUNE_BATCH_SIZE: usize = 1000;
+
+/// Progress of the pruning after a call to [prune_history].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryPruneProgress {
+    /// Number of blocks pruned by the call.
+    pub pruned: usize,
+    /// Level of the caboose, everything below it is pruned.
+    pub caboose_level: Level,
+}
+
+impl HistoryPruneProgress {
+    /// Returns true if everything below `below_level` is pruned.
+    pub fn is_done(&self, below_level: Level) -> bool {
+        self.caboose_level >= below_level
+    }
+}
+
+/// Prunes history of the chain below `below_level` according to `history_mode`,
+/// starting from the current caboose. At most `max_blocks` blocks are pruned, the
+/// lowest block not pruned becomes the new caboose.
+///
+/// Call it again until [HistoryPruneProgress::is_done] to prune everything below `below_level`.
+pub fn prune_history(
+    persistent_storage: &PersistentStorage,
+    chain_id: &ChainId,
+    history_mode: &HistoryMode,
+    below_level: Level,
+    max_blocks: usize,
+) -> Result<HistoryPruneProgress, StorageError> {
+    if history_mode.cycle_offset().is_none() {
+        return Ok(HistoryPruneProgress {
+            pruned: 0,
+            caboose_level: below_level,
+        });
+    }
+
+    let chain_meta_storage = ChainMetaStorage::new(persistent_storage);
+    let block_storage = BlockStorage::new(persistent_storage);
+    let block_meta_storage = BlockMetaStorage::new(persistent_storage);
+    let operations_storage = OperationsStorage::new(persistent_storage);
+    let operations_meta_storage = OperationsMetaStorage::new(persistent_storage);
+
+    // genesis is never pruned
+    let from_level = chain_meta_storage
+        .get_caboose(chain_id)?
+        .map_or(1, |caboose| std::cmp::max(*caboose.level(), 1));
+    if below_level <= from_level {
+        return Ok(HistoryPruneProgress {
+            pruned: 0,
+            caboose_level: from_level,
+        });
+    }
+
+    let to_level = std::cmp::min(
+        below_level,
+        from_level.saturating_add(max_blocks.try_into().unwrap_or(Level::MAX)),
+    );
+
+    let new_caboose = block_storage
+        .get_block_by_level(to_level)?
+        .filter(|block| block.header.level() == to_level)
+        .ok_or_else(|| StorageError::MissingKey {
+            when: "prune_history".into(),
+        })?;
+
+    let blocks =
+        block_storage.get_blocks_from_level(from_level, (to_level - from_level) as usize)?;
+
+    let mut pruned = 0;
+    for block in blocks {
+        let level = block.header.level();
+        if level >= to_level {
+            break;
+        }
+
+        match history_mode {
+            HistoryMode::Archive => (),
+            HistoryMode::Full { .. } => {
+                block_storage.delete_block_json_data(&block.hash)?;
+                block_meta_storage.delete_block_additional_data(&block.hash)?;
+            }
+            HistoryMode::Rolling { .. } => {
+                operations_storage.delete_operations(&block.hash)?;
+                operations_meta_storage.delete(&block.hash)?;
+                block_meta_storage.delete_block_additional_data(&block.hash)?;
+                block_meta_storage.delete(&block.hash)?;
+                block_storage.delete_block(&block.hash, level)?;
+            }
+        }
+        pruned += 1;
+    }
+
+    chain_meta_storage.set_caboose(
+        chain_id,
+        Head::new(
+            new_caboose.hash,
+            new_caboose.header.level(),
+            new_caboose.header.fitness().clone(),
+        ),
+    )?;
+    block_storage.flush()?;
+
+    Ok(HistoryPruneProgress {
+        pruned,
+        caboose_level: to_level,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_history_mode() {
+        assert_eq!(
+            "archive".parse::<HistoryMode>().unwrap(),
+            HistoryMode::Archive
+        );
+        assert_eq!(
+            "full".parse::<HistoryMode>().unwrap(),
+            HistoryMode::Full {
+                offset: HistoryMode::DEFAULT_CYCLE_OFFSET
+            }
+        );
+        assert_eq!(
+            "Rolling:3".parse::<HistoryMode>().unwrap(),
+            HistoryMode::Rolling { offset: 3 }
+        );
+        assert!("archive:3".parse::<HistoryMode>().is_err());
+        assert!("full:x".parse::<HistoryMode>().is_err());
+        assert!("partial".parse::<HistoryMode>().is_err());
+
+        for mode in [
+            HistoryMode::Archive,
+            HistoryMode::Full { offset: 2 },
+            HistoryMode::Rolling { offset: 7 },
+        ] {
+            assert_eq!(mode.to_string().parse::<HistoryMode>().unwrap(), mode);
+        }
+    }
+}
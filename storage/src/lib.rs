@@ -9,6 +9,7 @@ use std::sync::Arc;
 use std::{
     convert::{TryFrom, TryInto},
     path::PathBuf,
+    str::FromStr,
 };
 
 pub use rocksdb;
@@ -40,12 +41,14 @@ use crate::commit_log::{CommitLogError, CommitLogs};
 pub use crate::constants_storage::ConstantsStorage;
 pub use crate::cycle_eras_storage::CycleErasStorage;
 pub use crate::cycle_storage::CycleMetaStorage;
-use crate::database::tezedge_database::TezedgeDatabase;
+use crate::database::tezedge_database::{TezedgeDatabase, WriteBatch};
+pub use crate::history_mode::{prune_history, HistoryMode, HistoryPruneProgress};
 pub use crate::mempool_storage::{MempoolStorage, MempoolStorageKV};
 pub use crate::operations_meta_storage::{OperationsMetaStorage, OperationsMetaStorageKV};
 pub use crate::operations_storage::{
     OperationKey, OperationsStorage, OperationsStorageKV, OperationsStorageReader,
 };
+pub use crate::peer_address_book_storage::PeerAddressBookStorage;
 pub use crate::persistent::database::{Direction, IteratorMode};
 use crate::persistent::sequence::{SequenceError, Sequences};
 use crate::persistent::{DBError, Decoder, Encoder, SchemaError};
@@ -61,9 +64,11 @@ pub mod constants_storage;
 pub mod cycle_eras_storage;
 pub mod cycle_storage;
 pub mod database;
+pub mod history_mode;
 pub mod mempool_storage;
 pub mod operations_meta_storage;
 pub mod operations_storage;
+pub mod peer_address_book_storage;
 pub mod persistent;
 pub mod predecessor_storage;
 pub mod reward_storage;
@@ -244,6 +249,44 @@ pub struct Replay {
 pub struct StorageSnapshot {
     pub block: Option<BlockReference>,
     pub target_path: PathBuf,
+    pub format: SnapshotFormat,
+}
+
+/// Format of the produced storage snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    /// Copy of the TezEdge storage directories (main db + context)
+    Tezedge,
+    /// Octez `.full` snapshot, contains all blocks since genesis
+    OctezFull,
+    /// Octez `.rolling` snapshot, contains only the blocks needed to continue from the target block
+    OctezRolling,
+}
+
+impl SnapshotFormat {
+    pub fn possible_values() -> Vec<&'static str> {
+        vec!["tezedge", "full", "rolling"]
+    }
+}
+
+#[derive(Debug, Error)]
+#[error(
+    "Invalid snapshot format: {0}, expected one of {:?}",
+    SnapshotFormat::possible_values()
+)]
+pub struct ParseSnapshotFormatError(String);
+
+impl FromStr for SnapshotFormat {
+    type Err = ParseSnapshotFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "tezedge" => Ok(Self::Tezedge),
+            "full" => Ok(Self::OctezFull),
+            "rolling" => Ok(Self::OctezRolling),
+            _ => Err(ParseSnapshotFormatError(s.to_owned())),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -416,6 +459,9 @@ fn resolve_block_data(
 }
 
 /// Stores apply result to storage and mark block as applied, if everythnig is ok.
+///
+/// All the main database writes are committed in a single batch, so the block
+/// is either stored as applied with all its data, or not at all.
 pub fn store_applied_block_result(
     chain_meta_storage: &ChainMetaStorage,
     block_storage: &BlockStorage,
@@ -434,7 +480,8 @@ pub fn store_applied_block_result(
         block_result.block_header_proto_metadata_bytes,
         block_result.operations_proto_metadata_bytes,
     );
-    block_storage.put_block_json_data(block_hash, block_json_data)?;
+    let mut batch = WriteBatch::new();
+    block_storage.put_block_json_data_batched(&mut batch, block_hash, block_json_data)?;
 
     // store additional data
     let block_additional_data = BlockAdditionalData::new(
@@ -460,41 +507,57 @@ pub fn store_applied_block_result(
         },
         block_result.ops_metadata_hashes,
     );
-    block_meta_storage.put_block_additional_data(block_hash, &block_additional_data)?;
+    block_meta_storage.put_block_additional_data_batched(
+        &mut batch,
+        block_hash,
+        &block_additional_data,
+    )?;
 
     // TODO: check context checksum or context_hash
 
     // populate predecessor storage
-    block_meta_storage.store_predecessors(block_hash, block_metadata)?;
+    block_meta_storage.store_predecessors_batched(&mut batch, block_hash, block_metadata)?;
 
     // populate cycle data if is present in the response
     for cycle_data in block_result.cycle_rolls_owner_snapshots.into_iter() {
-        cycle_meta_storage.store_cycle_data(cycle_data)?;
+        cycle_meta_storage.store_cycle_data_batched(&mut batch, cycle_data)?;
     }
 
     // store new constants if they are present
     if let Some(constants) = block_result.new_protocol_constants_json {
-        constants_storage
-            .store_constants_data(block_result.next_protocol_hash.clone(), constants)?;
+        constants_storage.store_constants_data_batched(
+            &mut batch,
+            block_result.next_protocol_hash.clone(),
+            constants,
+        )?;
     }
 
     // store new cycle eras if they are present
     if let Some(new_cycle_eras) = block_result.new_cycle_eras_json {
-        cycle_eras_storage
-            .store_cycle_eras_data(block_result.next_protocol_hash.clone(), new_cycle_eras)?;
+        cycle_eras_storage.store_cycle_eras_data_batched(
+            &mut batch,
+            block_result.next_protocol_hash.clone(),
+            new_cycle_eras,
+        )?;
     }
 
     // if everything is stored and ok, we can considere this block as applied
     // mark current head as applied
-    block_metadata.set_is_applied(true);
-    block_meta_storage.put(block_hash, block_metadata)?;
+    let mut applied_metadata = block_metadata.clone();
+    applied_metadata.set_is_applied(true);
+    block_meta_storage.put_batched(&mut batch, block_hash, &applied_metadata)?;
 
     // TODO(zura): maybe move to separate storage call.
-    chain_meta_storage.set_current_head(
-        block_metadata.chain_id(),
-        Head::new(block_hash.clone(), block_metadata.level(), block_fitness),
+    chain_meta_storage.set_current_head_batched(
+        &mut batch,
+        applied_metadata.chain_id(),
+        Head::new(block_hash.clone(), applied_metadata.level(), block_fitness),
     )?;
 
+    // commit everything at once, the block is applied only if all of it is stored
+    block_meta_storage.commit_batch(batch)?;
+    *block_metadata = applied_metadata;
+
     // Flush to disk
     block_storage.flush()?;
 
@@ -641,6 +704,7 @@ pub mod initializer {
     use slog::{error, Logger};
 
     use crate::database::error::Error as DatabaseError;
+    use crate::database::schema_migration::{SchemaMigrationOptions, SchemaMigrations};
     use crate::database::tezedge_database::{TezedgeDatabase, TezedgeDatabaseBackendConfiguration};
     use crate::persistent::database::{open_kv, RocksDbKeyValueSchema};
     use crate::persistent::{open_main_db, DBError, DbConfiguration};
@@ -685,6 +749,7 @@ pub mod initializer {
                 crate::ShellAutomatonStateStorage::descriptor(cache),
                 crate::ShellAutomatonActionStorage::descriptor(cache),
                 crate::ShellAutomatonActionMetaStorage::descriptor(cache),
+                crate::PeerAddressBookStorage::descriptor(cache),
                 crate::reward_storage::RewardStorage::descriptor(cache),
             ]
         }
@@ -741,10 +806,36 @@ pub mod initializer {
         db_version: i64,
         expected_main_chain: &MainChain,
         backend_config: TezedgeDatabaseBackendConfiguration,
+        schema_migration: &SchemaMigrationOptions,
     ) -> Result<Arc<TezedgeDatabase>, DatabaseError> {
         let db = Arc::new(open_main_db(kv, config, backend_config, log.clone())?);
 
-        match check_database_compatibility(db.clone(), db_version, expected_main_chain, log) {
+        // database of another chain is rejected before anything is migrated
+        match check_main_chain(db.clone(), expected_main_chain, log) {
+            Ok(false) => {
+                return Err(DatabaseError::DatabaseIncompatibility {
+                    name: format!(
+                        "Database is incompatible with chain {}",
+                        expected_main_chain.chain_name
+                    ),
+                })
+            }
+            Err(e) => {
+                return Err(DatabaseError::DatabaseIncompatibility {
+                    name: format!("Failed to verify database compatibility reason: '{}'", e),
+                })
+            }
+            Ok(true) => (),
+        }
+
+        // older database is upgraded before its version is checked
+        if let Err(e) = SchemaMigrations::main_db().run(&db, db_version, schema_migration, log) {
+            return Err(DatabaseError::DatabaseIncompatibility {
+                name: format!("Failed to migrate database schema, reason: '{}'", e),
+            });
+        }
+
+        match check_database_version(db.clone(), db_version, log) {
             Ok(false) => Err(DatabaseError::DatabaseIncompatibility {
                 name: format!("Database is incompatible with version {}", db_version),
             }),
@@ -755,10 +846,9 @@ pub mod initializer {
         }
     }
 
-    fn check_database_compatibility(
+    fn check_database_version(
         db: Arc<TezedgeDatabase>,
         expected_database_version: i64,
-        expected_main_chain: &MainChain,
         log: &Logger,
     ) -> Result<bool, StorageError> {
         let mut system_info = SystemStorage::new(db);
@@ -779,7 +869,15 @@ pub mod initializer {
         if !db_version_ok {
             error!(log, "Incompatible database version found (expected {}, found {}). Please re-sync your node to empty storage - see configuration!", expected_database_version, found_database_version);
         }
+        Ok(db_version_ok)
+    }
 
+    fn check_main_chain(
+        db: Arc<TezedgeDatabase>,
+        expected_main_chain: &MainChain,
+        log: &Logger,
+    ) -> Result<bool, StorageError> {
+        let mut system_info = SystemStorage::new(db);
         let tezos_env_main_chain_id = &expected_main_chain.chain_id;
         let tezos_env_main_chain_name = &expected_main_chain.chain_name;
 
@@ -813,7 +911,7 @@ pub mod initializer {
             );
         }
 
-        Ok(db_version_ok && chain_id_ok)
+        Ok(chain_id_ok)
     }
 }
 
@@ -940,6 +1038,7 @@ pub mod tests_common {
                         ShellAutomatonStateStorage::descriptor(&db_cache),
                         ShellAutomatonActionStorage::descriptor(&db_cache),
                         ShellAutomatonActionMetaStorage::descriptor(&db_cache),
+                        PeerAddressBookStorage::descriptor(&db_cache),
                         RewardStorage::descriptor(&db_cache),
                     ],
                     &cfg,
@@ -973,6 +1072,7 @@ pub mod tests_common {
                         ShellAutomatonStateStorage::name(),
                         ShellAutomatonActionStorage::name(),
                         ShellAutomatonActionMetaStorage::name(),
+                        PeerAddressBookStorage::name(),
                         RewardStorage::name(),
                     ],
                 )?)
@@ -998,6 +1098,7 @@ pub mod tests_common {
                         ShellAutomatonStateStorage::descriptor(&db_cache),
                         ShellAutomatonActionStorage::descriptor(&db_cache),
                         ShellAutomatonActionMetaStorage::descriptor(&db_cache),
+                        PeerAddressBookStorage::descriptor(&db_cache),
                         RewardStorage::descriptor(&db_cache),
                     ],
                     &cfg,
@@ -104,6 +104,11 @@ impl OperationsMetaStorage {
     pub fn contains(&self, block_hash: &BlockHash) -> Result<bool, StorageError> {
         self.kv.contains(block_hash).map_err(StorageError::from)
     }
+
+    #[inline]
+    pub fn delete(&self, block_hash: &BlockHash) -> Result<(), StorageError> {
+        self.kv.delete(block_hash).map_err(StorageError::from)
+    }
 }
 
 impl KeyValueSchema for OperationsMetaStorage {
@@ -47,6 +47,16 @@ impl OperationsStorage {
         self.put(&key, message)
     }
 
+    /// Removes operations of all validation passes stored for the block.
+    pub fn delete_operations(&self, block_hash: &BlockHash) -> Result<(), StorageError> {
+        for message in self.get_operations(block_hash)? {
+            self.kv
+                .delete(&OperationKey::from(message.operations_for_block()))
+                .map_err(StorageError::from)?;
+        }
+        Ok(())
+    }
+
     #[inline]
     fn put(
         &self,
@@ -0,0 +1,78 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::sync::Arc;
+
+use rocksdb::{Cache, ColumnFamilyDescriptor};
+
+use crypto::hash::ChainId;
+
+use crate::database::tezedge_database::{KVStoreKeyValueSchema, TezedgeDatabaseWithIterator};
+use crate::persistent::database::{default_table_options, RocksDbKeyValueSchema};
+use crate::persistent::{Decoder, Encoder, KeyValueSchema};
+use crate::{PersistentStorage, StorageError};
+
+pub type PeerAddressBookStorageKV =
+    dyn TezedgeDatabaseWithIterator<PeerAddressBookStorage> + Sync + Send;
+
+/// Storage for the peer address book of the shell automaton, so that known
+/// good and bad peer addresses survive restarts.
+///
+/// The whole address book is stored under the chain id it was built for.
+#[derive(Clone)]
+pub struct PeerAddressBookStorage {
+    kv: Arc<PeerAddressBookStorageKV>,
+}
+
+impl PeerAddressBookStorage {
+    pub fn new(persistent_storage: &PersistentStorage) -> Self {
+        Self {
+            kv: persistent_storage.main_db(),
+        }
+    }
+
+    #[inline]
+    pub fn put<T>(&self, chain_id: &ChainId, address_book: &T) -> Result<(), StorageError>
+    where
+        T: Encoder,
+    {
+        self.kv
+            .put(chain_id, &address_book.encode()?)
+            .map_err(StorageError::from)
+    }
+
+    #[inline]
+    pub fn get<T>(&self, chain_id: &ChainId) -> Result<Option<T>, StorageError>
+    where
+        T: Decoder,
+    {
+        self.kv
+            .get(chain_id)
+            .map_err(StorageError::from)?
+            .map(|encoded| T::decode(&encoded).map_err(StorageError::from))
+            .transpose()
+    }
+}
+
+impl KeyValueSchema for PeerAddressBookStorage {
+    type Key = ChainId;
+    type Value = Vec<u8>;
+}
+
+impl RocksDbKeyValueSchema for PeerAddressBookStorage {
+    fn descriptor(cache: &Cache) -> ColumnFamilyDescriptor {
+        let cf_opts = default_table_options(cache);
+        ColumnFamilyDescriptor::new(Self::name(), cf_opts)
+    }
+
+    #[inline]
+    fn name() -> &'static str {
+        "peer_address_book_storage"
+    }
+}
+
+impl KVStoreKeyValueSchema for PeerAddressBookStorage {
+    fn column_name() -> &'static str {
+        Self::name()
+    }
+}
@@ -81,7 +81,8 @@ pub trait MultiInstanceable {
         }
     }
 }
-fn edgekv_db_cols() -> Vec<&'static str> {
+/// Names of all the main database columns.
+pub fn maindb_columns() -> Vec<&'static str> {
     vec![
         crate::block_storage::BlockPrimaryIndex::column_name(),
         crate::block_storage::BlockByLevelIndex::column_name(),
@@ -101,6 +102,8 @@ fn edgekv_db_cols() -> Vec<&'static str> {
         crate::ShellAutomatonStateStorage::column_name(),
         crate::ShellAutomatonActionStorage::column_name(),
         crate::ShellAutomatonActionMetaStorage::column_name(),
+        crate::PeerAddressBookStorage::column_name(),
+        crate::reward_storage::RewardStorage::column_name(),
     ]
 }
 
@@ -124,7 +127,7 @@ pub fn open_main_db<C: RocksDbColumnFactory>(
             }
         }
         TezedgeDatabaseBackendConfiguration::EdgeKV => TezedgeDatabaseBackendOptions::EdgeKV(
-            EdgeKVBackend::new(config.db_path.as_path(), edgekv_db_cols())?,
+            EdgeKVBackend::new(config.db_path.as_path(), maindb_columns())?,
         ),
     };
     Ok(TezedgeDatabase::new(backend, log))
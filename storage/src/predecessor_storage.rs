@@ -9,7 +9,9 @@ use serde::{Deserialize, Serialize};
 use crypto::hash::BlockHash;
 
 use crate::block_meta_storage::Meta;
-use crate::database::tezedge_database::{KVStoreKeyValueSchema, TezedgeDatabaseWithIterator};
+use crate::database::tezedge_database::{
+    KVStoreBatch, KVStoreKeyValueSchema, TezedgeDatabaseWithIterator, WriteBatch,
+};
 use crate::persistent::database::{default_table_options, RocksDbKeyValueSchema};
 use crate::persistent::{BincodeEncoded, KeyValueSchema};
 use crate::{PersistentStorage, StorageError};
@@ -49,6 +51,25 @@ impl PredecessorStorage {
         block_hash: &BlockHash,
         block_meta: &Meta,
         stored_predecessors_size: u32,
+    ) -> Result<(), StorageError> {
+        let mut batch = WriteBatch::new();
+        self.store_predecessors_batched(
+            &mut batch,
+            block_hash,
+            block_meta,
+            stored_predecessors_size,
+        )?;
+        self.kv.commit_batch(batch).map_err(StorageError::from)
+    }
+
+    /// Same as [PredecessorStorage::store_predecessors], but the predecessors
+    /// are only stored once the `batch` is committed.
+    pub fn store_predecessors_batched(
+        &self,
+        batch: &mut WriteBatch,
+        block_hash: &BlockHash,
+        block_meta: &Meta,
+        stored_predecessors_size: u32,
     ) -> Result<(), StorageError> {
         if let Some(direct_predecessor) = block_meta.predecessor() {
             // genesis
@@ -56,7 +77,7 @@ impl PredecessorStorage {
                 return Ok(());
             } else {
                 // put the direct predecessor to slot 0
-                self.put(
+                batch.put::<Self>(
                     &PredecessorKey::new(block_hash.clone(), 0),
                     direct_predecessor,
                 )?;
@@ -69,7 +90,7 @@ impl PredecessorStorage {
                     if let Some(p) = self.get(&predecessor_key)? {
                         let key =
                             PredecessorKey::new(block_hash.clone(), predecessor_exponent_slot);
-                        self.put(&key, &p)?;
+                        batch.put::<Self>(&key, &p)?;
                         predecessor = p;
                     } else {
                         return Ok(());
@@ -96,6 +117,11 @@ impl PredecessorStorage {
     pub fn get(&self, key: &PredecessorKey) -> Result<Option<BlockHash>, StorageError> {
         self.kv.get(key).map_err(StorageError::from)
     }
+
+    #[inline]
+    pub fn delete(&self, key: &PredecessorKey) -> Result<(), StorageError> {
+        self.kv.delete(key).map_err(StorageError::from)
+    }
 }
 
 impl BincodeEncoded for PredecessorKey {}
@@ -8,7 +8,9 @@ use serde::{Deserialize, Serialize};
 
 use crypto::hash::ChainId;
 
-use crate::database::tezedge_database::{KVStoreKeyValueSchema, TezedgeDatabaseWithIterator};
+use crate::database::tezedge_database::{
+    KVStoreBatch, KVStoreKeyValueSchema, TezedgeDatabaseWithIterator, WriteBatch,
+};
 use crate::persistent::database::{default_table_options, RocksDbKeyValueSchema};
 use crate::persistent::{BincodeEncoded, KeyValueSchema};
 use crate::StorageError;
@@ -16,6 +18,9 @@ use crate::StorageError;
 pub type SystemStorageKv = dyn TezedgeDatabaseWithIterator<SystemStorage> + Sync + Send;
 pub type DbVersion = i64;
 
+/// Version of the main database schema written by this version of the node.
+pub const MAIN_DB_VERSION: DbVersion = 21;
+
 /// Represents storage of the system settings.
 ///
 /// This storage differs from the other in regard that it is not exposing key-value pair
@@ -29,6 +34,7 @@ impl SystemStorage {
     const CHAIN_ID: &'static str = "chain_id";
     const DB_VERSION: &'static str = "db_version";
     const CHAIN_NAME: &'static str = "chain_name";
+    const DB_MIGRATION: &'static str = "db_migration";
 
     pub fn new(kv: Arc<SystemStorageKv>) -> Self {
         SystemStorage { kv }
@@ -85,6 +91,40 @@ impl SystemStorage {
             .map_err(StorageError::from)
     }
 
+    /// Version the database is being migrated from, if the last schema
+    /// migration didn't finish.
+    #[inline]
+    pub fn get_db_migration(&self) -> Result<Option<DbVersion>, StorageError> {
+        self.kv
+            .get(&Self::DB_MIGRATION.to_string())
+            .map(|result| match result {
+                Some(SystemValue::Integer(value)) => Some(value),
+                _ => None,
+            })
+            .map_err(StorageError::from)
+    }
+
+    #[inline]
+    pub fn set_db_migration(&mut self, from_db_version: DbVersion) -> Result<(), StorageError> {
+        self.kv
+            .put(
+                &Self::DB_MIGRATION.to_string(),
+                &SystemValue::Integer(from_db_version),
+            )
+            .map_err(StorageError::from)
+    }
+
+    /// Sets the `db_version` and clears the migration in progress at once.
+    pub fn finish_db_migration(&mut self, db_version: DbVersion) -> Result<(), StorageError> {
+        let mut batch = WriteBatch::new();
+        batch.put::<Self>(
+            &Self::DB_VERSION.to_string(),
+            &SystemValue::Integer(db_version),
+        )?;
+        batch.delete::<Self>(&Self::DB_MIGRATION.to_string())?;
+        self.kv.commit_batch(batch).map_err(StorageError::from)
+    }
+
     #[inline]
     pub fn get_chain_name(&self) -> Result<Option<String>, StorageError> {
         self.kv
@@ -0,0 +1,257 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::convert::TryInto;
+
+use anyhow::Error;
+
+use crypto::hash::{BlockHash, ChainId};
+use storage::tests_common::{create_logger, log_level, TmpStorage};
+use storage::*;
+use tezos_messages::p2p::encoding::fitness::Fitness;
+use tezos_messages::p2p::encoding::prelude::*;
+
+const BLOCKS_COUNT: i32 = 30;
+const JSON_DATA_SIZE: usize = 16 * 1024;
+
+#[test]
+fn test_prune_history_rolling() -> Result<(), Error> {
+    let tmp_storage = TmpStorage::create_to_out_dir("__history_mode_rolling")?;
+    let chain_id = make_chain_id();
+    let blocks = store_chain(tmp_storage.storage(), &chain_id)?;
+
+    let block_storage = BlockStorage::new(tmp_storage.storage());
+    let block_meta_storage = BlockMetaStorage::new(tmp_storage.storage());
+    let history_mode = HistoryMode::Rolling { offset: 1 };
+
+    let data_size = commit_log_disk_usage(&tmp_storage);
+    prune_until_done(tmp_storage.storage(), &chain_id, &history_mode, 20, 7)?;
+
+    for block in &blocks[1..20] {
+        assert!(block_storage.get(&block.hash)?.is_none());
+        assert!(block_meta_storage.get(&block.hash)?.is_none());
+        assert!(block_meta_storage
+            .get_additional_data(&block.hash)?
+            .is_none());
+    }
+    for block in blocks[..1].iter().chain(&blocks[20..]) {
+        assert_eq!(block_storage.get(&block.hash)?.as_ref(), Some(block));
+        assert!(block_storage.get_json_data(&block.hash)?.is_some());
+        assert!(block_meta_storage.get(&block.hash)?.is_some());
+        assert!(block_meta_storage
+            .get_additional_data(&block.hash)?
+            .is_some());
+    }
+
+    // Headers and json data of the pruned blocks are released
+    if cfg!(target_os = "linux") {
+        assert!(commit_log_disk_usage(&tmp_storage) < data_size);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_prune_history_full() -> Result<(), Error> {
+    let tmp_storage = TmpStorage::create_to_out_dir("__history_mode_full")?;
+    let chain_id = make_chain_id();
+    let blocks = store_chain(tmp_storage.storage(), &chain_id)?;
+
+    let block_storage = BlockStorage::new(tmp_storage.storage());
+    let block_meta_storage = BlockMetaStorage::new(tmp_storage.storage());
+    let history_mode = HistoryMode::Full { offset: 1 };
+
+    let data_size = commit_log_disk_usage(&tmp_storage);
+    prune_until_done(tmp_storage.storage(), &chain_id, &history_mode, 20, 7)?;
+
+    // Headers are kept, metadata is removed
+    for block in &blocks[1..20] {
+        assert_eq!(block_storage.get(&block.hash)?.as_ref(), Some(block));
+        assert_eq!(
+            block_storage
+                .get_block_by_level(block.header.level())?
+                .as_ref(),
+            Some(block)
+        );
+        assert!(block_storage.get_json_data(&block.hash)?.is_none());
+        assert!(block_meta_storage.get(&block.hash)?.is_some());
+        assert!(block_meta_storage
+            .get_additional_data(&block.hash)?
+            .is_none());
+    }
+    for block in blocks[..1].iter().chain(&blocks[20..]) {
+        assert!(block_storage.get_json_data(&block.hash)?.is_some());
+        assert!(block_meta_storage
+            .get_additional_data(&block.hash)?
+            .is_some());
+    }
+
+    // Json data of the pruned blocks is released
+    if cfg!(target_os = "linux") {
+        assert!(commit_log_disk_usage(&tmp_storage) < data_size);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_prune_history_continues_from_caboose() -> Result<(), Error> {
+    let tmp_storage = TmpStorage::create_to_out_dir("__history_mode_caboose")?;
+    let chain_id = make_chain_id();
+    let blocks = store_chain(tmp_storage.storage(), &chain_id)?;
+
+    let chain_meta_storage = ChainMetaStorage::new(tmp_storage.storage());
+    let history_mode = HistoryMode::Rolling { offset: 1 };
+
+    // Archive mode never prunes
+    let progress = prune_history(
+        tmp_storage.storage(),
+        &chain_id,
+        &HistoryMode::Archive,
+        20,
+        100,
+    )?;
+    assert_eq!(progress.pruned, 0);
+    assert!(chain_meta_storage.get_caboose(&chain_id)?.is_none());
+
+    // One batch at a time, the caboose is the lowest block left
+    let progress = prune_history(tmp_storage.storage(), &chain_id, &history_mode, 20, 7)?;
+    assert_eq!(
+        progress,
+        HistoryPruneProgress {
+            pruned: 7,
+            caboose_level: 8
+        }
+    );
+    assert!(!progress.is_done(20));
+    assert_eq!(
+        chain_meta_storage
+            .get_caboose(&chain_id)?
+            .map(|caboose| caboose.block_hash().clone()),
+        Some(blocks[8].hash.clone())
+    );
+
+    let progress = prune_until_done(tmp_storage.storage(), &chain_id, &history_mode, 20, 7)?;
+    assert_eq!(progress.caboose_level, 20);
+
+    // Nothing left below the caboose
+    let progress = prune_history(tmp_storage.storage(), &chain_id, &history_mode, 20, 7)?;
+    assert_eq!(progress.pruned, 0);
+    assert!(progress.is_done(20));
+
+    // Pruning further starts from the caboose
+    let progress = prune_history(tmp_storage.storage(), &chain_id, &history_mode, 25, 7)?;
+    assert_eq!(
+        progress,
+        HistoryPruneProgress {
+            pruned: 5,
+            caboose_level: 25
+        }
+    );
+
+    Ok(())
+}
+
+fn prune_until_done(
+    persistent_storage: &PersistentStorage,
+    chain_id: &ChainId,
+    history_mode: &HistoryMode,
+    below_level: i32,
+    max_blocks: usize,
+) -> Result<HistoryPruneProgress, Error> {
+    loop {
+        let progress = prune_history(
+            persistent_storage,
+            chain_id,
+            history_mode,
+            below_level,
+            max_blocks,
+        )?;
+        assert!(progress.pruned <= max_blocks);
+        if progress.is_done(below_level) {
+            return Ok(progress);
+        }
+    }
+}
+
+/// Stores `BLOCKS_COUNT` blocks, from genesis, with their json and additional data.
+fn store_chain(
+    persistent_storage: &PersistentStorage,
+    chain_id: &ChainId,
+) -> Result<Vec<BlockHeaderWithHash>, Error> {
+    let log = create_logger(log_level());
+    let block_storage = BlockStorage::new(persistent_storage);
+    let block_meta_storage = BlockMetaStorage::new(persistent_storage);
+
+    let mut blocks: Vec<BlockHeaderWithHash> = Vec::new();
+    for level in 0..BLOCKS_COUNT {
+        let predecessor = match blocks.last() {
+            Some(block) => block.hash.clone(),
+            None => "BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe".try_into()?,
+        };
+        let block = make_block_header(level, predecessor)?;
+
+        block_storage.put_block_header(&block)?;
+        block_meta_storage.put_block_header(&block, chain_id, &log)?;
+        block_storage.put_block_json_data(
+            &block.hash,
+            BlockJsonData::new("a".repeat(JSON_DATA_SIZE), vec![1; 128], vec![]),
+        )?;
+        block_meta_storage.put_block_additional_data(
+            &block.hash,
+            &BlockAdditionalData::new(
+                120,
+                0,
+                "PsBabyM1eUXZseaJdmXFApDSBqj8YBfwELoxZHHW77EMcAbbwAS".try_into()?,
+                "PsBabyM1eUXZseaJdmXFApDSBqj8YBfwELoxZHHW77EMcAbbwAS".try_into()?,
+                None,
+                None,
+                None,
+            ),
+        )?;
+
+        blocks.push(block);
+    }
+    block_storage.flush()?;
+
+    Ok(blocks)
+}
+
+fn make_block_header(level: i32, predecessor: BlockHash) -> Result<BlockHeaderWithHash, Error> {
+    let header = BlockHeaderBuilder::default()
+        .level(level)
+        .proto(1)
+        .predecessor(predecessor)
+        .timestamp((1_600_000_000 + level as i64 * 30).into())
+        .validation_pass(4)
+        .operations_hash("LLoaGLRPRx3Zf8kB4ACtgku8F4feeBiskeb41J1ciwfcXB3KzHKXc".try_into()?)
+        .fitness(Fitness::default())
+        .context("CoVmAcMV64uAQo8XvfLr9VDuz7HVZLT4cgK1w1qYmTjQNbGwQwDd".try_into()?)
+        .protocol_data(vec![0, 1, 2, 3, 4, 5, 6, 7, 8].into())
+        .build()
+        .map_err(Error::msg)?;
+
+    Ok(BlockHeaderWithHash::new(header)?)
+}
+
+fn make_chain_id() -> ChainId {
+    ChainId::from_base58_check("NetXdQprcVkpaWU").expect("valid chain id")
+}
+
+/// Disk space used by the block commit log.
+fn commit_log_disk_usage(tmp_storage: &TmpStorage) -> u64 {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        std::fs::metadata(tmp_storage.path().join("block_storage").join("table.data"))
+            .map(|metadata| metadata.blocks())
+            .unwrap_or(0)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = tmp_storage;
+        0
+    }
+}
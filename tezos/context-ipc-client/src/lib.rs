@@ -1,17 +1,29 @@
 // Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use std::sync::Arc;
+use std::sync::{mpsc, Arc, Mutex};
 
 use async_ipc::IpcError;
 use crypto::hash::ContextHash;
+use tezos_context::initializer::{initialize_readonly_ipc_index, IndexInitializationError};
+use tezos_context::proof::ContextProof;
+use tezos_context::{ContextError, TezedgeIndex};
 use tezos_context_api::{ContextKeyOwned, ContextValue, StringTreeObject};
 use tezos_protocol_ipc_client::{ProtocolRunnerApi, ProtocolServiceError};
 use thiserror::Error;
+use tokio::sync::oneshot;
 
 #[derive(Clone)]
 pub struct TezedgeContextClient {
     tezos_protocol_api: Arc<ProtocolRunnerApi>,
+    /// Requests to the thread building the Merkle proofs, started by the first request
+    proof_requests: Arc<Mutex<Option<mpsc::Sender<ProofRequest>>>>,
+}
+
+struct ProofRequest {
+    context_hash: ContextHash,
+    key: ContextKeyOwned,
+    result: oneshot::Sender<Result<Option<ContextProof>, TezedgeContextClientError>>,
 }
 
 #[derive(Debug, Error)]
@@ -26,11 +38,39 @@ pub enum TezedgeContextClientError {
         #[from]
         reason: IpcError,
     },
+    #[error("TezEdge context IPC server is not enabled")]
+    ContextIpcUnavailable,
+    #[error("Failed to connect to the context IPC server: {reason}")]
+    IndexInitializationError {
+        #[from]
+        reason: IndexInitializationError,
+    },
+    #[error("Context error: {reason}")]
+    ContextError {
+        #[from]
+        reason: ContextError,
+    },
+    #[error("Context proof thread is not running")]
+    ProofThreadStopped,
+}
+
+impl TezedgeContextClientError {
+    /// Whether the context was garbage collected while it was read, the
+    /// request can be retried.
+    pub fn is_garbage_collected(&self) -> bool {
+        match self {
+            Self::ContextError { reason } => reason.is_garbage_collected(),
+            _ => false,
+        }
+    }
 }
 
 impl TezedgeContextClient {
     pub fn new(tezos_protocol_api: Arc<ProtocolRunnerApi>) -> Self {
-        Self { tezos_protocol_api }
+        Self {
+            tezos_protocol_api,
+            proof_requests: Arc::new(Mutex::new(None)),
+        }
     }
 
     pub async fn get_key_from_history(
@@ -72,4 +112,238 @@ impl TezedgeContextClient {
             .get_context_tree_by_prefix(context_hash, prefix, depth)
             .await?)
     }
+
+    /// Builds a Merkle proof of `key` in the context of `context_hash`.
+    ///
+    /// Unlike the other queries, this one is served from the TezEdge context through
+    /// its IPC server, the OCaml side of the protocol runner is not involved.
+    pub async fn get_merkle_proof(
+        &self,
+        context_hash: &ContextHash,
+        key: ContextKeyOwned,
+    ) -> Result<Option<ContextProof>, TezedgeContextClientError> {
+        let socket_path = self
+            .tezos_protocol_api
+            .context_ipc_socket_path()
+            .ok_or(TezedgeContextClientError::ContextIpcUnavailable)?;
+        self.tezos_protocol_api.wait_for_context_init().await.ok();
+
+        let (result, receiver) = oneshot::channel();
+        let request = ProofRequest {
+            context_hash: context_hash.clone(),
+            key,
+            result,
+        };
+        self.send_proof_request(socket_path, request)?;
+
+        receiver
+            .await
+            .map_err(|_| TezedgeContextClientError::ProofThreadStopped)?
+    }
+
+    /// Sends the request to the proof thread, (re)starting it if it is not running.
+    fn send_proof_request(
+        &self,
+        socket_path: String,
+        request: ProofRequest,
+    ) -> Result<(), TezedgeContextClientError> {
+        let mut proof_requests = self
+            .proof_requests
+            .lock()
+            .map_err(|_| TezedgeContextClientError::ProofThreadStopped)?;
+        let request = match proof_requests.as_ref() {
+            Some(sender) => match sender.send(request) {
+                Ok(()) => return Ok(()),
+                Err(mpsc::SendError(request)) => request,
+            },
+            None => request,
+        };
+
+        let (sender, requests) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("ctx-proofs".to_string())
+            .spawn(move || serve_merkle_proofs(socket_path, requests))
+            .map_err(|_| TezedgeContextClientError::ProofThreadStopped)?;
+        sender
+            .send(request)
+            .map_err(|_| TezedgeContextClientError::ProofThreadStopped)?;
+        *proof_requests = Some(sender);
+        Ok(())
+    }
+}
+
+/// Builds the Merkle proofs with one index connected to the context IPC server,
+/// `TezedgeIndex` is not `Send`, so it lives on this thread. The thread ends when
+/// the client is dropped.
+///
+/// The index drops the objects and strings it cached when it sees that the
+/// context has been garbage collected since they were read. When the collection
+/// happens while a proof is built, the index is rebuilt and the proof built again.
+fn serve_merkle_proofs(socket_path: String, requests: mpsc::Receiver<ProofRequest>) {
+    let mut index = None;
+
+    for request in requests {
+        let mut result = get_merkle_proof(&mut index, &socket_path, &request);
+        if matches!(&result, Err(err) if err.is_garbage_collected()) {
+            index = None;
+            result = get_merkle_proof(&mut index, &socket_path, &request);
+        }
+        // the connection may be broken, reconnect for the next request
+        if result.is_err() {
+            index = None;
+        }
+        request.result.send(result).ok();
+    }
+}
+
+fn get_merkle_proof(
+    index: &mut Option<TezedgeIndex>,
+    socket_path: &str,
+    request: &ProofRequest,
+) -> Result<Option<ContextProof>, TezedgeContextClientError> {
+    let index = match index {
+        Some(index) => index,
+        None => index.insert(initialize_readonly_ipc_index(socket_path)?),
+    };
+    let key: Vec<&str> = request.key.iter().map(String::as_str).collect();
+    Ok(index.get_merkle_proof(&request.context_hash, &key)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc};
+
+    use crypto::hash::ContextHash;
+    use parking_lot::RwLock;
+    use tezos_context::kv_store::persistent::PersistentConfiguration;
+    use tezos_context::kv_store::readonly_ipc::IpcContextListener;
+    use tezos_context::proof::{verify_proof, ContextProof};
+    use tezos_context::{
+        ContextKeyValueStore, IndexApi, Persistent, ProtocolContextApi, ShellContextApi,
+        TezedgeContext, TezedgeIndex,
+    };
+    use tokio::sync::oneshot;
+
+    use super::{serve_merkle_proofs, ProofRequest};
+
+    fn commit_level(
+        index: &TezedgeIndex,
+        parent: Option<&ContextHash>,
+        level: usize,
+    ) -> ContextHash {
+        let context = match parent {
+            Some(parent) => index.checkout(parent).unwrap().unwrap(),
+            None => TezedgeContext::new(index.clone(), None, None),
+        };
+        let context = context
+            .add(&["level", &level.to_string()], &[level as u8; 50])
+            .unwrap();
+        context
+            .commit("Tezos".to_string(), "Commit".to_string(), level as i64)
+            .unwrap()
+    }
+
+    fn prove(
+        requests: &mpsc::Sender<ProofRequest>,
+        context_hash: &ContextHash,
+        key: &[&str],
+    ) -> Option<ContextProof> {
+        let (result, receiver) = oneshot::channel();
+        requests
+            .send(ProofRequest {
+                context_hash: context_hash.clone(),
+                key: key.iter().map(|s| s.to_string()).collect(),
+                result,
+            })
+            .unwrap();
+        receiver.blocking_recv().unwrap().unwrap()
+    }
+
+    fn assert_proof(
+        requests: &mpsc::Sender<ProofRequest>,
+        context_hash: &ContextHash,
+        level: usize,
+    ) {
+        let level = level.to_string();
+        let key = ["level", level.as_str()];
+        let proof = prove(requests, context_hash, &key).unwrap();
+        assert_eq!(
+            verify_proof(&proof, context_hash, &key).unwrap(),
+            Some(vec![level.parse::<u8>().unwrap(); 50])
+        );
+    }
+
+    #[test]
+    fn test_merkle_proof_after_garbage_collection() {
+        let db_path = std::env::temp_dir()
+            .join(format!("tezedge-context-proof-gc-{}", std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_string();
+        let socket_path = format!("{}.sock", db_path);
+        std::fs::remove_dir_all(&db_path).ok();
+
+        let mut repo = Persistent::try_new(PersistentConfiguration {
+            db_path: Some(db_path.clone()),
+            startup_check: false,
+            read_mode: false,
+        })
+        .unwrap();
+        repo.enable_garbage_collection(2);
+
+        let repo: Arc<RwLock<ContextKeyValueStore>> = Arc::new(RwLock::new(repo));
+        tezos_context::ffi::TEZEDGE_CONTEXT_REPOSITORY
+            .write()
+            .replace(Arc::clone(&repo));
+
+        let mut listener = IpcContextListener::try_new(&socket_path).unwrap();
+        std::thread::spawn(move || {
+            let log = slog::Logger::root(slog::Discard, slog::o!());
+            listener.handle_incoming_connections(&log);
+        });
+
+        let (requests, receiver) = mpsc::channel();
+        let proof_socket_path = socket_path.clone();
+        std::thread::spawn(move || serve_merkle_proofs(proof_socket_path, receiver));
+
+        let mut index = TezedgeIndex::new(repo, None);
+        let mut hashes = Vec::new();
+        for level in 0..3 {
+            index.cycle_started().unwrap();
+            let hash = commit_level(&index, hashes.last(), level);
+            index.block_applied(level as u32, &hash).unwrap();
+            hashes.push(hash);
+        }
+
+        // The proof index reads and caches objects of the repository
+        // before it is collected
+        assert_proof(&requests, &hashes[2], 1);
+
+        let mut level = hashes.len();
+        while index.exists(&hashes[0]).unwrap() {
+            assert!(level < 1000, "The repository has not been collected");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+
+            let hash = commit_level(&index, hashes.last(), level);
+            index.block_applied(level as u32, &hash).unwrap();
+            hashes.push(hash);
+            level += 1;
+        }
+
+        // The proofs are built from the new repository
+        assert!(prove(&requests, &hashes[0], &["level", "0"]).is_none());
+        for (level, hash) in hashes.iter().enumerate().skip(1) {
+            assert_proof(&requests, hash, level);
+            assert_proof(&requests, hash, level - 1);
+        }
+
+        drop(requests);
+        drop(index);
+        tezos_context::ffi::TEZEDGE_CONTEXT_REPOSITORY
+            .write()
+            .take();
+
+        std::fs::remove_dir_all(&db_path).ok();
+        std::fs::remove_file(&socket_path).ok();
+    }
 }
@@ -31,6 +31,7 @@ macro_rules! elog {
 }
 
 pub mod jemalloc;
+pub(crate) mod persistent;
 mod sorted_map;
 mod stats;
 pub(crate) mod worker;
@@ -48,6 +49,16 @@ pub trait GarbageCollector {
         block_level: u32,
         context_hash: &ContextHash,
     ) -> Result<(), GarbageCollectionError>;
+
+    /// Generation of the repository, incremented every time the repository is
+    /// replaced by the garbage collector.
+    ///
+    /// The `ObjectReference`, `HashId`, `DirectoryShapeId` and `StringId` obtained
+    /// from a previous generation are invalid, the objects and strings cached outside
+    /// of the repository must be dropped.
+    fn gc_generation(&self) -> u64 {
+        0
+    }
 }
 
 pub trait NotGarbageCollected {}
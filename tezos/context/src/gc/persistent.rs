@@ -0,0 +1,944 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Garbage collector for the on-disk repository (`Persistent`).
+//!
+//! The persistent context is append-only, so unreachable objects can't be
+//! removed in place. Instead, every time a cycle starts, the collector copies
+//! the commits of the last `N` cycles (and only the objects reachable from them)
+//! into a new repository, in a background thread.
+//! The background thread also copies the commits applied in the meantime and loads the
+//! new repository, so that the node only has to copy the last commits before the new
+//! repository replaces the current one.
+//!
+//! The collector is enabled with `Persistent::enable_garbage_collection`, see
+//! `initializer::set_persistent_gc_preserved_cycles`.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    thread::JoinHandle,
+};
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use crypto::hash::ContextHash;
+use tezos_timing::SerializeStats;
+use thiserror::Error;
+
+use crate::{
+    chunks::ChunkedVec,
+    gc::worker::{
+        exchange_directories, SNAPSHOT_DATA_LIMIT, SNAPSHOT_HASHES_LIMIT, SNAPSHOT_STRINGS_LIMIT,
+    },
+    hash::{hash_object, HashingError},
+    initializer::IndexInitializationError,
+    kv_store::{
+        in_memory::BATCH_CHUNK_CAPACITY,
+        inline_boxed_slice::InlinedBoxedSlice,
+        persistent::{Persistent, PersistentConfiguration},
+        HashId,
+    },
+    persistent::{DBError, KeyValueStoreBackend},
+    serialize::{persistent, SerializationError},
+    working_tree::{
+        storage::{Storage, StorageError},
+        string_interner::StringInterner,
+        working_tree::{MerkleError, SerializeOutput},
+        Object, ObjectReference,
+    },
+};
+
+use super::GarbageCollectionError;
+
+#[derive(Debug, Error)]
+pub(crate) enum CollectError {
+    #[error("DBError {error}")]
+    DBError {
+        #[from]
+        error: DBError,
+    },
+    #[error("MerkleError {error}")]
+    MerkleError {
+        #[from]
+        error: MerkleError,
+    },
+    #[error("StorageError {error}")]
+    StorageError {
+        #[from]
+        error: StorageError,
+    },
+    #[error("Fail while hashing {error}")]
+    HashingError {
+        #[from]
+        error: HashingError,
+    },
+    #[error("Fail during serialization {error}")]
+    SerializationError {
+        #[from]
+        error: SerializationError,
+    },
+    #[error("Fail while initializing repository {error}")]
+    IndexInitializationError {
+        #[from]
+        error: IndexInitializationError,
+    },
+    #[error("Object at offset {offset} has no hash")]
+    MissingHash { offset: u64 },
+    #[error("Object at offset {offset} has a different hash after being copied")]
+    HashMismatch { offset: u64 },
+    #[error("Object at offset {offset} has {found} children, expected {expected}")]
+    ChildrenMismatch {
+        offset: u64,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Unable to replace the context with the collected one: {reason}")]
+    ReplaceFailed { reason: String },
+    #[error("The collector thread stopped unexpectedly")]
+    ThreadStopped,
+}
+
+impl From<CollectError> for GarbageCollectionError {
+    fn from(error: CollectError) -> Self {
+        GarbageCollectionError::GarbageCollectorError {
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Commits to preserve, grouped by cycle
+type Cycles = VecDeque<Vec<ContextHash>>;
+
+/// Maximum number of times the background thread copies the commits applied since
+/// its last copy, before handing the collection over to the node
+const CATCH_UP_ROUNDS: usize = 8;
+
+/// A collection running in the background thread
+struct RunningCollection {
+    /// Receives the result of the collection, when the thread is done
+    result: Receiver<Result<Collection, CollectError>>,
+    /// Commits applied since the collection started, they are copied by the
+    /// background thread and then by `Collection::finish`
+    applied: Sender<ContextHash>,
+    /// The commits that the background thread didn't copy are left here
+    not_copied: Receiver<ContextHash>,
+    #[allow(dead_code)]
+    thread_handle: JoinHandle<()>,
+}
+
+pub(crate) struct PersistentCollector {
+    /// Path of the repository being collected
+    db_path: String,
+    /// Number of cycles to preserve
+    preserved_cycles: usize,
+    /// Commits of the last `Self::preserved_cycles` cycles.
+    ///
+    /// The last element is the current cycle
+    cycles: Cycles,
+    running: Option<RunningCollection>,
+}
+
+impl PersistentCollector {
+    pub(crate) fn new(db_path: &str, preserved_cycles: usize) -> Self {
+        log!(
+            "Persistent context garbage collection enabled, preserved_cycles={:?}",
+            preserved_cycles
+        );
+
+        Self {
+            db_path: db_path.to_string(),
+            preserved_cycles,
+            cycles: VecDeque::with_capacity(preserved_cycles + 1),
+            running: None,
+        }
+    }
+
+    pub(crate) fn new_cycle_started(&mut self) {
+        self.cycles.push_back(Vec::with_capacity(8192));
+
+        if self.cycles.len() <= self.preserved_cycles {
+            return;
+        }
+
+        // The oldest cycle is not preserved anymore
+        self.cycles.pop_front();
+
+        if self.running.is_some() {
+            // The previous collection is late, the commits of the dropped cycle
+            // will be collected on the next cycle
+            elog!("Persistent context garbage collection is still running, skipping this cycle");
+            return;
+        }
+
+        self.start_collection();
+    }
+
+    pub(crate) fn block_applied(&mut self, context_hash: &ContextHash) {
+        if let Some(cycle) = self.cycles.back_mut() {
+            cycle.push(context_hash.clone());
+        }
+
+        if let Some(running) = self.running.as_mut() {
+            // The receivers live in `running`, this can't fail
+            running.applied.send(context_hash.clone()).ok();
+        }
+    }
+
+    fn start_collection(&mut self) {
+        let commits: Vec<ContextHash> = self.cycles.iter().flatten().cloned().collect();
+        let db_path = self.db_path.clone();
+
+        let (sender, result) = crossbeam_channel::bounded(1);
+        let (applied, not_copied) = crossbeam_channel::unbounded();
+        let applied_since_start = not_copied.clone();
+
+        let thread_handle = std::thread::spawn(move || {
+            let now = std::time::Instant::now();
+            let result = collect_commits(&db_path, &commits, &applied_since_start);
+
+            match result.as_ref() {
+                Ok(_) => log!(
+                    "Persistent context collected in {:?}, ncommits={:?}",
+                    now.elapsed(),
+                    commits.len()
+                ),
+                Err(e) => elog!("Failed to collect persistent context: {:?}", e),
+            }
+
+            if let Err(e) = sender.send(result) {
+                elog!("Failed to send the collected persistent context: {:?}", e);
+            }
+        });
+
+        self.running = Some(RunningCollection {
+            result,
+            applied,
+            not_copied,
+            thread_handle,
+        });
+    }
+
+    /// Returns the collection when the background thread is done.
+    ///
+    /// The returned list contains the commits that the background thread didn't
+    /// copy, they still need to be copied with `Collection::finish`.
+    pub(crate) fn take_finished(
+        &mut self,
+    ) -> Result<Option<(Collection, Vec<ContextHash>)>, CollectError> {
+        let running = match self.running.as_mut() {
+            Some(running) => running,
+            None => return Ok(None),
+        };
+
+        let result = match running.result.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return Ok(None),
+            Err(TryRecvError::Disconnected) => Err(CollectError::ThreadStopped),
+        };
+
+        // `Self::running` is `Some`, checked above
+        let running = self.running.take().unwrap();
+        let not_copied = running.not_copied.try_iter().collect();
+
+        Ok(Some((result?, not_copied)))
+    }
+}
+
+fn collected_path(db_path: &str) -> String {
+    format!("{}-gc", db_path.trim_end_matches('/'))
+}
+
+fn open_source(db_path: &str) -> Result<Persistent, CollectError> {
+    let mut source = Persistent::try_new(PersistentConfiguration {
+        db_path: Some(db_path.to_string()),
+        startup_check: false,
+        read_mode: true,
+    })?;
+    source.reload_database()?;
+
+    Ok(source)
+}
+
+/// Copy `commits` (and all objects reachable from them) from the repository
+/// at `db_path` into a new repository, then the commits received on `applied`
+/// in the meantime.
+///
+/// `commits` must be ordered from the oldest to the latest.
+fn collect_commits(
+    db_path: &str,
+    commits: &[ContextHash],
+    applied: &Receiver<ContextHash>,
+) -> Result<Collection, CollectError> {
+    let source = open_source(db_path)?;
+
+    let mut collection = Collection::try_new(collected_path(db_path))?;
+    collection.copy_commits(&source, commits)?;
+    drop(source);
+
+    // The node keeps applying blocks, catch up with it so that only the
+    // last commits are left to `Collection::finish`
+    for _ in 0..CATCH_UP_ROUNDS {
+        let applied: Vec<ContextHash> = applied.try_iter().collect();
+        if applied.is_empty() {
+            break;
+        }
+
+        let source = open_source(db_path)?;
+        collection.copy_commits(&source, &applied)?;
+    }
+
+    collection.reopen()
+}
+
+/// The new repository, containing only the preserved commits
+pub(crate) struct Collection {
+    path: String,
+    repository: Persistent,
+    /// Map of the offsets in the source repository to their references in `Self::repository`
+    ///
+    /// Objects shared between commits are copied only once.
+    /// It holds one entry per preserved object (about 32 bytes each, including the
+    /// table overhead), so a collection needs that memory for the whole preserved
+    /// context until it is finished, e.g. around 1.5 GB for 50 million objects.
+    references: HashMap<u64, ObjectReference>,
+    output: SerializeOutput,
+    storage: Storage,
+    /// Strings of the source repository
+    strings: StringInterner,
+    stats: SerializeStats,
+    batch: ChunkedVec<(HashId, InlinedBoxedSlice), { BATCH_CHUNK_CAPACITY }>,
+}
+
+impl Collection {
+    fn try_new(path: String) -> Result<Self, CollectError> {
+        // Remove a previous collection that did not complete
+        if let Err(e) = std::fs::remove_dir_all(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(DBError::from(e).into());
+            }
+        }
+
+        let mut repository = Persistent::try_new(PersistentConfiguration {
+            db_path: Some(path.clone()),
+            startup_check: false,
+            read_mode: false,
+        })?;
+        repository.set_is_commiting();
+
+        let output = SerializeOutput::new(Some(repository.data_file_offset()));
+
+        Ok(Self {
+            path,
+            repository,
+            references: HashMap::default(),
+            output,
+            storage: Storage::default(),
+            strings: StringInterner::default(),
+            stats: SerializeStats::default(),
+            batch: Default::default(),
+        })
+    }
+
+    fn copy_commits(
+        &mut self,
+        source: &Persistent,
+        commits: &[ContextHash],
+    ) -> Result<(), CollectError> {
+        // String ids found in the objects refer to the strings of `source`
+        self.strings = source.string_interner.clone();
+
+        for context_hash in commits {
+            let commit_ref = match source.get_context_hash(context_hash)? {
+                Some(commit_ref) => commit_ref,
+                None => {
+                    elog!("Commit {:?} not found, it won't be preserved", context_hash);
+                    continue;
+                }
+            };
+
+            if self.references.contains_key(&commit_ref.offset().as_u64()) {
+                // Already copied
+                continue;
+            }
+
+            let new_commit_ref = self.copy_object(source, commit_ref)?;
+            self.repository.put_context_hash(new_commit_ref)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write data to disk when some limits have been reached.
+    ///
+    /// Same limits as when the in-memory repository creates a snapshot.
+    fn maybe_write_to_disk(&mut self) -> Result<(), CollectError> {
+        let data_limit_reached = self.output.len() >= SNAPSHOT_DATA_LIMIT;
+        let hashes_limit_reached = self.repository.hashes_in_memory_len() >= SNAPSHOT_HASHES_LIMIT;
+        let strings_limit_reached = self
+            .repository
+            .string_interner
+            .new_bytes_since_last_serialize()
+            >= SNAPSHOT_STRINGS_LIMIT;
+
+        if data_limit_reached || hashes_limit_reached || strings_limit_reached {
+            self.commit_to_disk()?;
+            self.repository.set_is_commiting();
+            // Deallocate strings and shapes that are already on disk, to save RAM
+            self.repository.deallocate_strings_shapes();
+        }
+
+        Ok(())
+    }
+
+    fn commit_to_disk(&mut self) -> Result<(), CollectError> {
+        self.repository
+            .commit_to_disk(&self.output)
+            .map_err(DBError::from)?;
+        self.output.clear();
+        Ok(())
+    }
+
+    /// Returns the references of the children of the object at `object_ref`
+    fn children_of(
+        &mut self,
+        source: &Persistent,
+        object_ref: ObjectReference,
+    ) -> Result<Vec<ObjectReference>, CollectError> {
+        let mut children = Vec::new();
+
+        match source.get_object(object_ref, &mut self.storage, &mut self.strings)? {
+            Object::Directory(dir_id) => {
+                if dir_id.is_inode() {
+                    self.storage
+                        .dir_full_load(dir_id, &mut self.strings, source)?;
+                }
+
+                let storage = &self.storage;
+                storage.dir_iterate_unsorted(dir_id, |(_, dir_entry_id)| {
+                    let dir_entry = storage.get_dir_entry(*dir_entry_id)?;
+
+                    if !dir_entry.is_inlined_blob() {
+                        children.push(dir_entry.get_reference());
+                    }
+
+                    Ok(())
+                })?;
+            }
+            Object::Commit(commit) => {
+                // The parent is not a child, it might not be preserved.
+                // See `Self::copy_parent_reference`
+                children.push(commit.root_ref);
+            }
+            Object::Blob(_) => {}
+        }
+
+        self.storage.clear();
+
+        Ok(children)
+    }
+
+    /// The parent of a commit is copied only when it is preserved, otherwise only
+    /// its hash is copied (same as in snapshots)
+    fn copy_parent_reference(
+        &mut self,
+        source: &Persistent,
+        parent_ref: ObjectReference,
+    ) -> Result<ObjectReference, CollectError> {
+        if let Some(new_ref) = parent_ref
+            .offset_opt()
+            .and_then(|offset| self.references.get(&offset.as_u64()))
+        {
+            return Ok(*new_ref);
+        }
+
+        let hash = source.get_hash(parent_ref)?.into_owned();
+        let hash_id = self.repository.put_hash(hash)?;
+
+        Ok(ObjectReference::new(Some(hash_id), None))
+    }
+
+    /// Copy the object at `object_ref` and its children into `Self::repository`.
+    ///
+    /// Returns the reference of the object in `Self::repository`.
+    fn copy_object(
+        &mut self,
+        source: &Persistent,
+        object_ref: ObjectReference,
+    ) -> Result<ObjectReference, CollectError> {
+        let source_offset = object_ref.offset().as_u64();
+
+        if let Some(new_ref) = self.references.get(&source_offset) {
+            return Ok(*new_ref);
+        }
+
+        self.maybe_write_to_disk()?;
+
+        // Children are copied first, we need their new references
+        let children = self.children_of(source, object_ref)?;
+        let mut new_children = Vec::with_capacity(children.len());
+
+        for child_ref in children {
+            new_children.push(self.copy_object(source, child_ref)?);
+        }
+
+        // Load the object again, `Self::storage` has been cleared by the children
+        let mut object = source.get_object(object_ref, &mut self.storage, &mut self.strings)?;
+
+        if let Object::Directory(dir_id) = object {
+            if dir_id.is_inode() {
+                self.storage
+                    .dir_full_load(dir_id, &mut self.strings, source)?;
+            }
+        }
+
+        // Remove all references to the source repository, hashes and offsets
+        // of inodes are recomputed below
+        self.storage.forget_references();
+
+        match &mut object {
+            Object::Directory(dir_id) => {
+                let storage = &self.storage;
+                let mut dir_entries = Vec::with_capacity(new_children.len());
+
+                storage.dir_iterate_unsorted(*dir_id, |(_, dir_entry_id)| {
+                    // Inlined blobs do not have a HashId or an offset
+                    if !storage.get_dir_entry(*dir_entry_id)?.is_inlined_blob() {
+                        dir_entries.push(*dir_entry_id);
+                    }
+
+                    Ok(())
+                })?;
+
+                // Same iteration order as in `Self::children_of`, the counts differ
+                // only when the object is corrupted
+                if dir_entries.len() != new_children.len() {
+                    return Err(CollectError::ChildrenMismatch {
+                        offset: source_offset,
+                        expected: new_children.len(),
+                        found: dir_entries.len(),
+                    });
+                }
+
+                for (dir_entry_id, new_ref) in dir_entries.into_iter().zip(&new_children) {
+                    let dir_entry = storage.get_dir_entry(dir_entry_id)?;
+
+                    dir_entry.set_offset(new_ref.offset());
+                    dir_entry.set_hash_id(new_ref.hash_id());
+                    dir_entry.set_commited(true);
+                }
+            }
+            Object::Commit(commit) => {
+                commit.root_ref = match new_children.as_slice() {
+                    [root_ref] => *root_ref,
+                    _ => {
+                        return Err(CollectError::ChildrenMismatch {
+                            offset: source_offset,
+                            expected: 1,
+                            found: new_children.len(),
+                        })
+                    }
+                };
+                commit.parent_commit_ref = match commit.parent_commit_ref {
+                    Some(parent_ref) => Some(self.copy_parent_reference(source, parent_ref)?),
+                    None => None,
+                };
+            }
+            Object::Blob(_) => {}
+        }
+
+        // Compute the hash in the new repository, this also computes the `HashId`
+        // of the inodes pointers
+        let hash_id = hash_object(&object, &mut self.repository, &self.storage, &self.strings)?
+            .ok_or(CollectError::MissingHash {
+                offset: source_offset,
+            })?;
+
+        if self.repository.get_hash(hash_id.into())? != source.get_hash(object_ref)? {
+            return Err(CollectError::HashMismatch {
+                offset: source_offset,
+            });
+        }
+
+        // Replace the string ids of `source` with the ones of `Self::repository`
+        let strings = &self.strings;
+        let repository = &mut self.repository;
+        self.storage
+            .directories
+            .for_each_mut::<_, CollectError>(|(string_id, _)| {
+                let s = strings.get_str(*string_id)?;
+                *string_id = repository.string_interner.make_string_id(s.as_ref());
+                Ok(())
+            })?;
+
+        // `serialize_object` reads the keys of non-shaped directories from the strings
+        // given as parameter, they must be the ones of `Self::repository`
+        let repository_strings = std::mem::take(&mut self.repository.string_interner);
+
+        let offset = persistent::serialize_object(
+            &object,
+            hash_id,
+            &mut self.output,
+            &self.storage,
+            &repository_strings,
+            &mut self.stats,
+            &mut self.batch,
+            &mut self.repository,
+        );
+
+        self.repository.string_interner = repository_strings;
+
+        let new_ref = ObjectReference::new(Some(hash_id), offset?);
+        self.references.insert(source_offset, new_ref);
+
+        self.storage.clear();
+
+        Ok(new_ref)
+    }
+
+    /// Write everything to disk and open the repository again, loaded like the
+    /// one used by the node.
+    fn reopen(mut self) -> Result<Self, CollectError> {
+        self.commit_to_disk()?;
+
+        let Self {
+            path,
+            repository,
+            references,
+            strings,
+            stats,
+            ..
+        } = self;
+        // Release the lock of the repository
+        drop(repository);
+
+        let mut repository = Persistent::try_new(PersistentConfiguration {
+            db_path: Some(path.clone()),
+            startup_check: false,
+            read_mode: false,
+        })?;
+        repository.reload_database()?;
+        repository.set_is_commiting();
+
+        let output = SerializeOutput::new(Some(repository.data_file_offset()));
+
+        Ok(Self {
+            path,
+            repository,
+            references,
+            output,
+            storage: Storage::default(),
+            strings,
+            stats,
+            batch: Default::default(),
+        })
+    }
+
+    /// Copy the commits that the background thread didn't copy, and write
+    /// everything to disk.
+    ///
+    /// Returns the new repository, it still needs to be moved to the path of
+    /// `source`, see `replace_with_collected`.
+    pub(crate) fn finish(
+        mut self,
+        source: &Persistent,
+        commits: &[ContextHash],
+    ) -> Result<Persistent, CollectError> {
+        self.copy_commits(source, commits)?;
+        self.commit_to_disk()?;
+
+        Ok(self.repository)
+    }
+}
+
+/// Move the collected repository to `db_path`, the files of the repositories are
+/// not reopened, only their directories are exchanged.
+///
+/// The previous repository is removed in the background.
+pub(crate) fn replace_with_collected(db_path: &str) -> Result<(), CollectError> {
+    let collected_path = collected_path(db_path);
+
+    exchange_directories(db_path, &collected_path).map_err(|e| CollectError::ReplaceFailed {
+        reason: e.to_string(),
+    })?;
+
+    // Out of the way of the next collection, while it is removed
+    let previous_path = format!("{}-previous-{}", collected_path, std::process::id());
+    std::fs::remove_dir_all(&previous_path).ok();
+    std::fs::rename(&collected_path, &previous_path).map_err(DBError::from)?;
+
+    std::thread::spawn(move || {
+        if let Err(e) = std::fs::remove_dir_all(&previous_path) {
+            elog!("Failed to remove the previous persistent context: {:?}", e);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use parking_lot::RwLock;
+
+    use crate::{
+        initializer::initialize_readonly_ipc_index, kv_store::readonly_ipc::IpcContextListener,
+        ContextKeyValueStore, IndexApi, ProtocolContextApi, ShellContextApi, TezedgeContext,
+        TezedgeIndex,
+    };
+
+    use super::*;
+
+    fn temp_db_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+
+        std::fs::remove_dir_all(&path).ok();
+        std::fs::remove_dir_all(collected_path(&path)).ok();
+
+        path
+    }
+
+    /// Commit on top of `parent`, it is checked out again because its objects
+    /// may have been moved by the collector
+    fn commit_level(
+        index: &TezedgeIndex,
+        parent: Option<&ContextHash>,
+        level: usize,
+    ) -> ContextHash {
+        let context = match parent {
+            Some(parent) => index.checkout(parent).unwrap().unwrap(),
+            None => TezedgeContext::new(index.clone(), None, None),
+        };
+
+        let context = context
+            .add(&["a", "b", "c"], &[level as u8 + 1; 40])
+            .unwrap();
+        let context = context
+            .add(&["level", &level.to_string()], &[level as u8; 50])
+            .unwrap();
+
+        context
+            .commit("Tezos".to_string(), "Commit".to_string(), level as i64)
+            .unwrap()
+    }
+
+    fn assert_level(index: &TezedgeIndex, hash: &ContextHash, level: usize) {
+        let context = index.checkout(hash).unwrap().unwrap();
+
+        assert_eq!(
+            context.find(&["a", "b", "c"]).unwrap().unwrap(),
+            vec![level as u8 + 1; 40]
+        );
+        for previous in 0..=level {
+            assert_eq!(
+                context
+                    .find(&["level", &previous.to_string()])
+                    .unwrap()
+                    .unwrap(),
+                vec![previous as u8; 50]
+            );
+        }
+    }
+
+    #[test]
+    fn test_collect_commits() {
+        let db_path = temp_db_path("tezedge-persistent-gc-collect");
+
+        let mut hashes = Vec::new();
+
+        {
+            let repo = Persistent::try_new(PersistentConfiguration {
+                db_path: Some(db_path.clone()),
+                startup_check: false,
+                read_mode: false,
+            })
+            .unwrap();
+            let repo: Arc<RwLock<ContextKeyValueStore>> = Arc::new(RwLock::new(repo));
+            let index = TezedgeIndex::new(repo, None);
+
+            for level in 0..4 {
+                let hash = commit_level(&index, hashes.last(), level);
+                hashes.push(hash);
+            }
+        }
+
+        // Preserve only the 3 last commits: the 2nd is copied first, the 3rd
+        // while catching up, and the 4th when finishing
+        let (applied, applied_since_start) = crossbeam_channel::unbounded();
+        applied.send(hashes[2].clone()).unwrap();
+
+        let collection = collect_commits(&db_path, &hashes[1..2], &applied_since_start).unwrap();
+        assert!(applied_since_start.is_empty());
+
+        let collected = {
+            let source = open_source(&db_path).unwrap();
+            collection.finish(&source, &hashes[3..]).unwrap()
+        };
+
+        let collected: Arc<RwLock<ContextKeyValueStore>> = Arc::new(RwLock::new(collected));
+        let index = TezedgeIndex::new(collected, None);
+
+        assert!(!index.exists(&hashes[0]).unwrap());
+
+        for (level, hash) in hashes.iter().enumerate().skip(1) {
+            assert_level(&index, hash, level);
+        }
+
+        drop(index);
+
+        // The collected repository can be opened again
+        let mut reopened = Persistent::try_new(PersistentConfiguration {
+            db_path: Some(collected_path(&db_path)),
+            startup_check: true,
+            read_mode: true,
+        })
+        .unwrap();
+        reopened.reload_database().unwrap();
+
+        let reopened: Arc<RwLock<ContextKeyValueStore>> = Arc::new(RwLock::new(reopened));
+        let index = TezedgeIndex::new(reopened, None);
+
+        assert!(!index.exists(&hashes[0]).unwrap());
+        assert_level(&index, &hashes[3], 3);
+
+        std::fs::remove_dir_all(&db_path).ok();
+        std::fs::remove_dir_all(collected_path(&db_path)).ok();
+    }
+
+    #[test]
+    fn test_garbage_collection() {
+        let db_path = temp_db_path("tezedge-persistent-gc");
+
+        let mut repo = Persistent::try_new(PersistentConfiguration {
+            db_path: Some(db_path.clone()),
+            startup_check: false,
+            read_mode: false,
+        })
+        .unwrap();
+        repo.enable_garbage_collection(2);
+
+        let repo: Arc<RwLock<ContextKeyValueStore>> = Arc::new(RwLock::new(repo));
+        let mut index = TezedgeIndex::new(repo, None);
+        let mut hashes = Vec::new();
+
+        // One commit per cycle, the collection starts on the 3rd cycle
+        for level in 0..3 {
+            index.cycle_started().unwrap();
+
+            let hash = commit_level(&index, hashes.last(), level);
+            index.block_applied(level as u32, &hash).unwrap();
+            hashes.push(hash);
+        }
+
+        // Keep applying blocks until the repository is replaced
+        let mut level = hashes.len();
+        while index.exists(&hashes[0]).unwrap() {
+            assert!(level < 1000, "The repository has not been collected");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+
+            let hash = commit_level(&index, hashes.last(), level);
+            index.block_applied(level as u32, &hash).unwrap();
+            hashes.push(hash);
+            level += 1;
+        }
+
+        for (level, hash) in hashes.iter().enumerate().skip(1) {
+            assert_level(&index, hash, level);
+        }
+
+        // The node keeps committing in the new repository
+        let hash = commit_level(&index, hashes.last(), level);
+        assert_level(&index, &hash, level);
+
+        drop(index);
+
+        // The replaced repository is complete on disk
+        let mut reopened = Persistent::try_new(PersistentConfiguration {
+            db_path: Some(db_path.clone()),
+            startup_check: true,
+            read_mode: true,
+        })
+        .unwrap();
+        reopened.reload_database().unwrap();
+
+        let reopened: Arc<RwLock<ContextKeyValueStore>> = Arc::new(RwLock::new(reopened));
+        let index = TezedgeIndex::new(reopened, None);
+
+        assert!(!index.exists(&hashes[0]).unwrap());
+        assert_level(&index, &hash, level);
+
+        std::fs::remove_dir_all(&db_path).ok();
+    }
+
+    #[test]
+    fn test_garbage_collection_readonly_ipc() {
+        let db_path = temp_db_path("tezedge-persistent-gc-ipc");
+        let socket_path = format!("{}.sock", db_path);
+
+        let mut repo = Persistent::try_new(PersistentConfiguration {
+            db_path: Some(db_path.clone()),
+            startup_check: false,
+            read_mode: false,
+        })
+        .unwrap();
+        repo.enable_garbage_collection(2);
+
+        let repo: Arc<RwLock<ContextKeyValueStore>> = Arc::new(RwLock::new(repo));
+        crate::ffi::TEZEDGE_CONTEXT_REPOSITORY
+            .write()
+            .replace(Arc::clone(&repo));
+
+        let mut listener = IpcContextListener::try_new(&socket_path).unwrap();
+        std::thread::spawn(move || {
+            let log = slog::Logger::root(slog::Discard, slog::o!());
+            listener.handle_incoming_connections(&log);
+        });
+        let readonly = initialize_readonly_ipc_index(&socket_path).unwrap();
+
+        let mut index = TezedgeIndex::new(repo, None);
+        let mut hashes = Vec::new();
+
+        for level in 0..3 {
+            index.cycle_started().unwrap();
+
+            let hash = commit_level(&index, hashes.last(), level);
+            index.block_applied(level as u32, &hash).unwrap();
+            hashes.push(hash);
+        }
+
+        // Checked out before the collection, their objects are read lazily
+        let context = index.checkout(&hashes[2]).unwrap().unwrap();
+        let readonly_context = readonly.checkout(&hashes[2]).unwrap().unwrap();
+        assert_level(&readonly, &hashes[2], 2);
+
+        let mut level = hashes.len();
+        while index.exists(&hashes[0]).unwrap() {
+            assert!(level < 1000, "The repository has not been collected");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+
+            let hash = commit_level(&index, hashes.last(), level);
+            index.block_applied(level as u32, &hash).unwrap();
+            hashes.push(hash);
+            level += 1;
+        }
+
+        // Their references are from the replaced repository, reading them fails
+        // instead of returning other objects
+        let error = context.find(&["level", "1"]).unwrap_err();
+        assert!(error.is_garbage_collected(), "{:?}", error);
+        let error = readonly_context.find(&["level", "1"]).unwrap_err();
+        assert!(error.is_garbage_collected(), "{:?}", error);
+
+        // Checked out again, the contexts are read from the new repository
+        assert!(!readonly.exists(&hashes[0]).unwrap());
+        for (level, hash) in hashes.iter().enumerate().skip(1) {
+            assert_level(&readonly, hash, level);
+        }
+
+        drop(index);
+        crate::ffi::TEZEDGE_CONTEXT_REPOSITORY.write().take();
+
+        std::fs::remove_dir_all(&db_path).ok();
+        std::fs::remove_file(&socket_path).ok();
+    }
+}
@@ -73,9 +73,9 @@ const SNAPSHOT_DEFAULT_DELAY: Duration = Duration::from_secs(60 * 60 * 12);
 
 /// While creating a snapshot, we write to disk some datas to avoid keeping
 /// them in RAM. The following constants define when to write on disk.
-const SNAPSHOT_DATA_LIMIT: usize = 20_000_000;
-const SNAPSHOT_HASHES_LIMIT: usize = 625_000;
-const SNAPSHOT_STRINGS_LIMIT: usize = 10_000_000;
+pub(crate) const SNAPSHOT_DATA_LIMIT: usize = 20_000_000;
+pub(crate) const SNAPSHOT_HASHES_LIMIT: usize = 625_000;
+pub(crate) const SNAPSHOT_STRINGS_LIMIT: usize = 10_000_000;
 
 /// Used for statistics
 ///
@@ -220,7 +220,7 @@ assert_eq_size!([u8; 16], Option<Box<[u8]>>);
 assert_eq_size!([u8; 8], Option<Box<ObjectHash>>);
 
 #[derive(Debug, Error)]
-enum GCError {
+pub(crate) enum GCError {
     #[error("HashId conversion failed")]
     HashIdFailed,
     #[error("Failed to traverse tree")]
@@ -316,9 +316,19 @@ fn replace_context_with_snapshot(path_context: &str, path_snapshot: &str) -> Res
     Ok(())
 }
 
-// `renameat2` is a Linux syscall
 #[cfg(target_os = "linux")]
 fn replace_context_with_snapshot(path_context: &str, path_snapshot: &str) -> Result<(), GCError> {
+    exchange_directories(path_context, path_snapshot)?;
+
+    // Remove snapshot path
+    std::fs::remove_dir_all(path_snapshot).map_err(DBError::from)?;
+
+    Ok(())
+}
+
+// `renameat2` is a Linux syscall
+#[cfg(target_os = "linux")]
+pub(crate) fn exchange_directories(path_context: &str, path_snapshot: &str) -> Result<(), GCError> {
     // Make sure `path_context` exist, or `renameat2` will fail
     std::fs::create_dir_all(&path_context).map_err(DBError::from)?;
 
@@ -348,8 +358,18 @@ fn replace_context_with_snapshot(path_context: &str, path_snapshot: &str) -> Res
         return Err(GCError::RenameAt2Failed { error });
     }
 
-    // Remove snapshot path
-    std::fs::remove_dir_all(path_snapshot).map_err(DBError::from)?;
+    Ok(())
+}
+
+/// Same as the Linux version, except that the directories are not exchanged atomically
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn exchange_directories(path_context: &str, path_snapshot: &str) -> Result<(), GCError> {
+    let path_exchange = format!("{}-exchange", path_snapshot);
+
+    std::fs::create_dir_all(&path_context).map_err(DBError::from)?;
+    std::fs::rename(path_context, &path_exchange).map_err(DBError::from)?;
+    std::fs::rename(path_snapshot, path_context).map_err(DBError::from)?;
+    std::fs::rename(&path_exchange, path_snapshot).map_err(DBError::from)?;
 
     Ok(())
 }
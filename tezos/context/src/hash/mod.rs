@@ -104,7 +104,7 @@ impl From<StorageError> for HashingError {
     }
 }
 
-fn encode_irmin_dir_entry_kind(kind: &DirEntryKind) -> [u8; 8] {
+pub(crate) fn encode_irmin_dir_entry_kind(kind: &DirEntryKind) -> [u8; 8] {
     match kind {
         DirEntryKind::Directory => [0, 0, 0, 0, 0, 0, 0, 0],
         DirEntryKind::Blob => [255, 0, 0, 0, 0, 0, 0, 0],
@@ -1,6 +1,8 @@
 // Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread::JoinHandle;
 use std::time::Duration;
@@ -119,6 +121,18 @@ fn spawn_reload_database(
     result
 }
 
+/// Number of cycles preserved by the garbage collector of the on-disk context,
+/// `0` when it is disabled
+static PERSISTENT_GC_PRESERVED_CYCLES: AtomicUsize = AtomicUsize::new(0);
+
+/// Enables the garbage collection of the on-disk context (`ContextKvStoreConfiguration::OnDisk`),
+/// for the indexes initialized afterwards.
+///
+/// The context configuration comes from OCaml, this is set by the protocol runner instead.
+pub fn set_persistent_gc_preserved_cycles(preserved_cycles: Option<usize>) {
+    PERSISTENT_GC_PRESERVED_CYCLES.store(preserved_cycles.unwrap_or(0), Ordering::Relaxed);
+}
+
 pub fn initialize_tezedge_index(
     configuration: &TezosContextTezEdgeStorageConfiguration,
     patch_context: Option<BoxRoot<PatchContextFunction>>,
@@ -137,11 +151,18 @@ pub fn initialize_tezedge_index(
             })?))
         }
         ContextKvStoreConfiguration::OnDisk(ref options) => {
-            Arc::new(RwLock::new(Persistent::try_new(PersistentConfiguration {
+            let mut repository = Persistent::try_new(PersistentConfiguration {
                 db_path: Some(options.base_path.clone()),
                 startup_check: options.startup_check,
                 read_mode: false,
-            })?))
+            })?;
+
+            let preserved_cycles = PERSISTENT_GC_PRESERVED_CYCLES.load(Ordering::Relaxed);
+            if preserved_cycles > 0 {
+                repository.enable_garbage_collection(preserved_cycles);
+            }
+
+            Arc::new(RwLock::new(repository))
         }
     };
 
@@ -154,6 +175,18 @@ pub fn initialize_tezedge_index(
     Ok(TezedgeIndex::new(repository, patch_context))
 }
 
+/// Connects an index to the context IPC server of the protocol runner, the repository
+/// is only read through IPC, like in the readonly protocol runners.
+pub fn initialize_readonly_ipc_index<P: AsRef<Path>>(
+    ipc_socket_path: P,
+) -> Result<TezedgeIndex, IndexInitializationError> {
+    let repository: Arc<RwLock<ContextKeyValueStore>> = Arc::new(RwLock::new(
+        ReadonlyIpcBackend::try_connect(ipc_socket_path)?,
+    ));
+
+    Ok(TezedgeIndex::new(repository, None))
+}
+
 pub fn initialize_tezedge_context(
     configuration: &TezosContextTezEdgeStorageConfiguration,
 ) -> Result<TezedgeContext, IndexInitializationError> {
@@ -23,12 +23,15 @@ use tezos_timing::{RepositoryMemoryUsage, SerializeStats};
 
 use crate::{
     chunks::ChunkedVec,
-    gc::NotGarbageCollected,
+    gc::{
+        persistent::{replace_with_collected, PersistentCollector},
+        GarbageCollectionError, GarbageCollector,
+    },
     hash::OBJECT_HASH_LEN,
     initializer::IndexInitializationError,
     persistent::{
         file::{
-            get_persistent_base_path, File, TAG_BIG_STRINGS, TAG_COMMIT_INDEX, TAG_DATA,
+            get_persistent_base_path, File, FileType, TAG_BIG_STRINGS, TAG_COMMIT_INDEX, TAG_DATA,
             TAG_HASHES, TAG_SHAPE, TAG_SHAPE_INDEX, TAG_SIZES, TAG_STRINGS,
         },
         get_commit_hash,
@@ -131,9 +134,19 @@ pub struct Persistent {
     /// [Hash of the following bytes, commit counter, file 1 size, file 2 size, .., file X size]
     /// This repeats 10 times
     sizes_file: File<{ TAG_SIZES }>,
+    base_path: String,
     startup_check: bool,
     lastest_commits_on_startup: VecDeque<ObjectReference>,
     read_statistics: Option<Mutex<ReadStatistics>>,
+    /// Garbage collector, `None` when the repository is opened in read mode or
+    /// when the garbage collection is disabled
+    ///
+    /// See `gc::persistent`
+    collector: Option<PersistentCollector>,
+    /// Incremented every time the repository is replaced by the garbage collector
+    ///
+    /// See `GarbageCollector::gc_generation`
+    gc_generation: u64,
 }
 
 impl Drop for Persistent {
@@ -142,7 +155,51 @@ impl Drop for Persistent {
     }
 }
 
-impl NotGarbageCollected for Persistent {}
+impl GarbageCollector for Persistent {
+    fn new_cycle_started(&mut self) -> Result<(), GarbageCollectionError> {
+        if let Some(collector) = self.collector.as_mut() {
+            collector.new_cycle_started();
+        }
+
+        Ok(())
+    }
+
+    fn block_applied(
+        &mut self,
+        _block_level: u32,
+        context_hash: &ContextHash,
+    ) -> Result<(), GarbageCollectionError> {
+        let collector = match self.collector.as_mut() {
+            Some(collector) => collector,
+            None => return Ok(()),
+        };
+
+        collector.block_applied(context_hash);
+
+        let (collection, not_copied) = match collector.take_finished()? {
+            Some(finished) => finished,
+            None => return Ok(()),
+        };
+
+        let now = std::time::Instant::now();
+        let repository = collection.finish(self, &not_copied)?;
+
+        replace_with_collected(&self.base_path)?;
+        self.replace_with(repository);
+
+        log!(
+            "Persistent context replaced with the collected one in {:?}, ncommits_copied={:?}",
+            now.elapsed(),
+            not_copied.len()
+        );
+
+        Ok(())
+    }
+
+    fn gc_generation(&self) -> u64 {
+        self.gc_generation
+    }
+}
 
 impl Flushable for Persistent {
     fn flush(&self) -> Result<(), anyhow::Error> {
@@ -287,6 +344,7 @@ impl Persistent {
             lock_file,
             commit_counter: Default::default(),
             sizes_file,
+            base_path,
             startup_check,
             lastest_commits_on_startup: VecDeque::default(),
             read_statistics: if read_mode {
@@ -294,9 +352,40 @@ impl Persistent {
             } else {
                 None
             },
+            collector: None,
+            gc_generation: 0,
         })
     }
 
+    /// Enables the garbage collection, the commits of the last `preserved_cycles`
+    /// cycles are preserved. It has no effect in read mode.
+    ///
+    /// See `gc::persistent`
+    pub fn enable_garbage_collection(&mut self, preserved_cycles: usize) {
+        if self.lock_file.is_some() {
+            self.collector = Some(PersistentCollector::new(&self.base_path, preserved_cycles));
+        }
+    }
+
+    /// Replace the repository with the one from the garbage collector, which has been
+    /// moved to `Self::base_path`.
+    ///
+    /// All `ObjectReference` and `StringId` from before are now invalid, the
+    /// generation is incremented so that their users notice it.
+    fn replace_with(&mut self, mut repository: Persistent) {
+        repository.base_path = self.base_path.clone();
+        if let Some(lock_file) = repository.lock_file.as_mut() {
+            lock_file.moved_to(&self.base_path);
+        }
+        if self.hashes.in_memory.dedup_hashes.is_some() {
+            repository.enable_hash_dedup();
+        }
+        repository.collector = self.collector.take();
+        repository.gc_generation = self.gc_generation + 1;
+
+        *self = repository;
+    }
+
     pub fn enable_hash_dedup(&mut self) {
         self.hashes.in_memory.dedup_hashes = Some(Default::default());
     }
@@ -874,6 +963,58 @@ impl FileSizes {
 
         Some(list_sizes)
     }
+
+    /// Verifies that the files at `base_path` match the latest entry in `sizes.db`.
+    ///
+    /// Unlike the startup check, this does not fall back to an older entry: a file
+    /// smaller than recorded or with a different checksum is an error. This is used
+    /// on repositories that are not written anymore (e.g. imported snapshots).
+    pub fn verify_latest(base_path: &str) -> Result<FileSizes, IndexInitializationError> {
+        let sizes_file = File::<{ TAG_SIZES }>::try_new(base_path, true)?;
+        let sizes = Self::make_list_from_file(&sizes_file)
+            .and_then(|mut list| list.pop())
+            .ok_or(IndexInitializationError::InvalidIntegrity)?;
+
+        fn check<const T: u64>(
+            base_path: &str,
+            size: u64,
+            checksum: u32,
+        ) -> Result<(), IndexInitializationError> {
+            let mut file = File::<T>::try_new(base_path, true)?;
+            if file.offset().as_u64() < size || file.update_checksum_until(size)? != checksum {
+                elog!(
+                    "Checksum of {:?} file do not match {:?} at offset {:?}",
+                    FileType::from(T),
+                    checksum,
+                    size
+                );
+                return Err(IndexInitializationError::InvalidIntegrity);
+            }
+            Ok(())
+        }
+
+        check::<{ TAG_STRINGS }>(base_path, sizes.strings_size, sizes.strings_checksum)?;
+        check::<{ TAG_COMMIT_INDEX }>(
+            base_path,
+            sizes.commit_index_size,
+            sizes.commit_index_checksum,
+        )?;
+        check::<{ TAG_SHAPE_INDEX }>(
+            base_path,
+            sizes.shape_index_size,
+            sizes.shape_index_checksum,
+        )?;
+        check::<{ TAG_BIG_STRINGS }>(
+            base_path,
+            sizes.big_strings_size,
+            sizes.big_strings_checksum,
+        )?;
+        check::<{ TAG_SHAPE }>(base_path, sizes.shape_size, sizes.shape_checksum)?;
+        check::<{ TAG_HASHES }>(base_path, sizes.hashes_size, sizes.hashes_checksum)?;
+        check::<{ TAG_DATA }>(base_path, sizes.data_size, sizes.data_checksum)?;
+
+        Ok(sizes)
+    }
 }
 
 /// Number of last commits we keep as references in `DeserializedCommitIndex::last_commits`
@@ -32,7 +32,10 @@ use crate::working_tree::string_interner::{StringId, StringInterner};
 use crate::working_tree::working_tree::{PostCommitData, SerializeOutput, WorkingTree};
 use crate::working_tree::{Object, ObjectReference};
 use crate::{
-    ffi::TezedgeIndexError, gc::NotGarbageCollected, persistent::KeyValueStoreBackend, ObjectHash,
+    ffi::TezedgeIndexError,
+    gc::{GarbageCollectionError, GarbageCollector},
+    persistent::KeyValueStoreBackend,
+    ObjectHash,
 };
 
 pub struct ReadonlyIpcBackend {
@@ -63,7 +66,25 @@ impl ReadonlyIpcBackend {
     }
 }
 
-impl NotGarbageCollected for ReadonlyIpcBackend {}
+impl GarbageCollector for ReadonlyIpcBackend {
+    fn new_cycle_started(&mut self) -> Result<(), GarbageCollectionError> {
+        Ok(())
+    }
+
+    fn block_applied(
+        &mut self,
+        _block_level: u32,
+        _context_hash: &ContextHash,
+    ) -> Result<(), GarbageCollectionError> {
+        Ok(())
+    }
+
+    /// Generation of the repository of the writable protocol runner, as
+    /// of the last response
+    fn gc_generation(&self) -> u64 {
+        self.client.gc_generation()
+    }
+}
 
 impl KeyValueStoreBackend for ReadonlyIpcBackend {
     fn reload_database(&mut self) -> Result<(), ReloadError> {
@@ -313,7 +334,10 @@ impl Persistable for ReadonlyIpcBackend {
 
 // IPC communication
 
-use std::{cell::RefCell, time::Duration};
+use std::{
+    cell::{Cell, RefCell},
+    time::Duration,
+};
 
 use ipc::{IpcClient, IpcError, IpcReceiver, IpcSender, IpcServer};
 use serde::{Deserialize, Serialize};
@@ -323,14 +347,17 @@ use strum_macros::IntoStaticStr;
 use super::{in_memory::HashObjectStore, HashId, VacantObjectHash};
 
 /// This request is generated by a readonly protool runner and is received by the writable protocol runner.
+///
+/// The requests refering to objects of the repository carry the generation of the
+/// repository they were obtained from (see `GarbageCollector::gc_generation`).
 #[derive(Serialize, Deserialize, Debug, IntoStaticStr)]
 enum ContextRequest {
     GetContextHashId(ContextHash),
-    GetHash(ObjectReference),
-    GetHashId(ObjectReference),
-    GetObjectBytes(ObjectReference),
-    GetShape(DirectoryShapeId),
-    ContainsObject(HashId),
+    GetHash(ObjectReference, u64),
+    GetHashId(ObjectReference, u64),
+    GetObjectBytes(ObjectReference, u64),
+    GetShape(DirectoryShapeId, u64),
+    ContainsObject(HashId, u64),
     ShutdownCall, // TODO: is this required?
 }
 
@@ -339,10 +366,14 @@ enum ContextRequest {
 enum ContextResponse {
     GetHashResponse(Result<ObjectHash, String>),
     GetHashIdResponse(Result<HashId, String>),
-    GetContextHashIdResponse(Result<Option<ObjectReference>, String>),
+    /// The object reference with the current generation of the repository
+    GetContextHashIdResponse(Result<Option<ObjectReference>, String>, u64),
     GetObjectBytesResponse(Result<Vec<u8>, String>),
     GetShapeResponse(Result<Vec<String>, String>),
     ContainsObjectResponse(Result<bool, String>),
+    /// Sent instead of the response when the request refers to a previous generation
+    /// of the repository, with the current generation
+    GarbageCollected(u64),
     ShutdownResult,
 }
 
@@ -401,6 +432,15 @@ pub enum ContextServiceError {
     /// Lock error
     #[error("Lock error: {message:?}")]
     LockPoisonError { message: String },
+    /// The repository has been replaced by the garbage collector, the objects read
+    /// before are invalid and the context must be checked out again.
+    #[error(
+        "Context garbage collected, generation {read_generation} is now {repository_generation}"
+    )]
+    GarbageCollected {
+        read_generation: u64,
+        repository_generation: u64,
+    },
 }
 
 impl<T> From<std::sync::PoisonError<T>> for ContextServiceError {
@@ -454,6 +494,8 @@ struct IpcServerIO {
 /// Encapsulate IPC communication.
 pub struct IpcContextClient {
     io: RefCell<IpcClientIO>,
+    /// Generation of the repository of the last response
+    gc_generation: Cell<u64>,
 }
 
 pub struct IpcContextServer {
@@ -475,13 +517,34 @@ impl IpcContextClient {
         let ipc_client: IpcClient<ContextResponse, ContextRequest> = IpcClient::new(socket_path);
         let (rx, tx) = ipc_client.connect()?;
         let io = RefCell::new(IpcClientIO { rx, tx });
-        Ok(Self { io })
+        Ok(Self {
+            io,
+            gc_generation: Cell::new(0),
+        })
+    }
+
+    /// Generation of the repository as of the last response, it is updated
+    /// by `Self::get_context_hash_id`
+    pub fn gc_generation(&self) -> u64 {
+        self.gc_generation.get()
+    }
+
+    fn garbage_collected(&self, repository_generation: u64) -> ContextServiceError {
+        let read_generation = self.gc_generation.replace(repository_generation);
+
+        ContextServiceError::GarbageCollected {
+            read_generation,
+            repository_generation,
+        }
     }
 
     /// Check if object with hash id exists
     pub fn contains_object(&self, hash_id: HashId) -> Result<bool, ContextServiceError> {
         let mut io = self.io.borrow_mut();
-        io.tx.send(&ContextRequest::ContainsObject(hash_id))?;
+        io.tx.send(&ContextRequest::ContainsObject(
+            hash_id,
+            self.gc_generation.get(),
+        ))?;
 
         // this might take a while, so we will use unusually long timeout
         match io
@@ -491,6 +554,9 @@ impl IpcContextClient {
             ContextResponse::ContainsObjectResponse(result) => {
                 result.map_err(|err| ContextError::ContainsObjectError { reason: err }.into())
             }
+            ContextResponse::GarbageCollected(generation) => {
+                Err(self.garbage_collected(generation))
+            }
             message => Err(ContextServiceError::UnexpectedMessage {
                 message: message.into(),
             }),
@@ -511,8 +577,11 @@ impl IpcContextClient {
             .rx
             .try_receive(Some(Self::TIMEOUT), Some(IpcContextListener::IO_TIMEOUT))?
         {
-            ContextResponse::GetContextHashIdResponse(result) => {
-                result.map_err(|err| ContextError::GetContextHashIdError { reason: err }.into())
+            ContextResponse::GetContextHashIdResponse(result, gc_generation) => {
+                let object_ref =
+                    result.map_err(|err| ContextError::GetContextHashIdError { reason: err })?;
+                self.gc_generation.set(gc_generation);
+                Ok(object_ref)
             }
             message => Err(ContextServiceError::UnexpectedMessage {
                 message: message.into(),
@@ -525,7 +594,10 @@ impl IpcContextClient {
         object_ref: ObjectReference,
     ) -> Result<Cow<ObjectHash>, ContextServiceError> {
         let mut io = self.io.borrow_mut();
-        io.tx.send(&ContextRequest::GetHash(object_ref))?;
+        io.tx.send(&ContextRequest::GetHash(
+            object_ref,
+            self.gc_generation.get(),
+        ))?;
 
         // this might take a while, so we will use unusually long timeout
         match io
@@ -535,6 +607,9 @@ impl IpcContextClient {
             ContextResponse::GetHashResponse(result) => result
                 .map(Cow::Owned)
                 .map_err(|err| ContextError::GetHashError { reason: err }.into()),
+            ContextResponse::GarbageCollected(generation) => {
+                Err(self.garbage_collected(generation))
+            }
             message => Err(ContextServiceError::UnexpectedMessage {
                 message: message.into(),
             }),
@@ -543,7 +618,10 @@ impl IpcContextClient {
 
     pub fn get_hash_id(&self, object_ref: ObjectReference) -> Result<HashId, ContextServiceError> {
         let mut io = self.io.borrow_mut();
-        io.tx.send(&ContextRequest::GetHashId(object_ref))?;
+        io.tx.send(&ContextRequest::GetHashId(
+            object_ref,
+            self.gc_generation.get(),
+        ))?;
 
         // this might take a while, so we will use unusually long timeout
         match io
@@ -553,6 +631,9 @@ impl IpcContextClient {
             ContextResponse::GetHashIdResponse(result) => {
                 result.map_err(|err| ContextError::GetHashIdError { reason: err }.into())
             }
+            ContextResponse::GarbageCollected(generation) => {
+                Err(self.garbage_collected(generation))
+            }
             message => Err(ContextServiceError::UnexpectedMessage {
                 message: message.into(),
             }),
@@ -565,7 +646,10 @@ impl IpcContextClient {
         shape_id: DirectoryShapeId,
     ) -> Result<Vec<String>, ContextServiceError> {
         let mut io = self.io.borrow_mut();
-        io.tx.send(&ContextRequest::GetShape(shape_id))?;
+        io.tx.send(&ContextRequest::GetShape(
+            shape_id,
+            self.gc_generation.get(),
+        ))?;
 
         // this might take a while, so we will use unusually long timeout
         match io
@@ -575,6 +659,9 @@ impl IpcContextClient {
             ContextResponse::GetShapeResponse(result) => {
                 result.map_err(|err| ContextError::GetShapeError { reason: err }.into())
             }
+            ContextResponse::GarbageCollected(generation) => {
+                Err(self.garbage_collected(generation))
+            }
             message => Err(ContextServiceError::UnexpectedMessage {
                 message: message.into(),
             }),
@@ -587,7 +674,10 @@ impl IpcContextClient {
         buffer: &'a mut Vec<u8>,
     ) -> Result<&'a [u8], ContextServiceError> {
         let mut io = self.io.borrow_mut();
-        io.tx.send(&ContextRequest::GetObjectBytes(object_ref))?;
+        io.tx.send(&ContextRequest::GetObjectBytes(
+            object_ref,
+            self.gc_generation.get(),
+        ))?;
 
         match io
             .rx
@@ -604,6 +694,9 @@ impl IpcContextClient {
                 buffer.append(&mut result);
                 Ok(buffer)
             }
+            ContextResponse::GarbageCollected(generation) => {
+                Err(self.garbage_collected(generation))
+            }
             message => Err(ContextServiceError::UnexpectedMessage {
                 message: message.into(),
             }),
@@ -740,6 +833,17 @@ impl IpcContextServer {
             })
     }
 
+    /// Returns the current generation of `repository` when the request refers
+    /// to another generation.
+    fn changed_gc_generation(repository: &ContextKeyValueStore, generation: u64) -> Option<u64> {
+        let current = repository.gc_generation();
+        if current != generation {
+            Some(current)
+        } else {
+            None
+        }
+    }
+
     /// Listen to new connections from context readers.
     /// Begin receiving commands from context readers until `ShutdownCall` command is received.
     pub fn process_context_requests(&self, log: &Logger) -> Result<(), IpcContextError> {
@@ -748,30 +852,50 @@ impl IpcContextServer {
             let cmd = io.rx.receive()?;
 
             match cmd {
-                ContextRequest::GetShape(shape_id) => match crate::ffi::get_context_index()? {
-                    None => io.tx.send(&ContextResponse::GetShapeResponse(Err(
-                        "Context index unavailable".to_owned(),
-                    )))?,
-                    Some(index) => {
-                        let repository = index.repository.read();
+                ContextRequest::GetShape(shape_id, generation) => {
+                    match crate::ffi::get_context_index()? {
+                        None => io.tx.send(&ContextResponse::GetShapeResponse(Err(
+                            "Context index unavailable".to_owned(),
+                        )))?,
+                        Some(index) => {
+                            let repository = index.repository.read();
 
-                        let res = Self::get_shape(shape_id, &*repository)
-                            .map_err(|err| format!("Context error: {:?}", err));
+                            if let Some(current) =
+                                Self::changed_gc_generation(&*repository, generation)
+                            {
+                                io.tx.send(&ContextResponse::GarbageCollected(current))?;
+                                continue;
+                            }
 
-                        io.tx.send(&ContextResponse::GetShapeResponse(res))?;
+                            let res = Self::get_shape(shape_id, &*repository)
+                                .map_err(|err| format!("Context error: {:?}", err));
+
+                            io.tx.send(&ContextResponse::GetShapeResponse(res))?;
+                        }
                     }
-                },
-                ContextRequest::ContainsObject(hash) => match crate::ffi::get_context_index()? {
-                    None => io.tx.send(&ContextResponse::ContainsObjectResponse(Err(
-                        "Context index unavailable".to_owned(),
-                    )))?,
-                    Some(index) => {
-                        let res = index
-                            .contains(hash)
-                            .map_err(|err| format!("Context error: {:?}", err));
-                        io.tx.send(&ContextResponse::ContainsObjectResponse(res))?;
+                }
+                ContextRequest::ContainsObject(hash, generation) => {
+                    match crate::ffi::get_context_index()? {
+                        None => io.tx.send(&ContextResponse::ContainsObjectResponse(Err(
+                            "Context index unavailable".to_owned(),
+                        )))?,
+                        Some(index) => {
+                            let repository = index.repository.read();
+
+                            if let Some(current) =
+                                Self::changed_gc_generation(&*repository, generation)
+                            {
+                                io.tx.send(&ContextResponse::GarbageCollected(current))?;
+                                continue;
+                            }
+
+                            let res = repository
+                                .contains(hash)
+                                .map_err(|err| format!("Context error: {:?}", err));
+                            io.tx.send(&ContextResponse::ContainsObjectResponse(res))?;
+                        }
                     }
-                },
+                }
 
                 ContextRequest::ShutdownCall => {
                     if let Err(e) = io.tx.send(&ContextResponse::ShutdownResult) {
@@ -782,32 +906,49 @@ impl IpcContextServer {
                 }
                 ContextRequest::GetContextHashId(context_hash) => {
                     match crate::ffi::get_context_index()? {
-                        None => io.tx.send(&ContextResponse::GetContextHashIdResponse(Err(
+                        None => io.tx.send(&ContextResponse::GetContextHashIdResponse(
+                            Err("Context index unavailable".to_owned()),
+                            0,
+                        ))?,
+                        Some(index) => {
+                            let repository = index.repository.read();
+
+                            let res = repository
+                                .get_context_hash(&context_hash)
+                                .map_err(|err| format!("Context error: {:?}", err));
+
+                            io.tx.send(&ContextResponse::GetContextHashIdResponse(
+                                res,
+                                repository.gc_generation(),
+                            ))?;
+                        }
+                    }
+                }
+                ContextRequest::GetHash(object_ref, generation) => {
+                    match crate::ffi::get_context_index()? {
+                        None => io.tx.send(&ContextResponse::GetHashResponse(Err(
                             "Context index unavailable".to_owned(),
                         )))?,
                         Some(index) => {
-                            let res = index
-                                .fetch_context_hash_id(&context_hash)
+                            let repository = index.repository.read();
+
+                            if let Some(current) =
+                                Self::changed_gc_generation(&*repository, generation)
+                            {
+                                io.tx.send(&ContextResponse::GarbageCollected(current))?;
+                                continue;
+                            }
+
+                            let res = repository
+                                .get_hash(object_ref)
+                                .map(|hash| hash.into_owned())
                                 .map_err(|err| format!("Context error: {:?}", err));
 
-                            io.tx
-                                .send(&ContextResponse::GetContextHashIdResponse(res))?;
+                            io.tx.send(&ContextResponse::GetHashResponse(res))?;
                         }
                     }
                 }
-                ContextRequest::GetHash(object_ref) => match crate::ffi::get_context_index()? {
-                    None => io.tx.send(&ContextResponse::GetHashResponse(Err(
-                        "Context index unavailable".to_owned(),
-                    )))?,
-                    Some(index) => {
-                        let res = index
-                            .fetch_hash(object_ref)
-                            .map_err(|err| format!("Context error: {:?}", err));
-
-                        io.tx.send(&ContextResponse::GetHashResponse(res))?;
-                    }
-                },
-                ContextRequest::GetObjectBytes(object_ref) => {
+                ContextRequest::GetObjectBytes(object_ref, generation) => {
                     match crate::ffi::get_context_index()? {
                         None => io.tx.send(&ContextResponse::GetObjectBytesResponse(Err(
                             "Context index unavailable".to_owned(),
@@ -815,6 +956,13 @@ impl IpcContextServer {
                         Some(index) => {
                             let repository = index.repository.read();
 
+                            if let Some(current) =
+                                Self::changed_gc_generation(&*repository, generation)
+                            {
+                                io.tx.send(&ContextResponse::GarbageCollected(current))?;
+                                continue;
+                            }
+
                             let res = Self::get_object_bytes(object_ref, &*repository)
                                 .map_err(|err| format!("Context error: {:?}", err));
 
@@ -822,19 +970,28 @@ impl IpcContextServer {
                         }
                     }
                 }
-                ContextRequest::GetHashId(object_ref) => match crate::ffi::get_context_index()? {
-                    None => io.tx.send(&ContextResponse::GetHashIdResponse(Err(
-                        "Context index unavailable".to_owned(),
-                    )))?,
-                    Some(index) => {
-                        let repository = index.repository.read();
+                ContextRequest::GetHashId(object_ref, generation) => {
+                    match crate::ffi::get_context_index()? {
+                        None => io.tx.send(&ContextResponse::GetHashIdResponse(Err(
+                            "Context index unavailable".to_owned(),
+                        )))?,
+                        Some(index) => {
+                            let repository = index.repository.read();
 
-                        let res = Self::get_hash_id(object_ref, &*repository)
-                            .map_err(|err| format!("Context error: {:?}", err));
+                            if let Some(current) =
+                                Self::changed_gc_generation(&*repository, generation)
+                            {
+                                io.tx.send(&ContextResponse::GarbageCollected(current))?;
+                                continue;
+                            }
 
-                        io.tx.send(&ContextResponse::GetHashIdResponse(res))?;
+                            let res = Self::get_hash_id(object_ref, &*repository)
+                                .map_err(|err| format!("Context error: {:?}", err));
+
+                            io.tx.send(&ContextResponse::GetHashIdResponse(res))?;
+                        }
                     }
-                },
+                }
             }
         }
 
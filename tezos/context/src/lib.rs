@@ -138,6 +138,7 @@ pub mod working_tree;
 pub mod ffi;
 pub mod from_ocaml;
 pub mod initializer;
+pub mod proof;
 pub mod timings;
 
 pub mod snapshot;
@@ -299,6 +300,20 @@ pub enum ContextError {
     LockError { reason: String },
 }
 
+impl ContextError {
+    /// Returns `true` when the repository has been replaced by the garbage collector
+    /// during the operation, it can be retried from a new checkout.
+    pub fn is_garbage_collected(&self) -> bool {
+        match self {
+            ContextError::DBError { error }
+            | ContextError::MerkleStorageError {
+                error: MerkleError::DBError { error },
+            } => error.is_garbage_collected(),
+            _ => false,
+        }
+    }
+}
+
 impl From<MerkleError> for ContextError {
     fn from(error: MerkleError) -> Self {
         ContextError::MerkleStorageError { error }
@@ -60,6 +60,11 @@ impl Lock {
         Ok(Self { path })
     }
 
+    /// The directory of the lock file has been moved to `base_path`
+    pub fn moved_to(&mut self, base_path: &str) {
+        self.path = PathBuf::from(base_path).join("lock");
+    }
+
     fn create_lock_file(path: &Path) -> Result<(), LockDatabaseError> {
         loop {
             match OpenOptions::new().write(true).create_new(true).open(path) {
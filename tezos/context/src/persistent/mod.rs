@@ -295,6 +295,25 @@ pub enum DBError {
         #[from]
         err: IndexInitializationError,
     },
+    #[error("Objects read from the repository generation {read_generation} are invalid since the garbage collection, the repository is at generation {repository_generation}")]
+    GarbageCollected {
+        read_generation: u64,
+        repository_generation: u64,
+    },
+}
+
+impl DBError {
+    /// Returns `true` when the repository has been replaced by the garbage collector
+    /// while reading it, the operation can be retried from a new checkout.
+    pub fn is_garbage_collected(&self) -> bool {
+        matches!(
+            self,
+            DBError::GarbageCollected { .. }
+                | DBError::IpcAccessError {
+                    reason: ContextServiceError::GarbageCollected { .. }
+                }
+        )
+    }
 }
 
 impl From<HashIdError> for DBError {
@@ -0,0 +1,600 @@
+// Copyright (c) SimpleStaking, Viable Systems and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Merkle proofs of context values.
+//!
+//! A proof has the shape of Irmin's `Proof.tree`: the directories and inodes on the path to
+//! the key are revealed, everything else is blinded (replaced by its hash). The hashes of the
+//! revealed objects are recomputed with the same encodings as in `crate::hash`, so the proof
+//! can be checked against a `ContextHash` with [`verify_proof`], without access to the context.
+
+use blake2::digest::{Update, VariableOutput};
+use blake2::VarBlake2b;
+use crypto::hash::{ContextHash, HashTrait};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::hash::index as index_of_key;
+use crate::hash::{encode_irmin_dir_entry_kind, hash_inlined_blob, HashingError};
+use crate::hash::{ObjectHash, OBJECT_HASH_LEN};
+use crate::working_tree::storage::{DirEntryId, DirectoryId, DirectoryOrInodeId, InodeId, Storage};
+use crate::working_tree::string_interner::StringInterner;
+use crate::working_tree::working_tree::MerkleError;
+use crate::working_tree::{DirEntryKind, Object, ObjectReference};
+use crate::{ContextKey, ContextKeyValueStore, TezedgeIndex};
+
+/// Proof of a key in the context of a commit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextProof {
+    pub commit: CommitProof,
+    /// Root directory of the commit
+    pub tree: ProofTree,
+}
+
+/// Fields of the commit hashed together with the root directory hash into the `ContextHash`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitProof {
+    #[serde(with = "object_hash_option_b58")]
+    pub parent: Option<ObjectHash>,
+    pub time: u64,
+    pub author: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofTree {
+    Value(#[serde(with = "hex_bytes")] Vec<u8>),
+    BlindedValue(#[serde(with = "object_hash_b58")] ObjectHash),
+    /// Directory of at most 256 entries, all of them are listed
+    Node(Vec<(String, ProofTree)>),
+    BlindedNode(#[serde(with = "object_hash_b58")] ObjectHash),
+    /// Directory of more than 256 entries
+    Inode(InodeProof),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InodeProof {
+    /// Number of entries in the directory under this inode
+    pub length: u64,
+    /// Pointers of the inode, by index
+    pub proofs: Vec<(u8, InodeProofTree)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InodeProofTree {
+    BlindedInode(#[serde(with = "object_hash_b58")] ObjectHash),
+    InodeValues(Vec<(String, ProofTree)>),
+    InodeTree(InodeProof),
+}
+
+#[derive(Debug, Error)]
+pub enum ProofError {
+    #[error("The key is empty")]
+    KeyEmpty,
+    #[error("Proof is for context hash {found}, expected {expected}")]
+    ContextHashMismatch { expected: String, found: String },
+    #[error("The path to the key {key} is blinded in the proof")]
+    BlindedPath { key: String },
+    #[error("Failed to hash the proof: {error}")]
+    HashingError {
+        #[from]
+        error: HashingError,
+    },
+}
+
+/// Builds the proof of `key` in the commit `object_ref`.
+pub(crate) fn build_proof(
+    index: &TezedgeIndex,
+    object_ref: ObjectReference,
+    key: &ContextKey,
+    storage: &mut Storage,
+    strings: &mut StringInterner,
+    repository: &ContextKeyValueStore,
+) -> Result<ContextProof, MerkleError> {
+    if key.is_empty() {
+        return Err(MerkleError::KeyEmpty);
+    }
+
+    let commit = index.get_commit(object_ref, storage, strings)?;
+    let parent = match commit.parent_commit_ref {
+        Some(parent_ref) => Some(repository.get_hash(parent_ref)?.into_owned()),
+        None => None,
+    };
+    let root_dir_id = index.get_directory(commit.root_ref, storage, strings)?;
+
+    let mut builder = ProofBuilder {
+        index,
+        storage,
+        strings,
+        repository,
+    };
+
+    Ok(ContextProof {
+        commit: CommitProof {
+            parent,
+            time: commit.time,
+            author: commit.author,
+            message: commit.message,
+        },
+        tree: builder.directory(root_dir_id, key)?,
+    })
+}
+
+struct ProofBuilder<'a> {
+    index: &'a TezedgeIndex,
+    storage: &'a mut Storage,
+    strings: &'a mut StringInterner,
+    repository: &'a ContextKeyValueStore,
+}
+
+impl ProofBuilder<'_> {
+    /// Reveals the directory `dir_id`, `key` is the rest of the path from this directory.
+    fn directory(
+        &mut self,
+        dir_id: DirectoryId,
+        key: &ContextKey,
+    ) -> Result<ProofTree, MerkleError> {
+        match dir_id.get_inode_id() {
+            Some(inode_id) => Ok(ProofTree::Inode(self.inode(inode_id, key)?)),
+            None => Ok(ProofTree::Node(self.small_dir(dir_id, key)?)),
+        }
+    }
+
+    fn small_dir(
+        &mut self,
+        dir_id: DirectoryId,
+        key: &ContextKey,
+    ) -> Result<Vec<(String, ProofTree)>, MerkleError> {
+        let dir = self.storage.get_small_dir(dir_id)?.to_vec();
+        let mut entries = Vec::with_capacity(dir.len());
+
+        for (name_id, dir_entry_id) in dir {
+            let name = self.strings.get_str(name_id)?.to_string();
+
+            let tree = if key[0] == name {
+                self.dir_entry(dir_entry_id, &key[1..])?
+            } else {
+                self.blinded_dir_entry(dir_entry_id)?
+            };
+            entries.push((name, tree));
+        }
+
+        Ok(entries)
+    }
+
+    fn inode(&mut self, inode_id: InodeId, key: &ContextKey) -> Result<InodeProof, MerkleError> {
+        let (depth, nchildren, pointers) = {
+            let inode = self.storage.get_inode(inode_id)?;
+            (inode.depth, inode.nchildren, inode.pointers)
+        };
+        let index_at_depth = index_of_key(depth as u32, key[0]) as usize;
+
+        let mut proofs = Vec::with_capacity(pointers.npointers());
+        for (ptr_index, thin_pointer_id) in pointers.iter() {
+            let tree = if ptr_index == index_at_depth {
+                let ptr_id =
+                    self.storage
+                        .pointer_load(thin_pointer_id, self.repository, self.strings)?;
+
+                match ptr_id {
+                    DirectoryOrInodeId::Directory(dir_id) => {
+                        InodeProofTree::InodeValues(self.small_dir(dir_id, key)?)
+                    }
+                    DirectoryOrInodeId::Inode(inode_id) => {
+                        InodeProofTree::InodeTree(self.inode(inode_id, key)?)
+                    }
+                }
+            } else {
+                let pointer = self.storage.pointer_copy(thin_pointer_id)?;
+                let hash_id = self
+                    .storage
+                    .pointer_retrieve_hashid(&pointer, self.repository)?
+                    .ok_or(HashingError::MissingPointer)?;
+                let hash = self
+                    .repository
+                    .get_hash(ObjectReference::new(Some(hash_id), None))?;
+
+                InodeProofTree::BlindedInode(hash.into_owned())
+            };
+            proofs.push((ptr_index as u8, tree));
+        }
+
+        Ok(InodeProof {
+            length: nchildren as u64,
+            proofs,
+        })
+    }
+
+    /// Reveals the entry on the path, `key` is the rest of the path from this entry.
+    fn dir_entry(
+        &mut self,
+        dir_entry_id: DirEntryId,
+        key: &ContextKey,
+    ) -> Result<ProofTree, MerkleError> {
+        match self
+            .index
+            .dir_entry_object(dir_entry_id, self.storage, self.strings)?
+        {
+            // Also when the key continues, the value shows that the path doesn't exist
+            Object::Blob(blob_id) => Ok(ProofTree::Value(self.storage.get_blob(blob_id)?.to_vec())),
+            Object::Directory(_) if key.is_empty() => self.blinded_dir_entry(dir_entry_id),
+            Object::Directory(dir_id) => self.directory(dir_id, key),
+            Object::Commit(_) => Err(MerkleError::FoundUnexpectedStructure {
+                sought: "Directory/Blob".to_string(),
+                found: "Commit".to_string(),
+            }),
+        }
+    }
+
+    fn blinded_dir_entry(&mut self, dir_entry_id: DirEntryId) -> Result<ProofTree, MerkleError> {
+        let dir_entry = self.storage.get_dir_entry(dir_entry_id)?;
+
+        let hash = match dir_entry.get_inlined_blob(&*self.storage) {
+            Some(blob) => hash_inlined_blob(blob)?,
+            None => self
+                .repository
+                .get_hash(dir_entry.get_reference())?
+                .into_owned(),
+        };
+
+        match dir_entry.dir_entry_kind() {
+            DirEntryKind::Blob => Ok(ProofTree::BlindedValue(hash)),
+            DirEntryKind::Directory => Ok(ProofTree::BlindedNode(hash)),
+        }
+    }
+}
+
+/// Checks `proof` against `context_hash` and returns the value of `key` in it.
+///
+/// Returns `Ok(None)` when the proof shows that the key has no value (the path doesn't exist,
+/// or it is a directory), and an error if the proof doesn't hash to `context_hash` or if it
+/// blinds a part of the path to `key`.
+pub fn verify_proof(
+    proof: &ContextProof,
+    context_hash: &ContextHash,
+    key: &ContextKey,
+) -> Result<Option<Vec<u8>>, ProofError> {
+    if key.is_empty() {
+        return Err(ProofError::KeyEmpty);
+    }
+
+    let root_hash = hash_tree(&proof.tree)?;
+    let commit_hash = hash_commit_proof(&proof.commit, &root_hash)?;
+
+    if commit_hash[..] != context_hash.as_ref()[..] {
+        return Err(ProofError::ContextHashMismatch {
+            expected: context_hash.to_base58_check(),
+            found: ContextHash::try_from_bytes(&commit_hash)
+                .map(|hash| hash.to_base58_check())
+                .unwrap_or_else(|_| hex::encode(commit_hash)),
+        });
+    }
+
+    let mut tree = &proof.tree;
+    for step in key {
+        let lookup = match tree {
+            ProofTree::Node(entries) => lookup_entry(entries, step),
+            ProofTree::Inode(inode) => lookup_inode(inode, 0, step),
+            ProofTree::BlindedNode(_) => Lookup::Blinded,
+            // A value on the path, the key doesn't exist
+            ProofTree::Value(_) | ProofTree::BlindedValue(_) => Lookup::Absent,
+        };
+
+        tree = match lookup {
+            Lookup::Found(child) => child,
+            Lookup::Absent => return Ok(None),
+            Lookup::Blinded => return Err(ProofError::BlindedPath { key: key.join("/") }),
+        };
+    }
+
+    match tree {
+        ProofTree::Value(value) => Ok(Some(value.clone())),
+        ProofTree::BlindedValue(_) => Err(ProofError::BlindedPath { key: key.join("/") }),
+        ProofTree::Node(_) | ProofTree::BlindedNode(_) | ProofTree::Inode(_) => Ok(None),
+    }
+}
+
+/// Result of one step of the path in a proof
+enum Lookup<'a> {
+    Found(&'a ProofTree),
+    Absent,
+    Blinded,
+}
+
+fn lookup_entry<'a>(entries: &'a [(String, ProofTree)], step: &str) -> Lookup<'a> {
+    match entries.iter().find(|(name, _)| name == step) {
+        Some((_, tree)) => Lookup::Found(tree),
+        None => Lookup::Absent,
+    }
+}
+
+fn lookup_inode<'a>(inode: &'a InodeProof, depth: u32, step: &str) -> Lookup<'a> {
+    let index_at_depth = index_of_key(depth, step) as u8;
+
+    match inode.proofs.iter().find(|(i, _)| *i == index_at_depth) {
+        None => Lookup::Absent,
+        Some((_, InodeProofTree::BlindedInode(_))) => Lookup::Blinded,
+        Some((_, InodeProofTree::InodeValues(entries))) => lookup_entry(entries, step),
+        Some((_, InodeProofTree::InodeTree(inode))) => lookup_inode(inode, depth + 1, step),
+    }
+}
+
+fn hash_value(value: &[u8]) -> Result<ObjectHash, HashingError> {
+    let mut hasher = VarBlake2b::new(OBJECT_HASH_LEN)?;
+    hasher.update(&(value.len() as u64).to_be_bytes());
+    hasher.update(value);
+
+    Ok(finalize(hasher))
+}
+
+fn finalize(hasher: VarBlake2b) -> ObjectHash {
+    let mut object_hash: ObjectHash = Default::default();
+    hasher.finalize_variable(|r| object_hash.copy_from_slice(r));
+    object_hash
+}
+
+fn tree_kind(tree: &ProofTree) -> DirEntryKind {
+    match tree {
+        ProofTree::Value(_) | ProofTree::BlindedValue(_) => DirEntryKind::Blob,
+        ProofTree::Node(_) | ProofTree::BlindedNode(_) | ProofTree::Inode(_) => {
+            DirEntryKind::Directory
+        }
+    }
+}
+
+/// Same encodings as `hash::hash_short_inode` and `hash::hash_long_inode`
+fn hash_tree(tree: &ProofTree) -> Result<ObjectHash, HashingError> {
+    match tree {
+        ProofTree::Value(value) => hash_value(value),
+        ProofTree::BlindedValue(hash) | ProofTree::BlindedNode(hash) => Ok(*hash),
+        ProofTree::Node(entries) => {
+            let mut hasher = VarBlake2b::new(OBJECT_HASH_LEN)?;
+            hasher.update(&(entries.len() as u64).to_be_bytes());
+
+            for (name, tree) in entries {
+                hasher.update(encode_irmin_dir_entry_kind(&tree_kind(tree)));
+                leb128::write::unsigned(&mut hasher, name.len() as u64)?;
+                hasher.update(name.as_bytes());
+                hasher.update(&(OBJECT_HASH_LEN as u64).to_be_bytes());
+                hasher.update(hash_tree(tree)?);
+            }
+
+            Ok(finalize(hasher))
+        }
+        ProofTree::Inode(inode) => hash_inode(inode, 0),
+    }
+}
+
+fn hash_inode(inode: &InodeProof, depth: u32) -> Result<ObjectHash, HashingError> {
+    let mut hasher = VarBlake2b::new(OBJECT_HASH_LEN)?;
+
+    hasher.update(&[1u8]);
+    leb128::write::unsigned(&mut hasher, depth as u64)?;
+    leb128::write::unsigned(&mut hasher, inode.length)?;
+    hasher.update(&[inode.proofs.len() as u8]);
+
+    for (ptr_index, pointer) in &inode.proofs {
+        hasher.update(&[*ptr_index]);
+
+        let hash = match pointer {
+            InodeProofTree::BlindedInode(hash) => *hash,
+            InodeProofTree::InodeTree(inode) => hash_inode(inode, depth + 1)?,
+            InodeProofTree::InodeValues(entries) => {
+                let mut hasher = VarBlake2b::new(OBJECT_HASH_LEN)?;
+                hasher.update(&[0u8]);
+                hasher.update(&[entries.len() as u8]);
+
+                for (name, tree) in entries {
+                    leb128::write::unsigned(&mut hasher, name.len() as u64)?;
+                    hasher.update(name.as_bytes());
+                    match tree_kind(tree) {
+                        DirEntryKind::Blob => hasher.update(&[1u8]),
+                        DirEntryKind::Directory => hasher.update(&[0u8]),
+                    };
+                    hasher.update(hash_tree(tree)?);
+                }
+
+                finalize(hasher)
+            }
+        };
+        hasher.update(hash);
+    }
+
+    Ok(finalize(hasher))
+}
+
+/// Same encoding as `hash::hash_commit`
+fn hash_commit_proof(
+    commit: &CommitProof,
+    root_hash: &ObjectHash,
+) -> Result<ObjectHash, HashingError> {
+    let mut hasher = VarBlake2b::new(OBJECT_HASH_LEN)?;
+    hasher.update(&(OBJECT_HASH_LEN as u64).to_be_bytes());
+    hasher.update(root_hash);
+
+    if let Some(parent) = commit.parent.as_ref() {
+        hasher.update(&(1_u64).to_be_bytes());
+        hasher.update(&(parent.len() as u64).to_be_bytes());
+        hasher.update(parent);
+    } else {
+        hasher.update(&(0_u64).to_be_bytes());
+    }
+
+    hasher.update(&commit.time.to_be_bytes());
+    hasher.update(&(commit.author.len() as u64).to_be_bytes());
+    hasher.update(commit.author.as_bytes());
+    hasher.update(&(commit.message.len() as u64).to_be_bytes());
+    hasher.update(commit.message.as_bytes());
+
+    Ok(finalize(hasher))
+}
+
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(d)?;
+        hex::decode(s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Hashes of the objects are encoded like the context hashes, as in Irmin's proofs
+mod object_hash_b58 {
+    use crypto::hash::{ContextHash, HashTrait};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::hash::ObjectHash;
+
+    pub fn serialize<S: Serializer>(hash: &ObjectHash, s: S) -> Result<S::Ok, S::Error> {
+        let hash = ContextHash::try_from_bytes(hash).map_err(serde::ser::Error::custom)?;
+        s.serialize_str(&hash.to_base58_check())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<ObjectHash, D::Error> {
+        let s = String::deserialize(d)?;
+        let hash = ContextHash::from_base58_check(&s).map_err(serde::de::Error::custom)?;
+        hash.as_ref()
+            .as_slice()
+            .try_into()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+mod object_hash_option_b58 {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::hash::ObjectHash;
+
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper(#[serde(with = "super::object_hash_b58")] ObjectHash);
+
+    pub fn serialize<S: Serializer>(hash: &Option<ObjectHash>, s: S) -> Result<S::Ok, S::Error> {
+        hash.map(Wrapper).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<ObjectHash>, D::Error> {
+        Ok(Option::<Wrapper>::deserialize(d)?.map(|Wrapper(hash)| hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tezos_context_api::{
+        ContextKvStoreConfiguration, TezosContextTezEdgeStorageConfiguration,
+        TezosContextTezedgeOnDiskBackendOptions,
+    };
+
+    use super::*;
+    use crate::initializer::initialize_tezedge_context;
+    use crate::{IndexApi, ProtocolContextApi, ShellContextApi, TezedgeContext};
+
+    fn commit_context() -> (TezedgeContext, ContextHash) {
+        let context = initialize_tezedge_context(&TezosContextTezEdgeStorageConfiguration {
+            backend: ContextKvStoreConfiguration::InMem(TezosContextTezedgeOnDiskBackendOptions {
+                base_path: "".to_string(),
+                startup_check: false,
+            }),
+            ipc_socket_path: None,
+        })
+        .unwrap();
+
+        let context = context.add(&["a", "b", "c"], &[1, 2, 3]).unwrap();
+        let context = context.add(&["m", "n", "o"], &[4, 5, 6]).unwrap();
+        let context_hash = context
+            .commit("Tezedge".to_string(), "first".to_string(), 1)
+            .unwrap();
+        let context = context.index.checkout(&context_hash).unwrap().unwrap();
+
+        // Enough entries for nested inodes
+        let mut context = context.add(&["a", "long value"], &[7; 100]).unwrap();
+        for i in 0..2000 {
+            let key = format!("key_{}", i);
+            context = context
+                .add(&["big", key.as_str()], i.to_string().as_bytes())
+                .unwrap();
+        }
+        let context_hash = context
+            .commit("Tezedge".to_string(), "second".to_string(), 2)
+            .unwrap();
+
+        (context, context_hash)
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        let (context, context_hash) = commit_context();
+
+        for (key, value) in [
+            (&["a", "b", "c"][..], Some(vec![1, 2, 3])),
+            (&["a", "long value"][..], Some(vec![7; 100])),
+            (&["big", "key_1234"][..], Some(b"1234".to_vec())),
+            (&["big", "key_1234", "x"][..], None),
+            (&["big", "no_such_key"][..], None),
+            (&["big"][..], None),
+            (&["z"][..], None),
+        ] {
+            let proof = context
+                .index
+                .get_merkle_proof(&context_hash, key)
+                .unwrap()
+                .unwrap();
+            assert!(proof.commit.parent.is_some());
+
+            assert_eq!(verify_proof(&proof, &context_hash, key).unwrap(), value);
+
+            let json = serde_json::to_string(&proof).unwrap();
+            let decoded: ContextProof = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded, proof);
+        }
+    }
+
+    #[test]
+    fn test_verify_invalid_proof() {
+        let (context, context_hash) = commit_context();
+        let key = &["big", "key_1234"];
+        let proof = context
+            .index
+            .get_merkle_proof(&context_hash, key)
+            .unwrap()
+            .unwrap();
+
+        // Other keys are blinded
+        assert!(matches!(
+            verify_proof(&proof, &context_hash, &["a", "b", "c"]),
+            Err(ProofError::BlindedPath { .. })
+        ));
+        assert!(matches!(
+            verify_proof(&proof, &context_hash, &["big", "key_1"]),
+            Err(ProofError::BlindedPath { .. })
+        ));
+
+        let mut tampered = proof.clone();
+        tampered.commit.message = "third".to_string();
+        assert!(matches!(
+            verify_proof(&tampered, &context_hash, key),
+            Err(ProofError::ContextHashMismatch { .. })
+        ));
+
+        let json = serde_json::to_string(&proof).unwrap();
+        let hex_value = hex::encode(b"1234");
+        let tampered: ContextProof =
+            serde_json::from_str(&json.replace(&hex_value, &hex::encode(b"4321"))).unwrap();
+        assert!(matches!(
+            verify_proof(&tampered, &context_hash, key),
+            Err(ProofError::ContextHashMismatch { .. })
+        ));
+
+        let unknown = ContextHash::try_from_bytes(&[0; 32]).unwrap();
+        assert!(context
+            .index
+            .get_merkle_proof(&unknown, key)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -3,7 +3,7 @@
 
 use std::{cell::RefCell, rc::Rc, sync::Arc};
 
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use crypto::hash::ContextHash;
 use parking_lot::RwLock;
 
@@ -33,6 +33,88 @@ pub fn reload_context_readonly(context_path: String) -> Result<Persistent, Error
     Ok(repo)
 }
 
+/// Verifies the integrity of a context that is not written anymore (e.g. an imported snapshot).
+///
+/// The files must match the latest sizes & checksums recorded in `sizes.db`, and the
+/// hashes of the whole tree of the commit are recomputed, the commit must hash
+/// back to `context_hash`.
+///
+/// Returns the root hash of the commit.
+pub fn verify_context(
+    context_path: String,
+    context_hash: &ContextHash,
+) -> Result<ObjectHash, Error> {
+    FileSizes::verify_latest(&context_path)?;
+
+    let read_repo: Arc<RwLock<ContextKeyValueStore>> =
+        Arc::new(RwLock::new(reload_context_readonly(context_path)?));
+    let index = TezedgeIndex::new(Arc::clone(&read_repo), None);
+
+    let computed_hash = compute_context_hash(&index, context_hash, |_| ())?;
+
+    if &computed_hash != context_hash {
+        return Err(anyhow!(
+            "Commit {} hashes to {}",
+            context_hash.to_base58_check(),
+            computed_hash.to_base58_check()
+        ));
+    }
+
+    let commit = fetch_commit(&index, context_hash)?;
+    let root_hash = read_repo.read().get_hash(commit.root_ref)?.into_owned();
+
+    Ok(root_hash)
+}
+
+fn fetch_commit(index: &TezedgeIndex, context_hash: &ContextHash) -> Result<Commit, Error> {
+    index
+        .fetch_commit_from_context_hash(context_hash)?
+        .ok_or_else(|| {
+            anyhow!(
+                "Commit {} not found in the context",
+                context_hash.to_base58_check()
+            )
+        })
+}
+
+/// Loads the whole tree of the commit and computes its context hash again,
+/// from the content of the tree, without using the hashes stored in the repository.
+fn compute_context_hash(
+    index: &TezedgeIndex,
+    context_hash: &ContextHash,
+    log: fn(&str) -> (),
+) -> Result<ContextHash, Error> {
+    let mut context = index.checkout(context_hash)?.ok_or_else(|| {
+        anyhow!(
+            "Commit {} not found in the context",
+            context_hash.to_base58_check()
+        )
+    })?;
+
+    let commit = fetch_commit(index, context_hash)?;
+    context.parent_commit_ref = commit.parent_commit_ref;
+
+    let now = std::time::Instant::now();
+    log(" Loading in memory...");
+
+    // Fetch all objects into `Storage`
+    context.tree.traverse_working_tree(false)?;
+
+    log(&format!(" Loading in memory ok {:?}", now.elapsed()));
+
+    // Remove all `HashId` to re-compute them
+    context.index.storage.borrow_mut().forget_references();
+
+    let now = std::time::Instant::now();
+    log(" Recomputing hashes...");
+
+    let context_hash = context.hash(commit.author, commit.message, commit.time as i64)?;
+
+    log(&format!(" Recomputing hashes ok {:?}", now.elapsed()));
+
+    Ok(context_hash)
+}
+
 /// Reads the whole tree of the commit, to extract `Storage`, that's
 /// where all the objects (directories and blobs) are stored
 pub fn read_commit_tree(
@@ -50,12 +132,22 @@ pub fn read_commit_tree(
 > {
     let read_repo: Arc<RwLock<ContextKeyValueStore>> = Arc::new(RwLock::new(ctx));
     let index = TezedgeIndex::new(Arc::clone(&read_repo), None);
-    let context = index.checkout(checkout_context_hash)?.unwrap();
+    let context = index.checkout(checkout_context_hash)?.ok_or_else(|| {
+        anyhow!(
+            "Commit {} not found in the context",
+            checkout_context_hash.to_base58_check()
+        )
+    })?;
 
     // Take the commit from repository
     let commit: Commit = index
         .fetch_commit_from_context_hash(checkout_context_hash)?
-        .unwrap();
+        .ok_or_else(|| {
+            anyhow!(
+                "Commit {} not found in the context",
+                checkout_context_hash.to_base58_check()
+            )
+        })?;
 
     // If the commit has a parent, fetch it
     // It is necessary for the snapshot to have it in its db
@@ -164,32 +256,70 @@ pub fn recompute_hashes(
     let read_repo: Arc<RwLock<ContextKeyValueStore>> = Arc::new(RwLock::new(read_ctx));
 
     let index = TezedgeIndex::new(Arc::clone(&read_repo), None);
-    let mut context = index.checkout(checkout_context_hash)?.unwrap();
+    let context_hash = compute_context_hash(&index, checkout_context_hash, log)?;
 
-    let commit: Commit = index
-        .fetch_commit_from_context_hash(checkout_context_hash)?
-        .unwrap();
-    context.parent_commit_ref = commit.parent_commit_ref;
-
-    let now = std::time::Instant::now();
-    log(" Loading in memory...");
-
-    // Fetch all objects into `Storage`
-    context.tree.traverse_working_tree(false)?;
+    assert_eq!(checkout_context_hash, &context_hash);
 
-    log(&format!(" Loading in memory ok {:?}", now.elapsed()));
+    Ok(())
+}
 
-    // Remove all `HashId` to re-compute them
-    context.index.storage.borrow_mut().forget_references();
+#[cfg(test)]
+mod tests {
+    use crate::ProtocolContextApi;
 
-    let now = std::time::Instant::now();
-    log(" Recomputing hashes...");
+    use super::*;
 
-    let context_hash = context.hash(commit.author, commit.message, commit.time as i64)?;
+    fn temp_db_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let path = path.to_str().unwrap().to_string();
 
-    log(&format!(" Recomputing hashes ok {:?}", now.elapsed()));
+        std::fs::remove_dir_all(&path).ok();
 
-    assert_eq!(checkout_context_hash, &context_hash);
+        path
+    }
 
-    Ok(())
+    #[test]
+    fn test_verify_context() {
+        let db_path = temp_db_path("tezedge-snapshot-verify-context");
+
+        let (genesis_hash, head_hash) = {
+            let repo = Persistent::try_new(PersistentConfiguration {
+                db_path: Some(db_path.clone()),
+                startup_check: false,
+                read_mode: false,
+            })
+            .unwrap();
+            let repo: Arc<RwLock<ContextKeyValueStore>> = Arc::new(RwLock::new(repo));
+            let index = TezedgeIndex::new(repo, None);
+
+            let context = TezedgeContext::new(index.clone(), None, None)
+                .add(&["a", "b", "c"], &[1; 40])
+                .unwrap();
+            let genesis_hash = context
+                .commit("Tezos".to_string(), "Genesis".to_string(), 0)
+                .unwrap();
+
+            let context = index
+                .checkout(&genesis_hash)
+                .unwrap()
+                .unwrap()
+                .add(&["a", "d"], &[2; 50])
+                .unwrap();
+            let head_hash = context
+                .commit("Tezos".to_string(), "Head".to_string(), 1)
+                .unwrap();
+
+            (genesis_hash, head_hash)
+        };
+
+        verify_context(db_path.clone(), &head_hash).unwrap();
+        verify_context(db_path.clone(), &genesis_hash).unwrap();
+
+        let unknown_hash =
+            ContextHash::from_base58_check("CoVmAcMV64uAQo8XvfLr9VDuz7HVZLT4cgK1w1qYmTjQNbGwQwDd")
+                .unwrap();
+        assert!(verify_context(db_path.clone(), &unknown_hash).is_err());
+
+        std::fs::remove_dir_all(&db_path).ok();
+    }
 }
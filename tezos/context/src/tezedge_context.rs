@@ -4,7 +4,11 @@
 //! This module implements the Tezos context API.
 
 use std::rc::Rc;
-use std::{cell::RefCell, convert::TryInto, sync::Arc};
+use std::{
+    cell::{Cell, RefCell},
+    convert::TryInto,
+    sync::Arc,
+};
 
 use crypto::hash::ContextHash;
 use ocaml_interop::BoxRoot;
@@ -17,6 +21,7 @@ use crate::{
     hash::ObjectHash,
     kv_store::HashId,
     persistent::{get_commit_hash, DBError},
+    proof::{build_proof, ContextProof},
     timings::send_statistics,
     working_tree::{
         storage::{BlobId, DirEntryId, DirectoryId, Storage},
@@ -98,6 +103,9 @@ pub struct TezedgeIndex {
     /// The working tree and repository have `StringId` which refers to
     /// a data inside `StringInterner`.
     pub string_interner: Rc<RefCell<Option<StringInterner>>>,
+    /// Generation of the repository (see `GarbageCollector::gc_generation`) the
+    /// objects in `storage` were read from.
+    gc_generation: Rc<Cell<u64>>,
 }
 
 use std::cell::RefMut;
@@ -112,11 +120,13 @@ impl TezedgeIndex {
         patch_context: Option<BoxRoot<PatchContextFunction>>,
     ) -> Self {
         let patch_context = Rc::new(patch_context);
+        let gc_generation = Rc::new(Cell::new(repository.read().gc_generation()));
         Self {
             patch_context,
             repository,
             storage: Default::default(),
             string_interner: Rc::new(RefCell::new(None)),
+            gc_generation,
         }
     }
 
@@ -125,11 +135,13 @@ impl TezedgeIndex {
         storage: Rc<RefCell<Storage>>,
         string_interner: Rc<RefCell<Option<StringInterner>>>,
     ) -> Self {
+        let gc_generation = Rc::new(Cell::new(repository.read().gc_generation()));
         Self {
             patch_context: Default::default(),
             repository,
             storage,
             string_interner,
+            gc_generation,
         }
     }
 
@@ -159,17 +171,62 @@ impl TezedgeIndex {
         Ok(RefMut::map(strings, |s| s.as_mut().unwrap()))
     }
 
+    /// Drops the objects and strings read from a previous generation of the
+    /// repository, their references are invalid since the garbage collection.
+    fn refresh_gc_generation(&self, generation: u64) {
+        if self.gc_generation.get() == generation {
+            return;
+        }
+
+        // The string ids changed, the strings will be taken again
+        // from the repository
+        self.string_interner.borrow_mut().take();
+        self.storage.borrow_mut().clear();
+        self.gc_generation.set(generation);
+    }
+
+    /// Returns an error when the repository has been replaced by the garbage
+    /// collector since the objects in `Self::storage` were read.
+    ///
+    /// The operation can be retried from a new checkout.
+    pub fn check_gc_generation(&self, repository: &ContextKeyValueStore) -> Result<(), DBError> {
+        let repository_generation = repository.gc_generation();
+        let read_generation = self.gc_generation.get();
+
+        if read_generation != repository_generation {
+            return Err(DBError::GarbageCollected {
+                read_generation,
+                repository_generation,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the reference of the commit `context_hash`, this is where the
+    /// operations on a context start.
+    ///
+    /// The objects from a previous generation of the repository are dropped.
+    fn get_context_hash_ref(
+        &self,
+        context_hash: &ContextHash,
+    ) -> Result<Option<ObjectReference>, DBError> {
+        let repository = self.repository.read();
+        let object_ref = repository.get_context_hash(context_hash)?;
+
+        // The readonly IPC backend updates its generation on `get_context_hash`
+        self.refresh_gc_generation(repository.gc_generation());
+
+        Ok(object_ref)
+    }
+
     pub fn fetch_commit_from_context_hash(
         &self,
         context_hash: &ContextHash,
     ) -> Result<Option<Commit>, ContextError> {
-        let object_ref = {
-            let repository = self.repository.read();
-
-            match repository.get_context_hash(context_hash)? {
-                Some(hash_id) => hash_id,
-                None => return Ok(None),
-            }
+        let object_ref = match self.get_context_hash_ref(context_hash)? {
+            Some(hash_id) => hash_id,
+            None => return Ok(None),
         };
 
         let mut storage = self.storage.borrow_mut();
@@ -196,6 +253,7 @@ impl TezedgeIndex {
         strings: &mut StringInterner,
     ) -> Result<Option<Object>, DBError> {
         let repository = self.repository.read();
+        self.check_gc_generation(&*repository)?;
 
         repository
             .get_object(object_ref, storage, strings)
@@ -206,7 +264,10 @@ impl TezedgeIndex {
     ///
     /// It reads it from the repository.
     pub fn fetch_hash(&self, object_ref: ObjectReference) -> Result<ObjectHash, DBError> {
-        Ok(self.repository.read().get_hash(object_ref)?.into_owned())
+        let repository = self.repository.read();
+        self.check_gc_generation(&*repository)?;
+
+        Ok(repository.get_hash(object_ref)?.into_owned())
     }
 
     /// Fetches object from the repository and deserialize it into `storage`.
@@ -719,6 +780,7 @@ impl TezedgeIndex {
             patch_context: Rc::clone(&self.patch_context),
             repository: Arc::clone(&self.repository),
             string_interner: Rc::clone(&self.string_interner),
+            gc_generation: Rc::new(Cell::new(self.gc_generation.get())),
         })
     }
 
@@ -727,16 +789,12 @@ impl TezedgeIndex {
         context_hash: &ContextHash,
         key: &ContextKey,
     ) -> Result<Option<ContextValue>, ContextError> {
-        let object_ref = {
-            let repository = self.repository.write();
-
-            match repository.get_context_hash(context_hash)? {
-                Some(hash_id) => hash_id,
-                None => {
-                    return Err(ContextError::UnknownContextHashError {
-                        context_hash: context_hash.to_base58_check(),
-                    })
-                }
+        let object_ref = match self.get_context_hash_ref(context_hash)? {
+            Some(hash_id) => hash_id,
+            None => {
+                return Err(ContextError::UnknownContextHashError {
+                    context_hash: context_hash.to_base58_check(),
+                })
             }
         };
 
@@ -753,15 +811,12 @@ impl TezedgeIndex {
         context_hash: &ContextHash,
         prefix: &ContextKey,
     ) -> Result<Option<Vec<(ContextKeyOwned, ContextValue)>>, ContextError> {
-        let object_ref = {
-            let repository = self.repository.read();
-            match repository.get_context_hash(context_hash)? {
-                Some(hash_id) => hash_id,
-                None => {
-                    return Err(ContextError::UnknownContextHashError {
-                        context_hash: context_hash.to_base58_check(),
-                    })
-                }
+        let object_ref = match self.get_context_hash_ref(context_hash)? {
+            Some(hash_id) => hash_id,
+            None => {
+                return Err(ContextError::UnknownContextHashError {
+                    context_hash: context_hash.to_base58_check(),
+                })
             }
         };
 
@@ -775,15 +830,12 @@ impl TezedgeIndex {
         prefix: &ContextKey,
         depth: Option<usize>,
     ) -> Result<StringTreeObject, ContextError> {
-        let object_ref = {
-            let repository = self.repository.read();
-            match repository.get_context_hash(context_hash)? {
-                Some(hash_id) => hash_id,
-                None => {
-                    return Err(ContextError::UnknownContextHashError {
-                        context_hash: context_hash.to_base58_check(),
-                    })
-                }
+        let object_ref = match self.get_context_hash_ref(context_hash)? {
+            Some(hash_id) => hash_id,
+            None => {
+                return Err(ContextError::UnknownContextHashError {
+                    context_hash: context_hash.to_base58_check(),
+                })
             }
         };
 
@@ -801,18 +853,53 @@ impl TezedgeIndex {
         )
         .map_err(ContextError::from)
     }
+
+    fn get_merkle_proof_impl(
+        &self,
+        context_hash: &ContextHash,
+        key: &ContextKey,
+    ) -> Result<Option<ContextProof>, ContextError> {
+        let object_ref = match self.get_context_hash_ref(context_hash)? {
+            Some(hash_id) => hash_id,
+            None => return Ok(None),
+        };
+
+        let mut storage = self.storage.borrow_mut();
+        let mut strings = self.get_string_interner()?;
+        let repository = self.repository.read();
+
+        build_proof(
+            self,
+            object_ref,
+            key,
+            &mut storage,
+            &mut strings,
+            &*repository,
+        )
+        .map(Some)
+        .map_err(ContextError::from)
+    }
+
+    /// Builds the Merkle proof of `key` in the context `context_hash`,
+    /// it can be checked with `proof::verify_proof`.
+    ///
+    /// Returns `None` when the context hash is unknown.
+    pub fn get_merkle_proof(
+        &self,
+        context_hash: &ContextHash,
+        key: &ContextKey,
+    ) -> Result<Option<ContextProof>, ContextError> {
+        let index = self.with_deallocation();
+        index.get_merkle_proof_impl(context_hash, key)
+    }
 }
 
 impl IndexApi<TezedgeContext> for TezedgeIndex {
     /// Checks if `context_hash` exists in the repository.
     fn exists(&self, context_hash: &ContextHash) -> Result<bool, ContextError> {
-        let object_ref = {
-            let repository = self.repository.read();
-
-            match repository.get_context_hash(context_hash)? {
-                Some(hash_id) => hash_id,
-                None => return Ok(false),
-            }
+        let object_ref = match self.get_context_hash_ref(context_hash)? {
+            Some(hash_id) => hash_id,
+            None => return Ok(false),
         };
 
         let mut storage = self.storage.borrow_mut();
@@ -824,13 +911,9 @@ impl IndexApi<TezedgeContext> for TezedgeIndex {
     }
 
     fn checkout(&self, context_hash: &ContextHash) -> Result<Option<TezedgeContext>, ContextError> {
-        let object_ref = {
-            let repository = self.repository.read();
-
-            match repository.get_context_hash(context_hash)? {
-                Some(hash_id) => hash_id,
-                None => return Ok(None),
-            }
+        let object_ref = match self.get_context_hash_ref(context_hash)? {
+            Some(hash_id) => hash_id,
+            None => return Ok(None),
         };
 
         // TODO: should we always be copying this value? is it possibe
@@ -875,10 +958,12 @@ impl IndexApi<TezedgeContext> for TezedgeIndex {
         block_level: u32,
         context_hash: &ContextHash,
     ) -> Result<(), ContextError> {
-        Ok(self
-            .repository
-            .write()
-            .block_applied(block_level, context_hash)?)
+        let mut repository = self.repository.write();
+
+        repository.block_applied(block_level, context_hash)?;
+        self.refresh_gc_generation(repository.gc_generation());
+
+        Ok(())
     }
 
     fn cycle_started(&mut self) -> Result<(), ContextError> {
@@ -1126,6 +1211,7 @@ impl TezedgeContext {
 
         let (commit_hash, serialize_stats) = {
             let mut repository = self.index.repository.write();
+            self.index.check_gc_generation(&*repository)?;
             let date: u64 = date.try_into()?;
 
             repository.commit(&self.tree, self.parent_commit_ref, author, message, date)?
@@ -1959,6 +1959,20 @@ impl Storage {
         Ok(pointer_inode_id)
     }
 
+    /// Returns the `DirectoryOrInodeId` of the pointer, fetching it from the repository
+    /// when it is not in `Self` yet.
+    pub fn pointer_load(
+        &mut self,
+        thin_pointer_id: ThinPointerId,
+        repository: &ContextKeyValueStore,
+        strings: &mut StringInterner,
+    ) -> Result<DirectoryOrInodeId, StorageError> {
+        match self.pointer_get_id(thin_pointer_id)? {
+            Some(ptr_id) => Ok(ptr_id),
+            None => self.pointer_fetch(thin_pointer_id, repository, strings),
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn insert_inode(
         &mut self,
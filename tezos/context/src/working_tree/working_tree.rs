@@ -749,6 +749,7 @@ impl WorkingTree {
     pub fn hash(&self) -> Result<ObjectHash, MerkleError> {
         let storage = self.index.storage.borrow();
         let mut repo = self.index.repository.write();
+        self.index.check_gc_generation(&*repo)?;
 
         let hash_id = match self.root {
             WorkingTreeRoot::Directory(_) => self.get_root_directory_hash(&mut *repo)?,
@@ -798,6 +799,7 @@ impl WorkingTree {
         let root = self.get_root_directory();
         let mut storage = self.index.storage.borrow_mut();
         let repository = self.index.repository.read();
+        self.index.check_gc_generation(&*repository)?;
         let mut strings = self.index.get_string_interner()?;
 
         let dir_id = self.find_or_create_directory(root, key, &mut storage, &mut strings)?;
@@ -1136,6 +1138,7 @@ impl WorkingTree {
         }
 
         let repository = self.index.repository.read();
+        self.index.check_gc_generation(&*repository)?;
         let dir_id = match new_dir_entry {
             None => storage.dir_remove(dir_id, last, strings, &*repository)?,
             Some(new_dir_entry) => {
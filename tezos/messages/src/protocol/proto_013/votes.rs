@@ -2,23 +2,31 @@
 // SPDX-License-Identifier: MIT
 
 use getset::Getters;
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 
-/// Struct for the delegates and they voting power (in rolls)
+/// Struct for the delegates and they voting power (stake in mutez)
 #[derive(Serialize, Debug, Clone, Getters, Eq, Ord, PartialEq, PartialOrd)]
 pub struct VoteListings {
     /// Public key hash (address, e.g tz1...)
     #[get = "pub"]
     pkh: String,
 
-    /// Number of rolls the pkh owns
+    /// Staking power of the pkh, encoded as a string like other int64 values
     #[get = "pub"]
-    rolls: i32,
+    #[serde(serialize_with = "i64_as_string")]
+    voting_power: i64,
 }
 
 impl VoteListings {
     /// Simple constructor to construct VoteListings
-    pub fn new(pkh: String, rolls: i32) -> Self {
-        Self { pkh, rolls }
+    pub fn new(pkh: String, voting_power: i64) -> Self {
+        Self { pkh, voting_power }
     }
 }
+
+fn i64_as_string<S>(value: &i64, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_str(&value.to_string())
+}
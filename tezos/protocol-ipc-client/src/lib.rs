@@ -72,6 +72,10 @@ pub struct ProtocolRunnerConfiguration {
     pub executable_path: PathBuf,
     #[serde(with = "slog_level_serde")]
     pub log_level: Level,
+    /// Number of cycles preserved by the garbage collector of the on-disk context,
+    /// `None` when it is disabled
+    #[serde(default)]
+    pub context_gc_preserved_cycles: Option<usize>,
 }
 
 impl ProtocolRunnerConfiguration {
@@ -82,6 +86,7 @@ impl ProtocolRunnerConfiguration {
         storage: TezosContextStorageConfiguration,
         executable_path: PathBuf,
         log_level: Level,
+        context_gc_preserved_cycles: Option<usize>,
     ) -> Self {
         Self {
             runtime_configuration,
@@ -90,6 +95,7 @@ impl ProtocolRunnerConfiguration {
             storage,
             executable_path,
             log_level,
+            context_gc_preserved_cycles,
         }
     }
 }
@@ -166,6 +172,7 @@ impl ProtocolRunnerApi {
         let ProtocolRunnerConfiguration {
             executable_path,
             log_level,
+            context_gc_preserved_cycles,
             ..
         } = &self.configuration;
         let child = Self::spawn_process(
@@ -173,6 +180,7 @@ impl ProtocolRunnerApi {
             &self.socket_path,
             &self.endpoint_name,
             log_level,
+            *context_gc_preserved_cycles,
             self.log.clone(),
             &self.tokio_runtime,
         )?;
@@ -205,11 +213,13 @@ impl ProtocolRunnerApi {
         socket_path: &Path,
         endpoint_name: &str,
         log_level: &Level,
+        context_gc_preserved_cycles: Option<usize>,
         log: Logger,
         tokio_runtime: &tokio::runtime::Handle,
     ) -> Result<tokio::process::Child, ProtocolRunnerError> {
         let _guard = tokio_runtime.enter();
-        let mut process = Command::new(executable_path)
+        let mut command = Command::new(executable_path);
+        command
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .arg("--socket-path")
@@ -217,8 +227,13 @@ impl ProtocolRunnerApi {
             .arg("--endpoint")
             .arg(endpoint_name)
             .arg("--log-level")
-            .arg(log_level.as_str().to_lowercase())
-            .spawn()?;
+            .arg(log_level.as_str().to_lowercase());
+        if let Some(preserved_cycles) = context_gc_preserved_cycles {
+            command
+                .arg("--context-gc-preserved-cycles")
+                .arg(preserved_cycles.to_string());
+        }
+        let mut process = command.spawn()?;
 
         Self::log_subprocess_output(tokio_runtime, &mut process, log.clone());
 
@@ -267,6 +282,11 @@ impl ProtocolRunnerApi {
         handle_output!("OCaml-err", "STDERR", process.stderr, log.clone());
     }
 
+    /// Path of the TezEdge context IPC socket, if the context IPC server is enabled.
+    pub fn context_ipc_socket_path(&self) -> Option<String> {
+        self.configuration.storage.get_ipc_socket_path()
+    }
+
     pub async fn wait_for_context_init(&self) -> Result<(), tokio::sync::watch::error::RecvError> {
         let mut watcher = self.status_watcher.lock().await;
         loop {